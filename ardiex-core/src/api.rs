@@ -0,0 +1,761 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use log::{info, warn};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::backup::BackupManager;
+use crate::config::{BackupConfig, BackupMode, SourceConfig};
+use crate::history::{self, RunRecord, RunStatus};
+use crate::http::{self, Request};
+use crate::logger;
+use crate::progress::ProgressUpdate;
+use crate::restore::{OverwritePolicy, RestoreManager, RestoreOptions};
+use crate::signing;
+use crate::trigger::{BackupTrigger, TriggerReason};
+
+const DEFAULT_HISTORY_LIMIT: usize = 50;
+const DEFAULT_LOG_LINES: usize = 100;
+const MAX_LOG_LINES: usize = 1000;
+
+/// Page served on `/` with `api_dashboard`; it talks to the API below.
+const DASHBOARD_HTML: &str = include_str!("dashboard/index.html");
+/// Finished restore jobs kept for `GET /api/restores`; running ones are
+/// always kept.
+const MAX_FINISHED_RESTORES: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestoreStatus {
+    Running,
+    Success,
+    Failure,
+}
+
+/// A restore started through `POST /api/restores`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoreJob {
+    pub id: u64,
+    pub backup_dir: PathBuf,
+    pub target_dir: PathBuf,
+    pub point: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub status: RestoreStatus,
+    pub files_restored: Option<usize>,
+    pub error: Option<String>,
+}
+
+/// A backup trigger waiting behind the running batch.
+#[derive(Debug, Clone, Serialize)]
+struct QueuedTrigger {
+    source_dir: PathBuf,
+    reason: String,
+}
+
+/// Latest step of a backup, mirror or restore running in the service,
+/// taken from the progress callback.
+#[derive(Debug, Clone, Serialize)]
+struct TransferProgress {
+    label: &'static str,
+    target: PathBuf,
+    file: Option<PathBuf>,
+    files_done: usize,
+    total_files: usize,
+    bytes_done: u64,
+    total_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct SourceStatus {
+    source_dir: PathBuf,
+    enabled: bool,
+    backup_dirs: Vec<PathBuf>,
+    backup_mode: BackupMode,
+    cron_schedule: Option<String>,
+    /// Next cron trigger of the source or one of its destinations.
+    next_backup: Option<DateTime<Utc>>,
+    running: bool,
+    last_run: Option<RunRecord>,
+    last_success: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BackupRequest {
+    source_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CheckRequest {
+    backup_dir: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RestoreRequest {
+    backup_dir: PathBuf,
+    target_dir: PathBuf,
+    #[serde(default)]
+    point: Option<String>,
+    #[serde(default)]
+    overwrite: Option<String>,
+}
+
+/// What the API reads and drives in a running service. `run` keeps it
+/// current: the settings after each hot reload, the sources being backed
+/// up, the triggers waiting and the progress of running work.
+#[derive(Clone)]
+pub struct ApiState {
+    shared: Arc<Shared>,
+    log_file: Option<PathBuf>,
+}
+
+struct Shared {
+    started_at: DateTime<Utc>,
+    history_path: PathBuf,
+    triggers: mpsc::Sender<BackupTrigger>,
+    config: RwLock<BackupConfig>,
+    running: Mutex<BTreeSet<PathBuf>>,
+    queued: Mutex<Vec<QueuedTrigger>>,
+    progress: Mutex<BTreeMap<PathBuf, TransferProgress>>,
+    restores: Mutex<Vec<RestoreJob>>,
+    next_restore_id: AtomicU64,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl ApiState {
+    pub fn new(
+        config: BackupConfig,
+        history_path: PathBuf,
+        triggers: mpsc::Sender<BackupTrigger>,
+    ) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                started_at: Utc::now(),
+                history_path,
+                triggers,
+                config: RwLock::new(config),
+                running: Mutex::new(BTreeSet::new()),
+                queued: Mutex::new(Vec::new()),
+                progress: Mutex::new(BTreeMap::new()),
+                restores: Mutex::new(Vec::new()),
+                next_restore_id: AtomicU64::new(1),
+            }),
+            log_file: None,
+        }
+    }
+
+    /// Serve the tail of `log_file` on `GET /api/logs`.
+    pub fn with_log_file(mut self, log_file: PathBuf) -> Self {
+        self.log_file = Some(log_file);
+        self
+    }
+
+    /// Settings to serve from now on, including a changed `api_token`.
+    pub fn set_config(&self, config: BackupConfig) {
+        *self
+            .shared
+            .config
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = config;
+    }
+
+    /// Sources being backed up right now; empty once the batch is done.
+    pub fn set_running(&self, source_dirs: &[PathBuf]) {
+        let finished = std::mem::replace(
+            &mut *lock(&self.shared.running),
+            source_dirs.iter().cloned().collect(),
+        );
+        // A backup cut short by an error never reports its last file.
+        let config = self.config();
+        let mut progress = lock(&self.shared.progress);
+        for source in config
+            .sources
+            .iter()
+            .filter(|source| finished.contains(&source.source_dir))
+        {
+            for backup_dir in source.effective_backup_dirs() {
+                progress.remove(&backup_dir);
+            }
+        }
+    }
+
+    /// Triggers waiting for their turn, in the order they will run.
+    pub fn set_queued(&self, pending: &[(PathBuf, TriggerReason)]) {
+        *lock(&self.shared.queued) = pending
+            .iter()
+            .map(|(source_dir, reason)| QueuedTrigger {
+                source_dir: source_dir.clone(),
+                reason: reason.to_string(),
+            })
+            .collect();
+    }
+
+    /// Keep the latest step of each running backup or restore; meant to be
+    /// installed with `progress::set_progress_callback`.
+    pub fn record_progress(&self, update: &ProgressUpdate<'_>) {
+        let mut progress = lock(&self.shared.progress);
+        if update.file.is_none() && update.files_done >= update.total_files {
+            progress.remove(update.target);
+            return;
+        }
+        progress.insert(
+            update.target.to_path_buf(),
+            TransferProgress {
+                label: update.label,
+                target: update.target.to_path_buf(),
+                file: update.file.map(Path::to_path_buf),
+                files_done: update.files_done,
+                total_files: update.total_files,
+                bytes_done: update.bytes_done,
+                total_bytes: update.total_bytes,
+            },
+        );
+    }
+
+    fn config(&self) -> BackupConfig {
+        self.shared
+            .config
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Whether `request` carries `Authorization: Bearer <api_token>`. With
+    /// no token configured nothing is authorized.
+    fn authorized(&self, request: &Request) -> bool {
+        let config = self
+            .shared
+            .config
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let (Some(expected), Some(given)) = (
+            config.api_token.as_deref().filter(|t| !t.is_empty()),
+            request
+                .headers
+                .get("authorization")
+                .and_then(|value| value.strip_prefix("Bearer ")),
+        ) else {
+            return false;
+        };
+        // Compare every byte so the response time does not leak a prefix.
+        given.len() == expected.len()
+            && given
+                .bytes()
+                .zip(expected.bytes())
+                .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+
+    fn status(&self) -> Response {
+        let running: Vec<PathBuf> = lock(&self.shared.running).iter().cloned().collect();
+        let restores_running = lock(&self.shared.restores)
+            .iter()
+            .filter(|job| job.status == RestoreStatus::Running)
+            .count();
+        let queued = lock(&self.shared.queued).clone();
+        let progress: Vec<TransferProgress> =
+            lock(&self.shared.progress).values().cloned().collect();
+        Response::json(
+            "200 OK",
+            &json!({
+                "started_at": self.shared.started_at,
+                "sources": self.config().sources.len(),
+                "running": running,
+                "queued": queued,
+                "progress": progress,
+                "restores_running": restores_running,
+            }),
+        )
+    }
+
+    fn sources(&self) -> Response {
+        let config = self.config();
+        let records = match history::read_records(&self.shared.history_path, None, usize::MAX) {
+            Ok(records) => records,
+            Err(e) => return Response::error("500 Internal Server Error", &format!("{:#}", e)),
+        };
+        let running = lock(&self.shared.running).clone();
+        let sources: Vec<SourceStatus> = config
+            .sources
+            .iter()
+            .map(|source| {
+                let resolved = source.resolve(&config);
+                let periodic = config.enable_periodic && source.enabled && resolved.enable_periodic;
+                let own = || {
+                    records
+                        .iter()
+                        .rev()
+                        .filter(|record| record.source_dir == source.source_dir)
+                };
+                SourceStatus {
+                    source_dir: source.source_dir.clone(),
+                    enabled: source.enabled,
+                    backup_dirs: source.effective_backup_dirs(),
+                    backup_mode: resolved.backup_mode,
+                    next_backup: periodic
+                        .then(|| next_backup(source, &resolved.cron_schedule))
+                        .flatten(),
+                    cron_schedule: periodic.then_some(resolved.cron_schedule),
+                    running: running.contains(&source.source_dir),
+                    last_run: own().next().cloned(),
+                    last_success: own()
+                        .find(|record| record.status == RunStatus::Success)
+                        .map(|record| record.finished_at),
+                }
+            })
+            .collect();
+        Response::json("200 OK", &sources)
+    }
+
+    fn history(&self, query: &HashMap<String, String>) -> Response {
+        let limit = match query.get("limit").map(|limit| limit.parse::<usize>()) {
+            None => DEFAULT_HISTORY_LIMIT,
+            Some(Ok(limit)) => limit,
+            Some(Err(_)) => return Response::error("400 Bad Request", "limit must be a number"),
+        };
+        let source = query.get("source").map(PathBuf::from);
+        match history::read_records(&self.shared.history_path, source.as_deref(), limit) {
+            Ok(records) => Response::json("200 OK", &records),
+            Err(e) => Response::error("500 Internal Server Error", &format!("{:#}", e)),
+        }
+    }
+
+    /// The last lines of the service log.
+    fn logs(&self, query: &HashMap<String, String>) -> Response {
+        let Some(ref log_file) = self.log_file else {
+            return Response::error("404 Not Found", "the service has no log file");
+        };
+        let lines = match query.get("lines").map(|lines| lines.parse::<usize>()) {
+            None => DEFAULT_LOG_LINES,
+            Some(Ok(lines)) => lines.min(MAX_LOG_LINES),
+            Some(Err(_)) => return Response::error("400 Bad Request", "lines must be a number"),
+        };
+        match logger::read_tail(log_file, lines) {
+            Ok(lines) => Response::json("200 OK", &lines),
+            Err(e) => Response::error("500 Internal Server Error", &format!("{:#}", e)),
+        }
+    }
+
+    /// Queue a backup of one source, or of every enabled source when the
+    /// body names none. Queued backups wait out blackout windows like any
+    /// other trigger.
+    async fn trigger_backups(&self, body: &[u8]) -> Response {
+        let request: BackupRequest = match parse_body(body) {
+            Ok(request) => request,
+            Err(response) => return response,
+        };
+        let config = self.config();
+        let source_dirs: Vec<PathBuf> = match request.source_dir {
+            Some(source_dir) => match config.sources.iter().find(|s| s.source_dir == source_dir) {
+                None => {
+                    return Response::error(
+                        "404 Not Found",
+                        &format!("{:?} is not a configured source", source_dir),
+                    );
+                }
+                Some(source) if !source.enabled => {
+                    return Response::error(
+                        "409 Conflict",
+                        &format!("{:?} is disabled", source_dir),
+                    );
+                }
+                Some(_) => vec![source_dir],
+            },
+            None => config
+                .sources
+                .iter()
+                .filter(|s| s.enabled)
+                .map(|s| s.source_dir.clone())
+                .collect(),
+        };
+
+        for source_dir in &source_dirs {
+            info!("[API] Queueing a backup of {:?}", source_dir);
+            let trigger = BackupTrigger::new(source_dir.clone(), TriggerReason::Api);
+            if self.shared.triggers.send(trigger).await.is_err() {
+                return Response::error("503 Service Unavailable", "the service is stopping");
+            }
+        }
+        Response::json("202 Accepted", &json!({ "queued": source_dirs }))
+    }
+
+    /// Start restoring a configured backup dir in the background; progress
+    /// is polled through `GET /api/restores`.
+    fn start_restore(&self, body: &[u8]) -> Response {
+        let mut request: RestoreRequest = match parse_body(body) {
+            Ok(request) => request,
+            Err(response) => return response,
+        };
+        let config = self.config();
+        if let Err(response) = owning_source(&config, &request.backup_dir) {
+            return response;
+        }
+        if !request.target_dir.is_absolute() {
+            return Response::error("400 Bad Request", "target_dir must be an absolute path");
+        }
+        if config.api_restore_roots.is_empty() {
+            return Response::error(
+                "403 Forbidden",
+                "API restores are disabled until api_restore_roots is set",
+            );
+        }
+        request.target_dir =
+            match confine_restore_target(&config.api_restore_roots, &request.target_dir) {
+                Some(target_dir) => target_dir,
+                None => {
+                    return Response::error(
+                        "403 Forbidden",
+                        &format!(
+                            "target_dir {:?} is outside api_restore_roots",
+                            request.target_dir
+                        ),
+                    );
+                }
+            };
+        // A token alone never replaces existing files unless asked to.
+        let overwrite = match request.overwrite.as_deref().map(OverwritePolicy::from_str) {
+            None => OverwritePolicy::Never,
+            Some(Ok(OverwritePolicy::Prompt)) => {
+                return Response::error(
+                    "400 Bad Request",
+                    "overwrite 'prompt' needs a terminal; use 'always', 'never' or 'if-older'",
+                );
+            }
+            Some(Ok(policy)) => policy,
+            Some(Err(e)) => return Response::error("400 Bad Request", &e.to_string()),
+        };
+        let signing_key = match signing::load_optional(config.signing_key_file.as_deref()) {
+            Ok(key) => key,
+            Err(e) => return Response::error("500 Internal Server Error", &format!("{:#}", e)),
+        };
+        let options = RestoreOptions {
+            signing_key,
+            overwrite,
+            ..Default::default()
+        };
+
+        let id = self.shared.next_restore_id.fetch_add(1, Ordering::Relaxed);
+        self.record_restore(RestoreJob {
+            id,
+            backup_dir: request.backup_dir.clone(),
+            target_dir: request.target_dir.clone(),
+            point: request.point.clone(),
+            started_at: Utc::now(),
+            finished_at: None,
+            status: RestoreStatus::Running,
+            files_restored: None,
+            error: None,
+        });
+        info!(
+            "[API] Restore {} started: {:?} -> {:?}",
+            id, request.backup_dir, request.target_dir
+        );
+
+        let state = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = RestoreManager::restore_to_point(
+                &request.backup_dir,
+                &request.target_dir,
+                request.point.as_deref(),
+                &options,
+            );
+            lock(&state.shared.progress)
+                .retain(|target, _| !target.starts_with(&request.backup_dir));
+            let mut restores = lock(&state.shared.restores);
+            if let Some(job) = restores.iter_mut().find(|job| job.id == id) {
+                job.finished_at = Some(Utc::now());
+                match result {
+                    Ok(files) => {
+                        info!("[API] Restore {} completed: {} files", id, files);
+                        job.status = RestoreStatus::Success;
+                        job.files_restored = Some(files);
+                    }
+                    Err(e) => {
+                        warn!("[API] Restore {} failed: {:#}", id, e);
+                        job.status = RestoreStatus::Failure;
+                        job.error = Some(format!("{:#}", e));
+                    }
+                }
+            }
+        });
+        Response::json("202 Accepted", &json!({ "id": id }))
+    }
+
+    /// Space used by each point of a configured backup dir.
+    async fn stats(&self, query: &HashMap<String, String>) -> Response {
+        let Some(backup_dir) = query.get("backup_dir").map(PathBuf::from) else {
+            return Response::error("400 Bad Request", "backup_dir is required");
+        };
+        if let Err(response) = owning_source(&self.config(), &backup_dir) {
+            return response;
+        }
+        match tokio::task::spawn_blocking(move || BackupManager::collect_backup_stats(&backup_dir))
+            .await
+        {
+            Ok(Ok(stats)) => Response::json("200 OK", &stats),
+            Ok(Err(e)) => Response::error("500 Internal Server Error", &format!("{:#}", e)),
+            Err(e) => Response::error("500 Internal Server Error", &e.to_string()),
+        }
+    }
+
+    /// Check a configured backup dir the way `verify_schedule` does and
+    /// answer with the report once it is done.
+    async fn check(&self, body: &[u8]) -> Response {
+        let request: CheckRequest = match parse_body(body) {
+            Ok(request) => request,
+            Err(response) => return response,
+        };
+        let config = self.config();
+        let resolved = match owning_source(&config, &request.backup_dir) {
+            Ok(source) => source.resolve_for(&config, &request.backup_dir),
+            Err(response) => return response,
+        };
+        info!("[API] Checking {:?}", request.backup_dir);
+        let outcome = tokio::task::spawn_blocking(move || {
+            let signing_key = signing::load_optional(resolved.signing_key_file.as_deref())?;
+            BackupManager::verify_backup_dir(
+                &request.backup_dir,
+                signing_key.as_ref(),
+                resolved.io_throttle_mbps,
+            )
+        })
+        .await;
+        match outcome {
+            Ok(Ok(report)) => Response::json("200 OK", &report),
+            Ok(Err(e)) => Response::error("500 Internal Server Error", &format!("{:#}", e)),
+            Err(e) => Response::error("500 Internal Server Error", &e.to_string()),
+        }
+    }
+
+    fn record_restore(&self, job: RestoreJob) {
+        let mut restores = lock(&self.shared.restores);
+        restores.push(job);
+        let finished = restores
+            .iter()
+            .filter(|job| job.status != RestoreStatus::Running)
+            .count();
+        let mut excess = finished.saturating_sub(MAX_FINISHED_RESTORES);
+        restores.retain(|job| {
+            if excess > 0 && job.status != RestoreStatus::Running {
+                excess -= 1;
+                return false;
+            }
+            true
+        });
+    }
+
+    fn restores(&self) -> Response {
+        Response::json("200 OK", &*lock(&self.shared.restores))
+    }
+}
+
+/// The configured source `backup_dir` belongs to; the API only reads and
+/// restores backup dirs of the settings it serves.
+fn owning_source<'a>(
+    config: &'a BackupConfig,
+    backup_dir: &Path,
+) -> Result<&'a SourceConfig, Response> {
+    config
+        .sources
+        .iter()
+        .find(|source| {
+            source
+                .effective_backup_dirs()
+                .iter()
+                .any(|dir| dir == backup_dir)
+        })
+        .ok_or_else(|| {
+            Response::error(
+                "404 Not Found",
+                &format!(
+                    "{:?} is not a backup dir of a configured source",
+                    backup_dir
+                ),
+            )
+        })
+}
+
+/// `target_dir` with symlinks resolved, if that lies under one of `roots`.
+/// The nearest existing ancestor is canonicalized and the rest appended, so
+/// a symlink inside a root cannot lead out of it; `..` is refused outright
+/// since it could climb back out through a part not created yet.
+fn confine_restore_target(roots: &[PathBuf], target_dir: &Path) -> Option<PathBuf> {
+    if target_dir
+        .components()
+        .any(|component| component == Component::ParentDir)
+    {
+        return None;
+    }
+    let mut existing = target_dir;
+    let mut missing = Vec::new();
+    let resolved = loop {
+        match existing.canonicalize() {
+            Ok(resolved) => break resolved,
+            Err(_) => {
+                missing.push(existing.file_name()?);
+                existing = existing.parent()?;
+            }
+        }
+    };
+    let resolved = missing
+        .iter()
+        .rev()
+        .fold(resolved, |path, part| path.join(part));
+    roots
+        .iter()
+        .filter_map(|root| root.canonicalize().ok())
+        .any(|root| resolved.starts_with(root))
+        .then_some(resolved)
+}
+
+/// Earliest next fire of the source's cron and its destinations' own
+/// crons, evaluated in UTC like `run`.
+fn next_backup(source: &SourceConfig, cron_schedule: &str) -> Option<DateTime<Utc>> {
+    let now = Utc::now();
+    std::iter::once(cron_schedule)
+        .chain(
+            source
+                .backup_dirs
+                .iter()
+                .filter_map(|destination| destination.cron_schedule.as_deref()),
+        )
+        .filter_map(|expr| Schedule::from_str(expr).ok()?.after(&now).next())
+        .min()
+}
+
+/// JSON body of a POST; an empty body reads as `{}`.
+fn parse_body<T: DeserializeOwned>(body: &[u8]) -> Result<T, Response> {
+    let body = if body.iter().all(u8::is_ascii_whitespace) {
+        b"{}".as_slice()
+    } else {
+        body
+    };
+    serde_json::from_slice(body)
+        .map_err(|e| Response::error("400 Bad Request", &format!("Invalid request body: {}", e)))
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn json<T: Serialize + ?Sized>(status: &'static str, value: &T) -> Self {
+        match serde_json::to_string(value) {
+            Ok(body) => Self {
+                status,
+                content_type: "application/json",
+                body,
+            },
+            Err(e) => Self::error("500 Internal Server Error", &e.to_string()),
+        }
+    }
+
+    fn error(status: &'static str, message: &str) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: json!({ "error": message }).to_string(),
+        }
+    }
+}
+
+/// Bind the API. Kept separate from [`spawn_server`] so a bad address or
+/// a port conflict fails `run` at startup.
+pub async fn bind(addr: &str) -> Result<TcpListener> {
+    TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind the REST API on {}", addr))
+}
+
+pub fn spawn_server(listener: TcpListener, state: ApiState) -> JoinHandle<()> {
+    if let Ok(addr) = listener.local_addr() {
+        info!("[API] Serving the REST API on http://{}/api", addr);
+    }
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let state = state.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_connection(stream, state).await {
+                            warn!("[API] Request failed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("[API] Accept failed: {}", e),
+            }
+        }
+    })
+}
+
+async fn handle_connection(mut stream: TcpStream, state: ApiState) -> Result<()> {
+    let response = match http::read_request(&mut stream, http::REQUEST_READ_TIMEOUT).await {
+        Ok(request) => route(&state, request).await,
+        Err(e) => Response::error("400 Bad Request", &format!("{:#}", e)),
+    };
+    let authenticate = if response.status.starts_with("401") {
+        "www-authenticate: Bearer\r\n"
+    } else {
+        ""
+    };
+    let head = format!(
+        "HTTP/1.1 {}\r\ncontent-type: {}\r\ncontent-length: {}\r\n{}connection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        authenticate
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+async fn route(state: &ApiState, request: Request) -> Response {
+    // The page holds no data; it asks for the token and calls the API.
+    if request.method == "GET" && request.path == "/" && state.config().api_dashboard {
+        return Response {
+            status: "200 OK",
+            content_type: "text/html; charset=utf-8",
+            body: DASHBOARD_HTML.to_string(),
+        };
+    }
+    if !state.authorized(&request) {
+        return Response::error("401 Unauthorized", "missing or wrong bearer token");
+    }
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/api/status") => state.status(),
+        ("GET", "/api/sources") => state.sources(),
+        ("GET", "/api/history") => state.history(&request.query),
+        ("GET", "/api/logs") => state.logs(&request.query),
+        ("POST", "/api/backups") => state.trigger_backups(&request.body).await,
+        ("GET", "/api/stats") => state.stats(&request.query).await,
+        ("POST", "/api/checks") => state.check(&request.body).await,
+        ("GET", "/api/restores") => state.restores(),
+        ("POST", "/api/restores") => state.start_restore(&request.body),
+        _ => Response::error("404 Not Found", "no such endpoint"),
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/api_tests.rs"]
+mod tests;
@@ -0,0 +1,469 @@
+use anyhow::{Context, Result};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::point_name;
+use crate::progress::FileProgress;
+
+/// Single-file backup container extension (`full_<ts>.ardx`).
+pub const ARCHIVE_EXTENSION: &str = "ardx";
+pub const PASSPHRASE_ENV_KEY: &str = "ARDIEX_ARCHIVE_PASSPHRASE";
+
+// Layout: header, then every file's data as a sequence of independently
+// deflated (and optionally ChaCha20-Poly1305 encrypted) chunks, then the
+// manifest as one more blob, and a fixed-size trailer pointing at it:
+//
+//   "ARDXARC1" | flags:u8 | [salt:16 if encrypted]
+//   chunk blobs ...
+//   manifest blob (JSON)
+//   manifest_offset:u64le | manifest_len:u64le | "ARDXEND1"
+//
+// Blobs are only ever appended, so a crash leaves a file without trailer
+// that is rejected as incomplete instead of being misread.
+const HEADER_MAGIC: &[u8; 8] = b"ARDXARC1";
+const TRAILER_MAGIC: &[u8; 8] = b"ARDXEND1";
+const TRAILER_LEN: u64 = 24;
+const FLAG_ENCRYPTED: u8 = 0b0000_0001;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChunkRef {
+    offset: u64,
+    len: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    /// Relative path with `/` separators, identical on every platform.
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+    chunks: Vec<ChunkRef>,
+}
+
+impl ArchiveEntry {
+    /// Relative path using the platform's separators.
+    pub fn relative_path(&self) -> PathBuf {
+        self.path.split('/').collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    entries: Vec<ArchiveEntry>,
+}
+
+/// `<backup_dir>/<backup_name>.ardx`
+pub fn archive_path(backup_dir: &Path, backup_name: &str) -> PathBuf {
+    backup_dir.join(format!("{}.{}", backup_name, ARCHIVE_EXTENSION))
+}
+
+/// Backup name (`full_*`/`inc_*`) of an archive file name, if it is one.
+pub fn backup_name_of(file_name: &str) -> Option<&str> {
+    file_name
+        .strip_suffix(ARCHIVE_EXTENSION)
+        .and_then(|stem| stem.strip_suffix('.'))
+}
+
+/// Name of a backup point stored at `path`: a directory named like
+/// `full_*`/`inc_*` (see `point_name`) or the same name as a `.ardx`
+/// archive file.
+pub fn backup_point_name(path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_string_lossy();
+    let name = if path.is_dir() {
+        file_name.to_string()
+    } else if path.is_file() {
+        backup_name_of(&file_name)?.to_string()
+    } else {
+        return None;
+    };
+    point_name::parse(&name).is_some().then_some(name)
+}
+
+/// Path of backup point `backup_name` in `backup_dir`, in whichever format
+/// it was written.
+pub fn locate_backup(backup_dir: &Path, backup_name: &str) -> Option<PathBuf> {
+    let dir = backup_dir.join(backup_name);
+    if dir.is_dir() {
+        return Some(dir);
+    }
+    let archive = archive_path(backup_dir, backup_name);
+    archive.is_file().then_some(archive)
+}
+
+pub fn passphrase_from_env() -> Option<String> {
+    std::env::var(PASSPHRASE_ENV_KEY)
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
+fn derive_cipher(passphrase: &str, salt: &[u8]) -> Result<ChaCha20Poly1305> {
+    let mut key = [0u8; 32];
+    argon2::Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("Failed to derive archive key: {}", e))?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+fn archive_relative_path(relative: &Path) -> Result<String> {
+    let mut parts = Vec::new();
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Archive entries must be relative paths: {:?}",
+                    relative
+                ));
+            }
+        }
+    }
+    Ok(parts.join("/"))
+}
+
+pub struct ArchiveWriter {
+    file: BufWriter<File>,
+    offset: u64,
+    cipher: Option<ChaCha20Poly1305>,
+    entries: Vec<ArchiveEntry>,
+}
+
+impl ArchiveWriter {
+    /// Start a new archive. With a passphrase every chunk and the manifest
+    /// are encrypted with a key derived from it (Argon2id, random salt).
+    pub fn create(path: &Path, passphrase: Option<&str>) -> Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("Failed to create archive {:?}", path))?;
+        let mut file = BufWriter::new(file);
+
+        let mut header = HEADER_MAGIC.to_vec();
+        let cipher = match passphrase {
+            Some(passphrase) => {
+                let mut salt = [0u8; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                header.push(FLAG_ENCRYPTED);
+                header.extend_from_slice(&salt);
+                Some(derive_cipher(passphrase, &salt)?)
+            }
+            None => {
+                header.push(0);
+                None
+            }
+        };
+        file.write_all(&header)?;
+
+        Ok(Self {
+            file,
+            offset: header.len() as u64,
+            cipher,
+            entries: Vec::new(),
+        })
+    }
+
+    fn write_blob(&mut self, plain: &[u8]) -> Result<ChunkRef> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(plain)?;
+        let compressed = encoder.finish()?;
+
+        let stored = match &self.cipher {
+            Some(cipher) => {
+                let mut nonce = [0u8; NONCE_LEN];
+                OsRng.fill_bytes(&mut nonce);
+                let sealed = cipher
+                    .encrypt(Nonce::from_slice(&nonce), compressed.as_slice())
+                    .map_err(|_| anyhow::anyhow!("Failed to encrypt archive chunk"))?;
+                let mut stored = nonce.to_vec();
+                stored.extend_from_slice(&sealed);
+                stored
+            }
+            None => compressed,
+        };
+
+        self.file.write_all(&stored)?;
+        let chunk = ChunkRef {
+            offset: self.offset,
+            len: stored.len() as u64,
+        };
+        self.offset += stored.len() as u64;
+        Ok(chunk)
+    }
+
+    pub fn add_file(&mut self, relative: &Path, source: &Path) -> Result<()> {
+        let path = archive_relative_path(relative)?;
+        let mut input =
+            File::open(source).with_context(|| format!("Failed to open {:?}", source))?;
+        let mut hasher = Sha256::new();
+        let mut chunks = Vec::new();
+        let mut size = 0u64;
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+
+        loop {
+            let mut filled = 0;
+            while filled < CHUNK_SIZE {
+                let n = input.read(&mut buffer[filled..])?;
+                if n == 0 {
+                    break;
+                }
+                filled += n;
+            }
+            if filled == 0 {
+                break;
+            }
+            hasher.update(&buffer[..filled]);
+            size += filled as u64;
+            chunks.push(self.write_blob(&buffer[..filled])?);
+            if filled < CHUNK_SIZE {
+                break;
+            }
+        }
+
+        self.entries.push(ArchiveEntry {
+            path,
+            size,
+            sha256: format!("{:x}", hasher.finalize()),
+            chunks,
+        });
+        Ok(())
+    }
+
+    /// Write the manifest and trailer and flush everything to disk.
+    pub fn finish(mut self) -> Result<()> {
+        let manifest = Manifest {
+            version: MANIFEST_VERSION,
+            entries: std::mem::take(&mut self.entries),
+        };
+        let manifest_ref = self.write_blob(&serde_json::to_vec(&manifest)?)?;
+        self.file.write_all(&manifest_ref.offset.to_le_bytes())?;
+        self.file.write_all(&manifest_ref.len.to_le_bytes())?;
+        self.file.write_all(TRAILER_MAGIC)?;
+        self.file.flush()?;
+        self.file.get_ref().sync_all()?;
+        Ok(())
+    }
+}
+
+/// Pack every file under `dir` (sorted by path) into a new archive.
+pub fn pack_dir(dir: &Path, archive: &Path, passphrase: Option<&str>) -> Result<()> {
+    let mut writer = ArchiveWriter::create(archive, passphrase)?;
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            writer.add_file(entry.path().strip_prefix(dir)?, entry.path())?;
+        }
+    }
+    writer.finish()
+}
+
+pub struct ArchiveReader {
+    path: PathBuf,
+    file: File,
+    /// End of the blob area (start of the trailer); no blob reaches past it.
+    data_end: u64,
+    cipher: Option<ChaCha20Poly1305>,
+    entries: Vec<ArchiveEntry>,
+}
+
+impl ArchiveReader {
+    pub fn open(path: &Path, passphrase: Option<&str>) -> Result<Self> {
+        let mut file =
+            File::open(path).with_context(|| format!("Failed to open archive {:?}", path))?;
+
+        let mut magic = [0u8; 9];
+        file.read_exact(&mut magic)
+            .with_context(|| format!("Not an ardiex archive: {:?}", path))?;
+        if &magic[..8] != HEADER_MAGIC {
+            return Err(anyhow::anyhow!("Not an ardiex archive: {:?}", path));
+        }
+        let cipher = if magic[8] & FLAG_ENCRYPTED != 0 {
+            let mut salt = [0u8; SALT_LEN];
+            file.read_exact(&mut salt)?;
+            let passphrase = passphrase.ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Archive {:?} is encrypted; set {} to read it",
+                    path,
+                    PASSPHRASE_ENV_KEY
+                )
+            })?;
+            Some(derive_cipher(passphrase, &salt)?)
+        } else {
+            None
+        };
+
+        let len = file.metadata()?.len();
+        let mut trailer = [0u8; TRAILER_LEN as usize];
+        if len < TRAILER_LEN {
+            return Err(anyhow::anyhow!("Archive is incomplete: {:?}", path));
+        }
+        file.seek(SeekFrom::Start(len - TRAILER_LEN))?;
+        file.read_exact(&mut trailer)?;
+        if &trailer[16..] != TRAILER_MAGIC {
+            return Err(anyhow::anyhow!("Archive is incomplete: {:?}", path));
+        }
+        let manifest_ref = ChunkRef {
+            offset: u64::from_le_bytes(trailer[..8].try_into()?),
+            len: u64::from_le_bytes(trailer[8..16].try_into()?),
+        };
+
+        let mut reader = Self {
+            path: path.to_path_buf(),
+            file,
+            data_end: len - TRAILER_LEN,
+            cipher,
+            entries: Vec::new(),
+        };
+        let manifest: Manifest = serde_json::from_slice(&reader.read_blob(&manifest_ref)?)
+            .with_context(|| format!("Invalid archive manifest: {:?}", path))?;
+        if manifest.version != MANIFEST_VERSION {
+            return Err(anyhow::anyhow!(
+                "Unsupported archive version {} in {:?}",
+                manifest.version,
+                path
+            ));
+        }
+        reader.entries = manifest.entries;
+        Ok(reader)
+    }
+
+    /// Open with the passphrase from `ARDIEX_ARCHIVE_PASSPHRASE`, if set.
+    pub fn open_with_env(path: &Path) -> Result<Self> {
+        Self::open(path, passphrase_from_env().as_deref())
+    }
+
+    pub fn entries(&self) -> &[ArchiveEntry] {
+        &self.entries
+    }
+
+    pub fn find(&self, relative: &Path) -> Option<&ArchiveEntry> {
+        let wanted = archive_relative_path(relative).ok()?;
+        self.entries.iter().find(|entry| entry.path == wanted)
+    }
+
+    fn read_blob(&mut self, chunk: &ChunkRef) -> Result<Vec<u8>> {
+        // Offsets come from the file itself; check them before allocating.
+        if chunk
+            .offset
+            .checked_add(chunk.len)
+            .is_none_or(|end| end > self.data_end)
+        {
+            return Err(anyhow::anyhow!(
+                "Corrupted archive {:?}: chunk at {} ({} bytes) lies outside the file",
+                self.path,
+                chunk.offset,
+                chunk.len
+            ));
+        }
+        let mut stored = vec![0u8; chunk.len as usize];
+        self.file.seek(SeekFrom::Start(chunk.offset))?;
+        self.file.read_exact(&mut stored)?;
+
+        let compressed = match &self.cipher {
+            Some(cipher) => {
+                if stored.len() < NONCE_LEN {
+                    return Err(anyhow::anyhow!("Truncated chunk in {:?}", self.path));
+                }
+                let (nonce, sealed) = stored.split_at(NONCE_LEN);
+                cipher
+                    .decrypt(Nonce::from_slice(nonce), sealed)
+                    .map_err(|_| {
+                        anyhow::anyhow!(
+                            "Failed to decrypt {:?} (wrong passphrase or corrupted data)",
+                            self.path
+                        )
+                    })?
+            }
+            None => stored,
+        };
+
+        let mut plain = Vec::new();
+        DeflateDecoder::new(compressed.as_slice())
+            .read_to_end(&mut plain)
+            .with_context(|| format!("Corrupted chunk in {:?}", self.path))?;
+        Ok(plain)
+    }
+
+    /// Stream an entry into `output`, verifying its size and checksum.
+    pub fn extract_to(
+        &mut self,
+        entry: &ArchiveEntry,
+        output: &mut impl Write,
+        mut progress: Option<&mut FileProgress>,
+    ) -> Result<()> {
+        let mut hasher = Sha256::new();
+        let mut size = 0u64;
+        for chunk in &entry.chunks {
+            let plain = self.read_blob(chunk)?;
+            hasher.update(&plain);
+            size += plain.len() as u64;
+            output.write_all(&plain)?;
+            if let Some(progress) = progress.as_deref_mut() {
+                progress.update(size);
+            }
+        }
+        if size != entry.size || format!("{:x}", hasher.finalize()) != entry.sha256 {
+            return Err(anyhow::anyhow!(
+                "Checksum mismatch for {} in {:?}",
+                entry.path,
+                self.path
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn read_entry(&mut self, entry: &ArchiveEntry) -> Result<Vec<u8>> {
+        // Only a hint; the size is checked against the data once it is read.
+        let mut data = Vec::with_capacity(entry.size.min(self.data_end) as usize);
+        self.extract_to(entry, &mut data, None)?;
+        Ok(data)
+    }
+
+    /// The last `len` bytes (or fewer) of an entry, decoding only the
+    /// chunks they lie in. Unlike `read_entry` the checksum is not checked.
+    pub fn read_entry_tail(&mut self, entry: &ArchiveEntry, len: u64) -> Result<Vec<u8>> {
+        let mut tail = Vec::new();
+        for chunk in entry.chunks.iter().rev() {
+            if tail.len() as u64 >= len {
+                break;
+            }
+            let mut plain = self.read_blob(chunk)?;
+            plain.extend_from_slice(&tail);
+            tail = plain;
+        }
+        let excess = tail.len().saturating_sub(len as usize);
+        Ok(tail.split_off(excess))
+    }
+
+    pub fn extract_file(
+        &mut self,
+        entry: &ArchiveEntry,
+        target: &Path,
+        progress: Option<&mut FileProgress>,
+    ) -> Result<()> {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut output = BufWriter::new(
+            File::create(target).with_context(|| format!("Failed to create {:?}", target))?,
+        );
+        self.extract_to(entry, &mut output, progress)?;
+        output.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/archive_tests.rs"]
+mod tests;
@@ -0,0 +1,233 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Per-point manifest of file attributes, stored at the root of each backup
+/// point (and inside `.ardx` archives) next to the backed-up files.
+pub const ATTRIBUTES_FILE_NAME: &str = ".ardiex-attrs.json";
+
+/// Keyed by `/`-separated path relative to the source dir.
+pub type AttributeManifest = BTreeMap<String, FileAttributes>;
+
+/// What plain `fs::copy` loses: mode bits, ownership, mtime and hard links.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileAttributes {
+    /// Unix permission bits including setuid/setgid/sticky.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+    /// Read-only flag, the only permission bit on non-Unix platforms.
+    #[serde(default)]
+    pub readonly: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<DateTime<Utc>>,
+    /// Manifest key of the file this one was hard-linked to in the source.
+    /// Restore recreates the link instead of leaving an independent copy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hard_link_to: Option<String>,
+    /// Extended attribute name to hex-encoded value, only recorded with
+    /// `preserve_xattrs`. On Linux this includes POSIX ACLs.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub xattrs: BTreeMap<String, String>,
+    /// How a delta-mode incremental stored the file: as a delta, or as a
+    /// full copy because the delta exceeded `delta_max_ratio`, the file
+    /// `delta_max_file_size` or a `file_strategies` rule said so. Unset when
+    /// there was no choice (full points, copy mode, no earlier version).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stored_as: Option<StoredAs>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoredAs {
+    Delta,
+    Full,
+}
+
+impl FileAttributes {
+    pub fn capture(metadata: &fs::Metadata) -> Self {
+        #[cfg(unix)]
+        let (mode, uid, gid) = {
+            use std::os::unix::fs::{MetadataExt, PermissionsExt};
+            (
+                Some(metadata.permissions().mode() & 0o7777),
+                Some(metadata.uid()),
+                Some(metadata.gid()),
+            )
+        };
+        #[cfg(not(unix))]
+        let (mode, uid, gid) = (None, None, None);
+
+        Self {
+            mode,
+            uid,
+            gid,
+            readonly: metadata.permissions().readonly(),
+            modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+            hard_link_to: None,
+            xattrs: BTreeMap::new(),
+            stored_as: None,
+        }
+    }
+
+    /// Read the file's extended attributes into `xattrs`. Attributes that
+    /// vanish between listing and reading are skipped.
+    pub fn capture_xattrs(&mut self, path: &Path) -> Result<()> {
+        #[cfg(unix)]
+        for name in
+            xattr::list(path).with_context(|| format!("Failed to list xattrs of {:?}", path))?
+        {
+            if let Some(value) = xattr::get(path, &name)
+                .with_context(|| format!("Failed to read xattr {:?} of {:?}", name, path))?
+            {
+                self.xattrs
+                    .insert(name.to_string_lossy().to_string(), encode_hex(&value));
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = path;
+        Ok(())
+    }
+
+    /// Reapply to a restored file. The mtime and xattrs go first while the
+    /// file is still writable, and the mode last since `chown` may clear
+    /// setuid bits. Xattrs the target filesystem rejects only log a warning.
+    pub fn apply(&self, path: &Path, restore_ownership: bool) -> Result<()> {
+        if let Some(modified) = self.modified {
+            File::options()
+                .write(true)
+                .open(path)
+                .and_then(|file| file.set_modified(SystemTime::from(modified)))
+                .with_context(|| format!("Failed to set mtime of {:?}", path))?;
+        }
+        self.apply_xattrs(path);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if restore_ownership && (self.uid.is_some() || self.gid.is_some()) {
+                std::os::unix::fs::chown(path, self.uid, self.gid).with_context(|| {
+                    format!(
+                        "Failed to restore ownership of {:?} (use --no-owner when not running as root)",
+                        path
+                    )
+                })?;
+            }
+            if let Some(mode) = self.mode {
+                fs::set_permissions(path, fs::Permissions::from_mode(mode))
+                    .with_context(|| format!("Failed to set mode of {:?}", path))?;
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = restore_ownership;
+            if self.readonly {
+                let mut permissions = fs::metadata(path)?.permissions();
+                permissions.set_readonly(true);
+                fs::set_permissions(path, permissions)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_xattrs(&self, path: &Path) {
+        for (name, value) in &self.xattrs {
+            let applied = decode_hex(value).and_then(|value| {
+                #[cfg(unix)]
+                {
+                    xattr::set(path, name, &value).map_err(anyhow::Error::from)
+                }
+                #[cfg(not(unix))]
+                {
+                    let _ = value;
+                    Err(anyhow::anyhow!("extended attributes are not supported"))
+                }
+            });
+            if let Err(e) = applied {
+                warn!("Could not restore xattr {} on {:?}: {:#}", name, path, e);
+            }
+        }
+    }
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(text: &str) -> Result<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return Err(anyhow::anyhow!("Invalid hex value"));
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).context("Invalid hex value"))
+        .collect()
+}
+
+/// Device/inode pair of a file with more than one hard link. Always `None`
+/// on platforms without stable link counts.
+pub fn hard_link_identity(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// Whether `relative` (inside a backup point) is the attribute manifest
+/// rather than a backed-up file.
+pub fn is_attributes_file(relative: &Path) -> bool {
+    relative == Path::new(ATTRIBUTES_FILE_NAME)
+}
+
+pub fn write_manifest(point_dir: &Path, manifest: &AttributeManifest) -> Result<()> {
+    let path = point_dir.join(ATTRIBUTES_FILE_NAME);
+    fs::write(&path, serde_json::to_vec(manifest)?)
+        .with_context(|| format!("Failed to write {:?}", path))
+}
+
+pub fn parse_manifest(content: &[u8]) -> Result<AttributeManifest> {
+    serde_json::from_slice(content).context("Invalid file attribute manifest")
+}
+
+/// The manifest of a directory backup point; empty for points written
+/// before attributes were recorded.
+pub fn read_manifest(point_dir: &Path) -> Result<AttributeManifest> {
+    let path = point_dir.join(ATTRIBUTES_FILE_NAME);
+    if !path.exists() {
+        return Ok(AttributeManifest::new());
+    }
+    parse_manifest(&fs::read(&path).with_context(|| format!("Failed to read {:?}", path))?)
+}
+
+/// `/`-separated manifest key for a path relative to the source dir.
+pub fn manifest_key(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Path relative to the source dir for a `/`-separated manifest key, using
+/// the platform's separators.
+pub fn key_path(key: &str) -> PathBuf {
+    key.split('/').collect()
+}
+
+#[cfg(test)]
+#[path = "tests/attrs_tests.rs"]
+mod tests;
@@ -0,0 +1,65 @@
+use super::*;
+use crate::config::AnomalyDetectionConfig;
+use std::collections::HashSet;
+
+impl BackupManager {
+    /// Compare the hashes recorded by the last backup with the current scan
+    /// and describe why the change looks like ransomware, if it does.
+    /// Ratios are relative to the files the last backup knew about.
+    pub(super) fn detect_change_anomaly(
+        previous: &HashMap<String, String>,
+        current: &HashMap<String, String>,
+        config: &AnomalyDetectionConfig,
+    ) -> Option<String> {
+        if !config.enabled || previous.is_empty() || previous.len() < config.min_files {
+            return None;
+        }
+        let tracked = previous.len() as f64;
+
+        let modified = previous
+            .iter()
+            .filter(|(path, hash)| current.get(*path).is_some_and(|now| now != *hash))
+            .count();
+
+        // A vanished file counts as renamed when a new file shares its name
+        // minus the extension, or is its name plus an extra extension.
+        let new_stems: HashSet<String> = current
+            .keys()
+            .filter(|path| !previous.contains_key(*path))
+            .map(|path| strip_extension(path))
+            .collect();
+        let renamed = previous
+            .keys()
+            .filter(|path| !current.contains_key(*path))
+            .filter(|path| new_stems.contains(*path) || new_stems.contains(&strip_extension(path)))
+            .count();
+
+        let mut reasons = Vec::new();
+        if modified as f64 / tracked > config.max_changed_ratio {
+            reasons.push(format!(
+                "{} of {} files modified ({:.0}% > {:.0}%)",
+                modified,
+                previous.len(),
+                modified as f64 / tracked * 100.0,
+                config.max_changed_ratio * 100.0
+            ));
+        }
+        if renamed as f64 / tracked > config.max_extension_change_ratio {
+            reasons.push(format!(
+                "{} of {} files renamed to a new extension ({:.0}% > {:.0}%)",
+                renamed,
+                previous.len(),
+                renamed as f64 / tracked * 100.0,
+                config.max_extension_change_ratio * 100.0
+            ));
+        }
+        (!reasons.is_empty()).then(|| reasons.join(", "))
+    }
+}
+
+fn strip_extension(path: &str) -> String {
+    Path::new(path)
+        .with_extension("")
+        .to_string_lossy()
+        .to_string()
+}
@@ -0,0 +1,586 @@
+use super::*;
+use crate::config::{BackupHistoryType, SourceMetadata};
+use crate::dedup;
+use crate::error::Error;
+use crate::lock::LOCK_FILE_NAME;
+use crate::signing::METADATA_SIGNATURE_FILE_NAME;
+use repair::RepairReport;
+use serde::Serialize;
+
+/// Files and dirs ardiex itself keeps next to the backup points.
+const KNOWN_ENTRIES: &[&str] = &[
+    "metadata.json",
+    METADATA_SIGNATURE_FILE_NAME,
+    "file_hashes.jsonl",
+    LOCK_FILE_NAME,
+    repo::REPO_FILE_NAME,
+    prune::APPEND_ONLY_MARKER,
+    report::REPORT_DIR,
+    mirror::MIRROR_DIR,
+    mirror::VERSIONS_DIR,
+    dedup::DEFAULT_STORE_DIR_NAME,
+    parity::PARITY_DIR,
+];
+
+/// Temp files an interrupted metadata save leaves behind.
+const TEMP_ENTRIES: &[&str] = &[
+    "metadata.json.tmp",
+    "file_hashes.jsonl.tmp",
+    reverse::REVERSE_PENDING_DIR,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckSeverity {
+    /// Restores still work, but something is left over or unverified.
+    Warning,
+    /// A restore could fail or bring back wrong content.
+    Error,
+}
+
+impl CheckSeverity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// One problem found by `ardiex check`.
+#[derive(Debug, Serialize)]
+pub struct CheckFinding {
+    /// `metadata`, `history`, `delta_chain`, `inc_checksum`, `signature`,
+    /// `partial` or `orphan`.
+    pub check: &'static str,
+    pub severity: CheckSeverity,
+    /// Backup point the finding is about, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub point: Option<String>,
+    pub message: String,
+    pub suggested_fix: String,
+    /// Whether `ardiex check --repair` fixes it.
+    pub repairable: bool,
+}
+
+/// Outcome of `ardiex check`. With `--repair`, `findings` are what is
+/// left after the repair and `repair` lists what it did.
+#[derive(Debug, Serialize)]
+pub struct CheckReport {
+    pub backup_dir: PathBuf,
+    pub backup_points: usize,
+    pub findings: Vec<CheckFinding>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repair: Option<RepairReport>,
+    /// Why `--repair` left the dir alone.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repair_skipped: Option<String>,
+}
+
+impl CheckReport {
+    pub fn error_count(&self) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity == CheckSeverity::Error)
+            .count()
+    }
+
+    /// One line per error finding, for alerts.
+    pub fn error_lines(&self) -> Vec<String> {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity == CheckSeverity::Error)
+            .map(|finding| match &finding.point {
+                Some(point) => format!("{}: {}: {}", finding.check, point, finding.message),
+                None => format!("{}: {}", finding.check, finding.message),
+            })
+            .collect()
+    }
+
+    fn push(
+        &mut self,
+        check: &'static str,
+        severity: CheckSeverity,
+        point: Option<&str>,
+        message: String,
+        suggested_fix: &str,
+    ) {
+        let repairable = [RESYNC_FIX, CLEANUP_FIX, PARITY_FIX].contains(&suggested_fix);
+        self.findings.push(CheckFinding {
+            check,
+            severity,
+            point: point.map(str::to_string),
+            message,
+            suggested_fix: suggested_fix.to_string(),
+            repairable,
+        });
+    }
+}
+
+const RESYNC_FIX: &str = "Run `ardiex check --repair` to resync metadata.json with the disk";
+const CLEANUP_FIX: &str = "Run `ardiex check --repair` to remove it";
+const PARITY_FIX: &str = "Run `ardiex check --repair` to rebuild it from parity";
+
+impl BackupManager {
+    /// Diagnose a backup dir without changing it: metadata history against
+    /// the points on disk, the delta chain, each incremental's recorded
+    /// `inc_checksum`, signatures (with a signing key) and leftover partial
+    /// or unknown entries, and points against their parity files. With
+    /// `repair`, damage parity covers is rebuilt first; the remaining
+    /// recoverable problems are then fixed by `repair_backup_dir` and the
+    /// dir is checked again.
+    pub fn check_backup_dir(
+        backup_dir: &Path,
+        repair: bool,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<CheckReport> {
+        if !backup_dir.is_dir() {
+            return Err(Error::BackupDirMissing(backup_dir.to_path_buf()).into());
+        }
+
+        let mut report = {
+            // Waits for a running backup, so its partial point is not
+            // mistaken for an abandoned one.
+            let _lock = BackupDirLock::acquire_shared(backup_dir, "check", None)?;
+            Self::run_checks(backup_dir, signing_key, None)?
+        };
+        if !repair || !report.findings.iter().any(|finding| finding.repairable) {
+            return Ok(report);
+        }
+
+        // Rebuilt blocks bring back the written content, which also clears
+        // the inc_checksum and signature errors they caused.
+        let mut parity_actions = Vec::new();
+        let damaged_points: Vec<String> = report
+            .findings
+            .iter()
+            .filter(|finding| finding.suggested_fix == PARITY_FIX)
+            .filter_map(|finding| finding.point.clone())
+            .collect();
+        if !damaged_points.is_empty() {
+            {
+                let _lock = BackupDirLock::acquire(backup_dir, "check")?;
+                for name in &damaged_points {
+                    let Some(path) = archive::locate_backup(backup_dir, name) else {
+                        continue;
+                    };
+                    let found = parity::repair(backup_dir, name, &path)?;
+                    let action = format!(
+                        "Rebuild {} block(s) of {} from parity ({} parity block(s) rewritten)",
+                        found.damaged_blocks, name, found.damaged_parity_blocks
+                    );
+                    info!("[{:?}] {}", backup_dir, action);
+                    parity_actions.push(action);
+                }
+            }
+            report = {
+                let _lock = BackupDirLock::acquire_shared(backup_dir, "check", None)?;
+                Self::run_checks(backup_dir, signing_key, None)?
+            };
+            if !report.findings.iter().any(|finding| finding.repairable) {
+                report.repair = Some(RepairReport {
+                    backup_dir: backup_dir.to_path_buf(),
+                    actions: parity_actions,
+                    ..Default::default()
+                });
+                return Ok(report);
+            }
+        }
+
+        // A resync would record the changed content as the expected one
+        // and hide the evidence.
+        let tampered = report.findings.iter().any(|finding| {
+            finding.severity == CheckSeverity::Error
+                && matches!(finding.check, "inc_checksum" | "signature")
+        });
+        if tampered {
+            if !parity_actions.is_empty() {
+                report.repair = Some(RepairReport {
+                    backup_dir: backup_dir.to_path_buf(),
+                    actions: parity_actions,
+                    ..Default::default()
+                });
+            }
+            report.repair_skipped = Some(
+                "backup points or metadata.json changed after they were written; \
+                 resolve that before repairing"
+                    .to_string(),
+            );
+            return Ok(report);
+        }
+
+        let mut repaired = Self::repair_backup_dir(backup_dir, false, signing_key)?;
+        repaired.actions.splice(0..0, parity_actions);
+        let mut report = {
+            let _lock = BackupDirLock::acquire_shared(backup_dir, "check", None)?;
+            Self::run_checks(backup_dir, signing_key, None)?
+        };
+        report.repair = Some(repaired);
+        Ok(report)
+    }
+
+    /// `check_backup_dir` for the `verify_schedule` of `run`: never
+    /// repairs, and paces reading the points to `io_throttle_mbps` so a
+    /// background check does not starve the machine.
+    pub fn verify_backup_dir(
+        backup_dir: &Path,
+        signing_key: Option<&SigningKey>,
+        io_throttle_mbps: Option<f64>,
+    ) -> Result<CheckReport> {
+        if !backup_dir.is_dir() {
+            return Err(Error::BackupDirMissing(backup_dir.to_path_buf()).into());
+        }
+        let mut throttle = io_throttle_mbps
+            .map(crate::throttle::Throttle::from_mb_per_sec)
+            .transpose()?;
+        let _lock = BackupDirLock::acquire_shared(backup_dir, "verify", None)?;
+        Self::run_checks(backup_dir, signing_key, throttle.as_mut())
+    }
+
+    fn run_checks(
+        backup_dir: &Path,
+        signing_key: Option<&SigningKey>,
+        mut throttle: Option<&mut crate::throttle::Throttle>,
+    ) -> Result<CheckReport> {
+        let points = Self::points_by_time(backup_dir)?;
+        let mut report = CheckReport {
+            backup_dir: backup_dir.to_path_buf(),
+            backup_points: points.len(),
+            findings: Vec::new(),
+            repair: None,
+            repair_skipped: None,
+        };
+        let metadata_path = backup_dir.join("metadata.json");
+        if points.is_empty() && !metadata_path.exists() {
+            return Ok(report);
+        }
+
+        let metadata = match fs::read_to_string(&metadata_path) {
+            Ok(content) => match serde_json::from_str::<SourceMetadata>(&content) {
+                Ok(metadata) => Some(metadata),
+                Err(e) => {
+                    report.push(
+                        "metadata",
+                        CheckSeverity::Error,
+                        None,
+                        format!("metadata.json could not be parsed ({})", e),
+                        RESYNC_FIX,
+                    );
+                    None
+                }
+            },
+            Err(_) => {
+                report.push(
+                    "metadata",
+                    CheckSeverity::Error,
+                    None,
+                    "metadata.json is missing while backup points exist".to_string(),
+                    RESYNC_FIX,
+                );
+                None
+            }
+        };
+
+        if let Some(metadata) = &metadata {
+            if points.is_empty() && !metadata.backup_history.is_empty() {
+                report.push(
+                    "history",
+                    CheckSeverity::Error,
+                    None,
+                    format!(
+                        "backup_history lists {} points but none are on disk",
+                        metadata.backup_history.len()
+                    ),
+                    RESYNC_FIX,
+                );
+            }
+            let content_changed = Self::check_inc_checksums(
+                backup_dir,
+                metadata,
+                &mut report,
+                throttle.as_deref_mut(),
+            );
+            if let Some(key) = signing_key {
+                Self::check_signatures(
+                    backup_dir,
+                    metadata,
+                    key,
+                    &mut report,
+                    throttle.as_deref_mut(),
+                );
+            }
+            if let Err(e) = Self::validate_backup_metadata_history(backup_dir, None) {
+                // Resyncing records the changed content as the expected one.
+                let fix = if content_changed {
+                    "Resolve the inc_checksum errors first; a resync would accept the changed content"
+                } else {
+                    RESYNC_FIX
+                };
+                report.push("history", CheckSeverity::Error, None, e.to_string(), fix);
+            }
+        }
+
+        Self::check_chain(&points, &mut report);
+        Self::check_parity(backup_dir, &points, &mut report, throttle);
+        Self::check_leftovers(backup_dir, &mut report)?;
+        Ok(report)
+    }
+
+    /// Backup points of `backup_dir` as (name, path, is_full), oldest first
+    /// by the timestamp in their name.
+    pub(super) fn points_by_time(backup_dir: &Path) -> Result<Vec<(String, PathBuf, bool)>> {
+        let mut points: Vec<(String, String, PathBuf, bool)> = fs::read_dir(backup_dir)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let path = entry.path();
+                let name = archive::backup_point_name(&path)?;
+                let parsed = point_name::parse(&name)?;
+                let (timestamp, is_full) = (parsed.timestamp.to_string(), parsed.is_full);
+                Some((timestamp, name, path, is_full))
+            })
+            .collect();
+        points.sort();
+        Ok(points
+            .into_iter()
+            .map(|(_, name, path, is_full)| (name, path, is_full))
+            .collect())
+    }
+
+    /// Recompute the checksum of every incremental in the history. Returns
+    /// whether any point's content no longer matches.
+    fn check_inc_checksums(
+        backup_dir: &Path,
+        metadata: &SourceMetadata,
+        report: &mut CheckReport,
+        mut throttle: Option<&mut crate::throttle::Throttle>,
+    ) -> bool {
+        let mut changed = false;
+        for entry in &metadata.backup_history {
+            if !matches!(entry.backup_type, BackupHistoryType::Incremental) {
+                continue;
+            }
+            let Some(path) = archive::locate_backup(backup_dir, &entry.backup_name) else {
+                continue;
+            };
+            let name = Some(entry.backup_name.as_str());
+            let Some(recorded) = entry.inc_checksum.as_deref() else {
+                report.push(
+                    "inc_checksum",
+                    CheckSeverity::Warning,
+                    name,
+                    "no inc_checksum recorded".to_string(),
+                    RESYNC_FIX,
+                );
+                continue;
+            };
+            if let Some(throttle) = throttle.as_deref_mut() {
+                throttle.consume(Self::backup_point_size(&path));
+            }
+            match Self::calculate_incremental_backup_checksum(&path) {
+                Ok(actual) if actual == recorded => {}
+                Ok(_) => {
+                    changed = true;
+                    report.push(
+                        "inc_checksum",
+                        CheckSeverity::Error,
+                        name,
+                        "content changed since the point was written".to_string(),
+                        "Restores from this point on may be wrong; the next backup starts a new full chain",
+                    );
+                }
+                Err(e) => {
+                    changed = true;
+                    report.push(
+                        "inc_checksum",
+                        CheckSeverity::Error,
+                        name,
+                        format!("could not be read: {:#}", e),
+                        "Check the disk; restores from this point on may fail",
+                    );
+                }
+            }
+        }
+        changed
+    }
+
+    fn check_signatures(
+        backup_dir: &Path,
+        metadata: &SourceMetadata,
+        key: &SigningKey,
+        report: &mut CheckReport,
+        mut throttle: Option<&mut crate::throttle::Throttle>,
+    ) {
+        if let Err(e) = Self::verify_metadata_signature(backup_dir, key) {
+            report.push(
+                "signature",
+                CheckSeverity::Error,
+                None,
+                e.to_string(),
+                "If the change was yours, `ardiex repair` re-signs metadata.json",
+            );
+        }
+        for entry in &metadata.backup_history {
+            let Some(path) = archive::locate_backup(backup_dir, &entry.backup_name) else {
+                continue;
+            };
+            if let Some(throttle) = throttle.as_deref_mut() {
+                throttle.consume(Self::backup_point_size(&path));
+            }
+            if let Err(e) = Self::verify_point_signature(entry, &path, key) {
+                report.push(
+                    "signature",
+                    CheckSeverity::Error,
+                    Some(&entry.backup_name),
+                    e.to_string(),
+                    "Do not restore from this point; the next backup starts a new full chain",
+                );
+            }
+        }
+    }
+
+    /// The oldest point must be a full and every delta of the incrementals
+    /// must load.
+    fn check_chain(points: &[(String, PathBuf, bool)], report: &mut CheckReport) {
+        if let Some((name, _, false)) = points.first() {
+            report.push(
+                "delta_chain",
+                CheckSeverity::Error,
+                Some(name),
+                "incremental point has no preceding full backup".to_string(),
+                "Points before the first full cannot be restored; the next backup is forced full",
+            );
+        }
+        // Fulls too: with reverse deltas their files can be deltas as well.
+        for (name, path, _) in points {
+            let checked = if path.is_file() {
+                Self::validate_delta_files_in_archive(path)
+            } else {
+                Self::validate_delta_files_in_dir(path)
+            };
+            if let Err(e) = checked {
+                report.push(
+                    "delta_chain",
+                    CheckSeverity::Error,
+                    Some(name),
+                    format!("{:#}", e),
+                    "Restores from this point on fail; the next backup is forced full",
+                );
+            }
+        }
+    }
+
+    /// Points against their parity files, and parity files without a point.
+    fn check_parity(
+        backup_dir: &Path,
+        points: &[(String, PathBuf, bool)],
+        report: &mut CheckReport,
+        mut throttle: Option<&mut crate::throttle::Throttle>,
+    ) {
+        for (name, path, _) in points {
+            let parity_path = parity::parity_path(backup_dir, name);
+            if !parity_path.is_file() {
+                continue;
+            }
+            if let Some(throttle) = throttle.as_deref_mut() {
+                throttle.consume(Self::backup_point_size(path));
+            }
+            let found = match parity::verify(backup_dir, name, path) {
+                Ok(found) => found,
+                Err(e) => {
+                    report.push(
+                        "parity",
+                        CheckSeverity::Warning,
+                        Some(name),
+                        format!("parity file is unusable: {:#}", e),
+                        "Delete it; the point stays restorable but unprotected",
+                    );
+                    continue;
+                }
+            };
+            if found.is_clean() {
+                continue;
+            }
+            if found.damaged_blocks > 0 || !found.damaged_files.is_empty() {
+                let files = found.damaged_files.join(", ");
+                if found.unrecoverable_blocks > 0 {
+                    report.push(
+                        "parity",
+                        CheckSeverity::Error,
+                        Some(name),
+                        format!(
+                            "{} damaged block(s) in {}, {} more than parity can rebuild",
+                            found.damaged_blocks, files, found.unrecoverable_blocks
+                        ),
+                        "Restore the files from another backup dir (`ardiex scrub`) or start a new full",
+                    );
+                } else {
+                    report.push(
+                        "parity",
+                        CheckSeverity::Error,
+                        Some(name),
+                        format!(
+                            "{} damaged block(s) in {}, rebuildable from parity",
+                            found.damaged_blocks, files
+                        ),
+                        PARITY_FIX,
+                    );
+                }
+            } else if found.damaged_parity_blocks > 0 {
+                report.push(
+                    "parity",
+                    CheckSeverity::Warning,
+                    Some(name),
+                    format!(
+                        "{} parity block(s) damaged; the point itself is intact",
+                        found.damaged_parity_blocks
+                    ),
+                    PARITY_FIX,
+                );
+            }
+        }
+        for orphan in parity::orphaned(backup_dir) {
+            report.push(
+                "parity",
+                CheckSeverity::Warning,
+                None,
+                format!(
+                    "{} belongs to no backup point",
+                    orphan.file_name().unwrap_or_default().to_string_lossy()
+                ),
+                CLEANUP_FIX,
+            );
+        }
+    }
+
+    /// Partial points left by interrupted runs, temp files and entries that
+    /// are neither points nor ardiex's own files.
+    fn check_leftovers(backup_dir: &Path, report: &mut CheckReport) -> Result<()> {
+        let mut names: Vec<String> = fs::read_dir(backup_dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| archive::backup_point_name(&entry.path()).is_none())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| !KNOWN_ENTRIES.contains(&name.as_str()))
+            .collect();
+        names.sort();
+        for name in names {
+            if name.starts_with(PARTIAL_BACKUP_PREFIX) || TEMP_ENTRIES.contains(&name.as_str()) {
+                report.push(
+                    "partial",
+                    CheckSeverity::Warning,
+                    None,
+                    format!("{} was left by an interrupted run", name),
+                    CLEANUP_FIX,
+                );
+            } else {
+                report.push(
+                    "orphan",
+                    CheckSeverity::Warning,
+                    None,
+                    format!("{} is not a backup point or an ardiex file", name),
+                    "Move it out of the backup dir if it is not needed",
+                );
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use super::*;
+use log::warn;
+use std::process::Command;
+
+impl BackupManager {
+    /// Run a source's `context_command` in the source directory and capture
+    /// its trimmed stdout. Failures only log a warning so the backup still runs.
+    pub(crate) fn capture_source_context(source_dir: &Path, command: &str) -> Option<String> {
+        let mut cmd = if cfg!(windows) {
+            let mut cmd = Command::new("cmd");
+            cmd.arg("/C");
+            cmd
+        } else {
+            let mut cmd = Command::new("sh");
+            cmd.arg("-c");
+            cmd
+        };
+        match cmd.arg(command).current_dir(source_dir).output() {
+            Ok(output) if output.status.success() => {
+                let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                if stdout.is_empty() {
+                    warn!(
+                        "Source {:?}: context_command produced no output: {}",
+                        source_dir, command
+                    );
+                    None
+                } else {
+                    Some(stdout)
+                }
+            }
+            Ok(output) => {
+                warn!(
+                    "Source {:?}: context_command failed ({}): {}",
+                    source_dir,
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+                None
+            }
+            Err(e) => {
+                warn!(
+                    "Source {:?}: failed to run context_command '{}': {}",
+                    source_dir, command, e
+                );
+                None
+            }
+        }
+    }
+}
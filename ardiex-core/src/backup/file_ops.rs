@@ -0,0 +1,816 @@
+use super::*;
+use crate::archive::{self, ArchiveReader};
+use crate::config::{BackupMode, SourceMetadata, source_scan_roots};
+use crate::error::Error;
+use log::{debug, info, warn};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// In-progress backup directory. Removed on drop unless `commit` renamed it
+/// to its final `full_*`/`inc_*` name.
+pub(super) struct PartialBackupDir {
+    path: PathBuf,
+    committed: bool,
+}
+
+impl PartialBackupDir {
+    pub(super) fn create(path: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&path)
+            .with_context(|| format!("Failed to create partial backup directory: {:?}", path))?;
+        Ok(Self {
+            path,
+            committed: false,
+        })
+    }
+
+    pub(super) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(super) fn commit(mut self, final_path: &Path) -> Result<()> {
+        fs::rename(&self.path, final_path).with_context(|| {
+            format!(
+                "Failed to finalize backup directory {:?} -> {:?}",
+                self.path, final_path
+            )
+        })?;
+        self.committed = true;
+        Ok(())
+    }
+
+    /// Pack the staged files into `final_path` as a single archive instead
+    /// of renaming the directory. The archive is written under the partial
+    /// name first, so a crash mid-pack leaves nothing that looks complete.
+    pub(super) fn commit_as_archive(
+        mut self,
+        final_path: &Path,
+        passphrase: Option<&str>,
+    ) -> Result<()> {
+        let staging = PathBuf::from(format!(
+            "{}.{}",
+            self.path.display(),
+            archive::ARCHIVE_EXTENSION
+        ));
+        if let Err(e) = archive::pack_dir(&self.path, &staging, passphrase) {
+            let _ = fs::remove_file(&staging);
+            return Err(e.context(format!("Failed to write archive {:?}", staging)));
+        }
+        fs::rename(&staging, final_path).with_context(|| {
+            format!(
+                "Failed to finalize backup archive {:?} -> {:?}",
+                staging, final_path
+            )
+        })?;
+        self.committed = true;
+        fs::remove_dir_all(&self.path).with_context(|| {
+            format!("Failed to remove archive staging directory {:?}", self.path)
+        })?;
+        Ok(())
+    }
+}
+
+impl Drop for PartialBackupDir {
+    fn drop(&mut self) {
+        if !self.committed && self.path.exists() {
+            if let Err(e) = fs::remove_dir_all(&self.path) {
+                warn!(
+                    "Failed to remove incomplete backup directory {:?}: {}",
+                    self.path, e
+                );
+            } else {
+                warn!("Removed incomplete backup directory: {:?}", self.path);
+            }
+        }
+    }
+}
+
+impl BackupManager {
+    /// Remove `partial_*` directories (and half-written archives) left behind
+    /// by a crashed run. Must be called while holding the backup dir lock.
+    pub(super) fn remove_stale_partial_backups(backup_dir: &Path) {
+        let Ok(entries) = fs::read_dir(backup_dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.starts_with(PARTIAL_BACKUP_PREFIX) {
+                continue;
+            }
+            let path = entry.path();
+            let removed = if path.is_dir() {
+                fs::remove_dir_all(&path)
+            } else {
+                fs::remove_file(&path)
+            };
+            match removed {
+                Ok(()) => info!("Removed stale partial backup: {:?}", path),
+                Err(e) => warn!("Failed to remove stale partial backup {:?}: {}", path, e),
+            }
+        }
+    }
+
+    /// Which files of `source` a backup takes, given the patterns and
+    /// limits in effect for one of its destinations.
+    pub(super) fn source_filter(
+        source: &SourceConfig,
+        resolved: &ResolvedSourceConfig,
+        source_dir: &Path,
+    ) -> Result<PathFilter> {
+        let mut filter = PathFilter::new(&resolved.include_patterns, &resolved.exclude_patterns)?
+            .with_file_limits(
+                source.min_file_size,
+                source.max_file_size,
+                source.modified_within,
+            );
+        if source.one_file_system {
+            filter = filter.with_one_file_system(source_dir)?;
+        }
+        Ok(filter)
+    }
+
+    /// Files of `source` that are new or changed since the last backup to
+    /// `backup_dir`, as `/`-separated paths relative to the source: what a
+    /// restore over the source would lose for good. A missing source has
+    /// nothing to lose.
+    pub fn unbacked_changes(
+        source: &SourceConfig,
+        resolved: &ResolvedSourceConfig,
+        backup_dir: &Path,
+    ) -> Result<Vec<String>> {
+        let (scan_base, scan_paths) = source.scan_base();
+        if !scan_base.exists() {
+            return Ok(Vec::new());
+        }
+        let filter = Self::source_filter(source, resolved, &scan_base)?;
+        let backup_dir = longpath::extended(backup_dir);
+        let mut metadata = Self::load_source_metadata(&backup_dir.join("metadata.json"));
+        Self::load_file_hashes(&backup_dir, &mut metadata)?;
+        let mut locked = LockedFiles::new(resolved);
+        let (_, changed, _) =
+            Self::scan_for_changes(&scan_base, &scan_paths, &metadata, &filter, &mut locked)?;
+        let mut changed: Vec<String> = changed
+            .iter()
+            .map(|path| attrs::manifest_key(path.strip_prefix(&scan_base).unwrap_or(path)))
+            .collect();
+        changed.sort();
+        Ok(changed)
+    }
+
+    pub(super) fn scan_for_changes(
+        source_dir: &Path,
+        paths: &[PathBuf],
+        metadata: &SourceMetadata,
+        filter: &PathFilter,
+        locked: &mut LockedFiles,
+    ) -> Result<(BackupType, Vec<PathBuf>, HashMap<String, String>)> {
+        let mut files_to_backup = Vec::new();
+        let mut current_hashes = HashMap::new();
+
+        if !source_dir.exists() {
+            return Err(Error::SourceMissing(source_dir.to_path_buf()).into());
+        }
+
+        Self::collect_files_in_roots(
+            source_dir,
+            paths,
+            &mut files_to_backup,
+            &mut current_hashes,
+            filter,
+            locked,
+        )?;
+
+        let backup_type = if metadata.last_full_backup.is_none() {
+            BackupType::Full
+        } else {
+            BackupType::Incremental
+        };
+
+        if matches!(backup_type, BackupType::Full) {
+            return Ok((backup_type, files_to_backup, current_hashes));
+        }
+
+        let changed_files: Vec<PathBuf> = files_to_backup
+            .into_iter()
+            .filter(|path| {
+                let relative_path = path.strip_prefix(source_dir).unwrap_or(path.as_path());
+                let key = attrs::manifest_key(relative_path);
+
+                let current_hash = current_hashes.get(&key);
+                let stored_hash = metadata.file_hashes.get(&key);
+
+                let changed = current_hash != stored_hash;
+                if changed {
+                    let status = if stored_hash.is_none() {
+                        "New"
+                    } else {
+                        "Changed"
+                    };
+                    debug!("{} since the last backup: {:?}", status, relative_path);
+                }
+                changed
+            })
+            .collect();
+
+        Ok((backup_type, changed_files, current_hashes))
+    }
+
+    /// `scan_for_changes` for an incremental whose changes are known: only
+    /// the `changed` paths (relative to `source_dir`, files or dirs, present
+    /// or removed) are rescanned and every other file keeps its hash from
+    /// `metadata`. Also returns the bytes hashed.
+    pub(super) fn scan_changed_paths(
+        source_dir: &Path,
+        paths: &[PathBuf],
+        metadata: &SourceMetadata,
+        changed: &BTreeSet<PathBuf>,
+        filter: &PathFilter,
+        locked: &mut LockedFiles,
+    ) -> Result<(Vec<PathBuf>, HashMap<String, String>, u64)> {
+        if !source_dir.exists() {
+            return Err(Error::SourceMissing(source_dir.to_path_buf()).into());
+        }
+
+        let mut current_hashes = metadata.file_hashes.clone();
+        let mut removed_dirs = HashSet::new();
+        let mut files = Vec::new();
+        let mut hashes = HashMap::new();
+        for relative in changed {
+            // A change above a configured subpath (e.g. its parent renamed)
+            // rescans the subpaths below it.
+            let targets = if paths.is_empty() || paths.iter().any(|p| relative.starts_with(p)) {
+                vec![relative.clone()]
+            } else {
+                paths
+                    .iter()
+                    .filter(|p| p.starts_with(relative))
+                    .cloned()
+                    .collect()
+            };
+            for target in targets {
+                let key = attrs::manifest_key(&target);
+                let path = source_dir.join(&target);
+                // Unless it was a file, it may have been a dir: what was
+                // below it is dropped and rescanned too.
+                if current_hashes.remove(&key).is_none() {
+                    removed_dirs.insert(format!("{}/", key));
+                }
+                if filter.is_excluded(&target)
+                    || !Self::ancestors_accepted(source_dir, &target, filter)
+                {
+                    continue;
+                }
+
+                if path.is_dir() {
+                    if filter.accepts_dir(&fs::metadata(&path)?) {
+                        Self::collect_files(
+                            source_dir,
+                            &path,
+                            &mut files,
+                            &mut hashes,
+                            filter,
+                            locked,
+                        )?;
+                    }
+                } else if path.is_file()
+                    && filter.is_included(&target)
+                    && filter.accepts_metadata(&fs::metadata(&path)?)
+                    && let Some(hash) =
+                        locked.read(&path, &key, || Self::calculate_file_hash(&path))?
+                {
+                    hashes.insert(key, hash);
+                    files.push(path);
+                }
+            }
+        }
+        if !removed_dirs.is_empty() {
+            current_hashes.retain(|key, _| {
+                !key.match_indices('/')
+                    .any(|(i, _)| removed_dirs.contains(&key[..=i]))
+            });
+        }
+        files.sort();
+        files.dedup();
+        let bytes_hashed = files
+            .iter()
+            .filter_map(|path| fs::metadata(path).ok())
+            .map(|m| m.len())
+            .sum();
+
+        let changed_files = files
+            .into_iter()
+            .filter(|path| {
+                let key = attrs::manifest_key(path.strip_prefix(source_dir).unwrap_or(path));
+                let changed = hashes.get(&key) != metadata.file_hashes.get(&key);
+                if changed {
+                    debug!("Reported changed since the last backup: {}", key);
+                }
+                changed
+            })
+            .collect();
+        current_hashes.extend(hashes);
+
+        Ok((changed_files, current_hashes, bytes_hashed))
+    }
+
+    /// Whether every directory above `relative` would be descended into by
+    /// a full scan (none on another filesystem under `one_file_system`).
+    fn ancestors_accepted(source_dir: &Path, relative: &Path, filter: &PathFilter) -> bool {
+        relative
+            .ancestors()
+            .skip(1)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .all(|dir| fs::metadata(source_dir.join(dir)).is_ok_and(|m| filter.accepts_dir(&m)))
+    }
+
+    pub(super) fn collect_all_files(
+        source_dir: &Path,
+        paths: &[PathBuf],
+        filter: &PathFilter,
+        locked: &mut LockedFiles,
+    ) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        let mut hashes = HashMap::new();
+        Self::collect_files_in_roots(source_dir, paths, &mut files, &mut hashes, filter, locked)?;
+        Ok(files)
+    }
+
+    /// Collect files under each configured subpath of the source (the whole
+    /// source when `paths` is empty). Keys stay relative to `source_dir`.
+    pub(super) fn collect_files_in_roots(
+        source_dir: &Path,
+        paths: &[PathBuf],
+        files: &mut Vec<PathBuf>,
+        hashes: &mut HashMap<String, String>,
+        filter: &PathFilter,
+        locked: &mut LockedFiles,
+    ) -> Result<()> {
+        for root in source_scan_roots(source_dir, paths) {
+            if root.is_dir() {
+                if !filter.accepts_dir(&fs::metadata(&root)?) {
+                    info!("Skipping subpath on another filesystem: {:?}", root);
+                    continue;
+                }
+                Self::collect_files(source_dir, &root, files, hashes, filter, locked)?;
+            } else if root.is_file() {
+                let relative_path = root.strip_prefix(source_dir).unwrap_or(root.as_path());
+                if !filter.accepts_file(relative_path)
+                    || !filter.accepts_metadata(&fs::metadata(&root)?)
+                {
+                    continue;
+                }
+                let relative_key = attrs::manifest_key(relative_path);
+                let Some(hash) =
+                    locked.read(&root, &relative_key, || Self::calculate_file_hash(&root))?
+                else {
+                    continue;
+                };
+                hashes.insert(relative_key, hash);
+                files.push(root);
+            } else {
+                warn!("Source subpath does not exist, skipping: {:?}", root);
+            }
+        }
+        Ok(())
+    }
+
+    fn collect_files(
+        base_dir: &Path,
+        dir: &Path,
+        files: &mut Vec<PathBuf>,
+        hashes: &mut HashMap<String, String>,
+        filter: &PathFilter,
+        locked: &mut LockedFiles,
+    ) -> Result<()> {
+        let entries = fs::read_dir(dir)?;
+
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            let relative_path = path.strip_prefix(base_dir).unwrap_or(path.as_path());
+
+            if filter.is_excluded(relative_path) {
+                continue;
+            }
+
+            if path.is_dir() {
+                if !filter.accepts_dir(&fs::metadata(&path)?) {
+                    info!("Skipping directory on another filesystem: {:?}", path);
+                    continue;
+                }
+                Self::collect_files(base_dir, &path, files, hashes, filter, locked)?;
+            } else if filter.is_included(relative_path)
+                && filter.accepts_metadata(&entry.metadata()?)
+            {
+                let key = attrs::manifest_key(relative_path);
+                let Some(hash) = locked.read(&path, &key, || Self::calculate_file_hash(&path))?
+                else {
+                    continue;
+                };
+                hashes.insert(key, hash);
+                files.push(path);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Manifest key of the primary (lexicographically first) member of each
+    /// hard-link group touched by this backup, keyed by device/inode. All
+    /// scanned files are considered so a new link to an unchanged file still
+    /// points at the copy restored from an earlier point.
+    pub(super) fn hard_link_primaries(
+        source_dir: &Path,
+        files_to_backup: &[PathBuf],
+        current_hashes: &HashMap<String, String>,
+    ) -> HashMap<(u64, u64), String> {
+        let mut primaries = HashMap::new();
+        let linked = files_to_backup.iter().any(|path| {
+            fs::metadata(path)
+                .ok()
+                .and_then(|m| attrs::hard_link_identity(&m))
+                .is_some()
+        });
+        if !linked {
+            return primaries;
+        }
+
+        for relative in current_hashes.keys() {
+            let Some(identity) = fs::metadata(source_dir.join(attrs::key_path(relative)))
+                .ok()
+                .and_then(|m| attrs::hard_link_identity(&m))
+            else {
+                continue;
+            };
+            let key = relative.clone();
+            primaries
+                .entry(identity)
+                .and_modify(|primary: &mut String| {
+                    if key < *primary {
+                        *primary = key.clone();
+                    }
+                })
+                .or_insert(key);
+        }
+        primaries
+    }
+
+    pub(crate) fn calculate_file_hash(path: &Path) -> Result<String> {
+        let file = fs::File::open(path)?;
+        let mut reader = BufReader::new(file);
+        let mut hasher = Sha256::new();
+        let mut buffer = [0; 8192];
+
+        loop {
+            let bytes_read = reader.read(&mut buffer)?;
+            if bytes_read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..bytes_read]);
+        }
+
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Most recent stored copy of `relative_path`. A copy inside an archive
+    /// is extracted to `scratch_path`, which the caller must remove.
+    pub(super) fn find_latest_backup_file(
+        backup_dir: &Path,
+        relative_path: &Path,
+        scratch_path: &Path,
+    ) -> Option<PathBuf> {
+        let mut backup_dirs: Vec<_> = fs::read_dir(backup_dir)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| archive::backup_point_name(&entry.path()).is_some())
+            .collect();
+
+        backup_dirs.sort_by(|a, b| {
+            let a_time = a
+                .metadata()
+                .and_then(|m| m.modified())
+                .unwrap_or(std::time::UNIX_EPOCH);
+            let b_time = b
+                .metadata()
+                .and_then(|m| m.modified())
+                .unwrap_or(std::time::UNIX_EPOCH);
+            b_time.cmp(&a_time)
+        });
+
+        for dir in backup_dirs {
+            let path = dir.path();
+            if path.is_file() {
+                let Ok(mut reader) = ArchiveReader::open_with_env(&path) else {
+                    continue;
+                };
+                let Some(entry) = reader.find(relative_path).cloned() else {
+                    continue;
+                };
+                match reader.extract_file(&entry, scratch_path, None) {
+                    Ok(()) => return Some(scratch_path.to_path_buf()),
+                    Err(e) => {
+                        warn!("Failed to read delta base from {:?}: {:#}", path, e);
+                        let _ = fs::remove_file(scratch_path);
+                        continue;
+                    }
+                }
+            }
+            let candidate = path.join(relative_path);
+            if candidate.exists() && candidate.is_file() {
+                return Some(candidate);
+            }
+        }
+
+        None
+    }
+
+    /// Re-hash a file just written to a backup point and fail when it does
+    /// not match the source, so bad RAM or disks are caught before the
+    /// point is committed.
+    pub(super) fn verify_written_file(written: &Path, expected_hash: &str) -> Result<()> {
+        let actual = Self::calculate_file_hash(written)
+            .with_context(|| format!("Failed to re-read {:?} for verification", written))?;
+        if actual != expected_hash {
+            return Err(anyhow::anyhow!(
+                "Verification failed for {:?}: content does not match the source",
+                written
+            ));
+        }
+        Ok(())
+    }
+
+    /// Reload a delta from disk, rebuild the file from its base in
+    /// `scratch_path` and check the result against the source hash.
+    pub(super) fn verify_written_delta(
+        base_path: &Path,
+        delta_path: &Path,
+        scratch_path: &Path,
+        expected_hash: &str,
+    ) -> Result<()> {
+        let rebuilt = delta::load_delta(delta_path)
+            .and_then(|delta_data| delta::apply_delta(base_path, &delta_data, scratch_path, None))
+            .and_then(|()| Self::calculate_file_hash(scratch_path));
+        let _ = fs::remove_file(scratch_path);
+        if rebuilt
+            .with_context(|| format!("Failed to rebuild {:?} for verification", delta_path))?
+            != expected_hash
+        {
+            return Err(anyhow::anyhow!(
+                "Verification failed for {:?}: delta does not reproduce the source",
+                delta_path
+            ));
+        }
+        Ok(())
+    }
+
+    /// Flush a written file's data to disk. Windows can only flush handles
+    /// opened for writing, so read-only files there are skipped; `fs::copy`
+    /// already closed them after a complete write.
+    pub(super) fn sync_file(path: &Path) -> Result<()> {
+        let file = if cfg!(unix) {
+            fs::File::open(path)
+        } else {
+            match fs::File::options().write(true).open(path) {
+                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => return Ok(()),
+                other => other,
+            }
+        };
+        file.and_then(|file| file.sync_all())
+            .with_context(|| format!("Failed to flush {:?}", path))
+    }
+
+    /// Flush a directory's entries so creates and renames inside it survive
+    /// a power cut. NTFS journals those itself, so this is Unix-only.
+    pub(super) fn sync_dir(dir: &Path) -> Result<()> {
+        #[cfg(unix)]
+        fs::File::open(dir)
+            .and_then(|file| file.sync_all())
+            .with_context(|| format!("Failed to flush directory {:?}", dir))?;
+        #[cfg(not(unix))]
+        let _ = dir;
+        Ok(())
+    }
+
+    /// Flush every file and directory below `dir`, children first.
+    pub(super) fn sync_tree(dir: &Path) -> Result<()> {
+        for entry in walkdir::WalkDir::new(dir).contents_first(true) {
+            let entry = entry?;
+            if entry.file_type().is_dir() {
+                Self::sync_dir(entry.path())?;
+            } else if entry.file_type().is_file() {
+                Self::sync_file(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Newest full backup stored as a directory. Archived fulls cannot be
+    /// linked into, so they are ignored.
+    pub(super) fn latest_full_backup_dir(backup_dir: &Path) -> Option<PathBuf> {
+        fs::read_dir(backup_dir)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .filter_map(|path| {
+                let name = archive::backup_point_name(&path)?;
+                let parsed = point_name::parse(&name)?;
+                parsed.is_full.then(|| (parsed.timestamp.to_string(), path))
+            })
+            .max_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, path)| path)
+    }
+
+    /// Hard-link `relative_path` from `previous_full` to `target` when the
+    /// stored copy still hashes to `expected_hash`. Re-hashing the old copy
+    /// keeps a damaged previous full from leaking into the new one. Returns
+    /// false when the file must be copied instead.
+    pub(super) fn link_from_previous_full(
+        previous_full: &Path,
+        relative_path: &Path,
+        target: &Path,
+        expected_hash: &str,
+    ) -> bool {
+        let candidate = previous_full.join(relative_path);
+        if !candidate.is_file() {
+            return false;
+        }
+        match Self::calculate_file_hash(&candidate) {
+            Ok(hash) if hash == expected_hash => fs::hard_link(&candidate, target).is_ok(),
+            _ => false,
+        }
+    }
+
+    /// Apply retention: keep at most `max_backups` points and, with a quota,
+    /// keep removing the oldest while the dir is larger than
+    /// `max_total_size`. In delta mode the latest full and its incs are
+    /// never removed, so the newest backup stays restorable. Returns the
+    /// size left in the dir when a quota is set.
+    ///
+    /// Append-only dirs are left untouched; only `ardiex prune
+    /// --allow-delete` removes points from them.
+    pub(super) fn cleanup_old_backups(
+        backup_dir: &Path,
+        max_backups: usize,
+        max_total_size: Option<u64>,
+        backup_mode: &BackupMode,
+    ) -> Result<Option<u64>> {
+        if Self::is_append_only(backup_dir) {
+            info!(
+                "[{:?}] Append-only backup dir, skipping retention (run `ardiex prune --allow-delete` to remove old backups)",
+                backup_dir
+            );
+            return Ok(max_total_size.map(|_| Self::total_backup_size(backup_dir)));
+        }
+        let plan = Self::plan_retention(backup_dir, max_backups, max_total_size, backup_mode)?;
+        Self::remove_backup_points(&plan.remove);
+        Ok(plan.remaining_size)
+    }
+
+    /// Backup points in `backup_dir`, oldest first. Points written within
+    /// one tick of a coarse filesystem clock share an mtime, so ties go by
+    /// the timestamp in the name.
+    pub(super) fn sorted_backup_points(backup_dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut points: Vec<(std::time::SystemTime, String, PathBuf)> = fs::read_dir(backup_dir)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let path = entry.path();
+                let name = archive::backup_point_name(&path)?;
+                let timestamp = point_name::parse(&name)
+                    .map(|name| name.timestamp.to_string())
+                    .unwrap_or_default();
+                let modified = entry
+                    .metadata()
+                    .and_then(|m| m.modified())
+                    .unwrap_or(std::time::UNIX_EPOCH);
+                Some((modified, timestamp, path))
+            })
+            .collect();
+        points.sort();
+        Ok(points.into_iter().map(|(_, _, path)| path).collect())
+    }
+
+    /// Which points retention would remove, oldest first, without touching
+    /// anything. Points held by a pin neither count toward `max_backups`
+    /// nor get removed.
+    pub(super) fn plan_retention(
+        backup_dir: &Path,
+        max_backups: usize,
+        max_total_size: Option<u64>,
+        backup_mode: &BackupMode,
+    ) -> Result<RetentionPlan> {
+        let points = Self::sorted_backup_points(backup_dir)?;
+        let held = Self::held_by_pins(backup_dir, &points, backup_mode);
+        let candidates: Vec<usize> = (0..points.len()).filter(|&i| !held[i]).collect();
+
+        // Everything from this index on is kept. Dedup incs list only
+        // changed files, so like delta they need their full.
+        let protect_from = if matches!(backup_mode, BackupMode::Delta | BackupMode::Dedup) {
+            points
+                .iter()
+                .rposition(|path| {
+                    archive::backup_point_name(path)
+                        .and_then(|name| point_name::parse(&name).map(|name| name.is_full))
+                        .unwrap_or(false)
+                })
+                .unwrap_or(0)
+        } else {
+            points.len().saturating_sub(1)
+        };
+        let protect_count = candidates.iter().filter(|&&i| i >= protect_from).count();
+        let keep_count = max_backups.max(protect_count);
+        let count_limited = candidates.len() > keep_count;
+        let mut to_remove = candidates.len().saturating_sub(keep_count);
+
+        let mut remaining_size = None;
+        if let Some(limit) = max_total_size {
+            let sizes: Vec<u64> = points
+                .iter()
+                .map(|path| Self::backup_point_size(path))
+                .collect();
+            let held_size: u64 = (0..points.len())
+                .filter(|&i| held[i])
+                .map(|i| sizes[i])
+                .sum();
+            let mut total: u64 = held_size
+                + candidates[to_remove..]
+                    .iter()
+                    .map(|&i| sizes[i])
+                    .sum::<u64>();
+            let removable = candidates.len().saturating_sub(protect_count);
+            while total > limit && to_remove < removable {
+                total -= sizes[candidates[to_remove]];
+                to_remove += 1;
+            }
+            if to_remove > candidates.len().saturating_sub(keep_count) {
+                info!(
+                    "[{:?}] Pruning to stay within max_total_size ({})",
+                    backup_dir,
+                    crate::units::format_size(limit)
+                );
+            }
+            remaining_size = Some(total);
+        }
+
+        if count_limited && keep_count > max_backups {
+            warn!(
+                "Keeping {} backups (> max_backups={}) to preserve delta restore chain",
+                keep_count, max_backups
+            );
+        }
+
+        Ok(RetentionPlan {
+            remove: candidates[..to_remove]
+                .iter()
+                .map(|&i| points[i].clone())
+                .collect(),
+            remaining_size,
+        })
+    }
+
+    pub(super) fn remove_backup_points(points: &[PathBuf]) {
+        for path in points {
+            let removed = if path.is_dir() {
+                fs::remove_dir_all(path)
+            } else {
+                fs::remove_file(path)
+            };
+            if let Err(e) = removed {
+                warn!("Failed to remove old backup {:?}: {}", path, e);
+            } else {
+                info!("Removed old backup: {:?}", path);
+                parity::remove_for_point(path);
+            }
+        }
+    }
+
+    /// Bytes on disk of all backup points in `backup_dir`.
+    fn total_backup_size(backup_dir: &Path) -> u64 {
+        fs::read_dir(backup_dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| archive::backup_point_name(path).is_some())
+                    .map(|path| Self::backup_point_size(&path))
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Bytes on disk of one backup point (directory tree or archive file).
+    pub(super) fn backup_point_size(path: &Path) -> u64 {
+        if path.is_file() {
+            return fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        }
+        walkdir::WalkDir::new(path)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .filter_map(|entry| entry.metadata().ok())
+            .map(|m| m.len())
+            .sum()
+    }
+}
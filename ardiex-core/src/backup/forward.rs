@@ -0,0 +1,226 @@
+use super::*;
+use crate::archive;
+use crate::config::SourceMetadata;
+use chrono::{DateTime, Duration as ChronoDuration};
+use log::{info, warn};
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// Outcome of one queue-and-forward pass for a source.
+#[derive(Debug, Default)]
+pub struct ForwardResult {
+    pub reachable: bool,
+    pub forwarded: Vec<String>,
+    /// Backups still waiting to be forwarded after this pass.
+    pub queued: usize,
+    pub oldest_queued_at: Option<DateTime<Utc>>,
+}
+
+impl ForwardResult {
+    /// True when the oldest queued backup has waited longer than `threshold`.
+    pub fn queue_exceeds(&self, threshold: ChronoDuration) -> bool {
+        self.oldest_queued_at
+            .is_some_and(|oldest| Utc::now() - oldest > threshold)
+    }
+}
+
+impl BackupManager {
+    /// Copy backups from `local_dir` that were not forwarded yet to
+    /// `forward_dir`, oldest first so the remote chain always starts with its
+    /// full. An unreachable `forward_dir` is not an error; the queue is kept.
+    pub fn forward_queued_backups(
+        local_dir: &Path,
+        forward_dir: &Path,
+        resolved: &ResolvedSourceConfig,
+    ) -> Result<ForwardResult> {
+        // The local lock is only held shared and only while reading, so a
+        // backup of `local_dir` waits at most for one point to be copied.
+        let metadata = {
+            let _local_lock = BackupDirLock::acquire_shared(local_dir, "forward", None)?;
+            Self::load_source_metadata(&local_dir.join("metadata.json"))
+        };
+        let signing_key = signing::load_optional(resolved.signing_key_file.as_deref())?;
+        let queued: Vec<&BackupHistoryEntry> = metadata
+            .backup_history
+            .iter()
+            .filter(|entry| entry.forwarded_at.is_none())
+            .collect();
+
+        let mut result = ForwardResult {
+            reachable: forward_dir.is_dir(),
+            queued: queued.len(),
+            oldest_queued_at: queued.first().map(|entry| entry.created_at),
+            ..Default::default()
+        };
+        if queued.is_empty() || !result.reachable {
+            return Ok(result);
+        }
+
+        {
+            // Same preparation as a backup into `forward_dir`, since that is
+            // what the copied points amount to.
+            let _remote_lock = BackupDirLock::acquire(forward_dir, "forward")?;
+            repo::check_writable(forward_dir, resolved)?;
+            Self::recover_pending_reverse(forward_dir, false)?;
+            let remote_before = Self::load_source_metadata(&forward_dir.join("metadata.json"));
+            // Local entries as of each copy, so their seal covers exactly
+            // the content that landed on the remote.
+            let mut copied_entries: Vec<BackupHistoryEntry> = Vec::new();
+            Self::remove_stale_partial_backups(forward_dir);
+            if resolved.append_only {
+                Self::mark_append_only(forward_dir)?;
+            }
+
+            for entry in &queued {
+                if archive::locate_backup(forward_dir, &entry.backup_name).is_none() {
+                    let copied = BackupDirLock::acquire_shared(local_dir, "forward", None)
+                        .and_then(|_lock| {
+                            let local_path = archive::locate_backup(local_dir, &entry.backup_name)
+                                .unwrap_or_else(|| local_dir.join(&entry.backup_name));
+                            Self::copy_backup_to(&local_path, forward_dir)?;
+                            Ok(Self::load_source_metadata(&local_dir.join("metadata.json"))
+                                .backup_history
+                                .into_iter()
+                                .find(|current| current.backup_name == entry.backup_name))
+                        });
+                    match copied {
+                        Ok(current) => copied_entries.extend(current),
+                        Err(e) => {
+                            // Stop here so later incrementals never land
+                            // without their predecessors; the rest stays
+                            // queued.
+                            warn!(
+                                "[FORWARD] Failed to forward {} to {:?}: {}",
+                                entry.backup_name, forward_dir, e
+                            );
+                            break;
+                        }
+                    }
+                    info!(
+                        "[FORWARD] Forwarded {} to {:?}",
+                        entry.backup_name, forward_dir
+                    );
+                }
+                result.forwarded.push(entry.backup_name.clone());
+            }
+
+            if !result.forwarded.is_empty() {
+                Self::cleanup_old_backups(
+                    forward_dir,
+                    resolved.max_backups,
+                    resolved.max_total_size,
+                    &resolved.backup_mode,
+                )?;
+                // Seeding from the local metadata and its hash journal keeps
+                // file hashes and annotations for every backup that exists
+                // on the remote.
+                let mut remote_metadata: SourceMetadata = metadata.clone();
+                // A later reverse rewrite re-seals local points, but the
+                // remote keeps the content it was sent; its entries keep the
+                // seal of that content.
+                for entry in &mut remote_metadata.backup_history {
+                    if let Some(sent) = copied_entries
+                        .iter()
+                        .chain(&remote_before.backup_history)
+                        .find(|sent| sent.backup_name == entry.backup_name)
+                    {
+                        entry.signature = sent.signature.clone();
+                        entry.reverse_deltas = sent.reverse_deltas;
+                    }
+                }
+                Self::synchronize_metadata_history_with_disk(forward_dir, &mut remote_metadata)?;
+                Self::save_source_metadata(
+                    &forward_dir.join("metadata.json"),
+                    &remote_metadata,
+                    signing_key.as_ref(),
+                )?;
+                let _local_lock = BackupDirLock::acquire_shared(local_dir, "forward", None)?;
+                Self::copy_file_hashes(local_dir, forward_dir)?;
+            }
+        }
+
+        if !result.forwarded.is_empty() {
+            Self::mark_forwarded(local_dir, &result.forwarded, signing_key.as_ref())?;
+        }
+
+        let remaining: Vec<&&BackupHistoryEntry> = queued
+            .iter()
+            .filter(|entry| !result.forwarded.contains(&entry.backup_name))
+            .collect();
+        result.queued = remaining.len();
+        result.oldest_queued_at = remaining.first().map(|entry| entry.created_at);
+        Ok(result)
+    }
+
+    /// Copy one backup point (directory or archive file) into `forward_dir`
+    /// under a partial name, then rename it into place.
+    pub(super) fn copy_backup_to(local_path: &Path, forward_dir: &Path) -> Result<()> {
+        let backup_name = local_path
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("Invalid backup path: {:?}", local_path))?
+            .to_string_lossy()
+            .to_string();
+        if local_path.is_file() {
+            let staging = forward_dir.join(format!("{}{}", PARTIAL_BACKUP_PREFIX, backup_name));
+            fs::copy(local_path, &staging)
+                .with_context(|| format!("Failed to copy {:?}", local_path))?;
+            return fs::rename(&staging, forward_dir.join(&backup_name))
+                .with_context(|| format!("Failed to finalize forwarded archive {:?}", staging));
+        }
+
+        // A point of a per-dir dedup store is only usable next to its
+        // chunks, so those go first; a shared store is reachable already
+        // and only learns that `forward_dir` uses it too.
+        if let Some(index) = dedup::read_index(local_path)? {
+            match &index.store {
+                Some(shared_store) => dedup::register_referrer(shared_store, forward_dir)?,
+                None => {
+                    let local_dir = local_path.parent().unwrap_or(local_path);
+                    ChunkStore::open(&index.store_root(local_dir)).copy_missing_to(
+                        &ChunkStore::open(&index.store_root(forward_dir)),
+                        &index,
+                    )?;
+                }
+            }
+        }
+
+        let partial = PartialBackupDir::create(
+            forward_dir.join(format!("{}{}", PARTIAL_BACKUP_PREFIX, backup_name)),
+        )?;
+        for entry in WalkDir::new(local_path) {
+            let entry = entry?;
+            let relative = entry.path().strip_prefix(local_path)?;
+            let target = partial.path().join(relative);
+            if entry.file_type().is_dir() {
+                fs::create_dir_all(&target)?;
+            } else {
+                fs::copy(entry.path(), &target)
+                    .with_context(|| format!("Failed to copy {:?}", entry.path()))?;
+            }
+        }
+        partial.commit(&forward_dir.join(&backup_name))
+    }
+
+    /// Record forwarding state in the local metadata.json. The local lock was
+    /// let go between points, so a backup may have rewritten the file since;
+    /// reload it under the exclusive lock.
+    fn mark_forwarded(
+        local_dir: &Path,
+        forwarded: &[String],
+        signing_key: Option<&SigningKey>,
+    ) -> Result<()> {
+        let _lock = BackupDirLock::acquire_after_readers(local_dir, "forward", BACKUP_READER_WAIT)?;
+        let metadata_path = local_dir.join("metadata.json");
+        let mut metadata = Self::load_source_metadata(&metadata_path);
+        let now = Utc::now();
+        for entry in metadata
+            .backup_history
+            .iter_mut()
+            .filter(|entry| forwarded.contains(&entry.backup_name))
+        {
+            entry.forwarded_at = Some(now);
+        }
+        Self::save_source_metadata(&metadata_path, &metadata, signing_key)
+    }
+}
@@ -0,0 +1,139 @@
+use super::*;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+/// Outcome of `ardiex gc` for one chunk store. With `dry_run` the chunks
+/// are only counted.
+#[derive(Debug, Default)]
+pub struct GcReport {
+    pub store: PathBuf,
+    pub dry_run: bool,
+    pub chunks: usize,
+    pub unreferenced: usize,
+    pub unreferenced_bytes: u64,
+}
+
+impl BackupManager {
+    /// Remove chunks no backup point references anymore. `backup_dirs` must
+    /// be every configured dir whose points may use one of the stores: the
+    /// per-dir `objects/` store of each and the `shared_stores` are swept
+    /// against the indexes found in all of them. Shared stores also name
+    /// the dirs using them (see `dedup::register_referrer`), which are
+    /// scanned too. Every dir is locked for the whole run so no backup can
+    /// publish an index in between.
+    ///
+    /// A shared store is only swept when every dir that may use it could be
+    /// read: a missing or unmounted dir, or a store without a list of its
+    /// dirs, fails the run instead of deleting chunks only those points use.
+    pub fn collect_garbage(
+        backup_dirs: &[PathBuf],
+        shared_stores: &[PathBuf],
+        dry_run: bool,
+    ) -> Result<Vec<GcReport>> {
+        let mut locks = Vec::new();
+        let mut referenced: BTreeMap<PathBuf, HashSet<String>> = BTreeMap::new();
+        let mut shared: BTreeSet<PathBuf> = shared_stores.iter().cloned().collect();
+        let mut checked_stores: HashSet<PathBuf> = HashSet::new();
+        let mut scanned: HashSet<PathBuf> = HashSet::new();
+        let mut missing: Vec<PathBuf> = Vec::new();
+        let mut pending: Vec<PathBuf> = backup_dirs.to_vec();
+
+        loop {
+            while let Some(backup_dir) = pending.pop() {
+                if !scanned.insert(backup_dir.clone()) {
+                    continue;
+                }
+                if fs::read_dir(&backup_dir).is_err() {
+                    missing.push(backup_dir);
+                    continue;
+                }
+                locks.push(BackupDirLock::acquire(&backup_dir, "gc")?);
+
+                let local_store = dedup::store_root(&backup_dir, None);
+                if local_store.is_dir() {
+                    referenced.entry(local_store).or_default();
+                }
+                for entry in fs::read_dir(&backup_dir)? {
+                    let path = entry?.path();
+                    if archive::backup_point_name(&path).is_none() {
+                        continue;
+                    }
+                    if let Some(index) = dedup::read_index(&path)? {
+                        if let Some(store) = &index.store {
+                            shared.insert(store.clone());
+                        }
+                        referenced
+                            .entry(index.store_root(&backup_dir))
+                            .or_default()
+                            .extend(dedup::referenced_chunks([&index]));
+                    }
+                }
+            }
+
+            // Dirs a shared store lists are scanned even when the config no
+            // longer has them; stop once no store adds a new one.
+            for store in &shared {
+                if !checked_stores.insert(store.clone()) || !store.is_dir() {
+                    continue;
+                }
+                let referrers = dedup::read_referrers(store)?.ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Shared chunk store {:?} has no {} listing the backup dirs using it; \
+                         refusing to sweep it. Run a backup of every source using it, then gc again",
+                        store,
+                        dedup::REFERRERS_FILE_NAME
+                    )
+                })?;
+                pending.extend(referrers.into_iter().filter(|dir| !scanned.contains(dir)));
+            }
+            if pending.is_empty() {
+                break;
+            }
+        }
+
+        if !shared.is_empty() && !missing.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Backup dir(s) {:?} are not available (unmounted or removed?); refusing to \
+                 sweep shared chunk stores their points may use. Reconnect them, or remove \
+                 dirs that are gone for good from the {} file of each store",
+                missing,
+                dedup::REFERRERS_FILE_NAME
+            ));
+        }
+        for store in &shared {
+            referenced.entry(store.clone()).or_default();
+        }
+
+        let mut reports = Vec::new();
+        for (store_root, in_use) in referenced {
+            let store = ChunkStore::open(&store_root);
+            let mut report = GcReport {
+                store: store_root.clone(),
+                dry_run,
+                ..Default::default()
+            };
+            for (hash, path, size) in store.list_chunks()? {
+                report.chunks += 1;
+                if in_use.contains(&hash) {
+                    continue;
+                }
+                report.unreferenced += 1;
+                report.unreferenced_bytes += size;
+                if !dry_run {
+                    fs::remove_file(&path)
+                        .with_context(|| format!("Failed to remove chunk {:?}", path))?;
+                }
+            }
+            if !dry_run && report.unreferenced > 0 {
+                info!(
+                    "[{:?}] Removed {} unreferenced chunks ({} bytes)",
+                    store_root, report.unreferenced, report.unreferenced_bytes
+                );
+            }
+            reports.push(report);
+        }
+        drop(locks);
+        Ok(reports)
+    }
+}
@@ -0,0 +1,277 @@
+use super::*;
+use crate::config::{HashJournalDigest, SourceMetadata};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+
+/// File hashes of a backup dir, kept out of metadata.json so a backup of a
+/// million-file source appends the few hashes it changed instead of
+/// rewriting all of them. Each line sets or (without `hash`) removes one
+/// key; the file is rewritten as a plain snapshot once superseded lines
+/// outnumber live ones. Its length and digest are saved in metadata.json,
+/// so a signed dir signs the journal too.
+const HASH_JOURNAL_FILE_NAME: &str = "file_hashes.jsonl";
+
+/// Superseded lines tolerated before compaction regardless of size, so
+/// small sources are not rewritten on every backup.
+const MIN_COMPACTION_SLACK: usize = 1024;
+
+#[derive(Serialize, Deserialize)]
+struct JournalLine {
+    path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hash: Option<String>,
+}
+
+/// What the journal on disk holds, to write only the difference on commit.
+pub(super) struct HashJournal {
+    path: PathBuf,
+    committed: HashMap<String, String>,
+    lines: usize,
+    /// Digest and length of what the file holds once committed.
+    hasher: blake3::Hasher,
+    len: u64,
+    /// Set when the file has to be rewritten rather than appended to: it is
+    /// missing, ends in a torn line, or is superseded by inline hashes.
+    rewrite: bool,
+}
+
+impl HashJournal {
+    /// What to record in metadata.json for the journal as committed.
+    pub(super) fn digest(&self) -> HashJournalDigest {
+        HashJournalDigest {
+            len: self.len,
+            blake3: self.hasher.finalize().to_hex().to_string(),
+        }
+    }
+
+    /// Write what changed between the journal and `hashes`, compacting it
+    /// when it has grown to more than twice the live entries.
+    pub(super) fn commit(
+        &mut self,
+        hashes: &HashMap<String, String>,
+        durability: &Durability,
+    ) -> Result<()> {
+        let mut changes: Vec<JournalLine> = hashes
+            .iter()
+            .filter(|(path, hash)| self.committed.get(*path) != Some(*hash))
+            .map(|(path, hash)| JournalLine {
+                path: path.clone(),
+                hash: Some(hash.clone()),
+            })
+            .collect();
+        changes.extend(
+            self.committed
+                .keys()
+                .filter(|path| !hashes.contains_key(*path))
+                .map(|path| JournalLine {
+                    path: path.clone(),
+                    hash: None,
+                }),
+        );
+        if changes.is_empty() && !self.rewrite {
+            return Ok(());
+        }
+
+        let fsync = matches!(durability, Durability::Fsync);
+        if self.rewrite || self.lines + changes.len() > 2 * hashes.len() + MIN_COMPACTION_SLACK {
+            let tmp_path = self.path.with_extension("jsonl.tmp");
+            let mut writer = std::io::BufWriter::new(
+                fs::File::create(&tmp_path)
+                    .with_context(|| format!("Failed to write {:?}", tmp_path))?,
+            );
+            let mut hasher = blake3::Hasher::new();
+            let mut len = 0;
+            for (path, hash) in hashes {
+                let line = JournalLine {
+                    path: path.clone(),
+                    hash: Some(hash.clone()),
+                };
+                let line = format!("{}\n", serde_json::to_string(&line)?);
+                writer.write_all(line.as_bytes())?;
+                hasher.update(line.as_bytes());
+                len += line.len() as u64;
+            }
+            writer.flush()?;
+            drop(writer);
+            if fsync {
+                BackupManager::sync_file(&tmp_path)?;
+            }
+            fs::rename(&tmp_path, &self.path)
+                .with_context(|| format!("Failed to replace {:?}", self.path))?;
+            if fsync && let Some(parent) = self.path.parent() {
+                BackupManager::sync_dir(parent)?;
+            }
+            self.lines = hashes.len();
+            self.hasher = hasher;
+            self.len = len;
+            self.rewrite = false;
+        } else {
+            let mut content = String::new();
+            for line in &changes {
+                content.push_str(&serde_json::to_string(line)?);
+                content.push('\n');
+            }
+            let mut file = fs::OpenOptions::new()
+                .append(true)
+                .open(&self.path)
+                .with_context(|| format!("Failed to open {:?}", self.path))?;
+            file.write_all(content.as_bytes())
+                .with_context(|| format!("Failed to append to {:?}", self.path))?;
+            if fsync {
+                file.sync_all()?;
+            }
+            self.lines += changes.len();
+            self.hasher.update(content.as_bytes());
+            self.len += content.len() as u64;
+        }
+        for line in changes {
+            match line.hash {
+                Some(hash) => self.committed.insert(line.path, hash),
+                None => self.committed.remove(&line.path),
+            };
+        }
+        Ok(())
+    }
+}
+
+impl BackupManager {
+    /// Fill `metadata.file_hashes` from the journal of `backup_dir`. Hashes
+    /// still inline in metadata.json, from before the journal or from an
+    /// older ardiex writing the dir since, win and replace the journal on
+    /// the next commit. Only the prefix recorded in metadata.json is read;
+    /// a journal that does not match it is discarded.
+    pub(super) fn load_file_hashes(
+        backup_dir: &Path,
+        metadata: &mut SourceMetadata,
+    ) -> Result<HashJournal> {
+        let path = backup_dir.join(HASH_JOURNAL_FILE_NAME);
+        let mut journal = HashJournal {
+            path,
+            committed: HashMap::new(),
+            lines: 0,
+            hasher: blake3::Hasher::new(),
+            len: 0,
+            rewrite: true,
+        };
+        if !metadata.file_hashes.is_empty() || !journal.path.exists() {
+            return Ok(journal);
+        }
+
+        let file = fs::File::open(&journal.path)
+            .with_context(|| format!("Failed to read {:?}", journal.path))?;
+        let mut reader = BufReader::new(file);
+        let mut buf = String::new();
+        let mut torn = false;
+        let mut uncommitted = false;
+        loop {
+            if let Some(recorded) = &metadata.hash_journal
+                && journal.len >= recorded.len
+            {
+                uncommitted = reader.read_line(&mut buf)? > 0;
+                break;
+            }
+            buf.clear();
+            if reader.read_line(&mut buf)? == 0 {
+                break;
+            }
+            // Only an interrupted append leaves a line without its newline.
+            let parsed = buf
+                .strip_suffix('\n')
+                .and_then(|line| serde_json::from_str::<JournalLine>(line).ok());
+            let Some(line) = parsed else {
+                if !buf.ends_with('\n') {
+                    torn = true;
+                    break;
+                }
+                // Like an unreadable metadata.json: start over, so the next
+                // backup re-copies what it cannot compare.
+                warn!(
+                    "[{:?}] Corrupt line {} in {}, discarding file hashes",
+                    backup_dir,
+                    journal.lines + 1,
+                    HASH_JOURNAL_FILE_NAME
+                );
+                return Ok(Self::discard_file_hashes(journal));
+            };
+            journal.hasher.update(buf.as_bytes());
+            journal.len += buf.len() as u64;
+            match line.hash {
+                Some(hash) => journal.committed.insert(line.path, hash),
+                None => journal.committed.remove(&line.path),
+            };
+            journal.lines += 1;
+        }
+        if let Some(recorded) = &metadata.hash_journal
+            && journal.digest() != *recorded
+        {
+            warn!(
+                "[{:?}] {} does not match metadata.json, discarding file hashes",
+                backup_dir, HASH_JOURNAL_FILE_NAME
+            );
+            return Ok(Self::discard_file_hashes(journal));
+        }
+        if torn {
+            warn!(
+                "[{:?}] Ignoring a partly written last line in {}",
+                backup_dir, HASH_JOURNAL_FILE_NAME
+            );
+        } else if uncommitted {
+            warn!(
+                "[{:?}] Ignoring lines of an unfinished backup in {}",
+                backup_dir, HASH_JOURNAL_FILE_NAME
+            );
+        }
+        journal.rewrite = torn || uncommitted;
+        metadata.file_hashes = journal.committed.clone();
+        Ok(journal)
+    }
+
+    fn discard_file_hashes(mut journal: HashJournal) -> HashJournal {
+        journal.committed.clear();
+        journal.lines = 0;
+        journal.hasher = blake3::Hasher::new();
+        journal.len = 0;
+        journal.rewrite = true;
+        journal
+    }
+
+    /// Commit the file hashes to the journal, then save the rest of
+    /// `metadata` to metadata.json. The journal goes first: a crash in
+    /// between leaves hashes for a point the next sync picks up from disk.
+    pub(super) fn save_metadata_and_hashes(
+        metadata_path: &Path,
+        metadata: &mut SourceMetadata,
+        journal: &mut HashJournal,
+        durability: &Durability,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<()> {
+        journal.commit(&metadata.file_hashes, durability)?;
+        metadata.hash_journal = Some(journal.digest());
+        let hashes = std::mem::take(&mut metadata.file_hashes);
+        let saved =
+            Self::save_source_metadata_with(metadata_path, metadata, durability, signing_key);
+        metadata.file_hashes = hashes;
+        saved
+    }
+
+    /// Copy the hash journal along with metadata.json to another backup
+    /// dir (forward, replicate), so backing up into that dir stays
+    /// incremental.
+    pub(super) fn copy_file_hashes(from_dir: &Path, to_dir: &Path) -> Result<()> {
+        let from = from_dir.join(HASH_JOURNAL_FILE_NAME);
+        if !from.is_file() {
+            return Ok(());
+        }
+        let to = to_dir.join(HASH_JOURNAL_FILE_NAME);
+        let tmp = to.with_extension("jsonl.tmp");
+        fs::copy(&from, &tmp).with_context(|| format!("Failed to copy {:?}", from))?;
+        fs::rename(&tmp, &to).with_context(|| format!("Failed to replace {:?}", to))?;
+        Ok(())
+    }
+
+    /// Whether the file hashes of `backup_dir` are recorded anywhere.
+    pub(super) fn has_file_hashes(backup_dir: &Path, metadata: &SourceMetadata) -> bool {
+        !metadata.file_hashes.is_empty()
+            || fs::metadata(backup_dir.join(HASH_JOURNAL_FILE_NAME)).is_ok_and(|m| m.len() > 0)
+    }
+}
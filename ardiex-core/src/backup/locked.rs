@@ -0,0 +1,101 @@
+use super::*;
+use crate::config::LockedFilePolicy;
+use crate::throttle::Throttle;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// OS errors for a file another process holds exclusively.
+#[cfg(windows)]
+pub(super) const LOCKED_OS_ERRORS: &[i32] = &[
+    32, // ERROR_SHARING_VIOLATION
+    33, // ERROR_LOCK_VIOLATION
+];
+#[cfg(unix)]
+pub(super) const LOCKED_OS_ERRORS: &[i32] = &[libc::EBUSY];
+#[cfg(not(any(windows, unix)))]
+pub(super) const LOCKED_OS_ERRORS: &[i32] = &[];
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+
+/// Retry-then-fail-or-skip handling of locked source files for one backup.
+/// Every source read goes through here, so it also paces them to
+/// `io_throttle_mbps`.
+#[derive(Debug, Default)]
+pub(super) struct LockedFiles {
+    retries: usize,
+    skip: bool,
+    throttle: Option<Throttle>,
+    /// Relative paths left out of this backup because they stayed locked.
+    pub(super) skipped: Vec<String>,
+}
+
+impl LockedFiles {
+    pub(super) fn new(resolved: &ResolvedSourceConfig) -> Self {
+        Self {
+            retries: resolved.locked_file_retries,
+            skip: resolved.locked_file_policy == LockedFilePolicy::Skip,
+            // Rejected by validation when not positive.
+            throttle: resolved
+                .io_throttle_mbps
+                .and_then(|mbps| Throttle::from_mb_per_sec(mbps).ok()),
+            skipped: Vec::new(),
+        }
+    }
+
+    /// Run `read` on a source file, retrying with backoff while the file is
+    /// locked. `None` means the file stayed locked and was skipped.
+    pub(super) fn read<T>(
+        &mut self,
+        path: &Path,
+        relative: &str,
+        mut read: impl FnMut() -> Result<T>,
+    ) -> Result<Option<T>> {
+        let mut delay = INITIAL_BACKOFF;
+        let mut attempt = 0;
+        loop {
+            let err = match read() {
+                Ok(value) => {
+                    if let Some(ref mut throttle) = self.throttle {
+                        throttle.consume(fs::metadata(path).map(|m| m.len()).unwrap_or(0));
+                    }
+                    return Ok(Some(value));
+                }
+                Err(err) if is_locked(&err) => err,
+                Err(err) => return Err(err),
+            };
+            if attempt < self.retries {
+                attempt += 1;
+                info!(
+                    "{:?} is locked, retrying in {} ms ({}/{})",
+                    path,
+                    delay.as_millis(),
+                    attempt,
+                    self.retries
+                );
+                std::thread::sleep(delay);
+                delay = (delay * 2).min(MAX_BACKOFF);
+                continue;
+            }
+            if !self.skip {
+                return Err(err.context(format!("{:?} is locked by another process", path)));
+            }
+            warn!("Skipping locked file {:?}: {:#}", path, err);
+            // Forced fulls rescan the source, so a file can come up twice.
+            if !self.skipped.iter().any(|skipped| skipped == relative) {
+                self.skipped.push(relative.to_string());
+            }
+            return Ok(None);
+        }
+    }
+}
+
+pub(super) fn is_locked(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<io::Error>())
+        .any(|e| {
+            e.raw_os_error()
+                .is_some_and(|code| LOCKED_OS_ERRORS.contains(&code))
+        })
+}
@@ -0,0 +1,751 @@
+use super::*;
+use crate::archive::{self, ArchiveReader};
+use crate::config::{
+    BackupHistoryEntry, BackupHistoryType, BackupOrigin, PORTABLE_KEY_FORMAT, SourceMetadata,
+};
+use crate::error::Error;
+use crate::signing::{self, SigningKey};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use log::warn;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+impl From<&BackupType> for BackupHistoryType {
+    fn from(backup_type: &BackupType) -> Self {
+        match backup_type {
+            BackupType::Full => BackupHistoryType::Full,
+            BackupType::Incremental => BackupHistoryType::Incremental,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct BackupDirEntry {
+    backup_name: String,
+    backup_type: BackupHistoryType,
+    created_at: DateTime<Utc>,
+    backup_path: PathBuf,
+}
+
+impl BackupManager {
+    pub fn load_source_metadata(metadata_path: &Path) -> SourceMetadata {
+        let mut metadata = Self::read_source_metadata(metadata_path);
+        Self::migrate_file_hash_keys(&mut metadata);
+        metadata
+    }
+
+    fn read_source_metadata(metadata_path: &Path) -> SourceMetadata {
+        if !metadata_path.exists() {
+            return SourceMetadata::default();
+        }
+
+        match fs::read_to_string(metadata_path) {
+            Ok(content) => match serde_json::from_str::<SourceMetadata>(&content) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    warn!(
+                        "Failed to parse metadata file {:?}, using default: {}",
+                        metadata_path, e
+                    );
+                    SourceMetadata::default()
+                }
+            },
+            Err(e) => {
+                warn!(
+                    "Failed to read metadata file {:?}, using default: {}",
+                    metadata_path, e
+                );
+                SourceMetadata::default()
+            }
+        }
+    }
+
+    /// Record `source` on this machine as the writer of a backup dir's
+    /// metadata. A dir last written for another source is refused: its
+    /// file hashes and retention belong to that source. Another machine
+    /// backing up the same source path is only warned about.
+    pub(super) fn claim_backup_origin(
+        backup_dir: &Path,
+        metadata: &mut SourceMetadata,
+        source: &SourceConfig,
+        resolved: &ResolvedSourceConfig,
+    ) -> Result<()> {
+        let origin = BackupOrigin {
+            host: resolved.host.clone(),
+            source_dir: source.source_dir.clone(),
+        };
+        match &metadata.origin {
+            Some(previous) if previous.source_dir != origin.source_dir => {
+                return Err(Error::ForeignBackupDir {
+                    backup_dir: backup_dir.to_path_buf(),
+                    source_dir: previous.source_dir.clone(),
+                    host: previous.host.clone(),
+                }
+                .into());
+            }
+            Some(previous) if previous.host != origin.host => warn!(
+                "[{:?}] Last backed up from {}, now from {}; machines sharing a destination \
+                 should use namespace_backups",
+                backup_dir, previous.host, origin.host
+            ),
+            _ => {}
+        }
+        metadata.origin = Some(origin);
+        Ok(())
+    }
+
+    /// Rewrite `\\`-separated keys of metadata written on Windows before keys
+    /// were portable. Unix names containing a backslash are rewritten too;
+    /// that only makes the file look changed once.
+    pub(super) fn migrate_file_hash_keys(metadata: &mut SourceMetadata) {
+        if metadata.key_format >= PORTABLE_KEY_FORMAT {
+            return;
+        }
+        metadata.file_hashes = std::mem::take(&mut metadata.file_hashes)
+            .into_iter()
+            .map(|(key, hash)| (key.replace('\\', "/"), hash))
+            .collect();
+        metadata.key_format = PORTABLE_KEY_FORMAT;
+    }
+
+    /// Write metadata.json via a temp file + rename so a crash mid-write
+    /// never leaves a truncated file behind. With a signing key the file is
+    /// signed into `metadata.json.sig`; without one a stale signature is
+    /// removed.
+    pub(crate) fn save_source_metadata(
+        metadata_path: &Path,
+        metadata: &SourceMetadata,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<()> {
+        Self::save_source_metadata_with(metadata_path, metadata, &Durability::Fast, signing_key)
+    }
+
+    /// With `Durability::Fsync` the new content is flushed before it
+    /// replaces the old file and the rename itself is flushed afterwards.
+    pub(super) fn save_source_metadata_with(
+        metadata_path: &Path,
+        metadata: &SourceMetadata,
+        durability: &Durability,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<()> {
+        let content = serde_json::to_string_pretty(metadata)?;
+        let tmp_path = metadata_path.with_extension("json.tmp");
+        fs::write(&tmp_path, &content)
+            .with_context(|| format!("Failed to write metadata file: {:?}", tmp_path))?;
+        let fsync = matches!(durability, Durability::Fsync);
+        if fsync {
+            Self::sync_file(&tmp_path)?;
+        }
+        fs::rename(&tmp_path, metadata_path)
+            .with_context(|| format!("Failed to replace metadata file: {:?}", metadata_path))?;
+
+        // A crash between the rename and this write leaves a mismatching
+        // signature, which the next start treats like any other drift.
+        let signature_path = metadata_path.with_extension("json.sig");
+        match signing_key {
+            Some(key) => {
+                fs::write(&signature_path, key.sign(content.as_bytes())).with_context(|| {
+                    format!("Failed to write metadata signature: {:?}", signature_path)
+                })?;
+                if fsync {
+                    Self::sync_file(&signature_path)?;
+                }
+            }
+            None if signature_path.exists() => fs::remove_file(&signature_path)
+                .with_context(|| format!("Failed to remove {:?}", signature_path))?,
+            None => {}
+        }
+        if fsync && let Some(parent) = metadata_path.parent() {
+            Self::sync_dir(parent)?;
+        }
+        Ok(())
+    }
+
+    /// Check `metadata.json` against its detached signature.
+    pub(crate) fn verify_metadata_signature(backup_dir: &Path, key: &SigningKey) -> Result<()> {
+        let metadata_path = backup_dir.join("metadata.json");
+        let content = fs::read(&metadata_path)
+            .with_context(|| format!("Failed to read {:?}", metadata_path))?;
+        let signature_path = signing::metadata_signature_path(backup_dir);
+        let Ok(signature) = fs::read_to_string(&signature_path) else {
+            return Err(anyhow::anyhow!(
+                "metadata.json is not signed: {:?} is missing",
+                signature_path
+            ));
+        };
+        if !key.verify(&content, &signature) {
+            return Err(anyhow::anyhow!(
+                "metadata.json signature mismatch in {:?}: the file was modified outside ardiex",
+                backup_dir
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check one backup point against the signature recorded in its history
+    /// entry. Points written before signing was enabled carry none and are
+    /// accepted, since the signed metadata.json vouches for that.
+    pub(crate) fn verify_point_signature(
+        entry: &BackupHistoryEntry,
+        backup_path: &Path,
+        key: &SigningKey,
+    ) -> Result<()> {
+        let Some(signature) = entry.signature.as_deref() else {
+            return Ok(());
+        };
+        let checksum = Self::calculate_incremental_backup_checksum(backup_path)?;
+        if !key.verify_point(&entry.backup_name, &checksum, signature) {
+            return Err(anyhow::anyhow!(
+                "backup {} does not match its signature: its content was modified",
+                entry.backup_name
+            ));
+        }
+        Ok(())
+    }
+
+    fn backup_history_type_from_name(backup_name: &str) -> Option<BackupHistoryType> {
+        point_name::parse(backup_name).map(|name| {
+            if name.is_full {
+                BackupHistoryType::Full
+            } else {
+                BackupHistoryType::Incremental
+            }
+        })
+    }
+
+    pub(crate) fn parse_backup_created_at(backup_name: &str) -> Option<DateTime<Utc>> {
+        let ts = point_name::parse(backup_name)?.timestamp;
+
+        for fmt in ["%Y%m%d_%H%M%S%3f", "%Y%m%d_%H%M%S"] {
+            if let Ok(naive) = NaiveDateTime::parse_from_str(ts, fmt) {
+                return Some(DateTime::<Utc>::from_naive_utc_and_offset(naive, Utc));
+            }
+        }
+
+        None
+    }
+
+    /// File count and total size of a backup point. For archives these are
+    /// the stored files' original sizes, matching the directory layout.
+    pub(super) fn collect_backup_dir_stats(backup_path: &Path) -> Result<(usize, u64)> {
+        if backup_path.is_file() {
+            let reader = ArchiveReader::open_with_env(backup_path)?;
+            let entries: Vec<_> = reader
+                .entries()
+                .iter()
+                .filter(|e| !attrs::is_attributes_file(&e.relative_path()))
+                .collect();
+            return Ok((entries.len(), entries.iter().map(|e| e.size).sum()));
+        }
+        // Dedup points hold only an index; count the files it describes.
+        if let Some(index) = dedup::read_index(backup_path)? {
+            return Ok((
+                index.files.len(),
+                index.files.values().map(|f| f.size).sum(),
+            ));
+        }
+
+        let mut files = 0usize;
+        let mut bytes = 0u64;
+
+        for entry in WalkDir::new(backup_path).into_iter() {
+            let entry = entry?;
+            if entry.file_type().is_file()
+                && !attrs::is_attributes_file(entry.path().strip_prefix(backup_path)?)
+            {
+                files += 1;
+                bytes += entry.metadata()?.len();
+            }
+        }
+
+        Ok((files, bytes))
+    }
+
+    pub(super) fn calculate_incremental_backup_checksum(backup_path: &Path) -> Result<String> {
+        if backup_path.is_file() {
+            return Self::calculate_archive_checksum(backup_path);
+        }
+
+        let mut files = Vec::new();
+        for entry in WalkDir::new(backup_path).into_iter() {
+            let entry = entry?;
+            if entry.file_type().is_file() {
+                files.push(entry.path().to_path_buf());
+            }
+        }
+
+        files.sort_by(|a, b| {
+            let rel_a = a.strip_prefix(backup_path).unwrap_or(a);
+            let rel_b = b.strip_prefix(backup_path).unwrap_or(b);
+            rel_a.cmp(rel_b)
+        });
+
+        let mut hasher = Sha256::new();
+        for file_path in files {
+            let relative_path = file_path
+                .strip_prefix(backup_path)
+                .unwrap_or(file_path.as_path());
+            let file_hash = Self::calculate_file_hash(&file_path)?;
+            let file_size = fs::metadata(&file_path)?.len();
+
+            hasher.update(relative_path.to_string_lossy().as_bytes());
+            hasher.update([0]);
+            hasher.update(file_hash.as_bytes());
+            hasher.update([0]);
+            hasher.update(file_size.to_le_bytes());
+        }
+
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Same digest as the directory form, taken from the archive manifest so
+    /// an archive and its staging directory produce identical checksums.
+    fn calculate_archive_checksum(archive_path: &Path) -> Result<String> {
+        let reader = ArchiveReader::open_with_env(archive_path)?;
+        let mut files: Vec<_> = reader
+            .entries()
+            .iter()
+            .map(|entry| (entry.relative_path(), entry))
+            .collect();
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut hasher = Sha256::new();
+        for (relative_path, entry) in files {
+            hasher.update(relative_path.to_string_lossy().as_bytes());
+            hasher.update([0]);
+            hasher.update(entry.sha256.as_bytes());
+            hasher.update([0]);
+            hasher.update(entry.size.to_le_bytes());
+        }
+
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    fn scan_backup_entries_from_disk(backup_dir: &Path) -> Result<Vec<BackupDirEntry>> {
+        if !backup_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(backup_dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let Some(backup_name) = archive::backup_point_name(&path) else {
+                continue;
+            };
+            let Some(backup_type) = Self::backup_history_type_from_name(&backup_name) else {
+                continue;
+            };
+
+            let created_at = Self::parse_backup_created_at(&backup_name).ok_or_else(|| {
+                anyhow::anyhow!("Invalid backup directory timestamp format: {}", backup_name)
+            })?;
+
+            entries.push(BackupDirEntry {
+                backup_name,
+                backup_type,
+                created_at,
+                backup_path: path,
+            });
+        }
+
+        entries.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.backup_name.cmp(&b.backup_name))
+        });
+
+        Ok(entries)
+    }
+
+    fn build_history_from_entries(entries: &[BackupDirEntry]) -> Result<Vec<BackupHistoryEntry>> {
+        let mut history = Vec::with_capacity(entries.len());
+
+        for entry in entries {
+            let (files_backed_up, bytes_processed) =
+                Self::collect_backup_dir_stats(&entry.backup_path)?;
+            let inc_checksum = if matches!(&entry.backup_type, BackupHistoryType::Incremental) {
+                Some(Self::calculate_incremental_backup_checksum(
+                    &entry.backup_path,
+                )?)
+            } else {
+                None
+            };
+
+            history.push(BackupHistoryEntry {
+                backup_name: entry.backup_name.clone(),
+                backup_type: entry.backup_type.clone(),
+                created_at: entry.created_at,
+                files_backed_up,
+                bytes_processed,
+                inc_checksum,
+                context: None,
+                resource_usage: None,
+                forwarded_at: None,
+                signature: None,
+                pinned: false,
+                tags: Vec::new(),
+                reverse_deltas: false,
+            });
+        }
+
+        Ok(history)
+    }
+
+    fn refresh_metadata_markers(metadata: &mut SourceMetadata) {
+        metadata.backup_history.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.backup_name.cmp(&b.backup_name))
+        });
+
+        metadata.last_backup = metadata.backup_history.last().map(|entry| entry.created_at);
+        metadata.last_full_backup = metadata
+            .backup_history
+            .iter()
+            .rev()
+            .find(|entry| matches!(entry.backup_type, BackupHistoryType::Full))
+            .map(|entry| entry.created_at);
+    }
+
+    pub(crate) fn synchronize_metadata_history_with_disk(
+        backup_dir: &Path,
+        metadata: &mut SourceMetadata,
+    ) -> Result<()> {
+        let entries = Self::scan_backup_entries_from_disk(backup_dir)?;
+        let previous_history = std::mem::take(&mut metadata.backup_history);
+        metadata.backup_history = Self::build_history_from_entries(&entries)?;
+        Self::carry_over_history_annotations(&previous_history, &mut metadata.backup_history);
+        Self::refresh_metadata_markers(metadata);
+        Ok(())
+    }
+
+    /// Drop file hashes that no point from the latest full on records, such
+    /// as keys left behind by a point deleted by hand, so the next backup
+    /// copies those files again instead of assuming they are stored.
+    /// Nothing is dropped when a point of that chain has no readable
+    /// manifest, since what it holds is then unknown. Returns how many
+    /// hashes were dropped.
+    pub(super) fn prune_stale_file_hashes(
+        backup_dir: &Path,
+        metadata: &mut SourceMetadata,
+    ) -> Result<usize> {
+        let entries = Self::scan_backup_entries_from_disk(backup_dir)?;
+        let Some(chain_start) = entries
+            .iter()
+            .rposition(|entry| matches!(entry.backup_type, BackupHistoryType::Full))
+        else {
+            return Ok(0);
+        };
+
+        let mut recorded = HashSet::new();
+        for entry in &entries[chain_start..] {
+            match Self::read_manifest_keys(&entry.backup_path) {
+                Ok(Some(keys)) => recorded.extend(keys),
+                Ok(None) => {
+                    debug!(
+                        "[{:?}] {} has no attribute manifest, keeping all file hashes",
+                        backup_dir, entry.backup_name
+                    );
+                    return Ok(0);
+                }
+                Err(e) => {
+                    debug!(
+                        "[{:?}] Cannot read the manifest of {}, keeping all file hashes: {:#}",
+                        backup_dir, entry.backup_name, e
+                    );
+                    return Ok(0);
+                }
+            }
+        }
+
+        let before = metadata.file_hashes.len();
+        metadata.file_hashes.retain(|key, _| recorded.contains(key));
+        let pruned = before - metadata.file_hashes.len();
+        if pruned > 0 {
+            info!(
+                "[{:?}] Pruned {} file hashes no backup point records",
+                backup_dir, pruned
+            );
+        }
+        Ok(pruned)
+    }
+
+    /// Keys of the attribute manifest of a directory or archive point,
+    /// `None` for points written without one.
+    fn read_manifest_keys(point_path: &Path) -> Result<Option<Vec<String>>> {
+        let content = if point_path.is_file() {
+            let mut reader = ArchiveReader::open_with_env(point_path)?;
+            match reader.find(Path::new(attrs::ATTRIBUTES_FILE_NAME)).cloned() {
+                Some(entry) => reader.read_entry(&entry)?,
+                None => return Ok(None),
+            }
+        } else {
+            let path = point_path.join(attrs::ATTRIBUTES_FILE_NAME);
+            if !path.is_file() {
+                return Ok(None);
+            }
+            fs::read(&path).with_context(|| format!("Failed to read {:?}", path))?
+        };
+        Ok(Some(attrs::parse_manifest(&content)?.into_keys().collect()))
+    }
+
+    /// Disk scans cannot recover annotations that only live in metadata.json
+    /// (captured source context, resource usage, forwarding state, pins,
+    /// tags, reverse deltas), so copy them over by name.
+    fn carry_over_history_annotations(
+        previous: &[BackupHistoryEntry],
+        rebuilt: &mut [BackupHistoryEntry],
+    ) {
+        for entry in rebuilt.iter_mut() {
+            if let Some(prev) = previous
+                .iter()
+                .find(|prev| prev.backup_name == entry.backup_name)
+            {
+                entry.context = prev.context.clone();
+                entry.resource_usage = prev.resource_usage.clone();
+                entry.forwarded_at = prev.forwarded_at;
+                entry.signature = prev.signature.clone();
+                entry.pinned = prev.pinned;
+                entry.tags = prev.tags.clone();
+                entry.reverse_deltas = prev.reverse_deltas;
+            }
+        }
+    }
+
+    /// Record a freshly written backup. `inc_checksum` is computed here from
+    /// the backup directory for incremental entries, and with a signing key
+    /// the same content digest is signed for every entry.
+    pub(crate) fn append_backup_history_entry(
+        metadata: &mut SourceMetadata,
+        mut entry: BackupHistoryEntry,
+        backup_path: &Path,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<()> {
+        Self::seal_history_entry(&mut entry, backup_path, signing_key)?;
+
+        metadata
+            .backup_history
+            .retain(|existing| existing.backup_name != entry.backup_name);
+        metadata.backup_history.push(entry);
+
+        Self::refresh_metadata_markers(metadata);
+        Ok(())
+    }
+
+    /// Recompute the stored size, `inc_checksum` and signature of a recorded
+    /// point whose stored files were rewritten, e.g. into reverse deltas.
+    pub(super) fn reseal_history_entry(
+        metadata: &mut SourceMetadata,
+        backup_name: &str,
+        backup_path: &Path,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<()> {
+        if let Some(entry) = metadata
+            .backup_history
+            .iter_mut()
+            .find(|entry| entry.backup_name == backup_name)
+        {
+            let (files, bytes) = Self::collect_backup_dir_stats(backup_path)?;
+            entry.files_backed_up = files;
+            entry.bytes_processed = bytes;
+            Self::seal_history_entry(entry, backup_path, signing_key)?;
+        }
+        Ok(())
+    }
+
+    /// Set `inc_checksum` for incrementals and, with a signing key, sign
+    /// the same content digest for every entry.
+    fn seal_history_entry(
+        entry: &mut BackupHistoryEntry,
+        backup_path: &Path,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<()> {
+        let is_incremental = matches!(&entry.backup_type, BackupHistoryType::Incremental);
+        let checksum = if is_incremental || signing_key.is_some() {
+            Some(Self::calculate_incremental_backup_checksum(backup_path)?)
+        } else {
+            None
+        };
+        entry.signature = signing_key
+            .zip(checksum.as_deref())
+            .map(|(key, checksum)| key.sign_point(&entry.backup_name, checksum));
+        entry.inc_checksum = checksum.filter(|_| is_incremental);
+        Ok(())
+    }
+
+    /// Apply `update` to the history entry of `point` (a backup name or its
+    /// timestamp) under the backup dir's lock and save metadata.json,
+    /// returning the point's name.
+    pub(super) fn update_history_entry(
+        backup_dir: &Path,
+        point: &str,
+        operation: &str,
+        signing_key: Option<&SigningKey>,
+        update: impl FnOnce(&mut BackupHistoryEntry) -> Result<()>,
+    ) -> Result<String> {
+        let metadata_path = backup_dir.join("metadata.json");
+        if !metadata_path.is_file() {
+            return Err(anyhow::anyhow!(
+                "{:?} has no metadata.json; is it a backup directory?",
+                backup_dir
+            ));
+        }
+
+        let _lock = BackupDirLock::acquire(backup_dir, operation)?;
+        let mut metadata = Self::load_source_metadata(&metadata_path);
+        Self::synchronize_metadata_history_with_disk(backup_dir, &mut metadata)?;
+        let entry = metadata
+            .backup_history
+            .iter_mut()
+            .find(|entry| {
+                entry.backup_name == point
+                    || point_name::parse(&entry.backup_name)
+                        .is_some_and(|name| name.timestamp == point)
+            })
+            .ok_or_else(|| anyhow::anyhow!("No backup point {} in {:?}", point, backup_dir))?;
+        update(entry)?;
+        let backup_name = entry.backup_name.clone();
+        Self::save_source_metadata(&metadata_path, &metadata, signing_key)?;
+        Ok(backup_name)
+    }
+
+    /// With a signing key, metadata.json and every signed point must also
+    /// match their signatures, so deliberate edits are caught along with
+    /// accidental drift.
+    pub(crate) fn validate_backup_metadata_history(
+        backup_dir: &Path,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<()> {
+        let disk_entries = Self::scan_backup_entries_from_disk(backup_dir)?;
+        if disk_entries.is_empty() {
+            return Ok(());
+        }
+
+        let metadata_path = backup_dir.join("metadata.json");
+        if !metadata_path.exists() {
+            return Err(anyhow::anyhow!(
+                "metadata.json is missing while backup directories exist: {:?}",
+                backup_dir
+            ));
+        }
+
+        if let Some(key) = signing_key {
+            Self::verify_metadata_signature(backup_dir, key)?;
+        }
+
+        let metadata = Self::load_source_metadata(&metadata_path);
+        if metadata.backup_history.is_empty() {
+            return Err(anyhow::anyhow!(
+                "metadata.backup_history is empty while backup directories exist: {:?}",
+                backup_dir
+            ));
+        }
+
+        let mut metadata_history = metadata.backup_history.clone();
+        metadata_history.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.backup_name.cmp(&b.backup_name))
+        });
+
+        let disk_history = Self::build_history_from_entries(&disk_entries)?;
+        if metadata_history.len() != disk_history.len() {
+            return Err(anyhow::anyhow!(
+                "metadata history count mismatch: metadata={}, disk={}",
+                metadata_history.len(),
+                disk_history.len()
+            ));
+        }
+
+        for (index, (meta, disk)) in metadata_history.iter().zip(disk_history.iter()).enumerate() {
+            if meta.backup_name != disk.backup_name {
+                return Err(anyhow::anyhow!(
+                    "metadata history mismatch at index {}: backup_name metadata='{}', disk='{}'",
+                    index,
+                    meta.backup_name,
+                    disk.backup_name
+                ));
+            }
+            if meta.backup_type != disk.backup_type {
+                return Err(anyhow::anyhow!(
+                    "metadata history mismatch at index {}: backup_type metadata='{:?}', disk='{:?}'",
+                    index,
+                    meta.backup_type,
+                    disk.backup_type
+                ));
+            }
+            if meta.files_backed_up != disk.files_backed_up {
+                return Err(anyhow::anyhow!(
+                    "metadata history mismatch at index {}: files_backed_up metadata={}, disk={}",
+                    index,
+                    meta.files_backed_up,
+                    disk.files_backed_up
+                ));
+            }
+            if meta.bytes_processed != disk.bytes_processed {
+                return Err(anyhow::anyhow!(
+                    "metadata history mismatch at index {}: bytes_processed metadata={}, disk={}",
+                    index,
+                    meta.bytes_processed,
+                    disk.bytes_processed
+                ));
+            }
+            if meta.inc_checksum.as_deref() != disk.inc_checksum.as_deref() {
+                return Err(anyhow::anyhow!(
+                    "metadata history mismatch at index {}: inc_checksum metadata={:?}, disk={:?}",
+                    index,
+                    meta.inc_checksum.as_deref(),
+                    disk.inc_checksum.as_deref()
+                ));
+            }
+        }
+
+        if let Some(key) = signing_key {
+            for (meta, disk) in metadata_history.iter().zip(disk_entries.iter()) {
+                Self::verify_point_signature(meta, &disk.backup_path, key)?;
+            }
+        }
+
+        let mut seen_full = false;
+        for entry in &metadata_history {
+            match entry.backup_type {
+                BackupHistoryType::Full => seen_full = true,
+                BackupHistoryType::Incremental if !seen_full => {
+                    return Err(anyhow::anyhow!(
+                        "incremental backup appears before any full backup: {}",
+                        entry.backup_name
+                    ));
+                }
+                BackupHistoryType::Incremental => {}
+            }
+        }
+
+        let expected_last_full = metadata_history
+            .iter()
+            .rev()
+            .find(|entry| matches!(entry.backup_type, BackupHistoryType::Full))
+            .map(|entry| entry.created_at);
+        let expected_last_backup = metadata_history.last().map(|entry| entry.created_at);
+
+        if metadata.last_full_backup != expected_last_full {
+            return Err(anyhow::anyhow!(
+                "metadata.last_full_backup mismatch: metadata={:?}, expected={:?}",
+                metadata.last_full_backup,
+                expected_last_full
+            ));
+        }
+        if metadata.last_backup != expected_last_backup {
+            return Err(anyhow::anyhow!(
+                "metadata.last_backup mismatch: metadata={:?}, expected={:?}",
+                metadata.last_backup,
+                expected_last_backup
+            ));
+        }
+
+        Ok(())
+    }
+}
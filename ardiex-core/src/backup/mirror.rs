@@ -0,0 +1,196 @@
+use super::*;
+use std::collections::HashSet;
+
+/// Subdirectory of a mirror destination holding the replica of the source.
+pub(super) const MIRROR_DIR: &str = "current";
+
+/// Subdirectory of a mirror destination holding `<time>/<path>` copies of
+/// the files a run replaced or removed, with `mirror_versions` on.
+pub(super) const VERSIONS_DIR: &str = "versions";
+
+/// What one mirror run changed in the replica.
+#[derive(Debug, Default)]
+pub(super) struct MirrorOutcome {
+    pub files_added: usize,
+    pub files_changed: usize,
+    pub files_removed: usize,
+    pub bytes_copied: u64,
+}
+
+/// Which files a mirror run takes out of the replica.
+pub(super) enum MirrorStale<'a> {
+    /// Incremental run: the files gone from the source since the last run.
+    Removed(&'a [String]),
+    /// First or forced-full run: everything not in this set, which also
+    /// clears files added to the replica by hand.
+    AllBut(HashSet<String>),
+}
+
+impl BackupManager {
+    /// The replica of a mirror-mode backup dir, if it has one.
+    pub(crate) fn mirror_dir(backup_dir: &Path) -> Option<PathBuf> {
+        let mirror_root = backup_dir.join(MIRROR_DIR);
+        mirror_root.is_dir().then_some(mirror_root)
+    }
+
+    /// True when the replica of `backup_dir` holds at least one file.
+    pub(super) fn mirror_has_files(backup_dir: &Path) -> bool {
+        walkdir::WalkDir::new(backup_dir.join(MIRROR_DIR))
+            .min_depth(1)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .any(|entry| !entry.file_type().is_dir())
+    }
+
+    /// Bring `<backup_dir>/current` in line with the source: copy `changed`,
+    /// each through a temp file so the replica never holds a half-written
+    /// file, then delete the `stale` files. With `mirror_versions`, replaced
+    /// and removed files are moved under `versions/<time>/` instead.
+    pub(super) fn mirror_changes(
+        source_dir: &Path,
+        backup_dir: &Path,
+        resolved: &ResolvedSourceConfig,
+        changed: &[PathBuf],
+        stale: MirrorStale,
+        locked: &mut LockedFiles,
+        cancel: &Cancellation,
+    ) -> Result<MirrorOutcome> {
+        let mirror_root = backup_dir.join(MIRROR_DIR);
+        fs::create_dir_all(&mirror_root)
+            .with_context(|| format!("Failed to create mirror directory {:?}", mirror_root))?;
+        let versions_root = resolved.mirror_versions.then(|| {
+            backup_dir
+                .join(VERSIONS_DIR)
+                .join(Utc::now().format("%Y%m%d_%H%M%S%3f").to_string())
+        });
+        let temp_file = backup_dir.join(format!("{}mirror.tmp", PARTIAL_BACKUP_PREFIX));
+        let mut outcome = MirrorOutcome::default();
+        let mut progress = Progress::new(
+            "Mirror progress",
+            backup_dir,
+            changed.len(),
+            changed
+                .iter()
+                .filter_map(|path| fs::metadata(path).ok())
+                .map(|m| m.len())
+                .sum(),
+        );
+
+        for file_path in changed {
+            cancel.check()?;
+            let relative = file_path.strip_prefix(source_dir)?;
+            progress.start_file(relative);
+            let source_metadata = fs::metadata(file_path)?;
+            let mut file_progress = progress.large_file(relative, source_metadata.len());
+            let copied = locked.read(file_path, &attrs::manifest_key(relative), || {
+                reflink::copy_file(file_path, &temp_file, file_progress.as_mut())
+            })?;
+            if copied.is_none() {
+                continue;
+            }
+            FileAttributes::capture(&source_metadata).apply(&temp_file, false)?;
+
+            let target = mirror_root.join(relative);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            if fs::symlink_metadata(&target).is_ok() {
+                Self::retire_mirrored_file(&target, relative, versions_root.as_deref())?;
+                outcome.files_changed += 1;
+            } else {
+                outcome.files_added += 1;
+            }
+            fs::rename(&temp_file, &target)
+                .with_context(|| format!("Failed to update mirrored file {:?}", target))?;
+            outcome.bytes_copied += source_metadata.len();
+            progress.finish_file(source_metadata.len());
+        }
+
+        let stale: Vec<PathBuf> = match stale {
+            MirrorStale::Removed(keys) => keys.iter().map(|key| attrs::key_path(key)).collect(),
+            MirrorStale::AllBut(keep) => {
+                let mut stale = Vec::new();
+                for entry in walkdir::WalkDir::new(&mirror_root).min_depth(1) {
+                    let entry = entry?;
+                    if entry.file_type().is_dir() {
+                        continue;
+                    }
+                    let relative = entry.path().strip_prefix(&mirror_root)?;
+                    if !keep.contains(&attrs::manifest_key(relative)) {
+                        stale.push(relative.to_path_buf());
+                    }
+                }
+                stale
+            }
+        };
+        for relative in &stale {
+            let target = mirror_root.join(relative);
+            if fs::symlink_metadata(&target).is_err() {
+                continue;
+            }
+            Self::retire_mirrored_file(&target, relative, versions_root.as_deref())?;
+            Self::remove_empty_parents(&target, &mirror_root);
+            debug!("Removed from mirror: {:?}", relative);
+            outcome.files_removed += 1;
+        }
+
+        if resolved.mirror_versions {
+            Self::prune_mirror_versions(backup_dir, resolved.max_backups);
+        }
+        Ok(outcome)
+    }
+
+    /// Move a mirrored file that is about to be replaced or removed into
+    /// the run's versions dir, or delete it when versions are off.
+    fn retire_mirrored_file(
+        target: &Path,
+        relative: &Path,
+        versions_root: Option<&Path>,
+    ) -> Result<()> {
+        match versions_root {
+            Some(versions_root) => {
+                let kept = versions_root.join(relative);
+                if let Some(parent) = kept.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::rename(target, &kept)
+                    .with_context(|| format!("Failed to keep old version of {:?}", target))
+            }
+            None => fs::remove_file(target)
+                .with_context(|| format!("Failed to remove mirrored file {:?}", target)),
+        }
+    }
+
+    /// Remove the directories left empty above a removed file, up to (not
+    /// including) `root`.
+    fn remove_empty_parents(path: &Path, root: &Path) {
+        let mut dir = path.parent();
+        while let Some(current) = dir {
+            if current == root || fs::remove_dir(current).is_err() {
+                break;
+            }
+            dir = current.parent();
+        }
+    }
+
+    /// Keep the newest `keep` runs' worth of old versions.
+    fn prune_mirror_versions(backup_dir: &Path, keep: usize) {
+        let versions = backup_dir.join(VERSIONS_DIR);
+        let Ok(entries) = fs::read_dir(&versions) else {
+            return;
+        };
+        let mut runs: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect();
+        runs.sort();
+        let excess = runs.len().saturating_sub(keep);
+        for run in &runs[..excess] {
+            match fs::remove_dir_all(run) {
+                Ok(()) => info!("Removed old mirror versions: {:?}", run),
+                Err(e) => warn!("Failed to remove old mirror versions {:?}: {}", run, e),
+            }
+        }
+    }
+}
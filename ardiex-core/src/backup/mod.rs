@@ -0,0 +1,1354 @@
+use crate::archive;
+use crate::attrs::{self, AttributeManifest, FileAttributes, StoredAs};
+use crate::change_journal;
+use crate::config::{
+    BackupConfig, BackupFormat, BackupHistoryEntry, BackupMode, DeltaLayout, Durability,
+    FileStrategy, ResolvedSourceConfig, ResourceUsage, SourceConfig,
+};
+use crate::dedup::{self, ChunkStore, DedupIndex};
+use crate::delta;
+use crate::error::Error;
+use crate::history::{self, RunRecord};
+use crate::lock::BackupDirLock;
+use crate::logger;
+use crate::longpath;
+use crate::metrics;
+use crate::notification::{self, HealthcheckEvent};
+use crate::parity;
+use crate::patterns::{PathFilter, PatternMap};
+use crate::point_name;
+use crate::progress::Progress;
+use crate::reflink::{self, CopyMethod};
+use crate::repo;
+use crate::signing::{self, SigningKey};
+use crate::snapshot::SourceSnapshot;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use log::{debug, error, info, warn};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::task;
+
+mod anomaly;
+mod check;
+mod context;
+mod file_ops;
+mod forward;
+mod gc;
+mod hash_journal;
+mod locked;
+mod metadata;
+mod mirror;
+mod pin;
+mod prune;
+mod repair;
+mod replicate;
+mod report;
+mod reverse;
+mod scrub;
+mod seed;
+mod space;
+mod stats;
+mod tag;
+mod usage;
+mod validation;
+
+pub(crate) use space::available_space;
+
+use file_ops::PartialBackupDir;
+use locked::LockedFiles;
+use mirror::MirrorStale;
+use prune::RetentionPlan;
+use usage::UsageSnapshot;
+
+#[cfg(test)]
+#[path = "../tests/backup_tests.rs"]
+mod tests;
+
+/// New backups are written under this prefix and renamed to `full_*`/`inc_*`
+/// only once complete, so an interrupted run never looks like a valid point.
+pub(crate) const PARTIAL_BACKUP_PREFIX: &str = "partial_";
+
+#[derive(Debug)]
+pub enum BackupType {
+    Full,
+    Incremental,
+}
+
+#[derive(Debug)]
+pub struct BackupResult {
+    pub backup_dir: PathBuf,
+    pub backup_type: BackupType,
+    pub files_backed_up: usize,
+    /// Of `files_backed_up`, those new since the last backup and those whose
+    /// content changed; the rest were unchanged files of a full backup.
+    pub files_added: usize,
+    pub files_changed: usize,
+    pub bytes_processed: u64,
+    /// Source size of the files this backup stored, whether as copies or
+    /// as deltas.
+    pub changed_bytes: u64,
+    /// Bytes delta files saved over storing those files in full.
+    pub delta_saved_bytes: u64,
+    pub duration_ms: u64,
+    pub context: Option<String>,
+    pub resource_usage: ResourceUsage,
+    /// Whether the backup dir is still above `max_total_size` after
+    /// retention ran; `None` without a quota or when retention did not run.
+    pub over_quota: Option<bool>,
+    /// Source files left out because they stayed locked under
+    /// `locked_file_policy = skip`.
+    pub skipped_files: Vec<String>,
+}
+
+/// Which destinations a run writes, judged by their cron schedules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum ScheduleFilter {
+    /// Every destination (manual backups).
+    #[default]
+    All,
+    /// Destinations with their own `cron_schedule` only once it is due.
+    DestinationSchedules,
+    /// Only destinations whose effective schedule is due (`run --once`).
+    Due,
+}
+
+/// How one run of a source backs up each of its destinations.
+#[derive(Debug, Clone, Default)]
+struct RunOptions {
+    accept_changes: bool,
+    schedule_filter: ScheduleFilter,
+    /// Paths (relative to the source) the watcher reported since the last
+    /// run; only these are rescanned. `None` scans the whole source.
+    changed_paths: Option<BTreeSet<PathBuf>>,
+}
+
+/// Set once a source's `backup_timeout` expires. The backup stops at the
+/// next file it would write, which drops (and removes) its partial dir.
+#[derive(Debug, Clone, Default)]
+struct Cancellation(Arc<AtomicBool>);
+
+impl Cancellation {
+    fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.0.load(Ordering::Relaxed) {
+            return Err(Error::BackupTimeout.into());
+        }
+        Ok(())
+    }
+}
+
+/// Consecutive over-quota runs after which a backup dir is reported.
+const QUOTA_WARN_AFTER_RUNS: u32 = 3;
+/// How long a backup waits for restores and forwards reading its dir
+/// before it gives up.
+const BACKUP_READER_WAIT: Duration = Duration::from_secs(10 * 60);
+
+pub struct BackupManager {
+    config: BackupConfig,
+    force_full_dirs: HashMap<PathBuf, bool>,
+    over_quota_runs: HashMap<PathBuf, u32>,
+    history_path: Option<PathBuf>,
+    accept_changes: bool,
+    schedule_filter: ScheduleFilter,
+    /// Paths the watcher reported per source since its last backup.
+    changed_paths: HashMap<PathBuf, BTreeSet<PathBuf>>,
+    /// When the last full scan of each source that succeeded started.
+    full_scans: HashMap<PathBuf, std::time::Instant>,
+}
+
+impl BackupManager {
+    pub fn new(config: BackupConfig) -> Self {
+        Self {
+            config,
+            force_full_dirs: HashMap::new(),
+            over_quota_runs: HashMap::new(),
+            history_path: None,
+            accept_changes: false,
+            schedule_filter: ScheduleFilter::All,
+            changed_paths: HashMap::new(),
+            full_scans: HashMap::new(),
+        }
+    }
+
+    /// Also append every run's results and failures to `path` (JSONL).
+    pub fn with_history(mut self, path: PathBuf) -> Self {
+        self.history_path = Some(path);
+        self
+    }
+
+    /// Back up even when anomaly detection would pause the run, after the
+    /// user has confirmed the mass change is legitimate.
+    pub fn accept_changes(mut self) -> Self {
+        self.accept_changes = true;
+        self
+    }
+
+    /// Skip destinations with their own `cron_schedule` until it comes due,
+    /// as `run` does; manual backups write every destination.
+    pub fn follow_destination_schedules(mut self) -> Self {
+        self.schedule_filter = ScheduleFilter::DestinationSchedules;
+        self
+    }
+
+    /// Write only destinations whose schedule (their own or the source's)
+    /// has come due since their last backup, skipping sources without
+    /// periodic backups; one scheduled pass for `run --once`.
+    pub fn only_due(mut self) -> Self {
+        self.schedule_filter = ScheduleFilter::Due;
+        self
+    }
+
+    /// Record paths the watcher saw change in `source_dir`, so its next
+    /// backup can rescan just those (see `full_scan_interval_secs`).
+    pub fn note_changed_paths(&mut self, source_dir: &Path, paths: BTreeSet<PathBuf>) {
+        self.changed_paths
+            .entry(source_dir.to_path_buf())
+            .or_default()
+            .extend(paths);
+    }
+
+    /// Paths to rescan for `source`, relative to it, or `None` for a full
+    /// scan. The reported paths only suffice when every destination was
+    /// brought up to date by a full scan in this process within
+    /// `full_scan_interval_secs` (the watcher may have missed changes
+    /// before it started) and none keeps its own schedule, which would
+    /// leave it behind on changes reported while it was not due.
+    fn take_changed_paths(
+        &mut self,
+        source: &SourceConfig,
+        resolved: &ResolvedSourceConfig,
+    ) -> Option<BTreeSet<PathBuf>> {
+        let changed = self.changed_paths.remove(&source.source_dir)?;
+        let recent = self
+            .full_scans
+            .get(&source.source_dir)
+            .is_some_and(|at| at.elapsed().as_secs() < resolved.full_scan_interval_secs);
+        if !recent
+            || self.schedule_filter == ScheduleFilter::Due
+            || !source.source_dir.is_dir()
+            || source.backup_dirs.iter().any(|d| d.cron_schedule.is_some())
+        {
+            return None;
+        }
+        let extended = longpath::extended(&source.source_dir);
+        let mut relative = BTreeSet::new();
+        for path in changed {
+            // Reported under another name (e.g. resolved symlinks).
+            let Ok(rel) = path
+                .strip_prefix(&extended)
+                .or_else(|_| path.strip_prefix(&source.source_dir))
+            else {
+                return None;
+            };
+            if rel.as_os_str().is_empty() {
+                return None;
+            }
+            relative.insert(rel.to_path_buf());
+        }
+        Some(relative)
+    }
+
+    pub async fn backup_all_sources(&mut self) -> Result<Vec<BackupResult>> {
+        self.backup_sources(None).await
+    }
+
+    /// Back up only the enabled sources at `source_dirs`, concurrently, for
+    /// triggers that concern specific sources.
+    pub async fn backup_source_dirs(
+        &mut self,
+        source_dirs: &[PathBuf],
+    ) -> Result<Vec<BackupResult>> {
+        self.backup_sources(Some(source_dirs)).await
+    }
+
+    async fn backup_sources(&mut self, only: Option<&[PathBuf]>) -> Result<Vec<BackupResult>> {
+        let config = self.config.clone();
+        let started = std::time::Instant::now();
+        let spawned_at = tokio::time::Instant::now();
+        let mut results = Vec::new();
+        let mut failures = Vec::new();
+        let mut records = Vec::new();
+        let mut reports = Vec::new();
+
+        let tasks: Vec<_> = config
+            .sources
+            .iter()
+            .filter(|s| s.enabled && only.is_none_or(|dirs| dirs.contains(&s.source_dir)))
+            .map(|source| {
+                let source = source.clone();
+                let source_dir = source.source_dir.clone();
+                let resolved = source.resolve(&config);
+                let backup_dirs = source.effective_backup_dirs();
+
+                let timeout = source.backup_timeout.map(Duration::from_secs);
+                let cancel = Cancellation::default();
+
+                let force_full_dirs = self.force_full_dirs.clone();
+                let run = RunOptions {
+                    accept_changes: self.accept_changes,
+                    schedule_filter: self.schedule_filter,
+                    changed_paths: self.take_changed_paths(&source, &resolved),
+                };
+                // A full scan that succeeds covers every change up to its start.
+                let full_scan = run.changed_paths.is_none().then(std::time::Instant::now);
+                let task_cancel = cancel.clone();
+                let task = task::spawn(logger::scope_source_log(
+                    &source_dir,
+                    Self::backup_source(
+                        source,
+                        backup_dirs.clone(),
+                        resolved,
+                        force_full_dirs,
+                        run,
+                        task_cancel,
+                    ),
+                ));
+                (source_dir, backup_dirs, task, timeout, cancel, full_scan)
+            })
+            .collect();
+
+        for (source_dir, backup_dirs, mut task, timeout, cancel, full_scan) in tasks {
+            // Kept only on success: after a failure, reported paths alone
+            // may miss changes the failed run would have written.
+            let last_full_scan = self.full_scans.remove(&source_dir);
+            // Failures cannot be pinned to one destination, so each
+            // reachable one gets a report of the failure.
+            let failure_reports = |error: &str| {
+                backup_dirs
+                    .iter()
+                    .filter(|dir| longpath::extended(dir).is_dir())
+                    .map(|dir| report::BackupReport::failure(&source_dir, dir, error.to_string()))
+                    .collect::<Vec<_>>()
+            };
+            let joined = match timeout {
+                Some(timeout) => {
+                    match tokio::time::timeout_at(spawned_at + timeout, &mut task).await {
+                        Ok(joined) => joined,
+                        Err(_) => {
+                            // Not awaited again: a backup blocked in a
+                            // syscall must not block the caller as well.
+                            cancel.cancel();
+                            task.abort();
+                            let message = format!(
+                                "Backup timed out after {} and was cancelled",
+                                crate::units::format_duration(timeout.as_secs())
+                            );
+                            logger::with_source_log(&source_dir, || {
+                                error!("[{:?}] {}", source_dir, message)
+                            });
+                            metrics::record_failure(&source_dir);
+                            reports.extend(failure_reports(&message));
+                            records.push(RunRecord::failure(&source_dir, message.clone()));
+                            failures.push(format!("{:?}: {}", source_dir, message));
+                            continue;
+                        }
+                    }
+                }
+                None => task.await,
+            };
+            match joined {
+                Ok(Ok(result)) => {
+                    if let Some(started) =
+                        full_scan.filter(|_| !result.is_empty()).or(last_full_scan)
+                    {
+                        self.full_scans.insert(source_dir.clone(), started);
+                    }
+                    for r in result {
+                        logger::with_source_log(&source_dir, || {
+                            info!("Backup completed: {:?}", r.backup_dir)
+                        });
+                        metrics::record_backup(&source_dir, &r);
+                        reports.push(report::BackupReport::success(&source_dir, &r));
+                        records.push(RunRecord::success(&source_dir, &r));
+                        results.push(r);
+                    }
+                }
+                Ok(Err(e)) => {
+                    logger::with_source_log(&source_dir, || error!("Backup failed: {:#}", e));
+                    metrics::record_failure(&source_dir);
+                    reports.extend(failure_reports(&format!("{:#}", e)));
+                    records.push(RunRecord::failure(&source_dir, format!("{:#}", e)));
+                    failures.push(format!("{:?}: {:#}", source_dir, e));
+                }
+                Err(e) => {
+                    logger::with_source_log(&source_dir, || error!("Task join error: {}", e));
+                    metrics::record_failure(&source_dir);
+                    reports.extend(failure_reports(&e.to_string()));
+                    records.push(RunRecord::failure(&source_dir, e.to_string()));
+                    failures.push(format!("{:?}: {}", source_dir, e));
+                }
+            }
+        }
+
+        if let Some(ref path) = self.history_path
+            && let Err(e) = history::append_records(path, &records)
+        {
+            warn!("Failed to record backup history: {:#}", e);
+        }
+        for report in &reports {
+            if let Err(e) = Self::write_backup_report(report, config.max_backup_reports) {
+                warn!(
+                    "[{:?}] Failed to write backup report: {:#}",
+                    report.backup_dir, e
+                );
+            }
+        }
+
+        // Startup validation can mark a backup dir as force-full once.
+        // After a successful full backup, clear that flag so subsequent
+        // backups in the same process can proceed as incremental.
+        for result in &results {
+            if matches!(result.backup_type, BackupType::Full) {
+                self.force_full_dirs.remove(&result.backup_dir);
+            }
+        }
+
+        for result in &results {
+            match result.over_quota {
+                Some(true) => {
+                    let runs = self
+                        .over_quota_runs
+                        .entry(result.backup_dir.clone())
+                        .or_insert(0);
+                    *runs += 1;
+                    if *runs >= QUOTA_WARN_AFTER_RUNS {
+                        warn!(
+                            "[{:?}] Still over max_total_size after {} consecutive backups; \
+                             the backups retention must keep exceed the quota",
+                            result.backup_dir, runs
+                        );
+                    }
+                }
+                Some(false) => {
+                    self.over_quota_runs.remove(&result.backup_dir);
+                }
+                None => {}
+            }
+        }
+
+        notification::notify_backup_run(
+            &config.notifications,
+            &results,
+            &failures,
+            started.elapsed(),
+        )
+        .await;
+
+        Ok(results)
+    }
+
+    async fn backup_source(
+        source: SourceConfig,
+        backup_dirs: Vec<PathBuf>,
+        resolved: ResolvedSourceConfig,
+        force_full_dirs: HashMap<PathBuf, bool>,
+        run: RunOptions,
+        cancel: Cancellation,
+    ) -> Result<Vec<BackupResult>> {
+        let healthcheck_url = source.healthcheck_url.clone();
+        if let Some(ref url) = healthcheck_url {
+            notification::ping_healthcheck(url, HealthcheckEvent::Start, String::new()).await;
+        }
+
+        let outcome = Self::backup_source_to_dirs(
+            source,
+            backup_dirs,
+            resolved,
+            force_full_dirs,
+            &run,
+            &cancel,
+        )
+        .await;
+
+        if let Some(ref url) = healthcheck_url {
+            match &outcome {
+                Ok(results) => {
+                    let summary = format!("{} destination(s) backed up", results.len());
+                    notification::ping_healthcheck(url, HealthcheckEvent::Success, summary).await;
+                }
+                Err(e) => {
+                    notification::ping_healthcheck(url, HealthcheckEvent::Fail, format!("{:#}", e))
+                        .await;
+                }
+            }
+        }
+        outcome
+    }
+
+    async fn backup_source_to_dirs(
+        source: SourceConfig,
+        backup_dirs: Vec<PathBuf>,
+        resolved: ResolvedSourceConfig,
+        force_full_dirs: HashMap<PathBuf, bool>,
+        run: &RunOptions,
+        cancel: &Cancellation,
+    ) -> Result<Vec<BackupResult>> {
+        let mut results = Vec::new();
+        if run.schedule_filter == ScheduleFilter::Due && !resolved.enable_periodic {
+            info!(
+                "[{:?}] Skipped: periodic backups are disabled",
+                source.source_dir
+            );
+            return Ok(results);
+        }
+
+        // Captured once per run so every destination records the same context.
+        let context = source
+            .context_command
+            .as_deref()
+            .and_then(|command| Self::capture_source_context(&source.scan_base().0, command));
+
+        // Held until every destination is written; dropping it removes the
+        // snapshot, also when a backup fails.
+        let snapshot = match source.snapshot {
+            Some(ref snapshot) => Some(SourceSnapshot::create(&source.source_dir, snapshot)?),
+            None => None,
+        };
+        // Extended-length paths keep deep trees and UNC shares working past
+        // MAX_PATH on Windows.
+        let source = SourceConfig {
+            source_dir: longpath::extended(match snapshot {
+                Some(ref snapshot) => snapshot.source_path(),
+                None => &source.source_dir,
+            }),
+            ..source
+        };
+
+        for backup_dir in &backup_dirs {
+            let destination = source.destination(backup_dir);
+            let own_schedule = destination.and_then(|d| d.cron_schedule.as_deref());
+            let schedule = match run.schedule_filter {
+                ScheduleFilter::All => None,
+                ScheduleFilter::DestinationSchedules => own_schedule,
+                ScheduleFilter::Due => Some(own_schedule.unwrap_or(&resolved.cron_schedule)),
+            };
+            if let Some(schedule) = schedule {
+                let metadata = Self::load_source_metadata(
+                    &longpath::extended(backup_dir).join("metadata.json"),
+                );
+                if !Self::destination_due(schedule, metadata.last_backup, Utc::now()) {
+                    info!(
+                        "[{:?}] Skipped: its cron_schedule '{}' is not due yet",
+                        backup_dir, schedule
+                    );
+                    continue;
+                }
+            }
+            let resolved = match destination {
+                Some(destination) => destination.apply(&resolved),
+                None => resolved.clone(),
+            };
+            let force_full = force_full_dirs.get(backup_dir).copied().unwrap_or(false);
+            let mut result = Self::perform_backup_to_dir(
+                &source,
+                &longpath::extended(backup_dir),
+                &resolved,
+                force_full,
+                run,
+                context.as_deref(),
+                cancel,
+            )
+            .await
+            .with_context(|| format!("Backup to {:?} failed", backup_dir))?;
+            // Reported under the configured path, not the `\\?\` form.
+            result.backup_dir = backup_dir.clone();
+            results.push(result);
+        }
+
+        Ok(results)
+    }
+
+    /// Whether `schedule` has an occurrence between the destination's last
+    /// backup and `now`. Never-backed-up destinations are always due.
+    fn destination_due(
+        schedule: &str,
+        last_backup: Option<chrono::DateTime<Utc>>,
+        now: chrono::DateTime<Utc>,
+    ) -> bool {
+        let Some(last_backup) = last_backup else {
+            return true;
+        };
+        match cron::Schedule::from_str(schedule) {
+            Ok(schedule) => schedule
+                .after(&last_backup)
+                .next()
+                .is_some_and(|next| next <= now),
+            // Rejected by validation; back up rather than silently never.
+            Err(_) => true,
+        }
+    }
+
+    async fn perform_backup_to_dir(
+        source: &SourceConfig,
+        backup_dir: &Path,
+        resolved: &ResolvedSourceConfig,
+        force_full: bool,
+        run: &RunOptions,
+        context: Option<&str>,
+        cancel: &Cancellation,
+    ) -> Result<BackupResult> {
+        let (scan_base, scan_paths) = source.scan_base();
+        let source_dir = scan_base.as_path();
+        let filter = Self::source_filter(source, resolved, source_dir)?;
+        let backup_mode = &resolved.backup_mode;
+        let start_time = std::time::Instant::now();
+        let usage_start = UsageSnapshot::capture();
+
+        fs::create_dir_all(backup_dir)
+            .with_context(|| format!("Failed to create backup directory: {:?}", backup_dir))?;
+        // Held until this function returns so concurrent `run`/`backup`
+        // processes never interleave writes to the same metadata.json.
+        // Readers copy one point at a time, so waiting them out is short.
+        let _lock = BackupDirLock::acquire_after_readers(backup_dir, "backup", BACKUP_READER_WAIT)?;
+        repo::check_writable(backup_dir, resolved)?;
+        Self::recover_pending_reverse(backup_dir, false)?;
+        Self::remove_stale_partial_backups(backup_dir);
+        if resolved.append_only {
+            Self::mark_append_only(backup_dir)?;
+        }
+
+        let metadata_path = backup_dir.join("metadata.json");
+        let mut metadata = Self::load_source_metadata(&metadata_path);
+        Self::claim_backup_origin(backup_dir, &mut metadata, source, resolved)?;
+        Self::synchronize_metadata_history_with_disk(backup_dir, &mut metadata)?;
+        let mut hash_journal = Self::load_file_hashes(backup_dir, &mut metadata)?;
+        let pruned_hashes = if resolved.prune_stale_hashes {
+            Self::prune_stale_file_hashes(backup_dir, &mut metadata)?
+        } else {
+            0
+        };
+        let signing_key = signing::load_optional(resolved.signing_key_file.as_deref())?;
+
+        // Checked before any work so a missing key never costs a full scan.
+        let archive_passphrase = match resolved.backup_format {
+            BackupFormat::Archive if resolved.archive_encryption => {
+                Some(archive::passphrase_from_env().ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "archive_encryption is enabled but {} is not set",
+                        archive::PASSPHRASE_ENV_KEY
+                    )
+                })?)
+            }
+            _ => None,
+        };
+        if matches!(backup_mode, BackupMode::Dedup)
+            && resolved.backup_format == BackupFormat::Archive
+        {
+            return Err(anyhow::anyhow!(
+                "backup_mode dedup requires backup_format directory"
+            ));
+        }
+
+        let mut locked = LockedFiles::new(resolved);
+        // Changed paths can only extend a chain: a first or forced full
+        // backup, and a mirror's sweep, need every file.
+        let rescan_only_changes = !force_full
+            && metadata.last_full_backup.is_some()
+            && !matches!(backup_mode, BackupMode::Mirror);
+        // Read before scanning, so changes made during the scan are read
+        // again by the next backup. A snapshot is not on the journaled volume.
+        let journal_cursor = (resolved.change_journal
+            && source.snapshot.is_none()
+            && !matches!(backup_mode, BackupMode::Mirror))
+        .then(|| change_journal::current_cursor(source_dir))
+        .flatten();
+        let journal_changes = journal_cursor
+            .as_ref()
+            .zip(metadata.change_journal.as_ref())
+            .filter(|_| rescan_only_changes)
+            .and_then(|(until, since)| change_journal::changed_since(source_dir, since, until));
+        let journal_advanced = metadata.change_journal != journal_cursor;
+        metadata.change_journal = journal_cursor;
+        let changed_paths = journal_changes
+            .as_ref()
+            .or(run.changed_paths.as_ref())
+            .filter(|_| rescan_only_changes);
+        let (mut backup_type, mut files_to_backup, current_hashes, mut bytes_read) =
+            match changed_paths {
+                Some(changed) => {
+                    info!(
+                        "[{:?}] Rescanning {} path(s) reported by the {}",
+                        backup_dir,
+                        changed.len(),
+                        if journal_changes.is_some() {
+                            "change journal"
+                        } else {
+                            "watcher"
+                        }
+                    );
+                    let (files, hashes, bytes_hashed) = Self::scan_changed_paths(
+                        source_dir,
+                        &scan_paths,
+                        &metadata,
+                        changed,
+                        &filter,
+                        &mut locked,
+                    )?;
+                    (BackupType::Incremental, files, hashes, bytes_hashed)
+                }
+                None => {
+                    let (backup_type, files, hashes) = Self::scan_for_changes(
+                        source_dir,
+                        &scan_paths,
+                        &metadata,
+                        &filter,
+                        &mut locked,
+                    )?;
+                    // Every scanned file is read once for hashing.
+                    let bytes_read: u64 = hashes
+                        .keys()
+                        .filter_map(|rel| fs::metadata(source_dir.join(attrs::key_path(rel))).ok())
+                        .map(|m| m.len())
+                        .sum();
+                    (backup_type, files, hashes, bytes_read)
+                }
+            };
+        cancel.check()?;
+
+        if let Some(reason) = Self::detect_change_anomaly(
+            &metadata.file_hashes,
+            &current_hashes,
+            &resolved.anomaly_detection,
+        ) {
+            if !run.accept_changes {
+                // Nothing is written, so existing backups and metadata stay
+                // as they are until the change is confirmed.
+                return Err(anyhow::anyhow!(
+                    "Backup paused, possible ransomware: {}. Existing backups were left untouched; \
+                     after checking the source run `ardiex backup --accept-changes`",
+                    reason
+                ));
+            }
+            warn!("[{:?}] Accepted suspicious change: {}", backup_dir, reason);
+        }
+
+        // Mirror mode keeps no points: the replica is brought up to date in
+        // place and metadata.json only tracks the hashes it reflects.
+        if matches!(backup_mode, BackupMode::Mirror) {
+            let sweep = force_full || !backup_dir.join(mirror::MIRROR_DIR).exists();
+            if sweep && matches!(backup_type, BackupType::Incremental) {
+                files_to_backup =
+                    Self::collect_all_files(source_dir, &scan_paths, &filter, &mut locked)?;
+            }
+            let previous_hashes = std::mem::take(&mut metadata.file_hashes);
+            if !sweep {
+                files_to_backup.retain(|path| {
+                    let key = attrs::manifest_key(path.strip_prefix(source_dir).unwrap_or(path));
+                    current_hashes.get(&key) != previous_hashes.get(&key)
+                });
+            }
+            let removed: Vec<String> = previous_hashes
+                .keys()
+                .filter(|key| !current_hashes.contains_key(*key) && !locked.skipped.contains(key))
+                .cloned()
+                .collect();
+            let stale = if sweep {
+                MirrorStale::AllBut(
+                    current_hashes
+                        .keys()
+                        .chain(&locked.skipped)
+                        .cloned()
+                        .collect(),
+                )
+            } else {
+                MirrorStale::Removed(&removed)
+            };
+            // A source that scans empty while the replica still has files
+            // is far more often an unmounted volume than a deliberate wipe,
+            // and without versions the sweep would delete the whole replica.
+            if current_hashes.is_empty()
+                && locked.skipped.is_empty()
+                && !run.accept_changes
+                && Self::mirror_has_files(backup_dir)
+            {
+                warn!(
+                    "[{:?}] Mirror update refused: source {:?} has no files but the replica still does",
+                    backup_dir, source_dir
+                );
+                return Err(anyhow::anyhow!(
+                    "Mirror update refused: source {:?} has no files but the replica at {:?} still does. \
+                     Nothing was removed; if the source was emptied on purpose run `ardiex backup --accept-changes`",
+                    source_dir,
+                    backup_dir.join(mirror::MIRROR_DIR)
+                ));
+            }
+            let outcome = Self::mirror_changes(
+                source_dir,
+                backup_dir,
+                resolved,
+                &files_to_backup,
+                stale,
+                &mut locked,
+                cancel,
+            )?;
+            info!(
+                "[{:?}] Mirror updated: {} added, {} changed, {} removed",
+                backup_dir, outcome.files_added, outcome.files_changed, outcome.files_removed
+            );
+
+            metadata.file_hashes = current_hashes;
+            for skipped in &locked.skipped {
+                metadata.file_hashes.remove(skipped);
+            }
+            Self::save_metadata_and_hashes(
+                &metadata_path,
+                &mut metadata,
+                &mut hash_journal,
+                &resolved.durability,
+                signing_key.as_ref(),
+            )?;
+            let bytes_written =
+                outcome.bytes_copied + fs::metadata(&metadata_path).map(|m| m.len()).unwrap_or(0);
+            return Ok(BackupResult {
+                backup_dir: backup_dir.to_path_buf(),
+                backup_type: if sweep {
+                    BackupType::Full
+                } else {
+                    BackupType::Incremental
+                },
+                files_backed_up: outcome.files_added + outcome.files_changed,
+                files_added: outcome.files_added,
+                files_changed: outcome.files_changed,
+                bytes_processed: outcome.bytes_copied,
+                changed_bytes: outcome.bytes_copied,
+                delta_saved_bytes: 0,
+                duration_ms: start_time.elapsed().as_millis() as u64,
+                context: context.map(str::to_string),
+                resource_usage: usage_start
+                    .finish(bytes_read + outcome.bytes_copied, bytes_written),
+                over_quota: None,
+                skipped_files: locked.skipped,
+            });
+        }
+
+        // Remove hashes for files that no longer exist in source.
+        let before_hashes = metadata.file_hashes.len();
+        metadata
+            .file_hashes
+            .retain(|path, _| current_hashes.contains_key(path));
+        let stale_removed = pruned_hashes > 0 || metadata.file_hashes.len() != before_hashes;
+
+        // Apply force_full flag from startup validation
+        if force_full && matches!(backup_type, BackupType::Incremental) {
+            info!("Forcing full backup based on startup validation");
+            backup_type = BackupType::Full;
+            // Re-collect full file set. scan_for_changes() returned only changed
+            // files for incremental mode, which could create an incomplete full.
+            files_to_backup =
+                Self::collect_all_files(source_dir, &scan_paths, &filter, &mut locked)?;
+        }
+
+        // Skip incremental backup if no files changed
+        if matches!(backup_type, BackupType::Incremental) && files_to_backup.is_empty() {
+            info!(
+                "[{:?}] No changes detected, skipping incremental backup",
+                backup_dir
+            );
+            let mut bytes_written = 0;
+            if stale_removed || journal_advanced {
+                Self::save_metadata_and_hashes(
+                    &metadata_path,
+                    &mut metadata,
+                    &mut hash_journal,
+                    &Durability::Fast,
+                    signing_key.as_ref(),
+                )?;
+                bytes_written = fs::metadata(&metadata_path).map(|m| m.len()).unwrap_or(0);
+                if stale_removed {
+                    info!(
+                        "[{:?}] Updated metadata after detecting deleted files",
+                        backup_dir
+                    );
+                }
+            }
+            return Ok(BackupResult {
+                backup_dir: backup_dir.to_path_buf(),
+                backup_type,
+                files_backed_up: 0,
+                files_added: 0,
+                files_changed: 0,
+                bytes_processed: 0,
+                changed_bytes: 0,
+                delta_saved_bytes: 0,
+                duration_ms: start_time.elapsed().as_millis() as u64,
+                context: context.map(str::to_string),
+                resource_usage: usage_start.finish(bytes_read, bytes_written),
+                over_quota: None,
+                skipped_files: locked.skipped,
+            });
+        }
+
+        // Full size of every file to store: an upper bound for deltas.
+        let required_bytes: u64 = files_to_backup
+            .iter()
+            .filter_map(|path| fs::metadata(path).ok())
+            .map(|m| m.len())
+            .sum();
+        Self::ensure_free_space(
+            backup_dir,
+            required_bytes,
+            resolved,
+            &mut metadata,
+            &metadata_path,
+            signing_key.as_ref(),
+        )?;
+
+        // Copy mode: always use file copy (no delta)
+        let use_delta = matches!(backup_mode, BackupMode::Delta)
+            && matches!(backup_type, BackupType::Incremental);
+        let file_strategies = PatternMap::new(&resolved.file_strategies)?;
+        // Reverse layout: changed files are stored whole and their previous
+        // version becomes a delta against them once the point is in place.
+        let reverse = use_delta && resolved.delta_layout == DeltaLayout::Reverse;
+        let mut reverse_candidates: Vec<(PathBuf, bool)> = Vec::new();
+
+        let backup_name = point_name::render(
+            &resolved.backup_name_template,
+            source.label.as_deref(),
+            matches!(backup_type, BackupType::Full),
+            &Utc::now().format(point_name::TIMESTAMP_FORMAT).to_string(),
+        );
+        let partial = PartialBackupDir::create(
+            backup_dir.join(format!("{}{}", PARTIAL_BACKUP_PREFIX, backup_name)),
+        )?;
+        let partial_path = partial.path().to_path_buf();
+        // Delta bases found inside archives are extracted here; the partial
+        // prefix lets stale cleanup catch it after a crash.
+        let delta_base_scratch =
+            backup_dir.join(format!("{}{}.base", PARTIAL_BACKUP_PREFIX, backup_name));
+        let verify_scratch =
+            backup_dir.join(format!("{}{}.verify", PARTIAL_BACKUP_PREFIX, backup_name));
+
+        let mut files_backed_up = 0;
+        let mut files_added = 0;
+        let mut files_changed = 0;
+        let mut bytes_processed = 0;
+        let mut changed_bytes = 0;
+        let mut delta_saved_bytes = 0u64;
+        let mut attributes = AttributeManifest::new();
+        let mut files_cloned = 0;
+        let mut files_linked = 0;
+        let previous_full = (matches!(backup_type, BackupType::Full)
+            && resolved.hardlink_full_backups
+            && matches!(resolved.backup_format, BackupFormat::Directory))
+        .then(|| Self::latest_full_backup_dir(backup_dir))
+        .flatten();
+        let dedup_store = matches!(backup_mode, BackupMode::Dedup).then(|| {
+            ChunkStore::open(&dedup::store_root(
+                backup_dir,
+                resolved.dedup_store.as_deref(),
+            ))
+            .with_write_options(
+                resolved.verify_writes,
+                matches!(resolved.durability, Durability::Fsync),
+            )
+        });
+        // Registered before any chunk is written, so gc never sweeps the
+        // store without this dir even if the backup is interrupted.
+        if let (Some(_), Some(shared_store)) = (&dedup_store, &resolved.dedup_store) {
+            dedup::register_referrer(shared_store, backup_dir)?;
+        }
+        let mut dedup_index = DedupIndex {
+            store: resolved.dedup_store.clone(),
+            ..Default::default()
+        };
+        let mut dedup_new_bytes = 0u64;
+        let link_primaries =
+            Self::hard_link_primaries(source_dir, &files_to_backup, &current_hashes);
+        let mut progress = Progress::new(
+            "Backup progress",
+            backup_dir,
+            files_to_backup.len(),
+            required_bytes,
+        );
+
+        for file_path in &files_to_backup {
+            cancel.check()?;
+            let relative_path = file_path.strip_prefix(source_dir)?;
+            progress.start_file(relative_path);
+            let backup_file_path = partial_path.join(relative_path);
+            let rel_key = attrs::manifest_key(relative_path);
+
+            // The file may have been locked since it was hashed.
+            if locked
+                .read(file_path, &rel_key, || Ok(fs::File::open(file_path)?))?
+                .is_none()
+            {
+                metadata.file_hashes.remove(&rel_key);
+                continue;
+            }
+
+            // Dedup points hold only their index, not the file tree.
+            if dedup_store.is_none()
+                && let Some(parent) = backup_file_path.parent()
+            {
+                fs::create_dir_all(parent)?;
+            }
+            let source_metadata = fs::metadata(file_path)?;
+            bytes_read += source_metadata.len();
+            changed_bytes += source_metadata.len();
+            debug!(
+                "Backing up {:?} ({} bytes)",
+                relative_path,
+                source_metadata.len()
+            );
+            let mut file_progress = progress.large_file(relative_path, source_metadata.len());
+            let manifest_key = rel_key.clone();
+            let mut file_attributes = FileAttributes::capture(&source_metadata);
+            if resolved.preserve_xattrs {
+                file_attributes.capture_xattrs(file_path)?;
+            }
+            file_attributes.hard_link_to = attrs::hard_link_identity(&source_metadata)
+                .and_then(|identity| link_primaries.get(&identity))
+                .filter(|primary| **primary != manifest_key)
+                .cloned();
+            attributes.insert(manifest_key.clone(), file_attributes);
+
+            let hash = current_hashes
+                .get(&rel_key)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("Missing precomputed hash for {}", rel_key))?;
+
+            match backup_type {
+                _ if let Some(store) = &dedup_store => {
+                    let (entry, written) = store.store_file(file_path, file_progress.as_mut())?;
+                    dedup_new_bytes += written;
+                    bytes_processed += entry.size;
+                    dedup_index.files.insert(manifest_key, entry);
+                }
+                BackupType::Full
+                    if previous_full.as_deref().is_some_and(|previous| {
+                        Self::link_from_previous_full(
+                            previous,
+                            relative_path,
+                            &backup_file_path,
+                            &hash,
+                        )
+                    }) =>
+                {
+                    files_linked += 1;
+                }
+                BackupType::Full => {
+                    if reflink::copy_file(file_path, &backup_file_path, file_progress.as_mut())?
+                        == CopyMethod::Reflink
+                    {
+                        files_cloned += 1;
+                    }
+                    if resolved.verify_writes {
+                        Self::verify_written_file(&backup_file_path, &hash)?;
+                    }
+                    let file_size = fs::metadata(file_path)?.len();
+                    bytes_processed += file_size;
+                }
+                BackupType::Incremental => {
+                    // Delta mode: find previous backup and create delta,
+                    // unless a `copy` strategy covers the file or it is too
+                    // big to hash twice. A `delta` strategy beats the cap.
+                    let strategy = use_delta
+                        .then(|| file_strategies.get(relative_path))
+                        .flatten()
+                        .or_else(|| {
+                            let too_big = use_delta
+                                && resolved
+                                    .delta_max_file_size
+                                    .is_some_and(|max| source_metadata.len() > max);
+                            if too_big {
+                                debug!(
+                                    "{:?} is over delta_max_file_size, copying it whole",
+                                    relative_path
+                                );
+                            }
+                            too_big.then_some(FileStrategy::Copy)
+                        });
+                    if reverse && strategy != Some(FileStrategy::Copy) {
+                        reverse_candidates.push((
+                            relative_path.to_path_buf(),
+                            strategy == Some(FileStrategy::Delta),
+                        ));
+                    }
+                    let prev_backup =
+                        (use_delta && !reverse && strategy != Some(FileStrategy::Copy))
+                            .then(|| {
+                                Self::find_latest_backup_file(
+                                    backup_dir,
+                                    relative_path,
+                                    &delta_base_scratch,
+                                )
+                            })
+                            .flatten();
+
+                    let mut stored_delta = false;
+                    if let Some(prev_path) = prev_backup {
+                        let delta_file_path = delta::delta_path(&backup_file_path, false);
+                        let file_size = fs::metadata(file_path)?.len();
+                        let written = delta::create_delta_with_block_size(
+                            &prev_path,
+                            file_path,
+                            resolved.delta_block_size,
+                            file_progress.as_mut(),
+                        )
+                        .and_then(|delta_data| {
+                            let encoded = delta::encode_delta(&delta_data)?;
+                            let too_large = strategy != Some(FileStrategy::Delta)
+                                && resolved.delta_max_ratio.is_some_and(|ratio| {
+                                    encoded.len() as f64 > ratio * file_size as f64
+                                });
+                            if !too_large {
+                                delta::save_delta(&encoded, &delta_file_path)?;
+                                if resolved.verify_writes {
+                                    Self::verify_written_delta(
+                                        &prev_path,
+                                        &delta_file_path,
+                                        &verify_scratch,
+                                        &hash,
+                                    )?;
+                                }
+                            }
+                            Ok((delta_data, encoded.len(), too_large))
+                        });
+                        if prev_path == delta_base_scratch {
+                            let _ = fs::remove_file(&delta_base_scratch);
+                        }
+                        let (delta_data, encoded_len, too_large) = written?;
+                        if too_large {
+                            info!(
+                                "Delta of {:?} is {} bytes, over delta_max_ratio of its {} bytes; storing a full copy",
+                                relative_path, encoded_len, file_size
+                            );
+                        } else {
+                            let delta_bytes = delta::delta_size(&delta_data);
+                            bytes_processed += delta_bytes as u64;
+                            delta_saved_bytes += file_size.saturating_sub(delta_bytes as u64);
+                            info!(
+                                "Delta backup: {:?} ({} bytes delta vs {} bytes full, {}/{} blocks changed)",
+                                relative_path,
+                                delta_bytes,
+                                file_size,
+                                delta_data.changed_blocks.len(),
+                                delta_data.total_blocks
+                            );
+                            stored_delta = true;
+                        }
+                        if let Some(file_attributes) = attributes.get_mut(&manifest_key) {
+                            file_attributes.stored_as = Some(if stored_delta {
+                                StoredAs::Delta
+                            } else {
+                                StoredAs::Full
+                            });
+                        }
+                    } else if strategy == Some(FileStrategy::Copy)
+                        && let Some(file_attributes) = attributes.get_mut(&manifest_key)
+                    {
+                        file_attributes.stored_as = Some(StoredAs::Full);
+                    }
+
+                    if !stored_delta {
+                        // Copy mode, a file's first version, a `copy`
+                        // strategy or a delta over delta_max_ratio: copy
+                        // the full file
+                        if reflink::copy_file(file_path, &backup_file_path, file_progress.as_mut())?
+                            == CopyMethod::Reflink
+                        {
+                            files_cloned += 1;
+                        }
+                        if resolved.verify_writes {
+                            Self::verify_written_file(&backup_file_path, &hash)?;
+                        }
+                        let file_size = fs::metadata(file_path)?.len();
+                        bytes_processed += file_size;
+                    }
+                }
+            }
+
+            files_backed_up += 1;
+            match metadata.file_hashes.get(&rel_key) {
+                None => files_added += 1,
+                Some(previous) if *previous != hash => files_changed += 1,
+                Some(_) => {}
+            }
+            progress.finish_file(source_metadata.len());
+
+            metadata.file_hashes.insert(rel_key, hash);
+        }
+
+        if files_cloned > 0 {
+            info!(
+                "[{:?}] {} of {} files cloned via reflink",
+                backup_dir, files_cloned, files_backed_up
+            );
+        }
+        if dedup_store.is_some() {
+            dedup::write_index(&partial_path, &dedup_index)?;
+            info!(
+                "[{:?}] Dedup: {} of {} bytes were new chunks",
+                backup_dir, dedup_new_bytes, bytes_processed
+            );
+        }
+        if files_linked > 0 {
+            info!(
+                "[{:?}] {} of {} files hard-linked to the previous full backup",
+                backup_dir, files_linked, files_backed_up
+            );
+        }
+        if !attributes.is_empty() {
+            attrs::write_manifest(&partial_path, &attributes)?;
+        }
+
+        Self::append_backup_history_entry(
+            &mut metadata,
+            BackupHistoryEntry {
+                backup_name: backup_name.clone(),
+                backup_type: (&backup_type).into(),
+                created_at: Utc::now(),
+                files_backed_up,
+                bytes_processed,
+                inc_checksum: None,
+                context: context.map(str::to_string),
+                resource_usage: None,
+                forwarded_at: None,
+                signature: None,
+                pinned: false,
+                tags: Vec::new(),
+                reverse_deltas: false,
+            },
+            &partial_path,
+            signing_key.as_ref(),
+        )?;
+        // With fsync durability the point must be on disk before
+        // metadata.json records it.
+        let fsync = matches!(resolved.durability, Durability::Fsync);
+        let point_path = match resolved.backup_format {
+            BackupFormat::Directory => backup_dir.join(&backup_name),
+            BackupFormat::Archive => archive::archive_path(backup_dir, &backup_name),
+        };
+        let mut bytes_written = match resolved.backup_format {
+            BackupFormat::Directory => {
+                let bytes = if dedup_store.is_some() {
+                    Self::backup_point_size(&partial_path) + dedup_new_bytes
+                } else {
+                    Self::collect_backup_dir_stats(&partial_path)?.1
+                };
+                if fsync {
+                    Self::sync_tree(&partial_path)?;
+                }
+                partial.commit(&point_path)?;
+                bytes
+            }
+            BackupFormat::Archive => {
+                partial.commit_as_archive(&point_path, archive_passphrase.as_deref())?;
+                if fsync {
+                    Self::sync_file(&point_path)?;
+                }
+                fs::metadata(&point_path)?.len()
+            }
+        };
+        // The point is complete without parity, so failing to write it
+        // only costs the protection.
+        if resolved.parity_percent > 0 {
+            match parity::write(
+                backup_dir,
+                &backup_name,
+                &point_path,
+                resolved.parity_percent,
+                fsync,
+            ) {
+                Ok(bytes) => bytes_written += bytes,
+                Err(e) => warn!(
+                    "[{:?}] Failed to write parity for {}: {:#}",
+                    backup_dir, backup_name, e
+                ),
+            }
+        }
+        if fsync {
+            Self::sync_dir(backup_dir)?;
+        }
+
+        let remaining_size = Self::cleanup_old_backups(
+            backup_dir,
+            resolved.max_backups,
+            resolved.max_total_size,
+            backup_mode,
+        )?;
+        let over_quota = remaining_size
+            .zip(resolved.max_total_size)
+            .map(|(size, limit)| size > limit);
+        if over_quota == Some(true) {
+            warn!(
+                "[{:?}] Backup dir uses {} which exceeds max_total_size",
+                backup_dir,
+                crate::units::format_size(remaining_size.unwrap_or_default())
+            );
+        }
+        Self::synchronize_metadata_history_with_disk(backup_dir, &mut metadata)?;
+
+        Self::save_metadata_and_hashes(
+            &metadata_path,
+            &mut metadata,
+            &mut hash_journal,
+            &resolved.durability,
+            signing_key.as_ref(),
+        )?;
+        bytes_written += fs::metadata(&metadata_path).map(|m| m.len()).unwrap_or(0);
+
+        if !reverse_candidates.is_empty() {
+            match Self::reverse_previous_versions(
+                backup_dir,
+                &backup_name,
+                &reverse_candidates,
+                resolved,
+                &mut metadata,
+                signing_key.as_ref(),
+            ) {
+                Ok(saved) => delta_saved_bytes += saved,
+                Err(e) => warn!(
+                    "[{:?}] Failed to store older versions as reverse deltas: {:#}",
+                    backup_dir, e
+                ),
+            }
+        }
+
+        let duration = start_time.elapsed();
+        let resource_usage = usage_start.finish(bytes_read, bytes_written);
+        // Usage is only known once everything (including metadata.json) is
+        // written, so it is stored with a second, cheap metadata save.
+        if let Some(entry) = metadata
+            .backup_history
+            .iter_mut()
+            .find(|entry| entry.backup_name == backup_name)
+        {
+            entry.resource_usage = Some(resource_usage.clone());
+            Self::save_metadata_and_hashes(
+                &metadata_path,
+                &mut metadata,
+                &mut hash_journal,
+                &resolved.durability,
+                signing_key.as_ref(),
+            )?;
+        }
+
+        Ok(BackupResult {
+            backup_dir: backup_dir.to_path_buf(),
+            backup_type,
+            files_backed_up,
+            files_added,
+            files_changed,
+            bytes_processed,
+            changed_bytes,
+            delta_saved_bytes,
+            duration_ms: duration.as_millis() as u64,
+            context: context.map(str::to_string),
+            resource_usage,
+            over_quota,
+            skipped_files: locked.skipped,
+        })
+    }
+}
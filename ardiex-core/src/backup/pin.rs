@@ -0,0 +1,91 @@
+use super::*;
+use std::collections::HashSet;
+
+impl BackupManager {
+    /// Pin or unpin `point` (a backup name or its timestamp) in the
+    /// metadata of `backup_dir`, returning the point's name. Pinned points
+    /// are kept by retention, low-space pruning and `ardiex prune` until
+    /// they are unpinned.
+    pub fn set_pinned(
+        backup_dir: &Path,
+        point: &str,
+        pinned: bool,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<String> {
+        let operation = if pinned { "pin" } else { "unpin" };
+        let backup_name =
+            Self::update_history_entry(backup_dir, point, operation, signing_key, |entry| {
+                entry.pinned = pinned;
+                Ok(())
+            })?;
+        info!(
+            "[{:?}] {} {}",
+            backup_dir,
+            if pinned { "Pinned" } else { "Unpinned" },
+            backup_name
+        );
+        Ok(backup_name)
+    }
+
+    /// Which of `points` (oldest first) a pin keeps: the pinned points
+    /// and, where incs need their full, every point from a pinned inc back
+    /// to that full. Once reverse deltas were written into that stretch,
+    /// the rest of the chain is kept as well, since they are rebuilt from
+    /// newer points.
+    pub(super) fn held_by_pins(
+        backup_dir: &Path,
+        points: &[PathBuf],
+        backup_mode: &BackupMode,
+    ) -> Vec<bool> {
+        let metadata = Self::load_source_metadata(&backup_dir.join("metadata.json"));
+        let pinned: HashSet<&str> = metadata
+            .backup_history
+            .iter()
+            .filter(|entry| entry.pinned)
+            .map(|entry| entry.backup_name.as_str())
+            .collect();
+        let reversed: HashSet<&str> = metadata
+            .backup_history
+            .iter()
+            .filter(|entry| entry.reverse_deltas)
+            .map(|entry| entry.backup_name.as_str())
+            .collect();
+        let chained = matches!(backup_mode, BackupMode::Delta | BackupMode::Dedup);
+        let names: Vec<Option<String>> = points
+            .iter()
+            .map(|path| archive::backup_point_name(path))
+            .collect();
+        let is_full = |name: &Option<String>| {
+            name.as_deref()
+                .and_then(point_name::parse)
+                .is_some_and(|name| name.is_full)
+        };
+
+        let mut held = vec![false; points.len()];
+        let mut chain_start = 0;
+        for (i, name) in names.iter().enumerate() {
+            let Some(name) = name else {
+                continue;
+            };
+            if is_full(&names[i]) {
+                chain_start = i;
+            }
+            if pinned.contains(name.as_str()) {
+                let from = if chained { chain_start } else { i };
+                held[from..=i].fill(true);
+                let needs_later = names[chain_start..=i]
+                    .iter()
+                    .flatten()
+                    .any(|name| reversed.contains(name.as_str()));
+                if chained && needs_later {
+                    let end = names[i + 1..]
+                        .iter()
+                        .position(is_full)
+                        .map_or(points.len(), |next_full| i + 1 + next_full);
+                    held[i..end].fill(true);
+                }
+            }
+        }
+        held
+    }
+}
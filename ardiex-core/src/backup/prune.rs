@@ -0,0 +1,93 @@
+use super::*;
+use crate::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Marker file that makes a backup dir append-only. It lives in the backup
+/// dir itself, so the dir stays protected even when it is later used by
+/// another config or another machine.
+pub(crate) const APPEND_ONLY_MARKER: &str = ".ardiex-append-only";
+
+/// Points selected by retention, oldest first.
+pub(super) struct RetentionPlan {
+    pub(super) remove: Vec<PathBuf>,
+    /// Size left in the dir when a quota is set.
+    pub(super) remaining_size: Option<u64>,
+}
+
+/// Outcome of `ardiex prune`. Without `--allow-delete` `removed` lists what
+/// would be removed.
+#[derive(Debug, Default)]
+pub struct PruneReport {
+    pub backup_dir: PathBuf,
+    pub deleted: bool,
+    pub append_only: bool,
+    pub removed: Vec<String>,
+    /// Size left after pruning when a quota was given.
+    pub remaining_size: Option<u64>,
+}
+
+impl BackupManager {
+    pub(crate) fn is_append_only(backup_dir: &Path) -> bool {
+        backup_dir.join(APPEND_ONLY_MARKER).is_file()
+    }
+
+    /// Write the append-only marker. Turning the flag off in the config does
+    /// not remove it; that takes deleting the marker by hand.
+    pub(super) fn mark_append_only(backup_dir: &Path) -> Result<()> {
+        let marker = backup_dir.join(APPEND_ONLY_MARKER);
+        if marker.is_file() {
+            return Ok(());
+        }
+        fs::create_dir_all(backup_dir)?;
+        fs::write(
+            &marker,
+            "Backups in this directory are append-only. Remove them with `ardiex prune --allow-delete`.\n",
+        )
+        .with_context(|| format!("Failed to write {:?}", marker))?;
+        info!("[{:?}] Marked backup dir append-only", backup_dir);
+        Ok(())
+    }
+
+    /// Explicit retention run, the only code path that deletes points from
+    /// an append-only dir. Lists the points that would go unless
+    /// `allow_delete` is set; metadata.json is brought in line afterwards.
+    pub fn prune_backup_dir(
+        backup_dir: &Path,
+        keep: usize,
+        max_total_size: Option<u64>,
+        backup_mode: &BackupMode,
+        allow_delete: bool,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<PruneReport> {
+        if !backup_dir.is_dir() {
+            return Err(Error::BackupDirMissing(backup_dir.to_path_buf()).into());
+        }
+        if keep == 0 {
+            return Err(anyhow::anyhow!("Must keep at least one backup"));
+        }
+
+        let _lock = BackupDirLock::acquire(backup_dir, "prune")?;
+        let plan = Self::plan_retention(backup_dir, keep, max_total_size, backup_mode)?;
+        let report = PruneReport {
+            backup_dir: backup_dir.to_path_buf(),
+            deleted: allow_delete,
+            append_only: Self::is_append_only(backup_dir),
+            removed: plan
+                .remove
+                .iter()
+                .filter_map(|path| archive::backup_point_name(path))
+                .collect(),
+            remaining_size: plan.remaining_size,
+        };
+
+        if allow_delete && !plan.remove.is_empty() {
+            Self::remove_backup_points(&plan.remove);
+            let metadata_path = backup_dir.join("metadata.json");
+            let mut metadata = Self::load_source_metadata(&metadata_path);
+            Self::synchronize_metadata_history_with_disk(backup_dir, &mut metadata)?;
+            Self::save_source_metadata(&metadata_path, &metadata, signing_key)?;
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,256 @@
+use super::*;
+use crate::config::{BackupHistoryType, SourceMetadata};
+use crate::error::Error;
+use crate::lock::{self, LockState};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Outcome of `ardiex repair`. In dry-run mode `actions` lists what would be
+/// done; otherwise it lists what was done.
+#[derive(Debug, Default, Serialize)]
+pub struct RepairReport {
+    pub backup_dir: PathBuf,
+    pub dry_run: bool,
+    pub actions: Vec<String>,
+    /// Problems repair cannot fix. The next backup to this dir is forced full.
+    pub unrecoverable: Vec<String>,
+}
+
+impl RepairReport {
+    pub fn is_clean(&self) -> bool {
+        self.actions.is_empty() && self.unrecoverable.is_empty()
+    }
+}
+
+impl BackupManager {
+    /// Fix recoverable inconsistencies in a backup dir: stale locks, an
+    /// interrupted reverse-delta rewrite and partial backups, leftover metadata and hash journal temp files,
+    /// parity files of deleted points, and a backup_history (including inc_checksums) that no longer matches the
+    /// dirs on disk.
+    /// Only a broken chain itself is reported as unrecoverable. With a
+    /// signing key a stale metadata signature is renewed, while points that
+    /// fail their own signature are reported as unrecoverable.
+    pub fn repair_backup_dir(
+        backup_dir: &Path,
+        dry_run: bool,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<RepairReport> {
+        if !backup_dir.is_dir() {
+            return Err(Error::BackupDirMissing(backup_dir.to_path_buf()).into());
+        }
+
+        let mut report = RepairReport {
+            backup_dir: backup_dir.to_path_buf(),
+            dry_run,
+            ..Default::default()
+        };
+
+        // Taking the lock fails if a live process holds it and silently takes
+        // over a stale one, which is exactly the repair we want either way. A
+        // dry run only inspects it: taking it writes the lock file.
+        let (_lock, stale) = if dry_run {
+            match lock::inspect(backup_dir)? {
+                LockState::Held(holder) => {
+                    return Err(Error::Locked {
+                        backup_dir: backup_dir.to_path_buf(),
+                        holder,
+                    }
+                    .into());
+                }
+                LockState::Stale(info) => (None, Some(info)),
+                LockState::Free => (None, None),
+            }
+        } else {
+            let lock = BackupDirLock::acquire(backup_dir, "repair")?;
+            let stale = lock.recovered_stale().cloned();
+            (Some(lock), stale)
+        };
+        if let Some(stale) = stale {
+            report.actions.push(format!(
+                "Clear stale lock left by pid {} ({}, since {})",
+                stale.pid, stale.operation, stale.acquired_at
+            ));
+        }
+
+        if let Some(action) = Self::recover_pending_reverse(backup_dir, dry_run)? {
+            report.actions.push(action);
+        }
+
+        let mut partials: Vec<PathBuf> = fs::read_dir(backup_dir)?
+            .filter_map(|e| e.ok())
+            .filter(|e| {
+                e.file_name()
+                    .to_string_lossy()
+                    .starts_with(PARTIAL_BACKUP_PREFIX)
+            })
+            .map(|e| e.path())
+            .collect();
+        partials.sort();
+        for partial in &partials {
+            report.actions.push(format!(
+                "Remove partial backup {:?}",
+                partial.file_name().unwrap_or_default()
+            ));
+        }
+        if !dry_run && !partials.is_empty() {
+            Self::remove_stale_partial_backups(backup_dir);
+        }
+
+        let metadata_path = backup_dir.join("metadata.json");
+        for tmp_name in ["metadata.json.tmp", "file_hashes.jsonl.tmp"] {
+            let tmp_path = backup_dir.join(tmp_name);
+            if !tmp_path.exists() {
+                continue;
+            }
+            report.actions.push(format!("Remove leftover {}", tmp_name));
+            if !dry_run {
+                fs::remove_file(&tmp_path)
+                    .with_context(|| format!("Failed to remove {:?}", tmp_path))?;
+            }
+        }
+
+        for orphan in parity::orphaned(backup_dir) {
+            report.actions.push(format!(
+                "Remove parity {:?} of a missing point",
+                orphan.file_name().unwrap_or_default()
+            ));
+            if !dry_run {
+                fs::remove_file(&orphan)
+                    .with_context(|| format!("Failed to remove {:?}", orphan))?;
+            }
+        }
+
+        let (mut metadata, metadata_problem) = if !metadata_path.exists() {
+            (
+                SourceMetadata::default(),
+                Some("metadata.json is missing".to_string()),
+            )
+        } else {
+            let content = fs::read_to_string(&metadata_path)
+                .with_context(|| format!("Failed to read {:?}", metadata_path))?;
+            match serde_json::from_str::<SourceMetadata>(&content) {
+                Ok(metadata) => (metadata, None),
+                Err(e) => (
+                    SourceMetadata::default(),
+                    Some(format!("metadata.json could not be parsed ({})", e)),
+                ),
+            }
+        };
+
+        let original = metadata.clone();
+        Self::synchronize_metadata_history_with_disk(backup_dir, &mut metadata)?;
+        // A fresh backup dir without metadata.json has nothing to repair.
+        let metadata_problem = metadata_problem
+            .filter(|_| metadata_path.exists() || !metadata.backup_history.is_empty());
+        let drift = metadata_problem.or_else(|| Self::describe_history_drift(&original, &metadata));
+        if let Some(reason) = drift {
+            let mut action = format!("Rebuild backup_history from disk: {}", reason);
+            if !Self::has_file_hashes(backup_dir, &metadata) && !metadata.backup_history.is_empty()
+            {
+                action
+                    .push_str(" (file hashes are lost; the next incremental re-copies all files)");
+            }
+            report.actions.push(action);
+            if !dry_run {
+                Self::save_source_metadata(&metadata_path, &metadata, signing_key)?;
+            }
+        } else if let Some(key) = signing_key
+            && metadata_path.exists()
+            && let Err(e) = Self::verify_metadata_signature(backup_dir, key)
+        {
+            report
+                .actions
+                .push(format!("Re-sign metadata.json ({})", e));
+            if !dry_run {
+                Self::save_source_metadata(&metadata_path, &metadata, signing_key)?;
+            }
+        }
+
+        if let Some(key) = signing_key {
+            for entry in &metadata.backup_history {
+                if let Some(path) = archive::locate_backup(backup_dir, &entry.backup_name)
+                    && let Err(e) = Self::verify_point_signature(entry, &path, key)
+                {
+                    report.unrecoverable.push(e.to_string());
+                }
+            }
+        }
+
+        if let Some(first) = metadata
+            .backup_history
+            .first()
+            .filter(|entry| matches!(entry.backup_type, BackupHistoryType::Incremental))
+        {
+            report.unrecoverable.push(format!(
+                "Incremental backup {} has no preceding full backup",
+                first.backup_name
+            ));
+        } else if metadata
+            .backup_history
+            .iter()
+            .any(|entry| matches!(entry.backup_type, BackupHistoryType::Incremental))
+            && !Self::validate_delta_chain(backup_dir)
+        {
+            report
+                .unrecoverable
+                .push("Delta chain contains corrupted or unreadable delta files".to_string());
+        }
+
+        Ok(report)
+    }
+
+    fn describe_history_drift(
+        original: &SourceMetadata,
+        rebuilt: &SourceMetadata,
+    ) -> Option<String> {
+        let missing_checksums: Vec<&str> = original
+            .backup_history
+            .iter()
+            .filter(|entry| {
+                matches!(entry.backup_type, BackupHistoryType::Incremental)
+                    && entry.inc_checksum.is_none()
+            })
+            .map(|entry| entry.backup_name.as_str())
+            .collect();
+        if !missing_checksums.is_empty() {
+            return Some(format!(
+                "missing inc_checksum for {}",
+                missing_checksums.join(", ")
+            ));
+        }
+
+        if original.backup_history.len() != rebuilt.backup_history.len() {
+            return Some(format!(
+                "history count mismatch (metadata={}, disk={})",
+                original.backup_history.len(),
+                rebuilt.backup_history.len()
+            ));
+        }
+
+        let mut original_history = original.backup_history.clone();
+        original_history.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.backup_name.cmp(&b.backup_name))
+        });
+        for (meta, disk) in original_history.iter().zip(rebuilt.backup_history.iter()) {
+            if meta.backup_name != disk.backup_name
+                || meta.backup_type != disk.backup_type
+                || meta.files_backed_up != disk.files_backed_up
+                || meta.bytes_processed != disk.bytes_processed
+                || meta.inc_checksum != disk.inc_checksum
+            {
+                return Some(format!("entry '{}' does not match disk", meta.backup_name));
+            }
+        }
+
+        if original.last_backup != rebuilt.last_backup
+            || original.last_full_backup != rebuilt.last_full_backup
+        {
+            return Some("last_backup/last_full_backup markers are stale".to_string());
+        }
+
+        None
+    }
+}
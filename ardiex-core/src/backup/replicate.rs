@@ -0,0 +1,97 @@
+use super::*;
+
+/// Outcome of `ardiex replicate`.
+#[derive(Debug, Default)]
+pub struct ReplicateResult {
+    /// Points copied by this run, oldest first.
+    pub copied: Vec<String>,
+    /// Points the destination already had.
+    pub already_present: usize,
+    pub bytes_copied: u64,
+}
+
+impl BackupManager {
+    /// Copy the points of `backup_dir` that `dest` does not have yet, oldest
+    /// first, then write its metadata.json, so `dest` stays a restorable
+    /// copy of the backup history without re-running backups. Points are
+    /// never changed once written, so a point already at `dest` is skipped
+    /// rather than compared; points pruned locally stay at `dest`.
+    pub fn replicate_backup_dir(
+        backup_dir: &Path,
+        dest: &Path,
+        signing_key: Option<&SigningKey>,
+        lock_timeout: Option<Duration>,
+    ) -> Result<ReplicateResult> {
+        let metadata_path = backup_dir.join("metadata.json");
+        if !metadata_path.is_file() {
+            return Err(anyhow::anyhow!(
+                "{:?} has no metadata.json; is it a backup directory?",
+                backup_dir
+            ));
+        }
+        // Shared, so replication runs alongside restores but never sees a
+        // backup half-way through rewriting metadata.json.
+        let _lock = BackupDirLock::acquire_shared(backup_dir, "replicate", lock_timeout)?;
+        let repo_info = repo::check_readable(backup_dir)?;
+        let metadata = Self::load_source_metadata(&metadata_path);
+        if metadata.backup_history.is_empty() {
+            return Err(anyhow::anyhow!(
+                "{:?} has no backup points to replicate",
+                backup_dir
+            ));
+        }
+        let same_dir = dest
+            .canonicalize()
+            .is_ok_and(|dest| backup_dir.canonicalize().is_ok_and(|dir| dir == dest));
+        if same_dir {
+            return Err(anyhow::anyhow!(
+                "Cannot replicate {:?} onto itself",
+                backup_dir
+            ));
+        }
+
+        fs::create_dir_all(dest)
+            .with_context(|| format!("Failed to create replica directory: {:?}", dest))?;
+        let _dest_lock = BackupDirLock::acquire(dest, "replicate")?;
+        if repo_info.is_some() && repo::check_readable(dest)?.is_none() {
+            fs::copy(
+                backup_dir.join(repo::REPO_FILE_NAME),
+                dest.join(repo::REPO_FILE_NAME),
+            )
+            .with_context(|| format!("Failed to copy {} to {:?}", repo::REPO_FILE_NAME, dest))?;
+        }
+        Self::remove_stale_partial_backups(dest);
+
+        let mut result = ReplicateResult::default();
+        for entry in &metadata.backup_history {
+            if archive::locate_backup(dest, &entry.backup_name).is_some() {
+                result.already_present += 1;
+                continue;
+            }
+            let local_path =
+                archive::locate_backup(backup_dir, &entry.backup_name).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "{} is in the metadata of {:?} but not on disk; run `ardiex repair` first",
+                        entry.backup_name,
+                        backup_dir
+                    )
+                })?;
+            // Stops at the first failure so a later run resumes with a
+            // chain that never has gaps.
+            Self::copy_backup_to(&local_path, dest)
+                .with_context(|| format!("Failed to replicate {}", entry.backup_name))?;
+            info!("[REPLICATE] Copied {} to {:?}", entry.backup_name, dest);
+            result.bytes_copied += Self::backup_point_size(&local_path);
+            result.copied.push(entry.backup_name.clone());
+        }
+
+        // Seeding from the local metadata and its hash journal keeps file
+        // hashes and annotations;
+        // the history itself is rebuilt from what is at `dest`.
+        let mut replica_metadata = metadata;
+        Self::synchronize_metadata_history_with_disk(dest, &mut replica_metadata)?;
+        Self::save_source_metadata(&dest.join("metadata.json"), &replica_metadata, signing_key)?;
+        Self::copy_file_hashes(backup_dir, dest)?;
+        Ok(result)
+    }
+}
@@ -0,0 +1,173 @@
+use super::*;
+use crate::history::RunStatus;
+use crate::units;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+/// Subdirectory of a backup dir holding the reports of recent runs.
+pub(crate) const REPORT_DIR: &str = "reports";
+
+/// What one run did to one backup dir, written next to its backups so the
+/// dir describes itself without the source machine's logs.
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct BackupReport {
+    pub finished_at: DateTime<Utc>,
+    pub source_dir: PathBuf,
+    pub backup_dir: PathBuf,
+    pub status: RunStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup_type: Option<String>,
+    pub files_added: usize,
+    pub files_changed: usize,
+    pub files_unchanged: usize,
+    /// Files left out because they stayed locked.
+    pub files_skipped: Vec<String>,
+    pub bytes_processed: u64,
+    pub delta_saved_bytes: u64,
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BackupReport {
+    pub(super) fn success(source_dir: &Path, result: &BackupResult) -> Self {
+        Self {
+            finished_at: Utc::now(),
+            source_dir: source_dir.to_path_buf(),
+            backup_dir: result.backup_dir.clone(),
+            status: RunStatus::Success,
+            backup_type: Some(
+                match result.backup_type {
+                    BackupType::Full => "full",
+                    BackupType::Incremental => "inc",
+                }
+                .to_string(),
+            ),
+            files_added: result.files_added,
+            files_changed: result.files_changed,
+            files_unchanged: result
+                .files_backed_up
+                .saturating_sub(result.files_added + result.files_changed),
+            files_skipped: result.skipped_files.clone(),
+            bytes_processed: result.bytes_processed,
+            delta_saved_bytes: result.delta_saved_bytes,
+            duration_ms: result.duration_ms,
+            error: None,
+        }
+    }
+
+    pub(super) fn failure(source_dir: &Path, backup_dir: &Path, error: String) -> Self {
+        Self {
+            finished_at: Utc::now(),
+            source_dir: source_dir.to_path_buf(),
+            backup_dir: backup_dir.to_path_buf(),
+            status: RunStatus::Failure,
+            backup_type: None,
+            files_added: 0,
+            files_changed: 0,
+            files_unchanged: 0,
+            files_skipped: Vec::new(),
+            bytes_processed: 0,
+            delta_saved_bytes: 0,
+            duration_ms: 0,
+            error: Some(error),
+        }
+    }
+
+    pub(super) fn to_text(&self) -> String {
+        let mut text = format!(
+            "Ardiex backup report\n\
+             Finished:    {}\n\
+             Source:      {}\n\
+             Backup dir:  {}\n",
+            self.finished_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S %:z"),
+            self.source_dir.display(),
+            self.backup_dir.display()
+        );
+        if let Some(ref error) = self.error {
+            text.push_str(&format!("Status:      FAILED\nError:       {}\n", error));
+            return text;
+        }
+        text.push_str(&format!(
+            "Status:      success ({} backup)\n\
+             Files:       {} added, {} changed, {} unchanged, {} skipped\n\
+             Written:     {}\n\
+             Delta saved: {}\n\
+             Duration:    {:.1}s\n",
+            self.backup_type.as_deref().unwrap_or("unknown"),
+            self.files_added,
+            self.files_changed,
+            self.files_unchanged,
+            self.files_skipped.len(),
+            units::format_size(self.bytes_processed),
+            units::format_size(self.delta_saved_bytes),
+            self.duration_ms as f64 / 1000.0
+        ));
+        if !self.files_skipped.is_empty() {
+            text.push_str("Skipped (locked):\n");
+            for file in &self.files_skipped {
+                text.push_str(&format!("  {}\n", file));
+            }
+        }
+        text
+    }
+}
+
+impl BackupManager {
+    /// Source dir named by the newest report in `backup_dir`, for backup
+    /// dirs whose source is no longer in the config.
+    pub fn reported_source_dir(backup_dir: &Path) -> Option<PathBuf> {
+        let report_dir = longpath::extended(backup_dir).join(REPORT_DIR);
+        let newest = fs::read_dir(&report_dir)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with("report_") && name.ends_with(".json"))
+            .max()?;
+        let content = fs::read_to_string(report_dir.join(newest)).ok()?;
+        serde_json::from_str::<BackupReport>(&content)
+            .ok()
+            .map(|report| report.source_dir)
+    }
+
+    /// Write `report` into its backup dir as `reports/report_<time>.json`
+    /// and `.txt`, then delete all but the newest `keep` reports.
+    pub(super) fn write_backup_report(report: &BackupReport, keep: usize) -> Result<()> {
+        if keep == 0 {
+            return Ok(());
+        }
+        let report_dir = longpath::extended(&report.backup_dir).join(REPORT_DIR);
+        fs::create_dir_all(&report_dir)
+            .with_context(|| format!("Failed to create report directory {:?}", report_dir))?;
+        let stem = format!("report_{}", report.finished_at.format("%Y%m%d_%H%M%S%3f"));
+        fs::write(
+            report_dir.join(format!("{}.json", stem)),
+            serde_json::to_string_pretty(report)?,
+        )?;
+        fs::write(report_dir.join(format!("{}.txt", stem)), report.to_text())?;
+
+        let mut stems: Vec<String> = fs::read_dir(&report_dir)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                name.strip_prefix("report_")?;
+                name.strip_suffix(".json").map(str::to_string)
+            })
+            .collect();
+        stems.sort();
+        let excess = stems.len().saturating_sub(keep);
+        for stem in &stems[..excess] {
+            for extension in ["json", "txt"] {
+                let path = report_dir.join(format!("{}.{}", stem, extension));
+                if let Err(e) = fs::remove_file(&path)
+                    && e.kind() != std::io::ErrorKind::NotFound
+                {
+                    warn!("Failed to remove old backup report {:?}: {}", path, e);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,770 @@
+use super::*;
+use crate::config::SourceMetadata;
+use crate::error::Error;
+use crate::restore::RestoreManager;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Where `reverse_previous_versions` keeps the old copies of a point while
+/// their reverse deltas replace them, until the resealed metadata is saved.
+pub(super) const REVERSE_PENDING_DIR: &str = "reverse.pending";
+const REVERSE_INTENT_FILE_NAME: &str = "intent.json";
+
+/// Written before the first copy of a point is replaced, so an interrupted
+/// swap can be finished or undone (`recover_pending_reverse`).
+#[derive(Serialize, Deserialize)]
+struct ReverseIntent {
+    point: String,
+    /// Relative paths of the copies being replaced; the old copy of each is
+    /// under `kept/` in the pending dir once it was moved out.
+    files: Vec<PathBuf>,
+}
+
+/// Outcome of `ardiex compact`. In dry-run mode the counts are what would
+/// be rewritten at most: an older copy whose delta would not be smaller
+/// stays as it is.
+#[derive(Debug, Default)]
+pub struct CompactReport {
+    pub backup_dir: PathBuf,
+    pub dry_run: bool,
+    /// Stored versions turned into reverse deltas or plain copies.
+    pub versions_rewritten: usize,
+    /// Points whose stored files changed.
+    pub points_rewritten: Vec<String>,
+    /// Chains left alone because they hold archive or dedup points.
+    pub chains_skipped: usize,
+    /// Bytes of all points before and after compacting.
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+/// How one version of a file is stored in a directory point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StoredKind {
+    Plain,
+    /// Delta against an earlier version.
+    Forward,
+    /// Delta against the next newer version.
+    Reverse,
+}
+
+/// One stored version of a file within a chain.
+struct StoredVersion {
+    /// Index of the point in the chain.
+    point: usize,
+    path: PathBuf,
+    kind: StoredKind,
+}
+
+impl BackupManager {
+    /// After a reverse-layout incremental committed `backup_name`, turn the
+    /// previous version of each of `files` into a reverse delta against the
+    /// new plain copy; `true` marks files a `delta` strategy covers. The
+    /// previous version is the nearest plain copy in an earlier directory
+    /// point of the same chain; a delta of the file or an archive or dedup
+    /// point ends the search. Failures only cost the saving, so they are
+    /// logged rather than returned. Returns the bytes saved.
+    pub(super) fn reverse_previous_versions(
+        backup_dir: &Path,
+        backup_name: &str,
+        files: &[(PathBuf, bool)],
+        resolved: &ResolvedSourceConfig,
+        metadata: &mut SourceMetadata,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<u64> {
+        let points = Self::points_by_time(backup_dir)?;
+        let Some(newest) = points.iter().position(|(name, _, _)| name == backup_name) else {
+            return Ok(0);
+        };
+        let newest_path = &points[newest].1;
+        // Newest first, back to the chain's full.
+        let mut earlier: Vec<(&String, &PathBuf)> = Vec::new();
+        for (name, path, is_full) in points[..newest].iter().rev() {
+            if path.is_file() || dedup::read_index(path)?.is_some() {
+                break;
+            }
+            earlier.push((name, path));
+            if *is_full {
+                break;
+            }
+        }
+
+        let mut by_point: BTreeMap<usize, Vec<(&PathBuf, bool)>> = BTreeMap::new();
+        for (relative, forced) in files {
+            let found = earlier.iter().position(|(_, path)| {
+                let stored = path.join(relative);
+                stored.is_file()
+                    || delta::delta_path(&stored, false).is_file()
+                    || delta::delta_path(&stored, true).is_file()
+            });
+            if let Some(index) = found
+                && earlier[index].1.join(relative).is_file()
+            {
+                by_point.entry(index).or_default().push((relative, *forced));
+            }
+        }
+
+        let pending = backup_dir.join(REVERSE_PENDING_DIR);
+        let scratch = backup_dir.join(format!("{}{}.verify", PARTIAL_BACKUP_PREFIX, backup_name));
+        let mut saved = 0;
+        for (index, files) in by_point {
+            let (name, path) = earlier[index];
+            if !Self::still_sealed(metadata, name, path, signing_key)? {
+                warn!(
+                    "[{:?}] {} no longer matches its checksum or signature; keeping its copies",
+                    backup_dir, name
+                );
+                continue;
+            }
+            // Every delta is staged before the first copy is replaced.
+            let mut staged_files = Vec::new();
+            for (relative, forced) in files {
+                let staged = delta::delta_path(&pending.join("staged").join(relative), true);
+                let converted = fs::create_dir_all(staged.parent().unwrap_or(&pending))
+                    .map_err(anyhow::Error::from)
+                    .and_then(|()| {
+                        Self::stage_reverse_delta(
+                            &path.join(relative),
+                            &newest_path.join(relative),
+                            (!forced).then_some(resolved.delta_max_ratio.unwrap_or(1.0)),
+                            resolved.delta_block_size,
+                            &staged,
+                            &scratch,
+                        )
+                    });
+                match converted {
+                    Ok(Some(bytes)) => {
+                        saved += bytes;
+                        staged_files.push(relative.clone());
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!(
+                        "[{:?}] Keeping {:?} in {} as a full copy: {:#}",
+                        backup_dir, relative, name, e
+                    ),
+                }
+            }
+            if staged_files.is_empty() {
+                let _ = fs::remove_dir_all(&pending);
+                continue;
+            }
+            if let Err(e) = Self::swap_in_reverse_deltas(
+                backup_dir,
+                name,
+                staged_files,
+                resolved,
+                metadata,
+                signing_key,
+            ) {
+                // Leave the point as its old seal describes it.
+                Self::recover_pending_reverse(backup_dir, false)?;
+                return Err(e);
+            }
+        }
+        Ok(saved)
+    }
+
+    /// Replace the copies of `files` in point `name` with the deltas staged
+    /// for them and save the resealed metadata, keeping the old copies and
+    /// an intent record in the pending dir until the metadata is saved.
+    fn swap_in_reverse_deltas(
+        backup_dir: &Path,
+        name: &str,
+        files: Vec<PathBuf>,
+        resolved: &ResolvedSourceConfig,
+        metadata: &mut SourceMetadata,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<()> {
+        let pending = backup_dir.join(REVERSE_PENDING_DIR);
+        let path = backup_dir.join(name);
+        let intent_path = pending.join(REVERSE_INTENT_FILE_NAME);
+        let intent = ReverseIntent {
+            point: name.to_string(),
+            files,
+        };
+        fs::write(&intent_path, serde_json::to_vec(&intent)?)
+            .with_context(|| format!("Failed to write {:?}", intent_path))?;
+        let fsync = matches!(resolved.durability, Durability::Fsync);
+        if fsync {
+            Self::sync_file(&intent_path)?;
+        }
+        for relative in &intent.files {
+            let old_copy = path.join(relative);
+            let kept = pending.join("kept").join(relative);
+            fs::create_dir_all(kept.parent().unwrap_or(&pending))?;
+            fs::rename(&old_copy, &kept)?;
+            fs::rename(
+                delta::delta_path(&pending.join("staged").join(relative), true),
+                delta::delta_path(&old_copy, true),
+            )?;
+        }
+        debug!(
+            "[{:?}] {} older version(s) in {} stored as reverse deltas",
+            backup_dir,
+            intent.files.len(),
+            name
+        );
+
+        // Resealed on a copy, so a failed save leaves `metadata` matching
+        // the point once the swap is undone. The hashes stay in the journal.
+        let hashes = std::mem::take(&mut metadata.file_hashes);
+        let mut resealed = metadata.clone();
+        metadata.file_hashes = hashes;
+        Self::reseal_reversed_point(backup_dir, &mut resealed, name, &path, signing_key)?;
+        Self::save_source_metadata_with(
+            &backup_dir.join("metadata.json"),
+            &resealed,
+            &resolved.durability,
+            signing_key,
+        )?;
+        resealed.file_hashes = std::mem::take(&mut metadata.file_hashes);
+        *metadata = resealed;
+        if let Err(e) = fs::remove_dir_all(&pending) {
+            warn!("[{:?}] Failed to remove {:?}: {}", backup_dir, pending, e);
+        }
+        Ok(())
+    }
+
+    /// Finish or undo a reverse-delta swap a crash interrupted: it stands
+    /// when metadata.json already holds the point's new seal, otherwise the
+    /// old copies are moved back so the point matches its recorded seal.
+    /// Returns what was (or in a dry run would be) done.
+    pub(super) fn recover_pending_reverse(
+        backup_dir: &Path,
+        dry_run: bool,
+    ) -> Result<Option<String>> {
+        let pending = backup_dir.join(REVERSE_PENDING_DIR);
+        if !pending.exists() {
+            return Ok(None);
+        }
+        let intent: Option<ReverseIntent> = fs::read(pending.join(REVERSE_INTENT_FILE_NAME))
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok());
+        // Without an intent nothing was replaced yet.
+        let Some(intent) = intent else {
+            if !dry_run {
+                fs::remove_dir_all(&pending)
+                    .with_context(|| format!("Failed to remove {:?}", pending))?;
+            }
+            return Ok(Some(format!("Remove unused {}", REVERSE_PENDING_DIR)));
+        };
+
+        let path = backup_dir.join(&intent.point);
+        let metadata = Self::load_source_metadata(&backup_dir.join("metadata.json"));
+        let recorded = metadata
+            .backup_history
+            .iter()
+            .find(|entry| entry.backup_name == intent.point)
+            .and_then(|entry| entry.inc_checksum.as_deref());
+        let sealed = match recorded {
+            Some(recorded) if path.is_dir() => {
+                Self::calculate_incremental_backup_checksum(&path)? == recorded
+            }
+            _ => !path.is_dir(),
+        };
+        let action = if sealed {
+            format!(
+                "Keep the reverse deltas written into {} before an interruption",
+                intent.point
+            )
+        } else {
+            format!(
+                "Undo the interrupted reverse-delta rewrite of {}",
+                intent.point
+            )
+        };
+        if dry_run {
+            return Ok(Some(action));
+        }
+        if !sealed {
+            for relative in &intent.files {
+                let kept = pending.join("kept").join(relative);
+                if !kept.is_file() {
+                    continue;
+                }
+                let old_copy = path.join(relative);
+                let reverse_delta = delta::delta_path(&old_copy, true);
+                if reverse_delta.exists() {
+                    fs::remove_file(&reverse_delta)
+                        .with_context(|| format!("Failed to remove {:?}", reverse_delta))?;
+                }
+                fs::rename(&kept, &old_copy)
+                    .with_context(|| format!("Failed to restore {:?}", old_copy))?;
+            }
+        }
+        fs::remove_dir_all(&pending).with_context(|| format!("Failed to remove {:?}", pending))?;
+        info!("[{:?}] {}", backup_dir, action);
+        Ok(Some(action))
+    }
+
+    /// Rewrite the delta chains of `backup_dir` into the reverse layout:
+    /// per chain and file, the newest version becomes a plain copy and each
+    /// older one a reverse delta against the next, unless the delta would
+    /// not be smaller (or `resolved` asks for a copy). Every version is
+    /// rebuilt first, so forward deltas, reverse deltas and plain copies
+    /// may be mixed. Chains with archive or dedup points are left alone.
+    pub fn compact_backup_dir(
+        backup_dir: &Path,
+        resolved: Option<&ResolvedSourceConfig>,
+        dry_run: bool,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<CompactReport> {
+        if !backup_dir.is_dir() {
+            return Err(Error::BackupDirMissing(backup_dir.to_path_buf()).into());
+        }
+        if let Some(resolved) = resolved
+            && !matches!(resolved.backup_mode, BackupMode::Delta)
+        {
+            return Err(anyhow::anyhow!(
+                "{:?} is not in delta mode; only delta chains are compacted",
+                backup_dir
+            ));
+        }
+        let _lock = if dry_run {
+            BackupDirLock::acquire_shared(backup_dir, "compact", None)?
+        } else {
+            if Self::is_append_only(backup_dir) {
+                return Err(anyhow::anyhow!(
+                    "{:?} is append-only; compacting would rewrite existing backups",
+                    backup_dir
+                ));
+            }
+            let lock = BackupDirLock::acquire(backup_dir, "compact")?;
+            Self::recover_pending_reverse(backup_dir, false)?;
+            lock
+        };
+        let strategies = PatternMap::new(
+            &resolved
+                .map(|resolved| resolved.file_strategies.clone())
+                .unwrap_or_default(),
+        )?;
+        let max_ratio = resolved.and_then(|resolved| resolved.delta_max_ratio);
+        let max_file_size = resolved.and_then(|resolved| resolved.delta_max_file_size);
+        let block_size = resolved.map_or(delta::DEFAULT_BLOCK_SIZE, |resolved| {
+            resolved.delta_block_size
+        });
+
+        let metadata_path = backup_dir.join("metadata.json");
+        let mut metadata = Self::load_source_metadata(&metadata_path);
+        let points = Self::points_by_time(backup_dir)?;
+        let mut report = CompactReport {
+            backup_dir: backup_dir.to_path_buf(),
+            dry_run,
+            bytes_before: points
+                .iter()
+                .map(|(_, path, _)| Self::backup_point_size(path))
+                .sum(),
+            ..Default::default()
+        };
+
+        let mut chains: Vec<Vec<(String, PathBuf)>> = Vec::new();
+        for (name, path, is_full) in points {
+            if is_full {
+                chains.push(Vec::new());
+            }
+            // Points before the first full cannot be restored anyway.
+            if let Some(chain) = chains.last_mut() {
+                chain.push((name, path));
+            }
+        }
+
+        let scratch_dir = backup_dir.join(format!("{}compact", PARTIAL_BACKUP_PREFIX));
+        let mut rewritten: BTreeSet<String> = BTreeSet::new();
+        let mut reversed: BTreeSet<String> = BTreeSet::new();
+        for chain in &chains {
+            let mut stored = false;
+            for (_, path) in chain {
+                stored |= path.is_file() || dedup::read_index(path)?.is_some();
+            }
+            if stored {
+                info!(
+                    "[{:?}] Leaving the chain of {} alone: it holds archive or dedup points",
+                    backup_dir, chain[0].0
+                );
+                report.chains_skipped += 1;
+                continue;
+            }
+
+            let versions = Self::stored_versions(chain)?;
+            if dry_run {
+                for file_versions in versions.values() {
+                    let newest = file_versions.len() - 1;
+                    for (i, version) in file_versions.iter().enumerate() {
+                        let candidate = match version.kind {
+                            StoredKind::Plain => i < newest,
+                            StoredKind::Forward => true,
+                            StoredKind::Reverse => false,
+                        };
+                        if candidate {
+                            report.versions_rewritten += 1;
+                            rewritten.insert(chain[version.point].0.clone());
+                        }
+                    }
+                }
+                continue;
+            }
+
+            let touched: BTreeSet<usize> = versions
+                .values()
+                .flatten()
+                .map(|version| version.point)
+                .collect();
+            for &point in &touched {
+                let (name, path) = &chain[point];
+                if !Self::still_sealed(&metadata, name, path, signing_key)? {
+                    return Err(anyhow::anyhow!(
+                        "{} no longer matches its checksum or signature; run `ardiex check` \
+                         before compacting",
+                        name
+                    ));
+                }
+            }
+
+            for (relative, file_versions) in &versions {
+                let size = file_versions
+                    .last()
+                    .and_then(|version| fs::metadata(&version.path).ok())
+                    .map_or(0, |m| m.len());
+                let strategy = strategies.get(relative).or_else(|| {
+                    max_file_size
+                        .is_some_and(|max| size > max)
+                        .then_some(FileStrategy::Copy)
+                });
+                let max_ratio = match strategy {
+                    Some(FileStrategy::Copy) => None,
+                    Some(FileStrategy::Delta) => Some(f64::INFINITY),
+                    None => Some(max_ratio.unwrap_or(1.0)),
+                };
+                fs::create_dir_all(&scratch_dir)?;
+                let outcome =
+                    Self::compact_file(file_versions, max_ratio, block_size, &scratch_dir);
+                let _ = fs::remove_dir_all(&scratch_dir);
+                let changed = outcome.with_context(|| {
+                    format!("Failed to compact {:?} in {:?}", relative, backup_dir)
+                })?;
+                for (point, kind) in changed {
+                    report.versions_rewritten += 1;
+                    rewritten.insert(chain[point].0.clone());
+                    if kind == StoredKind::Reverse {
+                        reversed.insert(chain[point].0.clone());
+                    }
+                }
+            }
+
+            for (name, path) in chain {
+                if !rewritten.contains(name) {
+                    continue;
+                }
+                if reversed.contains(name) {
+                    Self::reseal_reversed_point(
+                        backup_dir,
+                        &mut metadata,
+                        name,
+                        path,
+                        signing_key,
+                    )?;
+                } else {
+                    Self::reseal_history_entry(&mut metadata, name, path, signing_key)?;
+                    Self::rewrite_parity(backup_dir, name, path);
+                }
+            }
+        }
+
+        report.points_rewritten = rewritten.into_iter().collect();
+        if !dry_run && !report.points_rewritten.is_empty() {
+            Self::save_source_metadata(&metadata_path, &metadata, signing_key)?;
+        }
+        report.bytes_after = Self::points_by_time(backup_dir)?
+            .iter()
+            .map(|(_, path, _)| Self::backup_point_size(path))
+            .sum();
+        info!(
+            "[{:?}] Compacted {} version(s) in {} point(s) (dry_run: {})",
+            backup_dir,
+            report.versions_rewritten,
+            report.points_rewritten.len(),
+            dry_run
+        );
+        Ok(report)
+    }
+
+    /// Every file stored in the directory points of `chain`, with its
+    /// versions oldest first.
+    fn stored_versions(
+        chain: &[(String, PathBuf)],
+    ) -> Result<BTreeMap<PathBuf, Vec<StoredVersion>>> {
+        let mut versions: BTreeMap<PathBuf, Vec<StoredVersion>> = BTreeMap::new();
+        for (point, (_, path)) in chain.iter().enumerate() {
+            for entry in WalkDir::new(path).sort_by_file_name() {
+                let entry = entry?;
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                let relative = entry.path().strip_prefix(path)?;
+                if attrs::is_attributes_file(relative) {
+                    continue;
+                }
+                let name = entry.file_name().to_string_lossy();
+                let kind = if delta::is_reverse_delta_name(&name) {
+                    StoredKind::Reverse
+                } else if name.ends_with(".delta") {
+                    StoredKind::Forward
+                } else {
+                    StoredKind::Plain
+                };
+                versions
+                    .entry(RestoreManager::strip_delta_extension(relative))
+                    .or_default()
+                    .push(StoredVersion {
+                        point,
+                        path: entry.path().to_path_buf(),
+                        kind,
+                    });
+            }
+        }
+        Ok(versions)
+    }
+
+    /// Rewrite the versions of one file newest first: the newest as a plain
+    /// copy, each older one as a reverse delta against the next when
+    /// `max_ratio` allows it (`None` keeps copies), in `block_size` blocks.
+    /// Returns the points whose version changed and how it is stored now.
+    fn compact_file(
+        versions: &[StoredVersion],
+        max_ratio: Option<f64>,
+        block_size: usize,
+        scratch_dir: &Path,
+    ) -> Result<Vec<(usize, StoredKind)>> {
+        let mut content = Self::rebuild_versions(versions, scratch_dir)?;
+        let staged = scratch_dir.join("staged");
+        let verify_scratch = scratch_dir.join("verify");
+        let mut changed = Vec::new();
+        for i in (0..versions.len()).rev() {
+            let version = &versions[i];
+            // Already a delta against the next version, whose content does
+            // not change.
+            if version.kind == StoredKind::Reverse {
+                continue;
+            }
+            let plain_path = RestoreManager::strip_delta_extension(&version.path);
+            let staged_delta = match (content.get(i + 1), max_ratio) {
+                (Some(newer), Some(max_ratio)) => Self::stage_reverse_delta(
+                    &content[i],
+                    newer,
+                    Some(max_ratio),
+                    block_size,
+                    &staged,
+                    &verify_scratch,
+                )?
+                .is_some(),
+                _ => false,
+            };
+            if staged_delta {
+                fs::rename(&staged, delta::delta_path(&plain_path, true))?;
+                if version.kind == StoredKind::Plain {
+                    // Still the base of the next older version's delta.
+                    let kept = scratch_dir.join(i.to_string());
+                    fs::rename(&version.path, &kept)?;
+                    content[i] = kept;
+                } else {
+                    fs::remove_file(&version.path)?;
+                }
+                changed.push((version.point, StoredKind::Reverse));
+            } else if version.kind == StoredKind::Forward {
+                fs::copy(&content[i], &staged)?;
+                fs::rename(&staged, &plain_path)?;
+                fs::remove_file(&version.path)?;
+                content[i] = plain_path;
+                changed.push((version.point, StoredKind::Plain));
+            }
+        }
+        Ok(changed)
+    }
+
+    /// The content of every version, rebuilt into `scratch_dir` where it is
+    /// stored as a delta: forward deltas from an earlier version with the
+    /// hash they were made against, reverse deltas from the next version.
+    fn rebuild_versions(versions: &[StoredVersion], scratch_dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut content: Vec<Option<PathBuf>> = versions
+            .iter()
+            .map(|version| (version.kind == StoredKind::Plain).then(|| version.path.clone()))
+            .collect();
+        let mut hashes: Vec<Option<String>> = vec![None; versions.len()];
+        let mut hash_of = |i: usize, content: &[Option<PathBuf>]| -> Result<Option<String>> {
+            if hashes[i].is_none()
+                && let Some(path) = &content[i]
+            {
+                hashes[i] = Some(Self::calculate_file_hash(path)?);
+            }
+            Ok(hashes[i].clone())
+        };
+        loop {
+            let mut progressed = false;
+            for i in 0..versions.len() {
+                if content[i].is_some() {
+                    continue;
+                }
+                let delta_data = delta::load_delta(&versions[i].path)
+                    .with_context(|| format!("Failed to load delta {:?}", versions[i].path))?;
+                let expected = delta_data.original_file_hash.as_str();
+                let base = match versions[i].kind {
+                    StoredKind::Forward if expected == delta::EMPTY_FILE_HASH => {
+                        let empty = scratch_dir.join("empty");
+                        fs::File::create(&empty)?;
+                        Some(empty)
+                    }
+                    StoredKind::Forward => {
+                        let mut base = None;
+                        for j in (0..i).rev() {
+                            if hash_of(j, &content)?.as_deref() == Some(expected) {
+                                base = content[j].clone();
+                                break;
+                            }
+                        }
+                        base
+                    }
+                    StoredKind::Reverse if i + 1 < versions.len() => {
+                        match hash_of(i + 1, &content)? {
+                            Some(hash) if hash == expected => content[i + 1].clone(),
+                            Some(_) => {
+                                return Err(anyhow::anyhow!(
+                                    "Reverse delta {:?} was not made against the next version",
+                                    versions[i].path
+                                ));
+                            }
+                            None => None,
+                        }
+                    }
+                    _ => None,
+                };
+                if let Some(base) = base {
+                    let rebuilt = scratch_dir.join(i.to_string());
+                    delta::apply_delta(&base, &delta_data, &rebuilt, None)?;
+                    content[i] = Some(rebuilt);
+                    progressed = true;
+                }
+            }
+            if !progressed {
+                break;
+            }
+        }
+        content
+            .into_iter()
+            .zip(versions)
+            .map(|(content, version)| {
+                content.ok_or_else(|| {
+                    anyhow::anyhow!("No version in the chain to rebuild {:?} from", version.path)
+                })
+            })
+            .collect()
+    }
+
+    /// Write a reverse delta of `old_copy` against `newer_copy`, in
+    /// `block_size` blocks, to `staged` unless it is larger than
+    /// `max_ratio` of the copy. Returns the bytes
+    /// it saves, or `None` when nothing was staged. The delta is checked to
+    /// rebuild the copy, since callers remove the copy, the only one of that
+    /// version, once the delta is in place.
+    fn stage_reverse_delta(
+        old_copy: &Path,
+        newer_copy: &Path,
+        max_ratio: Option<f64>,
+        block_size: usize,
+        staged: &Path,
+        scratch: &Path,
+    ) -> Result<Option<u64>> {
+        let old_size = fs::metadata(old_copy)?.len();
+        let old_hash = Self::calculate_file_hash(old_copy)?;
+        let encoded = delta::encode_delta(&delta::create_delta_with_block_size(
+            newer_copy, old_copy, block_size, None,
+        )?)?;
+        if max_ratio.is_some_and(|ratio| encoded.len() as f64 > ratio * old_size as f64) {
+            debug!(
+                "Reverse delta of {:?} is {} bytes, over the limit for its {} bytes; keeping the copy",
+                old_copy,
+                encoded.len(),
+                old_size
+            );
+            return Ok(None);
+        }
+        delta::save_delta(&encoded, staged)?;
+        if let Err(e) = Self::verify_written_delta(newer_copy, staged, scratch, &old_hash) {
+            let _ = fs::remove_file(staged);
+            return Err(e);
+        }
+        Ok(Some(old_size.saturating_sub(encoded.len() as u64)))
+    }
+
+    /// Whether a point still matches the `inc_checksum` and signature its
+    /// history entry holds. A drifted point is not rewritten, so resealing
+    /// cannot hide the damage.
+    fn still_sealed(
+        metadata: &SourceMetadata,
+        name: &str,
+        path: &Path,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<bool> {
+        let Some(entry) = metadata
+            .backup_history
+            .iter()
+            .find(|entry| entry.backup_name == name)
+        else {
+            return Ok(true);
+        };
+        let signed = signing_key.filter(|_| entry.signature.is_some());
+        if entry.inc_checksum.is_none() && signed.is_none() {
+            return Ok(true);
+        }
+        let checksum = Self::calculate_incremental_backup_checksum(path)?;
+        if entry
+            .inc_checksum
+            .as_deref()
+            .is_some_and(|expected| expected != checksum)
+        {
+            return Ok(false);
+        }
+        Ok(signed.is_none_or(|key| {
+            key.verify_point(
+                name,
+                &checksum,
+                entry.signature.as_deref().unwrap_or_default(),
+            )
+        }))
+    }
+
+    /// Record that a point now holds reverse deltas, renew its checksum and
+    /// signature, and rewrite its parity.
+    fn reseal_reversed_point(
+        backup_dir: &Path,
+        metadata: &mut SourceMetadata,
+        name: &str,
+        path: &Path,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<()> {
+        if let Some(entry) = metadata
+            .backup_history
+            .iter_mut()
+            .find(|entry| entry.backup_name == name)
+        {
+            entry.reverse_deltas = true;
+        }
+        Self::reseal_history_entry(metadata, name, path, signing_key)?;
+        Self::rewrite_parity(backup_dir, name, path);
+        Ok(())
+    }
+
+    /// Parity written before a point's files were rewritten would report
+    /// them as damaged; one that cannot be renewed is removed instead.
+    fn rewrite_parity(backup_dir: &Path, name: &str, path: &Path) {
+        if let Err(e) = parity::rewrite(backup_dir, name, path) {
+            warn!(
+                "[{:?}] Removing the parity of {}, it could not be rewritten: {:#}",
+                backup_dir, name, e
+            );
+            parity::remove_for_point(path);
+        }
+    }
+}
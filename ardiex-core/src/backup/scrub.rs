@@ -0,0 +1,224 @@
+use super::*;
+use crate::archive::ArchiveReader;
+use crate::error::Error;
+use crate::restore::RestoreManager;
+use crate::throttle::Throttle;
+use walkdir::WalkDir;
+
+/// A stored copy whose content no longer matches the hash recorded when it
+/// was written.
+#[derive(Debug)]
+pub struct ScrubFinding {
+    pub backup_dir: PathBuf,
+    /// Manifest key of the file, e.g. `docs/a.txt`.
+    pub file: String,
+    pub stored_copy: PathBuf,
+    /// Matching copy in another destination, when one is intact.
+    pub healthy_copy: Option<PathBuf>,
+    pub repaired: bool,
+}
+
+/// Outcome of scrubbing the backup dirs of one source.
+#[derive(Debug, Default)]
+pub struct ScrubReport {
+    /// Stored copies hashed across all destinations.
+    pub copies_checked: usize,
+    pub findings: Vec<ScrubFinding>,
+}
+
+/// Newest plain copy of a file in one destination, hashed.
+struct StoredCopy {
+    path: PathBuf,
+    expected: String,
+    actual: String,
+}
+
+impl BackupManager {
+    /// Compare the newest stored copy of every file across the backup dirs
+    /// of one source against the hash recorded when it was backed up, and
+    /// replace a corrupted copy with an intact one from another destination
+    /// holding the same version. Only plain copies in directory points (or
+    /// a mirror's replica) are compared; files whose newest version is a
+    /// delta or inside an archive are left to `check`. A repaired copy in an
+    /// incremental matches its `inc_checksum` again, since the checksum was
+    /// taken from the same content.
+    pub fn scrub_backup_dirs(
+        backup_dirs: &[PathBuf],
+        dry_run: bool,
+        io_throttle_mbps: Option<f64>,
+    ) -> Result<ScrubReport> {
+        if backup_dirs.len() < 2 {
+            return Err(anyhow::anyhow!(
+                "Scrubbing needs at least two backup dirs to compare, got {}",
+                backup_dirs.len()
+            ));
+        }
+        let mut throttle = io_throttle_mbps
+            .map(Throttle::from_mb_per_sec)
+            .transpose()?;
+
+        // Exclusive for repairs, so no backup reads a copy being replaced.
+        let mut locks = Vec::new();
+        for backup_dir in backup_dirs {
+            if !backup_dir.is_dir() {
+                return Err(Error::BackupDirMissing(backup_dir.to_path_buf()).into());
+            }
+            locks.push(if dry_run {
+                BackupDirLock::acquire_shared(backup_dir, "scrub", None)?
+            } else {
+                BackupDirLock::acquire(backup_dir, "scrub")?
+            });
+        }
+
+        let mut report = ScrubReport::default();
+        let mut copies: Vec<HashMap<String, StoredCopy>> = Vec::new();
+        for backup_dir in backup_dirs {
+            let dir_copies = Self::hash_latest_copies(backup_dir, throttle.as_mut())?;
+            report.copies_checked += dir_copies.len();
+            copies.push(dir_copies);
+        }
+
+        for (index, backup_dir) in backup_dirs.iter().enumerate() {
+            let mut corrupted: Vec<(&String, &StoredCopy)> = copies[index]
+                .iter()
+                .filter(|(_, copy)| copy.actual != copy.expected)
+                .collect();
+            corrupted.sort_by(|a, b| a.0.cmp(b.0));
+            for (file, copy) in corrupted {
+                let healthy = copies
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, _)| *other != index)
+                    .filter_map(|(_, other)| other.get(file))
+                    .find(|other| other.expected == copy.expected && other.actual == other.expected)
+                    .map(|other| other.path.clone());
+                let repaired = match healthy {
+                    Some(ref healthy) if !dry_run => {
+                        Self::replace_stored_copy(backup_dir, &copy.path, healthy, &copy.expected)?;
+                        info!(
+                            "[SCRUB] Repaired {} in {:?} from {:?}",
+                            file, copy.path, healthy
+                        );
+                        true
+                    }
+                    Some(ref healthy) => {
+                        info!(
+                            "[SCRUB] {} in {:?} is corrupted, would repair from {:?}",
+                            file, copy.path, healthy
+                        );
+                        false
+                    }
+                    None => {
+                        warn!(
+                            "[SCRUB] {} in {:?} is corrupted and no other destination has an intact copy",
+                            file, copy.path
+                        );
+                        false
+                    }
+                };
+                report.findings.push(ScrubFinding {
+                    backup_dir: backup_dir.clone(),
+                    file: file.clone(),
+                    stored_copy: copy.path.clone(),
+                    healthy_copy: healthy,
+                    repaired,
+                });
+            }
+        }
+        drop(locks);
+        Ok(report)
+    }
+
+    /// Hash the newest plain copy of every file `backup_dir` records a hash
+    /// for, keyed by manifest key.
+    fn hash_latest_copies(
+        backup_dir: &Path,
+        mut throttle: Option<&mut Throttle>,
+    ) -> Result<HashMap<String, StoredCopy>> {
+        let mut metadata = Self::load_source_metadata(&backup_dir.join("metadata.json"));
+        Self::load_file_hashes(backup_dir, &mut metadata)?;
+        let latest = Self::latest_plain_copies(backup_dir)?;
+
+        let mut copies = HashMap::new();
+        for (key, expected) in &metadata.file_hashes {
+            let Some(Some(path)) = latest.get(key) else {
+                continue;
+            };
+            if let Some(throttle) = throttle.as_deref_mut() {
+                throttle.consume(fs::metadata(path).map(|m| m.len()).unwrap_or(0));
+            }
+            // An unreadable copy is as broken as a changed one.
+            let actual = Self::calculate_file_hash(path).unwrap_or_default();
+            copies.insert(
+                key.clone(),
+                StoredCopy {
+                    path: path.clone(),
+                    expected: expected.clone(),
+                    actual,
+                },
+            );
+        }
+        Ok(copies)
+    }
+
+    /// Newest stored form of each file in `backup_dir`: `Some` for a plain
+    /// copy in a directory point, `None` when it is a delta or in an
+    /// archive. A mirror with no points stands for itself.
+    fn latest_plain_copies(backup_dir: &Path) -> Result<HashMap<String, Option<PathBuf>>> {
+        let mut roots: Vec<PathBuf> = Self::points_by_time(backup_dir)?
+            .into_iter()
+            .rev()
+            .map(|(_, path, _)| path)
+            .collect();
+        if roots.is_empty()
+            && let Some(mirror_root) = Self::mirror_dir(backup_dir)
+        {
+            roots.push(mirror_root);
+        }
+
+        let mut latest: HashMap<String, Option<PathBuf>> = HashMap::new();
+        for root in roots {
+            if root.is_file() {
+                let reader = ArchiveReader::open_with_env(&root)?;
+                for entry in reader.entries() {
+                    let relative = RestoreManager::strip_delta_extension(&entry.relative_path());
+                    latest.entry(attrs::manifest_key(&relative)).or_insert(None);
+                }
+                continue;
+            }
+            for entry in WalkDir::new(&root).into_iter().filter_map(|e| e.ok()) {
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                let relative = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+                let is_delta = delta::is_delta_name(&relative.to_string_lossy());
+                let key = attrs::manifest_key(&RestoreManager::strip_delta_extension(relative));
+                latest
+                    .entry(key)
+                    .or_insert_with(|| (!is_delta).then(|| entry.path().to_path_buf()));
+            }
+        }
+        Ok(latest)
+    }
+
+    /// Swap `stored` for a copy of `healthy`, staged under a `partial_`
+    /// name so an interrupted repair is cleaned up like a partial backup.
+    fn replace_stored_copy(
+        backup_dir: &Path,
+        stored: &Path,
+        healthy: &Path,
+        expected_hash: &str,
+    ) -> Result<()> {
+        let staged = backup_dir.join(format!("{}scrub.tmp", PARTIAL_BACKUP_PREFIX));
+        fs::copy(healthy, &staged).with_context(|| format!("Failed to copy {:?}", healthy))?;
+        if let Err(e) = Self::verify_written_file(&staged, expected_hash) {
+            let _ = fs::remove_file(&staged);
+            return Err(e);
+        }
+        if let Ok(original) = fs::metadata(stored) {
+            fs::set_permissions(&staged, original.permissions())?;
+        }
+        fs::rename(&staged, stored).with_context(|| format!("Failed to replace {:?}", stored))?;
+        Ok(())
+    }
+}
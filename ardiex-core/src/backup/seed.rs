@@ -0,0 +1,165 @@
+use super::*;
+use crate::error::Error;
+use crate::lock::LOCK_FILE_NAME;
+
+/// Outcome of `ardiex import-seed`.
+#[derive(Debug)]
+pub struct SeedResult {
+    pub backup_name: String,
+    /// Files of the seed recorded in metadata.json as the source's state.
+    pub files_adopted: usize,
+    pub bytes_adopted: u64,
+    /// Files the source's patterns or limits leave out. They stay in the
+    /// full backup but are not tracked, so a restore still brings them back.
+    pub files_untracked: usize,
+}
+
+impl BackupManager {
+    /// Adopt a copy of `source` already sitting in `backup_dir` (e.g. from
+    /// rsync) as its first full backup: hash the copy, move it under
+    /// `full_<time>` and record it in metadata.json, so the next backup is
+    /// an incremental carrying only what differs from the copy. The copy's
+    /// own content and attributes are what the point stores; files edited
+    /// in the source since the copy was made are picked up by that next
+    /// incremental.
+    pub fn import_seed(
+        source: &SourceConfig,
+        resolved: &ResolvedSourceConfig,
+        backup_dir: &Path,
+    ) -> Result<SeedResult> {
+        if !matches!(resolved.backup_mode, BackupMode::Delta | BackupMode::Copy)
+            || resolved.backup_format != BackupFormat::Directory
+        {
+            return Err(anyhow::anyhow!(
+                "import-seed needs backup_mode delta or copy with backup_format directory"
+            ));
+        }
+        if !backup_dir.is_dir() {
+            return Err(Error::BackupDirMissing(backup_dir.to_path_buf()).into());
+        }
+
+        let _lock = BackupDirLock::acquire(backup_dir, "import-seed")?;
+        repo::check_writable(backup_dir, resolved)?;
+        let metadata_path = backup_dir.join("metadata.json");
+        let mut seed_entries = Vec::new();
+        for entry in fs::read_dir(backup_dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name == LOCK_FILE_NAME || name == repo::REPO_FILE_NAME {
+                continue;
+            }
+            if name == "metadata.json"
+                || name.starts_with(PARTIAL_BACKUP_PREFIX)
+                || archive::backup_point_name(&entry.path()).is_some()
+            {
+                return Err(anyhow::anyhow!(
+                    "{:?} already holds backups ({}); import-seed only adopts a plain copy",
+                    backup_dir,
+                    name
+                ));
+            }
+            seed_entries.push(entry.path());
+        }
+
+        // Hashed in place first: this is the slow part, and an interrupted
+        // run leaves the copy exactly as it was.
+        let (_, scan_paths) = source.scan_base();
+        let filter = Self::source_filter(source, resolved, backup_dir)?;
+        let mut locked = LockedFiles::new(resolved);
+        let mut files = Vec::new();
+        let mut hashes = HashMap::new();
+        Self::collect_files_in_roots(
+            backup_dir,
+            &scan_paths,
+            &mut files,
+            &mut hashes,
+            &filter,
+            &mut locked,
+        )?;
+        hashes.remove(LOCK_FILE_NAME);
+        if hashes.is_empty() {
+            return Err(anyhow::anyhow!(
+                "{:?} holds no files of the source to adopt",
+                backup_dir
+            ));
+        }
+
+        let backup_name = point_name::render(
+            &resolved.backup_name_template,
+            source.label.as_deref(),
+            true,
+            &Utc::now().format(point_name::TIMESTAMP_FORMAT).to_string(),
+        );
+        let point_path = backup_dir.join(&backup_name);
+        fs::create_dir(&point_path)
+            .with_context(|| format!("Failed to create {:?}", point_path))?;
+        for entry in &seed_entries {
+            let target = point_path.join(entry.file_name().unwrap_or_default());
+            fs::rename(entry, &target)
+                .with_context(|| format!("Failed to move {:?} into {:?}", entry, point_path))?;
+        }
+
+        let mut attributes = AttributeManifest::new();
+        let mut bytes_adopted = 0;
+        for key in hashes.keys() {
+            let path = point_path.join(attrs::key_path(key));
+            let file_metadata = fs::metadata(&path)?;
+            let mut file_attributes = FileAttributes::capture(&file_metadata);
+            if resolved.preserve_xattrs {
+                file_attributes.capture_xattrs(&path)?;
+            }
+            attributes.insert(key.clone(), file_attributes);
+            bytes_adopted += file_metadata.len();
+        }
+        attrs::write_manifest(&point_path, &attributes)?;
+        let (files_in_point, _) = Self::collect_backup_dir_stats(&point_path)?;
+        let files_untracked = files_in_point.saturating_sub(hashes.len());
+
+        let signing_key = signing::load_optional(resolved.signing_key_file.as_deref())?;
+        let mut metadata = Self::load_source_metadata(&metadata_path);
+        let mut hash_journal = Self::load_file_hashes(backup_dir, &mut metadata)?;
+        Self::claim_backup_origin(backup_dir, &mut metadata, source, resolved)?;
+        Self::append_backup_history_entry(
+            &mut metadata,
+            BackupHistoryEntry {
+                backup_name: backup_name.clone(),
+                backup_type: (&BackupType::Full).into(),
+                created_at: Utc::now(),
+                files_backed_up: hashes.len(),
+                bytes_processed: bytes_adopted,
+                inc_checksum: None,
+                context: Some("import-seed".to_string()),
+                resource_usage: None,
+                forwarded_at: None,
+                signature: None,
+                pinned: false,
+                tags: Vec::new(),
+                reverse_deltas: false,
+            },
+            &point_path,
+            signing_key.as_ref(),
+        )?;
+        Self::synchronize_metadata_history_with_disk(backup_dir, &mut metadata)?;
+        metadata.file_hashes = hashes;
+        Self::save_metadata_and_hashes(
+            &metadata_path,
+            &mut metadata,
+            &mut hash_journal,
+            &resolved.durability,
+            signing_key.as_ref(),
+        )?;
+        info!(
+            "[{:?}] Adopted {} files as {}",
+            backup_dir,
+            metadata.file_hashes.len(),
+            backup_name
+        );
+
+        Ok(SeedResult {
+            backup_name,
+            files_adopted: metadata.file_hashes.len(),
+            bytes_adopted,
+            files_untracked,
+        })
+    }
+}
@@ -0,0 +1,100 @@
+use super::*;
+use crate::config::{LowSpaceAction, SourceMetadata};
+use crate::units;
+use std::path::Path;
+
+impl BackupManager {
+    /// Make sure `backup_dir` has room for `required_bytes` before anything
+    /// is written. With `low_space_action: prune` the oldest points are
+    /// removed one at a time (never below `low_space_min_backups`, never
+    /// breaking the latest delta chain or touching pinned points) until the
+    /// backup fits. Append-only dirs are never pruned, so they always abort.
+    pub(super) fn ensure_free_space(
+        backup_dir: &Path,
+        required_bytes: u64,
+        resolved: &ResolvedSourceConfig,
+        metadata: &mut SourceMetadata,
+        metadata_path: &Path,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<()> {
+        let Some(mut available) = available_space(backup_dir) else {
+            return Ok(());
+        };
+        if available >= required_bytes {
+            return Ok(());
+        }
+
+        if matches!(resolved.low_space_action, LowSpaceAction::Prune)
+            && !Self::is_append_only(backup_dir)
+        {
+            let mut points = Self::count_backup_points(backup_dir, &resolved.backup_mode)?;
+            let pruned_from = points;
+            while available < required_bytes && points > resolved.low_space_min_backups {
+                Self::cleanup_old_backups(backup_dir, points - 1, None, &resolved.backup_mode)?;
+                let remaining = Self::count_backup_points(backup_dir, &resolved.backup_mode)?;
+                if remaining >= points {
+                    // Only the protected delta chain and pinned points are
+                    // left.
+                    break;
+                }
+                points = remaining;
+                available = available_space(backup_dir).unwrap_or(available);
+            }
+            if points < pruned_from {
+                warn!(
+                    "[{:?}] Low disk space: pruned {} old backup(s), {} available for {} needed",
+                    backup_dir,
+                    pruned_from - points,
+                    units::format_size(available),
+                    units::format_size(required_bytes)
+                );
+                Self::synchronize_metadata_history_with_disk(backup_dir, metadata)?;
+                Self::save_source_metadata(metadata_path, metadata, signing_key)?;
+            }
+        }
+
+        if available < required_bytes {
+            return Err(anyhow::anyhow!(
+                "Not enough free space in {:?}: backup needs about {} but only {} is available",
+                backup_dir,
+                units::format_size(required_bytes),
+                units::format_size(available)
+            ));
+        }
+        Ok(())
+    }
+
+    /// Points retention may still remove, i.e. the ones no pin holds.
+    fn count_backup_points(backup_dir: &Path, backup_mode: &BackupMode) -> Result<usize> {
+        let points = Self::sorted_backup_points(backup_dir)?;
+        Ok(Self::held_by_pins(backup_dir, &points, backup_mode)
+            .iter()
+            .filter(|held| !**held)
+            .count())
+    }
+}
+
+/// Bytes available to unprivileged writers on the filesystem holding
+/// `path`. `None` where it cannot be determined, which skips the check.
+#[cfg(unix)]
+pub(crate) fn available_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::zeroed();
+    // SAFETY: statvfs only writes into the provided, properly sized buffer.
+    let rc = unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) };
+    if rc != 0 {
+        return None;
+    }
+    // SAFETY: statvfs returned success, so the struct is initialized.
+    let stat = unsafe { stat.assume_init() };
+    // Field widths differ between platforms (u32 on some 32-bit targets).
+    #[allow(clippy::unnecessary_cast)]
+    Some((stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn available_space(_path: &Path) -> Option<u64> {
+    None
+}
@@ -0,0 +1,246 @@
+use super::*;
+use crate::archive::ArchiveReader;
+use crate::config::BackupHistoryType;
+use crate::error::Error;
+use crate::restore::RestoreManager;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// How many entries `ardiex stats` lists for largest files and hot spots.
+const TOP_ENTRIES: usize = 10;
+
+#[derive(Debug, Serialize)]
+pub struct BackupPointStats {
+    pub name: String,
+    pub backup_type: BackupHistoryType,
+    pub files: usize,
+    /// Bytes this point occupies on disk (the archive file for `.ardx`).
+    /// Files hard-linked from an older point are counted there only.
+    pub stored_bytes: u64,
+    pub delta_files: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StoredFile {
+    pub path: String,
+    pub backup_name: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChangeHotSpot {
+    pub path: String,
+    /// Number of incremental backups that stored a new version.
+    pub changes: usize,
+}
+
+/// Space usage report for `ardiex stats`.
+#[derive(Debug, Default, Serialize)]
+pub struct BackupStats {
+    pub backup_dir: PathBuf,
+    /// Everything under the backup dir, including metadata.json, with
+    /// hard-linked files counted once.
+    pub total_bytes: u64,
+    pub points: Vec<BackupPointStats>,
+    /// Bytes the files stored as deltas would have taken as full copies.
+    pub delta_full_bytes: u64,
+    /// Bytes actually stored for those deltas.
+    pub delta_stored_bytes: u64,
+    pub largest_files: Vec<StoredFile>,
+    pub hot_spots: Vec<ChangeHotSpot>,
+}
+
+impl BackupStats {
+    pub fn delta_saved_bytes(&self) -> i64 {
+        self.delta_full_bytes as i64 - self.delta_stored_bytes as i64
+    }
+}
+
+/// One stored file of a backup point.
+struct PointFile {
+    relative: String,
+    size: u64,
+    /// `size`, or 0 for a hard link to a file an older point already has.
+    stored: u64,
+    /// For a delta, the size of the file it restores.
+    full_size: Option<u64>,
+}
+
+impl BackupManager {
+    pub fn collect_backup_stats(backup_dir: &Path) -> Result<BackupStats> {
+        if !backup_dir.is_dir() {
+            return Err(Error::BackupDirMissing(backup_dir.to_path_buf()).into());
+        }
+
+        let mut stats = BackupStats {
+            backup_dir: backup_dir.to_path_buf(),
+            ..Default::default()
+        };
+        let mut linked = HashSet::new();
+        for entry in WalkDir::new(backup_dir) {
+            let entry = entry?;
+            if entry.file_type().is_file() {
+                let metadata = entry.metadata()?;
+                if attrs::hard_link_identity(&metadata).is_none_or(|id| linked.insert(id)) {
+                    stats.total_bytes += metadata.len();
+                }
+            }
+        }
+
+        let mut points: Vec<(String, PathBuf)> = fs::read_dir(backup_dir)?
+            .filter_map(|e| e.ok())
+            .filter_map(|e| archive::backup_point_name(&e.path()).map(|name| (name, e.path())))
+            .collect();
+        points.sort_by_key(|(name, _)| {
+            point_name::parse(name).map(|name| name.timestamp.to_string())
+        });
+
+        let mut all_files = Vec::new();
+        let mut changes: BTreeMap<String, usize> = BTreeMap::new();
+        // Oldest first, so a hard-linked file counts toward the point that
+        // first stored it.
+        let mut linked = HashSet::new();
+        for (name, path) in points {
+            let is_full = point_name::parse(&name).is_some_and(|name| name.is_full);
+            let files = Self::read_point_files(&path, &mut linked)?;
+            let mut point = BackupPointStats {
+                name: name.clone(),
+                backup_type: if is_full {
+                    BackupHistoryType::Full
+                } else {
+                    BackupHistoryType::Incremental
+                },
+                files: files.len(),
+                stored_bytes: if path.is_file() {
+                    fs::metadata(&path)?.len()
+                } else {
+                    files.iter().map(|f| f.stored).sum()
+                },
+                delta_files: 0,
+            };
+
+            for file in files {
+                let original = match file.full_size {
+                    Some(full_size) => {
+                        point.delta_files += 1;
+                        stats.delta_full_bytes += full_size;
+                        stats.delta_stored_bytes += file.size;
+                        RestoreManager::strip_delta_extension(Path::new(&file.relative))
+                            .to_string_lossy()
+                            .into_owned()
+                    }
+                    None => file.relative.clone(),
+                };
+                if !is_full {
+                    *changes.entry(original).or_default() += 1;
+                }
+                all_files.push(StoredFile {
+                    path: file.relative,
+                    backup_name: name.clone(),
+                    size: file.size,
+                });
+            }
+            stats.points.push(point);
+        }
+
+        all_files.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+        all_files.truncate(TOP_ENTRIES);
+        stats.largest_files = all_files;
+
+        let mut hot_spots: Vec<ChangeHotSpot> = changes
+            .into_iter()
+            .map(|(path, changes)| ChangeHotSpot { path, changes })
+            .collect();
+        hot_spots.sort_by(|a, b| b.changes.cmp(&a.changes).then_with(|| a.path.cmp(&b.path)));
+        hot_spots.truncate(TOP_ENTRIES);
+        stats.hot_spots = hot_spots;
+
+        Ok(stats)
+    }
+
+    /// Files of one backup point with `/`-separated relative paths. Only
+    /// the tail of a delta is read, for the `new_file_size` savings are
+    /// computed from. Hard links already in `linked` are not stored again.
+    fn read_point_files(path: &Path, linked: &mut HashSet<(u64, u64)>) -> Result<Vec<PointFile>> {
+        let mut files = Vec::new();
+        if path.is_file() {
+            let mut reader = ArchiveReader::open_with_env(path)?;
+            let entries = reader.entries().to_vec();
+            for entry in entries {
+                if attrs::is_attributes_file(&entry.relative_path()) {
+                    continue;
+                }
+                let full_size = if delta::is_delta_name(&entry.path) {
+                    let tail = reader.read_entry_tail(&entry, delta::SIZE_TAIL_LEN)?;
+                    match delta::new_file_size_in_tail(&tail) {
+                        Some(size) => Some(size),
+                        None => Some(
+                            delta::parse_delta(&reader.read_entry(&entry)?)
+                                .with_context(|| format!("Failed to load delta {}", entry.path))?
+                                .new_file_size,
+                        ),
+                    }
+                } else {
+                    None
+                };
+                files.push(PointFile {
+                    relative: entry.path.clone(),
+                    size: entry.size,
+                    stored: entry.size,
+                    full_size,
+                });
+            }
+            return Ok(files);
+        }
+        if let Some(index) = dedup::read_index(path)? {
+            return Ok(index
+                .files
+                .into_iter()
+                .map(|(relative, entry)| PointFile {
+                    relative,
+                    size: entry.size,
+                    stored: entry.size,
+                    full_size: None,
+                })
+                .collect());
+        }
+
+        for entry in WalkDir::new(path).sort_by_file_name() {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let relative = entry
+                .path()
+                .strip_prefix(path)?
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            if relative == attrs::ATTRIBUTES_FILE_NAME {
+                continue;
+            }
+            let full_size = if delta::is_delta_name(&relative) {
+                Some(
+                    delta::read_new_file_size(entry.path())
+                        .with_context(|| format!("Failed to load delta {}", relative))?,
+                )
+            } else {
+                None
+            };
+            let metadata = entry.metadata()?;
+            let first_link =
+                attrs::hard_link_identity(&metadata).is_none_or(|id| linked.insert(id));
+            files.push(PointFile {
+                relative,
+                size: metadata.len(),
+                stored: if first_link { metadata.len() } else { 0 },
+                full_size,
+            });
+        }
+        Ok(files)
+    }
+}
@@ -0,0 +1,49 @@
+use super::*;
+
+impl BackupManager {
+    /// Add `tag` to `point` (a backup name or its timestamp) in the metadata
+    /// of `backup_dir`, or remove it with `remove`, returning the point's
+    /// name. Tags are free-form, e.g. `pre-upgrade`, and pick the point for
+    /// `restore --tag`.
+    pub fn set_tag(
+        backup_dir: &Path,
+        point: &str,
+        tag: &str,
+        remove: bool,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<String> {
+        let tag = tag.trim();
+        if tag.is_empty() || tag.contains(char::is_whitespace) {
+            return Err(anyhow::anyhow!(
+                "Invalid tag '{}': use one word such as 'pre-upgrade'",
+                tag
+            ));
+        }
+        let backup_name =
+            Self::update_history_entry(backup_dir, point, "tag", signing_key, |entry| {
+                let tagged = entry.tags.iter().any(|existing| existing == tag);
+                if remove {
+                    if !tagged {
+                        return Err(anyhow::anyhow!(
+                            "{} is not tagged '{}'",
+                            entry.backup_name,
+                            tag
+                        ));
+                    }
+                    entry.tags.retain(|existing| existing != tag);
+                } else if !tagged {
+                    entry.tags.push(tag.to_string());
+                }
+                Ok(())
+            })?;
+        info!(
+            "[{:?}] {} tag '{}' {} {}",
+            backup_dir,
+            if remove { "Removed" } else { "Added" },
+            tag,
+            if remove { "from" } else { "to" },
+            backup_name
+        );
+        Ok(backup_name)
+    }
+}
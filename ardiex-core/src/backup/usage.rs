@@ -0,0 +1,69 @@
+use crate::config::ResourceUsage;
+
+/// Process counters captured at the start of a backup run.
+pub(super) struct UsageSnapshot {
+    cpu_time_ms: Option<u64>,
+}
+
+impl UsageSnapshot {
+    pub(super) fn capture() -> Self {
+        Self {
+            cpu_time_ms: process_cpu_time_ms(),
+        }
+    }
+
+    pub(super) fn finish(&self, bytes_read: u64, bytes_written: u64) -> ResourceUsage {
+        let cpu_time_ms = match (self.cpu_time_ms, process_cpu_time_ms()) {
+            (Some(start), Some(end)) => Some(end.saturating_sub(start)),
+            _ => None,
+        };
+
+        ResourceUsage {
+            cpu_time_ms,
+            bytes_read,
+            bytes_written,
+            peak_memory_kb: process_peak_memory_kb(),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn process_rusage() -> Option<libc::rusage> {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+    // SAFETY: getrusage only writes into the provided, properly sized buffer.
+    let rc = unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) };
+    if rc == 0 {
+        // SAFETY: getrusage returned success, so the struct is initialized.
+        Some(unsafe { usage.assume_init() })
+    } else {
+        None
+    }
+}
+
+#[cfg(unix)]
+fn process_cpu_time_ms() -> Option<u64> {
+    let usage = process_rusage()?;
+    let to_ms = |tv: libc::timeval| tv.tv_sec as u64 * 1000 + tv.tv_usec as u64 / 1000;
+    Some(to_ms(usage.ru_utime) + to_ms(usage.ru_stime))
+}
+
+#[cfg(unix)]
+fn process_peak_memory_kb() -> Option<u64> {
+    let usage = process_rusage()?;
+    // ru_maxrss is reported in bytes on macOS and in KiB elsewhere.
+    if cfg!(target_os = "macos") {
+        Some(usage.ru_maxrss as u64 / 1024)
+    } else {
+        Some(usage.ru_maxrss as u64)
+    }
+}
+
+#[cfg(not(unix))]
+fn process_cpu_time_ms() -> Option<u64> {
+    None
+}
+
+#[cfg(not(unix))]
+fn process_peak_memory_kb() -> Option<u64> {
+    None
+}
@@ -0,0 +1,738 @@
+use super::*;
+use crate::archive::{self, ArchiveReader};
+use crate::error::Error;
+use crate::patterns;
+use crate::units;
+use log::{info, warn};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration as StdDuration;
+
+impl BackupManager {
+    /// Validate all sources at program startup.
+    /// Checks delta chain integrity and auto full-backup interval for each backup directory.
+    /// Sets force_full flag per backup_dir so subsequent backups know to force full.
+    pub fn validate_all_sources(&mut self) -> Result<()> {
+        info!("Starting pre-flight validation of all backup sources...");
+        let config = self.config.clone();
+
+        // ── Global config validation ──
+
+        // Validate global cron_schedule
+        cron::Schedule::from_str(&config.cron_schedule).map_err(|e| {
+            anyhow::anyhow!(
+                "Invalid global cron_schedule '{}': {}",
+                config.cron_schedule,
+                e
+            )
+        })?;
+
+        // Validate global numeric values
+        if config.max_backups == 0 {
+            return Err(anyhow::anyhow!("Global max_backups must be > 0"));
+        }
+        if config.max_log_file_size_mb == 0 {
+            return Err(anyhow::anyhow!("Global max_log_file_size_mb must be > 0"));
+        }
+        if config.max_rotated_log_files == 0 {
+            return Err(anyhow::anyhow!("Global max_rotated_log_files must be > 0"));
+        }
+        if config.max_log_dir_size == Some(0) {
+            return Err(anyhow::anyhow!("Global max_log_dir_size must be > 0"));
+        }
+        for (key, ratio) in [
+            (
+                "max_changed_ratio",
+                config.anomaly_detection.max_changed_ratio,
+            ),
+            (
+                "max_extension_change_ratio",
+                config.anomaly_detection.max_extension_change_ratio,
+            ),
+        ] {
+            if !(ratio > 0.0 && ratio <= 1.0) {
+                return Err(anyhow::anyhow!(
+                    "anomaly_detection.{} must be in (0, 1]",
+                    key
+                ));
+            }
+        }
+        if config.low_space_min_backups == 0 {
+            return Err(anyhow::anyhow!("Global low_space_min_backups must be > 0"));
+        }
+        if config.max_concurrent_backups == 0 {
+            return Err(anyhow::anyhow!("max_concurrent_backups must be > 0"));
+        }
+        if let Some(mbps) = config.io_throttle_mbps
+            && !(mbps.is_finite() && mbps > 0.0)
+        {
+            return Err(anyhow::anyhow!("Global io_throttle_mbps must be > 0"));
+        }
+        if let Some(nice) = config.worker_nice
+            && !(1..=19).contains(&nice)
+        {
+            return Err(anyhow::anyhow!("worker_nice must be between 1 and 19"));
+        }
+        if let Some(ratio) = config.delta_max_ratio
+            && !(ratio.is_finite() && ratio > 0.0)
+        {
+            return Err(anyhow::anyhow!("Global delta_max_ratio must be > 0"));
+        }
+        if config.delta_max_file_size == Some(0) {
+            return Err(anyhow::anyhow!("Global delta_max_file_size must be > 0"));
+        }
+        if !delta::is_valid_block_size(config.delta_block_size) {
+            return Err(anyhow::anyhow!(
+                "delta_block_size must be a power of two from {} to {} bytes",
+                delta::MIN_BLOCK_SIZE,
+                delta::MAX_BLOCK_SIZE
+            ));
+        }
+        if config.parity_percent > 100 {
+            return Err(anyhow::anyhow!("Global parity_percent must be at most 100"));
+        }
+
+        if let Some(ref smtp) = config.notifications.smtp
+            && (smtp.host.trim().is_empty()
+                || !smtp.from.contains('@')
+                || smtp.to.is_empty()
+                || smtp.to.iter().any(|to| !to.contains('@')))
+        {
+            return Err(anyhow::anyhow!(
+                "notifications.smtp requires host, a from address and at least one to address"
+            ));
+        }
+
+        patterns::validate_patterns(&config.include_patterns)
+            .context("Invalid global include_patterns")?;
+        patterns::validate_patterns(&config.exclude_patterns)
+            .context("Invalid global exclude_patterns")?;
+        patterns::validate_patterns(&config.file_strategies.keys().cloned().collect::<Vec<_>>())
+            .context("Invalid global file_strategies")?;
+
+        if config.forward_interval_secs == 0 {
+            return Err(anyhow::anyhow!("Global forward_interval_secs must be > 0"));
+        }
+
+        if config.metrics_port == Some(0) {
+            return Err(anyhow::anyhow!(
+                "Global metrics_port must be between 1 and 65535"
+            ));
+        }
+
+        if let Some(ref bind) = config.api_bind {
+            bind.parse::<std::net::SocketAddr>().with_context(|| {
+                format!(
+                    "Invalid api_bind {:?}; use an address like 127.0.0.1:8686",
+                    bind
+                )
+            })?;
+            if config
+                .api_token
+                .as_deref()
+                .is_none_or(|token| token.trim().is_empty())
+            {
+                return Err(anyhow::anyhow!(
+                    "api_bind requires api_token; the REST API never runs unauthenticated"
+                ));
+            }
+        } else if config.api_dashboard {
+            return Err(anyhow::anyhow!(
+                "api_dashboard requires api_bind; the dashboard is served by the REST API"
+            ));
+        }
+        if let Some(root) = config.api_restore_roots.iter().find(|r| !r.is_absolute()) {
+            return Err(anyhow::anyhow!(
+                "api_restore_roots must be absolute paths: {:?}",
+                root
+            ));
+        }
+
+        for mapping in &config.path_mappings {
+            if !mapping.from.is_absolute() || !mapping.to.is_absolute() {
+                return Err(anyhow::anyhow!(
+                    "Path mapping must use absolute paths: {:?} -> {:?}",
+                    mapping.from,
+                    mapping.to
+                ));
+            }
+        }
+
+        point_name::validate_template(&config.backup_name_template)
+            .context("Invalid global backup_name_template")?;
+
+        if let Some(ref host_id) = config.host_id
+            && (host_id.trim().is_empty()
+                || host_id.trim_matches('.').is_empty()
+                || host_id.contains(['/', '\\']))
+        {
+            return Err(anyhow::anyhow!(
+                "host_id must be a single directory name: {:?}",
+                host_id
+            ));
+        }
+
+        if let Some(ref store) = config.dedup_store
+            && !store.is_absolute()
+        {
+            return Err(anyhow::anyhow!(
+                "dedup_store must be an absolute path: {:?}",
+                store
+            ));
+        }
+
+        let signing_key = match config.signing_key_file {
+            Some(ref key_file) => {
+                if !key_file.is_absolute() {
+                    return Err(anyhow::anyhow!(
+                        "signing_key_file must be an absolute path: {:?}",
+                        key_file
+                    ));
+                }
+                // A key stored next to the backups could be used to re-sign
+                // edited metadata.
+                if let Some(source) = config.sources.iter().find(|source| {
+                    source
+                        .effective_backup_dirs()
+                        .iter()
+                        .chain(source.forward_dir.as_ref())
+                        .any(|dir| key_file.starts_with(dir))
+                }) {
+                    return Err(anyhow::anyhow!(
+                        "signing_key_file {:?} must not be inside a backup directory of {:?}",
+                        key_file,
+                        source.source_dir
+                    ));
+                }
+                Some(crate::signing::SigningKey::load(key_file)?)
+            }
+            None => None,
+        };
+
+        // ── Per-source validation ──
+
+        let mut seen_sources: HashSet<PathBuf> = HashSet::new();
+
+        for source in &config.sources {
+            // Duplicate source check
+            if !seen_sources.insert(source.source_dir.clone()) {
+                return Err(anyhow::anyhow!(
+                    "Duplicate source directory: {:?}",
+                    source.source_dir
+                ));
+            }
+
+            // Source path must be absolute
+            if !source.source_dir.is_absolute() {
+                return Err(anyhow::anyhow!(
+                    "Source path must be absolute: {:?}",
+                    source.source_dir
+                ));
+            }
+
+            if !source.enabled {
+                continue;
+            }
+
+            // Source directory must exist
+            if !source.source_dir.exists() {
+                return Err(Error::SourceMissing(source.source_dir.clone()).into());
+            }
+
+            // Source must be a directory or a single regular file
+            if source.source_dir.is_file() {
+                if !source.paths.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "Source {:?}: paths cannot be used with a file source",
+                        source.source_dir
+                    ));
+                }
+                // The default `<source>/.backup` cannot live inside a file.
+                if source.backup_dirs.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "Source {:?}: a file source needs at least one backup directory",
+                        source.source_dir
+                    ));
+                }
+            } else if !source.source_dir.is_dir() {
+                return Err(anyhow::anyhow!(
+                    "Source path is neither a directory nor a regular file: {:?}",
+                    source.source_dir
+                ));
+            }
+
+            if let Some(ref snapshot) = source.snapshot {
+                crate::snapshot::validate(&source.source_dir, snapshot)
+                    .with_context(|| format!("Source {:?}: invalid snapshot", source.source_dir))?;
+            }
+
+            // Validate source-level overrides
+            if let Some(mb) = source.max_backups
+                && mb == 0
+            {
+                return Err(anyhow::anyhow!(
+                    "Source {:?}: max_backups must be > 0",
+                    source.source_dir
+                ));
+            }
+            if source.low_space_min_backups == Some(0) {
+                return Err(anyhow::anyhow!(
+                    "Source {:?}: low_space_min_backups must be > 0",
+                    source.source_dir
+                ));
+            }
+            if let Some(mbps) = source.io_throttle_mbps
+                && !(mbps.is_finite() && mbps > 0.0)
+            {
+                return Err(anyhow::anyhow!(
+                    "Source {:?}: io_throttle_mbps must be > 0",
+                    source.source_dir
+                ));
+            }
+            if let Some(ratio) = source.delta_max_ratio
+                && !(ratio.is_finite() && ratio > 0.0)
+            {
+                return Err(anyhow::anyhow!(
+                    "Source {:?}: delta_max_ratio must be > 0",
+                    source.source_dir
+                ));
+            }
+            if source.delta_max_file_size == Some(0) {
+                return Err(anyhow::anyhow!(
+                    "Source {:?}: delta_max_file_size must be > 0",
+                    source.source_dir
+                ));
+            }
+            if source.parity_percent.is_some_and(|percent| percent > 100) {
+                return Err(anyhow::anyhow!(
+                    "Source {:?}: parity_percent must be at most 100",
+                    source.source_dir
+                ));
+            }
+            if source.backup_timeout == Some(0) {
+                return Err(anyhow::anyhow!(
+                    "Source {:?}: backup_timeout must be > 0",
+                    source.source_dir
+                ));
+            }
+            for (key, value) in [
+                ("include_patterns", &source.include_patterns),
+                ("exclude_patterns", &source.exclude_patterns),
+            ] {
+                if let Some(list) = value {
+                    patterns::validate_patterns(list).with_context(|| {
+                        format!("Source {:?}: invalid {}", source.source_dir, key)
+                    })?;
+                }
+            }
+            if let Some(strategies) = &source.file_strategies {
+                patterns::validate_patterns(&strategies.keys().cloned().collect::<Vec<_>>())
+                    .with_context(|| {
+                        format!("Source {:?}: invalid file_strategies", source.source_dir)
+                    })?;
+            }
+            for (key, value) in [
+                ("cron_schedule", &source.cron_schedule),
+                ("verify_schedule", &source.verify_schedule),
+            ] {
+                if let Some(cs) = value {
+                    cron::Schedule::from_str(cs).map_err(|e| {
+                        anyhow::anyhow!(
+                            "Source {:?}: invalid {} '{}': {}",
+                            source.source_dir,
+                            key,
+                            cs,
+                            e
+                        )
+                    })?;
+                }
+            }
+
+            for sub in &source.paths {
+                if sub.is_absolute()
+                    || sub
+                        .components()
+                        .any(|c| matches!(c, std::path::Component::ParentDir))
+                {
+                    return Err(anyhow::anyhow!(
+                        "Source {:?}: paths entries must be relative subpaths without '..': {:?}",
+                        source.source_dir,
+                        sub
+                    ));
+                }
+                if !source.source_dir.join(sub).exists() {
+                    warn!(
+                        "Source {:?}: subpath {:?} does not exist yet",
+                        source.source_dir, sub
+                    );
+                }
+            }
+
+            // Backup dirs validation
+            let backup_dirs = source.effective_backup_dirs();
+
+            let mut seen_backup_dirs: HashSet<PathBuf> = HashSet::new();
+            for backup_dir in &backup_dirs {
+                // Backup path must be absolute
+                if !backup_dir.is_absolute() {
+                    return Err(anyhow::anyhow!(
+                        "Backup path must be absolute: {:?} (source: {:?})",
+                        backup_dir,
+                        source.source_dir
+                    ));
+                }
+
+                // Duplicate backup dir check
+                if !seen_backup_dirs.insert(backup_dir.clone()) {
+                    return Err(anyhow::anyhow!(
+                        "Duplicate backup directory: {:?} (source: {:?})",
+                        backup_dir,
+                        source.source_dir
+                    ));
+                }
+
+                // Source and backup must not be the same
+                if *backup_dir == source.source_dir {
+                    return Err(anyhow::anyhow!(
+                        "Backup directory cannot be the same as source: {:?}",
+                        backup_dir
+                    ));
+                }
+
+                // Auto-create backup directory if it doesn't exist
+                if !backup_dir.exists() {
+                    fs::create_dir_all(backup_dir).map_err(|e| {
+                        anyhow::anyhow!(
+                            "Failed to create backup directory {:?} (source: {:?}): {}",
+                            backup_dir,
+                            source.source_dir,
+                            e
+                        )
+                    })?;
+                    info!("Auto-created backup directory: {:?}", backup_dir);
+                } else if !backup_dir.is_dir() {
+                    return Err(anyhow::anyhow!(
+                        "Backup path is not a directory: {:?} (source: {:?})",
+                        backup_dir,
+                        source.source_dir
+                    ));
+                }
+            }
+
+            if let Some(ref forward_dir) = source.forward_dir {
+                if !forward_dir.is_absolute() {
+                    return Err(anyhow::anyhow!(
+                        "Forward path must be absolute: {:?} (source: {:?})",
+                        forward_dir,
+                        source.source_dir
+                    ));
+                }
+                if *forward_dir == source.source_dir || backup_dirs.contains(forward_dir) {
+                    return Err(anyhow::anyhow!(
+                        "Forward directory must differ from the source and its backup dirs: {:?}",
+                        forward_dir
+                    ));
+                }
+                // Not created here: an unreachable forward_dir just queues.
+            }
+
+            if let Some(ref template) = source.backup_name_template {
+                point_name::validate_template(template).with_context(|| {
+                    format!(
+                        "Invalid backup_name_template (source: {:?})",
+                        source.source_dir
+                    )
+                })?;
+            }
+            if let Some(ref label) = source.label {
+                point_name::validate_label(label)
+                    .with_context(|| format!("Source {:?}", source.source_dir))?;
+            }
+
+            if let Some(ref url) = source.healthcheck_url
+                && !(url.starts_with("http://") || url.starts_with("https://"))
+            {
+                return Err(anyhow::anyhow!(
+                    "healthcheck_url must be an http(s) URL: {} (source: {:?})",
+                    url,
+                    source.source_dir
+                ));
+            }
+
+            if let (Some(min), Some(max)) = (source.min_file_size, source.max_file_size)
+                && min > max
+            {
+                return Err(anyhow::anyhow!(
+                    "Source {:?}: min_file_size ({}) must not exceed max_file_size ({})",
+                    source.source_dir,
+                    units::format_size(min),
+                    units::format_size(max)
+                ));
+            }
+
+            for destination in &source.backup_dirs {
+                if destination.max_backups == Some(0) {
+                    return Err(anyhow::anyhow!(
+                        "Backup dir {:?}: max_backups must be > 0",
+                        destination.path
+                    ));
+                }
+                if let Some(ref schedule) = destination.cron_schedule {
+                    cron::Schedule::from_str(schedule).map_err(|e| {
+                        anyhow::anyhow!(
+                            "Backup dir {:?}: invalid cron_schedule '{}': {}",
+                            destination.path,
+                            schedule,
+                            e
+                        )
+                    })?;
+                }
+            }
+
+            // ── Delta chain / full interval validation ──
+            for backup_dir in &backup_dirs {
+                let resolved = source.resolve_for(&config, backup_dir);
+                if resolved.backup_format == BackupFormat::Archive
+                    && resolved.archive_encryption
+                    && archive::passphrase_from_env().is_none()
+                {
+                    return Err(anyhow::anyhow!(
+                        "archive_encryption is enabled but {} is not set (source: {:?})",
+                        archive::PASSPHRASE_ENV_KEY,
+                        source.source_dir
+                    ));
+                }
+                if matches!(resolved.backup_mode, BackupMode::Dedup)
+                    && resolved.backup_format == BackupFormat::Archive
+                {
+                    return Err(anyhow::anyhow!(
+                        "Source {:?}: backup_mode dedup requires backup_format directory ({:?})",
+                        source.source_dir,
+                        backup_dir
+                    ));
+                }
+                if matches!(resolved.backup_mode, BackupMode::Delta)
+                    && resolved.delta_layout == DeltaLayout::Reverse
+                {
+                    if resolved.backup_format == BackupFormat::Archive {
+                        return Err(anyhow::anyhow!(
+                            "Source {:?}: delta_layout reverse rewrites older points and requires \
+                             backup_format directory ({:?})",
+                            source.source_dir,
+                            backup_dir
+                        ));
+                    }
+                    if resolved.append_only {
+                        return Err(anyhow::anyhow!(
+                            "Source {:?}: delta_layout reverse rewrites older points and cannot be \
+                             append_only ({:?})",
+                            source.source_dir,
+                            backup_dir
+                        ));
+                    }
+                }
+                if matches!(resolved.backup_mode, BackupMode::Mirror) {
+                    if resolved.backup_format == BackupFormat::Archive {
+                        return Err(anyhow::anyhow!(
+                            "Source {:?}: backup_mode mirror requires backup_format directory ({:?})",
+                            source.source_dir,
+                            backup_dir
+                        ));
+                    }
+                    if resolved.append_only {
+                        return Err(anyhow::anyhow!(
+                            "Source {:?}: backup_mode mirror deletes removed files and cannot be \
+                             append_only ({:?})",
+                            source.source_dir,
+                            backup_dir
+                        ));
+                    }
+                }
+
+                let mut needs_full = false;
+
+                if let Err(e) =
+                    Self::validate_backup_metadata_history(backup_dir, signing_key.as_ref())
+                {
+                    warn!(
+                        "[{:?}] Metadata history validation failed: {}. Will force full backup \
+                         (run `ardiex repair` to fix recoverable metadata drift instead).",
+                        backup_dir, e
+                    );
+                    needs_full = true;
+                }
+
+                if matches!(resolved.backup_mode, BackupMode::Delta | BackupMode::Dedup) {
+                    let inc_count = Self::count_inc_since_last_full(backup_dir);
+                    if inc_count >= resolved.full_backup_interval {
+                        info!(
+                            "[{:?}] Full backup interval reached ({} inc backups), will force full",
+                            backup_dir, inc_count
+                        );
+                        needs_full = true;
+                    }
+
+                    if !needs_full {
+                        let chain_valid = Self::validate_delta_chain(backup_dir);
+                        if !chain_valid {
+                            warn!(
+                                "[{:?}] Delta chain integrity check failed, will force full",
+                                backup_dir
+                            );
+                            needs_full = true;
+                        }
+                    }
+                }
+
+                if needs_full {
+                    self.force_full_dirs.insert(backup_dir.clone(), true);
+                }
+                info!(
+                    "[{:?}] Validation complete (force_full: {})",
+                    backup_dir, needs_full
+                );
+            }
+        }
+        info!("Pre-flight validation complete.");
+        Ok(())
+    }
+
+    fn count_inc_since_last_full(backup_dir: &Path) -> usize {
+        let mut names: Vec<String> = match fs::read_dir(backup_dir) {
+            Ok(rd) => rd
+                .filter_map(|e| e.ok())
+                .filter_map(|e| archive::backup_point_name(&e.path()))
+                .collect(),
+            Err(_) => return 0,
+        };
+
+        // Newest first, whatever the name template puts before the time.
+        names.sort_by_key(|name| {
+            Reverse(point_name::parse(name).map(|name| name.timestamp.to_string()))
+        });
+
+        names
+            .iter()
+            .filter_map(|name| point_name::parse(name))
+            .take_while(|name| !name.is_full)
+            .count()
+    }
+
+    pub(crate) fn validate_delta_chain(backup_dir: &Path) -> bool {
+        let mut points: Vec<(String, PathBuf)> = match fs::read_dir(backup_dir) {
+            Ok(rd) => rd
+                .filter_map(|e| e.ok())
+                .filter_map(|e| archive::backup_point_name(&e.path()).map(|name| (name, e.path())))
+                .collect(),
+            Err(_) => return false,
+        };
+
+        // Oldest first, whatever the name template puts before the time.
+        points.sort_by_key(|(name, _)| {
+            point_name::parse(name).map(|name| name.timestamp.to_string())
+        });
+        let is_full = |name: &str| point_name::parse(name).is_some_and(|name| name.is_full);
+
+        // Find the latest full backup as the chain start
+        if !points.iter().any(|(name, _)| is_full(name)) {
+            return false;
+        }
+
+        // Validate each delta file in inc backups can be loaded
+        for (_, inc_path) in points.iter().filter(|(name, _)| !is_full(name)) {
+            let checked = if inc_path.is_file() {
+                Self::validate_delta_files_in_archive(inc_path)
+            } else {
+                Self::validate_delta_files_in_dir(inc_path)
+            };
+            if checked.is_err() {
+                warn!("Corrupted delta found in {:?}", inc_path);
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Calculate minimum backup interval based on source size (directory or
+    /// single file).
+    /// - up to 10MB: 1 second
+    /// - up to 100MB: 1 minute
+    /// - up to 1GB: 1 hour
+    /// - above 1GB: 1 hour per GB
+    pub fn calculate_min_interval_by_size(source_dir: &Path) -> StdDuration {
+        let total_bytes = Self::calculate_dir_size(source_dir);
+        let mb = total_bytes as f64 / (1024.0 * 1024.0);
+        let gb = total_bytes as f64 / (1024.0 * 1024.0 * 1024.0);
+
+        if mb <= 10.0 {
+            StdDuration::from_secs(1)
+        } else if mb <= 100.0 {
+            StdDuration::from_secs(60)
+        } else if gb <= 1.0 {
+            StdDuration::from_secs(3600)
+        } else {
+            let hours = gb.ceil() as u64;
+            StdDuration::from_secs(hours * 3600)
+        }
+    }
+
+    fn calculate_dir_size(dir: &Path) -> u64 {
+        if dir.is_file() {
+            return fs::metadata(dir).map(|m| m.len()).unwrap_or(0);
+        }
+        let mut total: u64 = 0;
+        if let Ok(entries) = fs::read_dir(dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    total += Self::calculate_dir_size(&path);
+                } else if let Ok(meta) = fs::metadata(&path) {
+                    total += meta.len();
+                }
+            }
+        }
+        total
+    }
+
+    pub(super) fn validate_delta_files_in_dir(dir: &Path) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            if path.is_dir() {
+                Self::validate_delta_files_in_dir(&path)?;
+            } else {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                if delta::is_delta_name(&name) {
+                    // Try to load and verify the delta file
+                    delta::load_delta(&path)
+                        .with_context(|| format!("Failed to load delta: {:?}", path))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reading each entry also verifies its checksum, so this catches both
+    /// undecodable deltas and damaged archive data.
+    pub(super) fn validate_delta_files_in_archive(archive_path: &Path) -> Result<()> {
+        let mut reader = ArchiveReader::open_with_env(archive_path)?;
+        let deltas: Vec<_> = reader
+            .entries()
+            .iter()
+            .filter(|entry| delta::is_delta_name(&entry.path))
+            .cloned()
+            .collect();
+        for entry in &deltas {
+            let content = reader.read_entry(entry)?;
+            delta::parse_delta(&content).with_context(|| {
+                format!("Failed to load delta {} in {:?}", entry.path, archive_path)
+            })?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::delta;
+use crate::reflink::{self, CopyMethod};
+use crate::units;
+
+/// Files the sample is split into for the copy runs, so every worker
+/// count up to this has work to share.
+const COPY_FILES: usize = 8;
+/// Worker counts the copy run is timed with.
+const WORKER_COUNTS: [usize; 4] = [1, 2, 4, 8];
+/// Block sizes the delta run is timed with.
+const BLOCK_SIZES: [usize; 5] = [1024, 4096, 16 * 1024, 64 * 1024, 256 * 1024];
+/// A worker count or block size is only worth it while it keeps this share
+/// of the best throughput.
+const GOOD_ENOUGH: f64 = 0.9;
+/// One byte is changed every this many bytes for the delta run, like
+/// scattered edits of a database or disk image.
+const EDIT_STRIDE: usize = 200 * 1024;
+
+#[derive(Debug, Serialize)]
+pub struct HashResult {
+    pub algorithm: &'static str,
+    pub mb_per_sec: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CopyResult {
+    pub workers: usize,
+    pub mb_per_sec: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeltaResult {
+    pub block_size: usize,
+    pub mb_per_sec: f64,
+    /// Encoded size of the delta of the edited sample.
+    pub delta_bytes: u64,
+}
+
+/// A setting and the value the measurements suggest for it.
+#[derive(Debug, Serialize)]
+pub struct Recommendation {
+    pub setting: &'static str,
+    pub value: String,
+    pub reason: String,
+}
+
+/// Outcome of `ardiex bench`.
+#[derive(Debug, Serialize)]
+pub struct BenchReport {
+    pub dir: PathBuf,
+    pub sample_bytes: u64,
+    pub hash: Vec<HashResult>,
+    /// `reflink` when the copies were clones, which makes them nearly
+    /// free and the copy figures meaningless, otherwise `copy`.
+    pub copy_method: &'static str,
+    pub copy: Vec<CopyResult>,
+    pub delta: Vec<DeltaResult>,
+    pub recommendations: Vec<Recommendation>,
+}
+
+fn mb_per_sec(bytes: u64, started: Instant) -> f64 {
+    bytes as f64 / 1_000_000.0 / started.elapsed().as_secs_f64().max(1e-9)
+}
+
+/// `len` bytes that are half noise and half repeated text, so neither
+/// compression nor hashing sees unusually friendly data.
+fn sample_data(len: usize) -> Vec<u8> {
+    const TEXT: &[u8] = b"2026-01-01 12:00:00 INFO request served in 12ms status=200 path=/index\n";
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut data = Vec::with_capacity(len);
+    while data.len() < len {
+        for _ in 0..512 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            data.extend_from_slice(&state.to_le_bytes());
+        }
+        data.extend(TEXT.iter().cycle().take(4096));
+    }
+    data.truncate(len);
+    data
+}
+
+/// Measure hashing, copying and delta creation on `sample_bytes` of
+/// generated data in a scratch dir under `dir`, which should be on the
+/// volume backups are written to. The scratch dir is removed afterwards.
+pub fn run(dir: &Path, sample_bytes: u64) -> Result<BenchReport> {
+    let work = dir.join(format!(".ardiex-bench-{}", std::process::id()));
+    fs::create_dir_all(&work).with_context(|| format!("Failed to create {:?}", work))?;
+    let report = run_in(dir, &work, sample_bytes);
+    let _ = fs::remove_dir_all(&work);
+    report
+}
+
+fn run_in(dir: &Path, work: &Path, sample_bytes: u64) -> Result<BenchReport> {
+    let len = usize::try_from(sample_bytes).context("Sample size does not fit in memory")?;
+    let data = sample_data(len);
+
+    let started = Instant::now();
+    std::hint::black_box(Sha256::digest(&data));
+    let sha256 = mb_per_sec(sample_bytes, started);
+    let started = Instant::now();
+    std::hint::black_box(blake3::hash(&data));
+    let blake3 = mb_per_sec(sample_bytes, started);
+    let hash = vec![
+        HashResult {
+            algorithm: "sha256",
+            mb_per_sec: sha256,
+        },
+        HashResult {
+            algorithm: "blake3",
+            mb_per_sec: blake3,
+        },
+    ];
+
+    let source = work.join("source");
+    fs::create_dir_all(&source)?;
+    let files: Vec<PathBuf> = data
+        .chunks(len.div_ceil(COPY_FILES).max(1))
+        .enumerate()
+        .map(|(i, part)| {
+            let path = source.join(format!("file{}.bin", i));
+            fs::write(&path, part).map(|_| path)
+        })
+        .collect::<std::io::Result<_>>()?;
+    let mut copy = Vec::new();
+    let mut copy_method = "copy";
+    for workers in WORKER_COUNTS {
+        let target = work.join(format!("copy{}", workers));
+        fs::create_dir_all(&target)?;
+        let started = Instant::now();
+        if copy_files(&files, &target, workers)? {
+            copy_method = "reflink";
+        }
+        copy.push(CopyResult {
+            workers,
+            mb_per_sec: mb_per_sec(sample_bytes, started),
+        });
+        fs::remove_dir_all(&target)?;
+    }
+
+    let original = work.join("original.bin");
+    let edited = work.join("edited.bin");
+    fs::write(&original, &data)?;
+    let mut changed = data;
+    for i in (0..changed.len()).step_by(EDIT_STRIDE) {
+        changed[i] ^= 0xff;
+    }
+    fs::write(&edited, &changed)?;
+    let mut delta = Vec::new();
+    for block_size in BLOCK_SIZES {
+        let started = Instant::now();
+        let made = delta::create_delta_with_block_size(&original, &edited, block_size, None)?;
+        let mb_per_sec = mb_per_sec(sample_bytes, started);
+        delta.push(DeltaResult {
+            block_size,
+            mb_per_sec,
+            delta_bytes: delta::encode_delta(&made)?.len() as u64,
+        });
+    }
+
+    let mut recommendations = Vec::new();
+    if let Some(recommendation) = recommend_workers(&copy) {
+        recommendations.push(recommendation);
+    }
+    if let Some(recommendation) = recommend_block_size(&delta) {
+        recommendations.push(recommendation);
+    }
+    Ok(BenchReport {
+        dir: dir.to_path_buf(),
+        sample_bytes,
+        hash,
+        copy_method,
+        copy,
+        delta,
+        recommendations,
+    })
+}
+
+/// Copy `files` into `target` on `workers` threads, flushing each copy to
+/// disk. Returns whether any copy was a reflink clone.
+fn copy_files(files: &[PathBuf], target: &Path, workers: usize) -> Result<bool> {
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|worker| {
+                scope.spawn(move || -> Result<bool> {
+                    let mut cloned = false;
+                    for file in files.iter().skip(worker).step_by(workers) {
+                        let Some(name) = file.file_name() else {
+                            continue;
+                        };
+                        let copied = target.join(name);
+                        cloned |= reflink::copy_file(file, &copied, None)? == CopyMethod::Reflink;
+                        fs::File::open(&copied)?.sync_all()?;
+                    }
+                    Ok(cloned)
+                })
+            })
+            .collect();
+        let mut cloned = false;
+        for handle in handles {
+            cloned |= handle.join().expect("copy benchmark thread panicked")?;
+        }
+        Ok(cloned)
+    })
+}
+
+/// Fewest workers that copy within `GOOD_ENOUGH` of the fastest run; more
+/// concurrent backups than that only compete for the disk.
+fn recommend_workers(copy: &[CopyResult]) -> Option<Recommendation> {
+    let best = copy.iter().map(|run| run.mb_per_sec).fold(0.0, f64::max);
+    let chosen = copy
+        .iter()
+        .find(|run| run.mb_per_sec >= best * GOOD_ENOUGH)?;
+    Some(Recommendation {
+        setting: "max_concurrent_backups",
+        value: chosen.workers.to_string(),
+        reason: format!(
+            "{} parallel copies reach {:.0} MB/s of the best {:.0} MB/s",
+            chosen.workers, chosen.mb_per_sec, best
+        ),
+    })
+}
+
+/// Block size with the smallest delta among those that hash within
+/// `GOOD_ENOUGH` of the fastest one.
+fn recommend_block_size(delta: &[DeltaResult]) -> Option<Recommendation> {
+    let best = delta.iter().map(|run| run.mb_per_sec).fold(0.0, f64::max);
+    let chosen = delta
+        .iter()
+        .filter(|run| run.mb_per_sec >= best * GOOD_ENOUGH)
+        .min_by_key(|run| run.delta_bytes)?;
+    Some(Recommendation {
+        setting: "delta_block_size",
+        value: chosen.block_size.to_string(),
+        reason: format!(
+            "smallest delta of scattered edits ({}) among block sizes within 90% of the \
+             fastest; creates deltas at {:.0} MB/s",
+            units::format_size(chosen.delta_bytes),
+            chosen.mb_per_sec
+        ),
+    })
+}
+
+#[cfg(test)]
+#[path = "tests/bench_tests.rs"]
+mod tests;
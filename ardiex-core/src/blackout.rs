@@ -0,0 +1,177 @@
+use anyhow::{Context, Result};
+use chrono::{Datelike, Duration, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+const ALL_DAYS: u8 = 0b111_1111;
+const DAY_NAMES: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Local-time window in which scheduled and event-driven backups wait,
+/// written as `[DAYS ]HH:MM-HH:MM`, e.g. `Mon-Fri 09:00-18:00` or
+/// `Sat,Sun 00:00-24:00`. Without days it applies every day. An end at or
+/// before the start runs past midnight into the next day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct BlackoutWindow {
+    /// Bit `n` set for the day `n` days after Monday the window starts on.
+    days: u8,
+    start: u32,
+    end: u32,
+}
+
+impl BlackoutWindow {
+    /// End of this window if `at` falls inside it.
+    fn active_until(&self, at: NaiveDateTime) -> Option<NaiveDateTime> {
+        // A window crossing midnight may have started the day before.
+        (0..=1).find_map(|days_back| {
+            let day = at.date() - Duration::days(days_back);
+            if self.days & (1 << day.weekday().num_days_from_monday()) == 0 {
+                return None;
+            }
+            let day_start = day.and_time(NaiveTime::MIN);
+            let start = day_start + Duration::minutes(self.start.into());
+            let mut end = day_start + Duration::minutes(self.end.into());
+            if self.end <= self.start {
+                end += Duration::days(1);
+            }
+            (start <= at && at < end).then_some(end)
+        })
+    }
+}
+
+/// When the blackout covering `at` ends, following windows that start
+/// before the previous one ends; `None` outside every window.
+pub fn blackout_until(windows: &[BlackoutWindow], at: NaiveDateTime) -> Option<NaiveDateTime> {
+    let mut until = at;
+    // Bounded: back-to-back windows cannot chain for more than a week.
+    for _ in 0..(windows.len() * 8) {
+        match windows.iter().filter_map(|w| w.active_until(until)).max() {
+            Some(end) => until = end,
+            None => break,
+        }
+    }
+    (until != at).then_some(until)
+}
+
+fn parse_weekday(name: &str) -> Result<u32> {
+    name.parse::<Weekday>()
+        .map(|day| day.num_days_from_monday())
+        .map_err(|_| anyhow::anyhow!("Unknown day '{}' (use Mon..Sun)", name))
+}
+
+fn parse_days(spec: &str) -> Result<u8> {
+    let mut days = 0u8;
+    for part in spec.split(',') {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (parse_weekday(first)?, parse_weekday(last)?),
+            None => {
+                let day = parse_weekday(part)?;
+                (day, day)
+            }
+        };
+        // Ranges may wrap around the week, e.g. `Fri-Mon`.
+        let mut day = first;
+        loop {
+            days |= 1 << day;
+            if day == last {
+                break;
+            }
+            day = (day + 1) % 7;
+        }
+    }
+    Ok(days)
+}
+
+fn parse_minutes(time: &str) -> Result<u32> {
+    let (hours, minutes) = time
+        .split_once(':')
+        .ok_or_else(|| anyhow::anyhow!("Expected HH:MM, got '{}'", time))?;
+    let hours: u32 = hours
+        .parse()
+        .with_context(|| format!("Invalid hour in '{}'", time))?;
+    let minutes: u32 = minutes
+        .parse()
+        .with_context(|| format!("Invalid minute in '{}'", time))?;
+    let total = hours * 60 + minutes;
+    if minutes >= 60 || total > MINUTES_PER_DAY {
+        anyhow::bail!("Time out of range: '{}'", time);
+    }
+    Ok(total)
+}
+
+impl FromStr for BlackoutWindow {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim();
+        let (days, times) = match value.rsplit_once(char::is_whitespace) {
+            Some((days, times)) => (parse_days(days.trim())?, times),
+            None => (ALL_DAYS, value),
+        };
+        let (start, end) = times
+            .split_once('-')
+            .ok_or_else(|| anyhow::anyhow!("Expected HH:MM-HH:MM, got '{}'", times))?;
+        let (start, end) = (parse_minutes(start)?, parse_minutes(end)?);
+        if start == MINUTES_PER_DAY {
+            anyhow::bail!("A blackout window cannot start at 24:00");
+        }
+        Ok(Self { days, start, end })
+    }
+}
+
+impl fmt::Display for BlackoutWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.days != ALL_DAYS {
+            // Runs of three or more days are written as ranges (`Mon-Fri`).
+            let mut parts = Vec::new();
+            let mut day = 0;
+            while day < 7 {
+                if self.days & (1 << day) == 0 {
+                    day += 1;
+                    continue;
+                }
+                let first = day;
+                while day + 1 < 7 && self.days & (1 << (day + 1)) != 0 {
+                    day += 1;
+                }
+                match day - first {
+                    0 => parts.push(DAY_NAMES[first].to_string()),
+                    1 => parts.extend([DAY_NAMES[first], DAY_NAMES[day]].map(String::from)),
+                    _ => parts.push(format!("{}-{}", DAY_NAMES[first], DAY_NAMES[day])),
+                }
+                day += 1;
+            }
+            write!(f, "{} ", parts.join(","))?;
+        }
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start / 60,
+            self.start % 60,
+            self.end / 60,
+            self.end % 60
+        )
+    }
+}
+
+impl TryFrom<String> for BlackoutWindow {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value
+            .parse()
+            .with_context(|| format!("Invalid blackout window '{}'", value))
+    }
+}
+
+impl From<BlackoutWindow> for String {
+    fn from(window: BlackoutWindow) -> Self {
+        window.to_string()
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/blackout_tests.rs"]
+mod tests;
@@ -0,0 +1,640 @@
+use anyhow::Result;
+use log::{debug, info, warn};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::JournalCursor;
+
+/// Where the change journal of the volume holding `source_dir` stands now,
+/// or `None` without one (Linux, FAT/network volumes, or no privilege to
+/// read it: the USN journal needs an administrator). Taken before a scan
+/// starts, so changes made while it runs are read again next time.
+pub fn current_cursor(source_dir: &Path) -> Option<JournalCursor> {
+    let root = fs::canonicalize(source_dir).ok()?;
+    match platform::current_cursor(&root) {
+        Ok(cursor) => cursor,
+        Err(e) => {
+            debug!("No change journal for {:?}: {:#}", source_dir, e);
+            None
+        }
+    }
+}
+
+/// Paths under `source_dir`, relative to it, that the journal recorded as
+/// created, modified, renamed or removed between `since` and `until`.
+/// `None` when the journal cannot account for every change in between
+/// (it was reset or wrapped, dropped events, or names a path it cannot
+/// place), which calls for a full scan.
+pub fn changed_since(
+    source_dir: &Path,
+    since: &JournalCursor,
+    until: &JournalCursor,
+) -> Option<BTreeSet<PathBuf>> {
+    if since.journal != until.journal || since.position > until.position {
+        info!(
+            "[{:?}] Change journal was reset since the last backup",
+            source_dir
+        );
+        return None;
+    }
+    let root = fs::canonicalize(source_dir).ok()?;
+    match platform::read_events(&root, since, until) {
+        Ok(Some(events)) => relative_changes(&root, events),
+        Ok(None) => {
+            info!(
+                "[{:?}] Change journal no longer covers every change since the last backup",
+                source_dir
+            );
+            None
+        }
+        Err(e) => {
+            warn!(
+                "[{:?}] Failed to read the change journal: {:#}",
+                source_dir, e
+            );
+            None
+        }
+    }
+}
+
+/// The journal's `(path, renamed or removed)` events under `root`, relative
+/// to it. Journals cover the whole volume, so events elsewhere are dropped;
+/// `None` when `root` itself or a directory above it was moved or removed.
+fn relative_changes(
+    root: &Path,
+    events: impl IntoIterator<Item = (PathBuf, bool)>,
+) -> Option<BTreeSet<PathBuf>> {
+    let mut changed = BTreeSet::new();
+    for (path, structural) in events {
+        match path.strip_prefix(root) {
+            Ok(relative) if !relative.as_os_str().is_empty() => {
+                changed.insert(relative.to_path_buf());
+            }
+            _ if structural && root.starts_with(&path) => return None,
+            _ => {}
+        }
+    }
+    Some(changed)
+}
+
+/// The NTFS update sequence number journal, read through the volume handle.
+#[cfg(all(windows, feature = "native-change-journal"))]
+mod platform {
+    use super::*;
+    use std::collections::HashMap;
+    use std::ffi::{OsString, c_void};
+    use std::fs::{File, OpenOptions};
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::{AsRawHandle, FromRawHandle, OwnedHandle, RawHandle};
+
+    const FSCTL_QUERY_USN_JOURNAL: u32 = 0x0009_00f4;
+    const FSCTL_READ_USN_JOURNAL: u32 = 0x0009_00bb;
+    const GENERIC_READ: u32 = 0x8000_0000;
+    const FILE_SHARE_ALL: u32 = 0x1 | 0x2 | 0x4;
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+    const ERROR_JOURNAL_ENTRY_DELETED: i32 = 1181;
+    const USN_REASON_FILE_DELETE: u32 = 0x0000_0200;
+    const USN_REASON_RENAME_OLD_NAME: u32 = 0x0000_1000;
+    const USN_REASON_RENAME_NEW_NAME: u32 = 0x0000_2000;
+    /// Fixed part of a USN_RECORD_V2, up to its file name.
+    const RECORD_HEADER_LEN: usize = 60;
+    /// Parent directories followed before a name is given up on.
+    const MAX_DEPTH: usize = 256;
+
+    /// USN_JOURNAL_DATA_V0, with room for the V1/V2 fields newer systems
+    /// fill in.
+    #[repr(C)]
+    #[allow(dead_code)] // Fields are read or written by the kernel.
+    #[derive(Default)]
+    struct UsnJournalData {
+        journal_id: u64,
+        first_usn: i64,
+        next_usn: i64,
+        lowest_valid_usn: i64,
+        max_usn: i64,
+        maximum_size: u64,
+        allocation_delta: u64,
+        newer_versions: [u64; 3],
+    }
+
+    /// READ_USN_JOURNAL_DATA_V0, which returns USN_RECORD_V2 records.
+    #[repr(C)]
+    #[allow(dead_code)] // Fields are read or written by the kernel.
+    struct ReadUsnJournalData {
+        start_usn: i64,
+        reason_mask: u32,
+        return_only_on_close: u32,
+        timeout: u64,
+        bytes_to_wait_for: u64,
+        usn_journal_id: u64,
+    }
+
+    /// FILE_ID_DESCRIPTOR with a 64-bit file reference (FileIdType).
+    #[repr(C)]
+    #[allow(dead_code)] // Fields are read or written by the kernel.
+    struct FileIdDescriptor {
+        size: u32,
+        kind: u32,
+        id: [u64; 2],
+    }
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn DeviceIoControl(
+            device: RawHandle,
+            code: u32,
+            input: *const c_void,
+            input_len: u32,
+            output: *mut c_void,
+            output_len: u32,
+            returned: *mut u32,
+            overlapped: *mut c_void,
+        ) -> i32;
+        fn GetVolumePathNameW(path: *const u16, volume: *mut u16, len: u32) -> i32;
+        fn OpenFileById(
+            hint: RawHandle,
+            id: *const FileIdDescriptor,
+            access: u32,
+            share: u32,
+            security: *mut c_void,
+            flags: u32,
+        ) -> RawHandle;
+        fn GetFinalPathNameByHandleW(file: RawHandle, path: *mut u16, len: u32, flags: u32) -> u32;
+    }
+
+    pub(super) fn current_cursor(root: &Path) -> Result<Option<JournalCursor>> {
+        let volume = open_volume(root)?;
+        let journal = query_journal(&volume)?;
+        Ok(Some(JournalCursor {
+            journal: format!("usn:{:x}", journal.journal_id),
+            position: journal.next_usn as u64,
+        }))
+    }
+
+    pub(super) fn read_events(
+        root: &Path,
+        since: &JournalCursor,
+        until: &JournalCursor,
+    ) -> Result<Option<Vec<(PathBuf, bool)>>> {
+        let volume = open_volume(root)?;
+        let journal = query_journal(&volume)?;
+        if format!("usn:{:x}", journal.journal_id) != since.journal
+            || (since.position as i64) < journal.lowest_valid_usn.max(journal.first_usn)
+        {
+            return Ok(None);
+        }
+
+        // (file, parent, reason, name) of every record in range.
+        let mut records = Vec::new();
+        let mut buffer = vec![0u64; 8192];
+        let mut next = since.position as i64;
+        'read: while next < until.position as i64 {
+            let input = ReadUsnJournalData {
+                start_usn: next,
+                reason_mask: u32::MAX,
+                return_only_on_close: 0,
+                timeout: 0,
+                bytes_to_wait_for: 0,
+                usn_journal_id: journal.journal_id,
+            };
+            let returned = match ioctl(&volume, FSCTL_READ_USN_JOURNAL, &input, &mut buffer) {
+                Ok(returned) => returned,
+                Err(e) if e.raw_os_error() == Some(ERROR_JOURNAL_ENTRY_DELETED) => {
+                    return Ok(None);
+                }
+                Err(e) => return Err(e.into()),
+            };
+            // SAFETY: the u64 buffer is valid for its byte length and more
+            // strictly aligned than u8.
+            let bytes: &[u8] =
+                unsafe { std::slice::from_raw_parts(buffer.as_ptr().cast(), buffer.len() * 8) };
+            let bytes = &bytes[..returned.min(bytes.len())];
+            if bytes.len() < 8 {
+                break;
+            }
+            let following = i64::from_le_bytes(bytes[..8].try_into()?);
+            let mut offset = 8;
+            while offset + RECORD_HEADER_LEN <= bytes.len() {
+                let record = &bytes[offset..];
+                let len = u32::from_le_bytes(record[..4].try_into()?) as usize;
+                if len < RECORD_HEADER_LEN || len > record.len() {
+                    break;
+                }
+                if u16::from_le_bytes(record[4..6].try_into()?) != 2 {
+                    return Ok(None);
+                }
+                if i64::from_le_bytes(record[24..32].try_into()?) >= until.position as i64 {
+                    break 'read;
+                }
+                let file = u64::from_le_bytes(record[8..16].try_into()?);
+                let parent = u64::from_le_bytes(record[16..24].try_into()?);
+                let reason = u32::from_le_bytes(record[40..44].try_into()?);
+                let name_len = u16::from_le_bytes(record[56..58].try_into()?) as usize;
+                let name_offset = u16::from_le_bytes(record[58..60].try_into()?) as usize;
+                let Some(name) = record.get(name_offset..name_offset + name_len) else {
+                    return Ok(None);
+                };
+                let name: Vec<u16> = name
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect();
+                records.push((file, parent, reason, OsString::from_wide(&name)));
+                offset += len;
+            }
+            if following <= next {
+                break;
+            }
+            next = following;
+        }
+
+        let mut resolver = Resolver {
+            volume: &volume,
+            names: HashMap::new(),
+            paths: HashMap::new(),
+        };
+        // Names as first recorded, for directories removed since.
+        for (file, parent, _, name) in &records {
+            resolver
+                .names
+                .entry(*file)
+                .or_insert_with(|| (*parent, name.clone()));
+        }
+        let mut events = Vec::with_capacity(records.len());
+        for (_, parent, reason, name) in records {
+            let Some(parent) = resolver.path_of(parent, MAX_DEPTH) else {
+                return Ok(None);
+            };
+            let structural = reason
+                & (USN_REASON_FILE_DELETE
+                    | USN_REASON_RENAME_OLD_NAME
+                    | USN_REASON_RENAME_NEW_NAME)
+                != 0;
+            events.push((parent.join(name), structural));
+        }
+        Ok(Some(events))
+    }
+
+    /// Paths of file references, opened by id while they exist and pieced
+    /// together from the journal's own records once they are gone.
+    struct Resolver<'a> {
+        volume: &'a File,
+        names: HashMap<u64, (u64, OsString)>,
+        paths: HashMap<u64, Option<PathBuf>>,
+    }
+
+    impl Resolver<'_> {
+        fn path_of(&mut self, id: u64, depth: usize) -> Option<PathBuf> {
+            if let Some(path) = self.paths.get(&id) {
+                return path.clone();
+            }
+            let path = open_path(self.volume, id).or_else(|| {
+                if depth == 0 {
+                    return None;
+                }
+                let (parent, name) = self.names.get(&id).cloned()?;
+                Some(self.path_of(parent, depth - 1)?.join(name))
+            });
+            self.paths.insert(id, path.clone());
+            path
+        }
+    }
+
+    /// `\\.\C:` (or `\\.\Volume{…}`) for the volume holding `root`.
+    fn open_volume(root: &Path) -> Result<File> {
+        let wide: Vec<u16> = root.as_os_str().encode_wide().chain([0]).collect();
+        let mut volume = vec![0u16; 512];
+        // SAFETY: both buffers are NUL-terminated/sized as passed.
+        if unsafe { GetVolumePathNameW(wide.as_ptr(), volume.as_mut_ptr(), volume.len() as u32) }
+            == 0
+        {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let len = volume.iter().position(|&c| c == 0).unwrap_or(volume.len());
+        let volume = OsString::from_wide(&volume[..len])
+            .to_string_lossy()
+            .into_owned();
+        let volume = volume.trim_end_matches('\\');
+        let device = match volume.strip_prefix(r"\\?\") {
+            Some(rest) => format!(r"\\.\{}", rest),
+            None => format!(r"\\.\{}", volume),
+        };
+        Ok(OpenOptions::new()
+            .access_mode(GENERIC_READ)
+            .share_mode(FILE_SHARE_ALL)
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+            .open(&device)?)
+    }
+
+    fn query_journal(volume: &File) -> Result<UsnJournalData> {
+        let mut data = UsnJournalData::default();
+        let mut returned = 0u32;
+        // SAFETY: no input; the output is a plain struct of its own size.
+        let ok = unsafe {
+            DeviceIoControl(
+                volume.as_raw_handle(),
+                FSCTL_QUERY_USN_JOURNAL,
+                std::ptr::null(),
+                0,
+                (&mut data as *mut UsnJournalData).cast(),
+                size_of::<UsnJournalData>() as u32,
+                &mut returned,
+                std::ptr::null_mut(),
+            )
+        };
+        if ok == 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(data)
+    }
+
+    fn ioctl(
+        volume: &File,
+        code: u32,
+        input: &ReadUsnJournalData,
+        output: &mut [u64],
+    ) -> std::io::Result<usize> {
+        let mut returned = 0u32;
+        // SAFETY: input and output are valid for the sizes passed.
+        let ok = unsafe {
+            DeviceIoControl(
+                volume.as_raw_handle(),
+                code,
+                (input as *const ReadUsnJournalData).cast(),
+                size_of::<ReadUsnJournalData>() as u32,
+                output.as_mut_ptr().cast(),
+                (output.len() * 8) as u32,
+                &mut returned,
+                std::ptr::null_mut(),
+            )
+        };
+        if ok == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(returned as usize)
+    }
+
+    fn open_path(volume: &File, id: u64) -> Option<PathBuf> {
+        let descriptor = FileIdDescriptor {
+            size: size_of::<FileIdDescriptor>() as u32,
+            kind: 0,
+            id: [id, 0],
+        };
+        // SAFETY: the descriptor outlives the call; an invalid handle is
+        // checked before it is wrapped.
+        let raw = unsafe {
+            OpenFileById(
+                volume.as_raw_handle(),
+                &descriptor,
+                0,
+                FILE_SHARE_ALL,
+                std::ptr::null_mut(),
+                FILE_FLAG_BACKUP_SEMANTICS,
+            )
+        };
+        if raw.is_null() || raw as isize == -1 {
+            return None;
+        }
+        // SAFETY: a freshly opened handle owned by nothing else.
+        let handle = unsafe { OwnedHandle::from_raw_handle(raw) };
+        let mut path = vec![0u16; 512];
+        loop {
+            // SAFETY: the buffer holds `path.len()` characters.
+            let len = unsafe {
+                GetFinalPathNameByHandleW(
+                    handle.as_raw_handle(),
+                    path.as_mut_ptr(),
+                    path.len() as u32,
+                    0,
+                )
+            } as usize;
+            if len == 0 {
+                return None;
+            }
+            if len < path.len() {
+                return Some(PathBuf::from(OsString::from_wide(&path[..len])));
+            }
+            path.resize(len + 1, 0);
+        }
+    }
+}
+
+/// The FSEvents history kept per volume, replayed from a stored event id.
+#[cfg(all(target_os = "macos", feature = "native-change-journal"))]
+mod platform {
+    use super::*;
+    use fsevent_sys as fse;
+    use fsevent_sys::core_foundation as cf;
+    use std::ffi::{CStr, CString, OsStr, c_char, c_void};
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::MetadataExt;
+    use std::time::{Duration, Instant};
+
+    /// Longest wait for FSEvents to finish replaying the history.
+    const HISTORY_TIMEOUT: Duration = Duration::from_secs(120);
+    /// Events that mean some changes were not recorded individually.
+    const INCOMPLETE: fse::FSEventStreamEventFlags = fse::kFSEventStreamEventFlagMustScanSubDirs
+        | fse::kFSEventStreamEventFlagUserDropped
+        | fse::kFSEventStreamEventFlagKernelDropped
+        | fse::kFSEventStreamEventFlagEventIdsWrapped
+        | fse::kFSEventStreamEventFlagRootChanged;
+    const STRUCTURAL: fse::FSEventStreamEventFlags =
+        fse::kFSEventStreamEventFlagItemRemoved | fse::kFSEventStreamEventFlagItemRenamed;
+
+    #[link(name = "CoreServices", kind = "framework")]
+    unsafe extern "C" {
+        fn FSEventsCopyUUIDForDevice(dev: libc::dev_t) -> cf::CFRef;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    unsafe extern "C" {
+        fn CFUUIDCreateString(allocator: cf::CFAllocatorRef, uuid: cf::CFRef) -> cf::CFStringRef;
+        fn CFRunLoopRunInMode(mode: cf::CFStringRef, seconds: f64, once: cf::Boolean) -> i32;
+    }
+
+    pub(super) fn current_cursor(root: &Path) -> Result<Option<JournalCursor>> {
+        let Some(journal) = volume_uuid(root)? else {
+            return Ok(None);
+        };
+        // SAFETY: no arguments; reads the system-wide latest event id.
+        let position = unsafe { fse::FSEventsGetCurrentEventId() };
+        Ok(Some(JournalCursor { journal, position }))
+    }
+
+    /// UUID of the FSEvents database for `root`'s volume; `None` when the
+    /// volume keeps no history (read-only or excluded volumes).
+    fn volume_uuid(root: &Path) -> Result<Option<String>> {
+        let device = fs::metadata(root)?.dev() as libc::dev_t;
+        let mut uuid = [0 as c_char; 64];
+        // SAFETY: every CF object created here is released before return;
+        // the buffer size is passed along.
+        let copied = unsafe {
+            let raw = FSEventsCopyUUIDForDevice(device);
+            if raw.is_null() {
+                return Ok(None);
+            }
+            let string = CFUUIDCreateString(cf::kCFAllocatorDefault, raw);
+            cf::CFRelease(raw);
+            if string.is_null() {
+                return Ok(None);
+            }
+            let copied = cf::CFStringGetCString(
+                string,
+                uuid.as_mut_ptr(),
+                uuid.len() as cf::CFIndex,
+                cf::kCFStringEncodingUTF8,
+            );
+            cf::CFRelease(string);
+            copied
+        };
+        if !copied {
+            return Ok(None);
+        }
+        // SAFETY: CFStringGetCString NUL-terminated the buffer.
+        let uuid = unsafe { CStr::from_ptr(uuid.as_ptr()) };
+        Ok(Some(format!("fsevents:{}", uuid.to_string_lossy())))
+    }
+
+    struct History {
+        until: u64,
+        events: Vec<(PathBuf, bool)>,
+        complete: bool,
+        done: bool,
+        run_loop: cf::CFRunLoopRef,
+    }
+
+    extern "C" fn on_events(
+        _stream: fse::FSEventStreamRef,
+        info: *mut c_void,
+        count: usize,
+        paths: *mut c_void,
+        flags: *const fse::FSEventStreamEventFlags,
+        ids: *const fse::FSEventStreamEventId,
+    ) {
+        // SAFETY: `info` is the History `read_events` handed to the stream;
+        // without kFSEventStreamCreateFlagUseCFTypes `paths` is a C string
+        // array, and all three arrays hold `count` entries.
+        unsafe {
+            let history = &mut *info.cast::<History>();
+            let paths = paths.cast::<*const c_char>();
+            for i in 0..count {
+                let flag = *flags.add(i);
+                if flag & fse::kFSEventStreamEventFlagHistoryDone != 0 {
+                    history.done = true;
+                    continue;
+                }
+                if *ids.add(i) > history.until {
+                    continue;
+                }
+                if flag & INCOMPLETE != 0 {
+                    history.complete = false;
+                }
+                let path = OsStr::from_bytes(CStr::from_ptr(*paths.add(i)).to_bytes());
+                history
+                    .events
+                    .push((PathBuf::from(path), flag & STRUCTURAL != 0));
+            }
+            if history.done {
+                cf::CFRunLoopStop(history.run_loop);
+            }
+        }
+    }
+
+    pub(super) fn read_events(
+        root: &Path,
+        since: &JournalCursor,
+        until: &JournalCursor,
+    ) -> Result<Option<Vec<(PathBuf, bool)>>> {
+        if volume_uuid(root)?.as_deref() != Some(since.journal.as_str()) {
+            return Ok(None);
+        }
+        let root_c = CString::new(root.as_os_str().as_bytes())?;
+        let mut history = History {
+            until: until.position,
+            events: Vec::new(),
+            complete: true,
+            done: false,
+            // SAFETY: returns this thread's run loop, which runs the stream.
+            run_loop: unsafe { cf::CFRunLoopGetCurrent() },
+        };
+        let history_ptr: *mut History = &mut history;
+        // SAFETY: the stream only calls back from CFRunLoopRunInMode below,
+        // while `history` is alive and only reached through `history_ptr`;
+        // the stream and CF objects are released before returning.
+        let started = unsafe {
+            let root_cf = cf::CFStringCreateWithCString(
+                cf::kCFAllocatorDefault,
+                root_c.as_ptr(),
+                cf::kCFStringEncodingUTF8,
+            );
+            let paths =
+                cf::CFArrayCreateMutable(cf::kCFAllocatorDefault, 1, &cf::kCFTypeArrayCallBacks);
+            cf::CFArrayAppendValue(paths, root_cf);
+            cf::CFRelease(root_cf);
+            let context = fse::FSEventStreamContext {
+                version: 0,
+                info: history_ptr.cast(),
+                retain: None,
+                release: None,
+                copy_description: None,
+            };
+            let stream = fse::FSEventStreamCreate(
+                cf::kCFAllocatorDefault,
+                on_events,
+                &context,
+                paths,
+                since.position,
+                0.0,
+                fse::kFSEventStreamCreateFlagFileEvents | fse::kFSEventStreamCreateFlagNoDefer,
+            );
+            cf::CFRelease(paths);
+            if stream.is_null() {
+                anyhow::bail!("FSEventStreamCreate failed for {:?}", root);
+            }
+            fse::FSEventStreamScheduleWithRunLoop(
+                stream,
+                (*history_ptr).run_loop,
+                cf::kCFRunLoopDefaultMode,
+            );
+            let started = fse::FSEventStreamStart(stream) != 0;
+            if started {
+                let deadline = Instant::now() + HISTORY_TIMEOUT;
+                while !(*history_ptr).done && Instant::now() < deadline {
+                    CFRunLoopRunInMode(cf::kCFRunLoopDefaultMode, 1.0, 0);
+                }
+                fse::FSEventStreamStop(stream);
+            }
+            fse::FSEventStreamInvalidate(stream);
+            fse::FSEventStreamRelease(stream);
+            started
+        };
+        if !started {
+            anyhow::bail!("FSEventStreamStart failed for {:?}", root);
+        }
+        Ok((history.done && history.complete).then_some(history.events))
+    }
+}
+
+/// No change journal elsewhere (inotify keeps no history), nor in builds
+/// without the `native-change-journal` feature.
+#[cfg(not(all(feature = "native-change-journal", any(windows, target_os = "macos"))))]
+mod platform {
+    use super::*;
+
+    pub(super) fn current_cursor(_root: &Path) -> Result<Option<JournalCursor>> {
+        Ok(None)
+    }
+
+    pub(super) fn read_events(
+        _root: &Path,
+        _since: &JournalCursor,
+        _until: &JournalCursor,
+    ) -> Result<Option<Vec<(PathBuf, bool)>>> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/change_journal_tests.rs"]
+mod tests;
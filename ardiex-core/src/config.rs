@@ -0,0 +1,1638 @@
+use crate::error::Error;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use crate::blackout::BlackoutWindow;
+use crate::config_format::ConfigFormat;
+use crate::config_location::{self, CONFIG_ENV_KEY, ConfigLocation, PROFILE_ENV_KEY};
+use crate::units;
+
+/// Settings file given with `--config`, set once at startup.
+static CONFIG_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+static PROFILE_OVERRIDE: OnceLock<String> = OnceLock::new();
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackupMode {
+    #[default]
+    #[serde(rename = "delta")]
+    Delta,
+    #[serde(rename = "copy")]
+    Copy,
+    /// Content-addressed chunks under `objects/` plus a per-point index,
+    /// so identical content is stored once (see `dedup.rs`).
+    #[serde(rename = "dedup")]
+    Dedup,
+    /// A browsable replica of the latest source state under `current/`
+    /// instead of point-in-time chains (see `backup/mirror.rs`).
+    #[serde(rename = "mirror")]
+    Mirror,
+}
+
+/// How a delta-mode incremental stores a changed file matching a
+/// `file_strategies` pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FileStrategy {
+    /// Block delta against the previous version, even past
+    /// `delta_max_ratio`.
+    #[serde(rename = "delta")]
+    Delta,
+    /// Full copy without computing a delta, for formats that are already
+    /// compressed. `skip-delta` is accepted as another name.
+    #[serde(rename = "copy", alias = "skip-delta")]
+    Copy,
+}
+
+/// Which version of a file delta mode keeps whole. `Forward` stores each
+/// change as a delta against the previous version; `Reverse` stores the
+/// newest version as a plain copy and turns the one before it into a
+/// delta against it, so restoring the latest point applies no deltas.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeltaLayout {
+    #[default]
+    #[serde(rename = "forward")]
+    Forward,
+    #[serde(rename = "reverse")]
+    Reverse,
+}
+
+/// Releases `ardiex update` and the startup check install from: only
+/// stable releases, or pre-releases as well.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UpdateChannel {
+    #[default]
+    #[serde(rename = "stable")]
+    Stable,
+    #[serde(rename = "beta")]
+    Beta,
+}
+
+impl UpdateChannel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UpdateChannel::Stable => "stable",
+            UpdateChannel::Beta => "beta",
+        }
+    }
+}
+
+impl FromStr for UpdateChannel {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "stable" => Ok(Self::Stable),
+            "beta" => Ok(Self::Beta),
+            _ => Err(anyhow::anyhow!(
+                "Invalid update channel: '{}'. Use 'stable' or 'beta'",
+                value
+            )),
+        }
+    }
+}
+
+/// On-disk layout of each backup: a directory tree, or one self-contained
+/// `.ardx` archive file per run (see `archive.rs`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackupFormat {
+    #[default]
+    #[serde(rename = "directory")]
+    Directory,
+    #[serde(rename = "archive")]
+    Archive,
+}
+
+/// How hard a finished backup is pushed to stable storage. `Fsync` flushes
+/// every written file and directory entry before `metadata.json` records the
+/// point, so a power cut right after a successful run cannot lose it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Durability {
+    #[default]
+    #[serde(rename = "fast")]
+    Fast,
+    #[serde(rename = "fsync")]
+    Fsync,
+}
+
+/// What to do with a source file another process holds exclusively (Outlook
+/// PSTs, running executables on Windows) once the retries are used up.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LockedFilePolicy {
+    /// Fail the backup of the source.
+    #[default]
+    #[serde(rename = "fail")]
+    Fail,
+    /// Leave the file out of this backup and report it; it is picked up by
+    /// the next backup that can read it.
+    #[serde(rename = "skip")]
+    Skip,
+}
+
+/// What to do when the backup volume lacks room for the next backup.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LowSpaceAction {
+    /// Fail the backup with a clear error.
+    #[default]
+    #[serde(rename = "abort")]
+    Abort,
+    /// Remove the oldest backups down to `low_space_min_backups` first.
+    #[serde(rename = "prune")]
+    Prune,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupConfig {
+    pub sources: Vec<SourceConfig>,
+    pub enable_periodic: bool,
+    pub enable_event_driven: bool,
+    pub exclude_patterns: Vec<String>,
+    /// When non-empty, only files matching one of these are backed up.
+    /// Applied before `exclude_patterns`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include_patterns: Vec<String>,
+    pub max_backups: usize,
+    #[serde(default)]
+    pub backup_mode: BackupMode,
+    #[serde(default)]
+    pub backup_format: BackupFormat,
+    #[serde(default)]
+    pub durability: Durability,
+    #[serde(default)]
+    pub low_space_action: LowSpaceAction,
+    /// Fewest backups `low_space_action: prune` may leave behind.
+    #[serde(default = "default_low_space_min_backups")]
+    pub low_space_min_backups: usize,
+    #[serde(default)]
+    pub locked_file_policy: LockedFilePolicy,
+    /// Retries, with doubling backoff, before a locked file falls to
+    /// `locked_file_policy`.
+    #[serde(default = "default_locked_file_retries")]
+    pub locked_file_retries: usize,
+    /// Size quota per backup dir in bytes. Retention also prunes the oldest
+    /// backups while a dir is over it (keeping the latest delta chain).
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "units::deserialize_opt_size_bytes"
+    )]
+    pub max_total_size: Option<u64>,
+    /// Run reports kept in each backup dir's `reports/`; 0 writes none.
+    #[serde(default = "default_max_backup_reports")]
+    pub max_backup_reports: usize,
+    /// Encrypt archive-format backups with a key derived from the
+    /// `ARDIEX_ARCHIVE_PASSPHRASE` environment variable.
+    #[serde(default)]
+    pub archive_encryption: bool,
+    /// Chunk store shared by every source in `dedup` mode. Unset, each
+    /// backup dir keeps its own `objects/` dir.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dedup_store: Option<PathBuf>,
+    /// Key file for HMAC-signed metadata.json and backup points. Must live
+    /// outside every backup dir.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing_key_file: Option<PathBuf>,
+    /// Record extended attributes (including POSIX ACLs, which Linux keeps
+    /// in `system.posix_acl_*`) and restore them. Off by default since not
+    /// every backup or restore filesystem supports them.
+    #[serde(default)]
+    pub preserve_xattrs: bool,
+    /// In a new full backup, hard-link files whose content matches the
+    /// previous full instead of copying them (rsnapshot style).
+    #[serde(default)]
+    pub hardlink_full_backups: bool,
+    /// In `mirror` mode, move files a run replaces or removes under
+    /// `versions/<time>/` instead of deleting them.
+    #[serde(default)]
+    pub mirror_versions: bool,
+    /// Name of new backup points, from `{type}` (`full`/`inc`),
+    /// `{timestamp}` and the source's `{label}` (see `point_name.rs`).
+    #[serde(default = "default_backup_name_template")]
+    pub backup_name_template: String,
+    /// Re-read every file written to a backup point and compare it with the
+    /// source hash before the point is committed.
+    #[serde(default)]
+    pub verify_writes: bool,
+    /// Drop file hashes no point from the latest full records during the
+    /// metadata sync at the start of each backup.
+    #[serde(default = "default_true")]
+    pub prune_stale_hashes: bool,
+    /// Largest delta an incremental stores, as a fraction of the file's
+    /// size; a bigger delta is replaced by a full copy. Unset always keeps
+    /// the delta.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta_max_ratio: Option<f64>,
+    /// Files larger than this many bytes are copied whole rather than
+    /// delta-ed, sparing the block hashing of both versions.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "units::deserialize_opt_size_bytes"
+    )]
+    pub delta_max_file_size: Option<u64>,
+    /// Bytes per block of new deltas, a power of two from 512 to 1 MiB.
+    /// Smaller blocks store less of scattered edits; larger ones hash
+    /// faster. Existing deltas keep the size they were made with.
+    #[serde(default = "default_delta_block_size")]
+    pub delta_block_size: usize,
+    /// Pattern to storage strategy for delta mode, e.g. `"*.jpg": "copy"`.
+    /// The longest matching pattern wins.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub file_strategies: BTreeMap<String, FileStrategy>,
+    /// Keep the newest version of each file whole and older ones as
+    /// reverse deltas (`reverse`), or the other way round (`forward`).
+    #[serde(default)]
+    pub delta_layout: DeltaLayout,
+    /// Reed-Solomon parity written next to each new point, as a percentage
+    /// of its size; 0 writes none.
+    #[serde(default)]
+    pub parity_percent: u32,
+    /// Mark backup dirs append-only: retention never deletes existing
+    /// points, only `ardiex prune --allow-delete` does.
+    #[serde(default)]
+    pub append_only: bool,
+    #[serde(default = "default_cron_schedule")]
+    pub cron_schedule: String,
+    /// Delay each cron trigger by a random 0..=this many seconds so sources
+    /// on the same schedule don't all hit the disk at once.
+    #[serde(default, deserialize_with = "units::deserialize_duration_secs")]
+    pub cron_jitter_secs: u64,
+    /// File-change backups only hash the paths the watcher reported; the
+    /// whole source is rescanned once this many seconds have passed since
+    /// the last full scan. 0 always scans the whole source.
+    #[serde(
+        default = "default_full_scan_interval_secs",
+        deserialize_with = "units::deserialize_duration_secs"
+    )]
+    pub full_scan_interval_secs: u64,
+    /// Take the changed paths from the filesystem's change journal (the
+    /// NTFS USN journal on Windows, FSEvents on macOS) instead of walking
+    /// and hashing the whole source, where one is available.
+    #[serde(default)]
+    pub change_journal: bool,
+    /// Local-time windows (`Mon-Fri 09:00-18:00`) in which scheduled and
+    /// event-driven backups are deferred until the window ends.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blackout_windows: Vec<BlackoutWindow>,
+    /// Sources `run` backs up at the same time when several triggers are
+    /// pending; the rest wait in the trigger queue.
+    #[serde(default = "default_max_concurrent_backups")]
+    pub max_concurrent_backups: usize,
+    /// Cap on how fast backups read source files, in MB/s.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub io_throttle_mbps: Option<f64>,
+    /// Niceness (1-19) `run` lowers itself to, so backups yield the CPU to
+    /// interactive work.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub worker_nice: Option<i32>,
+    /// Put `run` in the idle I/O scheduling class (Linux), so backups only
+    /// touch the disk when nothing else is using it.
+    #[serde(default)]
+    pub worker_ionice_idle: bool,
+    #[serde(default = "default_true")]
+    pub enable_min_interval_by_size: bool,
+    #[serde(
+        default = "default_max_log_file_size_mb",
+        deserialize_with = "units::deserialize_size_mb"
+    )]
+    pub max_log_file_size_mb: u64,
+    /// Rotated files kept per log file; older ones are deleted on rotation
+    /// and by `logs prune`.
+    #[serde(default = "default_max_rotated_log_files")]
+    pub max_rotated_log_files: usize,
+    /// Cap on the whole log directory, in bytes; `run` (hourly) and
+    /// `logs prune` delete the oldest rotated files to stay within it.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "units::deserialize_opt_size_bytes"
+    )]
+    pub max_log_dir_size: Option<u64>,
+    /// Have `run` also write each source's backup activity to its own
+    /// rotated file under `logs/sources/`.
+    #[serde(default)]
+    pub per_source_logs: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub path_mappings: Vec<PathMapping>,
+    /// Write each source's backups to `<backup_dir>/<host>/<source id>`, so
+    /// several machines can share one destination tree without their
+    /// metadata and retention touching each other's points.
+    #[serde(default)]
+    pub namespace_backups: bool,
+    /// Name of this machine in namespaced backup dirs and recorded backup
+    /// origins. Defaults to the hostname.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_id: Option<String>,
+    /// How often `run` checks whether `forward_dir` destinations are reachable.
+    #[serde(
+        default = "default_forward_interval_secs",
+        deserialize_with = "units::deserialize_duration_secs"
+    )]
+    pub forward_interval_secs: u64,
+    /// Warn when the oldest not-yet-forwarded backup is older than this.
+    #[serde(
+        default = "default_forward_alert_after_hours",
+        deserialize_with = "units::deserialize_duration_hours"
+    )]
+    pub forward_alert_after_hours: u64,
+    #[serde(default, skip_serializing_if = "NotificationConfig::is_empty")]
+    pub notifications: NotificationConfig,
+    #[serde(default)]
+    pub anomaly_detection: AnomalyDetectionConfig,
+    /// Serve Prometheus metrics on `/metrics` at this port while `run` is
+    /// active. Unset disables the exporter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_port: Option<u16>,
+    /// Address the exporter listens on; unset means loopback only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_address: Option<std::net::IpAddr>,
+    /// Serve the REST control API on this address (e.g. `127.0.0.1:8686`)
+    /// while `run` is active. Unset disables the API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_bind: Option<String>,
+    /// Bearer token every API request must carry; required with `api_bind`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_token: Option<String>,
+    /// Also serve the web dashboard on `/` of `api_bind`.
+    #[serde(default)]
+    pub api_dashboard: bool,
+    /// Directories `POST /api/restores` may write into; with none set the
+    /// API refuses restores.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub api_restore_roots: Vec<PathBuf>,
+    /// Install newer releases on start without asking; otherwise only
+    /// `ardiex update` does, after confirmation.
+    #[serde(default)]
+    pub auto_update: bool,
+    #[serde(default)]
+    pub update_channel: UpdateChannel,
+    /// Install releases without a verifiable `SHA256SUMS` signature.
+    /// Archives are still checked against `SHA256SUMS` when it exists.
+    #[serde(default)]
+    pub allow_unsigned_updates: bool,
+    pub metadata: HashMap<String, SourceMetadata>,
+}
+
+/// Maps a configured path prefix (e.g. the host path) to the path under which
+/// the same data is visible to this process (e.g. a container bind mount).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathMapping {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+impl PathMapping {
+    /// A mapping is only active where `from` is not visible but `to` is, so one
+    /// settings.json can be shared between host and container executions.
+    fn is_active(&self) -> bool {
+        !self.from.exists() && self.to.exists()
+    }
+}
+
+/// Where to POST a summary after each backup run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotificationConfig {
+    /// Generic webhook; receives the run summary as JSON.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook_url: Option<String>,
+    /// Slack incoming webhook; receives a formatted `text` message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slack_webhook_url: Option<String>,
+    #[serde(default)]
+    pub only_on_failure: bool,
+    /// Email alerts; sent only when a backup or pre-flight validation fails.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smtp: Option<SmtpConfig>,
+}
+
+impl NotificationConfig {
+    pub fn is_empty(&self) -> bool {
+        self.webhook_url.is_none() && self.slack_webhook_url.is_none() && self.smtp.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SmtpSecurity {
+    /// Plain connection upgraded with STARTTLS (usually port 587).
+    #[default]
+    #[serde(rename = "starttls")]
+    StartTls,
+    /// Implicit TLS (usually port 465).
+    #[serde(rename = "tls")]
+    Tls,
+    /// Unencrypted; only for local relays.
+    #[serde(rename = "none")]
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// Falls back to the `ARDIEX_SMTP_PASSWORD` environment variable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+impl Default for SmtpConfig {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: default_smtp_port(),
+            security: SmtpSecurity::default(),
+            username: None,
+            password: None,
+            from: String::new(),
+            to: Vec::new(),
+        }
+    }
+}
+
+/// Point-in-time snapshot taken before a source is backed up, so live
+/// databases and busy trees are read in a consistent state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum SnapshotConfig {
+    /// Read-only snapshot of the btrfs subvolume holding the source.
+    Btrfs {
+        /// Subvolume to snapshot (default: the source dir itself).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        subvolume: Option<PathBuf>,
+        /// Where the snapshot is created; must be on the same btrfs
+        /// filesystem (default: the subvolume's parent dir).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        snapshot_dir: Option<PathBuf>,
+    },
+    /// LVM snapshot of `volume`, mounted read-only for the backup.
+    Lvm {
+        /// Origin logical volume as `vg/lv`.
+        volume: String,
+        /// Where the origin volume is mounted; the source must be below it.
+        mount_point: PathBuf,
+        /// Copy-on-write space reserved for the snapshot, in `lvcreate`
+        /// syntax (default `1G`).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        size: Option<String>,
+    },
+    /// Windows Volume Shadow Copy of the volume holding the source.
+    Vss,
+}
+
+/// Pause a backup whose change pattern looks like ransomware (most files
+/// rewritten at once, or renamed to a new extension) instead of letting it
+/// rotate good backups out of retention.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnomalyDetectionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Fraction (0-1) of previously backed-up files modified in one run.
+    #[serde(default = "default_max_changed_ratio")]
+    pub max_changed_ratio: f64,
+    /// Fraction (0-1) of previously backed-up files replaced by the same
+    /// name with a different or extra extension (`a.docx` -> `a.docx.locked`).
+    #[serde(default = "default_max_extension_change_ratio")]
+    pub max_extension_change_ratio: f64,
+    /// Sources with fewer backed-up files are never paused.
+    #[serde(default = "default_anomaly_min_files")]
+    pub min_files: usize,
+}
+
+impl Default for AnomalyDetectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_changed_ratio: default_max_changed_ratio(),
+            max_extension_change_ratio: default_max_extension_change_ratio(),
+            min_files: default_anomaly_min_files(),
+        }
+    }
+}
+
+fn default_max_changed_ratio() -> f64 {
+    0.8
+}
+
+fn default_max_extension_change_ratio() -> f64 {
+    0.3
+}
+
+fn default_anomaly_min_files() -> usize {
+    20
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+fn default_cron_schedule() -> String {
+    "0 0 * * * *".to_string() // every hour
+}
+
+fn default_backup_name_template() -> String {
+    crate::point_name::DEFAULT_TEMPLATE.to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_max_log_file_size_mb() -> u64 {
+    20
+}
+
+fn default_max_rotated_log_files() -> usize {
+    30
+}
+
+fn default_forward_interval_secs() -> u64 {
+    60
+}
+
+fn default_delta_block_size() -> usize {
+    crate::delta::DEFAULT_BLOCK_SIZE
+}
+
+fn default_max_concurrent_backups() -> usize {
+    2
+}
+
+fn default_full_scan_interval_secs() -> u64 {
+    3600
+}
+
+fn default_max_backup_reports() -> usize {
+    10
+}
+
+fn default_low_space_min_backups() -> usize {
+    1
+}
+
+fn default_locked_file_retries() -> usize {
+    3
+}
+
+fn default_forward_alert_after_hours() -> u64 {
+    24
+}
+
+pub fn auto_full_backup_interval(max_backups: usize) -> usize {
+    if max_backups <= 1 { 1 } else { max_backups - 1 }
+}
+
+/// One entry of a source's `backup_dirs`: a plain path, or a table whose
+/// settings override the source's for this destination only (e.g. a local
+/// dir in delta mode every hour and a NAS in copy mode every night).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "BackupDestinationEntry", into = "BackupDestinationEntry")]
+pub struct BackupDestination {
+    pub path: PathBuf,
+    pub backup_mode: Option<BackupMode>,
+    /// `archive` stores each point as one compressed `.ardx` file.
+    pub backup_format: Option<BackupFormat>,
+    pub max_backups: Option<usize>,
+    /// Under `run`, this destination is only written once its own schedule
+    /// has come due since its last backup; other triggers skip it.
+    pub cron_schedule: Option<String>,
+}
+
+impl BackupDestination {
+    pub fn has_overrides(&self) -> bool {
+        self.backup_mode.is_some()
+            || self.backup_format.is_some()
+            || self.max_backups.is_some()
+            || self.cron_schedule.is_some()
+    }
+
+    /// `resolved` with this destination's overrides applied.
+    pub fn apply(&self, resolved: &ResolvedSourceConfig) -> ResolvedSourceConfig {
+        let mut resolved = resolved.clone();
+        if let Some(ref mode) = self.backup_mode {
+            resolved.backup_mode = mode.clone();
+        }
+        if let Some(ref format) = self.backup_format {
+            resolved.backup_format = format.clone();
+        }
+        if let Some(max_backups) = self.max_backups {
+            resolved.max_backups = max_backups;
+            resolved.full_backup_interval = auto_full_backup_interval(max_backups);
+        }
+        if let Some(ref schedule) = self.cron_schedule {
+            resolved.cron_schedule = schedule.clone();
+        }
+        resolved
+    }
+}
+
+impl From<PathBuf> for BackupDestination {
+    fn from(path: PathBuf) -> Self {
+        Self {
+            path,
+            ..Default::default()
+        }
+    }
+}
+
+/// On-disk form of `BackupDestination`: entries without overrides stay
+/// plain paths, so existing settings files read and save unchanged.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum BackupDestinationEntry {
+    Path(PathBuf),
+    Table {
+        path: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        backup_mode: Option<BackupMode>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        backup_format: Option<BackupFormat>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_backups: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cron_schedule: Option<String>,
+    },
+}
+
+impl From<BackupDestinationEntry> for BackupDestination {
+    fn from(entry: BackupDestinationEntry) -> Self {
+        match entry {
+            BackupDestinationEntry::Path(path) => path.into(),
+            BackupDestinationEntry::Table {
+                path,
+                backup_mode,
+                backup_format,
+                max_backups,
+                cron_schedule,
+            } => Self {
+                path,
+                backup_mode,
+                backup_format,
+                max_backups,
+                cron_schedule,
+            },
+        }
+    }
+}
+
+impl From<BackupDestination> for BackupDestinationEntry {
+    fn from(destination: BackupDestination) -> Self {
+        if !destination.has_overrides() {
+            return BackupDestinationEntry::Path(destination.path);
+        }
+        BackupDestinationEntry::Table {
+            path: destination.path,
+            backup_mode: destination.backup_mode,
+            backup_format: destination.backup_format,
+            max_backups: destination.max_backups,
+            cron_schedule: destination.cron_schedule,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SourceConfig {
+    pub source_dir: PathBuf,
+    pub backup_dirs: Vec<BackupDestination>,
+    pub enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exclude_patterns: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_patterns: Option<Vec<String>>,
+    /// Skip files larger than this many bytes (e.g. VM images).
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "units::deserialize_opt_size_bytes"
+    )]
+    pub max_file_size: Option<u64>,
+    /// Skip files smaller than this many bytes.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "units::deserialize_opt_size_bytes"
+    )]
+    pub min_file_size: Option<u64>,
+    /// Only back up files modified within this many seconds before the scan.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "units::deserialize_opt_duration_secs"
+    )]
+    pub modified_within: Option<u64>,
+    /// Cancel a backup of this source still running after this many
+    /// seconds, e.g. one stuck on an unresponsive network mount.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "units::deserialize_opt_duration_secs"
+    )]
+    pub backup_timeout: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_backups: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_mode: Option<BackupMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_format: Option<BackupFormat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub durability: Option<Durability>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub low_space_action: Option<LowSpaceAction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub low_space_min_backups: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locked_file_policy: Option<LockedFilePolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locked_file_retries: Option<usize>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "units::deserialize_opt_size_bytes"
+    )]
+    pub max_total_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub io_throttle_mbps: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preserve_xattrs: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hardlink_full_backups: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror_versions: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace_backups: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_name_template: Option<String>,
+    /// Short name filled into `{label}` of the backup name template.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verify_writes: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prune_stale_hashes: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta_max_ratio: Option<f64>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "units::deserialize_opt_size_bytes"
+    )]
+    pub delta_max_file_size: Option<u64>,
+    /// Replaces the global `file_strategies` as a whole.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_strategies: Option<BTreeMap<String, FileStrategy>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta_layout: Option<DeltaLayout>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parity_percent: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub append_only: Option<bool>,
+    /// Don't descend into directories on another filesystem than
+    /// `source_dir` (bind/network mounts, `/proc`-like trees).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub one_file_system: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cron_schedule: Option<String>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "units::deserialize_opt_duration_secs"
+    )]
+    pub cron_jitter_secs: Option<u64>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "units::deserialize_opt_duration_secs"
+    )]
+    pub full_scan_interval_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change_journal: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enable_event_driven: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enable_periodic: Option<bool>,
+    /// Queue a backup as soon as `run` starts instead of waiting for the
+    /// first cron slot (laptops that are rarely on at the scheduled hour).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub backup_on_start: bool,
+    /// Shell command run in the source directory before each backup; its
+    /// trimmed stdout (e.g. `git rev-parse HEAD`) is stored with the backup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_command: Option<String>,
+    /// Subpaths (relative to `source_dir`) to back up instead of the whole
+    /// tree. Only these subtrees are scanned and watched; empty means all.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<PathBuf>,
+    /// Intermittently reachable destination (e.g. a home NAS mount). Backups
+    /// land in the first backup dir and are forwarded here when it is reachable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forward_dir: Option<PathBuf>,
+    /// Dead-man's-switch ping URL (healthchecks.io style). `/start` is hit
+    /// when a backup begins, the URL itself on success and `/fail` on error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub healthcheck_url: Option<String>,
+    /// Cron schedule on which `run` checks this source's backup dirs in
+    /// the background (as `ardiex check` does) and notifies on problems.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verify_schedule: Option<String>,
+    /// Back up from a snapshot of the source instead of the live tree. It
+    /// is created before and removed after each backup run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<SnapshotConfig>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolvedSourceConfig {
+    pub exclude_patterns: Vec<String>,
+    pub anomaly_detection: AnomalyDetectionConfig,
+    pub include_patterns: Vec<String>,
+    pub max_backups: usize,
+    pub backup_mode: BackupMode,
+    pub backup_format: BackupFormat,
+    pub durability: Durability,
+    pub low_space_action: LowSpaceAction,
+    pub low_space_min_backups: usize,
+    pub locked_file_policy: LockedFilePolicy,
+    pub locked_file_retries: usize,
+    pub max_total_size: Option<u64>,
+    pub io_throttle_mbps: Option<f64>,
+    pub archive_encryption: bool,
+    pub signing_key_file: Option<PathBuf>,
+    pub dedup_store: Option<PathBuf>,
+    pub preserve_xattrs: bool,
+    pub hardlink_full_backups: bool,
+    pub mirror_versions: bool,
+    pub backup_name_template: String,
+    pub verify_writes: bool,
+    pub prune_stale_hashes: bool,
+    pub delta_max_ratio: Option<f64>,
+    pub delta_max_file_size: Option<u64>,
+    pub delta_block_size: usize,
+    pub file_strategies: BTreeMap<String, FileStrategy>,
+    pub delta_layout: DeltaLayout,
+    pub parity_percent: u32,
+    pub append_only: bool,
+    /// This machine's name, recorded as the origin of each backup.
+    pub host: String,
+    pub full_backup_interval: usize,
+    pub cron_schedule: String,
+    pub cron_jitter_secs: u64,
+    pub full_scan_interval_secs: u64,
+    pub change_journal: bool,
+    pub enable_event_driven: bool,
+    pub enable_periodic: bool,
+}
+
+impl SourceConfig {
+    pub fn effective_backup_dirs(&self) -> Vec<PathBuf> {
+        if self.backup_dirs.is_empty() {
+            vec![self.source_dir.join(".backup")]
+        } else {
+            self.backup_dirs.iter().map(|d| d.path.clone()).collect()
+        }
+    }
+
+    pub fn destination(&self, backup_dir: &Path) -> Option<&BackupDestination> {
+        self.backup_dirs.iter().find(|d| d.path == backup_dir)
+    }
+
+    /// `resolve` plus the overrides of the destination at `backup_dir`.
+    pub fn resolve_for(&self, global: &BackupConfig, backup_dir: &Path) -> ResolvedSourceConfig {
+        let resolved = self.resolve(global);
+        match self.destination(backup_dir) {
+            Some(destination) => destination.apply(&resolved),
+            None => resolved,
+        }
+    }
+
+    /// Directories (or files) to scan and watch for this source.
+    pub fn scan_roots(&self) -> Vec<PathBuf> {
+        source_scan_roots(&self.source_dir, &self.paths)
+    }
+
+    /// Directory that backed-up paths are relative to, and the subpaths to
+    /// scan in it. A file source (e.g. a SQLite DB or VM image) is scanned
+    /// as the only entry of its parent dir, so its backups and restores
+    /// hold it under its own file name.
+    pub fn scan_base(&self) -> (PathBuf, Vec<PathBuf>) {
+        if self.source_dir.is_file()
+            && let (Some(parent), Some(name)) =
+                (self.source_dir.parent(), self.source_dir.file_name())
+        {
+            return (parent.to_path_buf(), vec![PathBuf::from(name)]);
+        }
+        (self.source_dir.clone(), self.paths.clone())
+    }
+
+    pub fn resolve(&self, global: &BackupConfig) -> ResolvedSourceConfig {
+        let resolved_max_backups = self.max_backups.unwrap_or(global.max_backups);
+
+        ResolvedSourceConfig {
+            anomaly_detection: global.anomaly_detection.clone(),
+            exclude_patterns: self
+                .exclude_patterns
+                .clone()
+                .unwrap_or_else(|| global.exclude_patterns.clone()),
+            include_patterns: self
+                .include_patterns
+                .clone()
+                .unwrap_or_else(|| global.include_patterns.clone()),
+            max_backups: resolved_max_backups,
+            backup_mode: self
+                .backup_mode
+                .clone()
+                .unwrap_or_else(|| global.backup_mode.clone()),
+            backup_format: self
+                .backup_format
+                .clone()
+                .unwrap_or_else(|| global.backup_format.clone()),
+            durability: self
+                .durability
+                .clone()
+                .unwrap_or_else(|| global.durability.clone()),
+            low_space_action: self
+                .low_space_action
+                .clone()
+                .unwrap_or_else(|| global.low_space_action.clone()),
+            low_space_min_backups: self
+                .low_space_min_backups
+                .unwrap_or(global.low_space_min_backups),
+            locked_file_policy: self
+                .locked_file_policy
+                .clone()
+                .unwrap_or_else(|| global.locked_file_policy.clone()),
+            locked_file_retries: self
+                .locked_file_retries
+                .unwrap_or(global.locked_file_retries),
+            max_total_size: self.max_total_size.or(global.max_total_size),
+            io_throttle_mbps: self.io_throttle_mbps.or(global.io_throttle_mbps),
+            archive_encryption: global.archive_encryption,
+            signing_key_file: global.signing_key_file.clone(),
+            dedup_store: global.dedup_store.clone(),
+            preserve_xattrs: self.preserve_xattrs.unwrap_or(global.preserve_xattrs),
+            hardlink_full_backups: self
+                .hardlink_full_backups
+                .unwrap_or(global.hardlink_full_backups),
+            mirror_versions: self.mirror_versions.unwrap_or(global.mirror_versions),
+            backup_name_template: self
+                .backup_name_template
+                .clone()
+                .unwrap_or_else(|| global.backup_name_template.clone()),
+            verify_writes: self.verify_writes.unwrap_or(global.verify_writes),
+            prune_stale_hashes: self.prune_stale_hashes.unwrap_or(global.prune_stale_hashes),
+            delta_max_ratio: self.delta_max_ratio.or(global.delta_max_ratio),
+            delta_max_file_size: self.delta_max_file_size.or(global.delta_max_file_size),
+            delta_block_size: global.delta_block_size,
+            file_strategies: self
+                .file_strategies
+                .clone()
+                .unwrap_or_else(|| global.file_strategies.clone()),
+            delta_layout: self.delta_layout.unwrap_or(global.delta_layout),
+            parity_percent: self.parity_percent.unwrap_or(global.parity_percent),
+            append_only: self.append_only.unwrap_or(global.append_only),
+            host: global.host_name(),
+            // Full backup interval is always derived from max_backups.
+            full_backup_interval: auto_full_backup_interval(resolved_max_backups),
+            cron_schedule: self
+                .cron_schedule
+                .clone()
+                .unwrap_or_else(|| global.cron_schedule.clone()),
+            cron_jitter_secs: self.cron_jitter_secs.unwrap_or(global.cron_jitter_secs),
+            full_scan_interval_secs: self
+                .full_scan_interval_secs
+                .unwrap_or(global.full_scan_interval_secs),
+            change_journal: self.change_journal.unwrap_or(global.change_journal),
+            enable_event_driven: self
+                .enable_event_driven
+                .unwrap_or(global.enable_event_driven),
+            enable_periodic: self.enable_periodic.unwrap_or(global.enable_periodic),
+        }
+    }
+}
+
+pub fn source_scan_roots(source_dir: &Path, paths: &[PathBuf]) -> Vec<PathBuf> {
+    if paths.is_empty() {
+        vec![source_dir.to_path_buf()]
+    } else {
+        paths.iter().map(|p| source_dir.join(p)).collect()
+    }
+}
+
+/// `name` with everything but ASCII letters, digits, `-`, `_` and `.`
+/// replaced by `_`, for use as one directory name; `None` if nothing usable
+/// is left.
+fn namespace_component(name: &str) -> Option<String> {
+    let component: String = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    (!component.trim_matches('.').is_empty()).then_some(component)
+}
+
+/// `SourceMetadata::key_format` of metadata with `/`-separated keys.
+pub const PORTABLE_KEY_FORMAT: u32 = 1;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SourceMetadata {
+    pub last_full_backup: Option<DateTime<Utc>>,
+    pub last_backup: Option<DateTime<Utc>>,
+    /// Keyed by `/`-separated path relative to the source dir, so metadata
+    /// written on one OS is read correctly on another. Backups keep these
+    /// in `file_hashes.jsonl` and write this map empty; it is only filled
+    /// in metadata.json written before that (see `backup/hash_journal.rs`).
+    #[serde(default)]
+    pub file_hashes: HashMap<String, String>,
+    /// `PORTABLE_KEY_FORMAT` once `file_hashes` keys are `/`-separated;
+    /// older metadata used the writing OS's separator.
+    #[serde(default)]
+    pub key_format: u32,
+    #[serde(default)]
+    pub backup_history: Vec<BackupHistoryEntry>,
+    /// Machine and source that last backed up into this dir.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<BackupOrigin>,
+    /// Change-journal position this dir's last backup read up to, with
+    /// `change_journal` enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change_journal: Option<JournalCursor>,
+    /// Size and digest of `file_hashes.jsonl` as of this save, so the
+    /// journal is covered by the metadata signature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_journal: Option<HashJournalDigest>,
+}
+
+/// Committed prefix of a backup dir's hash journal. Lines past `len` were
+/// appended by a backup that never saved its metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HashJournalDigest {
+    pub len: u64,
+    /// blake3 of the first `len` bytes, hex.
+    pub blake3: String,
+}
+
+/// A point in a volume's change journal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalCursor {
+    /// Identity of the journal (USN journal id, FSEvents volume UUID);
+    /// a position in any other journal means nothing.
+    pub journal: String,
+    /// Next USN or FSEvents event id to read.
+    pub position: u64,
+}
+
+/// Who writes a backup dir, recorded so a second machine or source sharing
+/// the dir by mistake is caught before it mixes its metadata in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupOrigin {
+    pub host: String,
+    pub source_dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum BackupHistoryType {
+    #[serde(rename = "full")]
+    Full,
+    #[serde(rename = "inc")]
+    Incremental,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupHistoryEntry {
+    pub backup_name: String,
+    pub backup_type: BackupHistoryType,
+    pub created_at: DateTime<Utc>,
+    pub files_backed_up: usize,
+    pub bytes_processed: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inc_checksum: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource_usage: Option<ResourceUsage>,
+    /// When this backup was copied to the source's `forward_dir`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forwarded_at: Option<DateTime<Utc>>,
+    /// HMAC binding the backup name to its content, present when the point
+    /// was written with `signing_key_file` configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Set by `ardiex pin`: retention and `prune` never remove this point
+    /// or, for an incremental, the chain it restores from.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// Free-form labels from `ardiex tag`, for `restore --tag`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Set once older versions stored here were replaced by reverse deltas
+    /// against later points, which restoring this point then needs too.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub reverse_deltas: bool,
+}
+
+/// Resources consumed by one backup run. CPU time and peak memory are
+/// process-wide counters (unix only), so concurrent sources share them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_time_ms: Option<u64>,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peak_memory_kb: Option<u64>,
+}
+
+impl ResourceUsage {
+    pub fn summary(&self) -> String {
+        let cpu = self
+            .cpu_time_ms
+            .map(|ms| format!("{} ms", ms))
+            .unwrap_or_else(|| "n/a".to_string());
+        let peak = self
+            .peak_memory_kb
+            .map(|kb| format!("{:.1} MB", kb as f64 / 1024.0))
+            .unwrap_or_else(|| "n/a".to_string());
+        format!(
+            "cpu: {}, read: {:.2} MB, written: {:.2} MB, peak memory: {}",
+            cpu,
+            self.bytes_read as f64 / 1024.0 / 1024.0,
+            self.bytes_written as f64 / 1024.0 / 1024.0,
+            peak
+        )
+    }
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            sources: vec![],
+            enable_periodic: true,
+            enable_event_driven: true,
+            exclude_patterns: vec![
+                "*.tmp".to_string(),
+                "*.log".to_string(),
+                ".git/*".to_string(),
+                ".DS_Store".to_string(),
+            ],
+            include_patterns: vec![],
+            max_backups: 10,
+            backup_mode: BackupMode::Delta,
+            backup_format: BackupFormat::Directory,
+            durability: Durability::Fast,
+            low_space_action: LowSpaceAction::Abort,
+            low_space_min_backups: default_low_space_min_backups(),
+            locked_file_policy: LockedFilePolicy::Fail,
+            locked_file_retries: default_locked_file_retries(),
+            max_total_size: None,
+            max_backup_reports: default_max_backup_reports(),
+            archive_encryption: false,
+            signing_key_file: None,
+            dedup_store: None,
+            preserve_xattrs: false,
+            hardlink_full_backups: false,
+            mirror_versions: false,
+            backup_name_template: default_backup_name_template(),
+            verify_writes: false,
+            prune_stale_hashes: true,
+            delta_max_ratio: None,
+            delta_max_file_size: None,
+            delta_block_size: default_delta_block_size(),
+            file_strategies: BTreeMap::new(),
+            delta_layout: DeltaLayout::Forward,
+            parity_percent: 0,
+            append_only: false,
+            cron_schedule: "0 0 * * * *".to_string(),
+            cron_jitter_secs: 0,
+            full_scan_interval_secs: default_full_scan_interval_secs(),
+            change_journal: false,
+            blackout_windows: Vec::new(),
+            max_concurrent_backups: default_max_concurrent_backups(),
+            io_throttle_mbps: None,
+            worker_nice: None,
+            worker_ionice_idle: false,
+            enable_min_interval_by_size: true,
+            max_log_file_size_mb: default_max_log_file_size_mb(),
+            max_rotated_log_files: default_max_rotated_log_files(),
+            max_log_dir_size: None,
+            per_source_logs: false,
+            path_mappings: Vec::new(),
+            namespace_backups: false,
+            host_id: None,
+            forward_interval_secs: default_forward_interval_secs(),
+            forward_alert_after_hours: default_forward_alert_after_hours(),
+            notifications: NotificationConfig::default(),
+            anomaly_detection: AnomalyDetectionConfig::default(),
+            metrics_port: None,
+            metrics_address: None,
+            api_bind: None,
+            api_token: None,
+            api_dashboard: false,
+            api_restore_roots: Vec::new(),
+            auto_update: false,
+            update_channel: UpdateChannel::Stable,
+            allow_unsigned_updates: false,
+            metadata: HashMap::new(),
+        }
+    }
+}
+
+impl BackupConfig {
+    /// Translate a configured path into the path visible to this process.
+    pub fn map_path(&self, path: &Path) -> PathBuf {
+        for mapping in self.path_mappings.iter().filter(|m| m.is_active()) {
+            if let Ok(rest) = path.strip_prefix(&mapping.from) {
+                return mapping.to.join(rest);
+            }
+        }
+        path.to_path_buf()
+    }
+
+    /// Copy of this config with every configured path under a `remaps`
+    /// prefix moved to the new prefix, for taking the settings to another
+    /// machine or drive. Metadata follows its source.
+    pub fn remapped(&self, remaps: &[PathMapping]) -> BackupConfig {
+        let remap = |path: &Path| {
+            remaps
+                .iter()
+                .find_map(|m| path.strip_prefix(&m.from).ok().map(|rest| m.to.join(rest)))
+                .unwrap_or_else(|| path.to_path_buf())
+        };
+        let remap_opt = |path: &Option<PathBuf>| path.as_deref().map(remap);
+        let mut config = self.clone();
+        config.dedup_store = remap_opt(&self.dedup_store);
+        config.signing_key_file = remap_opt(&self.signing_key_file);
+        for source in &mut config.sources {
+            source.source_dir = remap(&source.source_dir);
+            for destination in &mut source.backup_dirs {
+                destination.path = remap(&destination.path);
+            }
+            source.forward_dir = remap_opt(&source.forward_dir);
+            match &mut source.snapshot {
+                Some(SnapshotConfig::Btrfs {
+                    subvolume,
+                    snapshot_dir,
+                }) => {
+                    *subvolume = remap_opt(subvolume);
+                    *snapshot_dir = remap_opt(snapshot_dir);
+                }
+                Some(SnapshotConfig::Lvm { mount_point, .. }) => {
+                    *mount_point = remap(mount_point);
+                }
+                Some(SnapshotConfig::Vss) | None => {}
+            }
+        }
+        config.metadata = self
+            .metadata
+            .iter()
+            .map(|(source, metadata)| {
+                let source = remap(Path::new(source)).to_string_lossy().into_owned();
+                (source, metadata.clone())
+            })
+            .collect();
+        config
+    }
+
+    /// The source writing to `backup_dir`, as one of its backup dirs or as
+    /// its `forward_dir`, together with that dir as configured (which may
+    /// be spelled differently from `backup_dir`).
+    pub fn source_for_backup_dir(&self, backup_dir: &Path) -> Option<(&SourceConfig, PathBuf)> {
+        let wanted = backup_dir
+            .canonicalize()
+            .unwrap_or_else(|_| backup_dir.to_path_buf());
+        self.sources.iter().find_map(|source| {
+            source
+                .effective_backup_dirs()
+                .into_iter()
+                .chain(source.forward_dir.clone())
+                .find(|dir| dir == backup_dir || dir.canonicalize().is_ok_and(|dir| dir == wanted))
+                .map(|dir| (source, dir))
+        })
+    }
+
+    /// Copy of this config with every source/backup path run through
+    /// `map_path`. Runtime components (validation, backup, watcher) use this.
+    /// With `namespace_backups`, every backup dir and `forward_dir` also
+    /// gets the source's `<host>/<source id>` subdirectory appended.
+    pub fn localized(&self) -> BackupConfig {
+        let mut config = self.clone();
+        for source in &mut config.sources {
+            let namespace = self.backup_namespace(source);
+            let localize = |dir: &Path| {
+                let dir = self.map_path(dir);
+                match &namespace {
+                    Some(namespace) => dir.join(namespace),
+                    None => dir,
+                }
+            };
+            source.source_dir = self.map_path(&source.source_dir);
+            for destination in &mut source.backup_dirs {
+                destination.path = localize(&destination.path);
+            }
+            source.forward_dir = source.forward_dir.as_deref().map(localize);
+        }
+        config
+    }
+
+    /// Name of this machine in namespaced backup dirs and backup origins:
+    /// `host_id`, else the hostname, reduced to a safe path component.
+    pub fn host_name(&self) -> String {
+        let host = self.host_id.clone().unwrap_or_else(|| {
+            hostname::get()
+                .map(|name| name.to_string_lossy().to_lowercase())
+                .unwrap_or_default()
+        });
+        namespace_component(&host).unwrap_or_else(|| "unknown-host".to_string())
+    }
+
+    /// `<host>/<source id>` subdirectory `source` keeps its backups in with
+    /// `namespace_backups`. The id is the source dir's name plus a short
+    /// hash of its configured path, so same-named sources stay apart.
+    pub fn backup_namespace(&self, source: &SourceConfig) -> Option<PathBuf> {
+        if !source.namespace_backups.unwrap_or(self.namespace_backups) {
+            return None;
+        }
+        let digest = Sha256::digest(source.source_dir.to_string_lossy().as_bytes());
+        let name = source
+            .source_dir
+            .file_name()
+            .and_then(|name| namespace_component(&name.to_string_lossy()))
+            .unwrap_or_else(|| "root".to_string());
+        let hash = format!("{:x}", digest);
+        Some(Path::new(&self.host_name()).join(format!("{}-{}", name, &hash[..8])))
+    }
+}
+
+pub struct ConfigManager {
+    pub config_path: PathBuf,
+    config: BackupConfig,
+}
+
+impl ConfigManager {
+    pub fn load_or_create() -> Result<Self> {
+        let config_path = get_config_path()?;
+        let format = ConfigFormat::of(&config_path);
+
+        let config = if config_path.exists() {
+            let content = fs::read_to_string(&config_path)
+                .with_context(|| format!("Failed to read {:?}", config_path))?;
+            format
+                .parse(&content)
+                .with_context(|| format!("Failed to parse {:?}", config_path))?
+        } else {
+            let config = BackupConfig::default();
+            let content = format.render(&config, None)?;
+            if let Some(parent) = config_path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create config directory {:?}", parent))?;
+            }
+            fs::write(&config_path, content)
+                .with_context(|| format!("Failed to create default {:?}", config_path))?;
+            config
+        };
+
+        Ok(Self {
+            config_path,
+            config,
+        })
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        let previous = fs::read_to_string(&self.config_path).ok();
+        let content =
+            ConfigFormat::of(&self.config_path).render(&self.config, previous.as_deref())?;
+        fs::write(&self.config_path, content)
+            .with_context(|| format!("Failed to save {:?}", self.config_path))?;
+        Ok(())
+    }
+
+    /// Run history store kept next to settings.json.
+    pub fn history_path(&self) -> PathBuf {
+        self.config_path
+            .with_file_name(crate::history::HISTORY_FILE_NAME)
+    }
+
+    /// PID file of the running service, next to settings.json.
+    pub fn pid_path(&self) -> PathBuf {
+        self.config_path
+            .with_file_name(crate::daemon::PID_FILE_NAME)
+    }
+
+    pub fn get_config(&self) -> &BackupConfig {
+        &self.config
+    }
+
+    pub fn get_config_mut(&mut self) -> &mut BackupConfig {
+        &mut self.config
+    }
+
+    pub fn add_source(&mut self, source_dir: PathBuf, backup_dirs: Vec<PathBuf>) -> Result<()> {
+        if !source_dir.exists() {
+            return Err(Error::SourceMissing(source_dir.clone()).into());
+        }
+
+        let source_config = SourceConfig {
+            source_dir: source_dir.clone(),
+            backup_dirs: backup_dirs.into_iter().map(Into::into).collect(),
+            enabled: true,
+            exclude_patterns: None,
+            max_backups: None,
+            backup_mode: None,
+            cron_schedule: None,
+            enable_event_driven: None,
+            enable_periodic: None,
+            ..Default::default()
+        };
+
+        if let Some(existing) = self
+            .config
+            .sources
+            .iter_mut()
+            .find(|s| s.source_dir == source_dir)
+        {
+            *existing = source_config;
+        } else {
+            self.config.sources.push(source_config);
+        }
+
+        self.save()?;
+        Ok(())
+    }
+
+    pub fn remove_source(&mut self, source_dir: &Path) -> Result<()> {
+        self.config.sources.retain(|s| s.source_dir != source_dir);
+        self.config
+            .metadata
+            .remove(&source_dir.to_string_lossy().into_owned());
+        self.save()?;
+        Ok(())
+    }
+
+    pub fn set_source_enabled(&mut self, source_dir: &Path, enabled: bool) -> Result<()> {
+        let source = self
+            .config
+            .sources
+            .iter_mut()
+            .find(|s| s.source_dir == source_dir)
+            .ok_or_else(|| anyhow::anyhow!("Source not found: {:?}", source_dir))?;
+        source.enabled = enabled;
+        self.save()
+    }
+
+    pub fn add_backup_dir(&mut self, source_dir: &Path, backup_dir: PathBuf) -> Result<()> {
+        if let Some(source) = self
+            .config
+            .sources
+            .iter_mut()
+            .find(|s| s.source_dir == source_dir)
+            && source.destination(&backup_dir).is_none()
+        {
+            source.backup_dirs.push(backup_dir.into());
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    pub fn remove_backup_dir(&mut self, source_dir: &Path, backup_dir: &Path) -> Result<()> {
+        if let Some(source) = self
+            .config
+            .sources
+            .iter_mut()
+            .find(|s| s.source_dir == source_dir)
+        {
+            source.backup_dirs.retain(|d| d.path != backup_dir);
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    pub fn add_path_mapping(&mut self, from: PathBuf, to: PathBuf) -> Result<()> {
+        self.config.path_mappings.retain(|m| m.from != from);
+        self.config.path_mappings.push(PathMapping { from, to });
+        self.save()
+    }
+
+    pub fn remove_path_mapping(&mut self, from: &Path) -> Result<()> {
+        self.config.path_mappings.retain(|m| m.from != from);
+        self.save()
+    }
+}
+
+/// Use `path` as the settings file for the rest of the process.
+pub fn set_config_path_override(path: &Path) -> Result<()> {
+    let path =
+        std::path::absolute(path).with_context(|| format!("Invalid config path {:?}", path))?;
+    CONFIG_PATH_OVERRIDE
+        .set(path)
+        .map_err(|_| anyhow::anyhow!("Config path is already set"))
+}
+
+pub fn set_profile_override(profile: &str) -> Result<()> {
+    config_location::validate_profile_name(profile).map_err(|e| anyhow::anyhow!(e))?;
+    PROFILE_OVERRIDE
+        .set(profile.to_string())
+        .map_err(|_| anyhow::anyhow!("Profile is already set"))
+}
+
+/// `--profile`, then `ARDIEX_PROFILE`.
+pub fn active_profile() -> Result<Option<String>> {
+    if let Some(profile) = PROFILE_OVERRIDE.get() {
+        return Ok(Some(profile.clone()));
+    }
+    match std::env::var(PROFILE_ENV_KEY) {
+        Ok(profile) if !profile.is_empty() => {
+            config_location::validate_profile_name(&profile).map_err(|e| anyhow::anyhow!(e))?;
+            Ok(Some(profile))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Names of the profiles that have a settings file.
+pub fn list_profiles() -> Result<Vec<String>> {
+    let mut exe_dir = std::env::current_exe().context("Failed to get executable path")?;
+    exe_dir.pop();
+    let dir = config_location::profiles_dir(config_location::platform_config_dir(), &exe_dir);
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Ok(Vec::new());
+    };
+    let mut profiles: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| config_location::existing_settings_file(&entry.path()).is_some())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    profiles.sort();
+    Ok(profiles)
+}
+
+/// `--config`, `--profile`, `ARDIEX_CONFIG`, `ARDIEX_PROFILE`, then the
+/// platform config dir (see `config_location::locate`).
+fn get_config_path() -> Result<PathBuf> {
+    let env_config = std::env::var_os(CONFIG_ENV_KEY)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from);
+    let mut exe_dir = std::env::current_exe().context("Failed to get executable path")?;
+    exe_dir.pop();
+    let platform_dir = config_location::platform_config_dir();
+    let location = match (CONFIG_PATH_OVERRIDE.get(), PROFILE_OVERRIDE.get()) {
+        (Some(path), _) => config_location::locate(Some(path.clone()), platform_dir, &exe_dir),
+        (None, Some(profile)) => config_location::locate_profile(profile, platform_dir, &exe_dir),
+        (None, None) if env_config.is_some() => {
+            config_location::locate(env_config, platform_dir, &exe_dir)
+        }
+        (None, None) => match active_profile()? {
+            Some(profile) => config_location::locate_profile(&profile, platform_dir, &exe_dir),
+            None => config_location::locate(None, platform_dir, &exe_dir),
+        },
+    };
+    if let Some(ref legacy) = location.migrate_from {
+        migrate_legacy_config(legacy, &location)?;
+    }
+    Ok(location.path)
+}
+
+/// Copy a settings file (and its run history) from next to the executable
+/// to the platform config dir. The old file is renamed so later edits are
+/// not made to a file nobody reads.
+fn migrate_legacy_config(legacy: &Path, location: &ConfigLocation) -> Result<()> {
+    let target_dir = location.path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(target_dir)
+        .with_context(|| format!("Failed to create config directory {:?}", target_dir))?;
+    fs::copy(legacy, &location.path)
+        .with_context(|| format!("Failed to migrate {:?} to {:?}", legacy, location.path))?;
+    let legacy_history = legacy.with_file_name(crate::history::HISTORY_FILE_NAME);
+    if legacy_history.is_file() {
+        fs::copy(
+            &legacy_history,
+            target_dir.join(crate::history::HISTORY_FILE_NAME),
+        )
+        .with_context(|| format!("Failed to migrate {:?}", legacy_history))?;
+    }
+    let mut migrated = legacy.as_os_str().to_os_string();
+    migrated.push(".migrated");
+    if let Err(e) = fs::rename(legacy, &migrated) {
+        // A read-only install dir keeps the old file; the new one wins.
+        warn!("Could not rename migrated config {:?}: {}", legacy, e);
+    }
+    info!("Migrated settings from {:?} to {:?}", legacy, location.path);
+    Ok(())
+}
+
+#[cfg(test)]
+#[path = "tests/config_tests.rs"]
+mod tests;
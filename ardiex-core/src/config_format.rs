@@ -0,0 +1,112 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use toml_edit::{DocumentMut, Item, Table, Value};
+
+use crate::config::BackupConfig;
+
+/// On-disk format of the settings file, picked by its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+}
+
+impl ConfigFormat {
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Json,
+        }
+    }
+
+    pub fn parse(self, content: &str) -> Result<BackupConfig> {
+        match self {
+            ConfigFormat::Json => serde_json::from_str(content).context("Invalid JSON"),
+            ConfigFormat::Toml => toml::from_str(content).context("Invalid TOML"),
+        }
+    }
+
+    /// Serialize `config`. For TOML the new values are merged into
+    /// `previous` (the file as it is on disk) so the user's comments and
+    /// layout survive the save.
+    pub fn render(self, config: &BackupConfig, previous: Option<&str>) -> Result<String> {
+        match self {
+            ConfigFormat::Json => {
+                serde_json::to_string_pretty(config).context("Failed to serialize config")
+            }
+            ConfigFormat::Toml => {
+                let rendered =
+                    toml::to_string_pretty(config).context("Failed to serialize config")?;
+                let Some(mut document) = previous.and_then(|p| p.parse::<DocumentMut>().ok())
+                else {
+                    return Ok(rendered);
+                };
+                let updated: DocumentMut = rendered
+                    .parse()
+                    .context("Failed to re-read serialized config")?;
+                merge_table(document.as_table_mut(), updated.as_table().clone());
+                Ok(document.to_string())
+            }
+        }
+    }
+}
+
+/// Make `old` hold exactly the entries of `new`, keeping the decor
+/// (comments, whitespace) of every key and value that is still there.
+fn merge_table(old: &mut Table, new: Table) {
+    old.retain(|key, _| new.contains_key(key));
+    for (key, new_item) in new {
+        match old.get_mut(&key) {
+            Some(old_item) => merge_item(old_item, new_item),
+            None => {
+                old.insert(&key, new_item);
+            }
+        }
+    }
+}
+
+fn merge_item(old: &mut Item, new: Item) {
+    match (old, new) {
+        (Item::Table(old), Item::Table(new)) => merge_table(old, new),
+        (Item::ArrayOfTables(old), Item::ArrayOfTables(new)) => {
+            let kept = new.len();
+            for (index, table) in new.into_iter().enumerate() {
+                match old.get_mut(index) {
+                    Some(existing) => merge_table(existing, table),
+                    None => old.push(table),
+                }
+            }
+            while old.len() > kept {
+                old.remove(old.len() - 1);
+            }
+        }
+        (Item::Value(old), Item::Value(mut new)) => {
+            if !same_value(old, &new) {
+                *new.decor_mut() = old.decor().clone();
+                *old = new;
+            }
+        }
+        (old, new) => *old = new,
+    }
+}
+
+/// Equal ignoring decor and string quoting, so untouched values keep the
+/// representation the user wrote.
+fn same_value(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => a.value() == b.value(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same_value(a, b))
+        }
+        _ => {
+            let (mut a, mut b) = (a.clone(), b.clone());
+            a.decor_mut().clear();
+            b.decor_mut().clear();
+            a.to_string() == b.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/config_format_tests.rs"]
+mod tests;
@@ -0,0 +1,130 @@
+use std::path::{Path, PathBuf};
+
+pub const JSON_CONFIG_FILE_NAME: &str = "settings.json";
+pub const TOML_CONFIG_FILE_NAME: &str = "settings.toml";
+
+/// Settings file to use instead of the default location; `--config` wins
+/// over it.
+pub const CONFIG_ENV_KEY: &str = "ARDIEX_CONFIG";
+
+/// Named settings set (`--profile`) to use instead of the default one.
+pub const PROFILE_ENV_KEY: &str = "ARDIEX_PROFILE";
+
+const APP_DIR_NAME: &str = "ardiex";
+const PROFILES_DIR_NAME: &str = "profiles";
+
+/// Where the settings file is read from, and the settings file next to the
+/// executable that has to be moved there first, if any.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConfigLocation {
+    pub path: PathBuf,
+    pub migrate_from: Option<PathBuf>,
+}
+
+/// Per-user config dir: `$XDG_CONFIG_HOME/ardiex` (or `~/.config/ardiex`)
+/// on Unix, `%APPDATA%\ardiex` on Windows with `%ProgramData%\ardiex` for
+/// service accounts without a profile.
+pub fn platform_config_dir() -> Option<PathBuf> {
+    let non_empty = |key: &str| std::env::var_os(key).filter(|value| !value.is_empty());
+    let base = if cfg!(windows) {
+        non_empty("APPDATA")
+            .or_else(|| non_empty("ProgramData"))?
+            .into()
+    } else {
+        non_empty("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| non_empty("HOME").map(|home| PathBuf::from(home).join(".config")))?
+    };
+    Some(base.join(APP_DIR_NAME))
+}
+
+/// `settings.toml` or `settings.json` in `dir`, whichever exists (TOML
+/// first).
+pub fn existing_settings_file(dir: &Path) -> Option<PathBuf> {
+    [TOML_CONFIG_FILE_NAME, JSON_CONFIG_FILE_NAME]
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Resolve the settings file: an explicit path as given, otherwise the
+/// platform config dir. A settings file still next to the executable is
+/// migrated when the platform dir has none; without a platform dir the
+/// executable's dir stays in use.
+pub fn locate(
+    explicit: Option<PathBuf>,
+    platform_dir: Option<PathBuf>,
+    exe_dir: &Path,
+) -> ConfigLocation {
+    if let Some(path) = explicit {
+        return ConfigLocation {
+            path,
+            migrate_from: None,
+        };
+    }
+    let legacy = existing_settings_file(exe_dir);
+    let Some(dir) = platform_dir else {
+        return ConfigLocation {
+            path: legacy.unwrap_or_else(|| exe_dir.join(JSON_CONFIG_FILE_NAME)),
+            migrate_from: None,
+        };
+    };
+    if let Some(path) = existing_settings_file(&dir) {
+        return ConfigLocation {
+            path,
+            migrate_from: None,
+        };
+    }
+    match legacy {
+        Some(legacy) => ConfigLocation {
+            path: dir.join(legacy.file_name().unwrap_or_default()),
+            migrate_from: Some(legacy),
+        },
+        None => ConfigLocation {
+            path: dir.join(JSON_CONFIG_FILE_NAME),
+            migrate_from: None,
+        },
+    }
+}
+
+/// Profile names become directory names, so only `[A-Za-z0-9_-]` is
+/// accepted.
+pub fn validate_profile_name(name: &str) -> Result<(), String> {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid profile name '{}': use letters, digits, '-' and '_'",
+            name
+        ))
+    }
+}
+
+/// Dir holding one subdirectory per profile, next to the default settings.
+pub fn profiles_dir(platform_dir: Option<PathBuf>, exe_dir: &Path) -> PathBuf {
+    platform_dir
+        .unwrap_or_else(|| exe_dir.to_path_buf())
+        .join(PROFILES_DIR_NAME)
+}
+
+/// Settings file of `profile`: `profiles/<profile>/settings.{toml,json}`.
+/// Profiles never take over the settings next to the executable.
+pub fn locate_profile(
+    profile: &str,
+    platform_dir: Option<PathBuf>,
+    exe_dir: &Path,
+) -> ConfigLocation {
+    let dir = profiles_dir(platform_dir, exe_dir).join(profile);
+    ConfigLocation {
+        path: existing_settings_file(&dir).unwrap_or_else(|| dir.join(JSON_CONFIG_FILE_NAME)),
+        migrate_from: None,
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/config_location_tests.rs"]
+mod tests;
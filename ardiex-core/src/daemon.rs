@@ -0,0 +1,328 @@
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// PID of the running `ardiex run`, kept next to settings.json.
+pub const PID_FILE_NAME: &str = "ardiex.pid";
+
+/// Stderr of a daemonized service (panics, logging set-up failures); the
+/// regular log already goes to the log dir.
+const DAEMON_STDERR_FILE_NAME: &str = "daemon.err";
+
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// PID file written by the running service and removed when it exits. The
+/// service holds an exclusive OS lock on `<PID file>.lock` for its whole
+/// lifetime, so a PID file whose lock can be taken again is stale, whatever
+/// process its PID now belongs to. The lock sits on a separate file because
+/// Windows locks are mandatory and would keep `stop` from reading the PID.
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+    lock: File,
+}
+
+/// `ardiex.pid.lock` next to `ardiex.pid`. Left on disk when released so
+/// a service opening it meanwhile never locks a removed file.
+fn lock_path(path: &Path) -> PathBuf {
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    PathBuf::from(lock_path)
+}
+
+impl PidFile {
+    /// Lock `path` and record this process in it. Fails while another
+    /// service holds it; a file left behind by a dead one is taken over.
+    pub fn create(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {:?}", parent))?;
+        }
+        let lock_path = lock_path(path);
+        let lock = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lock_path)
+            .with_context(|| format!("Failed to open PID lock file {:?}", lock_path))?;
+        match lock.try_lock() {
+            Ok(()) => {}
+            Err(fs::TryLockError::WouldBlock) => {
+                let holder = read_pid(path)
+                    .ok()
+                    .flatten()
+                    .map_or_else(|| "unknown pid".to_string(), |pid| format!("pid {}", pid));
+                anyhow::bail!(
+                    "The backup service is already running ({}, {:?})",
+                    holder,
+                    path
+                );
+            }
+            Err(fs::TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("Failed to lock {:?}", lock_path));
+            }
+        }
+        if let Some(pid) = read_pid(path).ok().flatten() {
+            warn!("Replacing stale PID file {:?} (pid {})", path, pid);
+        }
+        fs::write(path, format!("{}\n", std::process::id()))
+            .with_context(|| format!("Failed to write PID file {:?}", path))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            lock,
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        // Still locked, so no newer service can have replaced the file.
+        let _ = fs::remove_file(&self.path);
+        let _ = self.lock.unlock();
+    }
+}
+
+/// PID stored in `path`; `None` when there is no PID file.
+pub fn read_pid(path: &Path) -> Result<Option<u32>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read PID file {:?}", path)),
+    };
+    content
+        .trim()
+        .parse()
+        .map(Some)
+        .with_context(|| format!("Invalid PID file {:?}: {:?}", path, content.trim()))
+}
+
+/// PID of the service holding the lock of `path`; `None` when there is no
+/// PID file or nobody holds its lock (a stale file).
+pub fn running_pid(path: &Path) -> Result<Option<u32>> {
+    let lock_path = lock_path(path);
+    let lock = match File::open(&lock_path) {
+        Ok(lock) => lock,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to open {:?}", lock_path)),
+    };
+    match lock.try_lock_shared() {
+        Ok(()) => {
+            let _ = lock.unlock();
+            Ok(None)
+        }
+        Err(fs::TryLockError::WouldBlock) => read_pid(path)?
+            .map(Some)
+            .with_context(|| format!("PID file {:?} is locked but holds no PID", path)),
+        Err(fs::TryLockError::Error(e)) => {
+            Err(e).with_context(|| format!("Failed to inspect {:?}", lock_path))
+        }
+    }
+}
+
+/// Arguments for the detached child: the current ones without the flag
+/// that asked for detaching.
+pub fn daemon_args(args: &[String]) -> Vec<String> {
+    args.iter()
+        .filter(|arg| arg.as_str() != "--daemonize")
+        .cloned()
+        .collect()
+}
+
+/// Start this executable again with `args`, detached from the terminal,
+/// and return the child's PID. Stdout is dropped (the log file has the
+/// same lines); stderr goes to `daemon.err` in `log_dir`.
+pub fn spawn_detached(args: &[String], log_dir: Option<&Path>) -> Result<u32> {
+    let exe = std::env::current_exe().context("Failed to resolve current executable")?;
+    let stderr = match log_dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create log directory {:?}", dir))?;
+            let path = dir.join(DAEMON_STDERR_FILE_NAME);
+            let file: File = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .with_context(|| format!("Failed to open {:?}", path))?;
+            Stdio::from(file)
+        }
+        None => Stdio::null(),
+    };
+
+    let mut command = Command::new(&exe);
+    command
+        .args(args)
+        .env("ARDIEX_SKIP_UPDATE_CHECK", "1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(stderr);
+    detach(&mut command);
+    let child = command
+        .spawn()
+        .with_context(|| format!("Failed to start {:?} in the background", exe))?;
+    Ok(child.id())
+}
+
+#[cfg(unix)]
+fn detach(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+    // A new session without a controlling terminal, so closing the terminal
+    // (SIGHUP) or Ctrl+C there does not reach the service.
+    // SAFETY: setsid is async-signal-safe and the closure touches nothing
+    // else between fork and exec.
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(windows)]
+fn detach(command: &mut Command) {
+    use std::os::windows::process::CommandExt;
+    const DETACHED_PROCESS: u32 = 0x0000_0008;
+    const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+    command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+}
+
+#[cfg(unix)]
+pub fn is_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 only checks that the process exists and may be signalled.
+    let rc = unsafe { libc::kill(pid, 0) };
+    rc == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+pub fn is_running(pid: u32) -> bool {
+    Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+        .output()
+        .map(|out| {
+            String::from_utf8_lossy(&out.stdout)
+                .split_whitespace()
+                .any(|field| field == pid.to_string())
+        })
+        .unwrap_or(false)
+}
+
+/// Ask `pid` to shut down: SIGTERM on Unix, `taskkill` on Windows.
+#[cfg(unix)]
+fn terminate(pid: u32) -> Result<()> {
+    let raw = libc::pid_t::try_from(pid).context("PID out of range")?;
+    if unsafe { libc::kill(raw, libc::SIGTERM) } != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("Failed to signal pid {}", pid));
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn terminate(pid: u32) -> Result<()> {
+    let status = Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/T", "/F"])
+        .stdout(Stdio::null())
+        .status()
+        .context("Failed to run taskkill")?;
+    if !status.success() {
+        anyhow::bail!("taskkill failed for pid {} ({})", pid, status);
+    }
+    Ok(())
+}
+
+/// Whether `pid` runs this executable, so a PID file is never used to
+/// signal some unrelated process.
+pub fn is_ardiex(pid: u32) -> bool {
+    let Some(name) = process_name(pid) else {
+        return false;
+    };
+    let name = name.trim().to_ascii_lowercase();
+    let name = name.strip_suffix(".exe").unwrap_or(&name);
+    let expected = std::env::current_exe()
+        .ok()
+        .and_then(|exe| {
+            exe.file_stem()
+                .map(|stem| stem.to_string_lossy().to_ascii_lowercase())
+        })
+        .unwrap_or_else(|| "ardiex".to_string());
+    // Linux cuts process names to 15 bytes.
+    !name.is_empty() && (name == expected || (name.len() == 15 && expected.starts_with(name)))
+}
+
+#[cfg(target_os = "linux")]
+fn process_name(pid: u32) -> Option<String> {
+    fs::read_to_string(format!("/proc/{}/comm", pid)).ok()
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn process_name(pid: u32) -> Option<String> {
+    let out = Command::new("ps")
+        .args(["-o", "comm=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let name = String::from_utf8_lossy(&out.stdout);
+    Path::new(name.trim())
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+}
+
+#[cfg(windows)]
+fn process_name(pid: u32) -> Option<String> {
+    let out = Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/FO", "CSV", "/NH"])
+        .output()
+        .ok()?;
+    // "ardiex.exe","1234",...
+    String::from_utf8_lossy(&out.stdout)
+        .split(',')
+        .next()
+        .map(|name| name.trim().trim_matches('"').to_string())
+}
+
+/// Stop the service holding `pid_file` and wait up to `timeout` for it to
+/// exit. Returns the stopped PID, or `None` when nothing was running. Only
+/// a process that holds the file's lock and runs ardiex is signalled.
+pub fn stop(pid_file: &Path, timeout: Duration) -> Result<Option<u32>> {
+    let Some(pid) = running_pid(pid_file)? else {
+        if let Some(pid) = read_pid(pid_file).ok().flatten() {
+            warn!("Removing stale PID file {:?} (pid {})", pid_file, pid);
+            let _ = fs::remove_file(pid_file);
+        }
+        return Ok(None);
+    };
+    if !is_ardiex(pid) {
+        anyhow::bail!(
+            "PID file {:?} is locked by pid {}, which is not ardiex; not signalling it",
+            pid_file,
+            pid
+        );
+    }
+    terminate(pid)?;
+    info!("Sent stop request to the backup service (pid {})", pid);
+
+    let started = Instant::now();
+    while is_running(pid) {
+        if started.elapsed() >= timeout {
+            anyhow::bail!(
+                "The backup service (pid {}) did not exit within {}s",
+                pid,
+                timeout.as_secs()
+            );
+        }
+        std::thread::sleep(STOP_POLL_INTERVAL);
+    }
+    // A forcibly killed process cannot clean up after itself.
+    let _ = fs::remove_file(pid_file);
+    Ok(Some(pid))
+}
+
+#[cfg(test)]
+#[path = "tests/daemon_tests.rs"]
+mod tests;
@@ -0,0 +1,338 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::progress::FileProgress;
+
+/// Per-point index of a `dedup` backup, stored at the root of the point
+/// instead of the file contents.
+pub const INDEX_FILE_NAME: &str = ".ardiex-dedup-index.json";
+
+/// Chunk store inside the backup dir, used unless `dedup_store` points all
+/// dedup sources at one shared store.
+pub const DEFAULT_STORE_DIR_NAME: &str = "objects";
+
+/// Backup dirs holding points that use a shared store, one path per line,
+/// kept in the store itself so `gc` knows every dir to check before it
+/// deletes anything, including dirs of sources no longer configured.
+pub const REFERRERS_FILE_NAME: &str = ".ardiex-referrers";
+
+/// Files are split into fixed-size chunks, so identical files (and identical
+/// leading/aligned regions of large files) share objects.
+const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Where the chunks of one point live and which chunks make up each file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DedupIndex {
+    /// Shared store the chunks were written to; `None` means the
+    /// `objects/` dir of the backup dir holding the point.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store: Option<PathBuf>,
+    /// Keyed by `/`-separated path relative to the source dir.
+    pub files: BTreeMap<String, IndexedFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexedFile {
+    pub size: u64,
+    /// SHA-256 of each chunk in file order.
+    pub chunks: Vec<String>,
+}
+
+impl DedupIndex {
+    /// Chunk store this index refers to when read from `backup_dir`.
+    pub fn store_root(&self, backup_dir: &Path) -> PathBuf {
+        store_root(backup_dir, self.store.as_deref())
+    }
+}
+
+pub fn store_root(backup_dir: &Path, shared_store: Option<&Path>) -> PathBuf {
+    shared_store
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| backup_dir.join(DEFAULT_STORE_DIR_NAME))
+}
+
+/// Record that points in `backup_dir` use the shared store at
+/// `store_root`. Lines are appended, so concurrent backups into one store
+/// never drop each other's entry.
+pub fn register_referrer(store_root: &Path, backup_dir: &Path) -> Result<()> {
+    if read_referrers(store_root)?.is_some_and(|dirs| dirs.iter().any(|dir| dir == backup_dir)) {
+        return Ok(());
+    }
+    fs::create_dir_all(store_root)
+        .with_context(|| format!("Failed to create chunk store {:?}", store_root))?;
+    let path = store_root.join(REFERRERS_FILE_NAME);
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {:?}", path))?;
+    file.write_all(format!("{}\n", backup_dir.display()).as_bytes())
+        .and_then(|_| file.sync_all())
+        .with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// Backup dirs recorded by `register_referrer`, or `None` for a store
+/// that has no list (written before stores kept one).
+pub fn read_referrers(store_root: &Path) -> Result<Option<Vec<PathBuf>>> {
+    let path = store_root.join(REFERRERS_FILE_NAME);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for line in content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        let dir = PathBuf::from(line);
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    Ok(Some(dirs))
+}
+
+pub fn write_index(point_dir: &Path, index: &DedupIndex) -> Result<()> {
+    let path = point_dir.join(INDEX_FILE_NAME);
+    fs::write(&path, serde_json::to_vec(index)?)
+        .with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// The index of a point, or `None` for points not written in dedup mode.
+pub fn read_index(point_dir: &Path) -> Result<Option<DedupIndex>> {
+    let path = point_dir.join(INDEX_FILE_NAME);
+    if !point_dir.is_dir() || !path.is_file() {
+        return Ok(None);
+    }
+    let content = fs::read(&path).with_context(|| format!("Failed to read {:?}", path))?;
+    serde_json::from_slice(&content)
+        .map(Some)
+        .with_context(|| format!("Invalid dedup index {:?}", path))
+}
+
+/// Content-addressed chunk store: `<root>/<first two hex chars>/<sha256>`.
+pub struct ChunkStore {
+    root: PathBuf,
+    verify_writes: bool,
+    fsync: bool,
+}
+
+impl ChunkStore {
+    pub fn open(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            verify_writes: false,
+            fsync: false,
+        }
+    }
+
+    /// Re-read every new chunk before it is published, and flush it to
+    /// stable storage first.
+    pub fn with_write_options(mut self, verify_writes: bool, fsync: bool) -> Self {
+        self.verify_writes = verify_writes;
+        self.fsync = fsync;
+        self
+    }
+
+    pub fn object_path(&self, hash: &str) -> PathBuf {
+        self.root.join(&hash[..2.min(hash.len())]).join(hash)
+    }
+
+    /// Split `file_path` into chunks and store the ones the store does not
+    /// have yet. Returns the file's index entry and the bytes newly written.
+    pub fn store_file(
+        &self,
+        file_path: &Path,
+        mut progress: Option<&mut FileProgress>,
+    ) -> Result<(IndexedFile, u64)> {
+        let mut file =
+            File::open(file_path).with_context(|| format!("Failed to open {:?}", file_path))?;
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+        let mut entry = IndexedFile {
+            size: 0,
+            chunks: Vec::new(),
+        };
+        let mut written = 0u64;
+        loop {
+            let len = read_full(&mut file, &mut buffer)?;
+            if len == 0 {
+                break;
+            }
+            let chunk = &buffer[..len];
+            let hash = format!("{:x}", Sha256::digest(chunk));
+            if self.write_chunk(&hash, chunk)? {
+                written += len as u64;
+            }
+            entry.size += len as u64;
+            entry.chunks.push(hash);
+            if let Some(progress) = progress.as_deref_mut() {
+                progress.update(entry.size);
+            }
+        }
+        Ok((entry, written))
+    }
+
+    /// Publish a chunk via temp file + rename, so concurrent writers of the
+    /// same chunk and crashes never leave a torn object. Returns false when
+    /// the chunk already existed.
+    fn write_chunk(&self, hash: &str, data: &[u8]) -> Result<bool> {
+        let object = self.object_path(hash);
+        if object.is_file() {
+            return Ok(false);
+        }
+        let dir = object.parent().unwrap_or(&self.root);
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+        let tmp = dir.join(format!(".{}.{}.tmp", hash, std::process::id()));
+        let result = (|| -> Result<()> {
+            let mut out = File::create(&tmp)?;
+            out.write_all(data)?;
+            if self.fsync {
+                out.sync_all()?;
+            }
+            drop(out);
+            if self.verify_writes {
+                let stored = format!("{:x}", Sha256::digest(fs::read(&tmp)?));
+                if stored != hash {
+                    return Err(anyhow::anyhow!(
+                        "Write verification failed for chunk {}: stored content differs",
+                        hash
+                    ));
+                }
+            }
+            fs::rename(&tmp, &object)?;
+            Ok(())
+        })();
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result.with_context(|| format!("Failed to store chunk {:?}", object))?;
+        Ok(true)
+    }
+
+    /// Reassemble a file from its chunks, checking each chunk's hash.
+    pub fn restore_file(
+        &self,
+        entry: &IndexedFile,
+        target: &Path,
+        progress: Option<&mut FileProgress>,
+    ) -> Result<()> {
+        let mut out =
+            File::create(target).with_context(|| format!("Failed to create {:?}", target))?;
+        self.write_file(entry, &mut out, progress)
+    }
+
+    /// Stream a file's chunks into `output`, checking each chunk's hash.
+    pub fn write_file(
+        &self,
+        entry: &IndexedFile,
+        output: &mut impl Write,
+        mut progress: Option<&mut FileProgress>,
+    ) -> Result<()> {
+        let mut written = 0u64;
+        for hash in &entry.chunks {
+            let data = self.read_chunk(hash)?;
+            output.write_all(&data)?;
+            written += data.len() as u64;
+            if let Some(progress) = progress.as_deref_mut() {
+                progress.update(written);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_chunk(&self, hash: &str) -> Result<Vec<u8>> {
+        let object = self.object_path(hash);
+        let data = fs::read(&object).with_context(|| format!("Missing chunk {:?}", object))?;
+        if format!("{:x}", Sha256::digest(&data)) != hash {
+            return Err(anyhow::anyhow!("Chunk {:?} is corrupted", object));
+        }
+        Ok(data)
+    }
+
+    /// Whether `file` currently has exactly the content `entry` describes.
+    pub fn matches(entry: &IndexedFile, file: &Path) -> Result<bool> {
+        let mut reader = File::open(file)?;
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+        let mut chunks = entry.chunks.iter();
+        let mut size = 0u64;
+        loop {
+            let len = read_full(&mut reader, &mut buffer)?;
+            if len == 0 {
+                break;
+            }
+            size += len as u64;
+            let hash = format!("{:x}", Sha256::digest(&buffer[..len]));
+            if chunks.next() != Some(&hash) {
+                return Ok(false);
+            }
+        }
+        Ok(size == entry.size && chunks.next().is_none())
+    }
+
+    /// Copy the chunks `index` references that `target` lacks, checking
+    /// each one on the way. Returns the bytes copied.
+    pub fn copy_missing_to(&self, target: &ChunkStore, index: &DedupIndex) -> Result<u64> {
+        let mut copied = 0u64;
+        for hash in referenced_chunks([index]) {
+            if target.object_path(&hash).is_file() {
+                continue;
+            }
+            let data = self.read_chunk(&hash)?;
+            if target.write_chunk(&hash, &data)? {
+                copied += data.len() as u64;
+            }
+        }
+        Ok(copied)
+    }
+
+    /// Every chunk hash currently in the store with its size.
+    pub fn list_chunks(&self) -> Result<Vec<(String, PathBuf, u64)>> {
+        let mut chunks = Vec::new();
+        if !self.root.is_dir() {
+            return Ok(chunks);
+        }
+        for entry in walkdir::WalkDir::new(&self.root).min_depth(2).max_depth(2) {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Leftover temp files of interrupted writes start with a dot.
+            if entry.file_type().is_file() && !name.starts_with('.') {
+                chunks.push((name, entry.path().to_path_buf(), entry.metadata()?.len()));
+            }
+        }
+        Ok(chunks)
+    }
+}
+
+/// Fill `buffer` as far as the file allows, so chunk boundaries do not
+/// depend on how the OS splits reads.
+fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        let n = reader.read(&mut buffer[filled..])?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    Ok(filled)
+}
+
+/// All chunk hashes referenced by `indexes`.
+pub fn referenced_chunks<'a>(indexes: impl IntoIterator<Item = &'a DedupIndex>) -> HashSet<String> {
+    indexes
+        .into_iter()
+        .flat_map(|index| index.files.values())
+        .flat_map(|file| file.chunks.iter().cloned())
+        .collect()
+}
+
+#[cfg(test)]
+#[path = "tests/dedup_tests.rs"]
+mod tests;
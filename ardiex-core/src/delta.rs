@@ -0,0 +1,391 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::progress::FileProgress;
+
+/// Block size of deltas unless `delta_block_size` says otherwise. Deltas
+/// record their block size, so changing it only affects new ones.
+pub const DEFAULT_BLOCK_SIZE: usize = 4096;
+/// Smallest `delta_block_size`; smaller blocks make the per-block hashes
+/// outweigh the data they save.
+pub const MIN_BLOCK_SIZE: usize = 512;
+/// Largest `delta_block_size`; also the batch read per hashing thread, so
+/// every valid block size splits a batch into whole blocks.
+pub const MAX_BLOCK_SIZE: usize = 1024 * 1024;
+
+/// `DeltaFile::original_file_hash` of a delta made against an empty or
+/// missing file (SHA-256 of no bytes).
+pub const EMPTY_FILE_HASH: &str =
+    "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+/// Name a delta of `stored` is saved under: `<name>.<ext>.delta`, or
+/// `<name>.<ext>.rdelta` for a reverse delta (an older version stored
+/// against the next newer one). A file without an extension gets an empty
+/// one, e.g. `Makefile..delta`; that dot is added after the whole name, so
+/// `foo.` becomes `foo...delta` and stays distinct from `foo`'s delta.
+pub fn delta_path(stored: &Path, reverse: bool) -> PathBuf {
+    let suffix = if reverse { "rdelta" } else { "delta" };
+    let mut name = stored.file_name().unwrap_or_default().to_os_string();
+    if stored.extension().is_none_or(|ext| ext.is_empty()) {
+        name.push(".");
+    }
+    name.push(".");
+    name.push(suffix);
+    stored.with_file_name(name)
+}
+
+/// Whether a stored file name is a reverse delta.
+pub fn is_reverse_delta_name(name: &str) -> bool {
+    name.ends_with(".rdelta")
+}
+
+/// Whether a stored file name is a delta of either direction rather than
+/// a plain copy.
+pub fn is_delta_name(name: &str) -> bool {
+    name.ends_with(".delta") || is_reverse_delta_name(name)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeltaFile {
+    pub original_file_hash: String,
+    pub block_size: usize,
+    pub total_blocks: usize,
+    pub changed_blocks: Vec<DeltaBlock>,
+    pub new_file_size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeltaBlock {
+    pub index: usize,
+    pub hash: String,
+    pub data: Vec<u8>,
+}
+
+/// Threads block hashing is spread over.
+fn hash_threads() -> usize {
+    std::thread::available_parallelism()
+        .map_or(1, |threads| threads.get())
+        .min(8)
+}
+
+/// Whether `block_size` can be used for new deltas: a power of two from
+/// `MIN_BLOCK_SIZE` to `MAX_BLOCK_SIZE`.
+pub fn is_valid_block_size(block_size: usize) -> bool {
+    block_size.is_power_of_two() && (MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&block_size)
+}
+
+/// Fill `buffer` from `reader` unless it ends first, so batches always
+/// split into whole blocks.
+fn read_batch(reader: &mut impl Read, buffer: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(filled)
+}
+
+/// SHA-256 of each `block_size` block of `batch`, hashed on up to
+/// `threads` threads while `whole` (if any) is fed the same bytes on the
+/// calling one.
+fn hash_batch(
+    batch: &[u8],
+    block_size: usize,
+    threads: usize,
+    whole: Option<&mut Sha256>,
+) -> Vec<String> {
+    let hash_blocks = |bytes: &[u8]| -> Vec<String> {
+        bytes
+            .chunks(block_size)
+            .map(|block| format!("{:x}", Sha256::digest(block)))
+            .collect()
+    };
+    let blocks = batch.len().div_ceil(block_size);
+    if threads <= 1 || blocks <= 1 {
+        if let Some(whole) = whole {
+            whole.update(batch);
+        }
+        return hash_blocks(batch);
+    }
+    let per_thread = blocks.div_ceil(threads) * block_size;
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = batch
+            .chunks(per_thread)
+            .map(|part| scope.spawn(move || hash_blocks(part)))
+            .collect();
+        if let Some(whole) = whole {
+            whole.update(batch);
+        }
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("block hashing thread panicked"))
+            .collect()
+    })
+}
+
+/// Block hashes and whole-file SHA-256 of `file_path`, from one read.
+pub fn calculate_block_hashes(
+    file_path: &Path,
+    block_size: usize,
+) -> Result<(Vec<String>, String)> {
+    let mut file = fs::File::open(file_path)
+        .with_context(|| format!("Failed to open file: {:?}", file_path))?;
+    let threads = hash_threads();
+    let mut buffer = vec![0u8; threads * MAX_BLOCK_SIZE];
+    let mut hashes = Vec::new();
+    let mut whole = Sha256::new();
+    loop {
+        let bytes_read = read_batch(&mut file, &mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hashes.extend(hash_batch(
+            &buffer[..bytes_read],
+            block_size,
+            threads,
+            Some(&mut whole),
+        ));
+    }
+    Ok((hashes, format!("{:x}", whole.finalize())))
+}
+
+/// Block delta from `original_path` to `new_path` in
+/// `DEFAULT_BLOCK_SIZE` blocks, reporting how much of the new file has
+/// been read to `progress`.
+pub fn create_delta(
+    original_path: &Path,
+    new_path: &Path,
+    progress: Option<&mut FileProgress>,
+) -> Result<DeltaFile> {
+    create_delta_with_block_size(original_path, new_path, DEFAULT_BLOCK_SIZE, progress)
+}
+
+/// `create_delta` in `block_size` blocks. Blocks are hashed in batches
+/// across threads; the original is read once for both its block hashes
+/// and `original_file_hash`.
+pub fn create_delta_with_block_size(
+    original_path: &Path,
+    new_path: &Path,
+    block_size: usize,
+    mut progress: Option<&mut FileProgress>,
+) -> Result<DeltaFile> {
+    let (original_hashes, original_file_hash) = if original_path.exists() {
+        calculate_block_hashes(original_path, block_size)?
+    } else {
+        (Vec::new(), EMPTY_FILE_HASH.to_string())
+    };
+
+    let mut new_file = fs::File::open(new_path)
+        .with_context(|| format!("Failed to open new file: {:?}", new_path))?;
+    let new_file_size = fs::metadata(new_path)?.len();
+    let threads = hash_threads();
+    let mut buffer = vec![0u8; threads * MAX_BLOCK_SIZE];
+    let mut changed_blocks = Vec::new();
+    let mut block_index = 0;
+    let mut bytes_done = 0u64;
+
+    loop {
+        let bytes_read = read_batch(&mut new_file, &mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        let batch = &buffer[..bytes_read];
+        for (block, new_hash) in batch
+            .chunks(block_size)
+            .zip(hash_batch(batch, block_size, threads, None))
+        {
+            // Blocks past the original's end are new (file grew).
+            if original_hashes.get(block_index) != Some(&new_hash) {
+                changed_blocks.push(DeltaBlock {
+                    index: block_index,
+                    hash: new_hash,
+                    data: block.to_vec(),
+                });
+            }
+            block_index += 1;
+        }
+        bytes_done += bytes_read as u64;
+        if let Some(progress) = progress.as_deref_mut() {
+            progress.update(bytes_done);
+        }
+    }
+
+    let total_blocks = block_index;
+
+    Ok(DeltaFile {
+        original_file_hash,
+        block_size,
+        total_blocks,
+        changed_blocks,
+        new_file_size,
+    })
+}
+
+/// Write `original_path` with `delta` applied to `output_path`, reporting
+/// the bytes written so far to `progress`.
+pub fn apply_delta(
+    original_path: &Path,
+    delta: &DeltaFile,
+    output_path: &Path,
+    progress: Option<&mut FileProgress>,
+) -> Result<()> {
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let original: Box<dyn Read> = if original_path.exists() {
+        Box::new(fs::File::open(original_path)?)
+    } else {
+        Box::new(std::io::empty())
+    };
+    let mut writer = BufWriter::new(fs::File::create(output_path)?);
+    apply_delta_to(original, delta, &mut writer, progress)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Stream the version `delta` describes into `output`, given the content
+/// it was made against as `original`.
+pub fn apply_delta_to(
+    original: impl Read,
+    delta: &DeltaFile,
+    output: &mut impl Write,
+    mut progress: Option<&mut FileProgress>,
+) -> Result<()> {
+    let mut blocks: Vec<Vec<u8>> = Vec::new();
+
+    // Read original file blocks
+    let mut reader = BufReader::new(original);
+    let mut buffer = vec![0u8; delta.block_size];
+    loop {
+        let bytes_read = reader.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        blocks.push(buffer[..bytes_read].to_vec());
+    }
+
+    // Extend blocks if file grew
+    while blocks.len() < delta.total_blocks {
+        blocks.push(Vec::new());
+    }
+
+    // Apply changed blocks
+    for changed_block in &delta.changed_blocks {
+        if changed_block.index < blocks.len() {
+            blocks[changed_block.index] = changed_block.data.clone();
+        }
+    }
+
+    let mut bytes_written: u64 = 0;
+    for block in &blocks {
+        let remaining = delta.new_file_size - bytes_written;
+        let to_write = std::cmp::min(block.len() as u64, remaining) as usize;
+        output.write_all(&block[..to_write])?;
+        bytes_written += to_write as u64;
+        if let Some(progress) = progress.as_deref_mut() {
+            progress.update(bytes_written);
+        }
+    }
+    Ok(())
+}
+
+/// Serialized form of a delta, sized before deciding to store it.
+pub fn encode_delta(delta: &DeltaFile) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec(delta)?)
+}
+
+/// Write a delta encoded by `encode_delta`.
+pub fn save_delta(content: &[u8], delta_path: &Path) -> Result<()> {
+    if let Some(parent) = delta_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(delta_path, content)?;
+    Ok(())
+}
+
+pub fn load_delta(delta_path: &Path) -> Result<DeltaFile> {
+    let content = fs::read(delta_path)?;
+    parse_delta(&content)
+}
+
+/// Bytes at the end of an encoded delta that always hold its
+/// `new_file_size`, the last field written.
+pub const SIZE_TAIL_LEN: u64 = 64;
+
+/// `new_file_size` read from the last bytes of an encoded delta, without
+/// decoding its blocks. `None` if the tail does not end with that field.
+pub fn new_file_size_in_tail(tail: &[u8]) -> Option<u64> {
+    const FIELD: &[u8] = b"\"new_file_size\":";
+    let body = tail.strip_suffix(b"}")?;
+    let start = body.windows(FIELD.len()).rposition(|w| w == FIELD)? + FIELD.len();
+    std::str::from_utf8(&body[start..]).ok()?.parse().ok()
+}
+
+/// `new_file_size` of a stored delta, reading only its tail and falling
+/// back to decoding the whole file.
+pub fn read_new_file_size(delta_path: &Path) -> Result<u64> {
+    let mut file = fs::File::open(delta_path)?;
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(SIZE_TAIL_LEN)))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+    match new_file_size_in_tail(&tail) {
+        Some(size) => Ok(size),
+        None => Ok(load_delta(delta_path)?.new_file_size),
+    }
+}
+
+/// Decode a delta read from somewhere other than a plain file (e.g. an
+/// archive entry).
+pub fn parse_delta(content: &[u8]) -> Result<DeltaFile> {
+    let delta: DeltaFile = serde_json::from_slice(content)?;
+    Ok(delta)
+}
+
+/// Re-read a file produced by `apply_delta` and check its size and every
+/// block the delta replaced against the hashes recorded at backup time.
+pub fn verify_applied(delta: &DeltaFile, output_path: &Path) -> Result<()> {
+    let content =
+        fs::read(output_path).with_context(|| format!("Failed to read {:?}", output_path))?;
+    if content.len() as u64 != delta.new_file_size {
+        return Err(anyhow::anyhow!(
+            "Verification failed for {:?}: {} bytes written, expected {}",
+            output_path,
+            content.len(),
+            delta.new_file_size
+        ));
+    }
+    for block in &delta.changed_blocks {
+        let start = block.index * delta.block_size;
+        let written = content.get(start..start + block.data.len());
+        let matches = written.is_some_and(|written| {
+            let mut hasher = Sha256::new();
+            hasher.update(written);
+            format!("{:x}", hasher.finalize()) == block.hash
+        });
+        if !matches {
+            return Err(anyhow::anyhow!(
+                "Verification failed for {:?}: block {} does not match the delta",
+                output_path,
+                block.index
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub fn delta_size(delta: &DeltaFile) -> usize {
+    delta.changed_blocks.iter().map(|b| b.data.len()).sum()
+}
+
+#[cfg(test)]
+#[path = "tests/delta_tests.rs"]
+mod tests;
@@ -0,0 +1,531 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::backup::{self, BackupManager};
+use crate::config::BackupConfig;
+use crate::daemon;
+use crate::lock::{self, LockState};
+use crate::units;
+
+/// Free space below which a backup dir is reported as running low.
+const LOW_SPACE_BYTES: u64 = 1024 * 1024 * 1024;
+/// Clock differences up to this are treated as normal drift.
+const MAX_CLOCK_SKEW_SECS: i64 = 60;
+/// Created and removed again to learn whether a directory is writable.
+const PROBE_FILE_NAME: &str = ".ardiex-doctor-probe";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DoctorStatus {
+    Ok,
+    /// Works today, but is likely to cause trouble.
+    Warning,
+    /// Backups fail or cannot run as configured.
+    Error,
+}
+
+impl DoctorStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DoctorStatus::Ok => "ok",
+            DoctorStatus::Warning => "warning",
+            DoctorStatus::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DoctorFinding {
+    /// `config`, `permissions`, `cron`, `source`, `backup_dir`,
+    /// `disk_space`, `lock`, `inotify`, `clock` or `instance`.
+    pub check: &'static str,
+    pub status: DoctorStatus,
+    pub message: String,
+    /// What to do about it; only set for warnings and errors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+/// Outcome of `ardiex doctor`: every check that ran, passed or not.
+#[derive(Debug, Default, Serialize)]
+pub struct DoctorReport {
+    pub findings: Vec<DoctorFinding>,
+}
+
+impl DoctorReport {
+    pub fn count(&self, status: DoctorStatus) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.status == status)
+            .count()
+    }
+
+    fn ok(&mut self, check: &'static str, message: String) {
+        self.findings.push(DoctorFinding {
+            check,
+            status: DoctorStatus::Ok,
+            message,
+            hint: None,
+        });
+    }
+
+    fn problem(
+        &mut self,
+        check: &'static str,
+        status: DoctorStatus,
+        message: String,
+        hint: String,
+    ) {
+        self.findings.push(DoctorFinding {
+            check,
+            status,
+            message,
+            hint: Some(hint),
+        });
+    }
+}
+
+/// Check the environment `config`, loaded from `config_path`, runs in.
+/// Nothing is changed apart from a probe file created and removed in each
+/// directory ardiex writes to. `reference_time` is a trusted clock reading
+/// (e.g. an HTTP `Date` header) to measure skew against; `None` only
+/// checks the backups' own timestamps.
+pub fn diagnose(
+    config: &BackupConfig,
+    config_path: &Path,
+    reference_time: Option<DateTime<Utc>>,
+) -> DoctorReport {
+    let localized = config.localized();
+    let mut report = DoctorReport::default();
+    check_config_file(&mut report, config_path, config);
+    check_schedules(&mut report, config);
+    let service_pid = check_instance(
+        &mut report,
+        &config_path.with_file_name(daemon::PID_FILE_NAME),
+    );
+    check_backup_paths(&mut report, &localized, service_pid);
+    #[cfg(target_os = "linux")]
+    check_inotify(&mut report, &localized);
+    check_clock(&mut report, &localized, Utc::now(), reference_time);
+    report
+}
+
+fn probe_writable(dir: &Path) -> std::io::Result<()> {
+    let probe = dir.join(PROBE_FILE_NAME);
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)?;
+    fs::remove_file(&probe)
+}
+
+fn has_secrets(config: &BackupConfig) -> bool {
+    config.api_token.is_some()
+        || config.notifications.webhook_url.is_some()
+        || config.notifications.slack_webhook_url.is_some()
+        || config
+            .notifications
+            .smtp
+            .as_ref()
+            .is_some_and(|smtp| smtp.password.is_some())
+        || config
+            .sources
+            .iter()
+            .any(|source| source.healthcheck_url.is_some())
+}
+
+fn check_config_file(report: &mut DoctorReport, config_path: &Path, config: &BackupConfig) {
+    report.ok("config", format!("Settings loaded from {:?}", config_path));
+
+    // History and the PID file are written next to the settings.
+    if let Some(dir) = config_path.parent()
+        && let Err(e) = probe_writable(dir)
+    {
+        report.problem(
+            "permissions",
+            DoctorStatus::Error,
+            format!("Cannot write to {:?}: {}", dir, e),
+            format!(
+                "Run history and the service PID file live next to the settings; \
+                 give your user write access to {:?} or point --config elsewhere",
+                dir
+            ),
+        );
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let Ok(metadata) = fs::metadata(config_path) else {
+            return;
+        };
+        let mode = metadata.permissions().mode() & 0o777;
+        if mode & 0o200 == 0 {
+            report.problem(
+                "permissions",
+                DoctorStatus::Warning,
+                format!("{:?} is read-only (mode {:o})", config_path, mode),
+                format!(
+                    "`ardiex config set` cannot save changes; run `chmod u+w {}`",
+                    config_path.display()
+                ),
+            );
+        }
+        if mode & 0o077 != 0 && has_secrets(config) {
+            report.problem(
+                "permissions",
+                DoctorStatus::Warning,
+                format!(
+                    "{:?} holds tokens or passwords but is readable by other users (mode {:o})",
+                    config_path, mode
+                ),
+                format!("Run `chmod 600 {}`", config_path.display()),
+            );
+        }
+    }
+}
+
+fn check_schedules(report: &mut DoctorReport, config: &BackupConfig) {
+    let mut schedules = vec![("global cron_schedule".to_string(), &config.cron_schedule)];
+    for source in &config.sources {
+        for (key, schedule) in [
+            ("cron_schedule", &source.cron_schedule),
+            ("verify_schedule", &source.verify_schedule),
+        ] {
+            if let Some(schedule) = schedule {
+                schedules.push((format!("{:?} {}", source.source_dir, key), schedule));
+            }
+        }
+        for destination in &source.backup_dirs {
+            if let Some(ref schedule) = destination.cron_schedule {
+                schedules.push((format!("{:?} cron_schedule", destination.path), schedule));
+            }
+        }
+    }
+
+    let mut invalid = 0;
+    for (label, schedule) in &schedules {
+        if let Err(e) = cron::Schedule::from_str(schedule) {
+            invalid += 1;
+            report.problem(
+                "cron",
+                DoctorStatus::Error,
+                format!("{}: invalid expression {:?}: {}", label, schedule, e),
+                "Use six fields with seconds first, e.g. \"0 0 * * * *\" for hourly; \
+                 preview the result with `ardiex schedule`"
+                    .to_string(),
+            );
+        }
+    }
+    if invalid == 0 {
+        report.ok(
+            "cron",
+            format!("{} cron expression(s) are valid", schedules.len()),
+        );
+    }
+}
+
+/// Report the service PID file; returns the PID of a running service.
+fn check_instance(report: &mut DoctorReport, pid_path: &Path) -> Option<u32> {
+    match daemon::read_pid(pid_path) {
+        Err(e) => {
+            report.problem(
+                "instance",
+                DoctorStatus::Warning,
+                format!("{:#}", e),
+                format!(
+                    "Delete {:?} if no `ardiex run` is active; the next one writes it again",
+                    pid_path
+                ),
+            );
+            None
+        }
+        Ok(None) => {
+            report.ok("instance", "The backup service is not running".to_string());
+            None
+        }
+        Ok(Some(pid)) if daemon::running_pid(pid_path).ok().flatten() == Some(pid) => {
+            report.ok(
+                "instance",
+                format!("The backup service is running (pid {})", pid),
+            );
+            Some(pid)
+        }
+        Ok(Some(pid)) => {
+            report.problem(
+                "instance",
+                DoctorStatus::Warning,
+                format!(
+                    "Stale PID file {:?}: no service holds it (pid {})",
+                    pid_path, pid
+                ),
+                "The service exited without cleaning up; `ardiex run` replaces the file, \
+                 or delete it by hand"
+                    .to_string(),
+            );
+            None
+        }
+    }
+}
+
+fn check_backup_paths(report: &mut DoctorReport, config: &BackupConfig, service_pid: Option<u32>) {
+    for source in config.sources.iter().filter(|source| source.enabled) {
+        if !source.source_dir.exists() {
+            report.problem(
+                "source",
+                DoctorStatus::Error,
+                format!("Source {:?} does not exist", source.source_dir),
+                format!(
+                    "Mount or restore it, or stop backing it up with \
+                     `ardiex config disable-source {}`",
+                    source.source_dir.display()
+                ),
+            );
+        }
+        for backup_dir in source.effective_backup_dirs() {
+            check_backup_dir(report, &backup_dir, service_pid);
+        }
+    }
+}
+
+fn check_backup_dir(report: &mut DoctorReport, backup_dir: &Path, service_pid: Option<u32>) {
+    if !backup_dir.exists() {
+        report.problem(
+            "backup_dir",
+            DoctorStatus::Warning,
+            format!("{:?} does not exist yet", backup_dir),
+            "The first backup creates it; if it lives on removable or network storage, \
+             make sure that is mounted"
+                .to_string(),
+        );
+    } else if let Err(e) = probe_writable(backup_dir) {
+        report.problem(
+            "backup_dir",
+            DoctorStatus::Error,
+            format!("Cannot write to {:?}: {}", backup_dir, e),
+            "Give the user running ardiex write access, or remount the volume read-write"
+                .to_string(),
+        );
+    } else {
+        check_lock(report, backup_dir, service_pid);
+    }
+
+    // A missing dir will be created on the nearest existing ancestor's volume.
+    let Some(existing) = backup_dir.ancestors().find(|dir| dir.exists()) else {
+        return;
+    };
+    match backup::available_space(existing) {
+        Some(available) if available < LOW_SPACE_BYTES => report.problem(
+            "disk_space",
+            DoctorStatus::Warning,
+            format!(
+                "{:?}: only {} free",
+                backup_dir,
+                units::format_size(available)
+            ),
+            "Free up space, lower max_backups or max_total_size, or set \
+             low_space_action to prune so old points make room"
+                .to_string(),
+        ),
+        Some(available) => report.ok(
+            "disk_space",
+            format!("{:?}: {} free", backup_dir, units::format_size(available)),
+        ),
+        None => {}
+    }
+}
+
+fn check_lock(report: &mut DoctorReport, backup_dir: &Path, service_pid: Option<u32>) {
+    match lock::inspect(backup_dir) {
+        Ok(LockState::Free) => {}
+        Ok(LockState::Held(Some(info))) if Some(info.pid) == service_pid => report.ok(
+            "lock",
+            format!(
+                "{:?}: the service is running {} since {}",
+                backup_dir, info.operation, info.acquired_at
+            ),
+        ),
+        Ok(LockState::Held(holder)) => {
+            let holder = holder
+                .map(|info| {
+                    format!(
+                        " (pid {}, {} since {})",
+                        info.pid, info.operation, info.acquired_at
+                    )
+                })
+                .unwrap_or_default();
+            report.problem(
+                "lock",
+                DoctorStatus::Warning,
+                format!(
+                    "{:?} is locked by another ardiex process{}",
+                    backup_dir, holder
+                ),
+                "Backups of this dir wait for it to finish; if it is a second service \
+                 or profile writing to the same dir, give each its own backup dir"
+                    .to_string(),
+            );
+        }
+        Ok(LockState::Stale(info)) => report.problem(
+            "lock",
+            DoctorStatus::Warning,
+            format!(
+                "{:?}: stale lock left by pid {} during {} at {}",
+                backup_dir, info.pid, info.operation, info.acquired_at
+            ),
+            format!(
+                "The next backup takes it over; run `ardiex check {}` to look for \
+                 leftovers of the interrupted run",
+                backup_dir.display()
+            ),
+        ),
+        Err(e) => report.problem(
+            "lock",
+            DoctorStatus::Warning,
+            format!("{:#}", e),
+            "Check the permissions of the lock file".to_string(),
+        ),
+    }
+}
+
+/// Event-driven sources need one inotify watch per directory.
+#[cfg(target_os = "linux")]
+fn check_inotify(report: &mut DoctorReport, config: &BackupConfig) {
+    const MAX_USER_WATCHES: &str = "/proc/sys/fs/inotify/max_user_watches";
+
+    if !config.enable_event_driven {
+        return;
+    }
+    let roots: Vec<_> = config
+        .sources
+        .iter()
+        .filter(|source| source.enabled && source.resolve(config).enable_event_driven)
+        .flat_map(|source| source.scan_roots())
+        .collect();
+    if roots.is_empty() {
+        return;
+    }
+    let Some(limit) = fs::read_to_string(MAX_USER_WATCHES)
+        .ok()
+        .and_then(|limit| limit.trim().parse::<u64>().ok())
+    else {
+        return;
+    };
+    let watched = roots
+        .iter()
+        .flat_map(|root| walkdir::WalkDir::new(root).into_iter().flatten())
+        .filter(|entry| entry.file_type().is_dir())
+        .count() as u64;
+    report_watch_usage(report, watched, limit);
+}
+
+#[cfg(target_os = "linux")]
+fn report_watch_usage(report: &mut DoctorReport, watched: u64, limit: u64) {
+    let hint = format!(
+        "Raise the limit with `sudo sysctl fs.inotify.max_user_watches={}` and persist it \
+         in /etc/sysctl.d/, or turn off enable_event_driven for the largest source \
+         (cron backups still run)",
+        (watched * 2).max(limit)
+    );
+    if watched > limit {
+        report.problem(
+            "inotify",
+            DoctorStatus::Error,
+            format!(
+                "Event-driven sources have {} directories but max_user_watches is {}; \
+                 changes in the rest are missed",
+                watched, limit
+            ),
+            hint,
+        );
+    } else if watched * 5 > limit * 4 {
+        // Editors, IDEs and sync clients share the same per-user limit.
+        report.problem(
+            "inotify",
+            DoctorStatus::Warning,
+            format!(
+                "Event-driven sources use {} of {} inotify watches",
+                watched, limit
+            ),
+            hint,
+        );
+    } else {
+        report.ok(
+            "inotify",
+            format!(
+                "Event-driven sources use {} of {} inotify watches",
+                watched, limit
+            ),
+        );
+    }
+}
+
+fn check_clock(
+    report: &mut DoctorReport,
+    config: &BackupConfig,
+    now: DateTime<Utc>,
+    reference_time: Option<DateTime<Utc>>,
+) {
+    let hint = "Turn on time synchronization (`timedatectl set-ntp true`, or \
+                `w32tm /resync` on Windows); retention, schedules and point names \
+                follow the system clock";
+    let mut skewed = false;
+    for source in &config.sources {
+        for backup_dir in source.effective_backup_dirs() {
+            let metadata = BackupManager::load_source_metadata(&backup_dir.join("metadata.json"));
+            if let Some(last_backup) = metadata.last_backup
+                && (last_backup - now).num_seconds() > MAX_CLOCK_SKEW_SECS
+            {
+                skewed = true;
+                report.problem(
+                    "clock",
+                    DoctorStatus::Warning,
+                    format!(
+                        "{:?}: the last backup is dated {}, in the future; the clock moved backwards",
+                        backup_dir, last_backup
+                    ),
+                    hint.to_string(),
+                );
+            }
+        }
+    }
+
+    let Some(reference) = reference_time else {
+        if !skewed {
+            report.ok(
+                "clock",
+                "No backup is dated in the future (no reference clock reached)".to_string(),
+            );
+        }
+        return;
+    };
+    let skew = (now - reference).num_seconds();
+    if skew.abs() > MAX_CLOCK_SKEW_SECS {
+        report.problem(
+            "clock",
+            DoctorStatus::Warning,
+            format!(
+                "The system clock is {}s {} the reference clock",
+                skew.abs(),
+                if skew > 0 { "ahead of" } else { "behind" }
+            ),
+            hint.to_string(),
+        );
+    } else if !skewed {
+        report.ok(
+            "clock",
+            format!(
+                "The system clock is within {}s of the reference clock",
+                skew.abs()
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/doctor_tests.rs"]
+mod tests;
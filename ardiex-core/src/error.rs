@@ -0,0 +1,87 @@
+use crate::lock::LockInfo;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Failures an embedding program may want to tell apart.
+///
+/// They travel inside `anyhow::Error`, so callers look for them with
+/// `err.downcast_ref::<ardiex_core::Error>()`; every other failure is an
+/// I/O or parse error with context attached.
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// Another process holds the backup dir lock.
+    Locked {
+        backup_dir: PathBuf,
+        holder: Option<LockInfo>,
+    },
+    /// A shared (restore) lock was not granted within its timeout.
+    LockTimeout {
+        backup_dir: PathBuf,
+        operation: String,
+        holder: Option<LockInfo>,
+    },
+    SourceMissing(PathBuf),
+    BackupDirMissing(PathBuf),
+    /// The backup dir already holds backups of a different source.
+    ForeignBackupDir {
+        backup_dir: PathBuf,
+        source_dir: PathBuf,
+        host: String,
+    },
+    NoBackups(PathBuf),
+    /// The source's `backup_timeout` expired mid-backup.
+    BackupTimeout,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Locked { backup_dir, holder } => write_locked(f, backup_dir, holder.as_ref()),
+            Error::LockTimeout {
+                backup_dir,
+                operation,
+                holder,
+            } => {
+                write!(f, "Timed out waiting to {}: ", operation)?;
+                write_locked(f, backup_dir, holder.as_ref())
+            }
+            Error::SourceMissing(path) => write!(f, "Source directory does not exist: {:?}", path),
+            Error::BackupDirMissing(path) => {
+                write!(f, "Backup directory does not exist: {:?}", path)
+            }
+            Error::ForeignBackupDir {
+                backup_dir,
+                source_dir,
+                host,
+            } => write!(
+                f,
+                "{:?} holds backups of {:?} from {}; give each source its own backup dir \
+                 or turn on namespace_backups for a shared destination",
+                backup_dir, source_dir, host
+            ),
+            Error::NoBackups(path) => write!(f, "No backups found in {:?}", path),
+            Error::BackupTimeout => write!(f, "Backup cancelled: backup_timeout expired"),
+        }
+    }
+}
+
+fn write_locked(
+    f: &mut fmt::Formatter<'_>,
+    backup_dir: &Path,
+    holder: Option<&LockInfo>,
+) -> fmt::Result {
+    match holder {
+        Some(info) => write!(
+            f,
+            "Backup directory {:?} is locked by another ardiex process (pid: {}, operation: {}, since: {})",
+            backup_dir, info.pid, info.operation, info.acquired_at
+        ),
+        None => write!(
+            f,
+            "Backup directory {:?} is locked by another ardiex process",
+            backup_dir
+        ),
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,499 @@
+use crate::error::Error;
+use anyhow::{Context, Result};
+use chrono::{Datelike, Local, Timelike};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use log::{debug, info};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::archive::{ArchiveEntry, ArchiveReader};
+use crate::attrs::{self, AttributeManifest, FileAttributes};
+use crate::backup::BackupManager;
+use crate::dedup::{self, ChunkStore, IndexedFile};
+use crate::delta::{self, DeltaFile};
+use crate::lock::BackupDirLock;
+use crate::longpath;
+use crate::progress::Progress;
+use crate::repo;
+use crate::restore::{BackupEntry, RestoreManager};
+use crate::signing::SigningKey;
+
+/// Container `ardiex export` writes a restore point into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    TarGz,
+    Zip,
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "tar.gz" | "tgz" => Ok(Self::TarGz),
+            "zip" => Ok(Self::Zip),
+            _ => Err(anyhow::anyhow!(
+                "Invalid export format: '{}'. Use 'tar.gz' or 'zip'",
+                value
+            )),
+        }
+    }
+}
+
+impl ExportFormat {
+    /// The format an output file name implies: `.tar.gz`/`.tgz` or `.zip`.
+    pub fn from_output(output: &Path) -> Option<Self> {
+        let name = output.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// What an export wrote.
+#[derive(Debug)]
+pub struct ExportSummary {
+    /// Points the exported state was rebuilt from: the full backup and the
+    /// incrementals after it.
+    pub points: Vec<String>,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Where the version of a file that a restore point ends up with is kept.
+enum Stored {
+    /// Plain copy or delta in a directory point.
+    Dir(PathBuf),
+    /// Plain copy or delta in an `.ardx` point.
+    Archive(PathBuf, ArchiveEntry),
+    /// Chunk list of a dedup point, with the root of its chunk store.
+    Dedup(PathBuf, IndexedFile),
+}
+
+struct PlannedFile {
+    /// Index of the point in the chain, so delta bases are looked up only
+    /// in the points before it, and reverse deltas rebuilt from the ones
+    /// after it.
+    point: usize,
+    stored: Stored,
+    delta: bool,
+    reverse: bool,
+    /// Bytes as stored, for progress.
+    stored_size: u64,
+}
+
+/// Rebuild `restore_point` of `backup_dir` (latest when `None`) and write
+/// it to `output` as a `format` archive. Every file is produced straight
+/// into the archive stream, so no restored tree is ever written. The
+/// archive is written next to `output` and renamed into place once
+/// complete.
+pub fn export_point(
+    backup_dir: &Path,
+    restore_point: Option<&str>,
+    format: ExportFormat,
+    output: &Path,
+    signing_key: Option<&SigningKey>,
+    lock_timeout: Option<Duration>,
+) -> Result<ExportSummary> {
+    let backup_dir = &longpath::extended(backup_dir);
+    let _lock = BackupDirLock::acquire_shared(backup_dir, "export", lock_timeout)?;
+    repo::check_readable(backup_dir)?;
+    let backups = RestoreManager::list_backups(backup_dir)?;
+    if backups.is_empty() {
+        if let Some(mirror_root) = BackupManager::mirror_dir(backup_dir) {
+            return Err(anyhow::anyhow!(
+                "{:?} is a mirror with no restore points; archive {:?} directly",
+                backup_dir,
+                mirror_root
+            ));
+        }
+        return Err(Error::NoBackups(backup_dir.to_path_buf()).into());
+    }
+    let chain = RestoreManager::select_backups(&backups, restore_point)?;
+    if let Some(key) = signing_key {
+        RestoreManager::verify_signatures(backup_dir, &chain, key)?;
+    }
+
+    let files = plan_files(&chain)?;
+    let mut attributes = AttributeManifest::new();
+    for backup in &chain {
+        attributes.extend(RestoreManager::read_point_manifest(backup)?);
+    }
+    let whole_chain = RestoreManager::extend_to_chain_end(&backups, &chain);
+
+    let partial = output.with_file_name(format!(
+        "{}.partial",
+        output.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let written = write_export(&whole_chain, &files, &attributes, format, &partial);
+    let bytes = match written {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+    };
+    fs::rename(&partial, output)
+        .with_context(|| format!("Failed to move the export into place at {:?}", output))?;
+
+    let summary = ExportSummary {
+        points: chain.iter().map(|backup| backup.name.clone()).collect(),
+        files: files.len(),
+        bytes,
+    };
+    info!(
+        "Exported {} files ({} bytes) from {:?} to {:?}",
+        summary.files, summary.bytes, backup_dir, output
+    );
+    Ok(summary)
+}
+
+/// Every file the chain restores, with where its final version is kept.
+fn plan_files(chain: &[&BackupEntry]) -> Result<BTreeMap<PathBuf, PlannedFile>> {
+    let mut files = BTreeMap::new();
+    for (point, backup) in chain.iter().enumerate() {
+        if backup.path.is_file() {
+            let reader = ArchiveReader::open_with_env(&backup.path)?;
+            for entry in reader.entries() {
+                let relative = entry.relative_path();
+                if attrs::is_attributes_file(&relative) {
+                    continue;
+                }
+                files.insert(
+                    RestoreManager::strip_delta_extension(&relative),
+                    PlannedFile {
+                        point,
+                        delta: entry.path.ends_with(".delta"),
+                        reverse: false,
+                        stored_size: entry.size,
+                        stored: Stored::Archive(backup.path.clone(), entry.clone()),
+                    },
+                );
+            }
+        } else if let Some(index) = dedup::read_index(&backup.path)? {
+            let store_root = index.store_root(backup.path.parent().unwrap_or(&backup.path));
+            for (key, entry) in index.files {
+                files.insert(
+                    attrs::key_path(&key),
+                    PlannedFile {
+                        point,
+                        delta: false,
+                        reverse: false,
+                        stored_size: entry.size,
+                        stored: Stored::Dedup(store_root.clone(), entry),
+                    },
+                );
+            }
+        } else {
+            for entry in walkdir::WalkDir::new(&backup.path).min_depth(1) {
+                let entry = entry?;
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                let relative = entry.path().strip_prefix(&backup.path)?;
+                if attrs::is_attributes_file(relative) {
+                    continue;
+                }
+                let name = entry.file_name().to_string_lossy();
+                files.insert(
+                    RestoreManager::strip_delta_extension(relative),
+                    PlannedFile {
+                        point,
+                        delta: name.ends_with(".delta"),
+                        reverse: delta::is_reverse_delta_name(&name),
+                        stored_size: entry.metadata()?.len(),
+                        stored: Stored::Dir(entry.path().to_path_buf()),
+                    },
+                );
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Write every planned file into a new `format` archive at `path`; the
+/// number of content bytes written. `chain` runs past the exported point
+/// to the end of its chain, where reverse deltas are rebuilt from.
+fn write_export(
+    chain: &[&BackupEntry],
+    files: &BTreeMap<PathBuf, PlannedFile>,
+    attributes: &AttributeManifest,
+    format: ExportFormat,
+    path: &Path,
+) -> Result<u64> {
+    let mut writer = ExportWriter::create(path, format)?;
+    let mut readers: HashMap<PathBuf, ArchiveReader> = HashMap::new();
+    let mut bytes = 0u64;
+    let mut progress = Progress::new(
+        "Export progress",
+        path,
+        files.len(),
+        files.values().map(|file| file.stored_size).sum(),
+    );
+
+    for (relative, file) in files {
+        progress.start_file(relative);
+        debug!("Exporting {:?}", relative);
+        let attributes = attributes.get(&attrs::manifest_key(relative));
+        if file.reverse
+            && let Stored::Dir(stored) = &file.stored
+        {
+            // Rebuilt through a scratch file next to the export: it takes
+            // a chain of newer versions, not one base in memory.
+            let scratch = path.with_extension("rebuild");
+            let rebuilt = delta::load_delta(stored).and_then(|delta_data| {
+                RestoreManager::rebuild_reverse_delta(
+                    delta_data,
+                    relative,
+                    &chain[file.point + 1..],
+                    &scratch,
+                    None,
+                )
+            });
+            let added = rebuilt.and_then(|()| {
+                let size = fs::metadata(&scratch)?.len();
+                writer.add_file(relative, size, attributes, |out| {
+                    io::copy(&mut File::open(&scratch)?, out)?;
+                    Ok(())
+                })?;
+                Ok(size)
+            });
+            let _ = fs::remove_file(&scratch);
+            bytes += added.with_context(|| format!("Failed to rebuild {:?}", stored))?;
+        } else if file.delta {
+            let delta_data = match &file.stored {
+                Stored::Dir(path) => delta::load_delta(path)?,
+                Stored::Archive(archive, entry) => {
+                    delta::parse_delta(&archive_reader(&mut readers, archive)?.read_entry(entry)?)
+                        .with_context(|| format!("Failed to load delta {}", entry.path))?
+                }
+                Stored::Dedup(..) => {
+                    return Err(anyhow::anyhow!(
+                        "Dedup points hold no deltas: {:?}",
+                        relative
+                    ));
+                }
+            };
+            let base = delta_base(&delta_data, relative, &chain[..file.point], &mut readers)?;
+            writer.add_file(relative, delta_data.new_file_size, attributes, |mut out| {
+                delta::apply_delta_to(base.as_slice(), &delta_data, &mut out, None)
+            })?;
+            bytes += delta_data.new_file_size;
+        } else {
+            let size = match &file.stored {
+                Stored::Dir(path) => {
+                    let size = fs::metadata(path)?.len();
+                    writer.add_file(relative, size, attributes, |out| {
+                        io::copy(&mut File::open(path)?, out)?;
+                        Ok(())
+                    })?;
+                    size
+                }
+                Stored::Archive(archive, entry) => {
+                    let reader = archive_reader(&mut readers, archive)?;
+                    writer.add_file(relative, entry.size, attributes, |mut out| {
+                        reader.extract_to(entry, &mut out, None)
+                    })?;
+                    entry.size
+                }
+                Stored::Dedup(store_root, entry) => {
+                    let store = ChunkStore::open(store_root);
+                    writer.add_file(relative, entry.size, attributes, |mut out| {
+                        store.write_file(entry, &mut out, None)
+                    })?;
+                    entry.size
+                }
+            };
+            bytes += size;
+        }
+        progress.finish_file(file.stored_size);
+    }
+
+    writer.finish()?;
+    Ok(bytes)
+}
+
+fn archive_reader<'a>(
+    readers: &'a mut HashMap<PathBuf, ArchiveReader>,
+    archive: &Path,
+) -> Result<&'a mut ArchiveReader> {
+    Ok(match readers.entry(archive.to_path_buf()) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => entry.insert(ArchiveReader::open_with_env(archive)?),
+    })
+}
+
+/// Content of the file `delta_data` was made against: empty, or the
+/// newest plain copy with its hash in the `earlier` points of the chain.
+fn delta_base(
+    delta_data: &DeltaFile,
+    relative: &Path,
+    earlier: &[&BackupEntry],
+    readers: &mut HashMap<PathBuf, ArchiveReader>,
+) -> Result<Vec<u8>> {
+    let expected = delta_data.original_file_hash.as_str();
+    if expected == delta::EMPTY_FILE_HASH {
+        return Ok(Vec::new());
+    }
+    for point in earlier.iter().rev() {
+        if point.path.is_file() {
+            let reader = archive_reader(readers, &point.path)?;
+            if let Some(entry) = reader
+                .find(relative)
+                .filter(|entry| entry.sha256 == expected)
+                .cloned()
+            {
+                return reader.read_entry(&entry);
+            }
+            continue;
+        }
+        let candidate = point.path.join(relative);
+        if candidate.is_file()
+            && BackupManager::calculate_file_hash(&candidate).is_ok_and(|hash| hash == expected)
+        {
+            return Ok(fs::read(&candidate)?);
+        }
+    }
+    Err(anyhow::anyhow!(
+        "No base for the delta of {:?}: no earlier point of the chain holds the version it \
+         was made against (sha256 {})",
+        relative,
+        expected
+    ))
+}
+
+/// The archive being written; files are added one at a time, each one's
+/// content pushed straight into the compressed stream.
+enum ExportWriter {
+    TarGz(tar::Builder<GzEncoder<BufWriter<File>>>),
+    Zip(Box<zip::ZipWriter<BufWriter<File>>>),
+}
+
+impl ExportWriter {
+    fn create(path: &Path, format: ExportFormat) -> Result<Self> {
+        let file = BufWriter::new(
+            File::create(path).with_context(|| format!("Failed to create {:?}", path))?,
+        );
+        Ok(match format {
+            ExportFormat::TarGz => Self::TarGz(tar::Builder::new(GzEncoder::new(
+                file,
+                Compression::default(),
+            ))),
+            ExportFormat::Zip => Self::Zip(Box::new(zip::ZipWriter::new(file))),
+        })
+    }
+
+    /// Add `relative` holding `size` bytes, which `write` produces. The
+    /// recorded mode and mtime are kept; ownership only in tar.
+    fn add_file(
+        &mut self,
+        relative: &Path,
+        size: u64,
+        attributes: Option<&FileAttributes>,
+        write: impl FnOnce(&mut dyn Write) -> Result<()>,
+    ) -> Result<()> {
+        let mode = attributes.and_then(|attributes| attributes.mode);
+        let modified = attributes.and_then(|attributes| attributes.modified);
+        match self {
+            Self::TarGz(builder) => {
+                let mut header = tar::Header::new_gnu();
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_size(size);
+                header.set_mode(mode.unwrap_or(0o644) & 0o7777);
+                header.set_mtime(modified.map_or(0, |time| time.timestamp().max(0) as u64));
+                if let Some(uid) = attributes.and_then(|attributes| attributes.uid) {
+                    header.set_uid(uid.into());
+                }
+                if let Some(gid) = attributes.and_then(|attributes| attributes.gid) {
+                    header.set_gid(gid.into());
+                }
+                // Writes the header (and a long-name entry when needed);
+                // the content follows directly, padded to a whole block.
+                builder.append_data(&mut header, relative, io::empty())?;
+                let mut out = CountingWriter {
+                    inner: builder.get_mut(),
+                    written: 0,
+                };
+                write(&mut out)?;
+                if out.written != size {
+                    return Err(anyhow::anyhow!(
+                        "{:?} came out at {} bytes instead of {}",
+                        relative,
+                        out.written,
+                        size
+                    ));
+                }
+                let padding = (512 - size % 512) % 512;
+                out.inner.write_all(&vec![0u8; padding as usize])?;
+            }
+            Self::Zip(zip) => {
+                let mut options = zip::write::SimpleFileOptions::default()
+                    .compression_method(zip::CompressionMethod::Deflated)
+                    .large_file(size >= u32::MAX as u64);
+                if let Some(mode) = mode {
+                    options = options.unix_permissions(mode & 0o7777);
+                }
+                if let Some(time) = modified.and_then(|time| {
+                    // Zip stores local time from 1980 on.
+                    let local = time.with_timezone(&Local);
+                    zip::DateTime::from_date_and_time(
+                        u16::try_from(local.year()).ok()?,
+                        local.month() as u8,
+                        local.day() as u8,
+                        local.hour() as u8,
+                        local.minute() as u8,
+                        local.second() as u8,
+                    )
+                    .ok()
+                }) {
+                    options = options.last_modified_time(time);
+                }
+                zip.start_file(attrs::manifest_key(relative), options)?;
+                write(zip.as_mut())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        let mut file = match self {
+            Self::TarGz(builder) => builder.into_inner()?.finish()?,
+            Self::Zip(zip) => zip.finish()?,
+        };
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// Counts what a file's producer writes, so a tar entry is never shorter
+/// or longer than its header says.
+struct CountingWriter<'a, W: Write> {
+    inner: &'a mut W,
+    written: u64,
+}
+
+impl<W: Write> Write for CountingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/export_tests.rs"]
+mod tests;
@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::backup::{BackupResult, BackupType};
+
+/// Stored next to settings.json so it survives daemon restarts and log
+/// rotation.
+pub const HISTORY_FILE_NAME: &str = "history.jsonl";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RunStatus {
+    #[serde(rename = "success")]
+    Success,
+    #[serde(rename = "failure")]
+    Failure,
+}
+
+/// One line of the history file: a finished backup of one source to one
+/// destination, or a failed backup of a source.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub finished_at: DateTime<Utc>,
+    pub source_dir: PathBuf,
+    pub status: RunStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_dir: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_type: Option<String>,
+    #[serde(default)]
+    pub files_backed_up: usize,
+    #[serde(default)]
+    pub bytes_processed: u64,
+    #[serde(default)]
+    pub duration_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RunRecord {
+    pub fn success(source_dir: &Path, result: &BackupResult) -> Self {
+        Self {
+            finished_at: Utc::now(),
+            source_dir: source_dir.to_path_buf(),
+            status: RunStatus::Success,
+            backup_dir: Some(result.backup_dir.clone()),
+            backup_type: Some(
+                match result.backup_type {
+                    BackupType::Full => "full",
+                    BackupType::Incremental => "inc",
+                }
+                .to_string(),
+            ),
+            files_backed_up: result.files_backed_up,
+            bytes_processed: result.bytes_processed,
+            duration_ms: result.duration_ms,
+            error: None,
+        }
+    }
+
+    pub fn failure(source_dir: &Path, error: String) -> Self {
+        Self {
+            finished_at: Utc::now(),
+            source_dir: source_dir.to_path_buf(),
+            status: RunStatus::Failure,
+            backup_dir: None,
+            backup_type: None,
+            files_backed_up: 0,
+            bytes_processed: 0,
+            duration_ms: 0,
+            error: Some(error),
+        }
+    }
+}
+
+/// Append one run's records in a single write so concurrent `backup` and
+/// `run` processes never interleave partial lines.
+pub fn append_records(path: &Path, records: &[RunRecord]) -> Result<()> {
+    if records.is_empty() {
+        return Ok(());
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open history file {:?}", path))?;
+
+    // Start on a fresh line if a crash left a torn last record behind.
+    let mut lines = String::new();
+    if file.metadata()?.len() > 0 {
+        let mut last = [0u8; 1];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            lines.push('\n');
+        }
+    }
+    for record in records {
+        lines.push_str(&serde_json::to_string(record)?);
+        lines.push('\n');
+    }
+    file.write_all(lines.as_bytes())
+        .with_context(|| format!("Failed to write history file {:?}", path))?;
+    Ok(())
+}
+
+/// The newest `limit` records (optionally for one source), oldest first.
+/// Unreadable lines, e.g. a torn write after a crash, are skipped.
+pub fn read_records(path: &Path, source: Option<&Path>, limit: usize) -> Result<Vec<RunRecord>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read history file {:?}", path))?;
+
+    let mut records: Vec<RunRecord> = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<RunRecord>(line) {
+            Ok(record) => {
+                if source.is_none_or(|source| record.source_dir == source) {
+                    records.push(record);
+                }
+            }
+            Err(e) => warn!(
+                "Skipping invalid history line {} in {:?}: {}",
+                index + 1,
+                path,
+                e
+            ),
+        }
+    }
+
+    let skip = records.len().saturating_sub(limit);
+    Ok(records.split_off(skip))
+}
+
+#[cfg(test)]
+#[path = "tests/history_tests.rs"]
+mod tests;
@@ -0,0 +1,134 @@
+//! Minimal HTTP/1.1 request reading shared by the REST API and the
+//! metrics exporter.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+
+const MAX_REQUEST_HEAD_BYTES: usize = 8 * 1024;
+const MAX_REQUEST_BODY_BYTES: usize = 64 * 1024;
+/// Time a client gets to send its whole request, so a stalled or trickling
+/// connection never holds a task open.
+pub(crate) const REQUEST_READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub(crate) struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+/// Read one request, giving up once `timeout` has passed since the start.
+pub(crate) async fn read_request(stream: &mut TcpStream, timeout: Duration) -> Result<Request> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut data = Vec::new();
+    let mut buf = [0u8; 4096];
+    let head_end = loop {
+        if let Some(end) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end;
+        }
+        if data.len() >= MAX_REQUEST_HEAD_BYTES {
+            anyhow::bail!("Request head exceeds {} bytes", MAX_REQUEST_HEAD_BYTES);
+        }
+        let n = read_until(stream, &mut buf, deadline).await?;
+        if n == 0 {
+            anyhow::bail!("Connection closed before the request head ended");
+        }
+        data.extend_from_slice(&buf[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&data[..head_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let target = request_line.next().unwrap_or_default();
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let length = match headers.get("content-length") {
+        Some(value) => value.parse::<usize>().context("Invalid content-length")?,
+        None => 0,
+    };
+    if length > MAX_REQUEST_BODY_BYTES {
+        anyhow::bail!("Request body exceeds {} bytes", MAX_REQUEST_BODY_BYTES);
+    }
+    let mut body = data[head_end + 4..].to_vec();
+    while body.len() < length {
+        let n = read_until(stream, &mut buf, deadline).await?;
+        if n == 0 {
+            anyhow::bail!("Connection closed before the request body ended");
+        }
+        body.extend_from_slice(&buf[..n]);
+    }
+    body.truncate(length);
+
+    let (path, query) = parse_target(target);
+    Ok(Request {
+        method,
+        path,
+        query,
+        headers,
+        body,
+    })
+}
+
+async fn read_until(
+    stream: &mut TcpStream,
+    buf: &mut [u8],
+    deadline: tokio::time::Instant,
+) -> Result<usize> {
+    tokio::time::timeout_at(deadline, stream.read(buf))
+        .await
+        .map_err(|_| anyhow::anyhow!("Timed out reading the request"))?
+        .map_err(Into::into)
+}
+
+/// Split a request target into its decoded path and query parameters.
+fn parse_target(target: &str) -> (String, HashMap<String, String>) {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(name), percent_decode(value))
+        })
+        .collect();
+    (percent_decode(path), query)
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+#[path = "tests/http_tests.rs"]
+mod tests;
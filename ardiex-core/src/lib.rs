@@ -0,0 +1,77 @@
+//! Backup, restore, delta and configuration engine of ardiex, for the
+//! `ardiex` CLI and other Rust programs that back up or restore with it.
+//!
+//! [`BackupManager`] backs up the sources of a [`BackupConfig`],
+//! [`RestoreManager`] lists and restores backup points, and
+//! [`progress::set_progress_callback`] reports file progress of both.
+//! Failures are `anyhow::Error`s; the ones a caller may act on carry an
+//! [`Error`] that `downcast_ref` finds under any added context.
+
+pub mod backup;
+pub mod config;
+pub mod progress;
+pub mod restore;
+pub mod signing;
+
+// Used by the `ardiex` and `updater` binaries; not part of the library API.
+#[doc(hidden)]
+pub mod api;
+#[doc(hidden)]
+pub mod bench;
+#[doc(hidden)]
+pub mod blackout;
+#[doc(hidden)]
+pub mod config_format;
+#[doc(hidden)]
+pub mod config_location;
+#[doc(hidden)]
+pub mod daemon;
+#[doc(hidden)]
+pub mod delta;
+#[doc(hidden)]
+pub mod doctor;
+#[doc(hidden)]
+pub mod export;
+#[doc(hidden)]
+pub mod history;
+#[doc(hidden)]
+pub mod logger;
+#[doc(hidden)]
+pub mod metrics;
+#[doc(hidden)]
+pub mod notification;
+#[doc(hidden)]
+pub mod patterns;
+#[doc(hidden)]
+pub mod point_name;
+#[doc(hidden)]
+pub mod priority;
+#[doc(hidden)]
+pub mod release_verify;
+#[doc(hidden)]
+pub mod repo;
+#[doc(hidden)]
+pub mod snapshot;
+#[doc(hidden)]
+pub mod trigger;
+#[doc(hidden)]
+pub mod units;
+#[doc(hidden)]
+pub mod watcher;
+
+mod archive;
+mod attrs;
+mod change_journal;
+mod dedup;
+mod error;
+mod http;
+mod lock;
+mod longpath;
+mod parity;
+mod reflink;
+mod throttle;
+
+pub use backup::{BackupManager, BackupResult, BackupType};
+pub use config::{BackupConfig, ConfigManager, SourceConfig};
+pub use error::Error;
+pub use restore::{RestoreManager, RestoreOptions};
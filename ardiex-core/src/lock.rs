@@ -0,0 +1,251 @@
+use crate::error::Error;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+pub const LOCK_FILE_NAME: &str = ".ardiex.lock";
+
+const SHARED_LOCK_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockInfo {
+    pub pid: u32,
+    pub operation: String,
+    pub acquired_at: DateTime<Utc>,
+}
+
+/// A backup dir lock as seen from outside, without taking it.
+#[derive(Debug, Clone)]
+pub enum LockState {
+    /// No lock file, nobody holds it, or only readers (restores) do.
+    Free,
+    /// A writer is working on the dir right now.
+    Held(Option<LockInfo>),
+    /// Left behind by a writer that died mid-operation; the next writer
+    /// takes it over.
+    Stale(LockInfo),
+}
+
+/// Look at the lock of `backup_dir` without creating or taking it.
+pub fn inspect(backup_dir: &Path) -> Result<LockState> {
+    let path = backup_dir.join(LOCK_FILE_NAME);
+    let mut file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(LockState::Free),
+        Err(e) => return Err(e).with_context(|| format!("Failed to open lock file: {:?}", path)),
+    };
+    match file.try_lock_shared() {
+        Ok(()) => {
+            // Released writers empty the file, so any holder left is stale.
+            let info = BackupDirLock::read_info(&mut file);
+            let _ = file.unlock();
+            Ok(info.map_or(LockState::Free, LockState::Stale))
+        }
+        Err(fs::TryLockError::WouldBlock) => {
+            Ok(LockState::Held(BackupDirLock::read_info(&mut file)))
+        }
+        Err(fs::TryLockError::Error(e)) => {
+            Err(e).with_context(|| format!("Failed to inspect {:?}", path))
+        }
+    }
+}
+
+/// Per-backup-dir lock backed by an OS file lock.
+///
+/// Writers (backup, forward, repair) take it exclusively; readers such as
+/// restore take it shared, so several restores can run side by side but
+/// never while a chain is being written.
+///
+/// The OS releases the lock when the holding process dies, so a lock file
+/// that exists but can be locked again is stale and is simply taken over.
+/// The file is intentionally left on disk when released to avoid racing
+/// with another process that already opened it.
+#[derive(Debug)]
+pub struct BackupDirLock {
+    file: File,
+    path: PathBuf,
+    shared: bool,
+    recovered_stale: Option<LockInfo>,
+}
+
+impl BackupDirLock {
+    pub fn acquire(backup_dir: &Path, operation: &str) -> Result<Self> {
+        Self::acquire_after_readers(backup_dir, operation, Duration::ZERO)
+    }
+
+    /// Like `acquire`, but when only readers (restores, forwards) hold the
+    /// lock, wait up to `reader_wait` for them to finish. Another writer
+    /// still fails right away.
+    pub fn acquire_after_readers(
+        backup_dir: &Path,
+        operation: &str,
+        reader_wait: Duration,
+    ) -> Result<Self> {
+        let path = backup_dir.join(LOCK_FILE_NAME);
+        let mut file = Self::open(&path)?;
+        let started = Instant::now();
+        let mut announced = false;
+
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(fs::TryLockError::WouldBlock) => {
+                    // Only a writer records itself in the file.
+                    if reader_wait.is_zero() || Self::read_info(&mut file).is_some() {
+                        return Err(Self::locked_error(backup_dir, &mut file));
+                    }
+                    if started.elapsed() >= reader_wait {
+                        return Err(Error::LockTimeout {
+                            backup_dir: backup_dir.to_path_buf(),
+                            operation: operation.to_string(),
+                            holder: None,
+                        }
+                        .into());
+                    }
+                    if !announced {
+                        announced = true;
+                        info!(
+                            "[{:?}] Waiting for readers to finish before {}",
+                            backup_dir, operation
+                        );
+                    }
+                    std::thread::sleep(SHARED_LOCK_POLL_INTERVAL);
+                }
+                Err(fs::TryLockError::Error(e)) => {
+                    return Err(e).with_context(|| format!("Failed to lock {:?}", path));
+                }
+            }
+        }
+
+        let recovered_stale = Self::read_info(&mut file);
+        if let Some(ref stale) = recovered_stale {
+            info!(
+                "[{:?}] Recovered stale lock left by pid {} ({}, since {})",
+                backup_dir, stale.pid, stale.operation, stale.acquired_at
+            );
+        }
+
+        let info = LockInfo {
+            pid: std::process::id(),
+            operation: operation.to_string(),
+            acquired_at: Utc::now(),
+        };
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(serde_json::to_string(&info)?.as_bytes())?;
+        file.flush()?;
+
+        Ok(Self {
+            file,
+            path,
+            shared: false,
+            recovered_stale,
+        })
+    }
+
+    /// Take a shared read lock, waiting for an in-flight writer to finish
+    /// first. `timeout` of `None` waits as long as it takes.
+    ///
+    /// Shared holders never touch the lock file contents, which only ever
+    /// describe the exclusive holder.
+    pub fn acquire_shared(
+        backup_dir: &Path,
+        operation: &str,
+        timeout: Option<Duration>,
+    ) -> Result<Self> {
+        let path = backup_dir.join(LOCK_FILE_NAME);
+        let mut file = Self::open(&path)?;
+        let started = Instant::now();
+        let mut announced = false;
+
+        loop {
+            match file.try_lock_shared() {
+                Ok(()) => break,
+                Err(fs::TryLockError::WouldBlock) => {
+                    if timeout.is_some_and(|limit| started.elapsed() >= limit) {
+                        return Err(Error::LockTimeout {
+                            backup_dir: backup_dir.to_path_buf(),
+                            operation: operation.to_string(),
+                            holder: Self::read_info(&mut file),
+                        }
+                        .into());
+                    }
+                    if !announced {
+                        announced = true;
+                        match Self::read_info(&mut file) {
+                            Some(info) => info!(
+                                "[{:?}] Waiting for {} (pid {}) to finish before {}",
+                                backup_dir, info.operation, info.pid, operation
+                            ),
+                            None => info!(
+                                "[{:?}] Waiting for another ardiex process to finish before {}",
+                                backup_dir, operation
+                            ),
+                        }
+                    }
+                    std::thread::sleep(SHARED_LOCK_POLL_INTERVAL);
+                }
+                Err(fs::TryLockError::Error(e)) => {
+                    return Err(e).with_context(|| format!("Failed to lock {:?}", path));
+                }
+            }
+        }
+
+        Ok(Self {
+            file,
+            path,
+            shared: true,
+            recovered_stale: None,
+        })
+    }
+
+    /// Holder info of a stale lock that was taken over by this acquisition.
+    pub fn recovered_stale(&self) -> Option<&LockInfo> {
+        self.recovered_stale.as_ref()
+    }
+
+    fn open(path: &Path) -> Result<File> {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Failed to open lock file: {:?}", path))
+    }
+
+    fn locked_error(backup_dir: &Path, file: &mut File) -> anyhow::Error {
+        Error::Locked {
+            backup_dir: backup_dir.to_path_buf(),
+            holder: Self::read_info(file),
+        }
+        .into()
+    }
+
+    fn read_info(file: &mut File) -> Option<LockInfo> {
+        let mut content = String::new();
+        file.seek(SeekFrom::Start(0)).ok()?;
+        file.read_to_string(&mut content).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+}
+
+impl Drop for BackupDirLock {
+    fn drop(&mut self) {
+        if !self.shared {
+            let _ = self.file.set_len(0);
+        }
+        if let Err(e) = self.file.unlock() {
+            warn!("Failed to release lock {:?}: {}", self.path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/lock_tests.rs"]
+mod tests;
@@ -0,0 +1,494 @@
+use anyhow::{Context, Result};
+use env_logger::Builder;
+use env_logger::Env;
+use file_rotate::compression::Compression;
+use file_rotate::suffix::{AppendTimestamp, DateFrom, FileLimit};
+use file_rotate::{ContentLimit, FileRotate};
+use log::LevelFilter;
+use std::collections::HashMap;
+use std::fs;
+use std::future::Future;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+const DEFAULT_MAX_LOG_FILE_SIZE_MB: u64 = 20;
+pub const DEFAULT_MAX_ROTATED_LOG_FILES: usize = 30;
+/// How far back from the end of a log `read_tail` looks for lines.
+const TAIL_WINDOW_BYTES: u64 = 256 * 1024;
+const DATE_SUFFIX_PATTERN: &str = "%Y-%m-%d_%H-%M-%S";
+pub const DEFAULT_LOG_FILE_NAME: &str = "ardiex.log";
+/// Log directory to use instead of `logs/` next to the executable, e.g. the
+/// `LogsDirectory=` of a systemd unit.
+pub const LOG_DIR_ENV_KEY: &str = "ARDIEX_LOG_DIR";
+/// Subdirectory of the log directory holding per-source logs.
+pub const SOURCE_LOG_DIR: &str = "sources";
+
+/// Runs every console write of the log tee, e.g. to clear progress bars
+/// around it; without one the line is written directly.
+static CONSOLE_WRAPPER: OnceLock<fn(&mut dyn FnMut())> = OnceLock::new();
+
+pub fn set_console_wrapper(wrapper: fn(&mut dyn FnMut())) {
+    let _ = CONSOLE_WRAPPER.set(wrapper);
+}
+
+/// Log level asked for with the global `-q`/`-v` flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verbosity {
+    /// Warnings and errors only, kept in the log file and not echoed to
+    /// stdout.
+    Quiet,
+    /// Debug output from ardiex itself.
+    Verbose,
+    /// Trace output from ardiex and every library it uses.
+    Trace,
+}
+
+impl Verbosity {
+    /// `None` keeps the default level (RUST_LOG, then info).
+    pub fn from_flags(quiet: bool, verbose: u8) -> Option<Self> {
+        match (quiet, verbose) {
+            (true, _) => Some(Verbosity::Quiet),
+            (false, 0) => None,
+            (false, 1) => Some(Verbosity::Verbose),
+            (false, _) => Some(Verbosity::Trace),
+        }
+    }
+}
+
+static VERBOSITY: OnceLock<Verbosity> = OnceLock::new();
+
+/// Must run before logging is initialized to take effect.
+pub fn set_verbosity(verbosity: Verbosity) {
+    let _ = VERBOSITY.set(verbosity);
+}
+
+pub fn is_quiet() -> bool {
+    VERBOSITY.get() == Some(&Verbosity::Quiet)
+}
+
+/// Builder filtering at the `-q`/`-v` level, ignoring RUST_LOG; `None`
+/// without either flag.
+fn verbosity_builder() -> Option<Builder> {
+    let mut builder = Builder::new();
+    match VERBOSITY.get()? {
+        Verbosity::Quiet => builder.filter_level(LevelFilter::Warn),
+        Verbosity::Verbose => builder
+            .filter_level(LevelFilter::Info)
+            .filter_module("ardiex", LevelFilter::Debug),
+        Verbosity::Trace => builder.filter_level(LevelFilter::Trace),
+    };
+    Some(builder)
+}
+
+type SharedLog = Arc<Mutex<dyn Write + Send>>;
+
+tokio::task_local! {
+    /// Log file of the source the current task is backing up.
+    static SOURCE_LOG: SharedLog;
+}
+
+/// Where `run` writes per-source logs, and the files opened so far.
+struct SourceLogs {
+    dir: PathBuf,
+    max_bytes: usize,
+    max_files: usize,
+    open: HashMap<PathBuf, SharedLog>,
+}
+
+static SOURCE_LOGS: Mutex<Option<SourceLogs>> = Mutex::new(None);
+
+/// Also write each source's log lines to its own file in `dir`, rotated
+/// like the main log; `None` stops. Only lines logged inside
+/// [`scope_source_log`]/[`with_source_log`] are copied there.
+pub fn set_source_log_dir(
+    dir: Option<PathBuf>,
+    max_log_file_size_mb: u64,
+    max_rotated_files: usize,
+) -> Result<()> {
+    let source_logs = match dir {
+        Some(dir) => {
+            fs::create_dir_all(&dir)?;
+            Some(SourceLogs {
+                dir,
+                max_bytes: log_size_limit(max_log_file_size_mb)?.1,
+                max_files: max_rotated_files,
+                open: HashMap::new(),
+            })
+        }
+        None => None,
+    };
+    *SOURCE_LOGS
+        .lock()
+        .map_err(|_| anyhow::anyhow!("source log mutex poisoned"))? = source_logs;
+    Ok(())
+}
+
+/// File name of `source_dir`'s log: the path with every separator and
+/// other character unsafe in file names turned into `_`, e.g.
+/// `/home/user/docs` -> `home_user_docs.log`.
+pub fn source_log_file_name(source_dir: &Path) -> String {
+    let name: String = source_dir
+        .to_string_lossy()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let name = name.trim_matches('_');
+    format!("{}.log", if name.is_empty() { "root" } else { name })
+}
+
+fn source_log(source_dir: &Path) -> Option<SharedLog> {
+    let mut source_logs = SOURCE_LOGS.lock().ok()?;
+    let source_logs = source_logs.as_mut()?;
+    let log = source_logs
+        .open
+        .entry(source_dir.to_path_buf())
+        .or_insert_with(|| {
+            let file = source_logs.dir.join(source_log_file_name(source_dir));
+            Arc::new(Mutex::new(rotating_file(
+                file,
+                source_logs.max_bytes,
+                source_logs.max_files,
+            )))
+        });
+    Some(log.clone())
+}
+
+/// Run `future` with its log lines also going to `source_dir`'s own file
+/// when per-source logs are on.
+pub fn scope_source_log<F: Future>(
+    source_dir: &Path,
+    future: F,
+) -> impl Future<Output = F::Output> + use<F> {
+    let log = source_log(source_dir);
+    async move {
+        match log {
+            Some(log) => SOURCE_LOG.scope(log, future).await,
+            None => future.await,
+        }
+    }
+}
+
+/// [`scope_source_log`] for lines logged outside the source's own task.
+pub fn with_source_log<R>(source_dir: &Path, f: impl FnOnce() -> R) -> R {
+    match source_log(source_dir) {
+        Some(log) => SOURCE_LOG.sync_scope(log, f),
+        None => f(),
+    }
+}
+
+/// A broken per-source file must not take the main log down with it.
+fn write_source_log(buf: &[u8]) {
+    let _ = SOURCE_LOG.try_with(|log| {
+        if let Ok(mut log) = log.lock() {
+            let _ = log.write_all(buf);
+        }
+    });
+}
+
+struct TeeLogWriter<F: Write, O: Write> {
+    file_writer: Mutex<F>,
+    stdout: Mutex<O>,
+}
+
+impl<F: Write, O: Write> TeeLogWriter<F, O> {
+    fn new(file_writer: F, stdout: O) -> Self {
+        Self {
+            file_writer: Mutex::new(file_writer),
+            stdout: Mutex::new(stdout),
+        }
+    }
+}
+
+impl<F: Write, O: Write> Write for TeeLogWriter<F, O> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut file_writer = self
+            .file_writer
+            .lock()
+            .map_err(|_| io::Error::other("file logger mutex poisoned"))?;
+        file_writer.write_all(buf)?;
+        write_source_log(buf);
+
+        let mut stdout = self
+            .stdout
+            .lock()
+            .map_err(|_| io::Error::other("stdout logger mutex poisoned"))?;
+        let mut result = Ok(());
+        {
+            let mut write = || result = stdout.write_all(buf);
+            match CONSOLE_WRAPPER.get() {
+                Some(wrapper) => wrapper(&mut write),
+                None => write(),
+            }
+        }
+        result?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut file_writer = self
+            .file_writer
+            .lock()
+            .map_err(|_| io::Error::other("file logger mutex poisoned"))?;
+        file_writer.flush()?;
+
+        let mut stdout = self
+            .stdout
+            .lock()
+            .map_err(|_| io::Error::other("stdout logger mutex poisoned"))?;
+        stdout.flush()
+    }
+}
+
+fn apply_local_time_format(builder: &mut Builder) {
+    builder.format(|buf, record| {
+        let ts = chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+        writeln!(
+            buf,
+            "[{} {} {}] {}",
+            ts,
+            record.level(),
+            record.target(),
+            record.args()
+        )
+    });
+}
+
+/// `max_log_file_size_mb` (0 = default) and the same limit in bytes.
+fn log_size_limit(max_log_file_size_mb: u64) -> Result<(u64, usize)> {
+    let size_mb = if max_log_file_size_mb == 0 {
+        DEFAULT_MAX_LOG_FILE_SIZE_MB
+    } else {
+        max_log_file_size_mb
+    };
+    let max_bytes_u64 = size_mb
+        .checked_mul(1024 * 1024)
+        .ok_or_else(|| anyhow::anyhow!("max_log_file_size_mb is too large: {}", size_mb))?;
+    let max_bytes: usize = max_bytes_u64.try_into().map_err(|_| {
+        anyhow::anyhow!(
+            "max_log_file_size_mb is too large for this platform: {}",
+            size_mb
+        )
+    })?;
+    Ok((size_mb, max_bytes))
+}
+
+/// `max_files` of 0 keeps the default number of rotated files.
+fn rotating_file(
+    log_file: PathBuf,
+    max_bytes: usize,
+    max_files: usize,
+) -> FileRotate<AppendTimestamp> {
+    let max_files = if max_files == 0 {
+        DEFAULT_MAX_ROTATED_LOG_FILES
+    } else {
+        max_files
+    };
+    let suffix = AppendTimestamp::with_format(
+        DATE_SUFFIX_PATTERN,
+        FileLimit::MaxFiles(max_files),
+        DateFrom::Now,
+    );
+    let content_limit = ContentLimit::BytesSurpassed(max_bytes);
+
+    #[cfg(unix)]
+    let rotate = FileRotate::new(
+        log_file,
+        suffix,
+        content_limit,
+        Compression::OnRotate(1),
+        None,
+    );
+
+    #[cfg(not(unix))]
+    let rotate = FileRotate::new(log_file, suffix, content_limit, Compression::OnRotate(1));
+
+    rotate
+}
+
+pub fn init_file_logging_with_size(
+    log_dir: &PathBuf,
+    max_log_file_size_mb: u64,
+    max_rotated_files: usize,
+) -> Result<()> {
+    init_file_logging_with_size_and_name(
+        log_dir,
+        max_log_file_size_mb,
+        max_rotated_files,
+        DEFAULT_LOG_FILE_NAME,
+    )
+}
+
+pub fn init_file_logging_with_size_and_name(
+    log_dir: &PathBuf,
+    max_log_file_size_mb: u64,
+    max_rotated_files: usize,
+    log_file_name: &str,
+) -> Result<()> {
+    fs::create_dir_all(log_dir)?;
+
+    let sanitized_log_file_name = if log_file_name.trim().is_empty() {
+        DEFAULT_LOG_FILE_NAME
+    } else {
+        log_file_name
+    };
+    let log_file = log_dir.join(sanitized_log_file_name);
+    let (size_mb, max_bytes) = log_size_limit(max_log_file_size_mb)?;
+    let rotate = rotating_file(log_file.clone(), max_bytes, max_rotated_files);
+
+    let mut builder = verbosity_builder().unwrap_or_else(|| {
+        let mut builder = Builder::from_env(Env::default().default_filter_or("info"));
+        builder.filter_level(LevelFilter::Info);
+        builder
+    });
+    // Quiet runs keep the log file but leave stdout to the command itself.
+    let target: Box<dyn Write + Send> = if is_quiet() {
+        Box::new(TeeLogWriter::new(rotate, io::sink()))
+    } else {
+        Box::new(TeeLogWriter::new(rotate, io::stdout()))
+    };
+    builder.target(env_logger::Target::Pipe(target));
+    apply_local_time_format(&mut builder);
+    builder.init();
+
+    if !is_quiet() {
+        println!(
+            "Logging to file: {:?} (max size: {} MB, rotate: gzip + date suffix {})",
+            log_file, size_mb, DATE_SUFFIX_PATTERN
+        );
+    }
+    Ok(())
+}
+
+struct RotatedLog {
+    path: PathBuf,
+    /// Live log the file was rotated from.
+    log: PathBuf,
+    modified: SystemTime,
+    size: u64,
+}
+
+/// Rotated files (`<name>.log.<date>[.gz]`) in `log_dir` and its per-source
+/// directory, newest first, plus the total size of every file there.
+fn rotated_logs(log_dir: &Path) -> (Vec<RotatedLog>, u64) {
+    let mut rotated = Vec::new();
+    let mut total = 0;
+    for dir in [log_dir.to_path_buf(), log_dir.join(SOURCE_LOG_DIR)] {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+            total += metadata.len();
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.ends_with(".log") {
+                continue;
+            }
+            if let Some(at) = name.rfind(".log.") {
+                rotated.push(RotatedLog {
+                    path: entry.path(),
+                    log: dir.join(&name[..at + ".log".len()]),
+                    modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                    size: metadata.len(),
+                });
+            }
+        }
+    }
+    rotated.sort_by_key(|file| std::cmp::Reverse(file.modified));
+    (rotated, total)
+}
+
+/// What `prune_logs` removed, or would remove on a dry run.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct LogPruneReport {
+    pub files: Vec<PathBuf>,
+    pub bytes: u64,
+}
+
+/// Delete rotated log files beyond `max_rotated_files` per log, then the
+/// oldest ones until the log directory fits in `max_total_bytes`. Live
+/// logs are never touched, so the directory can stay above the cap.
+pub fn prune_logs(
+    log_dir: &Path,
+    max_rotated_files: usize,
+    max_total_bytes: Option<u64>,
+    dry_run: bool,
+) -> Result<LogPruneReport> {
+    let (rotated, total) = rotated_logs(log_dir);
+    let mut kept_per_log: HashMap<PathBuf, usize> = HashMap::new();
+    let mut keep = Vec::new();
+    let mut remove = Vec::new();
+    for file in rotated {
+        let kept = kept_per_log.entry(file.log.clone()).or_insert(0);
+        if *kept < max_rotated_files {
+            *kept += 1;
+            keep.push(file);
+        } else {
+            remove.push(file);
+        }
+    }
+    if let Some(max_total_bytes) = max_total_bytes {
+        let mut remaining = total - remove.iter().map(|file| file.size).sum::<u64>();
+        while remaining > max_total_bytes {
+            let Some(oldest) = keep.pop() else {
+                break;
+            };
+            remaining -= oldest.size;
+            remove.push(oldest);
+        }
+    }
+
+    let mut report = LogPruneReport::default();
+    for file in remove {
+        if !dry_run {
+            fs::remove_file(&file.path)
+                .with_context(|| format!("Failed to remove {:?}", file.path))?;
+        }
+        report.bytes += file.size;
+        report.files.push(file.path);
+    }
+    Ok(report)
+}
+
+/// The last `lines` lines of `log_file`, reading only its final
+/// [`TAIL_WINDOW_BYTES`] so a large live log stays cheap to poll.
+pub fn read_tail(log_file: &Path, lines: usize) -> Result<Vec<String>> {
+    let mut file = fs::File::open(log_file)
+        .with_context(|| format!("Failed to open log file {:?}", log_file))?;
+    let len = file.metadata()?.len();
+    let start = len.saturating_sub(TAIL_WINDOW_BYTES);
+    file.seek(SeekFrom::Start(start))?;
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)?;
+    let content = String::from_utf8_lossy(&content);
+    // A window starting mid-file begins with the tail of a cut line.
+    let skip_partial = usize::from(start > 0);
+    let all: Vec<&str> = content.lines().skip(skip_partial).collect();
+    Ok(all[all.len().saturating_sub(lines)..]
+        .iter()
+        .map(|line| line.to_string())
+        .collect())
+}
+
+pub fn init_console_logging() {
+    let mut builder = verbosity_builder()
+        .unwrap_or_else(|| Builder::from_env(Env::default().default_filter_or("info")));
+    apply_local_time_format(&mut builder);
+    builder.init();
+}
+
+#[cfg(test)]
+#[path = "tests/logger_tests.rs"]
+mod tests;
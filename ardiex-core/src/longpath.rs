@@ -0,0 +1,72 @@
+use std::path::{Path, PathBuf};
+
+const VERBATIM_PREFIX: &str = r"\\?\";
+const DEVICE_PREFIX: &str = r"\\.\";
+
+/// `path` in the form the file system APIs accept beyond `MAX_PATH`: on
+/// Windows an absolute `\\?\C:\...` or `\\?\UNC\server\share\...` path,
+/// elsewhere the path unchanged. Paths that cannot be converted (already
+/// verbatim, or not valid Unicode) are returned as they are.
+pub fn extended(path: &Path) -> PathBuf {
+    if !cfg!(windows) {
+        return path.to_path_buf();
+    }
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    match absolute.to_str().and_then(to_extended) {
+        Some(converted) => PathBuf::from(converted),
+        None => absolute,
+    }
+}
+
+/// Convert an absolute Windows path string to its extended-length form.
+/// Verbatim paths skip all normalization, so `/`, `.` and `..` are resolved
+/// here. `None` for relative or drive-relative paths.
+fn to_extended(path: &str) -> Option<String> {
+    if path.starts_with(VERBATIM_PREFIX) || path.starts_with(DEVICE_PREFIX) {
+        return Some(path.to_string());
+    }
+    let path = path.replace('/', "\\");
+    if let Some(unc) = path.strip_prefix(r"\\") {
+        // The server and share stay fixed; `..` never climbs above them.
+        let parts: Vec<&str> = unc.split('\\').filter(|p| !p.is_empty()).collect();
+        if parts.len() < 2 {
+            return None;
+        }
+        return Some(format!(
+            r"{}UNC\{}",
+            VERBATIM_PREFIX,
+            normalize(&parts[..2], &parts[2..])
+        ));
+    }
+    let bytes = path.as_bytes();
+    if bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && &bytes[1..3] == b":\\" {
+        let parts: Vec<&str> = path.split('\\').filter(|p| !p.is_empty()).collect();
+        return Some(format!(
+            "{}{}",
+            VERBATIM_PREFIX,
+            normalize(&parts[..1], &parts[1..])
+        ));
+    }
+    None
+}
+
+fn normalize(root: &[&str], rest: &[&str]) -> String {
+    let mut components: Vec<&str> = Vec::new();
+    for part in rest {
+        match *part {
+            "." => {}
+            ".." => {
+                components.pop();
+            }
+            part => components.push(part),
+        }
+    }
+    let mut normalized = root.join("\\");
+    normalized.push('\\');
+    normalized.push_str(&components.join("\\"));
+    normalized
+}
+
+#[cfg(test)]
+#[path = "tests/longpath_tests.rs"]
+mod tests;
@@ -0,0 +1,254 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::backup::{BackupResult, BackupType};
+use crate::http;
+
+/// Listen address without `metrics_address`: only local scrapers.
+pub const DEFAULT_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+#[derive(Debug, Default)]
+struct SourceMetrics {
+    full_backups: u64,
+    incremental_backups: u64,
+    bytes_processed: u64,
+    failures: u64,
+    last_success: Option<DateTime<Utc>>,
+    delta_savings_ratio: Option<f64>,
+}
+
+/// Process-wide counters exposed on `/metrics` while `run` is active.
+#[derive(Debug, Default)]
+struct Metrics {
+    sources: BTreeMap<PathBuf, SourceMetrics>,
+    forward_queue_depth: BTreeMap<PathBuf, usize>,
+}
+
+impl Metrics {
+    fn record_backup(&mut self, source_dir: &Path, result: &BackupResult) {
+        let entry = self.sources.entry(source_dir.to_path_buf()).or_default();
+        match result.backup_type {
+            BackupType::Full => entry.full_backups += 1,
+            BackupType::Incremental => {
+                entry.incremental_backups += 1;
+                // Share of the changed files' size that did not need storing.
+                let changed = result.changed_bytes;
+                if changed > 0 {
+                    let stored = result.bytes_processed.min(changed);
+                    entry.delta_savings_ratio = Some(1.0 - stored as f64 / changed as f64);
+                }
+            }
+        }
+        entry.bytes_processed += result.bytes_processed;
+        entry.last_success = Some(Utc::now());
+    }
+
+    fn record_failure(&mut self, source_dir: &Path) {
+        self.sources
+            .entry(source_dir.to_path_buf())
+            .or_default()
+            .failures += 1;
+    }
+
+    /// Prometheus text exposition format (version 0.0.4).
+    fn render(&self) -> String {
+        let mut out = String::new();
+        let sources = || self.sources.iter().map(|(dir, m)| (label_value(dir), m));
+
+        out.push_str("# HELP ardiex_backups_total Backups completed per source and type.\n");
+        out.push_str("# TYPE ardiex_backups_total counter\n");
+        for (source, m) in sources() {
+            let _ = writeln!(
+                out,
+                "ardiex_backups_total{{source=\"{}\",type=\"full\"}} {}",
+                source, m.full_backups
+            );
+            let _ = writeln!(
+                out,
+                "ardiex_backups_total{{source=\"{}\",type=\"inc\"}} {}",
+                source, m.incremental_backups
+            );
+        }
+
+        out.push_str("# HELP ardiex_backup_bytes_processed_total Bytes written to backups.\n");
+        out.push_str("# TYPE ardiex_backup_bytes_processed_total counter\n");
+        for (source, m) in sources() {
+            let _ = writeln!(
+                out,
+                "ardiex_backup_bytes_processed_total{{source=\"{}\"}} {}",
+                source, m.bytes_processed
+            );
+        }
+
+        out.push_str("# HELP ardiex_backup_failures_total Failed backup runs per source.\n");
+        out.push_str("# TYPE ardiex_backup_failures_total counter\n");
+        for (source, m) in sources() {
+            let _ = writeln!(
+                out,
+                "ardiex_backup_failures_total{{source=\"{}\"}} {}",
+                source, m.failures
+            );
+        }
+
+        out.push_str(
+            "# HELP ardiex_last_success_timestamp_seconds Unix time of the last successful backup.\n",
+        );
+        out.push_str("# TYPE ardiex_last_success_timestamp_seconds gauge\n");
+        for (source, m) in sources() {
+            if let Some(last) = m.last_success {
+                let _ = writeln!(
+                    out,
+                    "ardiex_last_success_timestamp_seconds{{source=\"{}\"}} {}",
+                    source,
+                    last.timestamp()
+                );
+            }
+        }
+
+        out.push_str(
+            "# HELP ardiex_delta_savings_ratio Share of changed bytes not stored by the last incremental backup.\n",
+        );
+        out.push_str("# TYPE ardiex_delta_savings_ratio gauge\n");
+        for (source, m) in sources() {
+            if let Some(ratio) = m.delta_savings_ratio {
+                let _ = writeln!(
+                    out,
+                    "ardiex_delta_savings_ratio{{source=\"{}\"}} {:.4}",
+                    source, ratio
+                );
+            }
+        }
+
+        out.push_str(
+            "# HELP ardiex_forward_queue_depth Backups waiting to be forwarded to forward_dir.\n",
+        );
+        out.push_str("# TYPE ardiex_forward_queue_depth gauge\n");
+        for (dir, depth) in &self.forward_queue_depth {
+            let _ = writeln!(
+                out,
+                "ardiex_forward_queue_depth{{backup_dir=\"{}\"}} {}",
+                label_value(dir),
+                depth
+            );
+        }
+
+        out
+    }
+}
+
+fn label_value(path: &Path) -> String {
+    path.to_string_lossy()
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn registry() -> &'static Mutex<Metrics> {
+    static REGISTRY: OnceLock<Mutex<Metrics>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(Metrics::default()))
+}
+
+fn with_registry(update: impl FnOnce(&mut Metrics)) {
+    match registry().lock() {
+        Ok(mut metrics) => update(&mut metrics),
+        Err(poisoned) => update(&mut poisoned.into_inner()),
+    }
+}
+
+pub fn record_backup(source_dir: &Path, result: &BackupResult) {
+    with_registry(|m| m.record_backup(source_dir, result));
+}
+
+pub fn record_failure(source_dir: &Path) {
+    with_registry(|m| m.record_failure(source_dir));
+}
+
+pub fn set_forward_queue_depth(local_dir: &Path, queued: usize) {
+    with_registry(|m| {
+        m.forward_queue_depth
+            .insert(local_dir.to_path_buf(), queued);
+    });
+}
+
+fn render_current() -> String {
+    let mut rendered = String::new();
+    with_registry(|m| rendered = m.render());
+    rendered
+}
+
+/// Bind the exporter on `address` (see [`DEFAULT_ADDRESS`]). Kept separate
+/// from [`spawn_server`] so a port conflict fails `run` at startup.
+pub async fn bind(address: Option<IpAddr>, port: u16) -> Result<TcpListener> {
+    let address = address.unwrap_or(DEFAULT_ADDRESS);
+    TcpListener::bind((address, port)).await.with_context(|| {
+        format!(
+            "Failed to bind metrics exporter on {}",
+            std::net::SocketAddr::new(address, port)
+        )
+    })
+}
+
+pub fn spawn_server(listener: TcpListener) -> JoinHandle<()> {
+    if let Ok(addr) = listener.local_addr() {
+        info!(
+            "[METRICS] Serving Prometheus metrics on http://{}/metrics",
+            addr
+        );
+    }
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_connection(stream, http::REQUEST_READ_TIMEOUT).await
+                        {
+                            warn!("[METRICS] Request failed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("[METRICS] Accept failed: {}", e),
+            }
+        }
+    })
+}
+
+async fn handle_connection(mut stream: TcpStream, timeout: Duration) -> Result<()> {
+    let request = http::read_request(&mut stream, timeout).await?;
+    let (status, content_type, body) = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/metrics") => (
+            "200 OK",
+            "text/plain; version=0.0.4; charset=utf-8",
+            render_current(),
+        ),
+        _ => (
+            "404 Not Found",
+            "text/plain; charset=utf-8",
+            "not found\n".to_string(),
+        ),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+#[path = "tests/metrics_tests.rs"]
+mod tests;
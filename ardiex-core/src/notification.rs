@@ -0,0 +1,347 @@
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde_json::{Value, json};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::backup::{BackupResult, BackupType};
+use crate::config::{NotificationConfig, SmtpConfig, SmtpSecurity};
+
+const SMTP_PASSWORD_ENV_KEY: &str = "ARDIEX_SMTP_PASSWORD";
+
+/// Stage reported to a source's `healthcheck_url`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthcheckEvent {
+    Start,
+    Success,
+    Fail,
+}
+
+fn healthcheck_ping_url(base: &str, event: HealthcheckEvent) -> String {
+    let base = base.trim_end_matches('/');
+    match event {
+        HealthcheckEvent::Start => format!("{}/start", base),
+        HealthcheckEvent::Success => base.to_string(),
+        HealthcheckEvent::Fail => format!("{}/fail", base),
+    }
+}
+
+fn send_ping(url: &str, body: String) -> Result<()> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .context("Failed to build healthcheck HTTP client")?;
+    client
+        .post(url)
+        .header(reqwest::header::USER_AGENT, "ardiex-notifier")
+        .body(body)
+        .send()
+        .with_context(|| format!("Failed to ping healthcheck {}", url))?
+        .error_for_status()
+        .with_context(|| format!("Healthcheck endpoint rejected ping: {}", url))?;
+    Ok(())
+}
+
+/// Ping a healthcheck endpoint so an external monitor notices when scheduled
+/// backups stop arriving. `message` becomes the request body (e.g. the error
+/// for a failure). Failures to ping are only logged.
+pub async fn ping_healthcheck(base_url: &str, event: HealthcheckEvent, message: String) {
+    let url = healthcheck_ping_url(base_url, event);
+    match tokio::task::spawn_blocking(move || send_ping(&url, message)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => warn!("[NOTIFY] {:#}", e),
+        Err(e) => warn!("[NOTIFY] Healthcheck task join error: {}", e),
+    }
+}
+
+fn should_notify(config: &NotificationConfig, failures: &[String]) -> bool {
+    let has_webhook = config.webhook_url.is_some() || config.slack_webhook_url.is_some();
+    has_webhook && (!config.only_on_failure || !failures.is_empty())
+}
+
+fn build_webhook_payload(
+    results: &[BackupResult],
+    failures: &[String],
+    elapsed: Duration,
+) -> Value {
+    let entries: Vec<Value> = results
+        .iter()
+        .map(|r| {
+            json!({
+                "backup_dir": r.backup_dir,
+                "backup_type": match r.backup_type {
+                    BackupType::Full => "full",
+                    BackupType::Incremental => "inc",
+                },
+                "files_backed_up": r.files_backed_up,
+                "bytes_processed": r.bytes_processed,
+                "duration_ms": r.duration_ms,
+                "skipped_files": r.skipped_files,
+            })
+        })
+        .collect();
+
+    json!({
+        "status": if failures.is_empty() { "success" } else { "failure" },
+        "duration_ms": elapsed.as_millis() as u64,
+        "total_files": results.iter().map(|r| r.files_backed_up).sum::<usize>(),
+        "total_bytes": results.iter().map(|r| r.bytes_processed).sum::<u64>(),
+        "results": entries,
+        "failures": failures,
+    })
+}
+
+fn build_slack_text(results: &[BackupResult], failures: &[String], elapsed: Duration) -> String {
+    let files: usize = results.iter().map(|r| r.files_backed_up).sum();
+    let bytes: u64 = results.iter().map(|r| r.bytes_processed).sum();
+    let mut text = format!(
+        "{} ardiex backup {}: {} destination(s), {} files, {:.2} MB in {} ms",
+        if failures.is_empty() {
+            ":white_check_mark:"
+        } else {
+            ":x:"
+        },
+        if failures.is_empty() {
+            "succeeded"
+        } else {
+            "failed"
+        },
+        results.len(),
+        files,
+        bytes as f64 / 1024.0 / 1024.0,
+        elapsed.as_millis()
+    );
+    for failure in failures {
+        text.push_str(&format!("\n• {}", failure));
+    }
+    text
+}
+
+/// Webhook URL for display: the path and query of Slack and most generic
+/// webhooks carry the secret, as do credentials before the host, so only
+/// the scheme and host are shown.
+pub fn mask_webhook_url(url: &str) -> String {
+    let host_start = url.find("://").map_or(0, |i| i + 3);
+    let rest = &url[host_start..];
+    let host_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let host = &rest[..host_end];
+    let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+    if host_end == rest.len() && host.len() == host_end {
+        return url.to_string();
+    }
+    format!("{}{}/***", &url[..host_start], host)
+}
+
+fn post_json(url: &str, body: &Value) -> Result<()> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(15))
+        .build()
+        .context("Failed to build notification HTTP client")?;
+    // reqwest errors embed the full URL; log the masked one instead.
+    let masked = mask_webhook_url(url);
+    client
+        .post(url)
+        .header(reqwest::header::USER_AGENT, "ardiex-notifier")
+        .json(body)
+        .send()
+        .map_err(reqwest::Error::without_url)
+        .with_context(|| format!("Failed to send notification to {}", masked))?
+        .error_for_status()
+        .map_err(reqwest::Error::without_url)
+        .with_context(|| format!("Notification endpoint rejected request: {}", masked))?;
+    Ok(())
+}
+
+/// One message for all failures of a run, so a bad night with several
+/// broken sources doesn't flood the inbox.
+fn build_failure_email(stage: &str, failures: &[String]) -> (String, String) {
+    let subject = format!(
+        "[ardiex] {} failed ({} error{})",
+        stage,
+        failures.len(),
+        if failures.len() == 1 { "" } else { "s" }
+    );
+    let mut body = format!(
+        "ardiex {} failed at {}.\n\n",
+        stage,
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S %z")
+    );
+    for (index, failure) in failures.iter().enumerate() {
+        body.push_str(&format!("{}. {}\n", index + 1, failure));
+    }
+    (subject, body)
+}
+
+fn send_email(smtp: &SmtpConfig, subject: &str, body: String) -> Result<()> {
+    use lettre::message::header::ContentType;
+    use lettre::transport::smtp::authentication::Credentials;
+    use lettre::{Message, SmtpTransport, Transport};
+
+    let mut message = Message::builder()
+        .from(
+            smtp.from
+                .parse()
+                .with_context(|| format!("Invalid SMTP from address: {}", smtp.from))?,
+        )
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN);
+    for to in &smtp.to {
+        message = message.to(to
+            .parse()
+            .with_context(|| format!("Invalid SMTP to address: {}", to))?);
+    }
+    let message = message.body(body).context("Failed to build alert email")?;
+
+    let mut transport = match smtp.security {
+        SmtpSecurity::StartTls => SmtpTransport::starttls_relay(&smtp.host)?,
+        SmtpSecurity::Tls => SmtpTransport::relay(&smtp.host)?,
+        SmtpSecurity::None => SmtpTransport::builder_dangerous(&smtp.host),
+    }
+    .port(smtp.port)
+    .timeout(Some(Duration::from_secs(15)));
+
+    let password = smtp
+        .password
+        .clone()
+        .or_else(|| std::env::var(SMTP_PASSWORD_ENV_KEY).ok());
+    if let (Some(username), Some(password)) = (smtp.username.clone(), password) {
+        transport = transport.credentials(Credentials::new(username, password));
+    }
+
+    transport
+        .build()
+        .send(&message)
+        .with_context(|| format!("Failed to send alert email via {}:{}", smtp.host, smtp.port))?;
+    Ok(())
+}
+
+/// Email all failures of one run (backup or pre-flight validation) as a
+/// single message. Delivery problems are only logged.
+pub async fn notify_failures_by_email(
+    config: &NotificationConfig,
+    stage: &str,
+    failures: &[String],
+) {
+    let Some(smtp) = config.smtp.clone() else {
+        return;
+    };
+    if failures.is_empty() {
+        return;
+    }
+
+    let (subject, body) = build_failure_email(stage, failures);
+    match tokio::task::spawn_blocking(move || send_email(&smtp, &subject, body)).await {
+        Ok(Ok(())) => info!("[NOTIFY] Failure alert email sent"),
+        Ok(Err(e)) => warn!("[NOTIFY] {:#}", e),
+        Err(e) => warn!("[NOTIFY] Email task join error: {}", e),
+    }
+}
+
+/// POST a summary of one `backup_all_sources` run to the configured webhook
+/// and/or Slack incoming webhook, and email failures if SMTP is set up.
+/// Delivery problems are logged, never returned, so a down endpoint can't
+/// fail a backup.
+pub async fn notify_backup_run(
+    config: &NotificationConfig,
+    results: &[BackupResult],
+    failures: &[String],
+    elapsed: Duration,
+) {
+    notify_failures_by_email(config, "backup", failures).await;
+
+    if !should_notify(config, failures) {
+        return;
+    }
+
+    let mut deliveries = Vec::new();
+    if let Some(ref url) = config.webhook_url {
+        deliveries.push((
+            url.clone(),
+            build_webhook_payload(results, failures, elapsed),
+        ));
+    }
+    if let Some(ref url) = config.slack_webhook_url {
+        deliveries.push((
+            url.clone(),
+            json!({ "text": build_slack_text(results, failures, elapsed) }),
+        ));
+    }
+
+    post_all(deliveries, "Backup run").await;
+}
+
+/// Alert on the problems a scheduled verification found in one backup dir,
+/// by email and to the webhooks. Unlike backup runs this ignores
+/// `only_on_failure`: a clean verification sends nothing.
+pub async fn notify_verification(
+    config: &NotificationConfig,
+    backup_dir: &Path,
+    problems: &[String],
+) {
+    if problems.is_empty() {
+        return;
+    }
+    let failures: Vec<String> = problems
+        .iter()
+        .map(|problem| format!("{:?}: {}", backup_dir, problem))
+        .collect();
+    notify_failures_by_email(config, "verification", &failures).await;
+
+    let mut deliveries = Vec::new();
+    if let Some(ref url) = config.webhook_url {
+        deliveries.push((
+            url.clone(),
+            build_verification_payload(backup_dir, problems),
+        ));
+    }
+    if let Some(ref url) = config.slack_webhook_url {
+        let mut text = format!(
+            ":warning: ardiex verification found {} problem(s) in {:?}",
+            problems.len(),
+            backup_dir
+        );
+        for problem in problems {
+            text.push_str(&format!("\n• {}", problem));
+        }
+        deliveries.push((url.clone(), json!({ "text": text })));
+    }
+    post_all(deliveries, "Verification").await;
+}
+
+fn build_verification_payload(backup_dir: &Path, problems: &[String]) -> Value {
+    json!({
+        "status": "verification_failed",
+        "backup_dir": backup_dir,
+        "problems": problems,
+    })
+}
+
+async fn post_all(deliveries: Vec<(String, Value)>, what: &'static str) {
+    if deliveries.is_empty() {
+        return;
+    }
+    // reqwest's blocking client must not run on the async executor.
+    let outcome = tokio::task::spawn_blocking(move || {
+        deliveries
+            .into_iter()
+            .map(|(url, body)| post_json(&url, &body))
+            .collect::<Vec<_>>()
+    })
+    .await;
+
+    match outcome {
+        Ok(sent) => {
+            for result in sent {
+                match result {
+                    Ok(()) => info!("[NOTIFY] {} notification sent", what),
+                    Err(e) => warn!("[NOTIFY] {:#}", e),
+                }
+            }
+        }
+        Err(e) => warn!("[NOTIFY] Notification task join error: {}", e),
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/notification_tests.rs"]
+mod tests;
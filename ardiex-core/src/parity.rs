@@ -0,0 +1,724 @@
+use anyhow::{Context, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::archive;
+use crate::attrs;
+
+/// Subdirectory of a backup dir holding one `<point>.par` file per point.
+pub const PARITY_DIR: &str = "parity";
+
+const MAGIC: &[u8; 8] = b"ARDXPAR1";
+const HASH_LEN: usize = 32;
+/// Trailer: header hash, header length, magic.
+const TRAILER_LEN: u64 = (HASH_LEN + 8 + 8) as u64;
+
+/// Data blocks per Reed-Solomon stripe. Data and parity rows of the Cauchy
+/// matrix must stay distinct in GF(2^8), so stripes stay below 128 blocks.
+const STRIPE_BLOCKS: usize = 100;
+const MIN_BLOCK_SIZE: u64 = 4096;
+const MAX_BLOCK_SIZE: u64 = 1024 * 1024;
+/// Blocks a point is split into before the block size grows past the minimum.
+const TARGET_BLOCKS: u64 = 1024;
+
+/// Parity file layout, all integers little-endian:
+///
+/// ```text
+/// parity blocks | data block hashes | parity block hashes
+///   | header JSON | SHA-256(header) | header length (u64) | "ARDXPAR1"
+/// ```
+///
+/// The point's files are read as one stream in path order and cut into
+/// `block_size` blocks; every `STRIPE_BLOCKS` of them get
+/// `ceil(n * parity_percent / 100)` parity blocks, so that many damaged
+/// blocks per stripe can be rebuilt. The block hashes tell which ones are
+/// damaged.
+#[derive(Debug, Serialize, Deserialize)]
+struct ParityHeader {
+    block_size: u64,
+    total_size: u64,
+    parity_percent: u32,
+    /// Files of the point in stream order; one unnamed entry for an archive.
+    files: Vec<PointFile>,
+    /// SHA-256 over the data and parity block hashes.
+    hashes_sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PointFile {
+    path: String,
+    size: u64,
+}
+
+struct Stripe {
+    first_data: usize,
+    data: usize,
+    first_parity: usize,
+    parity: usize,
+}
+
+fn stripes(total_size: u64, block_size: u64, parity_percent: u32) -> Vec<Stripe> {
+    let data_blocks = total_size.div_ceil(block_size) as usize;
+    let mut stripes = Vec::new();
+    let (mut first_data, mut first_parity) = (0, 0);
+    while first_data < data_blocks {
+        let data = STRIPE_BLOCKS.min(data_blocks - first_data);
+        let parity = (data * parity_percent as usize).div_ceil(100).max(1);
+        stripes.push(Stripe {
+            first_data,
+            data,
+            first_parity,
+            parity,
+        });
+        first_data += data;
+        first_parity += parity;
+    }
+    stripes
+}
+
+fn block_size_for(total_size: u64) -> u64 {
+    total_size
+        .div_ceil(TARGET_BLOCKS)
+        .next_multiple_of(MIN_BLOCK_SIZE)
+        .clamp(MIN_BLOCK_SIZE, MAX_BLOCK_SIZE)
+}
+
+const GF_TABLES: ([u8; 512], [u8; 256]) = {
+    let mut exp = [0u8; 512];
+    let mut log = [0u8; 256];
+    let mut x: u16 = 1;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = x as u8;
+        log[x as usize] = i as u8;
+        x <<= 1;
+        if x & 0x100 != 0 {
+            x ^= 0x11d;
+        }
+        i += 1;
+    }
+    while i < 512 {
+        exp[i] = exp[i - 255];
+        i += 1;
+    }
+    (exp, log)
+};
+const GF_EXP: [u8; 512] = GF_TABLES.0;
+const GF_LOG: [u8; 256] = GF_TABLES.1;
+
+fn gf_mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    GF_EXP[GF_LOG[a as usize] as usize + GF_LOG[b as usize] as usize]
+}
+
+fn gf_inv(a: u8) -> u8 {
+    GF_EXP[255 - GF_LOG[a as usize] as usize]
+}
+
+/// Cauchy matrix entry `1 / (x_row + y_col)` with x in 0..128 and y in
+/// 128..256: every square submatrix is invertible, so any damaged data
+/// blocks can be rebuilt from as many intact parity blocks.
+fn coefficient(parity_row: usize, data_col: usize) -> u8 {
+    gf_inv(parity_row as u8 ^ (128 + data_col as u8))
+}
+
+/// `dst += c * src` over GF(2^8), byte by byte.
+fn mul_add(dst: &mut [u8], src: &[u8], c: u8) {
+    if c == 0 {
+        return;
+    }
+    let log_c = GF_LOG[c as usize] as usize;
+    for (d, &s) in dst.iter_mut().zip(src) {
+        if s != 0 {
+            *d ^= GF_EXP[GF_LOG[s as usize] as usize + log_c];
+        }
+    }
+}
+
+/// Gauss-Jordan inverse of a square GF(2^8) matrix.
+fn invert(mut matrix: Vec<Vec<u8>>) -> Option<Vec<Vec<u8>>> {
+    let n = matrix.len();
+    let mut inverse: Vec<Vec<u8>> = (0..n)
+        .map(|row| (0..n).map(|col| u8::from(row == col)).collect())
+        .collect();
+    for col in 0..n {
+        let pivot = (col..n).find(|&row| matrix[row][col] != 0)?;
+        matrix.swap(col, pivot);
+        inverse.swap(col, pivot);
+        let scale = gf_inv(matrix[col][col]);
+        for value in matrix[col].iter_mut().chain(inverse[col].iter_mut()) {
+            *value = gf_mul(*value, scale);
+        }
+        for row in 0..n {
+            let factor = matrix[row][col];
+            if row == col || factor == 0 {
+                continue;
+            }
+            for k in 0..n {
+                matrix[row][k] ^= gf_mul(factor, matrix[col][k]);
+                inverse[row][k] ^= gf_mul(factor, inverse[col][k]);
+            }
+        }
+    }
+    Some(inverse)
+}
+
+fn sha256(data: &[u8]) -> [u8; HASH_LEN] {
+    Sha256::digest(data).into()
+}
+
+/// `<backup_dir>/parity/<point_name>.par`.
+pub fn parity_path(backup_dir: &Path, point_name: &str) -> PathBuf {
+    backup_dir
+        .join(PARITY_DIR)
+        .join(format!("{}.par", point_name))
+}
+
+fn point_files(point_path: &Path) -> Result<Vec<PointFile>> {
+    if point_path.is_file() {
+        return Ok(vec![PointFile {
+            path: String::new(),
+            size: fs::metadata(point_path)?.len(),
+        }]);
+    }
+    let mut files = Vec::new();
+    for entry in WalkDir::new(point_path).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry
+            .path()
+            .strip_prefix(point_path)
+            .unwrap_or(entry.path());
+        files.push(PointFile {
+            path: attrs::manifest_key(relative),
+            size: entry.metadata()?.len(),
+        });
+    }
+    Ok(files)
+}
+
+fn file_path(point_path: &Path, file: &PointFile) -> PathBuf {
+    if file.path.is_empty() {
+        point_path.to_path_buf()
+    } else {
+        point_path.join(&file.path)
+    }
+}
+
+/// Read as much of `buf` as the file has; the rest is left untouched.
+fn read_full(file: &mut File, buf: &mut [u8]) -> usize {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..]) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => filled += n,
+        }
+    }
+    filled
+}
+
+/// The files of a point read back to back as recorded, with zeros for
+/// whatever a missing or truncated file lacks.
+struct ContentReader<'a> {
+    point_path: &'a Path,
+    files: &'a [PointFile],
+    next: usize,
+    file: Option<File>,
+    left: u64,
+}
+
+impl<'a> ContentReader<'a> {
+    fn new(point_path: &'a Path, files: &'a [PointFile]) -> Self {
+        Self {
+            point_path,
+            files,
+            next: 0,
+            file: None,
+            left: 0,
+        }
+    }
+
+    fn read_block(&mut self, buf: &mut [u8]) {
+        buf.fill(0);
+        let mut filled = 0;
+        while filled < buf.len() {
+            if self.left == 0 {
+                let Some(file) = self.files.get(self.next) else {
+                    return;
+                };
+                self.next += 1;
+                self.left = file.size;
+                self.file = File::open(file_path(self.point_path, file)).ok();
+                continue;
+            }
+            let want = self.left.min((buf.len() - filled) as u64) as usize;
+            let target = &mut buf[filled..filled + want];
+            if let Some(file) = self.file.as_mut()
+                && read_full(file, target) < want
+            {
+                self.file = None;
+            }
+            filled += want;
+            self.left -= want as u64;
+        }
+    }
+}
+
+/// (file index, offset in file, length) pieces of the stream range
+/// `offset..offset + len`.
+fn segments(files: &[PointFile], offset: u64, len: u64) -> Vec<(usize, u64, u64)> {
+    let mut pieces = Vec::new();
+    let (mut start, end) = (offset, offset + len);
+    let mut file_start = 0;
+    for (index, file) in files.iter().enumerate() {
+        let file_end = file_start + file.size;
+        if start < file_end && start < end {
+            let piece_end = end.min(file_end);
+            pieces.push((index, start - file_start, piece_end - start));
+            start = piece_end;
+        }
+        file_start = file_end;
+    }
+    pieces
+}
+
+fn read_block_at(point_path: &Path, header: &ParityHeader, block: usize, buf: &mut [u8]) {
+    buf.fill(0);
+    let offset = block as u64 * header.block_size;
+    let len = header.block_size.min(header.total_size - offset);
+    let mut at = 0;
+    for (index, file_offset, piece) in segments(&header.files, offset, len) {
+        let target = &mut buf[at..at + piece as usize];
+        if let Ok(mut file) = File::open(file_path(point_path, &header.files[index]))
+            && file.seek(SeekFrom::Start(file_offset)).is_ok()
+        {
+            read_full(&mut file, target);
+        }
+        at += piece as usize;
+    }
+}
+
+fn write_block_at(
+    point_path: &Path,
+    header: &ParityHeader,
+    block: usize,
+    buf: &[u8],
+) -> Result<()> {
+    let offset = block as u64 * header.block_size;
+    let len = header.block_size.min(header.total_size - offset);
+    let mut at = 0;
+    for (index, file_offset, piece) in segments(&header.files, offset, len) {
+        let path = file_path(point_path, &header.files[index]);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("Failed to open {:?} for repair", path))?;
+        file.seek(SeekFrom::Start(file_offset))?;
+        file.write_all(&buf[at..at + piece as usize])
+            .with_context(|| format!("Failed to repair {:?}", path))?;
+        at += piece as usize;
+    }
+    Ok(())
+}
+
+/// Write parity for the point at `point_path`, `percent` of its size,
+/// and return the bytes written.
+pub fn write(
+    backup_dir: &Path,
+    point_name: &str,
+    point_path: &Path,
+    percent: u32,
+    fsync: bool,
+) -> Result<u64> {
+    let files = point_files(point_path)?;
+    let total_size: u64 = files.iter().map(|file| file.size).sum();
+    let block_size = block_size_for(total_size);
+    let path = parity_path(backup_dir, point_name);
+    fs::create_dir_all(backup_dir.join(PARITY_DIR))?;
+    let tmp_path = path.with_extension("par.tmp");
+    let mut out = BufWriter::new(
+        File::create(&tmp_path).with_context(|| format!("Failed to create {:?}", tmp_path))?,
+    );
+
+    let mut reader = ContentReader::new(point_path, &files);
+    let mut data_hashes = Vec::new();
+    let mut parity_hashes = Vec::new();
+    let mut block = vec![0u8; block_size as usize];
+    for stripe in stripes(total_size, block_size, percent) {
+        let mut parity = vec![vec![0u8; block_size as usize]; stripe.parity];
+        for col in 0..stripe.data {
+            reader.read_block(&mut block);
+            data_hashes.extend_from_slice(&sha256(&block));
+            for (row, parity_block) in parity.iter_mut().enumerate() {
+                mul_add(parity_block, &block, coefficient(row, col));
+            }
+        }
+        for parity_block in &parity {
+            parity_hashes.extend_from_slice(&sha256(parity_block));
+            out.write_all(parity_block)?;
+        }
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(&data_hashes);
+    hasher.update(&parity_hashes);
+    let header = serde_json::to_vec(&ParityHeader {
+        block_size,
+        total_size,
+        parity_percent: percent,
+        files,
+        hashes_sha256: format!("{:x}", hasher.finalize()),
+    })?;
+    out.write_all(&data_hashes)?;
+    out.write_all(&parity_hashes)?;
+    out.write_all(&header)?;
+    out.write_all(&sha256(&header))?;
+    out.write_all(&(header.len() as u64).to_le_bytes())?;
+    out.write_all(MAGIC)?;
+    let file = out.into_inner().map_err(|e| e.into_error())?;
+    if fsync {
+        file.sync_all()?;
+    }
+    drop(file);
+    fs::rename(&tmp_path, &path).with_context(|| format!("Failed to replace {:?}", path))?;
+    Ok(fs::metadata(&path)?.len())
+}
+
+/// A parity file with its header and block hashes checked.
+struct ParityIndex {
+    file: File,
+    header: ParityHeader,
+    stripes: Vec<Stripe>,
+    data_hashes: Vec<u8>,
+    parity_hashes: Vec<u8>,
+}
+
+impl ParityIndex {
+    fn open(path: &Path) -> Result<Self> {
+        let mut file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+        let len = file.metadata()?.len();
+        if len < TRAILER_LEN {
+            return Err(anyhow::anyhow!("too short to be a parity file"));
+        }
+        let mut trailer = [0u8; TRAILER_LEN as usize];
+        file.seek(SeekFrom::Start(len - TRAILER_LEN))?;
+        file.read_exact(&mut trailer)?;
+        if &trailer[HASH_LEN + 8..] != MAGIC {
+            return Err(anyhow::anyhow!("not a parity file"));
+        }
+        let header_len = u64::from_le_bytes(trailer[HASH_LEN..HASH_LEN + 8].try_into()?);
+        if header_len > len - TRAILER_LEN {
+            return Err(anyhow::anyhow!("header length is out of range"));
+        }
+        let mut header_bytes = vec![0u8; header_len as usize];
+        file.seek(SeekFrom::Start(len - TRAILER_LEN - header_len))?;
+        file.read_exact(&mut header_bytes)?;
+        if sha256(&header_bytes)[..] != trailer[..HASH_LEN] {
+            return Err(anyhow::anyhow!("header checksum mismatch"));
+        }
+        let header: ParityHeader = serde_json::from_slice(&header_bytes)?;
+        if header.block_size == 0 || header.parity_percent > 100 {
+            return Err(anyhow::anyhow!("invalid header"));
+        }
+
+        let stripes = stripes(header.total_size, header.block_size, header.parity_percent);
+        let data_blocks: usize = stripes.iter().map(|s| s.data).sum();
+        let parity_blocks: usize = stripes.iter().map(|s| s.parity).sum();
+        let parity_bytes = parity_blocks as u64 * header.block_size;
+        let hash_bytes = ((data_blocks + parity_blocks) * HASH_LEN) as u64;
+        if parity_bytes + hash_bytes + header_len + TRAILER_LEN != len {
+            return Err(anyhow::anyhow!("file size does not match its header"));
+        }
+        let mut hashes = vec![0u8; hash_bytes as usize];
+        file.seek(SeekFrom::Start(parity_bytes))?;
+        file.read_exact(&mut hashes)?;
+        if format!("{:x}", Sha256::digest(&hashes)) != header.hashes_sha256 {
+            return Err(anyhow::anyhow!("block hash checksum mismatch"));
+        }
+        let parity_hashes = hashes.split_off(data_blocks * HASH_LEN);
+        Ok(Self {
+            file,
+            header,
+            stripes,
+            data_hashes: hashes,
+            parity_hashes,
+        })
+    }
+
+    fn data_hash(&self, block: usize) -> &[u8] {
+        &self.data_hashes[block * HASH_LEN..(block + 1) * HASH_LEN]
+    }
+
+    fn read_parity_block(&mut self, block: usize, buf: &mut [u8]) -> Result<()> {
+        self.file
+            .seek(SeekFrom::Start(block as u64 * self.header.block_size))?;
+        self.file.read_exact(buf)?;
+        Ok(())
+    }
+
+    fn parity_block_intact(&mut self, block: usize, buf: &mut [u8]) -> bool {
+        self.read_parity_block(block, buf).is_ok()
+            && sha256(buf)[..] == self.parity_hashes[block * HASH_LEN..(block + 1) * HASH_LEN]
+    }
+}
+
+/// What verifying a point against its parity found.
+#[derive(Debug, Default)]
+pub struct ParityCheck {
+    /// Data blocks that no longer match.
+    pub damaged_blocks: usize,
+    /// Damaged data blocks beyond what their stripe's parity can rebuild.
+    pub unrecoverable_blocks: usize,
+    pub damaged_parity_blocks: usize,
+    /// Files holding damaged blocks or grown past their recorded size.
+    pub damaged_files: Vec<String>,
+}
+
+impl ParityCheck {
+    pub fn is_clean(&self) -> bool {
+        self.damaged_blocks == 0 && self.damaged_parity_blocks == 0 && self.damaged_files.is_empty()
+    }
+}
+
+struct Scan {
+    check: ParityCheck,
+    damaged_data: Vec<usize>,
+    damaged_parity: Vec<usize>,
+    oversized: Vec<usize>,
+}
+
+fn scan(point_path: &Path, index: &mut ParityIndex) -> Scan {
+    let block_size = index.header.block_size as usize;
+    let mut block = vec![0u8; block_size];
+    let mut damaged_data = Vec::new();
+    let mut damaged_parity = Vec::new();
+    let mut unrecoverable_blocks = 0;
+    let files = index.header.files.clone();
+    let mut reader = ContentReader::new(point_path, &files);
+    for s in 0..index.stripes.len() {
+        let (first_data, data) = (index.stripes[s].first_data, index.stripes[s].data);
+        let (first_parity, parity) = (index.stripes[s].first_parity, index.stripes[s].parity);
+        let mut damaged = 0;
+        for b in first_data..first_data + data {
+            reader.read_block(&mut block);
+            if sha256(&block)[..] != *index.data_hash(b) {
+                damaged_data.push(b);
+                damaged += 1;
+            }
+        }
+        let mut intact_parity = 0;
+        for p in first_parity..first_parity + parity {
+            if index.parity_block_intact(p, &mut block) {
+                intact_parity += 1;
+            } else {
+                damaged_parity.push(p);
+            }
+        }
+        if damaged > intact_parity {
+            unrecoverable_blocks += damaged;
+        }
+    }
+
+    let oversized: Vec<usize> = files
+        .iter()
+        .enumerate()
+        .filter(|(_, file)| {
+            fs::metadata(file_path(point_path, file)).is_ok_and(|m| m.len() > file.size)
+        })
+        .map(|(i, _)| i)
+        .collect();
+    let mut damaged_files: Vec<String> = damaged_data
+        .iter()
+        .flat_map(|&b| {
+            let offset = b as u64 * index.header.block_size;
+            let len = index
+                .header
+                .block_size
+                .min(index.header.total_size - offset);
+            segments(&files, offset, len)
+        })
+        .map(|(i, _, _)| i)
+        .chain(oversized.iter().copied())
+        .map(|i| files[i].path.clone())
+        .collect();
+    damaged_files.sort();
+    damaged_files.dedup();
+
+    Scan {
+        check: ParityCheck {
+            damaged_blocks: damaged_data.len(),
+            unrecoverable_blocks,
+            damaged_parity_blocks: damaged_parity.len(),
+            damaged_files,
+        },
+        damaged_data,
+        damaged_parity,
+        oversized,
+    }
+}
+
+/// Check the point at `point_path` against its parity file. An error means
+/// the parity file itself is unusable.
+pub fn verify(backup_dir: &Path, point_name: &str, point_path: &Path) -> Result<ParityCheck> {
+    let mut index = ParityIndex::open(&parity_path(backup_dir, point_name))?;
+    Ok(scan(point_path, &mut index).check)
+}
+
+/// Rebuild the damaged blocks of a point from its parity, cut files back
+/// to their recorded size and rewrite damaged parity blocks. Returns what
+/// was found before repairing; fails without writing anything when a stripe
+/// has more damage than parity.
+pub fn repair(backup_dir: &Path, point_name: &str, point_path: &Path) -> Result<ParityCheck> {
+    let path = parity_path(backup_dir, point_name);
+    let mut index = ParityIndex::open(&path)?;
+    let scan = scan(point_path, &mut index);
+    if scan.check.unrecoverable_blocks > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} damaged blocks in {} exceed what its parity can rebuild",
+            scan.check.unrecoverable_blocks,
+            scan.check.damaged_blocks,
+            point_name
+        ));
+    }
+
+    let block_size = index.header.block_size as usize;
+    for s in 0..index.stripes.len() {
+        let (first_data, data) = (index.stripes[s].first_data, index.stripes[s].data);
+        let (first_parity, parity) = (index.stripes[s].first_parity, index.stripes[s].parity);
+        let damaged: Vec<usize> = scan
+            .damaged_data
+            .iter()
+            .copied()
+            .filter(|b| (first_data..first_data + data).contains(b))
+            .collect();
+        if damaged.is_empty() {
+            continue;
+        }
+        let rows: Vec<usize> = (first_parity..first_parity + parity)
+            .filter(|p| !scan.damaged_parity.contains(p))
+            .take(damaged.len())
+            .collect();
+
+        // Each intact parity block minus the intact data it covers leaves
+        // a combination of the damaged blocks only.
+        let mut syndromes = vec![vec![0u8; block_size]; rows.len()];
+        for (syndrome, &p) in syndromes.iter_mut().zip(&rows) {
+            index.read_parity_block(p, syndrome)?;
+        }
+        let mut block = vec![0u8; block_size];
+        for b in (first_data..first_data + data).filter(|b| !damaged.contains(b)) {
+            read_block_at(point_path, &index.header, b, &mut block);
+            for (syndrome, &p) in syndromes.iter_mut().zip(&rows) {
+                mul_add(
+                    syndrome,
+                    &block,
+                    coefficient(p - first_parity, b - first_data),
+                );
+            }
+        }
+        let matrix = rows
+            .iter()
+            .map(|&p| {
+                damaged
+                    .iter()
+                    .map(|&b| coefficient(p - first_parity, b - first_data))
+                    .collect()
+            })
+            .collect();
+        let inverse = invert(matrix).context("Parity matrix is not invertible")?;
+        for (i, &b) in damaged.iter().enumerate() {
+            let mut rebuilt = vec![0u8; block_size];
+            for (r, syndrome) in syndromes.iter().enumerate() {
+                mul_add(&mut rebuilt, syndrome, inverse[i][r]);
+            }
+            if sha256(&rebuilt)[..] != *index.data_hash(b) {
+                return Err(anyhow::anyhow!(
+                    "Block {} of {} could not be rebuilt from parity",
+                    b,
+                    point_name
+                ));
+            }
+            write_block_at(point_path, &index.header, b, &rebuilt)?;
+        }
+    }
+
+    for &i in &scan.oversized {
+        let file = &index.header.files[i];
+        fs::OpenOptions::new()
+            .write(true)
+            .open(file_path(point_path, file))?
+            .set_len(file.size)?;
+    }
+    if !scan.damaged_parity.is_empty() {
+        let percent = index.header.parity_percent;
+        drop(index);
+        write(backup_dir, point_name, point_path, percent, false)?;
+    }
+    Ok(scan.check)
+}
+
+/// Rewrite the parity of a point whose files were changed on purpose, at
+/// the percentage it was written with. Points without parity are skipped.
+pub fn rewrite(backup_dir: &Path, point_name: &str, point_path: &Path) -> Result<()> {
+    let path = parity_path(backup_dir, point_name);
+    if !path.is_file() {
+        return Ok(());
+    }
+    let percent = ParityIndex::open(&path)?.header.parity_percent;
+    write(backup_dir, point_name, point_path, percent, false)?;
+    Ok(())
+}
+
+/// Remove the parity file of a point being deleted.
+pub fn remove_for_point(point_path: &Path) {
+    let (Some(backup_dir), Some(name)) =
+        (point_path.parent(), archive::backup_point_name(point_path))
+    else {
+        return;
+    };
+    let path = parity_path(backup_dir, &name);
+    if path.exists()
+        && let Err(e) = fs::remove_file(&path)
+    {
+        warn!("Failed to remove parity {:?}: {}", path, e);
+    }
+}
+
+/// Parity files (and leftover temp files) of `backup_dir` whose point is
+/// gone.
+pub fn orphaned(backup_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(backup_dir.join(PARITY_DIR)) else {
+        return Vec::new();
+    };
+    let mut orphans: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            match name.strip_suffix(".par") {
+                Some(point) => archive::locate_backup(backup_dir, point).is_none(),
+                None => true,
+            }
+        })
+        .collect();
+    orphans.sort();
+    orphans
+}
+
+#[cfg(test)]
+#[path = "tests/parity_tests.rs"]
+mod tests;
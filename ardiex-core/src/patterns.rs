@@ -0,0 +1,196 @@
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::collections::BTreeMap;
+use std::fs::Metadata;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Compiled include/exclude patterns for one source.
+///
+/// Patterns are globs matched against paths relative to the source dir:
+/// `*` and `?` stay within one path component, `**` spans any number of
+/// them and `[abc]` / `[!abc]` are character classes. A pattern matches at
+/// any depth unless it starts with `/`, which anchors it to the source root.
+/// A path also matches when one of its parent directories does, so
+/// `node_modules` covers everything below it.
+///
+/// Size/age limits and the one-filesystem check are applied separately
+/// through `accepts_metadata` and `accepts_dir`, since they need metadata
+/// rather than a name.
+#[derive(Debug, Clone)]
+pub struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    modified_after: Option<SystemTime>,
+    /// Device of the source dir when directories on other filesystems are
+    /// skipped.
+    root_device: Option<u64>,
+}
+
+impl PathFilter {
+    pub fn new(include_patterns: &[String], exclude_patterns: &[String]) -> Result<Self> {
+        let include = if include_patterns.is_empty() {
+            None
+        } else {
+            Some(compile(include_patterns)?)
+        };
+        Ok(Self {
+            include,
+            exclude: compile(exclude_patterns)?,
+            min_size: None,
+            max_size: None,
+            modified_after: None,
+            root_device: None,
+        })
+    }
+
+    /// Add size bounds in bytes and a maximum age in seconds, measured from
+    /// now so one scan uses a single cutoff.
+    pub fn with_file_limits(
+        mut self,
+        min_size: Option<u64>,
+        max_size: Option<u64>,
+        modified_within_secs: Option<u64>,
+    ) -> Self {
+        self.min_size = min_size;
+        self.max_size = max_size;
+        self.modified_after = modified_within_secs
+            .and_then(|secs| SystemTime::now().checked_sub(Duration::from_secs(secs)));
+        self
+    }
+
+    /// Only descend into directories on the same filesystem as `source_dir`.
+    /// Has no effect on platforms without device ids.
+    pub fn with_one_file_system(mut self, source_dir: &Path) -> Result<Self> {
+        self.root_device = device_id(&std::fs::metadata(source_dir)?);
+        Ok(self)
+    }
+
+    /// Whether a directory may be descended into.
+    pub fn accepts_dir(&self, metadata: &Metadata) -> bool {
+        self.root_device
+            .is_none_or(|root| device_id(metadata).is_none_or(|device| device == root))
+    }
+
+    /// Whether `relative` (or a directory above it) matches an exclude.
+    pub fn is_excluded(&self, relative: &Path) -> bool {
+        matches_path_or_parent(&self.exclude, relative)
+    }
+
+    /// Whether a file passes the include list: always when it is empty.
+    pub fn is_included(&self, relative: &Path) -> bool {
+        self.include
+            .as_ref()
+            .is_none_or(|include| matches_path_or_parent(include, relative))
+    }
+
+    /// Whether a file's size and modification time are within the limits.
+    /// A missing mtime never filters a file out.
+    pub fn accepts_metadata(&self, metadata: &Metadata) -> bool {
+        let size = metadata.len();
+        if self.min_size.is_some_and(|min| size < min)
+            || self.max_size.is_some_and(|max| size > max)
+        {
+            return false;
+        }
+        match (self.modified_after, metadata.modified()) {
+            (Some(cutoff), Ok(modified)) => modified >= cutoff,
+            _ => true,
+        }
+    }
+
+    /// Includes first, then excludes remove matches.
+    pub fn accepts_file(&self, relative: &Path) -> bool {
+        self.is_included(relative) && !self.is_excluded(relative)
+    }
+}
+
+/// Values keyed by pattern, such as `file_strategies`, matched like
+/// `PathFilter` patterns. When several match, the longest pattern wins, so
+/// `db/*.bin` overrides `*.bin`.
+#[derive(Debug, Clone)]
+pub struct PatternMap<T> {
+    set: GlobSet,
+    /// Pattern length and value, in the order the set was built.
+    values: Vec<(usize, T)>,
+}
+
+impl<T: Copy> PatternMap<T> {
+    pub fn new(map: &BTreeMap<String, T>) -> Result<Self> {
+        let patterns: Vec<String> = map.keys().cloned().collect();
+        Ok(Self {
+            set: compile(&patterns)?,
+            values: map
+                .iter()
+                .map(|(pattern, value)| (pattern.len(), *value))
+                .collect(),
+        })
+    }
+
+    /// Value of the longest pattern matching `relative` or a directory
+    /// above it.
+    pub fn get(&self, relative: &Path) -> Option<T> {
+        if self.set.is_empty() {
+            return None;
+        }
+        relative
+            .ancestors()
+            .take_while(|p| !p.as_os_str().is_empty())
+            .flat_map(|p| self.set.matches(p))
+            .max_by_key(|&index| self.values[index].0)
+            .map(|index| self.values[index].1)
+    }
+}
+
+/// Check that every pattern is a valid glob, naming the first bad one.
+pub fn validate_patterns(patterns: &[String]) -> Result<()> {
+    compile(patterns).map(|_| ())
+}
+
+fn compile(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let trimmed = pattern.trim().trim_end_matches('/');
+        let glob = match trimmed.strip_prefix('/') {
+            Some(anchored) => anchored.to_string(),
+            None if trimmed.starts_with("**/") => trimmed.to_string(),
+            None => format!("**/{}", trimmed),
+        };
+        builder.add(
+            GlobBuilder::new(&glob)
+                .literal_separator(true)
+                .build()
+                .with_context(|| format!("Invalid pattern '{}'", pattern))?,
+        );
+    }
+    Ok(builder.build()?)
+}
+
+fn device_id(metadata: &Metadata) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some(metadata.dev())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+fn matches_path_or_parent(set: &GlobSet, relative: &Path) -> bool {
+    if set.is_empty() {
+        return false;
+    }
+    relative
+        .ancestors()
+        .take_while(|p| !p.as_os_str().is_empty())
+        .any(|p| set.is_match(p))
+}
+
+#[cfg(test)]
+#[path = "tests/patterns_tests.rs"]
+mod tests;
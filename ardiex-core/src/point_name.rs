@@ -0,0 +1,132 @@
+use anyhow::Result;
+
+use crate::backup::PARTIAL_BACKUP_PREFIX;
+
+/// `backup_name_template` of `full_<time>`/`inc_<time>` names.
+pub const DEFAULT_TEMPLATE: &str = "{type}_{timestamp}";
+
+/// `{timestamp}` of new points, in UTC.
+pub const TIMESTAMP_FORMAT: &str = "%Y%m%d_%H%M%S%3f";
+
+/// The parts of a backup point name restore, retention and metadata sync
+/// go by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointName<'a> {
+    pub is_full: bool,
+    /// `YYYYMMDD_HHMMSS[mmm]`, which also orders points chronologically.
+    pub timestamp: &'a str,
+}
+
+/// Parse any point name a template can produce. Names are split on `_`;
+/// the timestamp is the `YYYYMMDD` + `HHMMSS[mmm]` segment pair and the
+/// type the one other segment that is `full` or `inc`, so labels and
+/// literal text around them need no knowledge of the template.
+pub fn parse(name: &str) -> Option<PointName<'_>> {
+    if name.starts_with(PARTIAL_BACKUP_PREFIX) {
+        return None;
+    }
+    let segments: Vec<&str> = name.split('_').collect();
+    let date = (0..segments.len().saturating_sub(1))
+        .find(|&i| is_digits(segments[i], &[8]) && is_digits(segments[i + 1], &[6, 9]))?;
+    let types: Vec<bool> = segments
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != date && *i != date + 1)
+        .filter_map(|(_, segment)| match *segment {
+            "full" => Some(true),
+            "inc" => Some(false),
+            _ => None,
+        })
+        .collect();
+    let [is_full] = types[..] else {
+        return None;
+    };
+    let start: usize = segments[..date].iter().map(|s| s.len() + 1).sum();
+    let end = start + segments[date].len() + 1 + segments[date + 1].len();
+    Some(PointName {
+        is_full,
+        timestamp: &name[start..end],
+    })
+}
+
+fn is_digits(segment: &str, lengths: &[usize]) -> bool {
+    lengths.contains(&segment.len()) && segment.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Name of a new point from `template`. A `{label}` without a label is
+/// dropped together with the `_` next to it.
+pub fn render(template: &str, label: Option<&str>, is_full: bool, timestamp: &str) -> String {
+    let rendered = template
+        .replace("{label}", label.unwrap_or_default())
+        .replace("{type}", if is_full { "full" } else { "inc" })
+        .replace("{timestamp}", timestamp);
+    rendered
+        .split('_')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Check that every name `template` renders parses back to its type and
+/// timestamp, with and without a label.
+pub fn validate_template(template: &str) -> Result<()> {
+    for placeholder in ["{type}", "{timestamp}"] {
+        if template.matches(placeholder).count() != 1 {
+            return Err(anyhow::anyhow!(
+                "backup_name_template must contain {} exactly once: '{}'",
+                placeholder,
+                template
+            ));
+        }
+    }
+    let literal = template
+        .replace("{label}", "")
+        .replace("{type}", "")
+        .replace("{timestamp}", "");
+    if let Some(c) = literal
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+    {
+        return Err(anyhow::anyhow!(
+            "backup_name_template may only add letters, digits, '-', '_' and '.', not '{}'",
+            c
+        ));
+    }
+    let timestamp = "20240101_120000000";
+    for label in [None, Some("label")] {
+        for is_full in [true, false] {
+            let name = render(template, label, is_full, timestamp);
+            if parse(&name) != Some(PointName { is_full, timestamp }) {
+                return Err(anyhow::anyhow!(
+                    "backup_name_template '{}' gives names like '{}' that cannot be read back; \
+                     keep {{type}} and {{timestamp}} as their own '_'-separated parts",
+                    template,
+                    name
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A label is one `_`-free name part, so it never reads as a type or
+/// timestamp.
+pub fn validate_label(label: &str) -> Result<()> {
+    let valid = !label.is_empty()
+        && label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.'))
+        && !matches!(label, "full" | "inc" | "partial")
+        && !label.trim_matches('.').is_empty();
+    if !valid {
+        return Err(anyhow::anyhow!(
+            "Invalid label '{}': use letters, digits, '-' and '.' (not 'full', 'inc' or 'partial')",
+            label
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[path = "tests/point_name_tests.rs"]
+mod tests;
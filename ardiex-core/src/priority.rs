@@ -0,0 +1,67 @@
+#[cfg(unix)]
+use anyhow::Context;
+use anyhow::Result;
+
+/// `IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT` from linux/ioprio.h.
+#[cfg(target_os = "linux")]
+const IOPRIO_IDLE: libc::c_int = 3 << 13;
+#[cfg(target_os = "linux")]
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+
+/// Lower this process's CPU priority to `nice` and, with `io_idle`, its
+/// I/O priority to the idle class. Linux keeps both per thread, so every
+/// current thread is changed; threads started later inherit them.
+pub fn lower_process_priority(nice: Option<i32>, io_idle: bool) -> Result<()> {
+    if nice.is_none() && !io_idle {
+        return Ok(());
+    }
+    apply(nice, io_idle)
+}
+
+#[cfg(target_os = "linux")]
+fn apply(nice: Option<i32>, io_idle: bool) -> Result<()> {
+    let tasks = std::fs::read_dir("/proc/self/task").context("Failed to list process threads")?;
+    for task in tasks.flatten() {
+        let Ok(tid) = task.file_name().to_string_lossy().parse::<libc::id_t>() else {
+            continue;
+        };
+        if let Some(nice) = nice
+            && unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, nice) } != 0
+        {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("Failed to set niceness {} for thread {}", nice, tid));
+        }
+        if io_idle
+            && unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, tid, IOPRIO_IDLE) }
+                != 0
+        {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("Failed to set idle I/O priority for thread {}", tid));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn apply(nice: Option<i32>, io_idle: bool) -> Result<()> {
+    if let Some(nice) = nice
+        && unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0
+    {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("Failed to set niceness {}", nice));
+    }
+    if io_idle {
+        log::warn!("worker_ionice_idle is only supported on Linux; ignoring it");
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn apply(_nice: Option<i32>, _io_idle: bool) -> Result<()> {
+    log::warn!("worker_nice/worker_ionice_idle are not supported on this platform; ignoring them");
+    Ok(())
+}
+
+#[cfg(test)]
+#[path = "tests/priority_tests.rs"]
+mod tests;
@@ -0,0 +1,263 @@
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::info;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::logger;
+
+const BAR_TEMPLATE: &str = "{prefix:.bold} [{bar:30.cyan/blue}] {bytes}/{total_bytes} \
+                            {binary_bytes_per_sec} ETA {eta} {msg}";
+
+const FILE_BAR_TEMPLATE: &str = "  {msg} [{bar:30.green/blue}] {bytes}/{total_bytes} \
+                                 {binary_bytes_per_sec} ETA {eta}";
+
+/// Files at least this large report their own byte progress while being
+/// copied, delta'd or reassembled, since one of them can take minutes.
+pub const LARGE_FILE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Bars of every backup/restore in this process, once an interactive
+/// command has turned them on.
+static BARS: OnceLock<MultiProgress> = OnceLock::new();
+
+/// Draw progress bars instead of the 10% log lines when stdout is a
+/// terminal. Called by `backup` and `restore`; `run`, piped output and
+/// `--quiet` keep logging.
+pub fn enable_bars_if_interactive() {
+    if !std::io::stdout().is_terminal() || logger::is_quiet() {
+        return;
+    }
+    BARS.get_or_init(|| MultiProgress::with_draw_target(ProgressDrawTarget::stdout()));
+    // Log lines echoed to the console would tear through the bars.
+    logger::set_console_wrapper(suspend_bars);
+}
+
+/// One step of a [`Progress`], as handed to the progress callback.
+#[derive(Debug, Clone, Copy)]
+pub struct ProgressUpdate<'a> {
+    pub label: &'static str,
+    /// Backup dir being written, or restore/export target.
+    pub target: &'a Path,
+    /// File being started; `None` once it is finished.
+    pub file: Option<&'a Path>,
+    pub files_done: usize,
+    pub total_files: usize,
+    pub bytes_done: u64,
+    pub total_bytes: u64,
+}
+
+type ProgressCallback = Arc<dyn Fn(&ProgressUpdate<'_>) + Send + Sync>;
+
+static CALLBACK: RwLock<Option<ProgressCallback>> = RwLock::new(None);
+
+/// Call `callback` as every backup, restore and export of this process
+/// starts and finishes each file, replacing any earlier callback. Runs on
+/// the worker's thread, so it should return quickly.
+pub fn set_progress_callback(callback: impl Fn(&ProgressUpdate<'_>) + Send + Sync + 'static) {
+    if let Ok(mut slot) = CALLBACK.write() {
+        *slot = Some(Arc::new(callback));
+    }
+}
+
+/// Stop calling the callback set by [`set_progress_callback`].
+pub fn clear_progress_callback() {
+    if let Ok(mut slot) = CALLBACK.write() {
+        *slot = None;
+    }
+}
+
+fn suspend_bars(write: &mut dyn FnMut()) {
+    match BARS.get() {
+        Some(bars) => bars.suspend(write),
+        None => write(),
+    }
+}
+
+/// Progress of one backup or restore over a known set of files: a bar
+/// with the current file, throughput and ETA when bars are on, otherwise
+/// `"<label>: N% (done/total files)"` every 10%.
+pub struct Progress {
+    label: &'static str,
+    target: PathBuf,
+    total_files: usize,
+    files_done: usize,
+    total_bytes: u64,
+    bytes_done: u64,
+    last_logged: usize,
+    bar: Option<ProgressBar>,
+    callback: Option<ProgressCallback>,
+}
+
+impl Progress {
+    /// `target` (its file name) tells concurrent bars apart.
+    pub fn new(label: &'static str, target: &Path, total_files: usize, total_bytes: u64) -> Self {
+        let bar = BARS.get().map(|bars| {
+            let style = ProgressStyle::with_template(BAR_TEMPLATE)
+                .unwrap_or_else(|_| ProgressStyle::default_bar())
+                .progress_chars("=> ");
+            let name = target
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| target.display().to_string());
+            bars.add(
+                ProgressBar::new(total_bytes)
+                    .with_style(style)
+                    .with_prefix(name),
+            )
+        });
+        Self {
+            label,
+            target: target.to_path_buf(),
+            total_files,
+            files_done: 0,
+            total_bytes,
+            bytes_done: 0,
+            last_logged: 0,
+            bar,
+            callback: CALLBACK.read().ok().and_then(|slot| slot.clone()),
+        }
+    }
+
+    fn notify(&self, file: Option<&Path>) {
+        if let Some(ref callback) = self.callback {
+            callback(&ProgressUpdate {
+                label: self.label,
+                target: &self.target,
+                file,
+                files_done: self.files_done,
+                total_files: self.total_files,
+                bytes_done: self.bytes_done,
+                total_bytes: self.total_bytes,
+            });
+        }
+    }
+
+    /// Show `file` as the one being worked on.
+    pub fn start_file(&self, file: &Path) {
+        self.notify(Some(file));
+        if let Some(ref bar) = self.bar {
+            bar.set_message(format!(
+                "{}/{} {}",
+                self.files_done + 1,
+                self.total_files,
+                file.display()
+            ));
+        }
+    }
+
+    /// Byte progress for `file` when its work covers at least
+    /// [`LARGE_FILE_BYTES`]: a second bar under this one when bars are on,
+    /// otherwise a log line every 10%.
+    pub fn large_file(&self, file: &Path, total_bytes: u64) -> Option<FileProgress> {
+        if total_bytes < LARGE_FILE_BYTES {
+            return None;
+        }
+        let bar = match (BARS.get(), &self.bar) {
+            (Some(bars), Some(parent)) => {
+                let style = ProgressStyle::with_template(FILE_BAR_TEMPLATE)
+                    .unwrap_or_else(|_| ProgressStyle::default_bar())
+                    .progress_chars("=> ");
+                Some(
+                    bars.insert_after(
+                        parent,
+                        ProgressBar::new(total_bytes)
+                            .with_style(style)
+                            .with_message(file.display().to_string()),
+                    ),
+                )
+            }
+            _ => None,
+        };
+        Some(FileProgress::new(self.label, file, total_bytes, bar))
+    }
+
+    /// Count one more finished file of `bytes` bytes.
+    pub fn finish_file(&mut self, bytes: u64) {
+        self.files_done += 1;
+        self.bytes_done += bytes;
+        self.notify(None);
+        if let Some(ref bar) = self.bar {
+            bar.inc(bytes);
+            return;
+        }
+        if self.total_files == 0 {
+            return;
+        }
+        let percent = (self.files_done * 100) / self.total_files;
+        if percent / 10 > self.last_logged / 10 {
+            self.last_logged = percent;
+            info!(
+                "{}: {}% ({}/{} files)",
+                self.label, percent, self.files_done, self.total_files
+            );
+        }
+    }
+}
+
+/// Progress within one large file, fed the running byte count by the copy,
+/// delta or chunking loop working on it.
+pub struct FileProgress {
+    label: &'static str,
+    file: PathBuf,
+    total_bytes: u64,
+    done: u64,
+    last_logged: u64,
+    bar: Option<ProgressBar>,
+}
+
+impl FileProgress {
+    fn new(label: &'static str, file: &Path, total_bytes: u64, bar: Option<ProgressBar>) -> Self {
+        Self {
+            label,
+            file: file.to_path_buf(),
+            total_bytes,
+            done: 0,
+            last_logged: 0,
+            bar,
+        }
+    }
+
+    /// `done` bytes of the file are through.
+    pub fn update(&mut self, done: u64) {
+        self.done = done.min(self.total_bytes);
+        if let Some(ref bar) = self.bar {
+            bar.set_position(self.done);
+            return;
+        }
+        if self.total_bytes == 0 {
+            return;
+        }
+        let percent = self.done * 100 / self.total_bytes;
+        if percent / 10 > self.last_logged / 10 {
+            self.last_logged = percent;
+            info!(
+                "{}: {:?} {}% ({} / {})",
+                self.label,
+                self.file,
+                percent,
+                HumanBytes(self.done),
+                HumanBytes(self.total_bytes)
+            );
+        }
+    }
+}
+
+impl Drop for FileProgress {
+    fn drop(&mut self) {
+        if let Some(ref bar) = self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if let Some(ref bar) = self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/progress_tests.rs"]
+mod tests;
@@ -0,0 +1,87 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+use crate::progress::FileProgress;
+use crate::throttle;
+
+/// How `copy_file` produced the destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyMethod {
+    /// Copy-on-write clone sharing the source's blocks (Btrfs, XFS, APFS).
+    Reflink,
+    /// Regular `fs::copy`.
+    Copy,
+}
+
+/// Copy `src` to a new file `dst`, cloning it when both live on a
+/// filesystem with reflink support and falling back to `fs::copy` otherwise
+/// (different filesystems, ext4, tmpfs, non-Unix platforms). The destination
+/// gets the source's permissions either way, like `fs::copy`. A regular
+/// copy reports its running byte count to `progress`.
+pub fn copy_file(
+    src: &Path,
+    dst: &Path,
+    progress: Option<&mut FileProgress>,
+) -> Result<CopyMethod> {
+    if try_clone(src, dst) {
+        return Ok(CopyMethod::Reflink);
+    }
+    throttle::copy_file(src, dst, None, progress)
+        .with_context(|| format!("Failed to copy {:?} to {:?}", src, dst))?;
+    Ok(CopyMethod::Copy)
+}
+
+/// FICLONE shares all extents of `src` with a freshly created `dst`. On
+/// failure the half-created destination is removed so the fallback starts
+/// clean.
+#[cfg(target_os = "linux")]
+fn try_clone(src: &Path, dst: &Path) -> bool {
+    use std::os::fd::AsRawFd;
+
+    let Ok(source) = fs::File::open(src) else {
+        return false;
+    };
+    let Ok(permissions) = source.metadata().map(|m| m.permissions()) else {
+        return false;
+    };
+    let Ok(target) = fs::File::create(dst) else {
+        return false;
+    };
+    // SAFETY: both descriptors are open for the duration of the call and
+    // FICLONE takes the source descriptor as its only argument.
+    let rc = unsafe { libc::ioctl(target.as_raw_fd(), libc::FICLONE, source.as_raw_fd()) };
+    if rc != 0 || fs::set_permissions(dst, permissions).is_err() {
+        drop(target);
+        let _ = fs::remove_file(dst);
+        return false;
+    }
+    true
+}
+
+/// clonefile(2) creates `dst` itself and copies metadata along with it.
+#[cfg(target_os = "macos")]
+fn try_clone(src: &Path, dst: &Path) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let (Ok(src), Ok(dst_c)) = (
+        CString::new(src.as_os_str().as_bytes()),
+        CString::new(dst.as_os_str().as_bytes()),
+    ) else {
+        return false;
+    };
+    // An existing destination would make clonefile fail with EEXIST.
+    let _ = fs::remove_file(dst);
+    // SAFETY: both arguments are valid NUL-terminated paths.
+    unsafe { libc::clonefile(src.as_ptr(), dst_c.as_ptr(), 0) == 0 }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn try_clone(_src: &Path, _dst: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+#[path = "tests/reflink_tests.rs"]
+mod tests;
@@ -0,0 +1,72 @@
+use anyhow::{Context, Result};
+use minisign_verify::{PublicKey, Signature};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// Release asset listing the SHA-256 of every archive, one
+/// `<hex>  <asset name>` line each (`sha256sum` output).
+pub const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+/// minisign signature of `CHECKSUMS_ASSET`.
+pub const SIGNATURE_ASSET: &str = "SHA256SUMS.minisig";
+
+/// minisign public key release checksums are signed with, baked in at
+/// build time from `ARDIEX_UPDATE_PUBLIC_KEY`. Builds without one cannot
+/// verify signatures.
+pub fn embedded_public_key() -> Option<&'static str> {
+    option_env!("ARDIEX_UPDATE_PUBLIC_KEY")
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+}
+
+/// Check that `signature` (a `.minisig` file) was made over `checksums`
+/// by the secret key of `public_key` (its base64 line or the whole
+/// `.pub` file).
+pub fn verify_signature(checksums: &[u8], signature: &str, public_key: &str) -> Result<()> {
+    let public_key = match public_key.trim().lines().count() {
+        1 => PublicKey::from_base64(public_key.trim()),
+        _ => PublicKey::decode(public_key.trim()),
+    }
+    .map_err(|e| anyhow::anyhow!("Invalid update public key: {}", e))?;
+    let signature = Signature::decode(signature.trim())
+        .map_err(|e| anyhow::anyhow!("Invalid {} file: {}", SIGNATURE_ASSET, e))?;
+    public_key
+        .verify(checksums, &signature, false)
+        .map_err(|e| anyhow::anyhow!("{} signature check failed: {}", CHECKSUMS_ASSET, e))
+}
+
+/// SHA-256 listed for `asset_name` in a `CHECKSUMS_ASSET` file.
+pub fn expected_checksum(checksums: &str, asset_name: &str) -> Option<String> {
+    checksums.lines().find_map(|line| {
+        let (hash, name) = line.trim().split_once(char::is_whitespace)?;
+        // `sha256sum -b` marks names with `*`.
+        let name = name.trim_start();
+        let name = name.strip_prefix('*').unwrap_or(name);
+        (name == asset_name).then(|| hash.to_ascii_lowercase())
+    })
+}
+
+/// Check the downloaded `archive` against its line in `checksums`; an
+/// archive the file does not list fails too.
+pub fn verify_archive(archive: &Path, asset_name: &str, checksums: &str) -> Result<()> {
+    let expected = expected_checksum(checksums, asset_name)
+        .ok_or_else(|| anyhow::anyhow!("{} has no entry for {}", CHECKSUMS_ASSET, asset_name))?;
+    let mut file = File::open(archive).with_context(|| format!("Failed to open {:?}", archive))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).with_context(|| format!("Failed to read {:?}", archive))?;
+    let actual = format!("{:x}", hasher.finalize());
+    if actual != expected {
+        return Err(anyhow::anyhow!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            asset_name,
+            expected,
+            actual
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[path = "tests/release_verify_tests.rs"]
+mod tests;
@@ -0,0 +1,187 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::config::{BackupFormat, BackupMode, ResolvedSourceConfig};
+
+/// Format descriptor written by `ardiex init-repo` next to metadata.json.
+pub const REPO_FILE_NAME: &str = "repo.json";
+
+/// Layout of backup dirs this build writes. Bump it, and teach `migrate`
+/// the step, whenever a change would make older builds misread a dir.
+pub const REPO_FORMAT_VERSION: u32 = 2;
+
+/// Oldest format `migrate` can bring up to date.
+const MIN_MIGRATABLE_VERSION: u32 = 1;
+
+/// Contents of `repo.json`: which format a backup dir is in and how its
+/// points are written.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepoInfo {
+    pub format_version: u32,
+    pub backup_mode: BackupMode,
+    pub backup_format: BackupFormat,
+    /// `none` for directory points, `deflate` for `.ardx` archives.
+    pub compression: String,
+    /// `none`, or `chacha20-poly1305` for encrypted archives.
+    pub encryption: String,
+    pub created_at: DateTime<Utc>,
+    pub created_by: RepoCreator,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepoCreator {
+    pub host: String,
+    /// ardiex version that ran `init-repo`.
+    pub version: String,
+}
+
+impl RepoInfo {
+    pub fn new(
+        backup_mode: BackupMode,
+        backup_format: BackupFormat,
+        archive_encryption: bool,
+        host: &str,
+    ) -> Self {
+        let (compression, encryption) = match backup_format {
+            BackupFormat::Directory => ("none", "none"),
+            BackupFormat::Archive if archive_encryption => ("deflate", "chacha20-poly1305"),
+            BackupFormat::Archive => ("deflate", "none"),
+        };
+        Self {
+            format_version: REPO_FORMAT_VERSION,
+            backup_mode,
+            backup_format,
+            compression: compression.to_string(),
+            encryption: encryption.to_string(),
+            created_at: Utc::now(),
+            created_by: RepoCreator {
+                host: host.to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+        }
+    }
+}
+
+/// `repo.json` of `backup_dir`, `None` for dirs created before format
+/// versioning or never initialized.
+pub fn read(backup_dir: &Path) -> Result<Option<RepoInfo>> {
+    let path = backup_dir.join(REPO_FILE_NAME);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+    let info = serde_json::from_str(&content).with_context(|| format!("Invalid {:?}", path))?;
+    Ok(Some(info))
+}
+
+fn write(backup_dir: &Path, info: &RepoInfo) -> Result<()> {
+    let path = backup_dir.join(REPO_FILE_NAME);
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, serde_json::to_string_pretty(info)?)
+        .with_context(|| format!("Failed to write {:?}", tmp_path))?;
+    fs::rename(&tmp_path, &path).with_context(|| format!("Failed to replace {:?}", path))?;
+    Ok(())
+}
+
+/// Write `repo.json` for `backup_dir`, creating the dir if needed. A dir
+/// that already has one is left alone.
+pub fn init(backup_dir: &Path, info: &RepoInfo) -> Result<()> {
+    if let Some(existing) = read(backup_dir)? {
+        return Err(anyhow::anyhow!(
+            "{:?} is already initialized (format {}, created {} by {})",
+            backup_dir,
+            existing.format_version,
+            existing.created_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            existing.created_by.host
+        ));
+    }
+    fs::create_dir_all(backup_dir)
+        .with_context(|| format!("Failed to create backup directory: {:?}", backup_dir))?;
+    write(backup_dir, info)
+}
+
+/// Check before restore, export or replicate reads `backup_dir`: only a
+/// format newer than this build is refused. Older formats are still read
+/// as they are; the next backup migrates them.
+pub fn check_readable(backup_dir: &Path) -> Result<Option<RepoInfo>> {
+    let info = read(backup_dir)?;
+    if let Some(info) = &info
+        && info.format_version > REPO_FORMAT_VERSION
+    {
+        return Err(anyhow::anyhow!(
+            "{:?} uses backup format {}, newer than the {} this ardiex {} understands; upgrade ardiex",
+            backup_dir,
+            info.format_version,
+            REPO_FORMAT_VERSION,
+            env!("CARGO_PKG_VERSION")
+        ));
+    }
+    Ok(info)
+}
+
+/// Check before a backup writes to `backup_dir`: newer formats are
+/// refused, older ones migrated, and settings that differ from the ones
+/// the dir was initialized with are warned about.
+pub fn check_writable(backup_dir: &Path, resolved: &ResolvedSourceConfig) -> Result<()> {
+    let Some(mut info) = check_readable(backup_dir)? else {
+        return Ok(());
+    };
+    if info.format_version < REPO_FORMAT_VERSION {
+        migrate(backup_dir, &mut info)?;
+    }
+
+    let current = RepoInfo::new(
+        resolved.backup_mode.clone(),
+        resolved.backup_format.clone(),
+        resolved.archive_encryption,
+        &resolved.host,
+    );
+    if info.backup_mode != current.backup_mode
+        || info.backup_format != current.backup_format
+        || info.encryption != current.encryption
+    {
+        warn!(
+            "[{:?}] Initialized for {:?}/{:?} (encryption: {}) but backing up as {:?}/{:?} (encryption: {}); earlier points keep their layout",
+            backup_dir,
+            info.backup_mode,
+            info.backup_format,
+            info.encryption,
+            current.backup_mode,
+            current.backup_format,
+            current.encryption
+        );
+    }
+    Ok(())
+}
+
+/// Bring a dir written with an older format up to date; each bump adds the
+/// step from the previous version here.
+///
+/// - 1 -> 2: file hashes moved from metadata.json to `file_hashes.jsonl`.
+///   Hashes still inline are read as before and moved by the backup
+///   itself, so there is nothing to rewrite up front.
+fn migrate(backup_dir: &Path, info: &mut RepoInfo) -> Result<()> {
+    if info.format_version < MIN_MIGRATABLE_VERSION {
+        return Err(anyhow::anyhow!(
+            "{:?} uses backup format {}, too old to migrate (oldest supported: {})",
+            backup_dir,
+            info.format_version,
+            MIN_MIGRATABLE_VERSION
+        ));
+    }
+    info!(
+        "[{:?}] Migrating backup format {} -> {}",
+        backup_dir, info.format_version, REPO_FORMAT_VERSION
+    );
+    info.format_version = REPO_FORMAT_VERSION;
+    write(backup_dir, info)
+}
+
+#[cfg(test)]
+#[path = "tests/repo_tests.rs"]
+mod tests;
@@ -0,0 +1,1149 @@
+use crate::error::Error;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, Utc};
+use log::{debug, info, warn};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::archive::{self, ArchiveReader};
+use crate::attrs::{self, AttributeManifest};
+use crate::backup::BackupManager;
+use crate::dedup::{self, ChunkStore, DedupIndex};
+use crate::delta::{self, DeltaFile};
+use crate::lock::BackupDirLock;
+use crate::longpath;
+use crate::point_name;
+use crate::progress::{FileProgress, Progress};
+use crate::repo;
+use crate::signing::SigningKey;
+use crate::throttle::{self, Throttle};
+
+#[derive(Debug)]
+pub struct BackupEntry {
+    pub name: String,
+    pub path: PathBuf,
+    pub is_full: bool,
+    pub timestamp: String,
+}
+
+/// What happens to a file already in the target that the restore would
+/// replace.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    #[default]
+    Always,
+    Never,
+    /// Only when the existing file is older than the backed-up version.
+    IfOlder,
+    /// Ask for each file on the terminal.
+    Prompt,
+}
+
+impl FromStr for OverwritePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            "if-older" => Ok(Self::IfOlder),
+            "prompt" => Ok(Self::Prompt),
+            _ => Err(anyhow::anyhow!(
+                "Invalid overwrite policy: '{}'. Use 'always', 'never', 'if-older' or 'prompt'",
+                value
+            )),
+        }
+    }
+}
+
+/// What restoring a point would write, worked out before writing anything.
+#[derive(Debug, Default)]
+pub struct RestorePreview {
+    /// Points applied: the full backup and the incrementals after it.
+    pub points: Vec<String>,
+    /// Restored files the target does not have yet.
+    pub new_files: Vec<PathBuf>,
+    /// Restored files already in the target, replaced unless `--overwrite`
+    /// says otherwise.
+    pub existing_files: Vec<PathBuf>,
+}
+
+/// How fit one of a source's backup dirs is to restore from.
+#[derive(Debug)]
+pub struct BackupDirHealth {
+    pub backup_dir: PathBuf,
+    /// Timestamp of its newest point.
+    pub latest_point: Option<String>,
+    /// Why it cannot be restored from; `None` when it can.
+    pub problem: Option<String>,
+}
+
+/// Restore behaviour beyond the restore point itself.
+#[derive(Debug, Default, Clone)]
+pub struct RestoreOptions {
+    /// Cap restore write throughput (MB/s) so a large restore does not
+    /// starve live traffic on the target file server.
+    pub throttle_mb_per_sec: Option<f64>,
+    /// How long to wait for a running backup to release the destination
+    /// before giving up. `None` waits until it is done.
+    pub lock_timeout_secs: Option<u64>,
+    /// Leave restored files owned by the restoring user instead of the
+    /// recorded uid/gid; needed when not running as root.
+    pub skip_ownership: bool,
+    /// Re-read every file after it is written and compare it with the
+    /// backup: copies by hash, deltas block by block.
+    pub verify_writes: bool,
+    /// Refuse to restore unless metadata.json and every applied point match
+    /// their signatures.
+    pub signing_key: Option<SigningKey>,
+    /// Whether files already in the target are replaced.
+    pub overwrite: OverwritePolicy,
+    /// Move files about to be replaced to a directory next to the target
+    /// instead of discarding them.
+    pub backup_existing: bool,
+}
+
+/// Where a point sits in the chain a restore applies.
+struct ChainPosition<'a> {
+    index: usize,
+    /// The applied points, followed by the rest of their chain up to the
+    /// next full: reverse deltas are rebuilt from points past the restore
+    /// point too.
+    chain: &'a [&'a BackupEntry],
+    /// Every restored file with the index of the point holding the version
+    /// the target ends up with.
+    final_points: &'a BTreeMap<PathBuf, usize>,
+}
+
+impl<'a> ChainPosition<'a> {
+    /// Points applied before this one, where forward delta bases are
+    /// looked up.
+    fn earlier(&self) -> &'a [&'a BackupEntry] {
+        &self.chain[..self.index]
+    }
+
+    /// Newer points of the chain, nearest first.
+    fn later(&self) -> &'a [&'a BackupEntry] {
+        &self.chain[self.index + 1..]
+    }
+
+    /// Whether a later applied point stores `relative` again, so this
+    /// point's version never reaches the target.
+    fn superseded(&self, relative: &Path) -> bool {
+        self.final_points
+            .get(relative)
+            .is_some_and(|&point| point > self.index)
+    }
+}
+
+pub struct RestoreManager;
+
+impl RestoreManager {
+    pub fn list_backups(backup_dir: &Path) -> Result<Vec<BackupEntry>> {
+        let mut entries: Vec<BackupEntry> = fs::read_dir(backup_dir)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let path = entry.path();
+                let name = archive::backup_point_name(&path)?;
+                let parsed = point_name::parse(&name)?;
+                let (is_full, timestamp) = (parsed.is_full, parsed.timestamp.to_string());
+
+                Some(BackupEntry {
+                    name,
+                    path,
+                    is_full,
+                    timestamp,
+                })
+            })
+            .collect();
+
+        entries.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        Ok(entries)
+    }
+
+    pub fn restore_to_point(
+        backup_dir: &Path,
+        target_dir: &Path,
+        restore_point: Option<&str>,
+        options: &RestoreOptions,
+    ) -> Result<usize> {
+        let backup_dir = &longpath::extended(backup_dir);
+        let target_dir = &longpath::extended(target_dir);
+        let mut throttle = options
+            .throttle_mb_per_sec
+            .map(Throttle::from_mb_per_sec)
+            .transpose()?;
+        if Self::list_backups(backup_dir)?.is_empty() {
+            if let Some(mirror_root) = BackupManager::mirror_dir(backup_dir) {
+                return Err(anyhow::anyhow!(
+                    "{:?} is a mirror with no restore points; copy the files from {:?} directly",
+                    backup_dir,
+                    mirror_root
+                ));
+            }
+            return Err(Error::NoBackups(backup_dir.to_path_buf()).into());
+        }
+
+        // A running service may be writing the chain right now; wait for it,
+        // then list again so the restore sees the finished chain.
+        let _lock = BackupDirLock::acquire_shared(
+            backup_dir,
+            "restore",
+            options.lock_timeout_secs.map(Duration::from_secs),
+        )?;
+        repo::check_readable(backup_dir)?;
+        let backups = Self::list_backups(backup_dir)?;
+
+        // Determine which backups to apply
+        let backups_to_apply = Self::select_backups(&backups, restore_point)?;
+        if let Some(key) = &options.signing_key {
+            Self::verify_signatures(backup_dir, &backups_to_apply, key)?;
+        }
+
+        let keep = Self::resolve_existing_files(&backups_to_apply, target_dir, options)?;
+        let chain = Self::extend_to_chain_end(&backups, &backups_to_apply);
+        let final_points = Self::restored_files(&backups_to_apply)?;
+
+        fs::create_dir_all(target_dir)
+            .with_context(|| format!("Failed to create restore directory: {:?}", target_dir))?;
+
+        let mut total_files_restored = 0;
+        let total_backups = backups_to_apply.len();
+
+        for (i, backup) in backups_to_apply.iter().enumerate() {
+            let position = ChainPosition {
+                index: i,
+                chain: &chain,
+                final_points: &final_points,
+            };
+            let files_restored =
+                Self::apply_backup(backup, &position, target_dir, &keep, throttle.as_mut())?;
+            if options.verify_writes {
+                Self::verify_backup(backup, &position, target_dir, &keep)?;
+            }
+            Self::apply_attributes(backup, target_dir, &keep, !options.skip_ownership)?;
+            total_files_restored += files_restored;
+            let progress = ((i + 1) * 100) / total_backups;
+            info!(
+                "Restore progress: {}% - Applied backup '{}': {} files restored",
+                progress, backup.name, files_restored
+            );
+        }
+
+        info!(
+            "Restore completed: {} total files restored to {:?}",
+            total_files_restored, target_dir
+        );
+
+        Ok(total_files_restored)
+    }
+
+    /// Check each of `backup_dirs` (reachable, has points, delta chain
+    /// loads, metadata signature holds when `signing_key` is set) and
+    /// return them best first: healthy before broken, then newest latest
+    /// point first.
+    pub fn rank_backup_dirs(
+        backup_dirs: &[PathBuf],
+        signing_key: Option<&SigningKey>,
+    ) -> Vec<BackupDirHealth> {
+        let mut ranked: Vec<BackupDirHealth> = backup_dirs
+            .iter()
+            .map(|backup_dir| {
+                let extended = longpath::extended(backup_dir);
+                let backups = match Self::list_backups(&extended) {
+                    Ok(backups) => backups,
+                    Err(e) => {
+                        return BackupDirHealth {
+                            backup_dir: backup_dir.clone(),
+                            latest_point: None,
+                            problem: Some(format!("unreachable: {}", e)),
+                        };
+                    }
+                };
+                let latest_point = backups.last().map(|backup| backup.timestamp.clone());
+                let problem = if backups.is_empty() {
+                    Some("no backups".to_string())
+                } else if !BackupManager::validate_delta_chain(&extended) {
+                    Some("broken backup chain".to_string())
+                } else {
+                    signing_key.and_then(|key| {
+                        BackupManager::verify_metadata_signature(&extended, key)
+                            .err()
+                            .map(|e| format!("signature check failed: {:#}", e))
+                    })
+                };
+                BackupDirHealth {
+                    backup_dir: backup_dir.clone(),
+                    latest_point,
+                    problem,
+                }
+            })
+            .collect();
+        ranked.sort_by_key(|health| {
+            (
+                health.problem.is_some(),
+                std::cmp::Reverse(health.latest_point.clone()),
+            )
+        });
+        ranked
+    }
+
+    /// Which points restoring `restore_point` into `target_dir` applies and
+    /// which of the files it writes are new there.
+    pub fn preview_restore(
+        backup_dir: &Path,
+        target_dir: &Path,
+        restore_point: Option<&str>,
+    ) -> Result<RestorePreview> {
+        let backup_dir = &longpath::extended(backup_dir);
+        let target_dir = &longpath::extended(target_dir);
+        let backups = Self::list_backups(backup_dir)?;
+        let backups_to_apply = Self::select_backups(&backups, restore_point)?;
+        let mut preview = RestorePreview {
+            points: backups_to_apply.iter().map(|b| b.name.clone()).collect(),
+            ..Default::default()
+        };
+        for relative in Self::restored_files(&backups_to_apply)?.into_keys() {
+            if fs::symlink_metadata(target_dir.join(&relative)).is_ok() {
+                preview.existing_files.push(relative);
+            } else {
+                preview.new_files.push(relative);
+            }
+        }
+        Ok(preview)
+    }
+
+    /// Checked before anything is written, so a tampered chain never reaches
+    /// the target.
+    pub(crate) fn verify_signatures(
+        backup_dir: &Path,
+        backups: &[&BackupEntry],
+        key: &SigningKey,
+    ) -> Result<()> {
+        BackupManager::verify_metadata_signature(backup_dir, key)?;
+        let metadata = BackupManager::load_source_metadata(&backup_dir.join("metadata.json"));
+        for backup in backups {
+            let entry = metadata
+                .backup_history
+                .iter()
+                .find(|entry| entry.backup_name == backup.name)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Backup {} is not recorded in the signed metadata.json",
+                        backup.name
+                    )
+                })?;
+            BackupManager::verify_point_signature(entry, &backup.path, key)?;
+        }
+        info!("Signatures verified for {} backup(s)", backups.len());
+        Ok(())
+    }
+
+    /// Decide, before anything is written, what happens to the files in
+    /// `target_dir` the chain would replace, and return those to leave
+    /// alone. With `backup_existing` the others are moved aside first.
+    fn resolve_existing_files(
+        backups: &[&BackupEntry],
+        target_dir: &Path,
+        options: &RestoreOptions,
+    ) -> Result<HashSet<PathBuf>> {
+        let mut keep = HashSet::new();
+        if !target_dir.is_dir() {
+            return Ok(keep);
+        }
+        let mut replaced = Vec::new();
+        let manifests = match options.overwrite {
+            OverwritePolicy::IfOlder => backups
+                .iter()
+                .map(|backup| Self::read_point_manifest(backup))
+                .collect::<Result<Vec<_>>>()?,
+            _ => Vec::new(),
+        };
+        let mut replace_all = false;
+        for (relative, point) in Self::restored_files(backups)? {
+            let existing = target_dir.join(&relative);
+            let Ok(metadata) = fs::symlink_metadata(&existing) else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+            let overwrite = match options.overwrite {
+                OverwritePolicy::Always => true,
+                OverwritePolicy::Never => false,
+                OverwritePolicy::IfOlder => {
+                    // The recorded mtime, else when the point was taken.
+                    let backed_up = manifests[point]
+                        .get(&attrs::manifest_key(&relative))
+                        .and_then(|attributes| attributes.modified)
+                        .or_else(|| BackupManager::parse_backup_created_at(&backups[point].name));
+                    let existing_modified: Option<DateTime<Utc>> =
+                        metadata.modified().ok().map(Into::into);
+                    match (existing_modified, backed_up) {
+                        (Some(existing), Some(backed_up)) => existing < backed_up,
+                        _ => true,
+                    }
+                }
+                OverwritePolicy::Prompt if replace_all => true,
+                OverwritePolicy::Prompt => match Self::ask_overwrite(&relative)?.as_str() {
+                    "y" | "yes" => true,
+                    "a" | "all" => {
+                        replace_all = true;
+                        true
+                    }
+                    "q" | "quit" => anyhow::bail!("Restore cancelled; nothing was written"),
+                    _ => false,
+                },
+            };
+            if overwrite {
+                replaced.push(relative);
+            } else {
+                debug!("Keeping existing {:?}", existing);
+                keep.insert(relative);
+            }
+        }
+        if !keep.is_empty() {
+            info!(
+                "Keeping {} existing file(s) in {:?}",
+                keep.len(),
+                target_dir
+            );
+        }
+        if options.backup_existing && !replaced.is_empty() {
+            let side_dir = Self::replaced_files_dir(target_dir);
+            for relative in &replaced {
+                Self::move_file(&target_dir.join(relative), &side_dir.join(relative))?;
+            }
+            info!(
+                "Moved {} file(s) about to be replaced to {:?}",
+                replaced.len(),
+                side_dir
+            );
+        }
+        Ok(keep)
+    }
+
+    /// Every file the chain restores, with the index of the last point that
+    /// stores it (the version the target ends up with).
+    fn restored_files(backups: &[&BackupEntry]) -> Result<BTreeMap<PathBuf, usize>> {
+        let mut files = BTreeMap::new();
+        for (point, backup) in backups.iter().enumerate() {
+            let relatives: Vec<PathBuf> = if backup.path.is_file() {
+                ArchiveReader::open_with_env(&backup.path)?
+                    .entries()
+                    .iter()
+                    .map(|entry| entry.relative_path())
+                    .collect()
+            } else if let Some(index) = dedup::read_index(&backup.path)? {
+                index.files.keys().map(|key| attrs::key_path(key)).collect()
+            } else {
+                let mut relatives = Vec::new();
+                for entry in walkdir::WalkDir::new(&backup.path) {
+                    let entry = entry?;
+                    if entry.file_type().is_file() {
+                        relatives.push(entry.path().strip_prefix(&backup.path)?.to_path_buf());
+                    }
+                }
+                relatives
+            };
+            for relative in relatives {
+                if !attrs::is_attributes_file(&relative) {
+                    files.insert(Self::strip_delta_extension(&relative), point);
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    /// Ask whether to replace `relative`; the lowercased answer.
+    fn ask_overwrite(relative: &Path) -> Result<String> {
+        if !std::io::stdin().is_terminal() {
+            return Err(anyhow::anyhow!(
+                "stdin is not a terminal; use --overwrite always, never or if-older"
+            ));
+        }
+        print!(
+            "{} already exists. Overwrite? [y]es/[N]o/[a]ll/[q]uit ",
+            relative.display()
+        );
+        std::io::stdout().flush()?;
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        Ok(input.trim().to_lowercase())
+    }
+
+    /// `<target>.replaced_<time>` next to the target.
+    fn replaced_files_dir(target_dir: &Path) -> PathBuf {
+        let stamp = Local::now().format("%Y%m%d_%H%M%S");
+        match target_dir.file_name() {
+            Some(name) => {
+                target_dir.with_file_name(format!("{}.replaced_{}", name.to_string_lossy(), stamp))
+            }
+            None => target_dir.join(format!(".replaced_{}", stamp)),
+        }
+    }
+
+    fn move_file(from: &Path, to: &Path) -> Result<()> {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if fs::rename(from, to).is_err() {
+            // Across filesystems: copy, then remove the original.
+            fs::copy(from, to).with_context(|| format!("Failed to move {:?} to {:?}", from, to))?;
+            fs::remove_file(from).with_context(|| format!("Failed to remove {:?}", from))?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn select_backups<'a>(
+        backups: &'a [BackupEntry],
+        restore_point: Option<&str>,
+    ) -> Result<Vec<&'a BackupEntry>> {
+        // Find the latest full backup before the restore point
+        let cutoff = restore_point.unwrap_or("99999999_999999");
+
+        let latest_full = backups
+            .iter()
+            .rfind(|b| b.is_full && b.timestamp.as_str() <= cutoff);
+
+        let full_backup = match latest_full {
+            Some(b) => b,
+            None => return Err(anyhow::anyhow!("No full backup found before restore point")),
+        };
+
+        let mut result = vec![full_backup];
+
+        // Add incremental backups after the full backup and before the restore point
+        for backup in backups {
+            if !backup.is_full
+                && backup.timestamp.as_str() > full_backup.timestamp.as_str()
+                && backup.timestamp.as_str() <= cutoff
+            {
+                result.push(backup);
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// `selected` (as returned by `select_backups`) followed by the
+    /// incrementals after it up to the next full.
+    pub(crate) fn extend_to_chain_end<'a>(
+        backups: &'a [BackupEntry],
+        selected: &[&'a BackupEntry],
+    ) -> Vec<&'a BackupEntry> {
+        let mut chain = selected.to_vec();
+        if let Some(last) = selected.last() {
+            chain.extend(
+                backups
+                    .iter()
+                    .filter(|backup| backup.timestamp > last.timestamp)
+                    .take_while(|backup| !backup.is_full),
+            );
+        }
+        chain
+    }
+
+    /// Restore the files `backup` stores.
+    fn apply_backup(
+        backup: &BackupEntry,
+        position: &ChainPosition,
+        target_dir: &Path,
+        keep: &HashSet<PathBuf>,
+        mut throttle: Option<&mut Throttle>,
+    ) -> Result<usize> {
+        if backup.path.is_file() {
+            return Self::apply_archive(
+                &backup.path,
+                position.earlier(),
+                target_dir,
+                keep,
+                throttle,
+            );
+        }
+        if let Some(index) = dedup::read_index(&backup.path)? {
+            return Self::apply_dedup(&backup.path, &index, target_dir, keep, throttle);
+        }
+
+        // Measure the point first for progress tracking
+        let (total_files, total_bytes) = Self::measure_files(&backup.path)?;
+        let mut files_restored = 0;
+        let mut progress = Progress::new(
+            "Restore file progress",
+            &backup.path,
+            total_files,
+            total_bytes,
+        );
+
+        Self::restore_dir_point(
+            &backup.path,
+            target_dir,
+            position,
+            &mut files_restored,
+            &mut progress,
+            &mut throttle,
+            keep,
+        )?;
+
+        Ok(files_restored)
+    }
+
+    /// Check every file `backup` just wrote to `target_dir`. Runs before the
+    /// attributes are reapplied, while the files are still writable. Kept
+    /// files and superseded reverse deltas were never written.
+    fn verify_backup(
+        backup: &BackupEntry,
+        position: &ChainPosition,
+        target_dir: &Path,
+        keep: &HashSet<PathBuf>,
+    ) -> Result<()> {
+        let kept = |relative: &Path| keep.contains(&Self::strip_delta_extension(relative));
+        if backup.path.is_file() {
+            let mut reader = ArchiveReader::open_with_env(&backup.path)?;
+            let entries = reader.entries().to_vec();
+            for entry in entries.iter().filter(|entry| {
+                !attrs::is_attributes_file(&entry.relative_path()) && !kept(&entry.relative_path())
+            }) {
+                let relative_path = entry.relative_path();
+                if entry.path.ends_with(".delta") {
+                    let delta_data = delta::parse_delta(&reader.read_entry(entry)?)
+                        .with_context(|| format!("Failed to load delta {}", entry.path))?;
+                    let target_file = target_dir.join(Self::strip_delta_extension(&relative_path));
+                    delta::verify_applied(&delta_data, &target_file)?;
+                } else {
+                    Self::verify_copy(&target_dir.join(&relative_path), &entry.sha256)?;
+                }
+            }
+            return Ok(());
+        }
+        if let Some(index) = dedup::read_index(&backup.path)? {
+            for (relative, entry) in &index.files {
+                if kept(&attrs::key_path(relative)) {
+                    continue;
+                }
+                let target_file = target_dir.join(attrs::key_path(relative));
+                if !ChunkStore::matches(entry, &target_file)? {
+                    return Err(anyhow::anyhow!(
+                        "Verification failed for {:?}: content does not match the backup",
+                        target_file
+                    ));
+                }
+            }
+            return Ok(());
+        }
+
+        for entry in walkdir::WalkDir::new(&backup.path) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let relative_path = entry.path().strip_prefix(&backup.path)?;
+            if attrs::is_attributes_file(relative_path) || kept(relative_path) {
+                continue;
+            }
+            let name = relative_path.to_string_lossy();
+            if delta::is_reverse_delta_name(&name) {
+                let original_rel = Self::strip_delta_extension(relative_path);
+                if !position.superseded(&original_rel) {
+                    let delta_data = delta::load_delta(entry.path())?;
+                    delta::verify_applied(&delta_data, &target_dir.join(original_rel))?;
+                }
+            } else if name.ends_with(".delta") {
+                let delta_data = delta::load_delta(entry.path())?;
+                let target_file = target_dir.join(Self::strip_delta_extension(relative_path));
+                delta::verify_applied(&delta_data, &target_file)?;
+            } else {
+                let expected = BackupManager::calculate_file_hash(entry.path())?;
+                Self::verify_copy(&target_dir.join(relative_path), &expected)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_copy(target_file: &Path, expected_hash: &str) -> Result<()> {
+        let actual = BackupManager::calculate_file_hash(target_file)
+            .with_context(|| format!("Failed to re-read {:?} for verification", target_file))?;
+        if actual != expected_hash {
+            return Err(anyhow::anyhow!(
+                "Verification failed for {:?}: content does not match the backup",
+                target_file
+            ));
+        }
+        Ok(())
+    }
+
+    /// Number and total size of the files stored in `dir`.
+    fn measure_files(dir: &Path) -> Result<(usize, u64)> {
+        let mut count = 0;
+        let mut bytes = 0;
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.is_dir() {
+                let (dir_count, dir_bytes) = Self::measure_files(&path)?;
+                count += dir_count;
+                bytes += dir_bytes;
+            } else if entry.file_name() != attrs::ATTRIBUTES_FILE_NAME {
+                count += 1;
+                bytes += entry.metadata()?.len();
+            }
+        }
+        Ok((count, bytes))
+    }
+
+    /// Recreate the hard links and reapply the mode, ownership and mtime
+    /// recorded for the files this point stored. Files a later point
+    /// overwrites get its attributes then; kept files are left as they are.
+    fn apply_attributes(
+        backup: &BackupEntry,
+        target_dir: &Path,
+        keep: &HashSet<PathBuf>,
+        restore_ownership: bool,
+    ) -> Result<()> {
+        let manifest: AttributeManifest = Self::read_point_manifest(backup)?
+            .into_iter()
+            .filter(|(relative, _)| !keep.contains(&attrs::key_path(relative)))
+            .collect();
+
+        for (relative, attributes) in &manifest {
+            let Some(ref primary) = attributes.hard_link_to else {
+                continue;
+            };
+            let primary_file = target_dir.join(attrs::key_path(primary));
+            let link_file = target_dir.join(attrs::key_path(relative));
+            if !primary_file.is_file() {
+                warn!(
+                    "Cannot recreate hard link {:?}: {:?} was not restored",
+                    link_file, primary_file
+                );
+                continue;
+            }
+            Self::remove_existing_target(&link_file)?;
+            fs::hard_link(&primary_file, &link_file)
+                .with_context(|| format!("Failed to link {:?} to {:?}", link_file, primary_file))?;
+        }
+
+        for (relative, attributes) in &manifest {
+            let target_file = target_dir.join(attrs::key_path(relative));
+            if target_file.is_file() {
+                attributes.apply(&target_file, restore_ownership)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The attributes recorded for the files `backup` stored; empty for
+    /// points without a manifest.
+    pub(crate) fn read_point_manifest(backup: &BackupEntry) -> Result<AttributeManifest> {
+        if backup.path.is_file() {
+            let mut reader = ArchiveReader::open_with_env(&backup.path)?;
+            return match reader.find(Path::new(attrs::ATTRIBUTES_FILE_NAME)).cloned() {
+                Some(entry) => attrs::parse_manifest(&reader.read_entry(&entry)?),
+                None => Ok(AttributeManifest::new()),
+            };
+        }
+        attrs::read_manifest(&backup.path)
+    }
+
+    /// Replace rather than overwrite: the target may be read-only or a hard
+    /// link whose other names must keep their content.
+    fn remove_existing_target(target_file: &Path) -> Result<()> {
+        if target_file.is_file() {
+            fs::remove_file(target_file)
+                .with_context(|| format!("Failed to replace {:?}", target_file))?;
+        }
+        Ok(())
+    }
+
+    fn restore_dir_point(
+        backup_path: &Path,
+        target_dir: &Path,
+        position: &ChainPosition,
+        files_restored: &mut usize,
+        progress: &mut Progress,
+        throttle: &mut Option<&mut Throttle>,
+        keep: &HashSet<PathBuf>,
+    ) -> Result<()> {
+        for entry in walkdir::WalkDir::new(backup_path).min_depth(1) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let path = entry.path();
+            let relative_path = path.strip_prefix(backup_path)?;
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+
+            if attrs::is_attributes_file(relative_path) {
+                continue;
+            }
+            if keep.contains(&Self::strip_delta_extension(relative_path)) {
+                progress.finish_file(entry.metadata()?.len());
+                continue;
+            }
+            if delta::is_reverse_delta_name(&file_name) {
+                let original_rel = Self::strip_delta_extension(relative_path);
+                if position.superseded(&original_rel) {
+                    // A later point of this restore writes the file again.
+                    progress.finish_file(entry.metadata()?.len());
+                    continue;
+                }
+                progress.start_file(relative_path);
+                debug!("Rebuilding {:?} from newer points", path);
+                let target_file = target_dir.join(&original_rel);
+                let delta_data = delta::load_delta(path)?;
+                let mut file_progress =
+                    progress.large_file(&original_rel, delta_data.new_file_size);
+                Self::rebuild_reverse_delta(
+                    delta_data,
+                    &original_rel,
+                    position.later(),
+                    &target_file,
+                    file_progress.as_mut(),
+                )
+                .with_context(|| format!("Failed to rebuild {:?}", path))?;
+                if let Some(throttle) = throttle.as_deref_mut() {
+                    throttle.consume(fs::metadata(&target_file)?.len());
+                }
+                *files_restored += 1;
+                progress.finish_file(entry.metadata()?.len());
+                continue;
+            }
+            progress.start_file(relative_path);
+            debug!("Restoring {:?}", path);
+            if file_name.ends_with(".delta") {
+                // Delta file: apply delta to restore
+                // Remove .delta extension to get the original relative path
+                let original_rel = Self::strip_delta_extension(relative_path);
+                let target_file = target_dir.join(&original_rel);
+
+                let delta_data = delta::load_delta(path)?;
+                let mut file_progress =
+                    progress.large_file(&original_rel, delta_data.new_file_size);
+                Self::apply_delta_to_target(
+                    &delta_data,
+                    &original_rel,
+                    &target_file,
+                    position.earlier(),
+                    file_progress.as_mut(),
+                )
+                .with_context(|| format!("Failed to apply delta {:?}", path))?;
+                if let Some(throttle) = throttle.as_deref_mut() {
+                    throttle.consume(fs::metadata(&target_file)?.len());
+                }
+
+                *files_restored += 1;
+            } else {
+                // Regular file: copy directly
+                let target_file = target_dir.join(relative_path);
+
+                if let Some(parent) = target_file.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                Self::remove_existing_target(&target_file)?;
+
+                let mut file_progress = progress.large_file(relative_path, entry.metadata()?.len());
+                throttle::copy_file(
+                    path,
+                    &target_file,
+                    throttle.as_deref_mut(),
+                    file_progress.as_mut(),
+                )?;
+                *files_restored += 1;
+            }
+            progress.finish_file(entry.metadata()?.len());
+        }
+
+        Ok(())
+    }
+
+    /// Rebuild `target_file` from `delta_data`. A delta records the hash of
+    /// the file it was made against, which is not always the version the
+    /// target holds by now (it may be missing, or an earlier delta of the
+    /// chain may already have changed it), so the base is checked first.
+    fn apply_delta_to_target(
+        delta_data: &DeltaFile,
+        relative: &Path,
+        target_file: &Path,
+        earlier: &[&BackupEntry],
+        progress: Option<&mut FileProgress>,
+    ) -> Result<()> {
+        let scratch = target_file.with_extension("tmp_base");
+        let base = Self::find_delta_base(delta_data, relative, target_file, earlier, &scratch);
+        let temp_file = target_file.with_extension("tmp_restore");
+        let applied = base.and_then(|base| match base {
+            Some(base) => delta::apply_delta(&base, delta_data, &temp_file, progress),
+            // Made against an empty file; `scratch` was never written, so
+            // there is nothing to read.
+            None => delta::apply_delta(&scratch, delta_data, &temp_file, progress),
+        });
+        let _ = fs::remove_file(&scratch);
+        if let Err(e) = applied {
+            let _ = fs::remove_file(&temp_file);
+            return Err(e);
+        }
+        fs::rename(&temp_file, target_file)?;
+        Ok(())
+    }
+
+    /// Rebuild into `target_file` the older version of `relative` that a
+    /// reverse delta stands for. Reverse deltas are made against the next
+    /// newer version, so the `later` points are searched for the plain copy
+    /// the run of reverse deltas ends at, which is then patched back newest
+    /// first. Each step's base is checked against the hash its delta
+    /// records.
+    pub(crate) fn rebuild_reverse_delta(
+        delta_data: DeltaFile,
+        relative: &Path,
+        later: &[&BackupEntry],
+        target_file: &Path,
+        mut progress: Option<&mut FileProgress>,
+    ) -> Result<()> {
+        let reverse_name = delta::delta_path(relative, true);
+        let mut deltas = vec![delta_data];
+        let mut newest = None;
+        for point in later {
+            // Reverse layouts only write directory points.
+            if point.path.is_file() {
+                break;
+            }
+            let plain = point.path.join(relative);
+            if plain.is_file() {
+                newest = Some(plain);
+                break;
+            }
+            let reverse = point.path.join(&reverse_name);
+            if reverse.is_file() {
+                deltas.push(
+                    delta::load_delta(&reverse)
+                        .with_context(|| format!("Failed to load delta {:?}", reverse))?,
+                );
+            }
+        }
+        let newest = newest.ok_or_else(|| {
+            anyhow::anyhow!(
+                "No newer plain copy of {:?} in the chain to rebuild its reverse delta from",
+                relative
+            )
+        })?;
+
+        let scratch = [
+            target_file.with_extension("tmp_base"),
+            target_file.with_extension("tmp_restore"),
+        ];
+        let rebuilt = deltas
+            .iter()
+            .rev()
+            .enumerate()
+            .try_fold(newest, |current, (step, delta_data)| {
+                let base_hash = BackupManager::calculate_file_hash(&current)?;
+                if base_hash != delta_data.original_file_hash {
+                    return Err(anyhow::anyhow!(
+                        "Reverse delta of {:?} was made against sha256 {}, but the newer \
+                         version in the chain has {}",
+                        relative,
+                        delta_data.original_file_hash,
+                        base_hash
+                    ));
+                }
+                let output = scratch[step % 2].clone();
+                let last = step + 1 == deltas.len();
+                delta::apply_delta(
+                    &current,
+                    delta_data,
+                    &output,
+                    if last { progress.as_deref_mut() } else { None },
+                )?;
+                Ok(output)
+            })
+            .and_then(|rebuilt| {
+                if let Some(parent) = target_file.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                Self::remove_existing_target(target_file)?;
+                Ok(fs::rename(&rebuilt, target_file)?)
+            });
+        for scratch in &scratch {
+            let _ = fs::remove_file(scratch);
+        }
+        rebuilt
+    }
+
+    /// The file `delta_data` was made against: the target when it still has
+    /// that content, else the newest plain copy in an earlier point of the
+    /// chain (archive entries are extracted to `scratch`). `None` for a
+    /// delta made against an empty file. Fails rather than guess.
+    fn find_delta_base(
+        delta_data: &DeltaFile,
+        relative: &Path,
+        target_file: &Path,
+        earlier: &[&BackupEntry],
+        scratch: &Path,
+    ) -> Result<Option<PathBuf>> {
+        let expected = delta_data.original_file_hash.as_str();
+        let matches = |path: &Path| {
+            BackupManager::calculate_file_hash(path).is_ok_and(|hash| hash == expected)
+        };
+        if target_file.is_file() && matches(target_file) {
+            return Ok(Some(target_file.to_path_buf()));
+        }
+        if expected == delta::EMPTY_FILE_HASH {
+            return Ok(None);
+        }
+        for point in earlier.iter().rev() {
+            if point.path.is_file() {
+                let mut reader = ArchiveReader::open_with_env(&point.path)?;
+                let Some(entry) = reader.find(relative).cloned() else {
+                    continue;
+                };
+                reader.extract_file(&entry, scratch, None)?;
+                if matches(scratch) {
+                    return Ok(Some(scratch.to_path_buf()));
+                }
+                continue;
+            }
+            let candidate = point.path.join(relative);
+            if candidate.is_file() && matches(&candidate) {
+                debug!("Delta base for {:?} found in {}", relative, point.name);
+                return Ok(Some(candidate));
+            }
+        }
+        Err(anyhow::anyhow!(
+            "No base for the delta of {:?}: neither the target nor an earlier point of the \
+             chain holds the version it was made against (sha256 {})",
+            relative,
+            expected
+        ))
+    }
+
+    /// Restore every entry of an archive backup point. Entries are read
+    /// straight from the archive, which verifies each one's checksum.
+    fn apply_archive(
+        archive_path: &Path,
+        earlier: &[&BackupEntry],
+        target_dir: &Path,
+        keep: &HashSet<PathBuf>,
+        mut throttle: Option<&mut Throttle>,
+    ) -> Result<usize> {
+        let mut reader = ArchiveReader::open_with_env(archive_path)?;
+        let entries: Vec<_> = reader
+            .entries()
+            .iter()
+            .filter(|entry| !attrs::is_attributes_file(&entry.relative_path()))
+            .filter(|entry| !keep.contains(&Self::strip_delta_extension(&entry.relative_path())))
+            .cloned()
+            .collect();
+        let total_files = entries.len();
+        let total_bytes = entries.iter().map(|entry| entry.size).sum();
+        let mut progress = Progress::new(
+            "Restore file progress",
+            archive_path,
+            total_files,
+            total_bytes,
+        );
+
+        for entry in &entries {
+            let relative_path = entry.relative_path();
+            progress.start_file(&relative_path);
+            debug!("Restoring {} from {:?}", entry.path, archive_path);
+            if entry.path.ends_with(".delta") {
+                let original_rel = Self::strip_delta_extension(&relative_path);
+                let target_file = target_dir.join(&original_rel);
+                let delta_data = delta::parse_delta(&reader.read_entry(entry)?)
+                    .with_context(|| format!("Failed to load delta {}", entry.path))?;
+                let mut file_progress =
+                    progress.large_file(&relative_path, delta_data.new_file_size);
+                Self::apply_delta_to_target(
+                    &delta_data,
+                    &original_rel,
+                    &target_file,
+                    earlier,
+                    file_progress.as_mut(),
+                )
+                .with_context(|| format!("Failed to apply delta {}", entry.path))?;
+            } else {
+                let target_file = target_dir.join(&relative_path);
+                Self::remove_existing_target(&target_file)?;
+                let mut file_progress = progress.large_file(&relative_path, entry.size);
+                reader.extract_file(entry, &target_file, file_progress.as_mut())?;
+            }
+            if let Some(throttle) = throttle.as_deref_mut() {
+                throttle.consume(entry.size);
+            }
+            progress.finish_file(entry.size);
+        }
+
+        Ok(total_files)
+    }
+
+    /// Reassemble every file a dedup point's index lists from the chunk
+    /// store. Each chunk is checked against its hash while reading.
+    fn apply_dedup(
+        point_path: &Path,
+        index: &DedupIndex,
+        target_dir: &Path,
+        keep: &HashSet<PathBuf>,
+        mut throttle: Option<&mut Throttle>,
+    ) -> Result<usize> {
+        let backup_dir = point_path.parent().unwrap_or(point_path);
+        let store = ChunkStore::open(&index.store_root(backup_dir));
+        let files: Vec<_> = index
+            .files
+            .iter()
+            .filter(|(relative, _)| !keep.contains(&attrs::key_path(relative)))
+            .collect();
+        let total_files = files.len();
+        let total_bytes = files.iter().map(|(_, entry)| entry.size).sum();
+        let mut progress = Progress::new(
+            "Restore file progress",
+            point_path,
+            total_files,
+            total_bytes,
+        );
+
+        for (relative, entry) in files {
+            progress.start_file(Path::new(relative));
+            debug!("Restoring {} from the chunk store", relative);
+            let target_file = target_dir.join(attrs::key_path(relative));
+            if let Some(parent) = target_file.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            Self::remove_existing_target(&target_file)?;
+            let mut file_progress = progress.large_file(Path::new(relative), entry.size);
+            store
+                .restore_file(entry, &target_file, file_progress.as_mut())
+                .with_context(|| format!("Failed to restore {}", relative))?;
+            if let Some(throttle) = throttle.as_deref_mut() {
+                throttle.consume(entry.size);
+            }
+            progress.finish_file(entry.size);
+        }
+
+        Ok(total_files)
+    }
+
+    pub(crate) fn strip_delta_extension(path: &Path) -> PathBuf {
+        let path_str = path.to_string_lossy();
+        // e.g. "file.bin.delta" -> "file.bin"
+        // e.g. "file.txt.delta" -> "file.txt"
+        // e.g. "Makefile..delta" -> "Makefile" (backups name the delta of an
+        // extensionless file with an empty extension before ".delta")
+        // e.g. "foo...delta" -> "foo." (only that one added dot is removed)
+        // e.g. "file.txt.rdelta" -> "file.txt" (reverse delta)
+        if let Some(stripped) = path_str
+            .strip_suffix(".delta")
+            .or_else(|| path_str.strip_suffix(".rdelta"))
+        {
+            PathBuf::from(stripped.strip_suffix('.').unwrap_or(stripped))
+        } else {
+            path.to_path_buf()
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/restore_tests.rs"]
+mod tests;
@@ -0,0 +1,102 @@
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Detached HMAC of `metadata.json`, written next to it on every save.
+pub const METADATA_SIGNATURE_FILE_NAME: &str = "metadata.json.sig";
+
+/// Shortest accepted key; shorter secrets are too easy to brute-force
+/// from a signature.
+const MIN_KEY_LEN: usize = 16;
+
+/// HMAC-SHA256 key for tamper-evident metadata. It is read from a file that
+/// must live outside every backup dir, so whoever can edit a backup cannot
+/// re-sign it.
+#[derive(Clone)]
+pub struct SigningKey(Vec<u8>);
+
+impl std::fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SigningKey(..)")
+    }
+}
+
+impl SigningKey {
+    pub fn load(path: &Path) -> Result<Self> {
+        let key =
+            fs::read(path).with_context(|| format!("Failed to read signing key {:?}", path))?;
+        Self::from_bytes(key).with_context(|| format!("Invalid signing key {:?}", path))
+    }
+
+    pub fn from_bytes(key: Vec<u8>) -> Result<Self> {
+        if key.len() < MIN_KEY_LEN {
+            return Err(anyhow::anyhow!(
+                "signing key must be at least {} bytes",
+                MIN_KEY_LEN
+            ));
+        }
+        Ok(Self(key))
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.0).expect("HMAC accepts keys of any length")
+    }
+
+    /// Hex-encoded HMAC of `data`.
+    pub fn sign(&self, data: &[u8]) -> String {
+        let mut mac = self.mac();
+        mac.update(data);
+        format!("{:x}", mac.finalize().into_bytes())
+    }
+
+    /// Constant-time check of a hex signature produced by `sign`.
+    pub fn verify(&self, data: &[u8], signature: &str) -> bool {
+        let Some(expected) = decode_hex(signature.trim()) else {
+            return false;
+        };
+        let mut mac = self.mac();
+        mac.update(data);
+        mac.verify_slice(&expected).is_ok()
+    }
+
+    /// Signature of one backup point: its name bound to the digest of its
+    /// content, so a point can be neither edited nor swapped for another.
+    pub fn sign_point(&self, backup_name: &str, content_checksum: &str) -> String {
+        self.sign(&point_message(backup_name, content_checksum))
+    }
+
+    pub fn verify_point(&self, backup_name: &str, content_checksum: &str, signature: &str) -> bool {
+        self.verify(&point_message(backup_name, content_checksum), signature)
+    }
+}
+
+fn point_message(backup_name: &str, content_checksum: &str) -> Vec<u8> {
+    [backup_name.as_bytes(), &[0], content_checksum.as_bytes()].concat()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
+
+pub fn metadata_signature_path(backup_dir: &Path) -> PathBuf {
+    backup_dir.join(METADATA_SIGNATURE_FILE_NAME)
+}
+
+/// Load the configured key, if any.
+pub fn load_optional(path: Option<&Path>) -> Result<Option<SigningKey>> {
+    path.map(SigningKey::load).transpose()
+}
+
+#[cfg(test)]
+#[path = "tests/signing_tests.rs"]
+mod tests;
@@ -0,0 +1,354 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::SnapshotConfig;
+
+const DEFAULT_LVM_SNAPSHOT_SIZE: &str = "1G";
+
+/// Snapshot of one source for the duration of a backup run. The backup
+/// reads from `source_path()`; dropping the value removes the snapshot.
+pub struct SourceSnapshot {
+    source_path: PathBuf,
+    teardown: Teardown,
+}
+
+enum Teardown {
+    Btrfs {
+        snapshot: PathBuf,
+    },
+    Lvm {
+        /// `vg/lv` of the snapshot volume.
+        volume: String,
+        mount_dir: Option<PathBuf>,
+    },
+    Vss {
+        shadow_id: String,
+    },
+}
+
+impl SourceSnapshot {
+    pub fn create(source_dir: &Path, config: &SnapshotConfig) -> Result<Self> {
+        validate(source_dir, config)?;
+        match config {
+            SnapshotConfig::Btrfs {
+                subvolume,
+                snapshot_dir,
+            } => {
+                let subvolume = subvolume.as_deref().unwrap_or(source_dir);
+                let snapshot_dir = snapshot_dir
+                    .as_deref()
+                    .or_else(|| subvolume.parent())
+                    .ok_or_else(|| anyhow::anyhow!("No snapshot_dir for {:?}", subvolume))?;
+                let snapshot = snapshot_dir.join(snapshot_name());
+                let source_path = map_into(source_dir, subvolume, &snapshot)?;
+                run(Command::new("btrfs")
+                    .args(["subvolume", "snapshot", "-r"])
+                    .arg(subvolume)
+                    .arg(&snapshot))?;
+                info!("Created btrfs snapshot {:?} of {:?}", snapshot, subvolume);
+                Ok(Self {
+                    source_path,
+                    teardown: Teardown::Btrfs { snapshot },
+                })
+            }
+            SnapshotConfig::Lvm {
+                volume,
+                mount_point,
+                size,
+            } => {
+                let (group, _) = volume
+                    .split_once('/')
+                    .ok_or_else(|| anyhow::anyhow!("LVM volume must be 'vg/lv': {}", volume))?;
+                let name = snapshot_name();
+                let mount_dir = std::env::temp_dir().join(&name);
+                let source_path = map_into(source_dir, mount_point, &mount_dir)?;
+                run(Command::new("lvcreate")
+                    .args(["--snapshot", "--name", &name, "--size"])
+                    .arg(size.as_deref().unwrap_or(DEFAULT_LVM_SNAPSHOT_SIZE))
+                    .arg(volume))?;
+                info!("Created LVM snapshot {}/{} of {}", group, name, volume);
+                // From here on dropping `snapshot` removes the volume.
+                let mut snapshot = Self {
+                    source_path,
+                    teardown: Teardown::Lvm {
+                        volume: format!("{}/{}", group, name),
+                        mount_dir: None,
+                    },
+                };
+                fs::create_dir_all(&mount_dir)
+                    .with_context(|| format!("Failed to create {:?}", mount_dir))?;
+                let device = format!("/dev/{}/{}", group, name);
+                // XFS refuses to mount a snapshot next to its origin unless
+                // the duplicate UUID is ignored.
+                run(Command::new("mount")
+                    .args(["-o", "ro", &device])
+                    .arg(&mount_dir))
+                .or_else(|_| {
+                    run(Command::new("mount")
+                        .args(["-o", "ro,nouuid", &device])
+                        .arg(&mount_dir))
+                })
+                .inspect_err(|_| {
+                    let _ = fs::remove_dir(&mount_dir);
+                })?;
+                if let Teardown::Lvm { mount_dir: dir, .. } = &mut snapshot.teardown {
+                    *dir = Some(mount_dir);
+                }
+                Ok(snapshot)
+            }
+            SnapshotConfig::Vss => {
+                let source = source_dir.to_string_lossy();
+                let (drive, _) = split_drive(&source).ok_or_else(|| {
+                    anyhow::anyhow!("VSS needs a source on a drive letter: {:?}", source_dir)
+                })?;
+                let script = format!(
+                    "$r = (Get-WmiObject -List Win32_ShadowCopy).Create('{}\\', 'ClientAccessible'); \
+                     if ($r.ReturnValue -ne 0) {{ exit $r.ReturnValue }}; \
+                     $s = Get-WmiObject Win32_ShadowCopy | Where-Object {{ $_.ID -eq $r.ShadowID }}; \
+                     Write-Output $s.ID; Write-Output $s.DeviceObject",
+                    drive
+                );
+                let output = run(Command::new("powershell").args([
+                    "-NoProfile",
+                    "-NonInteractive",
+                    "-Command",
+                    &script,
+                ]))?;
+                let (shadow_id, device) = parse_vss_output(&output)?;
+                info!("Created VSS shadow copy {} of {}", shadow_id, drive);
+                Ok(Self {
+                    source_path: vss_source_path(&device, &source)?,
+                    teardown: Teardown::Vss { shadow_id },
+                })
+            }
+        }
+    }
+
+    pub fn source_path(&self) -> &Path {
+        &self.source_path
+    }
+}
+
+impl Drop for SourceSnapshot {
+    fn drop(&mut self) {
+        if let Err(e) = self.teardown.run() {
+            warn!("Failed to remove source snapshot: {:#}", e);
+        }
+    }
+}
+
+impl Teardown {
+    fn run(&self) -> Result<()> {
+        match self {
+            Teardown::Btrfs { snapshot } => {
+                run(Command::new("btrfs")
+                    .args(["subvolume", "delete"])
+                    .arg(snapshot))?;
+                info!("Removed btrfs snapshot {:?}", snapshot);
+            }
+            Teardown::Lvm { volume, mount_dir } => {
+                if let Some(dir) = mount_dir {
+                    run(Command::new("umount").arg(dir))?;
+                    let _ = fs::remove_dir(dir);
+                }
+                run(Command::new("lvremove").args(["-f", volume]))?;
+                info!("Removed LVM snapshot {}", volume);
+            }
+            Teardown::Vss { shadow_id } => {
+                run(Command::new("vssadmin").args([
+                    "delete",
+                    "shadows",
+                    &format!("/Shadow={}", shadow_id),
+                    "/Quiet",
+                ]))?;
+                info!("Removed VSS shadow copy {}", shadow_id);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check a snapshot setting without touching the system.
+pub fn validate(source_dir: &Path, config: &SnapshotConfig) -> Result<()> {
+    match config {
+        SnapshotConfig::Btrfs {
+            subvolume,
+            snapshot_dir,
+        } => {
+            for path in subvolume.iter().chain(snapshot_dir) {
+                if !path.is_absolute() {
+                    return Err(anyhow::anyhow!(
+                        "snapshot paths must be absolute: {:?}",
+                        path
+                    ));
+                }
+            }
+            match subvolume {
+                Some(subvolume) => map_into(source_dir, subvolume, subvolume).map(drop),
+                None if source_dir.is_file() => Err(anyhow::anyhow!(
+                    "a btrfs snapshot of a file source needs its subvolume"
+                )),
+                None => Ok(()),
+            }
+        }
+        SnapshotConfig::Lvm {
+            volume,
+            mount_point,
+            ..
+        } => {
+            if volume.split('/').filter(|part| !part.is_empty()).count() != 2 {
+                return Err(anyhow::anyhow!("LVM volume must be 'vg/lv': {}", volume));
+            }
+            if !mount_point.is_absolute() {
+                return Err(anyhow::anyhow!(
+                    "snapshot mount_point must be absolute: {:?}",
+                    mount_point
+                ));
+            }
+            map_into(source_dir, mount_point, mount_point).map(drop)
+        }
+        SnapshotConfig::Vss => {
+            if !cfg!(windows) {
+                return Err(anyhow::anyhow!(
+                    "VSS snapshots are only available on Windows"
+                ));
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Parse the `config set-source <src> snapshot` value: `btrfs`,
+/// `btrfs:<subvolume>`, `lvm:<vg/lv>:<mount point>[:<size>]` or `vss`.
+pub fn parse_spec(spec: &str) -> Result<SnapshotConfig> {
+    let mut parts = spec.splitn(2, ':');
+    let kind = parts.next().unwrap_or_default();
+    let rest = parts.next();
+    match (kind, rest) {
+        ("btrfs", None) => Ok(SnapshotConfig::Btrfs {
+            subvolume: None,
+            snapshot_dir: None,
+        }),
+        ("btrfs", Some(subvolume)) => Ok(SnapshotConfig::Btrfs {
+            subvolume: Some(PathBuf::from(subvolume)),
+            snapshot_dir: None,
+        }),
+        ("lvm", Some(rest)) => {
+            let mut fields = rest.splitn(3, ':');
+            let volume = fields.next().unwrap_or_default();
+            let mount_point = fields.next().filter(|m| !m.is_empty()).ok_or_else(|| {
+                anyhow::anyhow!("lvm snapshot needs 'lvm:<vg/lv>:<mount point>[:<size>]'")
+            })?;
+            Ok(SnapshotConfig::Lvm {
+                volume: volume.to_string(),
+                mount_point: PathBuf::from(mount_point),
+                size: fields.next().map(str::to_string),
+            })
+        }
+        ("vss", None) => Ok(SnapshotConfig::Vss),
+        _ => Err(anyhow::anyhow!(
+            "Invalid snapshot: '{}'. Use 'btrfs', 'btrfs:<subvolume>', \
+             'lvm:<vg/lv>:<mount point>[:<size>]' or 'vss'",
+            spec
+        )),
+    }
+}
+
+/// The `parse_spec` form of `config`, for `config list`.
+pub fn spec(config: &SnapshotConfig) -> String {
+    match config {
+        SnapshotConfig::Btrfs { subvolume, .. } => match subvolume {
+            Some(subvolume) => format!("btrfs:{}", subvolume.display()),
+            None => "btrfs".to_string(),
+        },
+        SnapshotConfig::Lvm {
+            volume,
+            mount_point,
+            size,
+        } => match size {
+            Some(size) => format!("lvm:{}:{}:{}", volume, mount_point.display(), size),
+            None => format!("lvm:{}:{}", volume, mount_point.display()),
+        },
+        SnapshotConfig::Vss => "vss".to_string(),
+    }
+}
+
+fn snapshot_name() -> String {
+    format!(
+        "ardiex-snapshot-{}-{}",
+        std::process::id(),
+        Utc::now().format("%Y%m%d_%H%M%S%3f")
+    )
+}
+
+/// Where `source_dir`, which lives below `origin`, appears in a snapshot of
+/// `origin` mounted at `snapshot`.
+fn map_into(source_dir: &Path, origin: &Path, snapshot: &Path) -> Result<PathBuf> {
+    let relative = source_dir.strip_prefix(origin).map_err(|_| {
+        anyhow::anyhow!(
+            "Source {:?} is not below the snapshotted volume {:?}",
+            source_dir,
+            origin
+        )
+    })?;
+    Ok(snapshot.join(relative))
+}
+
+/// `("C:", "\\Users\\me")` for `C:\Users\me`.
+fn split_drive(path: &str) -> Option<(&str, &str)> {
+    let bytes = path.as_bytes();
+    (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':')
+        .then(|| path.split_at(2))
+}
+
+/// The shadow copy id and device object printed by the VSS create script.
+fn parse_vss_output(output: &str) -> Result<(String, String)> {
+    let mut lines = output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    match (lines.next(), lines.next()) {
+        (Some(id), Some(device)) => Ok((id.to_string(), device.to_string())),
+        _ => Err(anyhow::anyhow!(
+            "Unexpected output from VSS shadow copy creation: {}",
+            output.trim()
+        )),
+    }
+}
+
+/// Path of `source` inside the shadow copy `device`
+/// (`\\?\GLOBALROOT\Device\HarddiskVolumeShadowCopyN`).
+fn vss_source_path(device: &str, source: &str) -> Result<PathBuf> {
+    let (_, rest) = split_drive(source)
+        .ok_or_else(|| anyhow::anyhow!("VSS needs a source on a drive letter: {}", source))?;
+    Ok(PathBuf::from(format!(
+        "{}\\{}",
+        device.trim_end_matches('\\'),
+        rest.trim_start_matches(['\\', '/'])
+    )))
+}
+
+/// Run a snapshot tool, failing with its stderr when it exits non-zero.
+fn run(command: &mut Command) -> Result<String> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to run {}", program))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "{} failed ({}): {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(test)]
+#[path = "tests/snapshot_tests.rs"]
+mod tests;
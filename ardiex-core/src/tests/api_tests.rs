@@ -0,0 +1,427 @@
+use super::*;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncReadExt;
+
+const TOKEN: &str = "s3cret-token";
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), ts))
+}
+
+fn make_config(sources: &[(&Path, &Path, bool)]) -> BackupConfig {
+    BackupConfig {
+        sources: sources
+            .iter()
+            .map(|&(source_dir, backup_dir, enabled)| SourceConfig {
+                source_dir: source_dir.to_path_buf(),
+                backup_dirs: vec![backup_dir.to_path_buf().into()],
+                enabled,
+                ..Default::default()
+            })
+            .collect(),
+        backup_mode: BackupMode::Copy,
+        api_token: Some(TOKEN.to_string()),
+        ..Default::default()
+    }
+}
+
+async fn serve(state: ApiState) -> Result<(u16, JoinHandle<()>)> {
+    let listener = bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    Ok((port, spawn_server(listener, state)))
+}
+
+/// Send one request and return the status line and the JSON body.
+async fn call(
+    port: u16,
+    method: &str,
+    target: &str,
+    token: Option<&str>,
+    body: &str,
+) -> Result<(String, serde_json::Value)> {
+    let (status, body) = call_raw(port, method, target, token, body).await?;
+    Ok((status, serde_json::from_str(&body)?))
+}
+
+async fn call_raw(
+    port: u16,
+    method: &str,
+    target: &str,
+    token: Option<&str>,
+    body: &str,
+) -> Result<(String, String)> {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await?;
+    let auth = token
+        .map(|token| format!("authorization: Bearer {}\r\n", token))
+        .unwrap_or_default();
+    stream
+        .write_all(
+            format!(
+                "{} {} HTTP/1.1\r\nhost: localhost\r\n{}content-length: {}\r\n\r\n{}",
+                method,
+                target,
+                auth,
+                body.len(),
+                body
+            )
+            .as_bytes(),
+        )
+        .await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .context("response without a head")?;
+    let status = head.lines().next().unwrap_or_default().to_string();
+    Ok((status, body.to_string()))
+}
+
+#[test]
+fn restore_targets_must_stay_under_a_root() -> Result<()> {
+    let base = unique_temp_dir("ardiex_api_roots");
+    let root = base.join("restore");
+    fs::create_dir_all(root.join("docs"))?;
+    fs::create_dir_all(base.join("etc"))?;
+    let roots = [root.clone()];
+    let root = root.canonicalize()?;
+
+    assert_eq!(confine_restore_target(&roots, &root), Some(root.clone()));
+    assert_eq!(
+        confine_restore_target(&roots, &root.join("docs/new/deeper")),
+        Some(root.join("docs/new/deeper"))
+    );
+    assert_eq!(confine_restore_target(&roots, &base.join("restored")), None);
+    assert_eq!(confine_restore_target(&roots, &root.join("../etc")), None);
+    assert_eq!(confine_restore_target(&[], &root), None);
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(base.join("etc"), root.join("escape"))?;
+        assert_eq!(
+            confine_restore_target(&roots, &root.join("escape/passwd.d")),
+            None
+        );
+    }
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn requests_without_the_token_are_rejected() -> Result<()> {
+    let (tx, _rx) = mpsc::channel(8);
+    let state = ApiState::new(make_config(&[]), unique_temp_dir("ardiex_api_auth"), tx);
+    let (port, server) = serve(state.clone()).await?;
+
+    let (status, _) = call(port, "GET", "/api/status", None, "").await?;
+    assert!(status.contains("401"));
+    let (status, _) = call(port, "GET", "/api/status", Some("wrong-token!"), "").await?;
+    assert!(status.contains("401"));
+    let (status, body) = call(port, "GET", "/api/status", Some(TOKEN), "").await?;
+    assert!(status.contains("200"));
+    assert_eq!(body["sources"], 0);
+
+    // A token removed by a hot reload locks the API.
+    state.set_config(BackupConfig {
+        api_token: None,
+        ..make_config(&[])
+    });
+    let (status, _) = call(port, "GET", "/api/status", Some(TOKEN), "").await?;
+    assert!(status.contains("401"));
+
+    server.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn backups_are_queued_for_enabled_sources_only() -> Result<()> {
+    let (on, off) = (Path::new("/srv/api-on"), Path::new("/srv/api-off"));
+    let config = make_config(&[
+        (on, Path::new("/backup/on"), true),
+        (off, Path::new("/backup/off"), false),
+    ]);
+    let (tx, mut rx) = mpsc::channel(8);
+    let state = ApiState::new(config, unique_temp_dir("ardiex_api_trigger"), tx);
+    let (port, server) = serve(state.clone()).await?;
+
+    let (status, body) = call(port, "POST", "/api/backups", Some(TOKEN), "").await?;
+    assert!(status.contains("202"));
+    assert_eq!(body["queued"], serde_json::json!(["/srv/api-on"]));
+    let trigger = rx.try_recv()?;
+    assert_eq!(trigger.source_dir, on);
+    assert_eq!(trigger.reason, TriggerReason::Api);
+    assert!(rx.try_recv().is_err());
+
+    let (status, _) = call(
+        port,
+        "POST",
+        "/api/backups",
+        Some(TOKEN),
+        r#"{"source_dir":"/srv/api-off"}"#,
+    )
+    .await?;
+    assert!(status.contains("409"));
+    let (status, _) = call(
+        port,
+        "POST",
+        "/api/backups",
+        Some(TOKEN),
+        r#"{"source_dir":"/srv/unknown"}"#,
+    )
+    .await?;
+    assert!(status.contains("404"));
+
+    state.set_running(&[on.to_path_buf()]);
+    let (_, sources) = call(port, "GET", "/api/sources", Some(TOKEN), "").await?;
+    assert_eq!(sources[0]["running"], true);
+    assert_eq!(sources[1]["running"], false);
+    assert_eq!(sources[1]["enabled"], false);
+
+    server.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn restore_runs_in_the_background_and_reports_its_job() -> Result<()> {
+    let base = unique_temp_dir("ardiex_api_restore");
+    let (source, backup, target) = (base.join("src"), base.join("bk"), base.join("out"));
+    fs::create_dir_all(&source)?;
+    fs::create_dir_all(&backup)?;
+    fs::create_dir_all(&target)?;
+    fs::write(source.join("a.txt"), b"hello")?;
+    let mut config = make_config(&[(&source, &backup, true)]);
+    let history_path = base.join("history.jsonl");
+    let mut manager = BackupManager::new(config.clone()).with_history(history_path.clone());
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    let (tx, _rx) = mpsc::channel(8);
+    let state = ApiState::new(config.clone(), history_path, tx);
+    let (port, server) = serve(state.clone()).await?;
+    let post = |body: serde_json::Value| async move {
+        call(
+            port,
+            "POST",
+            "/api/restores",
+            Some(TOKEN),
+            &body.to_string(),
+        )
+        .await
+    };
+
+    let (_, sources) = call(port, "GET", "/api/sources", Some(TOKEN), "").await?;
+    assert_eq!(sources[0]["last_run"]["status"], "success");
+    let query = format!("/api/history?source={}&limit=1", source.display());
+    let (_, history) = call(port, "GET", &query, Some(TOKEN), "").await?;
+    assert_eq!(history.as_array().map(Vec::len), Some(1));
+
+    // Without api_restore_roots the API restores nowhere.
+    let (status, _) = post(serde_json::json!({"backup_dir": backup, "target_dir": target})).await?;
+    assert!(status.contains("403"));
+    config.api_restore_roots = vec![target.clone()];
+    state.set_config(config);
+
+    let (status, _) =
+        post(serde_json::json!({"backup_dir": base.join("other"), "target_dir": target})).await?;
+    assert!(status.contains("404"));
+
+    for outside in [base.join("elsewhere"), target.join("../elsewhere")] {
+        let (status, body) =
+            post(serde_json::json!({"backup_dir": backup, "target_dir": outside})).await?;
+        assert!(status.contains("403"), "{:?}: {}", outside, status);
+        assert!(
+            body["error"]
+                .as_str()
+                .unwrap_or_default()
+                .contains("api_restore_roots")
+        );
+    }
+    // The client cannot switch the confinement off.
+    let (status, _) = post(serde_json::json!({
+        "backup_dir": backup,
+        "target_dir": base.join("elsewhere"),
+        "outside_restore_roots": true,
+    }))
+    .await?;
+    assert!(status.contains("400"));
+    assert!(!base.join("elsewhere").exists());
+
+    let wait_for = |id: serde_json::Value| async move {
+        let mut job = serde_json::Value::Null;
+        for _ in 0..100 {
+            let (_, jobs) = call(port, "GET", "/api/restores", Some(TOKEN), "").await?;
+            job = jobs
+                .as_array()
+                .and_then(|jobs| jobs.iter().find(|job| job["id"] == id))
+                .cloned()
+                .unwrap_or_default();
+            if job["status"] != "running" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        Ok::<_, anyhow::Error>(job)
+    };
+
+    let (status, body) =
+        post(serde_json::json!({"backup_dir": backup, "target_dir": target})).await?;
+    assert!(status.contains("202"));
+    let job = wait_for(body["id"].clone()).await?;
+    assert_eq!(job["status"], "success");
+    assert_eq!(job["files_restored"], 1);
+    assert_eq!(fs::read(target.join("a.txt"))?, b"hello");
+
+    // Existing files are kept unless the request asks for overwrite.
+    fs::write(target.join("a.txt"), b"edited")?;
+    let (_, body) = post(serde_json::json!({"backup_dir": backup, "target_dir": target})).await?;
+    assert_eq!(wait_for(body["id"].clone()).await?["status"], "success");
+    assert_eq!(fs::read(target.join("a.txt"))?, b"edited");
+    let (_, body) = post(serde_json::json!({
+        "backup_dir": backup,
+        "target_dir": target,
+        "overwrite": "always",
+    }))
+    .await?;
+    assert_eq!(wait_for(body["id"].clone()).await?["status"], "success");
+    assert_eq!(fs::read(target.join("a.txt"))?, b"hello");
+
+    server.abort();
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn dashboard_is_served_only_when_enabled() -> Result<()> {
+    let (tx, _rx) = mpsc::channel(8);
+    let state = ApiState::new(make_config(&[]), unique_temp_dir("ardiex_api_dash"), tx);
+    let (port, server) = serve(state.clone()).await?;
+
+    let (status, _) = call_raw(port, "GET", "/", None, "").await?;
+    assert!(status.contains("401"));
+    let (status, _) = call_raw(port, "GET", "/", Some(TOKEN), "").await?;
+    assert!(status.contains("404"));
+
+    state.set_config(BackupConfig {
+        api_dashboard: true,
+        ..make_config(&[])
+    });
+    // The page itself needs no token; the data it fetches does.
+    let (status, page) = call_raw(port, "GET", "/", None, "").await?;
+    assert!(status.contains("200"));
+    assert!(page.contains("/api/sources"));
+    let (status, _) = call_raw(port, "GET", "/api/sources", None, "").await?;
+    assert!(status.contains("401"));
+
+    server.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn stats_and_checks_run_against_configured_backup_dirs() -> Result<()> {
+    let base = unique_temp_dir("ardiex_api_check");
+    let (source, backup) = (base.join("src"), base.join("bk"));
+    fs::create_dir_all(&source)?;
+    fs::create_dir_all(&backup)?;
+    fs::write(source.join("a.txt"), b"hello")?;
+    let mut config = make_config(&[(&source, &backup, true)]);
+    config.enable_periodic = true;
+    config.cron_schedule = "0 0 * * * *".to_string();
+    let mut manager = BackupManager::new(config.clone());
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    let (tx, _rx) = mpsc::channel(8);
+    let (port, server) = serve(ApiState::new(config, base.join("history.jsonl"), tx)).await?;
+
+    let (_, sources) = call(port, "GET", "/api/sources", Some(TOKEN), "").await?;
+    let next: DateTime<Utc> = serde_json::from_value(sources[0]["next_backup"].clone())?;
+    assert!(next > Utc::now());
+
+    let query = format!("/api/stats?backup_dir={}", backup.display());
+    let (status, stats) = call(port, "GET", &query, Some(TOKEN), "").await?;
+    assert!(status.contains("200"));
+    assert_eq!(stats["points"].as_array().map(Vec::len), Some(1));
+    assert_eq!(stats["points"][0]["backup_type"], "full");
+
+    let request = serde_json::json!({ "backup_dir": backup });
+    let (status, report) = call(
+        port,
+        "POST",
+        "/api/checks",
+        Some(TOKEN),
+        &request.to_string(),
+    )
+    .await?;
+    assert!(status.contains("200"));
+    assert_eq!(report["backup_points"], 1);
+    assert_eq!(report["findings"], serde_json::json!([]));
+
+    let foreign = serde_json::json!({ "backup_dir": base.join("other") });
+    let (status, _) = call(
+        port,
+        "POST",
+        "/api/checks",
+        Some(TOKEN),
+        &foreign.to_string(),
+    )
+    .await?;
+    assert!(status.contains("404"));
+    let (status, _) = call(port, "GET", "/api/stats", Some(TOKEN), "").await?;
+    assert!(status.contains("400"));
+
+    server.abort();
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn status_reports_queue_and_progress_and_logs_are_tailed() -> Result<()> {
+    let base = unique_temp_dir("ardiex_api_activity");
+    fs::create_dir_all(&base)?;
+    let log_file = base.join("ardiex.log");
+    fs::write(&log_file, "first\nsecond\nthird\n")?;
+    let (source, backup) = (Path::new("/srv/api-busy"), Path::new("/backup/busy"));
+    let (tx, _rx) = mpsc::channel(8);
+    let state = ApiState::new(
+        make_config(&[(source, backup, true)]),
+        base.join("history.jsonl"),
+        tx,
+    )
+    .with_log_file(log_file);
+    let (port, server) = serve(state.clone()).await?;
+
+    state.set_running(&[source.to_path_buf()]);
+    state.set_queued(&[(PathBuf::from("/srv/api-next"), TriggerReason::FileChange)]);
+    state.record_progress(&ProgressUpdate {
+        label: "Backup progress",
+        target: backup,
+        file: Some(Path::new("a.txt")),
+        files_done: 1,
+        total_files: 3,
+        bytes_done: 10,
+        total_bytes: 30,
+    });
+    let (_, status) = call(port, "GET", "/api/status", Some(TOKEN), "").await?;
+    assert_eq!(status["queued"][0]["source_dir"], "/srv/api-next");
+    assert_eq!(status["queued"][0]["reason"], "file change");
+    assert_eq!(status["progress"][0]["target"], "/backup/busy");
+    assert_eq!(status["progress"][0]["file"], "a.txt");
+    assert_eq!(status["progress"][0]["files_done"], 1);
+
+    // The batch ending drops progress its backups never finished.
+    state.set_running(&[]);
+    let (_, status) = call(port, "GET", "/api/status", Some(TOKEN), "").await?;
+    assert_eq!(status["progress"], serde_json::json!([]));
+
+    let (_, lines) = call(port, "GET", "/api/logs?lines=2", Some(TOKEN), "").await?;
+    assert_eq!(lines, serde_json::json!(["second", "third"]));
+
+    server.abort();
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
@@ -0,0 +1,181 @@
+use super::*;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_nanos();
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), ts))
+}
+
+/// A source tree with a nested file and one spanning several chunks.
+fn make_tree(dir: &Path) -> Result<Vec<u8>> {
+    fs::create_dir_all(dir.join("nested"))?;
+    fs::write(dir.join("a.txt"), b"hello")?;
+    fs::write(dir.join("nested").join("empty.txt"), b"")?;
+    let large: Vec<u8> = (0..CHUNK_SIZE * 2 + 100).map(|i| (i % 251) as u8).collect();
+    fs::write(dir.join("nested").join("large.bin"), &large)?;
+    Ok(large)
+}
+
+#[test]
+fn pack_dir_roundtrips_all_files() -> Result<()> {
+    let base = unique_temp_dir("ardiex_archive_roundtrip");
+    let large = make_tree(&base.join("src"))?;
+    let archive = archive_path(&base, "full_20260101_000000000");
+    pack_dir(&base.join("src"), &archive, None)?;
+
+    let mut reader = ArchiveReader::open(&archive, None)?;
+    let paths: Vec<&str> = reader.entries().iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(paths, vec!["a.txt", "nested/empty.txt", "nested/large.bin"]);
+
+    let entry = reader
+        .find(Path::new("nested").join("large.bin").as_path())
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("large.bin missing"))?;
+    assert_eq!(entry.chunks.len(), 3);
+    assert_eq!(reader.read_entry(&entry)?, large);
+    // The last chunk holds 100 bytes, so this tail spans two chunks.
+    assert_eq!(
+        reader.read_entry_tail(&entry, 200)?,
+        &large[large.len() - 200..]
+    );
+
+    let empty = reader.entries()[1].clone();
+    let target = base.join("out").join("empty.txt");
+    reader.extract_file(&empty, &target, None)?;
+    assert_eq!(fs::read(&target)?, b"");
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn encrypted_archive_requires_the_right_passphrase() -> Result<()> {
+    let base = unique_temp_dir("ardiex_archive_encrypted");
+    make_tree(&base.join("src"))?;
+    let archive = base.join("full_20260101_000000000.ardx");
+    pack_dir(&base.join("src"), &archive, Some("correct horse"))?;
+
+    // Neither file names nor contents are readable without the key.
+    let raw = fs::read(&archive)?;
+    assert!(!raw.windows(5).any(|w| w == b"a.txt"));
+
+    let missing = ArchiveReader::open(&archive, None)
+        .err()
+        .map(|e| e.to_string());
+    assert!(missing.is_some_and(|msg| msg.contains(PASSPHRASE_ENV_KEY)));
+    let wrong = ArchiveReader::open(&archive, Some("wrong")).err();
+    assert!(format!("{:#}", wrong.expect("wrong passphrase must fail")).contains("decrypt"));
+
+    let mut reader = ArchiveReader::open(&archive, Some("correct horse"))?;
+    let entry = reader.entries()[0].clone();
+    assert_eq!(reader.read_entry(&entry)?, b"hello");
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn archive_without_trailer_is_rejected_as_incomplete() -> Result<()> {
+    let base = unique_temp_dir("ardiex_archive_incomplete");
+    make_tree(&base.join("src"))?;
+    let archive = base.join("inc_20260101_000000000.ardx");
+    pack_dir(&base.join("src"), &archive, None)?;
+
+    let raw = fs::read(&archive)?;
+    fs::write(&archive, &raw[..raw.len() - 4])?;
+    let err = ArchiveReader::open(&archive, None).err();
+    assert!(
+        err.expect("truncated archive must fail")
+            .to_string()
+            .contains("incomplete")
+    );
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn corrupted_chunk_is_detected_on_read() -> Result<()> {
+    let base = unique_temp_dir("ardiex_archive_corrupt");
+    fs::create_dir_all(base.join("src"))?;
+    fs::write(base.join("src").join("a.txt"), vec![b'x'; 4096])?;
+    let archive = base.join("full_20260101_000000000.ardx");
+    pack_dir(&base.join("src"), &archive, None)?;
+
+    let entry = ArchiveReader::open(&archive, None)?.entries()[0].clone();
+    let mut raw = fs::read(&archive)?;
+    let chunk = &entry.chunks[0];
+    raw[(chunk.offset + chunk.len / 2) as usize] ^= 0xff;
+    fs::write(&archive, &raw)?;
+
+    let mut reader = ArchiveReader::open(&archive, None)?;
+    assert!(reader.read_entry(&entry).is_err());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn chunk_refs_outside_the_file_are_rejected_before_reading() -> Result<()> {
+    let base = unique_temp_dir("ardiex_archive_bad_index");
+    make_tree(&base.join("src"))?;
+    let archive = base.join("full_20260101_000000000.ardx");
+    pack_dir(&base.join("src"), &archive, None)?;
+
+    let mut entry = ArchiveReader::open(&archive, None)?.entries()[0].clone();
+    let mut reader = ArchiveReader::open(&archive, None)?;
+    entry.size = u64::MAX;
+    assert!(reader.read_entry(&entry).is_err());
+    entry.chunks[0].len = u64::MAX - 1;
+    let err = reader.read_entry(&entry).expect_err("huge chunk must fail");
+    assert!(err.to_string().contains("outside the file"));
+    entry.chunks[0].offset = u64::MAX;
+    entry.chunks[0].len = 2;
+    assert!(reader.read_entry(&entry).is_err());
+
+    // A manifest length past the end of the file in the trailer.
+    let mut raw = fs::read(&archive)?;
+    let len_at = raw.len() - 16;
+    raw[len_at..len_at + 8].copy_from_slice(&(1u64 << 40).to_le_bytes());
+    fs::write(&archive, &raw)?;
+    let err = ArchiveReader::open(&archive, None).err();
+    assert!(
+        err.expect("corrupt trailer must fail")
+            .to_string()
+            .contains("outside the file")
+    );
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn backup_point_name_accepts_directories_and_archives() -> Result<()> {
+    let base = unique_temp_dir("ardiex_archive_point_name");
+    fs::create_dir_all(base.join("full_20260101_000000000"))?;
+    fs::write(base.join("inc_20260101_000100000.ardx"), b"")?;
+    fs::write(base.join("inc_20260101_000200000"), b"")?;
+    fs::write(base.join("metadata.json"), b"{}")?;
+
+    let name = |file: &str| backup_point_name(&base.join(file));
+    assert_eq!(
+        name("full_20260101_000000000").as_deref(),
+        Some("full_20260101_000000000")
+    );
+    assert_eq!(
+        name("inc_20260101_000100000.ardx").as_deref(),
+        Some("inc_20260101_000100000")
+    );
+    assert_eq!(name("inc_20260101_000200000"), None);
+    assert_eq!(name("metadata.json"), None);
+    assert_eq!(
+        locate_backup(&base, "inc_20260101_000100000"),
+        Some(base.join("inc_20260101_000100000.ardx"))
+    );
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
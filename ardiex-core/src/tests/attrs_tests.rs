@@ -0,0 +1,122 @@
+use super::*;
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_nanos();
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), ts))
+}
+
+#[test]
+fn captured_attributes_are_reapplied_to_a_copy() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_attrs_roundtrip");
+    fs::create_dir_all(&dir)?;
+    let original = dir.join("original.sh");
+    fs::write(&original, b"#!/bin/sh")?;
+    let mtime = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    File::options()
+        .write(true)
+        .open(&original)?
+        .set_modified(mtime)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&original, fs::Permissions::from_mode(0o750))?;
+    }
+
+    let attributes = FileAttributes::capture(&fs::metadata(&original)?);
+    let copy = dir.join("copy.sh");
+    fs::copy(&original, &copy)?;
+    attributes.apply(&copy, false)?;
+
+    let restored = fs::metadata(&copy)?;
+    assert_eq!(restored.modified()?, mtime);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(restored.permissions().mode() & 0o7777, 0o750);
+    }
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn manifest_roundtrips_and_is_recognised_by_name() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_attrs_manifest");
+    fs::create_dir_all(&dir)?;
+    assert!(read_manifest(&dir)?.is_empty());
+
+    let mut manifest = AttributeManifest::new();
+    manifest.insert(
+        manifest_key(&Path::new("nested").join("a.txt")),
+        FileAttributes {
+            mode: Some(0o644),
+            uid: Some(1000),
+            gid: Some(1000),
+            readonly: false,
+            modified: None,
+            hard_link_to: None,
+            xattrs: BTreeMap::new(),
+            stored_as: Some(StoredAs::Full),
+        },
+    );
+    write_manifest(&dir, &manifest)?;
+    assert_eq!(read_manifest(&dir)?, manifest);
+    assert!(manifest.contains_key("nested/a.txt"));
+    assert!(is_attributes_file(Path::new(ATTRIBUTES_FILE_NAME)));
+    assert!(!is_attributes_file(
+        &Path::new("nested").join(ATTRIBUTES_FILE_NAME)
+    ));
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn hex_values_roundtrip() -> Result<()> {
+    let value = vec![0u8, 1, 0x7f, 0xff];
+    assert_eq!(encode_hex(&value), "00017fff");
+    assert_eq!(decode_hex("00017fff")?, value);
+    assert!(decode_hex("abc").is_err());
+    assert!(decode_hex("zz").is_err());
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn user_xattrs_are_captured_and_reapplied() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_attrs_xattr");
+    fs::create_dir_all(&dir)?;
+    let original = dir.join("tagged.txt");
+    fs::write(&original, b"x")?;
+    // tmpfs without user xattr support: nothing to check.
+    if xattr::set(&original, "user.ardiex.test", b"\x00tag").is_err() {
+        fs::remove_dir_all(&dir)?;
+        return Ok(());
+    }
+
+    let mut attributes = FileAttributes::capture(&fs::metadata(&original)?);
+    attributes.capture_xattrs(&original)?;
+    assert_eq!(
+        attributes
+            .xattrs
+            .get("user.ardiex.test")
+            .map(String::as_str),
+        Some("00746167")
+    );
+
+    let copy = dir.join("copy.txt");
+    fs::write(&copy, b"x")?;
+    attributes.apply(&copy, false)?;
+    assert_eq!(
+        xattr::get(&copy, "user.ardiex.test")?,
+        Some(b"\x00tag".to_vec())
+    );
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
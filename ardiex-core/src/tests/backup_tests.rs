@@ -0,0 +1,4189 @@
+use super::*;
+use crate::config::{
+    BackupConfig, BackupDestination, BackupHistoryType, BackupMode, LockedFilePolicy,
+    PORTABLE_KEY_FORMAT, SourceConfig, SourceMetadata,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_nanos();
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), ts))
+}
+
+fn no_filter() -> PathFilter {
+    PathFilter::new(&[], &[]).expect("empty patterns compile")
+}
+
+fn contains_delta_file(dir: &Path) -> Result<bool> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_dir() {
+            if contains_delta_file(&path)? {
+                return Ok(true);
+            }
+        } else {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            if file_name.ends_with(".delta") {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+fn make_source(source_dir: &Path, backup_dirs: Vec<PathBuf>, enabled: bool) -> SourceConfig {
+    SourceConfig {
+        source_dir: source_dir.to_path_buf(),
+        backup_dirs: backup_dirs.into_iter().map(Into::into).collect(),
+        enabled,
+        exclude_patterns: None,
+        max_backups: None,
+        backup_mode: None,
+        cron_schedule: None,
+        enable_event_driven: None,
+        enable_periodic: None,
+        ..Default::default()
+    }
+}
+
+fn make_config(
+    sources: Vec<SourceConfig>,
+    backup_mode: BackupMode,
+    max_backups: usize,
+    exclude_patterns: Vec<String>,
+) -> BackupConfig {
+    BackupConfig {
+        sources,
+        enable_periodic: true,
+        enable_event_driven: false,
+        exclude_patterns,
+        max_backups,
+        backup_mode,
+        cron_schedule: "0 0 * * * *".to_string(),
+        enable_min_interval_by_size: false,
+        max_log_file_size_mb: 20,
+        metadata: HashMap::new(),
+        ..Default::default()
+    }
+}
+
+fn load_metadata_with_hashes(backup_dir: &Path) -> Result<SourceMetadata> {
+    let mut metadata = BackupManager::load_source_metadata(&backup_dir.join("metadata.json"));
+    BackupManager::load_file_hashes(backup_dir, &mut metadata)?;
+    Ok(metadata)
+}
+
+fn list_backup_dirs(backup_dir: &Path) -> Result<Vec<String>> {
+    let mut entries: Vec<String> = fs::read_dir(backup_dir)?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|name| name.starts_with("full_") || name.starts_with("inc_"))
+        .collect();
+    entries.sort();
+    Ok(entries)
+}
+
+fn find_latest_dir_with_prefix(backup_dir: &Path, prefix: &str) -> Result<PathBuf> {
+    let mut candidates: Vec<String> = list_backup_dirs(backup_dir)?
+        .into_iter()
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    candidates.sort();
+
+    let selected = candidates
+        .last()
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("{} backup directory not found", prefix))?;
+    Ok(backup_dir.join(selected))
+}
+
+#[tokio::test]
+async fn clears_force_full_after_first_full_in_same_process() -> Result<()> {
+    let base = unique_temp_dir("ardiex_force_full_test");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+
+    let file_path = source_dir.join("sample.txt");
+    fs::write(&file_path, b"v1")?;
+
+    let source = SourceConfig {
+        source_dir: source_dir.clone(),
+        backup_dirs: vec![backup_dir.clone().into()],
+        enabled: true,
+        exclude_patterns: None,
+        max_backups: None,
+        backup_mode: None,
+        cron_schedule: None,
+        enable_event_driven: None,
+        enable_periodic: None,
+        ..Default::default()
+    };
+
+    let config = BackupConfig {
+        sources: vec![source],
+        enable_periodic: true,
+        enable_event_driven: true,
+        exclude_patterns: vec![],
+        max_backups: 10,
+        backup_mode: BackupMode::Delta,
+        cron_schedule: "0 0 * * * *".to_string(),
+        enable_min_interval_by_size: true,
+        max_log_file_size_mb: 20,
+        metadata: HashMap::new(),
+        ..Default::default()
+    };
+
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+
+    let first = manager.backup_all_sources().await?;
+    assert_eq!(first.len(), 1);
+    assert!(matches!(first[0].backup_type, BackupType::Full));
+
+    std::thread::sleep(Duration::from_millis(5));
+    fs::write(&file_path, b"v2")?;
+
+    let second = manager.backup_all_sources().await?;
+    assert_eq!(second.len(), 1);
+    assert!(matches!(second[0].backup_type, BackupType::Incremental));
+
+    let entries: Vec<String> = fs::read_dir(&backup_dir)?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+
+    assert!(entries.iter().any(|n| n.starts_with("full_")));
+    assert!(entries.iter().any(|n| n.starts_with("inc_")));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn copy_mode_creates_incremental_copy_without_delta_file() -> Result<()> {
+    let base = unique_temp_dir("ardiex_copy_mode_test");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+
+    let file_path = source_dir.join("sample.txt");
+    fs::write(&file_path, b"copy_v1")?;
+
+    let source = SourceConfig {
+        source_dir: source_dir.clone(),
+        backup_dirs: vec![backup_dir.clone().into()],
+        enabled: true,
+        exclude_patterns: None,
+        max_backups: None,
+        backup_mode: None,
+        cron_schedule: None,
+        enable_event_driven: None,
+        enable_periodic: None,
+        ..Default::default()
+    };
+
+    let config = BackupConfig {
+        sources: vec![source],
+        enable_periodic: true,
+        enable_event_driven: false,
+        exclude_patterns: vec![],
+        max_backups: 10,
+        backup_mode: BackupMode::Copy,
+        cron_schedule: "0 0 * * * *".to_string(),
+        enable_min_interval_by_size: true,
+        max_log_file_size_mb: 20,
+        metadata: HashMap::new(),
+        ..Default::default()
+    };
+
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+
+    let first = manager.backup_all_sources().await?;
+    assert_eq!(first.len(), 1);
+    assert!(matches!(first[0].backup_type, BackupType::Full));
+
+    std::thread::sleep(Duration::from_millis(5));
+    fs::write(&file_path, b"copy_v2")?;
+
+    let second = manager.backup_all_sources().await?;
+    assert_eq!(second.len(), 1);
+    assert!(matches!(second[0].backup_type, BackupType::Incremental));
+
+    let entries: Vec<String> = fs::read_dir(&backup_dir)?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+
+    let inc_name = entries
+        .iter()
+        .find(|n| n.starts_with("inc_"))
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("incremental backup directory not found"))?;
+    let inc_path = backup_dir.join(inc_name);
+    let copied_file = inc_path.join("sample.txt");
+
+    assert!(copied_file.exists());
+    assert_eq!(fs::read(&copied_file)?, b"copy_v2");
+    assert!(!contains_delta_file(&backup_dir)?);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn validates_incremental_checksum_from_metadata_on_startup() -> Result<()> {
+    let base = unique_temp_dir("ardiex_inc_checksum_test");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+
+    let file_path = source_dir.join("sample.txt");
+    fs::write(&file_path, b"checksum_v1")?;
+
+    let source = SourceConfig {
+        source_dir: source_dir.clone(),
+        backup_dirs: vec![backup_dir.clone().into()],
+        enabled: true,
+        exclude_patterns: None,
+        max_backups: None,
+        backup_mode: None,
+        cron_schedule: None,
+        enable_event_driven: None,
+        enable_periodic: None,
+        ..Default::default()
+    };
+
+    let config = BackupConfig {
+        sources: vec![source],
+        enable_periodic: true,
+        enable_event_driven: false,
+        exclude_patterns: vec![],
+        max_backups: 10,
+        backup_mode: BackupMode::Copy,
+        cron_schedule: "0 0 * * * *".to_string(),
+        enable_min_interval_by_size: true,
+        max_log_file_size_mb: 20,
+        metadata: HashMap::new(),
+        ..Default::default()
+    };
+
+    let mut manager = BackupManager::new(config.clone());
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    std::thread::sleep(Duration::from_millis(5));
+    fs::write(&file_path, b"checksum_v2")?;
+    manager.backup_all_sources().await?;
+
+    let metadata_path = backup_dir.join("metadata.json");
+    let metadata_content = fs::read_to_string(&metadata_path)?;
+    let metadata: SourceMetadata = serde_json::from_str(&metadata_content)?;
+    let inc_entry = metadata
+        .backup_history
+        .iter()
+        .find(|entry| matches!(entry.backup_type, BackupHistoryType::Incremental))
+        .ok_or_else(|| anyhow::anyhow!("incremental backup history entry not found"))?;
+    assert!(inc_entry.inc_checksum.is_some());
+
+    let inc_dir_name = metadata
+        .backup_history
+        .iter()
+        .find(|entry| matches!(entry.backup_type, BackupHistoryType::Incremental))
+        .map(|entry| entry.backup_name.clone())
+        .ok_or_else(|| anyhow::anyhow!("incremental backup directory name not found"))?;
+    let inc_file = backup_dir.join(inc_dir_name).join("sample.txt");
+    fs::write(&inc_file, b"tampered_incremental_backup")?;
+
+    let mut restarted_manager = BackupManager::new(config);
+    restarted_manager.validate_all_sources()?;
+    let result = restarted_manager.backup_all_sources().await?;
+    assert_eq!(result.len(), 1);
+    assert!(matches!(result[0].backup_type, BackupType::Full));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn validate_all_sources_rejects_relative_source_path() -> Result<()> {
+    let backup_dir = unique_temp_dir("ardiex_relative_backup_dir");
+    fs::create_dir_all(&backup_dir)?;
+
+    let source = SourceConfig {
+        source_dir: PathBuf::from("relative/source"),
+        backup_dirs: vec![backup_dir.clone().into()],
+        enabled: true,
+        exclude_patterns: None,
+        max_backups: None,
+        backup_mode: None,
+        cron_schedule: None,
+        enable_event_driven: None,
+        enable_periodic: None,
+        ..Default::default()
+    };
+
+    let config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    let mut manager = BackupManager::new(config);
+    let err = manager
+        .validate_all_sources()
+        .expect_err("relative source path must be rejected");
+    assert!(err.to_string().contains("Source path must be absolute"));
+
+    fs::remove_dir_all(&backup_dir)?;
+    Ok(())
+}
+
+#[test]
+fn validate_all_sources_auto_creates_missing_backup_dir() -> Result<()> {
+    let base = unique_temp_dir("ardiex_autocreate_backup_dir");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup_new");
+    fs::create_dir_all(&source_dir)?;
+
+    let source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    let config = make_config(vec![source], BackupMode::Delta, 10, vec![]);
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+
+    assert!(backup_dir.exists());
+    assert!(backup_dir.is_dir());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn validate_all_sources_marks_force_full_when_metadata_is_missing() -> Result<()> {
+    let base = unique_temp_dir("ardiex_force_full_metadata_missing");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+    fs::create_dir_all(backup_dir.join("full_20260224_120000123"))?;
+
+    let source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    let config = make_config(vec![source], BackupMode::Delta, 10, vec![]);
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+
+    assert_eq!(manager.force_full_dirs.get(&backup_dir), Some(&true));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn validate_backup_metadata_history_fails_when_metadata_json_missing() -> Result<()> {
+    let backup_dir = unique_temp_dir("ardiex_history_validation_missing_metadata");
+    fs::create_dir_all(backup_dir.join("full_20260224_120000123"))?;
+
+    let err = BackupManager::validate_backup_metadata_history(&backup_dir, None)
+        .expect_err("history validation must fail when metadata.json is missing");
+    assert!(err.to_string().contains("metadata.json is missing"));
+
+    fs::remove_dir_all(&backup_dir)?;
+    Ok(())
+}
+
+#[test]
+fn load_source_metadata_returns_default_for_invalid_json() -> Result<()> {
+    let backup_dir = unique_temp_dir("ardiex_invalid_metadata_json");
+    fs::create_dir_all(&backup_dir)?;
+    let metadata_path = backup_dir.join("metadata.json");
+    fs::write(&metadata_path, "{invalid json")?;
+
+    let metadata = BackupManager::load_source_metadata(&metadata_path);
+    assert!(metadata.last_full_backup.is_none());
+    assert!(metadata.last_backup.is_none());
+    assert!(metadata.file_hashes.is_empty());
+    assert!(metadata.backup_history.is_empty());
+
+    fs::remove_dir_all(&backup_dir)?;
+    Ok(())
+}
+
+#[test]
+fn synchronize_metadata_history_with_disk_rebuilds_history_and_checksum() -> Result<()> {
+    let backup_dir = unique_temp_dir("ardiex_sync_metadata_history");
+    let full_dir = backup_dir.join("full_20260224_120000123");
+    let inc_dir = backup_dir.join("inc_20260224_120100456");
+    fs::create_dir_all(&full_dir)?;
+    fs::create_dir_all(&inc_dir)?;
+    fs::write(full_dir.join("a.txt"), b"full-data")?;
+    fs::write(inc_dir.join("a.txt"), b"inc-data")?;
+
+    let mut metadata = SourceMetadata::default();
+    BackupManager::synchronize_metadata_history_with_disk(&backup_dir, &mut metadata)?;
+
+    assert_eq!(metadata.backup_history.len(), 2);
+    assert!(matches!(
+        metadata.backup_history[0].backup_type,
+        BackupHistoryType::Full
+    ));
+    assert!(matches!(
+        metadata.backup_history[1].backup_type,
+        BackupHistoryType::Incremental
+    ));
+    assert!(metadata.backup_history[1].inc_checksum.is_some());
+    assert!(metadata.last_full_backup.is_some());
+    assert!(metadata.last_backup.is_some());
+
+    fs::remove_dir_all(&backup_dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn copy_mode_prunes_old_backups_to_max_backups() -> Result<()> {
+    let base = unique_temp_dir("ardiex_copy_prune_old_backups");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+
+    let file_path = source_dir.join("sample.txt");
+    fs::write(&file_path, b"v1")?;
+
+    let source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    let config = make_config(vec![source], BackupMode::Copy, 2, vec![]);
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+
+    manager.backup_all_sources().await?;
+    std::thread::sleep(Duration::from_millis(5));
+
+    fs::write(&file_path, b"v2")?;
+    manager.backup_all_sources().await?;
+    std::thread::sleep(Duration::from_millis(5));
+
+    fs::write(&file_path, b"v3")?;
+    manager.backup_all_sources().await?;
+
+    let entries = list_backup_dirs(&backup_dir)?;
+    assert_eq!(entries.len(), 2);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn backup_source_dir_backs_up_only_the_targeted_source() -> Result<()> {
+    let base = unique_temp_dir("ardiex_backup_single_source");
+    let (source_a, source_b) = (base.join("source_a"), base.join("source_b"));
+    let (backup_a, backup_b) = (base.join("backup_a"), base.join("backup_b"));
+    for dir in [&source_a, &source_b, &backup_a, &backup_b] {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(source_a.join("a.txt"), b"a")?;
+    fs::write(source_b.join("b.txt"), b"b")?;
+
+    let config = make_config(
+        vec![
+            make_source(&source_a, vec![backup_a.clone()], true),
+            make_source(&source_b, vec![backup_b.clone()], true),
+        ],
+        BackupMode::Copy,
+        10,
+        vec![],
+    );
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+
+    let results = manager
+        .backup_source_dirs(std::slice::from_ref(&source_a))
+        .await?;
+    assert_eq!(results.len(), 1);
+    assert_eq!(list_backup_dirs(&backup_a)?.len(), 1);
+    assert!(list_backup_dirs(&backup_b)?.is_empty());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn full_backup_respects_exclude_patterns() -> Result<()> {
+    let base = unique_temp_dir("ardiex_full_exclude_patterns");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+
+    fs::write(source_dir.join("keep.txt"), b"keep")?;
+    fs::write(source_dir.join("drop.tmp"), b"drop")?;
+
+    let source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    let config = make_config(
+        vec![source],
+        BackupMode::Copy,
+        10,
+        vec!["*.tmp".to_string()],
+    );
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    let full_dir = find_latest_dir_with_prefix(&backup_dir, "full_")?;
+    assert!(full_dir.join("keep.txt").exists());
+    assert!(!full_dir.join("drop.tmp").exists());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn include_patterns_apply_before_excludes() -> Result<()> {
+    let base = unique_temp_dir("ardiex_include_patterns");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(source_dir.join("nested"))?;
+    fs::create_dir_all(&backup_dir)?;
+
+    fs::write(source_dir.join("main.rs"), b"fn main() {}")?;
+    fs::write(source_dir.join("nested").join("lib.rs"), b"pub fn f() {}")?;
+    fs::write(source_dir.join("generated.rs"), b"// generated")?;
+    fs::write(source_dir.join("notes.txt"), b"notes")?;
+
+    let mut source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    source.include_patterns = Some(vec!["*.rs".to_string()]);
+    let config = make_config(
+        vec![source],
+        BackupMode::Copy,
+        10,
+        vec!["*generated.rs".to_string()],
+    );
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    let full_dir = find_latest_dir_with_prefix(&backup_dir, "full_")?;
+    assert!(full_dir.join("main.rs").exists());
+    assert!(full_dir.join("nested").join("lib.rs").exists());
+    assert!(!full_dir.join("generated.rs").exists());
+    assert!(!full_dir.join("notes.txt").exists());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn size_and_age_filters_skip_files() -> Result<()> {
+    let base = unique_temp_dir("ardiex_size_age_filters");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+
+    fs::write(source_dir.join("keep.txt"), vec![b'k'; 100])?;
+    fs::write(source_dir.join("tiny.txt"), b"t")?;
+    fs::write(source_dir.join("huge.img"), vec![b'h'; 4096])?;
+    fs::write(source_dir.join("ancient.txt"), vec![b'a'; 100])?;
+    fs::File::options()
+        .write(true)
+        .open(source_dir.join("ancient.txt"))?
+        .set_modified(SystemTime::now() - Duration::from_secs(90 * 24 * 3600))?;
+
+    let mut source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    source.min_file_size = Some(10);
+    source.max_file_size = Some(1024);
+    source.modified_within = Some(30 * 24 * 3600);
+    let config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    let full_dir = find_latest_dir_with_prefix(&backup_dir, "full_")?;
+    assert!(full_dir.join("keep.txt").exists());
+    assert!(!full_dir.join("tiny.txt").exists());
+    assert!(!full_dir.join("huge.img").exists());
+    assert!(!full_dir.join("ancient.txt").exists());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn disabled_source_is_skipped_in_backup_all_sources() -> Result<()> {
+    let base = unique_temp_dir("ardiex_disabled_source_skip");
+    let source_enabled = base.join("source_enabled");
+    let source_disabled = base.join("source_disabled");
+    let backup_enabled = base.join("backup_enabled");
+    let backup_disabled = base.join("backup_disabled");
+    fs::create_dir_all(&source_enabled)?;
+    fs::create_dir_all(&source_disabled)?;
+    fs::create_dir_all(&backup_enabled)?;
+    fs::create_dir_all(&backup_disabled)?;
+    fs::write(source_enabled.join("a.txt"), b"enabled")?;
+    fs::write(source_disabled.join("b.txt"), b"disabled")?;
+
+    let enabled_source = make_source(&source_enabled, vec![backup_enabled.clone()], true);
+    let disabled_source = make_source(&source_disabled, vec![backup_disabled.clone()], false);
+    let config = make_config(
+        vec![enabled_source, disabled_source],
+        BackupMode::Copy,
+        10,
+        vec![],
+    );
+
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    let results = manager.backup_all_sources().await?;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].backup_dir, backup_enabled);
+
+    let disabled_entries = list_backup_dirs(&backup_disabled)?;
+    assert!(disabled_entries.is_empty());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn max_backups_interval_forces_full_after_restart_in_delta_mode() -> Result<()> {
+    let base = unique_temp_dir("ardiex_auto_full_interval_restart");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+    let file_path = source_dir.join("sample.txt");
+    fs::write(&file_path, b"v1")?;
+
+    let source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    let config = make_config(vec![source], BackupMode::Delta, 3, vec![]);
+
+    let mut manager1 = BackupManager::new(config.clone());
+    manager1.validate_all_sources()?;
+    let result1 = manager1.backup_all_sources().await?;
+    assert!(matches!(result1[0].backup_type, BackupType::Full));
+
+    std::thread::sleep(Duration::from_millis(5));
+    fs::write(&file_path, b"v2")?;
+    let mut manager2 = BackupManager::new(config.clone());
+    manager2.validate_all_sources()?;
+    let result2 = manager2.backup_all_sources().await?;
+    assert!(matches!(result2[0].backup_type, BackupType::Incremental));
+
+    std::thread::sleep(Duration::from_millis(5));
+    fs::write(&file_path, b"v3")?;
+    let mut manager3 = BackupManager::new(config.clone());
+    manager3.validate_all_sources()?;
+    let result3 = manager3.backup_all_sources().await?;
+    assert!(matches!(result3[0].backup_type, BackupType::Incremental));
+
+    std::thread::sleep(Duration::from_millis(5));
+    fs::write(&file_path, b"v4")?;
+    let mut manager4 = BackupManager::new(config);
+    manager4.validate_all_sources()?;
+    let result4 = manager4.backup_all_sources().await?;
+    assert!(matches!(result4[0].backup_type, BackupType::Full));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn delta_over_max_ratio_is_stored_as_a_full_copy() -> Result<()> {
+    use crate::restore::{RestoreManager, RestoreOptions};
+
+    let base = unique_temp_dir("ardiex_delta_max_ratio");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    let restore_dir = base.join("restore");
+    fs::create_dir_all(&source_dir)?;
+    let mut big: Vec<u8> = (0..64 * 1024u32).map(|i| (i % 251) as u8).collect();
+    fs::write(source_dir.join("big.bin"), &big)?;
+    fs::write(source_dir.join("small.txt"), b"v1")?;
+
+    let mut source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    source.delta_max_ratio = Some(0.5);
+    let config = make_config(vec![source], BackupMode::Delta, 10, vec![]);
+    let mut manager = BackupManager::new(config.clone());
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    std::thread::sleep(Duration::from_millis(5));
+    big[100] ^= 0xff;
+    fs::write(source_dir.join("big.bin"), &big)?;
+    fs::write(source_dir.join("small.txt"), b"v2")?;
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    // One changed block of 64 KiB stays a delta; the whole of a tiny file
+    // encodes larger than the file itself.
+    let inc = find_latest_dir_with_prefix(&backup_dir, "inc_")?;
+    assert!(inc.join("big.bin.delta").is_file());
+    assert_eq!(fs::read(inc.join("small.txt"))?, b"v2");
+    assert!(!inc.join("small.txt.delta").exists());
+    let manifest = crate::attrs::read_manifest(&inc)?;
+    assert_eq!(manifest["big.bin"].stored_as, Some(StoredAs::Delta));
+    assert_eq!(manifest["small.txt"].stored_as, Some(StoredAs::Full));
+
+    RestoreManager::restore_to_point(&backup_dir, &restore_dir, None, &RestoreOptions::default())?;
+    assert_eq!(fs::read(restore_dir.join("big.bin"))?, big);
+    assert_eq!(fs::read(restore_dir.join("small.txt"))?, b"v2");
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn file_strategies_and_size_cap_pick_copy_or_delta_per_file() -> Result<()> {
+    let base = unique_temp_dir("ardiex_file_strategies");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    let mut photo: Vec<u8> = (0..16 * 1024u32).map(|i| (i % 253) as u8).collect();
+    fs::write(source_dir.join("photo.jpg"), &photo)?;
+    fs::write(source_dir.join("dump.sql"), b"v1")?;
+
+    fs::write(source_dir.join("disk.img"), &photo)?;
+
+    let mut source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    // The ratio alone would turn the tiny dump into a full copy.
+    source.delta_max_ratio = Some(0.5);
+    source.delta_max_file_size = Some(4096);
+    source.file_strategies = Some(BTreeMap::from([
+        ("*.jpg".to_string(), FileStrategy::Copy),
+        ("*.sql".to_string(), FileStrategy::Delta),
+    ]));
+    let config = make_config(vec![source], BackupMode::Delta, 10, vec![]);
+    let mut manager = BackupManager::new(config.clone());
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    std::thread::sleep(Duration::from_millis(5));
+    photo[10] ^= 0xff;
+    fs::write(source_dir.join("photo.jpg"), &photo)?;
+    fs::write(source_dir.join("dump.sql"), b"v2")?;
+    fs::write(source_dir.join("disk.img"), &photo)?;
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    let inc = find_latest_dir_with_prefix(&backup_dir, "inc_")?;
+    assert_eq!(fs::read(inc.join("photo.jpg"))?, photo);
+    assert!(inc.join("dump.sql.delta").is_file());
+    let manifest = crate::attrs::read_manifest(&inc)?;
+    assert_eq!(manifest["photo.jpg"].stored_as, Some(StoredAs::Full));
+    assert_eq!(manifest["dump.sql"].stored_as, Some(StoredAs::Delta));
+    // Over delta_max_file_size and matching no strategy.
+    assert_eq!(fs::read(inc.join("disk.img"))?, photo);
+    assert_eq!(manifest["disk.img"].stored_as, Some(StoredAs::Full));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn calculate_min_interval_by_size_respects_thresholds() -> Result<()> {
+    let base = unique_temp_dir("ardiex_interval_by_size");
+    fs::create_dir_all(&base)?;
+
+    // <= 10MB => 1s
+    let tiny = base.join("tiny.bin");
+    fs::write(&tiny, vec![0u8; 1024])?;
+    assert_eq!(
+        BackupManager::calculate_min_interval_by_size(&base),
+        Duration::from_secs(1)
+    );
+
+    // >10MB && <=100MB => 60s
+    let mid = base.join("mid.bin");
+    let mid_file = fs::File::create(&mid)?;
+    mid_file.set_len(11 * 1024 * 1024)?;
+    assert_eq!(
+        BackupManager::calculate_min_interval_by_size(&base),
+        Duration::from_secs(60)
+    );
+
+    // >100MB && <=1GB => 3600s
+    let large = base.join("large.bin");
+    let large_file = fs::File::create(&large)?;
+    large_file.set_len(110 * 1024 * 1024)?;
+    assert_eq!(
+        BackupManager::calculate_min_interval_by_size(&base),
+        Duration::from_secs(3600)
+    );
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn scan_for_changes_returns_full_without_last_full_metadata() -> Result<()> {
+    let base = unique_temp_dir("ardiex_scan_full_without_marker");
+    fs::create_dir_all(&base)?;
+    fs::write(base.join("a.txt"), b"v1")?;
+    let metadata = SourceMetadata::default();
+
+    let (backup_type, files, _current_hashes) = BackupManager::scan_for_changes(
+        &base,
+        &[],
+        &metadata,
+        &no_filter(),
+        &mut LockedFiles::default(),
+    )?;
+    assert!(matches!(backup_type, BackupType::Full));
+    assert_eq!(files.len(), 1);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn scan_for_changes_returns_only_modified_files_for_incremental() -> Result<()> {
+    let base = unique_temp_dir("ardiex_scan_incremental_changes");
+    fs::create_dir_all(&base)?;
+    let a = base.join("a.txt");
+    let b = base.join("b.txt");
+    fs::write(&a, b"same")?;
+    fs::write(&b, b"old")?;
+
+    let a_hash = BackupManager::calculate_file_hash(&a)?;
+    let b_hash_old = BackupManager::calculate_file_hash(&b)?;
+    fs::write(&b, b"new")?;
+
+    let mut metadata = SourceMetadata {
+        last_full_backup: Some(chrono::Utc::now()),
+        last_backup: Some(chrono::Utc::now()),
+        file_hashes: HashMap::new(),
+        key_format: PORTABLE_KEY_FORMAT,
+        backup_history: vec![],
+        origin: None,
+        change_journal: None,
+        hash_journal: None,
+    };
+    metadata.file_hashes.insert("a.txt".to_string(), a_hash);
+    metadata.file_hashes.insert("b.txt".to_string(), b_hash_old);
+
+    let (backup_type, files, _current_hashes) = BackupManager::scan_for_changes(
+        &base,
+        &[],
+        &metadata,
+        &no_filter(),
+        &mut LockedFiles::default(),
+    )?;
+    assert!(matches!(backup_type, BackupType::Incremental));
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].file_name().unwrap_or_default(), "b.txt");
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn scan_changed_paths_rescans_only_reported_files_and_dirs() -> Result<()> {
+    let base = unique_temp_dir("ardiex_scan_changed_paths");
+    fs::create_dir_all(base.join("old"))?;
+    fs::write(base.join("a.txt"), b"same")?;
+    fs::write(base.join("b.txt"), b"old")?;
+    fs::write(base.join("old/c.txt"), b"gone soon")?;
+    let mut metadata = SourceMetadata {
+        last_full_backup: Some(chrono::Utc::now()),
+        ..Default::default()
+    };
+    for key in ["a.txt", "b.txt", "old/c.txt"] {
+        let hash = BackupManager::calculate_file_hash(&base.join(key))?;
+        metadata.file_hashes.insert(key.to_string(), hash);
+    }
+
+    // b.txt changed without being reported; the rest was.
+    fs::write(base.join("b.txt"), b"new")?;
+    fs::remove_dir_all(base.join("old"))?;
+    fs::create_dir_all(base.join("new/deep"))?;
+    fs::write(base.join("new/deep/d.txt"), b"fresh")?;
+    let changed = BTreeSet::from([PathBuf::from("old"), PathBuf::from("new")]);
+
+    let (files, hashes, bytes_hashed) = BackupManager::scan_changed_paths(
+        &base,
+        &[],
+        &metadata,
+        &changed,
+        &no_filter(),
+        &mut LockedFiles::default(),
+    )?;
+    assert_eq!(files, vec![base.join("new/deep/d.txt")]);
+    assert_eq!(bytes_hashed, 5);
+    let mut keys: Vec<&str> = hashes.keys().map(String::as_str).collect();
+    keys.sort();
+    assert_eq!(keys, vec!["a.txt", "b.txt", "new/deep/d.txt"]);
+    assert_eq!(hashes["b.txt"], metadata.file_hashes["b.txt"]);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn locked_files_retry_then_skip_or_fail_per_policy() -> Result<()> {
+    let base = unique_temp_dir("ardiex_locked_files");
+    let mut source = make_source(&base, vec![base.join("backup")], true);
+    source.locked_file_retries = Some(1);
+    let config = make_config(vec![source.clone()], BackupMode::Copy, 10, vec![]);
+    let locked_error = || -> Result<()> {
+        Err(std::io::Error::from_raw_os_error(locked::LOCKED_OS_ERRORS[0]).into())
+    };
+
+    let mut attempts = 0;
+    let mut failing = LockedFiles::new(&source.resolve(&config));
+    let err = failing
+        .read(&base.join("a.pst"), "a.pst", || {
+            attempts += 1;
+            locked_error()
+        })
+        .expect_err("fail policy must abort");
+    assert_eq!(attempts, 2);
+    assert!(format!("{:#}", err).contains("locked by another process"));
+
+    source.locked_file_policy = Some(LockedFilePolicy::Skip);
+    let mut skipping = LockedFiles::new(&source.resolve(&config));
+    assert!(
+        skipping
+            .read(&base.join("a.pst"), "a.pst", locked_error)?
+            .is_none()
+    );
+    assert_eq!(
+        skipping.read(&base.join("b.txt"), "b.txt", || Ok(7))?,
+        Some(7)
+    );
+    assert_eq!(skipping.skipped, vec!["a.pst".to_string()]);
+
+    // Other errors are never retried or skipped.
+    assert!(
+        skipping
+            .read(&base.join("c.txt"), "c.txt", || -> Result<()> {
+                Err(anyhow::anyhow!("disk on fire"))
+            })
+            .is_err()
+    );
+    Ok(())
+}
+
+#[test]
+fn scan_keys_use_forward_slashes_and_old_windows_keys_are_migrated() -> Result<()> {
+    let base = unique_temp_dir("ardiex_portable_keys");
+    let source_dir = base.join("source");
+    let file = source_dir.join("docs").join("sub").join("a.txt");
+    fs::create_dir_all(file.parent().expect("parent"))?;
+    fs::write(&file, b"a")?;
+    let hash = BackupManager::calculate_file_hash(&file)?;
+
+    let metadata_path = base.join("metadata.json");
+    fs::write(
+        &metadata_path,
+        serde_json::json!({
+            "last_full_backup": chrono::Utc::now(),
+            "last_backup": chrono::Utc::now(),
+            "file_hashes": { "docs\\sub\\a.txt": hash },
+        })
+        .to_string(),
+    )?;
+    let metadata = BackupManager::load_source_metadata(&metadata_path);
+    assert_eq!(metadata.key_format, PORTABLE_KEY_FORMAT);
+    assert_eq!(
+        metadata.file_hashes.keys().collect::<Vec<_>>(),
+        vec!["docs/sub/a.txt"]
+    );
+
+    // The migrated key matches the scan, so nothing counts as changed.
+    let (backup_type, files, current_hashes) = BackupManager::scan_for_changes(
+        &source_dir.join("docs"),
+        &[],
+        &metadata,
+        &no_filter(),
+        &mut LockedFiles::default(),
+    )?;
+    assert!(matches!(backup_type, BackupType::Incremental));
+    assert_eq!(current_hashes.keys().collect::<Vec<_>>(), vec!["sub/a.txt"]);
+    assert_eq!(files.len(), 1);
+
+    let (_, files, _) = BackupManager::scan_for_changes(
+        &source_dir,
+        &[],
+        &metadata,
+        &no_filter(),
+        &mut LockedFiles::default(),
+    )?;
+    assert!(files.is_empty());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn find_latest_backup_file_prefers_most_recent_backup() -> Result<()> {
+    let base = unique_temp_dir("ardiex_find_latest_backup");
+    fs::create_dir_all(&base)?;
+    let full = base.join("full_20260224_120000");
+    let inc = base.join("inc_20260224_121000");
+    fs::create_dir_all(&full)?;
+    fs::create_dir_all(&inc)?;
+    fs::write(full.join("a.txt"), b"full")?;
+    std::thread::sleep(Duration::from_millis(5));
+    fs::write(inc.join("a.txt"), b"inc")?;
+
+    let found =
+        BackupManager::find_latest_backup_file(&base, Path::new("a.txt"), &base.join("scratch"))
+            .ok_or_else(|| anyhow::anyhow!("latest backup file not found"))?;
+    assert_eq!(fs::read(found)?, b"inc");
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn cleanup_old_backups_delta_mode_preserves_latest_full_chain() -> Result<()> {
+    let base = unique_temp_dir("ardiex_cleanup_delta_chain");
+    fs::create_dir_all(&base)?;
+
+    let full1 = base.join("full_20260224_100000");
+    let inc1 = base.join("inc_20260224_101000");
+    let full2 = base.join("full_20260224_110000");
+    let inc2 = base.join("inc_20260224_111000");
+    let inc3 = base.join("inc_20260224_112000");
+    for dir in [&full1, &inc1, &full2, &inc2, &inc3] {
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join("marker.txt"), dir.to_string_lossy().as_bytes())?;
+        std::thread::sleep(Duration::from_millis(5));
+    }
+
+    BackupManager::cleanup_old_backups(&base, 2, None, &BackupMode::Delta)?;
+    let entries = list_backup_dirs(&base)?;
+    assert_eq!(
+        entries,
+        vec![
+            "full_20260224_110000".to_string(),
+            "inc_20260224_111000".to_string(),
+            "inc_20260224_112000".to_string(),
+        ]
+    );
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn cleanup_old_backups_keeps_pinned_points_and_the_chain_they_need() -> Result<()> {
+    let base = unique_temp_dir("ardiex_cleanup_pinned");
+    fs::create_dir_all(&base)?;
+
+    let names = [
+        "full_20260224_100000",
+        "inc_20260224_101000",
+        "inc_20260224_102000",
+        "full_20260224_110000",
+        "inc_20260224_111000",
+        "full_20260224_120000",
+    ];
+    for name in names {
+        fs::create_dir_all(base.join(name))?;
+        fs::write(base.join(name).join("marker.txt"), name.as_bytes())?;
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    BackupManager::save_source_metadata(
+        &base.join("metadata.json"),
+        &SourceMetadata::default(),
+        None,
+    )?;
+
+    let pinned = BackupManager::set_pinned(&base, "20260224_101000", true, None)?;
+    assert_eq!(pinned, "inc_20260224_101000");
+    assert!(BackupManager::set_pinned(&base, "inc_20260224_999999", true, None).is_err());
+
+    BackupManager::cleanup_old_backups(&base, 1, None, &BackupMode::Delta)?;
+    assert_eq!(
+        list_backup_dirs(&base)?,
+        vec![
+            "full_20260224_100000".to_string(),
+            "full_20260224_120000".to_string(),
+            "inc_20260224_101000".to_string(),
+        ]
+    );
+
+    BackupManager::set_pinned(&base, "inc_20260224_101000", false, None)?;
+    BackupManager::cleanup_old_backups(&base, 1, None, &BackupMode::Delta)?;
+    assert_eq!(
+        list_backup_dirs(&base)?,
+        vec!["full_20260224_120000".to_string()]
+    );
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn set_tag_adds_and_removes_tags_that_survive_metadata_sync() -> Result<()> {
+    let base = unique_temp_dir("ardiex_tag_points");
+    fs::create_dir_all(base.join("full_20260224_100000"))?;
+    fs::write(base.join("full_20260224_100000").join("a.txt"), b"a")?;
+    let metadata_path = base.join("metadata.json");
+    BackupManager::save_source_metadata(&metadata_path, &SourceMetadata::default(), None)?;
+
+    BackupManager::set_tag(&base, "20260224_100000", "pre-upgrade", false, None)?;
+    BackupManager::set_tag(&base, "full_20260224_100000", "pre-upgrade", false, None)?;
+    assert!(BackupManager::set_tag(&base, "20260224_100000", "two words", false, None).is_err());
+
+    let mut metadata = BackupManager::load_source_metadata(&metadata_path);
+    BackupManager::synchronize_metadata_history_with_disk(&base, &mut metadata)?;
+    assert_eq!(
+        metadata.backup_history[0].tags,
+        vec!["pre-upgrade".to_string()]
+    );
+
+    BackupManager::set_tag(&base, "20260224_100000", "pre-upgrade", true, None)?;
+    assert!(BackupManager::set_tag(&base, "20260224_100000", "pre-upgrade", true, None).is_err());
+    let metadata = BackupManager::load_source_metadata(&metadata_path);
+    assert!(metadata.backup_history[0].tags.is_empty());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn cleanup_old_backups_prunes_by_total_size_but_keeps_latest_chain() -> Result<()> {
+    let base = unique_temp_dir("ardiex_cleanup_quota");
+    fs::create_dir_all(&base)?;
+
+    let names = [
+        "full_20260224_100000",
+        "inc_20260224_101000",
+        "full_20260224_110000",
+        "inc_20260224_111000",
+    ];
+    for name in names {
+        fs::create_dir_all(base.join(name))?;
+        fs::write(base.join(name).join("data.bin"), vec![0u8; 1000])?;
+        std::thread::sleep(Duration::from_millis(5));
+    }
+
+    // Under the quota: nothing is removed.
+    let remaining = BackupManager::cleanup_old_backups(&base, 10, Some(4000), &BackupMode::Delta)?;
+    assert_eq!(remaining, Some(4000));
+    assert_eq!(list_backup_dirs(&base)?.len(), 4);
+
+    let remaining = BackupManager::cleanup_old_backups(&base, 10, Some(2500), &BackupMode::Delta)?;
+    assert_eq!(remaining, Some(2000));
+    assert_eq!(
+        list_backup_dirs(&base)?,
+        vec![names[2].to_string(), names[3].to_string()]
+    );
+
+    // The latest full chain is never pruned, even over the quota.
+    let remaining = BackupManager::cleanup_old_backups(&base, 10, Some(500), &BackupMode::Delta)?;
+    assert_eq!(remaining, Some(2000));
+    assert_eq!(list_backup_dirs(&base)?.len(), 2);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn validate_all_sources_forces_full_when_chain_has_no_full_backup() -> Result<()> {
+    let base = unique_temp_dir("ardiex_validate_chain_no_full");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    let inc = backup_dir.join("inc_20260224_121000");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&inc)?;
+    fs::write(inc.join("a.txt"), b"v1")?;
+
+    let mut metadata = SourceMetadata::default();
+    BackupManager::synchronize_metadata_history_with_disk(&backup_dir, &mut metadata)?;
+    fs::write(
+        backup_dir.join("metadata.json"),
+        serde_json::to_string_pretty(&metadata)?,
+    )?;
+
+    let source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    let config = make_config(vec![source], BackupMode::Delta, 10, vec![]);
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    assert_eq!(manager.force_full_dirs.get(&backup_dir), Some(&true));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn validate_all_sources_forces_full_when_auto_interval_is_reached() -> Result<()> {
+    let base = unique_temp_dir("ardiex_force_full_auto_interval");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+
+    for name in [
+        "full_20260224_100000",
+        "inc_20260224_101000",
+        "inc_20260224_102000",
+    ] {
+        let dir = backup_dir.join(name);
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("a.txt"), name.as_bytes())?;
+        std::thread::sleep(Duration::from_millis(5));
+    }
+
+    let mut metadata = SourceMetadata::default();
+    BackupManager::synchronize_metadata_history_with_disk(&backup_dir, &mut metadata)?;
+    fs::write(
+        backup_dir.join("metadata.json"),
+        serde_json::to_string_pretty(&metadata)?,
+    )?;
+
+    let source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    // max_backups=3 => auto interval = 2. current inc tail is 2, so startup should force full.
+    let config = make_config(vec![source], BackupMode::Delta, 3, vec![]);
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    assert_eq!(manager.force_full_dirs.get(&backup_dir), Some(&true));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn multi_backup_dirs_receive_full_and_incremental_backups() -> Result<()> {
+    let base = unique_temp_dir("ardiex_multi_backup_dirs");
+    let source_dir = base.join("source");
+    let backup_a = base.join("backup_a");
+    let backup_b = base.join("backup_b");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_a)?;
+    fs::create_dir_all(&backup_b)?;
+    let file_path = source_dir.join("sample.txt");
+    fs::write(&file_path, b"v1")?;
+
+    let source = make_source(&source_dir, vec![backup_a.clone(), backup_b.clone()], true);
+    let config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+
+    let first = manager.backup_all_sources().await?;
+    assert_eq!(first.len(), 2);
+    assert!(
+        first
+            .iter()
+            .all(|r| matches!(r.backup_type, BackupType::Full))
+    );
+
+    std::thread::sleep(Duration::from_millis(5));
+    fs::write(&file_path, b"v2")?;
+    let second = manager.backup_all_sources().await?;
+    assert_eq!(second.len(), 2);
+    assert!(
+        second
+            .iter()
+            .all(|r| matches!(r.backup_type, BackupType::Incremental))
+    );
+
+    for backup_dir in [&backup_a, &backup_b] {
+        let entries = list_backup_dirs(backup_dir)?;
+        assert!(entries.iter().any(|name| name.starts_with("full_")));
+        assert!(entries.iter().any(|name| name.starts_with("inc_")));
+    }
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn validate_all_sources_forces_full_on_metadata_history_mismatch() -> Result<()> {
+    let base = unique_temp_dir("ardiex_metadata_mismatch_force_full");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+
+    let full_dir = backup_dir.join("full_20260224_100000123");
+    let inc_dir = backup_dir.join("inc_20260224_101000456");
+    fs::create_dir_all(&full_dir)?;
+    fs::create_dir_all(&inc_dir)?;
+    fs::write(full_dir.join("a.txt"), b"full")?;
+    fs::write(inc_dir.join("a.txt"), b"inc")?;
+
+    let mut metadata = SourceMetadata::default();
+    BackupManager::synchronize_metadata_history_with_disk(&backup_dir, &mut metadata)?;
+    metadata.backup_history.pop();
+    fs::write(
+        backup_dir.join("metadata.json"),
+        serde_json::to_string_pretty(&metadata)?,
+    )?;
+
+    let source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    let config = make_config(vec![source], BackupMode::Delta, 10, vec![]);
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    assert_eq!(manager.force_full_dirs.get(&backup_dir), Some(&true));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn deleted_file_is_removed_from_metadata_hashes() -> Result<()> {
+    let base = unique_temp_dir("ardiex_deleted_file_metadata_cleanup");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+
+    let keep = source_dir.join("keep.txt");
+    let remove = source_dir.join("remove.txt");
+    fs::write(&keep, b"keep-v1")?;
+    fs::write(&remove, b"remove-v1")?;
+
+    let source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    let config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    let mut manager = BackupManager::new(config.clone());
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    fs::remove_file(&remove)?;
+    std::thread::sleep(Duration::from_millis(5));
+    let result = manager.backup_all_sources().await?;
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].files_backed_up, 0);
+
+    let metadata = load_metadata_with_hashes(&backup_dir)?;
+    assert!(metadata.file_hashes.contains_key("keep.txt"));
+    assert!(!metadata.file_hashes.contains_key("remove.txt"));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn reported_paths_are_rescanned_alone_until_the_next_full_scan() -> Result<()> {
+    let base = unique_temp_dir("ardiex_reported_paths");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::write(source_dir.join("a.txt"), b"a-v1")?;
+    fs::write(source_dir.join("b.txt"), b"b-v1")?;
+
+    let source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    let config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    let mut manager = BackupManager::new(config.clone());
+    // Reported before any full scan: ignored, the whole source is read.
+    manager.note_changed_paths(&source_dir, BTreeSet::from([source_dir.join("a.txt")]));
+    let results = manager
+        .backup_source_dirs(std::slice::from_ref(&source_dir))
+        .await?;
+    assert_eq!(results[0].files_backed_up, 2);
+
+    fs::write(source_dir.join("a.txt"), b"a-v2")?;
+    fs::write(source_dir.join("b.txt"), b"b-v2")?;
+    manager.note_changed_paths(&source_dir, BTreeSet::from([source_dir.join("a.txt")]));
+    let results = manager
+        .backup_source_dirs(std::slice::from_ref(&source_dir))
+        .await?;
+    assert_eq!(results[0].files_backed_up, 1);
+    let metadata = load_metadata_with_hashes(&backup_dir)?;
+    assert_eq!(
+        metadata.file_hashes["a.txt"],
+        BackupManager::calculate_file_hash(&source_dir.join("a.txt"))?
+    );
+
+    // A trigger without paths scans everything and catches b.txt.
+    let results = manager
+        .backup_source_dirs(std::slice::from_ref(&source_dir))
+        .await?;
+    assert_eq!(results[0].files_backed_up, 1);
+    let metadata = load_metadata_with_hashes(&backup_dir)?;
+    assert_eq!(
+        metadata.file_hashes["b.txt"],
+        BackupManager::calculate_file_hash(&source_dir.join("b.txt"))?
+    );
+
+    // With full_scan_interval_secs = 0 reported paths are never trusted.
+    let mut config = config;
+    config.full_scan_interval_secs = 0;
+    let mut manager = BackupManager::new(config);
+    manager.backup_all_sources().await?;
+    fs::write(source_dir.join("b.txt"), b"b-v3")?;
+    manager.note_changed_paths(&source_dir, BTreeSet::from([source_dir.join("a.txt")]));
+    let results = manager
+        .backup_source_dirs(std::slice::from_ref(&source_dir))
+        .await?;
+    assert_eq!(results[0].files_backed_up, 1);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn hashes_of_a_deleted_point_are_pruned_so_its_files_are_copied_again() -> Result<()> {
+    let base = unique_temp_dir("ardiex_prune_stale_hashes");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+    fs::write(source_dir.join("a.txt"), b"a")?;
+
+    let source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    let mut config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    config.prune_stale_hashes = false;
+    let mut manager = BackupManager::new(config.clone());
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+    std::thread::sleep(Duration::from_millis(5));
+    fs::write(source_dir.join("b.txt"), b"b")?;
+    manager.backup_all_sources().await?;
+
+    let points = list_backup_dirs(&backup_dir)?;
+    assert_eq!(points.len(), 2);
+    fs::remove_dir_all(backup_dir.join(&points[1]))?;
+
+    // Switched off, b.txt is still taken for stored and nothing is written.
+    let results = manager.backup_all_sources().await?;
+    assert_eq!(results[0].files_backed_up, 0);
+    assert!(
+        load_metadata_with_hashes(&backup_dir)?
+            .file_hashes
+            .contains_key("b.txt")
+    );
+
+    // No startup validation here: it would force a full for the missing
+    // point and hide what pruning alone does.
+    config.prune_stale_hashes = true;
+    let mut manager = BackupManager::new(config);
+    std::thread::sleep(Duration::from_millis(5));
+    let results = manager.backup_all_sources().await?;
+    assert_eq!(results[0].files_backed_up, 1);
+    let points = list_backup_dirs(&backup_dir)?;
+    assert!(backup_dir.join(&points[1]).join("b.txt").is_file());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn file_hashes_are_appended_to_a_journal_instead_of_metadata_json() -> Result<()> {
+    let base = unique_temp_dir("ardiex_hash_journal");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+    fs::write(source_dir.join("a.txt"), b"a-v1")?;
+    fs::write(source_dir.join("b.txt"), b"b-v1")?;
+
+    let source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    let config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    let mut manager = BackupManager::new(config.clone());
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    let metadata_path = backup_dir.join("metadata.json");
+    let journal_path = backup_dir.join("file_hashes.jsonl");
+    let inline: SourceMetadata = serde_json::from_str(&fs::read_to_string(&metadata_path)?)?;
+    assert!(inline.file_hashes.is_empty());
+    assert_eq!(fs::read_to_string(&journal_path)?.lines().count(), 2);
+
+    std::thread::sleep(Duration::from_millis(5));
+    fs::write(source_dir.join("a.txt"), b"a-v2")?;
+    manager.backup_all_sources().await?;
+    assert_eq!(fs::read_to_string(&journal_path)?.lines().count(), 3);
+    let metadata = load_metadata_with_hashes(&backup_dir)?;
+    assert_eq!(metadata.file_hashes.len(), 2);
+
+    // Hashes written inline by an older build win over the journal.
+    let mut older = inline;
+    older.file_hashes = metadata.file_hashes.clone();
+    older.file_hashes.remove("b.txt");
+    fs::write(&metadata_path, serde_json::to_string(&older)?)?;
+    let reloaded = load_metadata_with_hashes(&backup_dir)?;
+    assert_eq!(reloaded.file_hashes.len(), 1);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn hash_journal_must_match_the_digest_in_metadata_json() -> Result<()> {
+    let base = unique_temp_dir("ardiex_hash_journal_digest");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+    fs::write(source_dir.join("a.txt"), b"a-v1")?;
+    fs::write(source_dir.join("b.txt"), b"b-v1")?;
+
+    let source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    let config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    let journal_path = backup_dir.join("file_hashes.jsonl");
+    let committed = fs::read_to_string(&journal_path)?;
+    let metadata = load_metadata_with_hashes(&backup_dir)?;
+    assert_eq!(metadata.file_hashes.len(), 2);
+    let recorded = metadata.hash_journal.clone().expect("digest recorded");
+    assert_eq!(recorded.len, committed.len() as u64);
+
+    // Lines appended by a backup that never saved its metadata are ignored.
+    fs::write(
+        &journal_path,
+        format!("{}{{\"path\":\"c.txt\",\"hash\":\"00\"}}\n", committed),
+    )?;
+    let metadata = load_metadata_with_hashes(&backup_dir)?;
+    assert_eq!(metadata.file_hashes.len(), 2);
+    assert!(!metadata.file_hashes.contains_key("c.txt"));
+
+    // An edited journal is discarded, so the next backup re-copies.
+    let hash = metadata.file_hashes["a.txt"].clone();
+    fs::write(
+        &journal_path,
+        committed.replace(&hash, &"0".repeat(hash.len())),
+    )?;
+    let metadata = load_metadata_with_hashes(&backup_dir)?;
+    assert!(metadata.file_hashes.is_empty());
+    manager.backup_all_sources().await?;
+    assert_eq!(load_metadata_with_hashes(&backup_dir)?.file_hashes.len(), 2);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn delta_mode_creates_delta_file_when_previous_backup_exists() -> Result<()> {
+    let base = unique_temp_dir("ardiex_delta_file_creation");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+
+    let file_path = source_dir.join("sample.txt");
+    fs::write(&file_path, b"delta-v1")?;
+
+    let source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    let config = make_config(vec![source], BackupMode::Delta, 10, vec![]);
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    std::thread::sleep(Duration::from_millis(5));
+    fs::write(&file_path, b"delta-v2")?;
+    manager.backup_all_sources().await?;
+
+    let inc_dir = find_latest_dir_with_prefix(&backup_dir, "inc_")?;
+    assert!(contains_delta_file(&inc_dir)?);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn validate_all_sources_rejects_duplicate_sources() -> Result<()> {
+    let base = unique_temp_dir("ardiex_duplicate_sources");
+    let source_dir = base.join("source");
+    let backup_a = base.join("backup_a");
+    let backup_b = base.join("backup_b");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_a)?;
+    fs::create_dir_all(&backup_b)?;
+
+    let source1 = make_source(&source_dir, vec![backup_a], true);
+    let source2 = make_source(&source_dir, vec![backup_b], true);
+    let config = make_config(vec![source1, source2], BackupMode::Copy, 10, vec![]);
+    let mut manager = BackupManager::new(config);
+
+    let err = manager
+        .validate_all_sources()
+        .expect_err("duplicate sources must be rejected");
+    assert!(err.to_string().contains("Duplicate source directory"));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn validate_all_sources_rejects_duplicate_backup_dirs() -> Result<()> {
+    let base = unique_temp_dir("ardiex_duplicate_backup_dirs");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+
+    let source = make_source(&source_dir, vec![backup_dir.clone(), backup_dir], true);
+    let config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    let mut manager = BackupManager::new(config);
+
+    let err = manager
+        .validate_all_sources()
+        .expect_err("duplicate backup dirs must be rejected");
+    assert!(err.to_string().contains("Duplicate backup directory"));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn validate_all_sources_rejects_same_source_and_backup_path() -> Result<()> {
+    let base = unique_temp_dir("ardiex_source_equals_backup");
+    let source_dir = base.join("source");
+    fs::create_dir_all(&source_dir)?;
+
+    let source = make_source(&source_dir, vec![source_dir.clone()], true);
+    let config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    let mut manager = BackupManager::new(config);
+
+    let err = manager
+        .validate_all_sources()
+        .expect_err("same source/backup path must be rejected");
+    assert!(
+        err.to_string()
+            .contains("Backup directory cannot be the same as source")
+    );
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn validate_all_sources_rejects_nonexistent_source_dir() -> Result<()> {
+    let base = unique_temp_dir("ardiex_nonexistent_source");
+    let source_dir = base.join("missing_source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&backup_dir)?;
+
+    let source = make_source(&source_dir, vec![backup_dir], true);
+    let config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    let mut manager = BackupManager::new(config);
+
+    let err = manager
+        .validate_all_sources()
+        .expect_err("nonexistent source must be rejected");
+    assert!(err.to_string().contains("Source directory does not exist"));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn validate_all_sources_rejects_file_source_without_backup_dir() -> Result<()> {
+    let base = unique_temp_dir("ardiex_source_file_path");
+    let source_file = base.join("source_file.txt");
+    fs::create_dir_all(&base)?;
+    fs::write(&source_file, b"not a dir")?;
+
+    let source = make_source(&source_file, vec![], true);
+    let config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    let mut manager = BackupManager::new(config);
+
+    let err = manager
+        .validate_all_sources()
+        .expect_err("file source without backup dir must be rejected");
+    assert!(
+        err.to_string()
+            .contains("a file source needs at least one backup directory")
+    );
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn validate_all_sources_rejects_source_max_backups_zero() -> Result<()> {
+    let base = unique_temp_dir("ardiex_source_max_backups_zero");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+
+    let mut source = make_source(&source_dir, vec![backup_dir], true);
+    source.max_backups = Some(0);
+    let config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    let mut manager = BackupManager::new(config);
+
+    let err = manager
+        .validate_all_sources()
+        .expect_err("source max_backups=0 must be rejected");
+    assert!(err.to_string().contains("max_backups must be > 0"));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn validate_all_sources_rejects_source_invalid_cron() -> Result<()> {
+    let base = unique_temp_dir("ardiex_source_invalid_cron");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+
+    let mut source = make_source(&source_dir, vec![backup_dir], true);
+    source.cron_schedule = Some("invalid cron".to_string());
+    let config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    let mut manager = BackupManager::new(config);
+
+    let err = manager
+        .validate_all_sources()
+        .expect_err("invalid source cron must be rejected");
+    assert!(err.to_string().contains("invalid cron_schedule"));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn validate_all_sources_rejects_invalid_global_cron() -> Result<()> {
+    let base = unique_temp_dir("ardiex_global_invalid_cron");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+
+    let source = make_source(&source_dir, vec![backup_dir], true);
+    let mut config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    config.cron_schedule = "invalid cron".to_string();
+    let mut manager = BackupManager::new(config);
+
+    let err = manager
+        .validate_all_sources()
+        .expect_err("invalid global cron must be rejected");
+    assert!(err.to_string().contains("Invalid global cron_schedule"));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn validate_all_sources_rejects_global_max_backups_zero() -> Result<()> {
+    let base = unique_temp_dir("ardiex_global_max_backups_zero");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+
+    let source = make_source(&source_dir, vec![backup_dir], true);
+    let mut config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    config.max_backups = 0;
+    let mut manager = BackupManager::new(config);
+
+    let err = manager
+        .validate_all_sources()
+        .expect_err("global max_backups=0 must be rejected");
+    assert!(err.to_string().contains("Global max_backups must be > 0"));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn validate_all_sources_rejects_global_log_size_zero() -> Result<()> {
+    let base = unique_temp_dir("ardiex_global_log_size_zero");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+
+    let source = make_source(&source_dir, vec![backup_dir], true);
+    let mut config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    config.max_log_file_size_mb = 0;
+    let mut manager = BackupManager::new(config);
+
+    let err = manager
+        .validate_all_sources()
+        .expect_err("global log size 0 must be rejected");
+    assert!(
+        err.to_string()
+            .contains("Global max_log_file_size_mb must be > 0")
+    );
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn scan_for_changes_fails_for_missing_source_directory() {
+    let missing = PathBuf::from("/tmp/ardiex_missing_source_for_scan");
+    let metadata = SourceMetadata::default();
+    let err = BackupManager::scan_for_changes(
+        &missing,
+        &[],
+        &metadata,
+        &no_filter(),
+        &mut LockedFiles::default(),
+    )
+    .expect_err("missing source must return error");
+    assert!(err.to_string().contains("Source directory does not exist"));
+}
+
+#[test]
+fn validate_backup_metadata_history_fails_when_only_incremental_exists() -> Result<()> {
+    let backup_dir = unique_temp_dir("ardiex_history_only_incremental");
+    let inc_dir = backup_dir.join("inc_20260224_100000123");
+    fs::create_dir_all(&inc_dir)?;
+    fs::write(inc_dir.join("a.txt"), b"v1")?;
+
+    let mut metadata = SourceMetadata::default();
+    BackupManager::synchronize_metadata_history_with_disk(&backup_dir, &mut metadata)?;
+    fs::write(
+        backup_dir.join("metadata.json"),
+        serde_json::to_string_pretty(&metadata)?,
+    )?;
+
+    let err = BackupManager::validate_backup_metadata_history(&backup_dir, None)
+        .expect_err("incremental before full must be rejected");
+    assert!(
+        err.to_string()
+            .contains("incremental backup appears before any full backup")
+    );
+
+    fs::remove_dir_all(&backup_dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn context_command_output_is_recorded_in_history_and_result() -> Result<()> {
+    let base = unique_temp_dir("ardiex_context_command");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::write(source_dir.join("a.txt"), b"v1")?;
+
+    let mut source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    source.context_command = Some("echo build-42".to_string());
+    let config = make_config(vec![source], BackupMode::Delta, 10, vec![]);
+
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    let results = manager.backup_all_sources().await?;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].context.as_deref(), Some("build-42"));
+
+    let metadata = BackupManager::load_source_metadata(&backup_dir.join("metadata.json"));
+    assert_eq!(metadata.backup_history.len(), 1);
+    assert_eq!(
+        metadata.backup_history[0].context.as_deref(),
+        Some("build-42")
+    );
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn failing_context_command_does_not_abort_backup() -> Result<()> {
+    let base = unique_temp_dir("ardiex_context_command_fail");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::write(source_dir.join("a.txt"), b"v1")?;
+
+    let mut source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    source.context_command = Some("exit 3".to_string());
+    let config = make_config(vec![source], BackupMode::Delta, 10, vec![]);
+
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    let results = manager.backup_all_sources().await?;
+    assert_eq!(results.len(), 1);
+    assert!(results[0].context.is_none());
+    assert_eq!(results[0].files_backed_up, 1);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn backup_timeout_cancels_a_hung_backup_and_records_failure() -> Result<()> {
+    let base = unique_temp_dir("ardiex_backup_timeout");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::write(source_dir.join("a.txt"), b"v1")?;
+
+    let mut source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    // Stands in for a source that stops responding mid-backup.
+    source.context_command = Some("sleep 2".to_string());
+    source.backup_timeout = Some(1);
+    let config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    let started = std::time::Instant::now();
+    let results = manager.backup_all_sources().await?;
+    assert!(results.is_empty());
+    assert!(started.elapsed() < Duration::from_secs(2));
+
+    // Once unblocked, the cancelled backup stops before writing anything.
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let leftovers: Vec<_> = fs::read_dir(&backup_dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|name| name.starts_with("full_") || name.starts_with(PARTIAL_BACKUP_PREFIX))
+        .collect();
+    assert!(leftovers.is_empty(), "unexpected {:?}", leftovers);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn synchronize_metadata_history_keeps_recorded_context() -> Result<()> {
+    let backup_dir = unique_temp_dir("ardiex_sync_keeps_context");
+    let full_dir = backup_dir.join("full_20260224_120000123");
+    fs::create_dir_all(&full_dir)?;
+    fs::write(full_dir.join("a.txt"), b"full-data")?;
+
+    let mut metadata = SourceMetadata::default();
+    BackupManager::synchronize_metadata_history_with_disk(&backup_dir, &mut metadata)?;
+    metadata.backup_history[0].context = Some("v1.2.3".to_string());
+
+    BackupManager::synchronize_metadata_history_with_disk(&backup_dir, &mut metadata)?;
+    assert_eq!(
+        metadata.backup_history[0].context.as_deref(),
+        Some("v1.2.3")
+    );
+
+    fs::remove_dir_all(&backup_dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn backup_fails_when_backup_dir_is_locked() -> Result<()> {
+    let base = unique_temp_dir("ardiex_backup_locked");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+    fs::write(source_dir.join("a.txt"), b"v1")?;
+
+    let config = make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Delta,
+        10,
+        vec![],
+    );
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+
+    let held = crate::lock::BackupDirLock::acquire(&backup_dir, "backup")?;
+    let results = manager.backup_all_sources().await?;
+    assert!(results.is_empty());
+    assert!(list_backup_dirs(&backup_dir)?.is_empty());
+
+    drop(held);
+    let results = manager.backup_all_sources().await?;
+    assert_eq!(results.len(), 1);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn validate_all_sources_rejects_relative_path_mapping() {
+    let mut config = make_config(vec![], BackupMode::Delta, 10, vec![]);
+    config.path_mappings = vec![crate::config::PathMapping {
+        from: PathBuf::from("host/data"),
+        to: PathBuf::from("/data"),
+    }];
+    let mut manager = BackupManager::new(config);
+
+    let err = manager
+        .validate_all_sources()
+        .expect_err("relative path mapping must be rejected");
+    assert!(
+        err.to_string()
+            .contains("Path mapping must use absolute paths")
+    );
+}
+
+#[tokio::test]
+async fn backup_removes_stale_partial_dirs_and_leaves_none_behind() -> Result<()> {
+    let base = unique_temp_dir("ardiex_partial_cleanup");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::write(source_dir.join("a.txt"), b"v1")?;
+
+    let stale = backup_dir.join("partial_full_20260224_120000123");
+    fs::create_dir_all(&stale)?;
+    fs::write(stale.join("a.txt"), b"half-written")?;
+
+    let config = make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Delta,
+        10,
+        vec![],
+    );
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    let results = manager.backup_all_sources().await?;
+    assert_eq!(results.len(), 1);
+
+    assert!(!stale.exists());
+    let leftovers: Vec<String> = fs::read_dir(&backup_dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|name| name.starts_with(PARTIAL_BACKUP_PREFIX))
+        .collect();
+    assert!(leftovers.is_empty());
+    assert_eq!(list_backup_dirs(&backup_dir)?.len(), 1);
+
+    let metadata = BackupManager::load_source_metadata(&backup_dir.join("metadata.json"));
+    assert_eq!(metadata.backup_history.len(), 1);
+    assert!(!backup_dir.join("metadata.json.tmp").exists());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn uncommitted_partial_backup_dir_is_removed_on_drop() -> Result<()> {
+    let base = unique_temp_dir("ardiex_partial_drop");
+    let partial_path = base.join("partial_inc_20260224_120000123");
+
+    {
+        let partial = PartialBackupDir::create(partial_path.clone())?;
+        fs::write(partial.path().join("a.txt"), b"data")?;
+    }
+    assert!(!partial_path.exists());
+
+    let partial = PartialBackupDir::create(partial_path.clone())?;
+    let final_path = base.join("inc_20260224_120000123");
+    partial.commit(&final_path)?;
+    assert!(final_path.is_dir());
+    assert!(!partial_path.exists());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn backup_result_and_history_record_resource_usage() -> Result<()> {
+    let base = unique_temp_dir("ardiex_resource_usage");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::write(source_dir.join("a.txt"), vec![7u8; 4096])?;
+    fs::write(source_dir.join("b.txt"), vec![9u8; 1024])?;
+
+    let config = make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Copy,
+        10,
+        vec![],
+    );
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    let results = manager.backup_all_sources().await?;
+    assert_eq!(results.len(), 1);
+
+    let usage = &results[0].resource_usage;
+    // Hashing pass + copy pass both read every file of a full backup.
+    assert_eq!(usage.bytes_read, 2 * (4096 + 1024));
+    assert!(usage.bytes_written >= 4096 + 1024);
+    if cfg!(unix) {
+        assert!(usage.cpu_time_ms.is_some());
+        assert!(usage.peak_memory_kb.unwrap_or(0) > 0);
+    }
+
+    let metadata = BackupManager::load_source_metadata(&backup_dir.join("metadata.json"));
+    assert_eq!(
+        metadata.backup_history[0].resource_usage.as_ref(),
+        Some(usage)
+    );
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn repair_backup_dir_rebuilds_missing_metadata_and_removes_partials() -> Result<()> {
+    let backup_dir = unique_temp_dir("ardiex_repair_rebuild");
+    let full_dir = backup_dir.join("full_20260224_120000123");
+    let inc_dir = backup_dir.join("inc_20260224_120100456");
+    fs::create_dir_all(&full_dir)?;
+    fs::create_dir_all(&inc_dir)?;
+    fs::create_dir_all(backup_dir.join("partial_inc_20260224_120200789"))?;
+    fs::write(full_dir.join("a.txt"), b"full-data")?;
+    fs::write(inc_dir.join("a.txt"), b"inc-data")?;
+
+    let dry = BackupManager::repair_backup_dir(&backup_dir, true, None)?;
+    assert_eq!(dry.actions.len(), 2);
+    assert!(dry.unrecoverable.is_empty());
+    assert!(!backup_dir.join("metadata.json").exists());
+    assert!(backup_dir.join("partial_inc_20260224_120200789").exists());
+
+    let report = BackupManager::repair_backup_dir(&backup_dir, false, None)?;
+    assert_eq!(report.actions, dry.actions);
+    assert!(!backup_dir.join("partial_inc_20260224_120200789").exists());
+    BackupManager::validate_backup_metadata_history(&backup_dir, None)?;
+
+    let again = BackupManager::repair_backup_dir(&backup_dir, false, None)?;
+    assert!(again.is_clean());
+
+    fs::remove_dir_all(&backup_dir)?;
+    Ok(())
+}
+
+#[test]
+fn repair_dry_run_reports_a_stale_lock_without_touching_it() -> Result<()> {
+    let backup_dir = unique_temp_dir("ardiex_repair_dry_lock");
+    fs::create_dir_all(&backup_dir)?;
+    let lock_path = backup_dir.join(crate::lock::LOCK_FILE_NAME);
+    let stale = crate::lock::LockInfo {
+        pid: 999_999,
+        operation: "backup".to_string(),
+        acquired_at: chrono::Utc::now(),
+    };
+    fs::write(&lock_path, serde_json::to_string(&stale)?)?;
+    let before = fs::read(&lock_path)?;
+
+    let dry = BackupManager::repair_backup_dir(&backup_dir, true, None)?;
+    assert!(dry.actions.iter().any(|a| a.contains("pid 999999")));
+    assert_eq!(fs::read(&lock_path)?, before);
+
+    let held = BackupDirLock::acquire(&backup_dir, "backup")?;
+    let err = BackupManager::repair_backup_dir(&backup_dir, true, None)
+        .expect_err("dry run must refuse a held lock");
+    assert!(matches!(
+        err.downcast_ref::<crate::error::Error>(),
+        Some(crate::error::Error::Locked { .. })
+    ));
+    drop(held);
+
+    fs::remove_dir_all(&backup_dir)?;
+    Ok(())
+}
+
+#[test]
+fn repair_backup_dir_reports_chain_without_full_as_unrecoverable() -> Result<()> {
+    let backup_dir = unique_temp_dir("ardiex_repair_unrecoverable");
+    let inc_dir = backup_dir.join("inc_20260224_120100456");
+    fs::create_dir_all(&inc_dir)?;
+    fs::write(inc_dir.join("a.txt"), b"inc-data")?;
+
+    let report = BackupManager::repair_backup_dir(&backup_dir, false, None)?;
+    assert_eq!(report.unrecoverable.len(), 1);
+    assert!(report.unrecoverable[0].contains("no preceding full backup"));
+
+    fs::remove_dir_all(&backup_dir)?;
+    Ok(())
+}
+
+#[test]
+fn check_backup_dir_reports_drift_and_leftovers_and_repairs_them() -> Result<()> {
+    let backup_dir = unique_temp_dir("ardiex_check");
+    let full_dir = backup_dir.join("full_20260224_120000123");
+    let inc_dir = backup_dir.join("inc_20260224_120100456");
+    fs::create_dir_all(&full_dir)?;
+    fs::create_dir_all(&inc_dir)?;
+    fs::write(full_dir.join("a.txt"), b"full-data")?;
+    fs::write(inc_dir.join("a.txt"), b"inc-data")?;
+    BackupManager::repair_backup_dir(&backup_dir, false, None)?;
+    assert!(
+        BackupManager::check_backup_dir(&backup_dir, false, None)?
+            .findings
+            .is_empty()
+    );
+
+    fs::create_dir_all(backup_dir.join("partial_inc_20260224_120200789"))?;
+    fs::write(backup_dir.join("notes.txt"), b"mine")?;
+    let report = BackupManager::check_backup_dir(&backup_dir, false, None)?;
+    let checks: Vec<&str> = report.findings.iter().map(|f| f.check).collect();
+    assert_eq!(checks, vec!["orphan", "partial"]);
+    assert_eq!(report.error_count(), 0);
+
+    let report = BackupManager::check_backup_dir(&backup_dir, true, None)?;
+    assert!(!backup_dir.join("partial_inc_20260224_120200789").exists());
+    assert_eq!(report.findings.len(), 1);
+    assert!(report.repair.is_some());
+
+    // Changed content is reported and not resynced away by --repair.
+    fs::write(inc_dir.join("a.txt"), b"tampered")?;
+    fs::create_dir_all(backup_dir.join("partial_inc_20260224_120300000"))?;
+    let report = BackupManager::check_backup_dir(&backup_dir, true, None)?;
+    assert!(
+        report
+            .findings
+            .iter()
+            .any(|f| f.check == "inc_checksum"
+                && f.point.as_deref() == Some("inc_20260224_120100456"))
+    );
+    assert!(report.error_count() > 0);
+    assert!(report.repair.is_none() && report.repair_skipped.is_some());
+    assert!(backup_dir.join("partial_inc_20260224_120300000").exists());
+
+    fs::remove_dir_all(&backup_dir)?;
+    Ok(())
+}
+
+#[test]
+fn verify_backup_dir_reports_changed_points_under_a_throttle() -> Result<()> {
+    let backup_dir = unique_temp_dir("ardiex_verify");
+    let full_dir = backup_dir.join("full_20260224_120000123");
+    let inc_dir = backup_dir.join("inc_20260224_120100456");
+    fs::create_dir_all(&full_dir)?;
+    fs::create_dir_all(&inc_dir)?;
+    fs::write(full_dir.join("a.txt"), b"full-data")?;
+    fs::write(inc_dir.join("a.txt"), b"inc-data")?;
+    BackupManager::repair_backup_dir(&backup_dir, false, None)?;
+    assert!(
+        BackupManager::verify_backup_dir(&backup_dir, None, Some(100.0))?
+            .error_lines()
+            .is_empty()
+    );
+
+    fs::write(inc_dir.join("a.txt"), b"tampered")?;
+    let report = BackupManager::verify_backup_dir(&backup_dir, None, Some(100.0))?;
+    assert!(
+        report
+            .error_lines()
+            .iter()
+            .any(|line| line.starts_with("inc_checksum: inc_20260224_120100456:"))
+    );
+    assert!(BackupManager::verify_backup_dir(&backup_dir, None, Some(0.0)).is_err());
+
+    fs::remove_dir_all(&backup_dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn scrub_repairs_a_corrupted_copy_from_another_backup_dir() -> Result<()> {
+    let base = unique_temp_dir("ardiex_scrub");
+    let source_dir = base.join("source");
+    let first = base.join("first");
+    let second = base.join("second");
+    fs::create_dir_all(&source_dir)?;
+    fs::write(source_dir.join("a.txt"), b"alpha")?;
+    fs::write(source_dir.join("b.txt"), b"beta")?;
+
+    let source = make_source(&source_dir, vec![first.clone(), second.clone()], true);
+    let config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+    let dirs = vec![first.clone(), second.clone()];
+    assert!(
+        BackupManager::scrub_backup_dirs(&dirs, false, None)?
+            .findings
+            .is_empty()
+    );
+
+    let corrupted = find_latest_dir_with_prefix(&first, "full_")?.join("a.txt");
+    fs::write(&corrupted, b"alphb")?;
+    let report = BackupManager::scrub_backup_dirs(&dirs, true, None)?;
+    assert_eq!(report.copies_checked, 4);
+    assert_eq!(report.findings.len(), 1);
+    assert!(report.findings[0].healthy_copy.is_some() && !report.findings[0].repaired);
+    assert_eq!(fs::read(&corrupted)?, b"alphb");
+
+    let report = BackupManager::scrub_backup_dirs(&dirs, false, None)?;
+    assert!(report.findings[0].repaired);
+    assert_eq!(fs::read(&corrupted)?, b"alpha");
+
+    // Both copies broken: reported, nothing to repair from.
+    fs::write(&corrupted, b"alphb")?;
+    fs::write(
+        find_latest_dir_with_prefix(&second, "full_")?.join("a.txt"),
+        b"alphc",
+    )?;
+    let report = BackupManager::scrub_backup_dirs(&dirs, false, None)?;
+    assert_eq!(report.findings.len(), 2);
+    assert!(
+        report
+            .findings
+            .iter()
+            .all(|f| f.healthy_copy.is_none() && !f.repaired)
+    );
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn check_rebuilds_a_corrupted_point_from_its_parity() -> Result<()> {
+    let base = unique_temp_dir("ardiex_parity_check");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    let content: Vec<u8> = (0..30_000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(source_dir.join("a.bin"), &content)?;
+
+    let source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    let mut config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    config.parity_percent = 10;
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    let point = find_latest_dir_with_prefix(&backup_dir, "full_")?;
+    let point_name = point.file_name().unwrap().to_string_lossy().to_string();
+    assert!(
+        backup_dir
+            .join("parity")
+            .join(format!("{}.par", point_name))
+            .is_file()
+    );
+    assert!(
+        BackupManager::check_backup_dir(&backup_dir, false, None)?
+            .findings
+            .is_empty()
+    );
+
+    let mut corrupted = content.clone();
+    corrupted[12_345] ^= 0xff;
+    fs::write(point.join("a.bin"), &corrupted)?;
+    let report = BackupManager::check_backup_dir(&backup_dir, false, None)?;
+    assert!(
+        report
+            .findings
+            .iter()
+            .any(|f| f.check == "parity" && f.repairable)
+    );
+
+    let report = BackupManager::check_backup_dir(&backup_dir, true, None)?;
+    assert!(report.findings.is_empty(), "{:?}", report.findings);
+    assert!(report.repair.is_some());
+    assert_eq!(fs::read(point.join("a.bin"))?, content);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn backup_with_paths_only_includes_listed_subtrees() -> Result<()> {
+    let base = unique_temp_dir("ardiex_sparse_paths");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(source_dir.join("docs/nested"))?;
+    fs::create_dir_all(source_dir.join("src"))?;
+    fs::write(source_dir.join("docs/nested/a.md"), b"doc")?;
+    fs::write(source_dir.join("README.md"), b"readme")?;
+    fs::write(source_dir.join("src/main.rs"), b"code")?;
+
+    let mut source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    source.paths = vec![PathBuf::from("docs"), PathBuf::from("README.md")];
+    let config = make_config(vec![source], BackupMode::Copy, 10, vec![]);
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    let results = manager.backup_all_sources().await?;
+    assert_eq!(results[0].files_backed_up, 2);
+
+    let full = find_latest_dir_with_prefix(&backup_dir, "full_")?;
+    assert!(full.join("docs/nested/a.md").exists());
+    assert!(full.join("README.md").exists());
+    assert!(!full.join("src").exists());
+
+    let metadata = load_metadata_with_hashes(&backup_dir)?;
+    let mut keys: Vec<_> = metadata.file_hashes.keys().cloned().collect();
+    keys.sort();
+    assert_eq!(
+        keys,
+        vec!["README.md".to_string(), "docs/nested/a.md".to_string()]
+    );
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn validate_all_sources_rejects_paths_escaping_source() -> Result<()> {
+    let base = unique_temp_dir("ardiex_sparse_paths_escape");
+    let source_dir = base.join("source");
+    fs::create_dir_all(&source_dir)?;
+
+    let mut source = make_source(&source_dir, vec![base.join("backup")], true);
+    source.paths = vec![PathBuf::from("../other")];
+    let mut manager = BackupManager::new(make_config(vec![source], BackupMode::Copy, 10, vec![]));
+    let err = manager
+        .validate_all_sources()
+        .expect_err("paths escaping the source must be rejected");
+    assert!(err.to_string().contains("relative subpaths"));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn forward_queued_backups_waits_for_reachable_destination() -> Result<()> {
+    let base = unique_temp_dir("ardiex_forward_queue");
+    let source_dir = base.join("source");
+    let local_dir = base.join("local");
+    let forward_dir = base.join("nas");
+    fs::create_dir_all(&source_dir)?;
+    fs::write(source_dir.join("a.txt"), b"v1")?;
+
+    let source = make_source(&source_dir, vec![local_dir.clone()], true);
+    let config = make_config(vec![source.clone()], BackupMode::Copy, 10, vec![]);
+    let resolved = source.resolve(&config);
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    // NAS not mounted: nothing is copied and the backup stays queued.
+    let result = BackupManager::forward_queued_backups(&local_dir, &forward_dir, &resolved)?;
+    assert!(!result.reachable);
+    assert_eq!(result.queued, 1);
+    assert!(result.forwarded.is_empty());
+    assert!(!forward_dir.exists());
+
+    fs::create_dir_all(&forward_dir)?;
+    let result = BackupManager::forward_queued_backups(&local_dir, &forward_dir, &resolved)?;
+    assert_eq!(result.forwarded.len(), 1);
+    assert_eq!(result.queued, 0);
+    assert_eq!(
+        list_backup_dirs(&forward_dir)?,
+        list_backup_dirs(&local_dir)?
+    );
+    BackupManager::validate_backup_metadata_history(&forward_dir, None)?;
+
+    let local_metadata = BackupManager::load_source_metadata(&local_dir.join("metadata.json"));
+    assert!(local_metadata.backup_history[0].forwarded_at.is_some());
+
+    let again = BackupManager::forward_queued_backups(&local_dir, &forward_dir, &resolved)?;
+    assert!(again.forwarded.is_empty());
+    assert_eq!(again.queued, 0);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn forward_waits_for_a_backup_holding_the_local_dir() -> Result<()> {
+    let base = unique_temp_dir("ardiex_forward_local_lock");
+    let source_dir = base.join("source");
+    let local_dir = base.join("local");
+    let forward_dir = base.join("nas");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&forward_dir)?;
+    fs::write(source_dir.join("a.txt"), b"v1")?;
+
+    let source = make_source(&source_dir, vec![local_dir.clone()], true);
+    let config = make_config(vec![source.clone()], BackupMode::Copy, 10, vec![]);
+    let resolved = source.resolve(&config);
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    // Stands in for a backup or prune rewriting the local points.
+    let backup_lock = BackupDirLock::acquire(&local_dir, "backup")?;
+    let forwarding = {
+        let (local_dir, forward_dir) = (local_dir.clone(), forward_dir.clone());
+        std::thread::spawn(move || {
+            BackupManager::forward_queued_backups(&local_dir, &forward_dir, &resolved)
+        })
+    };
+    std::thread::sleep(Duration::from_millis(300));
+    assert!(!forwarding.is_finished());
+    assert!(list_backup_dirs(&forward_dir)?.is_empty());
+
+    drop(backup_lock);
+    let result = forwarding.join().expect("forward thread panicked")?;
+    assert_eq!(result.forwarded.len(), 1);
+    assert_eq!(
+        list_backup_dirs(&forward_dir)?,
+        list_backup_dirs(&local_dir)?
+    );
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn backup_lands_locally_while_a_forward_reads_the_dir() -> Result<()> {
+    let base = unique_temp_dir("ardiex_backup_during_forward");
+    let source_dir = base.join("source");
+    let local_dir = base.join("local");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&local_dir)?;
+    fs::write(source_dir.join("a.txt"), b"v1")?;
+
+    // Stands in for a forward copying a point out of `local_dir`.
+    let forward_lock = BackupDirLock::acquire_shared(&local_dir, "forward", None)?;
+    let releaser = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(300));
+        drop(forward_lock);
+    });
+
+    let source = make_source(&source_dir, vec![local_dir.clone()], true);
+    let mut manager = BackupManager::new(make_config(vec![source], BackupMode::Copy, 10, vec![]));
+    manager.validate_all_sources()?;
+    let results = manager.backup_all_sources().await?;
+    releaser.join().expect("releaser thread panicked");
+
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].backup_dir, local_dir);
+    assert_eq!(list_backup_dirs(&local_dir)?.len(), 1);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn forward_result_flags_queue_older_than_threshold() {
+    let result = forward::ForwardResult {
+        queued: 2,
+        oldest_queued_at: Some(Utc::now() - chrono::Duration::hours(30)),
+        ..Default::default()
+    };
+    assert!(result.queue_exceeds(chrono::Duration::hours(24)));
+    assert!(!result.queue_exceeds(chrono::Duration::hours(48)));
+    assert!(!forward::ForwardResult::default().queue_exceeds(chrono::Duration::hours(0)));
+}
+
+#[tokio::test]
+async fn backup_pings_healthcheck_start_and_success() -> Result<()> {
+    use std::io::{Read, Write};
+
+    let base = unique_temp_dir("ardiex_healthcheck_test");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+    fs::write(source_dir.join("a.txt"), b"v1")?;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}/ping/abc", listener.local_addr()?);
+    let server = std::thread::spawn(move || -> std::io::Result<Vec<String>> {
+        let mut request_lines = Vec::new();
+        for _ in 0..2 {
+            let (mut stream, _) = listener.accept()?;
+            let mut buf = vec![0u8; 16 * 1024];
+            let n = stream.read(&mut buf)?;
+            let request = String::from_utf8_lossy(&buf[..n]).to_string();
+            request_lines.push(request.lines().next().unwrap_or_default().to_string());
+            stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")?;
+        }
+        Ok(request_lines)
+    });
+
+    let mut source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    source.healthcheck_url = Some(url);
+    let config = make_config(vec![source], BackupMode::Copy, 5, vec![]);
+    let mut manager = BackupManager::new(config);
+    manager.backup_all_sources().await?;
+
+    let request_lines = server.join().expect("server thread panicked")?;
+    assert!(request_lines[0].starts_with("POST /ping/abc/start "));
+    assert!(request_lines[1].starts_with("POST /ping/abc "));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_format_writes_single_files_and_restores_delta_chain() -> Result<()> {
+    use crate::config::BackupFormat;
+    use crate::restore::{RestoreManager, RestoreOptions};
+
+    let base = unique_temp_dir("ardiex_archive_format");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    let restore_dir = base.join("restore");
+    fs::create_dir_all(source_dir.join("nested"))?;
+    let original = vec![7u8; 64 * 1024];
+    fs::write(source_dir.join("big.bin"), &original)?;
+    fs::write(source_dir.join("nested").join("a.txt"), b"v1")?;
+
+    let mut config = make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Delta,
+        10,
+        vec![],
+    );
+    config.backup_format = BackupFormat::Archive;
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    std::thread::sleep(Duration::from_millis(5));
+    let mut changed = original.clone();
+    changed[10] = 1;
+    fs::write(source_dir.join("big.bin"), &changed)?;
+    let results = manager.backup_all_sources().await?;
+    assert!(matches!(results[0].backup_type, BackupType::Incremental));
+
+    // One file per run, no directories or leftovers.
+    assert!(list_backup_dirs(&backup_dir)?.is_empty());
+    let mut names: Vec<String> = fs::read_dir(&backup_dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|name| {
+            name != "metadata.json"
+                && name != "file_hashes.jsonl"
+                && name != crate::lock::LOCK_FILE_NAME
+                && name != report::REPORT_DIR
+        })
+        .collect();
+    names.sort();
+    assert_eq!(names.len(), 2);
+    assert!(names[0].starts_with("full_") && names[0].ends_with(".ardx"));
+    assert!(names[1].starts_with("inc_") && names[1].ends_with(".ardx"));
+
+    let inc = crate::archive::ArchiveReader::open(&backup_dir.join(&names[1]), None)?;
+    assert!(inc.find(Path::new("big.bin.delta")).is_some());
+
+    BackupManager::validate_backup_metadata_history(&backup_dir, None)?;
+    assert!(BackupManager::validate_delta_chain(&backup_dir));
+
+    RestoreManager::restore_to_point(&backup_dir, &restore_dir, None, &RestoreOptions::default())?;
+    assert_eq!(fs::read(restore_dir.join("big.bin"))?, changed);
+    assert_eq!(fs::read(restore_dir.join("nested").join("a.txt"))?, b"v1");
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn backup_all_sources_appends_results_and_failures_to_history() -> Result<()> {
+    let base = unique_temp_dir("ardiex_history_record");
+    let source_dir = base.join("source");
+    let missing_dir = base.join("missing");
+    fs::create_dir_all(&source_dir)?;
+    fs::write(source_dir.join("a.txt"), b"v1")?;
+    let history_path = base.join("history.jsonl");
+
+    let config = make_config(
+        vec![
+            make_source(&source_dir, vec![base.join("backup")], true),
+            make_source(&missing_dir, vec![base.join("backup_missing")], true),
+        ],
+        BackupMode::Copy,
+        5,
+        vec![],
+    );
+    let mut manager = BackupManager::new(config).with_history(history_path.clone());
+    manager.backup_all_sources().await?;
+
+    let records = history::read_records(&history_path, None, 10)?;
+    assert_eq!(records.len(), 2);
+    let ok = records
+        .iter()
+        .find(|r| r.source_dir == source_dir)
+        .ok_or_else(|| anyhow::anyhow!("success record missing"))?;
+    assert_eq!(ok.status, history::RunStatus::Success);
+    assert_eq!(ok.backup_type.as_deref(), Some("full"));
+    let failed = records
+        .iter()
+        .find(|r| r.source_dir == missing_dir)
+        .ok_or_else(|| anyhow::anyhow!("failure record missing"))?;
+    assert_eq!(failed.status, history::RunStatus::Failure);
+    assert!(
+        failed
+            .error
+            .as_deref()
+            .is_some_and(|e| e.contains("does not exist"))
+    );
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn collect_backup_stats_reports_points_savings_and_hot_spots() -> Result<()> {
+    let base = unique_temp_dir("ardiex_backup_stats");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    let mut big = vec![0u8; 256 * 1024];
+    fs::write(source_dir.join("big.bin"), &big)?;
+    fs::write(source_dir.join("notes.txt"), b"v1")?;
+
+    let config = make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Delta,
+        10,
+        vec![],
+    );
+    let mut manager = BackupManager::new(config);
+    manager.backup_all_sources().await?;
+    for round in 1..=2u8 {
+        std::thread::sleep(Duration::from_millis(5));
+        big[0] = round;
+        fs::write(source_dir.join("big.bin"), &big)?;
+        manager.backup_all_sources().await?;
+    }
+
+    let stats = BackupManager::collect_backup_stats(&backup_dir)?;
+    assert_eq!(stats.points.len(), 3);
+    assert!(matches!(
+        stats.points[0].backup_type,
+        BackupHistoryType::Full
+    ));
+    assert_eq!(stats.points[1].delta_files, 1);
+    assert_eq!(stats.delta_full_bytes, 2 * big.len() as u64);
+    assert!(stats.delta_saved_bytes() > 0);
+    assert_eq!(stats.largest_files[0].path, "big.bin");
+    assert_eq!(stats.hot_spots.len(), 1);
+    assert_eq!(stats.hot_spots[0].path, "big.bin");
+    assert_eq!(stats.hot_spots[0].changes, 2);
+    assert!(stats.total_bytes >= stats.points.iter().map(|p| p.stored_bytes).sum::<u64>());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn collect_backup_stats_counts_hard_linked_files_once() -> Result<()> {
+    let base = unique_temp_dir("ardiex_stats_hard_links");
+    let older = base.join("full_20260101_000000000");
+    let newer = base.join("full_20260102_000000000");
+    fs::create_dir_all(&older)?;
+    fs::create_dir_all(&newer)?;
+    fs::write(older.join("shared.bin"), vec![0u8; 4096])?;
+    fs::hard_link(older.join("shared.bin"), newer.join("shared.bin"))?;
+    fs::write(newer.join("new.txt"), b"new")?;
+
+    let stats = BackupManager::collect_backup_stats(&base)?;
+    assert_eq!(stats.points[0].stored_bytes, 4096);
+    assert_eq!(stats.points[1].stored_bytes, 3);
+    assert_eq!(stats.points[1].files, 2);
+    assert_eq!(stats.total_bytes, 4096 + 3);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn restore_reapplies_recorded_mode_and_mtime() -> Result<()> {
+    use crate::restore::{RestoreManager, RestoreOptions};
+
+    let base = unique_temp_dir("ardiex_preserve_attrs");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    let restore_dir = base.join("restore");
+    fs::create_dir_all(&source_dir)?;
+    let script = source_dir.join("run.sh");
+    let locked = source_dir.join("locked.txt");
+    fs::write(&script, b"#!/bin/sh\necho v1")?;
+    fs::write(&locked, b"v1")?;
+    let set_mtime = |path: &Path, secs: u64| -> Result<()> {
+        fs::File::options()
+            .write(true)
+            .open(path)?
+            .set_modified(UNIX_EPOCH + Duration::from_secs(secs))?;
+        Ok(())
+    };
+    set_mtime(&script, 1_600_000_000)?;
+    let mut readonly = fs::metadata(&locked)?.permissions();
+    readonly.set_readonly(true);
+    fs::set_permissions(&locked, readonly.clone())?;
+
+    let config = make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Copy,
+        10,
+        vec![],
+    );
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    // The read-only file changes, so the incremental must overwrite the
+    // copy restored from the full backup.
+    std::thread::sleep(Duration::from_millis(5));
+    fs::remove_file(&locked)?;
+    fs::write(&locked, b"v2")?;
+    fs::set_permissions(&locked, readonly)?;
+    set_mtime(&locked, 1_650_000_000)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o750))?;
+    }
+    fs::write(&script, b"#!/bin/sh\necho v2")?;
+    set_mtime(&script, 1_700_000_000)?;
+    manager.backup_all_sources().await?;
+
+    let options = RestoreOptions {
+        skip_ownership: true,
+        ..Default::default()
+    };
+    let restored = RestoreManager::restore_to_point(&backup_dir, &restore_dir, None, &options)?;
+    assert_eq!(restored, 4);
+
+    let restored_script = fs::metadata(restore_dir.join("run.sh"))?;
+    assert_eq!(
+        restored_script.modified()?,
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(restored_script.permissions().mode() & 0o7777, 0o750);
+    }
+    let restored_locked = fs::metadata(restore_dir.join("locked.txt"))?;
+    assert_eq!(fs::read(restore_dir.join("locked.txt"))?, b"v2");
+    assert!(restored_locked.permissions().readonly());
+    assert_eq!(
+        restored_locked.modified()?,
+        UNIX_EPOCH + Duration::from_secs(1_650_000_000)
+    );
+
+    let full_dir = find_latest_dir_with_prefix(&backup_dir, "full_")?;
+    assert!(full_dir.join(crate::attrs::ATTRIBUTES_FILE_NAME).exists());
+    let metadata = BackupManager::load_source_metadata(&backup_dir.join("metadata.json"));
+    assert_eq!(metadata.backup_history[0].files_backed_up, 2);
+
+    make_writable(&base)?;
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_writes_checks_copies_and_deltas_on_backup_and_restore() -> Result<()> {
+    use crate::restore::{RestoreManager, RestoreOptions};
+
+    let base = unique_temp_dir("ardiex_verify_writes");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    let restore_dir = base.join("restore");
+    fs::create_dir_all(&source_dir)?;
+    let data = source_dir.join("data.bin");
+    let mut content = vec![7u8; 20_000];
+    fs::write(&data, &content)?;
+    fs::write(source_dir.join("notes.txt"), b"v1")?;
+
+    let mut config = make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Delta,
+        10,
+        vec![],
+    );
+    config.verify_writes = true;
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    std::thread::sleep(Duration::from_millis(5));
+    content[12_345] = 9;
+    fs::write(&data, &content)?;
+    manager.backup_all_sources().await?;
+    let inc_dir = find_latest_dir_with_prefix(&backup_dir, "inc_")?;
+    assert!(inc_dir.join("data.bin.delta").exists());
+    assert_eq!(
+        fs::read_dir(&backup_dir)?
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().ends_with(".verify"))
+            .count(),
+        0
+    );
+
+    let options = RestoreOptions {
+        skip_ownership: true,
+        verify_writes: true,
+        ..Default::default()
+    };
+    RestoreManager::restore_to_point(&backup_dir, &restore_dir, None, &options)?;
+    assert_eq!(fs::read(restore_dir.join("data.bin"))?, content);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn fsync_durability_completes_directory_and_archive_backups() -> Result<()> {
+    use crate::config::{BackupFormat, Durability};
+
+    for format in [BackupFormat::Directory, BackupFormat::Archive] {
+        let base = unique_temp_dir("ardiex_fsync_durability");
+        let source_dir = base.join("source");
+        let backup_dir = base.join("backup");
+        fs::create_dir_all(source_dir.join("nested"))?;
+        fs::write(source_dir.join("nested/a.txt"), b"durable")?;
+
+        let mut config = make_config(
+            vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+            BackupMode::Delta,
+            10,
+            vec![],
+        );
+        config.durability = Durability::Fsync;
+        config.backup_format = format.clone();
+        let mut manager = BackupManager::new(config);
+        manager.validate_all_sources()?;
+        manager.backup_all_sources().await?;
+
+        let metadata = BackupManager::load_source_metadata(&backup_dir.join("metadata.json"));
+        assert_eq!(metadata.backup_history.len(), 1, "{:?}", format);
+        assert!(!backup_dir.join("metadata.json.tmp").exists());
+        let point = archive::locate_backup(&backup_dir, &metadata.backup_history[0].backup_name);
+        assert!(point.is_some(), "{:?}", format);
+
+        fs::remove_dir_all(&base)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn low_space_aborts_or_prunes_down_to_floor() -> Result<()> {
+    use crate::config::LowSpaceAction;
+
+    let base = unique_temp_dir("ardiex_low_space");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+
+    let mut config = make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Copy,
+        10,
+        vec![],
+    );
+    config.low_space_min_backups = 2;
+    let mut manager = BackupManager::new(config.clone());
+    manager.validate_all_sources()?;
+    for round in 0..4 {
+        fs::write(source_dir.join("file.txt"), format!("round {}", round))?;
+        manager.backup_all_sources().await?;
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(list_backup_dirs(&backup_dir)?.len(), 4);
+    assert!(super::space::available_space(&backup_dir).is_some());
+
+    let metadata_path = backup_dir.join("metadata.json");
+    let mut metadata = BackupManager::load_source_metadata(&metadata_path);
+    let mut resolved = config.sources[0].resolve(&config);
+
+    // Nothing can make room for u64::MAX bytes.
+    let err = BackupManager::ensure_free_space(
+        &backup_dir,
+        u64::MAX,
+        &resolved,
+        &mut metadata,
+        &metadata_path,
+        None,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("Not enough free space"));
+    assert_eq!(list_backup_dirs(&backup_dir)?.len(), 4);
+
+    resolved.low_space_action = LowSpaceAction::Prune;
+    assert!(
+        BackupManager::ensure_free_space(
+            &backup_dir,
+            u64::MAX,
+            &resolved,
+            &mut metadata,
+            &metadata_path,
+            None,
+        )
+        .is_err()
+    );
+    assert_eq!(list_backup_dirs(&backup_dir)?.len(), 2);
+    let saved = BackupManager::load_source_metadata(&metadata_path);
+    assert_eq!(saved.backup_history.len(), 2);
+
+    // A small backup fits without touching anything.
+    BackupManager::ensure_free_space(
+        &backup_dir,
+        1,
+        &resolved,
+        &mut metadata,
+        &metadata_path,
+        None,
+    )?;
+    assert_eq!(list_backup_dirs(&backup_dir)?.len(), 2);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn detect_change_anomaly_flags_mass_modification_and_extension_renames() {
+    use crate::config::AnomalyDetectionConfig;
+
+    let config = AnomalyDetectionConfig {
+        enabled: true,
+        min_files: 10,
+        ..Default::default()
+    };
+    let previous: HashMap<String, String> = (0..10)
+        .map(|i| (format!("docs/file{}.docx", i), format!("h{}", i)))
+        .collect();
+
+    let mut modified = previous.clone();
+    for (i, hash) in modified.values_mut().enumerate() {
+        if i < 9 {
+            hash.push('x');
+        }
+    }
+    let reason = BackupManager::detect_change_anomaly(&previous, &modified, &config)
+        .expect("90% modified is anomalous");
+    assert!(reason.contains("9 of 10 files modified"));
+
+    // 50% modified stays below the default 80% threshold.
+    let mut some_modified = previous.clone();
+    for i in 0..5 {
+        some_modified.insert(format!("docs/file{}.docx", i), "changed".to_string());
+    }
+    assert!(BackupManager::detect_change_anomaly(&previous, &some_modified, &config).is_none());
+
+    // Four files replaced by an encrypted copy with an extra extension.
+    let mut renamed = previous.clone();
+    for i in 0..4 {
+        renamed.remove(&format!("docs/file{}.docx", i));
+        renamed.insert(format!("docs/file{}.docx.locked", i), "enc".to_string());
+    }
+    let reason = BackupManager::detect_change_anomaly(&previous, &renamed, &config)
+        .expect("40% renamed is anomalous");
+    assert!(reason.contains("4 of 10 files renamed"));
+
+    let disabled = AnomalyDetectionConfig::default();
+    assert!(BackupManager::detect_change_anomaly(&previous, &modified, &disabled).is_none());
+    let small = AnomalyDetectionConfig {
+        min_files: 11,
+        ..config
+    };
+    assert!(BackupManager::detect_change_anomaly(&previous, &modified, &small).is_none());
+}
+
+#[tokio::test]
+async fn anomalous_incremental_is_paused_until_changes_are_accepted() -> Result<()> {
+    let base = unique_temp_dir("ardiex_anomaly_pause");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    for i in 0..20 {
+        fs::write(
+            source_dir.join(format!("doc{}.txt", i)),
+            format!("original {}", i),
+        )?;
+    }
+
+    let mut config = make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Copy,
+        10,
+        vec![],
+    );
+    config.anomaly_detection.enabled = true;
+    let mut manager = BackupManager::new(config.clone());
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    std::thread::sleep(Duration::from_millis(5));
+    for i in 0..20 {
+        fs::write(
+            source_dir.join(format!("doc{}.txt", i)),
+            b"encrypted garbage",
+        )?;
+    }
+    let results = manager.backup_all_sources().await?;
+    assert!(results.is_empty());
+    assert_eq!(list_backup_dirs(&backup_dir)?.len(), 1);
+    let metadata = load_metadata_with_hashes(&backup_dir)?;
+    assert_eq!(metadata.backup_history.len(), 1);
+    assert_eq!(metadata.file_hashes.len(), 20);
+
+    // Still paused on the next run: the recorded hashes were not updated.
+    assert!(manager.backup_all_sources().await?.is_empty());
+
+    let mut accepting = BackupManager::new(config).accept_changes();
+    accepting.validate_all_sources()?;
+    let results = accepting.backup_all_sources().await?;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].files_backed_up, 20);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+/// Read-only files block `remove_dir_all` on some platforms.
+fn make_writable(dir: &Path) -> Result<()> {
+    for entry in walkdir::WalkDir::new(dir) {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            let mut permissions = entry.metadata()?.permissions();
+            if permissions.readonly() {
+                #[allow(clippy::permissions_set_readonly_false)]
+                permissions.set_readonly(false);
+                fs::set_permissions(entry.path(), permissions)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn hard_links_are_recreated_on_restore() -> Result<()> {
+    use crate::restore::{RestoreManager, RestoreOptions};
+    use std::os::unix::fs::MetadataExt;
+
+    let base = unique_temp_dir("ardiex_hard_links");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(source_dir.join("cur"))?;
+    fs::write(source_dir.join("cur").join("mail1.eml"), b"hello")?;
+    fs::hard_link(
+        source_dir.join("cur").join("mail1.eml"),
+        source_dir.join("linked.eml"),
+    )?;
+
+    let config = make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Delta,
+        10,
+        vec![],
+    );
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    let first = base.join("restore_first");
+    RestoreManager::restore_to_point(&backup_dir, &first, None, &RestoreOptions::default())?;
+    let primary = fs::metadata(first.join("cur").join("mail1.eml"))?;
+    let link = fs::metadata(first.join("linked.eml"))?;
+    assert_eq!(primary.ino(), link.ino());
+    assert_eq!(primary.nlink(), 2);
+
+    // Break the link in the source: restoring the new copy must not clobber
+    // the file it used to share an inode with.
+    std::thread::sleep(Duration::from_millis(5));
+    fs::remove_file(source_dir.join("linked.eml"))?;
+    fs::write(source_dir.join("linked.eml"), b"independent")?;
+    manager.backup_all_sources().await?;
+
+    let second = base.join("restore_second");
+    RestoreManager::restore_to_point(&backup_dir, &second, None, &RestoreOptions::default())?;
+    assert_eq!(fs::read(second.join("cur").join("mail1.eml"))?, b"hello");
+    assert_eq!(fs::read(second.join("linked.eml"))?, b"independent");
+    assert_eq!(fs::metadata(second.join("linked.eml"))?.nlink(), 1);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn hardlinked_full_shares_unchanged_files_with_previous_full() -> Result<()> {
+    use crate::restore::{RestoreManager, RestoreOptions};
+    use std::os::unix::fs::MetadataExt;
+
+    let base = unique_temp_dir("ardiex_hardlink_fulls");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    let restore_dir = base.join("restore");
+    fs::create_dir_all(source_dir.join("docs"))?;
+    fs::write(source_dir.join("docs/stable.txt"), b"unchanged")?;
+    fs::write(source_dir.join("changing.txt"), b"v1")?;
+
+    let mut config = make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Delta,
+        2,
+        vec![],
+    );
+    // max_backups 2 forces a full after every incremental.
+    config.hardlink_full_backups = true;
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+    let ino = |path: PathBuf| fs::metadata(path).map(|m| m.ino());
+    let first_full = find_latest_dir_with_prefix(&backup_dir, "full_")?;
+    // Read before rotation removes the first full.
+    let stable_ino = ino(first_full.join("docs/stable.txt"))?;
+    let changing_ino = ino(first_full.join("changing.txt"))?;
+
+    std::thread::sleep(Duration::from_millis(5));
+    fs::write(source_dir.join("changing.txt"), b"v2")?;
+    manager.backup_all_sources().await?;
+    // The interval is reached, so the next run is a forced full.
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+    let second_full = find_latest_dir_with_prefix(&backup_dir, "full_")?;
+    assert_ne!(first_full, second_full);
+
+    assert_eq!(ino(second_full.join("docs/stable.txt"))?, stable_ino);
+    assert_ne!(ino(second_full.join("changing.txt"))?, changing_ino);
+    assert_eq!(fs::read(second_full.join("changing.txt"))?, b"v2");
+
+    let options = RestoreOptions {
+        skip_ownership: true,
+        ..Default::default()
+    };
+    RestoreManager::restore_to_point(&backup_dir, &restore_dir, None, &options)?;
+    assert_eq!(fs::read(restore_dir.join("docs/stable.txt"))?, b"unchanged");
+    assert_eq!(fs::read(restore_dir.join("changing.txt"))?, b"v2");
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn append_only_dir_keeps_every_backup_until_explicit_prune() -> Result<()> {
+    let base = unique_temp_dir("ardiex_append_only");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+
+    let mut config = make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Copy,
+        1,
+        vec![],
+    );
+    config.append_only = true;
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    for i in 0..3 {
+        fs::write(source_dir.join("a.txt"), format!("v{}", i))?;
+        manager.backup_all_sources().await?;
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    assert!(BackupManager::is_append_only(&backup_dir));
+    let points = list_backup_dirs(&backup_dir)?;
+    assert_eq!(points.len(), 3);
+
+    // Without --allow-delete prune only reports.
+    let report =
+        BackupManager::prune_backup_dir(&backup_dir, 1, None, &BackupMode::Copy, false, None)?;
+    assert!(report.append_only);
+    assert_eq!(report.removed, points[..2].to_vec());
+    assert_eq!(list_backup_dirs(&backup_dir)?.len(), 3);
+
+    let report =
+        BackupManager::prune_backup_dir(&backup_dir, 1, None, &BackupMode::Copy, true, None)?;
+    assert_eq!(report.removed.len(), 2);
+    assert_eq!(list_backup_dirs(&backup_dir)?, points[2..].to_vec());
+    let metadata = BackupManager::load_source_metadata(&backup_dir.join("metadata.json"));
+    assert_eq!(metadata.backup_history.len(), 1);
+    assert_eq!(metadata.backup_history[0].backup_name, points[2]);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn signed_metadata_detects_edits_to_metadata_and_backup_points() -> Result<()> {
+    use crate::restore::{RestoreManager, RestoreOptions};
+    use crate::signing::SigningKey;
+
+    let base = unique_temp_dir("ardiex_signed_metadata");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    let key_file = base.join("signing.key");
+    fs::create_dir_all(&source_dir)?;
+    fs::write(&key_file, [7u8; 32])?;
+    fs::write(source_dir.join("a.txt"), b"v1")?;
+
+    let mut config = make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Delta,
+        10,
+        vec![],
+    );
+    config.signing_key_file = Some(key_file.clone());
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+    std::thread::sleep(Duration::from_millis(5));
+    fs::write(source_dir.join("a.txt"), b"v2")?;
+    manager.backup_all_sources().await?;
+
+    let key = SigningKey::load(&key_file)?;
+    let metadata_path = backup_dir.join("metadata.json");
+    let metadata = BackupManager::load_source_metadata(&metadata_path);
+    assert_eq!(metadata.backup_history.len(), 2);
+    assert!(
+        metadata
+            .backup_history
+            .iter()
+            .all(|e| e.signature.is_some())
+    );
+    BackupManager::validate_backup_metadata_history(&backup_dir, Some(&key))?;
+
+    let options = RestoreOptions {
+        skip_ownership: true,
+        signing_key: Some(key.clone()),
+        ..Default::default()
+    };
+    RestoreManager::restore_to_point(&backup_dir, &base.join("restore"), None, &options)?;
+
+    // An edit the history comparison cannot see still breaks the signature.
+    let original = fs::read(&metadata_path)?;
+    let mut edited = metadata.clone();
+    edited.backup_history[0].context = Some("forged".to_string());
+    fs::write(&metadata_path, serde_json::to_string_pretty(&edited)?)?;
+    assert!(BackupManager::validate_backup_metadata_history(&backup_dir, None).is_ok());
+    let err = BackupManager::validate_backup_metadata_history(&backup_dir, Some(&key))
+        .expect_err("edited metadata must fail");
+    assert!(err.to_string().contains("signature mismatch"));
+    assert!(
+        RestoreManager::restore_to_point(&backup_dir, &base.join("restore2"), None, &options)
+            .is_err()
+    );
+    fs::write(&metadata_path, original)?;
+
+    let full = find_latest_dir_with_prefix(&backup_dir, "full_")?;
+    fs::write(full.join("a.txt"), b"v9")?;
+    let err = BackupManager::validate_backup_metadata_history(&backup_dir, Some(&key))
+        .expect_err("edited point must fail");
+    assert!(err.to_string().contains("does not match its signature"));
+    let err = RestoreManager::restore_to_point(&backup_dir, &base.join("restore3"), None, &options)
+        .expect_err("restore of an edited point must fail");
+    assert!(err.to_string().contains("does not match its signature"));
+    assert!(!base.join("restore3").join("a.txt").exists());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn dedup_mode_stores_shared_content_once_and_gc_drops_orphans() -> Result<()> {
+    use crate::restore::{RestoreManager, RestoreOptions};
+
+    let base = unique_temp_dir("ardiex_dedup_mode");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(source_dir.join("sub"))?;
+    fs::write(source_dir.join("a.txt"), b"shared content")?;
+    fs::write(source_dir.join("sub/renamed.txt"), b"shared content")?;
+
+    let mut manager = BackupManager::new(make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Dedup,
+        1,
+        vec![],
+    ));
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+    let store = ChunkStore::open(&backup_dir.join(dedup::DEFAULT_STORE_DIR_NAME));
+    assert_eq!(store.list_chunks()?.len(), 1);
+
+    std::thread::sleep(Duration::from_millis(5));
+    fs::write(source_dir.join("a.txt"), b"changed")?;
+    manager.backup_all_sources().await?;
+    assert_eq!(store.list_chunks()?.len(), 2);
+    let metadata = BackupManager::load_source_metadata(&backup_dir.join("metadata.json"));
+    assert_eq!(metadata.backup_history.len(), 2);
+    assert_eq!(metadata.backup_history[1].files_backed_up, 1);
+    BackupManager::validate_backup_metadata_history(&backup_dir, None)?;
+
+    let restore_dir = base.join("restore");
+    RestoreManager::restore_to_point(&backup_dir, &restore_dir, None, &RestoreOptions::default())?;
+    assert_eq!(fs::read(restore_dir.join("a.txt"))?, b"changed");
+    assert_eq!(
+        fs::read(restore_dir.join("sub/renamed.txt"))?,
+        b"shared content"
+    );
+
+    // Retention keeps the full the inc builds on; the old content of a.txt
+    // is still referenced by sub/renamed.txt, so nothing is garbage.
+    let reports = BackupManager::collect_garbage(std::slice::from_ref(&backup_dir), &[], true)?;
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].unreferenced, 0);
+
+    // Points removed by hand leave their chunks behind until gc runs.
+    for point in list_backup_dirs(&backup_dir)? {
+        fs::remove_dir_all(backup_dir.join(point))?;
+    }
+    let reports = BackupManager::collect_garbage(std::slice::from_ref(&backup_dir), &[], true)?;
+    assert_eq!(reports[0].unreferenced, 2);
+    assert_eq!(store.list_chunks()?.len(), 2);
+    BackupManager::collect_garbage(std::slice::from_ref(&backup_dir), &[], false)?;
+    assert!(store.list_chunks()?.is_empty());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn gc_keeps_shared_chunks_of_missing_and_unconfigured_dirs() -> Result<()> {
+    let base = unique_temp_dir("ardiex_gc_shared_missing");
+    let store_dir = base.join("store");
+    let mut sources = Vec::new();
+    for name in ["a", "b"] {
+        let source_dir = base.join(format!("source_{}", name));
+        fs::create_dir_all(&source_dir)?;
+        fs::write(source_dir.join("file.txt"), format!("content of {}", name))?;
+        sources.push(make_source(
+            &source_dir,
+            vec![base.join(format!("backup_{}", name))],
+            true,
+        ));
+    }
+    let mut config = make_config(sources, BackupMode::Dedup, 5, vec![]);
+    config.dedup_store = Some(store_dir.clone());
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+
+    let store = ChunkStore::open(&store_dir);
+    assert_eq!(store.list_chunks()?.len(), 2);
+    let backup_a = base.join("backup_a");
+    let backup_b = base.join("backup_b");
+    assert_eq!(
+        dedup::read_referrers(&store_dir)?.map(|dirs| dirs.len()),
+        Some(2)
+    );
+
+    // Source b was removed from the config and its disk is unplugged.
+    let unplugged = base.join("unplugged");
+    fs::rename(&backup_b, &unplugged)?;
+    let stores = std::slice::from_ref(&store_dir);
+    let err = BackupManager::collect_garbage(std::slice::from_ref(&backup_a), stores, false)
+        .expect_err("gc must refuse while a referencing dir is missing");
+    assert!(err.to_string().contains("not available"));
+    assert_eq!(store.list_chunks()?.len(), 2);
+
+    // Plugged back in, its points are found through the store's list.
+    fs::rename(&unplugged, &backup_b)?;
+    let reports = BackupManager::collect_garbage(std::slice::from_ref(&backup_a), stores, false)?;
+    let shared = reports
+        .iter()
+        .find(|report| report.store == store_dir)
+        .expect("shared store report");
+    assert_eq!(shared.unreferenced, 0);
+    assert_eq!(store.list_chunks()?.len(), 2);
+
+    // A store that cannot name its dirs is never swept.
+    fs::remove_file(store_dir.join(dedup::REFERRERS_FILE_NAME))?;
+    assert!(
+        BackupManager::collect_garbage(std::slice::from_ref(&backup_a), stores, false).is_err()
+    );
+    assert_eq!(store.list_chunks()?.len(), 2);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn file_source_keeps_a_delta_chain_and_restores_the_file() -> Result<()> {
+    use crate::restore::{RestoreManager, RestoreOptions};
+
+    let base = unique_temp_dir("ardiex_file_source");
+    let source_file = base.join("data").join("app.db");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(source_file.parent().expect("parent"))?;
+    // A sibling must not be picked up with the file.
+    fs::write(base.join("data").join("other.txt"), b"unrelated")?;
+    let mut content: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+    fs::write(&source_file, &content)?;
+
+    let mut manager = BackupManager::new(make_config(
+        vec![make_source(&source_file, vec![backup_dir.clone()], true)],
+        BackupMode::Delta,
+        10,
+        vec![],
+    ));
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+    std::thread::sleep(Duration::from_millis(5));
+    content[100] ^= 0xff;
+    fs::write(&source_file, &content)?;
+    manager.backup_all_sources().await?;
+
+    let points = list_backup_dirs(&backup_dir)?;
+    assert_eq!(points.len(), 2);
+    assert!(backup_dir.join(&points[0]).join("app.db").is_file());
+    assert!(!backup_dir.join(&points[0]).join("other.txt").exists());
+    assert!(backup_dir.join(&points[1]).join("app.db.delta").is_file());
+    let metadata = load_metadata_with_hashes(&backup_dir)?;
+    assert_eq!(
+        metadata.file_hashes.keys().collect::<Vec<_>>(),
+        vec!["app.db"]
+    );
+
+    let restore_dir = base.join("restore");
+    RestoreManager::restore_to_point(&backup_dir, &restore_dir, None, &RestoreOptions::default())?;
+    assert_eq!(fs::read(restore_dir.join("app.db"))?, content);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn reverse_layout_keeps_the_newest_version_whole_and_restores_every_point() -> Result<()> {
+    use crate::config::DeltaLayout;
+    use crate::restore::{RestoreManager, RestoreOptions};
+
+    let base = unique_temp_dir("ardiex_reverse_layout");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    let mut content: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+    fs::write(source_dir.join("app.db"), &content)?;
+
+    let mut source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    source.delta_layout = Some(DeltaLayout::Reverse);
+    let mut manager = BackupManager::new(make_config(vec![source], BackupMode::Delta, 10, vec![]));
+    manager.validate_all_sources()?;
+    let mut versions = vec![content.clone()];
+    manager.backup_all_sources().await?;
+    for byte in [100, 200] {
+        std::thread::sleep(Duration::from_millis(5));
+        content[byte] ^= 0xff;
+        fs::write(source_dir.join("app.db"), &content)?;
+        manager.backup_all_sources().await?;
+        versions.push(content.clone());
+    }
+
+    let points = list_backup_dirs(&backup_dir)?;
+    assert_eq!(points.len(), 3);
+    assert!(backup_dir.join(&points[0]).join("app.db.rdelta").is_file());
+    assert!(backup_dir.join(&points[1]).join("app.db.rdelta").is_file());
+    assert_eq!(
+        fs::read(backup_dir.join(&points[2]).join("app.db"))?,
+        content
+    );
+    let metadata = load_metadata_with_hashes(&backup_dir)?;
+    let flagged: Vec<bool> = metadata
+        .backup_history
+        .iter()
+        .map(|entry| entry.reverse_deltas)
+        .collect();
+    assert_eq!(flagged, vec![true, true, false]);
+    // The rewritten points were resealed.
+    let report = BackupManager::check_backup_dir(&backup_dir, false, None)?;
+    assert_eq!(report.error_count(), 0, "{:?}", report.findings);
+
+    for (point, version) in points.iter().zip(&versions) {
+        let restore_dir = base.join(format!("restore_{}", point));
+        let timestamp = point.split_once('_').map(|(_, timestamp)| timestamp);
+        RestoreManager::restore_to_point(
+            &backup_dir,
+            &restore_dir,
+            timestamp,
+            &RestoreOptions::default(),
+        )?;
+        assert_eq!(&fs::read(restore_dir.join("app.db"))?, version);
+    }
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn points_forwarded_before_a_reverse_rewrite_restore_from_the_remote_with_a_key() -> Result<()>
+{
+    use crate::config::DeltaLayout;
+    use crate::restore::{RestoreManager, RestoreOptions};
+    use crate::signing::SigningKey;
+
+    let base = unique_temp_dir("ardiex_forward_reverse");
+    let source_dir = base.join("source");
+    let local_dir = base.join("local");
+    let forward_dir = base.join("nas");
+    let key_file = base.join("signing.key");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&forward_dir)?;
+    fs::write(&key_file, [7u8; 32])?;
+    let mut content: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+    fs::write(source_dir.join("app.db"), &content)?;
+
+    let mut source = make_source(&source_dir, vec![local_dir.clone()], true);
+    source.delta_layout = Some(DeltaLayout::Reverse);
+    let mut config = make_config(vec![source.clone()], BackupMode::Delta, 10, vec![]);
+    config.signing_key_file = Some(key_file.clone());
+    let resolved = source.resolve(&config);
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    let mut versions = vec![content.clone()];
+    manager.backup_all_sources().await?;
+    BackupManager::forward_queued_backups(&local_dir, &forward_dir, &resolved)?;
+    for byte in [100, 200] {
+        std::thread::sleep(Duration::from_millis(5));
+        content[byte] ^= 0xff;
+        fs::write(source_dir.join("app.db"), &content)?;
+        // Rewrites the points already sent into reverse deltas locally.
+        manager.backup_all_sources().await?;
+        BackupManager::forward_queued_backups(&local_dir, &forward_dir, &resolved)?;
+        versions.push(content.clone());
+    }
+
+    let points = list_backup_dirs(&forward_dir)?;
+    assert_eq!(points, list_backup_dirs(&local_dir)?);
+    assert!(local_dir.join(&points[0]).join("app.db.rdelta").is_file());
+    let key = SigningKey::load(&key_file)?;
+    BackupManager::validate_backup_metadata_history(&forward_dir, Some(&key))?;
+
+    let options = RestoreOptions {
+        skip_ownership: true,
+        signing_key: Some(key),
+        ..Default::default()
+    };
+    for (point, version) in points.iter().zip(&versions) {
+        let restore_dir = base.join(format!("restore_{}", point));
+        let timestamp = point.split_once('_').map(|(_, timestamp)| timestamp);
+        RestoreManager::restore_to_point(&forward_dir, &restore_dir, timestamp, &options)?;
+        assert_eq!(&fs::read(restore_dir.join("app.db"))?, version);
+    }
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn interrupted_reverse_rewrite_is_kept_or_undone_to_match_the_seal() -> Result<()> {
+    use crate::config::DeltaLayout;
+
+    let base = unique_temp_dir("ardiex_reverse_crash");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    let mut content: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+    fs::write(source_dir.join("app.db"), &content)?;
+
+    let mut source = make_source(&source_dir, vec![backup_dir.clone()], true);
+    source.delta_layout = Some(DeltaLayout::Reverse);
+    let mut manager = BackupManager::new(make_config(vec![source], BackupMode::Delta, 10, vec![]));
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+    let mut middle = Vec::new();
+    for byte in [100, 200] {
+        std::thread::sleep(Duration::from_millis(5));
+        content[byte] ^= 0xff;
+        fs::write(source_dir.join("app.db"), &content)?;
+        manager.backup_all_sources().await?;
+        if middle.is_empty() {
+            middle = content.clone();
+        }
+    }
+    let points = list_backup_dirs(&backup_dir)?;
+    let point = backup_dir.join(&points[1]);
+    let pending = backup_dir.join(reverse::REVERSE_PENDING_DIR);
+    let leave_pending = || -> Result<()> {
+        fs::create_dir_all(pending.join("kept"))?;
+        fs::write(pending.join("kept").join("app.db"), &middle)?;
+        fs::write(
+            pending.join("intent.json"),
+            format!(r#"{{"point":"{}","files":["app.db"]}}"#, points[1]),
+        )?;
+        Ok(())
+    };
+
+    // Crashed after the resealed metadata was saved: the swap stands.
+    leave_pending()?;
+    let report = BackupManager::repair_backup_dir(&backup_dir, false, None)?;
+    assert!(report.actions[0].starts_with("Keep the reverse deltas"));
+    assert!(!pending.exists());
+    assert!(point.join("app.db.rdelta").is_file());
+    let check = BackupManager::check_backup_dir(&backup_dir, false, None)?;
+    assert_eq!(check.error_count(), 0, "{:?}", check.findings);
+
+    // Crashed before it was saved: metadata.json still holds the old seal.
+    fs::rename(point.join("app.db.rdelta"), base.join("app.db.rdelta"))?;
+    fs::write(point.join("app.db"), &middle)?;
+    let old_checksum = BackupManager::calculate_incremental_backup_checksum(&point)?;
+    fs::remove_file(point.join("app.db"))?;
+    fs::rename(base.join("app.db.rdelta"), point.join("app.db.rdelta"))?;
+    let metadata_path = backup_dir.join("metadata.json");
+    let mut metadata = BackupManager::load_source_metadata(&metadata_path);
+    let entry = metadata
+        .backup_history
+        .iter_mut()
+        .find(|entry| entry.backup_name == points[1])
+        .expect("history entry");
+    entry.inc_checksum = Some(old_checksum);
+    entry.reverse_deltas = false;
+    BackupManager::save_source_metadata(&metadata_path, &metadata, None)?;
+    leave_pending()?;
+
+    let dry = BackupManager::repair_backup_dir(&backup_dir, true, None)?;
+    assert!(dry.actions[0].starts_with("Undo the interrupted"));
+    assert!(pending.exists());
+    let report = BackupManager::repair_backup_dir(&backup_dir, false, None)?;
+    assert_eq!(report.actions, dry.actions);
+    assert!(!pending.exists());
+    assert!(!point.join("app.db.rdelta").exists());
+    assert_eq!(fs::read(point.join("app.db"))?, middle);
+    let check = BackupManager::check_backup_dir(&backup_dir, false, None)?;
+    assert_eq!(check.error_count(), 0, "{:?}", check.findings);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn compact_rewrites_a_forward_chain_into_the_reverse_layout() -> Result<()> {
+    use crate::restore::{RestoreManager, RestoreOptions};
+
+    let base = unique_temp_dir("ardiex_compact");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    let mut content: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+    fs::write(source_dir.join("app.db"), &content)?;
+    fs::write(source_dir.join("notes.txt"), b"unchanged")?;
+
+    let mut manager = BackupManager::new(make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Delta,
+        10,
+        vec![],
+    ));
+    manager.validate_all_sources()?;
+    let mut versions = vec![content.clone()];
+    manager.backup_all_sources().await?;
+    for byte in [100, 200] {
+        std::thread::sleep(Duration::from_millis(5));
+        content[byte] ^= 0xff;
+        fs::write(source_dir.join("app.db"), &content)?;
+        manager.backup_all_sources().await?;
+        versions.push(content.clone());
+    }
+    let points = list_backup_dirs(&backup_dir)?;
+    assert!(backup_dir.join(&points[2]).join("app.db.delta").is_file());
+
+    let report = BackupManager::compact_backup_dir(&backup_dir, None, true, None)?;
+    assert_eq!(report.versions_rewritten, 3);
+    assert!(backup_dir.join(&points[2]).join("app.db.delta").is_file());
+
+    let report = BackupManager::compact_backup_dir(&backup_dir, None, false, None)?;
+    assert_eq!(report.points_rewritten, points);
+    assert!(report.bytes_after < report.bytes_before + 1024);
+    assert!(backup_dir.join(&points[0]).join("app.db.rdelta").is_file());
+    assert!(backup_dir.join(&points[1]).join("app.db.rdelta").is_file());
+    assert_eq!(
+        fs::read(backup_dir.join(&points[2]).join("app.db"))?,
+        content
+    );
+    // Only one version of notes.txt, so it stays in the full.
+    assert!(backup_dir.join(&points[0]).join("notes.txt").is_file());
+    let report = BackupManager::check_backup_dir(&backup_dir, false, None)?;
+    assert_eq!(report.error_count(), 0, "{:?}", report.findings);
+
+    for (point, version) in points.iter().zip(&versions) {
+        let restore_dir = base.join(format!("restore_{}", point));
+        let timestamp = point.split_once('_').map(|(_, timestamp)| timestamp);
+        RestoreManager::restore_to_point(
+            &backup_dir,
+            &restore_dir,
+            timestamp,
+            &RestoreOptions::default(),
+        )?;
+        assert_eq!(&fs::read(restore_dir.join("app.db"))?, version);
+        assert_eq!(fs::read(restore_dir.join("notes.txt"))?, b"unchanged");
+    }
+
+    // A second run finds nothing left to do.
+    let report = BackupManager::compact_backup_dir(&backup_dir, None, false, None)?;
+    assert!(report.points_rewritten.is_empty());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn trees_deeper_than_max_path_back_up_and_restore() -> Result<()> {
+    use crate::restore::{RestoreManager, RestoreOptions};
+
+    let base = unique_temp_dir("ardiex_long_paths");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    let mut deep_dir = source_dir.clone();
+    for i in 0..6 {
+        deep_dir = deep_dir.join(format!("{}_{}", "nested_directory_level".repeat(2), i));
+    }
+    fs::create_dir_all(&deep_dir)?;
+    let deep_file = deep_dir.join("report_with_a_fairly_long_file_name.txt");
+    assert!(deep_file.strip_prefix(&base)?.as_os_str().len() > 260);
+    fs::write(&deep_file, b"v1")?;
+
+    let mut manager = BackupManager::new(make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Delta,
+        10,
+        vec![],
+    ));
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+    std::thread::sleep(Duration::from_millis(5));
+    fs::write(&deep_file, b"v2")?;
+    let results = manager.backup_all_sources().await?;
+    assert_eq!(results[0].backup_dir, backup_dir);
+    assert_eq!(results[0].files_backed_up, 1);
+
+    let restore_dir = base.join("restore");
+    RestoreManager::restore_to_point(&backup_dir, &restore_dir, None, &RestoreOptions::default())?;
+    let relative = deep_file.strip_prefix(&source_dir)?;
+    assert_eq!(fs::read(restore_dir.join(relative))?, b"v2");
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn destinations_apply_their_own_mode_retention_and_schedule() -> Result<()> {
+    let base = unique_temp_dir("ardiex_destination_overrides");
+    let source_dir = base.join("source");
+    let local = base.join("local");
+    let nas = base.join("nas");
+    fs::create_dir_all(&source_dir)?;
+    let file_path = source_dir.join("sample.txt");
+    fs::write(&file_path, b"v1")?;
+
+    let mut source = make_source(&source_dir, vec![local.clone(), nas.clone()], true);
+    source.backup_dirs[1] = BackupDestination {
+        path: nas.clone(),
+        backup_mode: Some(BackupMode::Copy),
+        max_backups: Some(1),
+        ..Default::default()
+    };
+    let config = make_config(vec![source], BackupMode::Delta, 10, vec![]);
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+
+    for version in ["v2", "v3"] {
+        manager.backup_all_sources().await?;
+        std::thread::sleep(Duration::from_millis(5));
+        fs::write(&file_path, version)?;
+    }
+    manager.backup_all_sources().await?;
+
+    assert_eq!(list_backup_dirs(&local)?.len(), 3);
+    assert_eq!(list_backup_dirs(&nas)?.len(), 1);
+
+    // Once backed up, a destination on a yearly schedule is not due again.
+    let now = Utc::now();
+    assert!(BackupManager::destination_due("0 0 0 1 1 *", None, now));
+    assert!(!BackupManager::destination_due(
+        "0 0 0 1 1 *",
+        Some(now),
+        now
+    ));
+    assert!(BackupManager::destination_due(
+        "0 * * * * *",
+        Some(now - chrono::Duration::minutes(2)),
+        now
+    ));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn each_run_writes_a_report_into_the_backup_dir() -> Result<()> {
+    let base = unique_temp_dir("ardiex_backup_report_test");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+    let mut content = vec![7u8; 256 * 1024];
+    fs::write(source_dir.join("large.bin"), &content)?;
+    fs::write(source_dir.join("same.txt"), b"same")?;
+
+    let mut config = make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Delta,
+        10,
+        vec![],
+    );
+    config.max_backup_reports = 2;
+    let mut manager = BackupManager::new(config);
+    manager.backup_all_sources().await?;
+
+    std::thread::sleep(Duration::from_millis(5));
+    content[1000] = 8;
+    fs::write(source_dir.join("large.bin"), &content)?;
+    fs::write(source_dir.join("new.txt"), b"new")?;
+    let results = manager.backup_all_sources().await?;
+    assert_eq!(results[0].files_added, 1);
+    assert_eq!(results[0].files_changed, 1);
+    assert!(results[0].delta_saved_bytes > 200 * 1024);
+
+    let report_dir = backup_dir.join(report::REPORT_DIR);
+    let mut names: Vec<String> = fs::read_dir(&report_dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    assert_eq!(names.len(), 4);
+    let latest: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(report_dir.join(&names[2]))?)?;
+    assert_eq!(latest["status"], "success");
+    assert_eq!(latest["backup_type"], "inc");
+    assert_eq!(latest["files_added"], 1);
+    assert_eq!(latest["files_changed"], 1);
+    let text = fs::read_to_string(report_dir.join(&names[3]))?;
+    assert!(text.contains("1 added, 1 changed, 0 unchanged, 0 skipped"));
+
+    // Only the newest two runs are kept.
+    std::thread::sleep(Duration::from_millis(5));
+    manager.backup_all_sources().await?;
+    let remaining: Vec<String> = fs::read_dir(&report_dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(remaining.len(), 4);
+    assert!(!remaining.iter().any(|name| *name == names[0]));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn unbacked_changes_lists_what_an_in_place_restore_would_lose() -> Result<()> {
+    let base = unique_temp_dir("ardiex_unbacked_changes_test");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+    fs::write(source_dir.join("kept.txt"), b"kept")?;
+    fs::write(source_dir.join("edited.txt"), b"before")?;
+
+    let config = make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Copy,
+        10,
+        vec![],
+    );
+    let source = config.sources[0].clone();
+    let resolved = source.resolve(&config);
+    let mut manager = BackupManager::new(config);
+    manager.backup_all_sources().await?;
+    assert!(BackupManager::unbacked_changes(&source, &resolved, &backup_dir)?.is_empty());
+    assert_eq!(
+        BackupManager::reported_source_dir(&backup_dir),
+        Some(source_dir.clone())
+    );
+
+    fs::write(source_dir.join("edited.txt"), b"after")?;
+    fs::create_dir_all(source_dir.join("sub"))?;
+    fs::write(source_dir.join("sub/new.txt"), b"new")?;
+    assert_eq!(
+        BackupManager::unbacked_changes(&source, &resolved, &backup_dir)?,
+        vec!["edited.txt".to_string(), "sub/new.txt".to_string()]
+    );
+
+    // A deleted source has nothing left to lose.
+    fs::remove_dir_all(&source_dir)?;
+    assert!(BackupManager::unbacked_changes(&source, &resolved, &backup_dir)?.is_empty());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn mirror_mode_tracks_the_source_and_keeps_replaced_versions() -> Result<()> {
+    let base = unique_temp_dir("ardiex_mirror_mode");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(source_dir.join("docs"))?;
+    fs::write(source_dir.join("docs/gone.txt"), b"old")?;
+    fs::write(source_dir.join("edited.txt"), b"v1")?;
+
+    let mut config = make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Mirror,
+        5,
+        vec![],
+    );
+    config.mirror_versions = true;
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+    let current = backup_dir.join(super::mirror::MIRROR_DIR);
+    assert_eq!(fs::read(current.join("docs/gone.txt"))?, b"old");
+    assert_eq!(fs::read(current.join("edited.txt"))?, b"v1");
+
+    std::thread::sleep(Duration::from_millis(5));
+    fs::remove_file(source_dir.join("docs/gone.txt"))?;
+    fs::write(source_dir.join("edited.txt"), b"v2")?;
+    fs::write(source_dir.join("added.txt"), b"new")?;
+    manager.backup_all_sources().await?;
+
+    assert_eq!(fs::read(current.join("edited.txt"))?, b"v2");
+    assert_eq!(fs::read(current.join("added.txt"))?, b"new");
+    assert!(!current.join("docs").exists());
+    assert!(find_latest_dir_with_prefix(&backup_dir, "full_").is_err());
+
+    let versions: Vec<PathBuf> = fs::read_dir(backup_dir.join(super::mirror::VERSIONS_DIR))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<_>>()?;
+    assert_eq!(versions.len(), 1);
+    assert_eq!(fs::read(versions[0].join("edited.txt"))?, b"v1");
+    assert_eq!(fs::read(versions[0].join("docs/gone.txt"))?, b"old");
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn mirror_refuses_to_empty_the_replica_for_an_empty_source() -> Result<()> {
+    let base = unique_temp_dir("ardiex_mirror_empty_source");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(source_dir.join("docs"))?;
+    fs::write(source_dir.join("docs/a.txt"), b"a")?;
+    fs::write(source_dir.join("b.txt"), b"b")?;
+
+    let config = make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Mirror,
+        5,
+        vec![],
+    );
+    let mut manager = BackupManager::new(config.clone());
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+    let current = backup_dir.join(super::mirror::MIRROR_DIR);
+
+    // Looks like an unmounted volume: the dir is there but empty.
+    std::thread::sleep(Duration::from_millis(5));
+    fs::remove_dir_all(&source_dir)?;
+    fs::create_dir_all(&source_dir)?;
+    let results = manager.backup_all_sources().await?;
+    assert!(results.is_empty());
+    assert_eq!(fs::read(current.join("docs/a.txt"))?, b"a");
+    assert_eq!(fs::read(current.join("b.txt"))?, b"b");
+
+    let mut manager = BackupManager::new(config).accept_changes();
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+    assert!(!current.join("b.txt").exists());
+    assert!(!current.join("docs").exists());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn import_seed_adopts_a_copy_so_the_next_backup_is_incremental() -> Result<()> {
+    let base = unique_temp_dir("ardiex_import_seed");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(source_dir.join("docs"))?;
+    fs::create_dir_all(backup_dir.join("docs"))?;
+    for dir in [&source_dir, &backup_dir] {
+        fs::write(dir.join("docs/same.txt"), b"unchanged")?;
+        fs::write(dir.join("skip.tmp"), b"excluded")?;
+    }
+    fs::write(source_dir.join("edited.txt"), b"new")?;
+    fs::write(backup_dir.join("edited.txt"), b"old")?;
+
+    let config = make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Delta,
+        5,
+        vec!["*.tmp".to_string()],
+    );
+    let source = config.sources[0].clone();
+    let resolved = source.resolve_for(&config, &backup_dir);
+    let seeded = BackupManager::import_seed(&source, &resolved, &backup_dir)?;
+    assert_eq!(seeded.files_adopted, 2);
+    assert_eq!(seeded.files_untracked, 1);
+    let full = backup_dir.join(&seeded.backup_name);
+    assert_eq!(fs::read(full.join("docs/same.txt"))?, b"unchanged");
+    assert!(!backup_dir.join("docs").exists());
+    assert!(BackupManager::import_seed(&source, &resolved, &backup_dir).is_err());
+
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    let results = manager.backup_all_sources().await?;
+    assert!(matches!(results[0].backup_type, BackupType::Incremental));
+    assert_eq!(results[0].files_backed_up, 1);
+    assert_eq!(results[0].files_changed, 1);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn replicate_copies_only_the_points_the_destination_lacks() -> Result<()> {
+    let base = unique_temp_dir("ardiex_replicate");
+    let source_dir = base.join("source");
+    let backup_dir = base.join("backup");
+    let replica = base.join("offsite");
+    fs::create_dir_all(&source_dir)?;
+    fs::write(source_dir.join("a.txt"), b"v1")?;
+
+    let config = make_config(
+        vec![make_source(&source_dir, vec![backup_dir.clone()], true)],
+        BackupMode::Delta,
+        5,
+        vec![],
+    );
+    let mut manager = BackupManager::new(config);
+    manager.validate_all_sources()?;
+    manager.backup_all_sources().await?;
+    std::thread::sleep(Duration::from_millis(5));
+    fs::write(source_dir.join("a.txt"), b"v2")?;
+    manager.backup_all_sources().await?;
+
+    let first = BackupManager::replicate_backup_dir(&backup_dir, &replica, None, None)?;
+    assert_eq!(first.copied.len(), 2);
+    assert_eq!(first.already_present, 0);
+
+    std::thread::sleep(Duration::from_millis(5));
+    fs::write(source_dir.join("b.txt"), b"new")?;
+    manager.backup_all_sources().await?;
+    let second = BackupManager::replicate_backup_dir(&backup_dir, &replica, None, None)?;
+    assert_eq!(second.copied.len(), 1);
+    assert_eq!(second.already_present, 2);
+
+    let local = load_metadata_with_hashes(&backup_dir)?;
+    let copied = load_metadata_with_hashes(&replica)?;
+    let names = |metadata: &SourceMetadata| -> Vec<String> {
+        metadata
+            .backup_history
+            .iter()
+            .map(|entry| entry.backup_name.clone())
+            .collect()
+    };
+    assert_eq!(names(&copied), names(&local));
+    assert_eq!(copied.file_hashes, local.file_hashes);
+    assert!(BackupManager::replicate_backup_dir(&backup_dir, &backup_dir, None, None).is_err());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn backup_refuses_a_dir_recorded_for_another_source() -> Result<()> {
+    let base = unique_temp_dir("ardiex_backup_origin");
+    let first_source = base.join("first");
+    let second_source = base.join("second");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(&first_source)?;
+    fs::create_dir_all(&second_source)?;
+    fs::write(first_source.join("a.txt"), b"a")?;
+    fs::write(second_source.join("b.txt"), b"b")?;
+
+    let config = make_config(
+        vec![make_source(&first_source, vec![backup_dir.clone()], true)],
+        BackupMode::Delta,
+        5,
+        vec![],
+    );
+    BackupManager::new(config).backup_all_sources().await?;
+    let metadata = BackupManager::load_source_metadata(&backup_dir.join("metadata.json"));
+    let origin = metadata.origin.expect("origin recorded");
+    assert_eq!(origin.source_dir, first_source);
+
+    let config = make_config(
+        vec![make_source(&second_source, vec![backup_dir.clone()], true)],
+        BackupMode::Delta,
+        5,
+        vec![],
+    );
+    let error = BackupManager::perform_backup_to_dir(
+        &config.sources[0],
+        &backup_dir,
+        &config.sources[0].resolve(&config),
+        false,
+        &RunOptions::default(),
+        None,
+        &Cancellation::default(),
+    )
+    .await
+    .expect_err("second source must not share the dir");
+    assert!(error.to_string().contains("holds backups of"));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use super::*;
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let ts = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), ts))
+}
+
+#[test]
+fn run_measures_every_stage_and_cleans_up() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_bench_run");
+    fs::create_dir_all(&dir)?;
+
+    let report = run(&dir, 1024 * 1024)?;
+    let algorithms: Vec<_> = report.hash.iter().map(|run| run.algorithm).collect();
+    assert_eq!(algorithms, vec!["sha256", "blake3"]);
+    assert_eq!(report.copy.len(), WORKER_COUNTS.len());
+    assert_eq!(report.delta.len(), BLOCK_SIZES.len());
+    // Smaller blocks carry less unchanged data around each edit.
+    assert!(report.delta[0].delta_bytes < report.delta[4].delta_bytes);
+    let settings: Vec<_> = report
+        .recommendations
+        .iter()
+        .map(|recommendation| recommendation.setting)
+        .collect();
+    assert_eq!(settings, vec!["max_concurrent_backups", "delta_block_size"]);
+    let block_size: usize = report.recommendations[1].value.parse()?;
+    assert!(delta::is_valid_block_size(block_size));
+    assert_eq!(fs::read_dir(&dir)?.count(), 0);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn recommendations_trade_speed_for_fewer_workers_and_smaller_deltas() {
+    let copy =
+        [(1, 100.0), (2, 185.0), (4, 200.0), (8, 190.0)].map(|(workers, mb_per_sec)| CopyResult {
+            workers,
+            mb_per_sec,
+        });
+    assert_eq!(recommend_workers(&copy).map(|r| r.value), Some("2".into()));
+
+    let delta = [
+        (1024, 80.0, 1_000),
+        (4096, 95.0, 3_000),
+        (16384, 100.0, 9_000),
+    ]
+    .map(|(block_size, mb_per_sec, delta_bytes)| DeltaResult {
+        block_size,
+        mb_per_sec,
+        delta_bytes,
+    });
+    assert_eq!(
+        recommend_block_size(&delta).map(|r| r.value),
+        Some("4096".into())
+    );
+}
@@ -0,0 +1,72 @@
+use super::*;
+use chrono::NaiveDate;
+
+fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+    // 2026-03-02 is a Monday.
+    NaiveDate::from_ymd_opt(2026, 3, day)
+        .and_then(|d| d.and_hms_opt(hour, minute, 0))
+        .expect("valid date")
+}
+
+fn windows(specs: &[&str]) -> Vec<BlackoutWindow> {
+    specs
+        .iter()
+        .map(|spec| spec.parse().expect("valid window"))
+        .collect()
+}
+
+#[test]
+fn weekday_office_hours_defer_until_the_window_ends() {
+    let windows = windows(&["Mon-Fri 09:00-18:00"]);
+
+    assert_eq!(blackout_until(&windows, at(2, 10, 30)), Some(at(2, 18, 0)));
+    assert_eq!(blackout_until(&windows, at(2, 18, 0)), None);
+    assert_eq!(blackout_until(&windows, at(2, 8, 59)), None);
+    // Saturday is outside the listed days.
+    assert_eq!(blackout_until(&windows, at(7, 10, 0)), None);
+}
+
+#[test]
+fn overnight_windows_and_adjacent_windows_chain() {
+    let windows = windows(&["22:00-06:00", "Tue 06:00-07:30"]);
+
+    // Monday 23:00 runs into the Tuesday morning window.
+    assert_eq!(blackout_until(&windows, at(2, 23, 0)), Some(at(3, 7, 30)));
+    // Tuesday 01:00 started on Monday's window.
+    assert_eq!(blackout_until(&windows, at(3, 1, 0)), Some(at(3, 7, 30)));
+    assert_eq!(blackout_until(&windows, at(4, 5, 0)), Some(at(4, 6, 0)));
+}
+
+#[test]
+fn windows_round_trip_through_their_text_form() {
+    for spec in [
+        "Mon-Fri 09:00-18:00",
+        "Sat,Sun 00:00-24:00",
+        "Mon,Wed-Fri 12:00-13:00",
+        "22:00-06:00",
+    ] {
+        let window: BlackoutWindow = spec.parse().expect("valid window");
+        assert_eq!(window.to_string(), spec);
+    }
+    let wrapped: BlackoutWindow = "Fri-Mon 10:00-11:00".parse().expect("valid window");
+    assert_eq!(wrapped.to_string(), "Mon,Fri-Sun 10:00-11:00");
+}
+
+#[test]
+fn malformed_windows_are_rejected() {
+    for spec in [
+        "Mon-Fri",
+        "Funday 09:00-10:00",
+        "09:00-25:00",
+        "24:00-01:00",
+        "9-10",
+    ] {
+        assert!(
+            spec.parse::<BlackoutWindow>().is_err(),
+            "{} must fail",
+            spec
+        );
+    }
+    let err = serde_json::from_str::<BlackoutWindow>("\"Mon 9:00\"").expect_err("invalid");
+    assert!(err.to_string().contains("Invalid blackout window"));
+}
@@ -0,0 +1,45 @@
+use super::*;
+
+#[test]
+fn relative_changes_keep_paths_under_the_source_only() {
+    let root = Path::new("/vol/data/source");
+    let events = vec![
+        (PathBuf::from("/vol/data/source/a.txt"), false),
+        (PathBuf::from("/vol/data/source/docs/b.txt"), true),
+        (PathBuf::from("/vol/data/source/docs/b.txt"), false),
+        (PathBuf::from("/vol/data/source"), false),
+        (PathBuf::from("/vol/data/sourced/c.txt"), true),
+        (PathBuf::from("/vol/other/d.txt"), true),
+        (PathBuf::from("/vol/data"), false),
+    ];
+
+    let changed = relative_changes(root, events).expect("every change accounted for");
+    assert_eq!(
+        changed.into_iter().collect::<Vec<_>>(),
+        vec![PathBuf::from("a.txt"), PathBuf::from("docs/b.txt")]
+    );
+}
+
+#[test]
+fn moving_the_source_or_a_dir_above_it_needs_a_full_scan() {
+    let root = Path::new("/vol/data/source");
+    for moved in ["/vol/data/source", "/vol/data", "/vol"] {
+        let events = vec![
+            (PathBuf::from("/vol/data/source/a.txt"), false),
+            (PathBuf::from(moved), true),
+        ];
+        assert_eq!(relative_changes(root, events), None, "{}", moved);
+    }
+}
+
+#[cfg(any(target_os = "linux", not(feature = "native-change-journal")))]
+#[test]
+fn builds_without_a_journal_backend_fall_back_to_the_full_scan() {
+    let dir = std::env::temp_dir();
+    assert_eq!(current_cursor(&dir), None);
+    let cursor = JournalCursor {
+        journal: "usn:1".to_string(),
+        position: 1,
+    };
+    assert_eq!(changed_since(&dir, &cursor, &cursor), None);
+}
@@ -0,0 +1,108 @@
+use super::*;
+use crate::config::{BackupDestination, BackupMode, SourceConfig};
+use std::path::PathBuf;
+
+#[test]
+fn format_is_picked_by_extension() {
+    assert_eq!(
+        ConfigFormat::of(Path::new("/etc/ardiex/settings.toml")),
+        ConfigFormat::Toml
+    );
+    assert_eq!(
+        ConfigFormat::of(Path::new("settings.TOML")),
+        ConfigFormat::Toml
+    );
+    assert_eq!(
+        ConfigFormat::of(Path::new("settings.json")),
+        ConfigFormat::Json
+    );
+}
+
+#[test]
+fn toml_round_trips_the_same_config_as_json() -> Result<()> {
+    let mut config = BackupConfig::default();
+    config.sources.push(SourceConfig {
+        source_dir: PathBuf::from("/home/user/documents"),
+        backup_dirs: vec![
+            PathBuf::from("/backup/documents").into(),
+            BackupDestination {
+                path: PathBuf::from("/mnt/nas/documents"),
+                backup_mode: Some(BackupMode::Copy),
+                cron_schedule: Some("0 0 2 * * *".to_string()),
+                ..Default::default()
+            },
+        ],
+        enabled: true,
+        max_backups: Some(5),
+        ..Default::default()
+    });
+
+    let toml = ConfigFormat::Toml.render(&config, None)?;
+    assert!(toml.contains("[[sources]]"));
+    let parsed = ConfigFormat::Toml.parse(&toml)?;
+    assert_eq!(
+        serde_json::to_value(&parsed)?,
+        serde_json::to_value(&config)?
+    );
+    Ok(())
+}
+
+#[test]
+fn toml_save_keeps_comments_and_untouched_values() -> Result<()> {
+    let mut config = BackupConfig::default();
+    config.sources.push(SourceConfig {
+        source_dir: PathBuf::from("/home/user/documents"),
+        backup_dirs: vec![PathBuf::from("/backup/documents").into()],
+        enabled: true,
+        ..Default::default()
+    });
+    // Hand edits: comments and a literal string.
+    let previous = format!(
+        "# Backups for the laptop\n{}",
+        ConfigFormat::Toml.render(&config, None)?
+    )
+    .replacen(
+        &format!("max_backups = {}\n", config.max_backups),
+        &format!("max_backups = {} # keep them all\n", config.max_backups),
+        1,
+    )
+    .replacen("[[sources]]\n", "[[sources]]\n# Work documents\n", 1)
+    .replacen(
+        "source_dir = \"/home/user/documents\"",
+        "source_dir = '/home/user/documents'",
+        1,
+    );
+
+    let mut config = ConfigFormat::Toml.parse(&previous)?;
+    config.max_backups = 20;
+    config.sources.push(SourceConfig {
+        source_dir: PathBuf::from("/home/user/photos"),
+        backup_dirs: vec![PathBuf::from("/backup/photos").into()],
+        enabled: true,
+        ..Default::default()
+    });
+
+    let saved = ConfigFormat::Toml.render(&config, Some(&previous))?;
+    assert!(saved.starts_with("# Backups for the laptop\n"));
+    assert!(saved.contains("max_backups = 20 # keep them all\n"));
+    assert!(saved.contains("# Work documents\nsource_dir = '/home/user/documents'"));
+    assert!(saved.contains("/home/user/photos"));
+
+    let reparsed = ConfigFormat::Toml.parse(&saved)?;
+    assert_eq!(reparsed.max_backups, 20);
+    assert_eq!(reparsed.sources.len(), 2);
+
+    config.sources.remove(0);
+    let saved = ConfigFormat::Toml.render(&config, Some(&saved))?;
+    assert!(!saved.contains("/home/user/documents"));
+    assert_eq!(ConfigFormat::Toml.parse(&saved)?.sources.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn invalid_toml_is_rejected() {
+    let err = ConfigFormat::Toml
+        .parse("max_backups = [")
+        .expect_err("broken TOML must fail");
+    assert!(err.to_string().contains("Invalid TOML"));
+}
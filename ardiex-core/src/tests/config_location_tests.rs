@@ -0,0 +1,98 @@
+use super::*;
+use std::fs;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_nanos();
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), ts))
+}
+
+#[test]
+fn explicit_path_is_used_as_given() {
+    let location = locate(
+        Some(PathBuf::from("/etc/ardiex/settings.toml")),
+        Some(PathBuf::from("/home/me/.config/ardiex")),
+        Path::new("/opt/ardiex"),
+    );
+    assert_eq!(
+        location,
+        ConfigLocation {
+            path: PathBuf::from("/etc/ardiex/settings.toml"),
+            migrate_from: None,
+        }
+    );
+}
+
+#[test]
+fn platform_dir_is_the_default_and_exe_adjacent_settings_are_migrated() -> anyhow::Result<()> {
+    let base = unique_temp_dir("ardiex_config_location");
+    let exe_dir = base.join("bin");
+    let platform_dir = base.join("config").join("ardiex");
+    fs::create_dir_all(&exe_dir)?;
+
+    // Fresh install: default file in the platform dir.
+    let location = locate(None, Some(platform_dir.clone()), &exe_dir);
+    assert_eq!(location.path, platform_dir.join(JSON_CONFIG_FILE_NAME));
+    assert_eq!(location.migrate_from, None);
+
+    // Settings left next to the executable move over, keeping their format.
+    fs::write(exe_dir.join(TOML_CONFIG_FILE_NAME), "max_backups = 3\n")?;
+    let location = locate(None, Some(platform_dir.clone()), &exe_dir);
+    assert_eq!(location.path, platform_dir.join(TOML_CONFIG_FILE_NAME));
+    assert_eq!(
+        location.migrate_from,
+        Some(exe_dir.join(TOML_CONFIG_FILE_NAME))
+    );
+
+    // Once the platform dir has a file, it wins.
+    fs::create_dir_all(&platform_dir)?;
+    fs::write(platform_dir.join(JSON_CONFIG_FILE_NAME), "{}")?;
+    let location = locate(None, Some(platform_dir.clone()), &exe_dir);
+    assert_eq!(location.path, platform_dir.join(JSON_CONFIG_FILE_NAME));
+    assert_eq!(location.migrate_from, None);
+
+    // Without a platform dir the executable's dir stays in use.
+    let location = locate(None, None, &exe_dir);
+    assert_eq!(location.path, exe_dir.join(TOML_CONFIG_FILE_NAME));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn profiles_get_their_own_settings_dir() -> anyhow::Result<()> {
+    let base = unique_temp_dir("ardiex_config_profiles");
+    let exe_dir = base.join("bin");
+    let platform_dir = base.join("config").join("ardiex");
+    fs::create_dir_all(&exe_dir)?;
+    fs::write(exe_dir.join(JSON_CONFIG_FILE_NAME), "{}")?;
+
+    let work = platform_dir.join("profiles").join("work");
+    let location = locate_profile("work", Some(platform_dir.clone()), &exe_dir);
+    assert_eq!(location.path, work.join(JSON_CONFIG_FILE_NAME));
+    assert_eq!(location.migrate_from, None);
+
+    fs::create_dir_all(&work)?;
+    fs::write(work.join(TOML_CONFIG_FILE_NAME), "max_backups = 3\n")?;
+    let location = locate_profile("work", Some(platform_dir), &exe_dir);
+    assert_eq!(location.path, work.join(TOML_CONFIG_FILE_NAME));
+
+    let location = locate_profile("work", None, &exe_dir);
+    assert_eq!(
+        location.path,
+        exe_dir
+            .join("profiles")
+            .join("work")
+            .join(JSON_CONFIG_FILE_NAME)
+    );
+
+    assert!(validate_profile_name("personal_2").is_ok());
+    assert!(validate_profile_name("").is_err());
+    assert!(validate_profile_name("../etc").is_err());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
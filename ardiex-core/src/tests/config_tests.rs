@@ -0,0 +1,439 @@
+use super::*;
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let ts = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), ts))
+}
+
+#[test]
+fn auto_full_backup_interval_has_expected_bounds() {
+    assert_eq!(auto_full_backup_interval(0), 1);
+    assert_eq!(auto_full_backup_interval(1), 1);
+    assert_eq!(auto_full_backup_interval(2), 1);
+    assert_eq!(auto_full_backup_interval(10), 9);
+}
+
+#[test]
+fn effective_backup_dirs_uses_source_backup_dir_when_empty() {
+    let source = SourceConfig {
+        source_dir: PathBuf::from("/tmp/source"),
+        backup_dirs: vec![],
+        enabled: true,
+        exclude_patterns: None,
+        max_backups: None,
+        backup_mode: None,
+        cron_schedule: None,
+        enable_event_driven: None,
+        enable_periodic: None,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        source.effective_backup_dirs(),
+        vec![PathBuf::from("/tmp/source/.backup")]
+    );
+}
+
+#[test]
+fn effective_backup_dirs_keeps_explicit_backup_dirs() {
+    let source = SourceConfig {
+        source_dir: PathBuf::from("/tmp/source"),
+        backup_dirs: vec![
+            PathBuf::from("/tmp/backup1").into(),
+            PathBuf::from("/tmp/backup2").into(),
+        ],
+        enabled: true,
+        exclude_patterns: None,
+        max_backups: None,
+        backup_mode: None,
+        cron_schedule: None,
+        enable_event_driven: None,
+        enable_periodic: None,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        source.effective_backup_dirs(),
+        vec![PathBuf::from("/tmp/backup1"), PathBuf::from("/tmp/backup2")]
+    );
+}
+
+#[test]
+fn resolve_uses_global_defaults_when_source_overrides_are_absent() {
+    let global = BackupConfig {
+        sources: vec![],
+        enable_periodic: true,
+        enable_event_driven: true,
+        exclude_patterns: vec!["*.tmp".to_string()],
+        max_backups: 10,
+        backup_mode: BackupMode::Delta,
+        cron_schedule: "0 0 * * * *".to_string(),
+        enable_min_interval_by_size: true,
+        max_log_file_size_mb: 20,
+        metadata: HashMap::new(),
+        ..Default::default()
+    };
+
+    let source = SourceConfig {
+        source_dir: PathBuf::from("/tmp/source"),
+        backup_dirs: vec![],
+        enabled: true,
+        exclude_patterns: None,
+        max_backups: None,
+        backup_mode: None,
+        cron_schedule: None,
+        enable_event_driven: None,
+        enable_periodic: None,
+        ..Default::default()
+    };
+
+    let resolved = source.resolve(&global);
+    assert_eq!(resolved.exclude_patterns, vec!["*.tmp".to_string()]);
+    assert_eq!(resolved.max_backups, 10);
+    assert!(matches!(resolved.backup_mode, BackupMode::Delta));
+    assert_eq!(resolved.full_backup_interval, 9);
+    assert_eq!(resolved.cron_schedule, "0 0 * * * *");
+    assert!(resolved.enable_event_driven);
+    assert!(resolved.enable_periodic);
+}
+
+#[test]
+fn resolve_source_overrides_take_precedence() {
+    let global = BackupConfig {
+        sources: vec![],
+        enable_periodic: true,
+        enable_event_driven: true,
+        exclude_patterns: vec!["*.tmp".to_string()],
+        max_backups: 10,
+        backup_mode: BackupMode::Delta,
+        cron_schedule: "0 0 * * * *".to_string(),
+        enable_min_interval_by_size: true,
+        max_log_file_size_mb: 20,
+        metadata: HashMap::new(),
+        ..Default::default()
+    };
+
+    let source = SourceConfig {
+        source_dir: PathBuf::from("/tmp/source"),
+        backup_dirs: vec![],
+        enabled: true,
+        exclude_patterns: Some(vec!["*.cache".to_string()]),
+        max_backups: Some(3),
+        backup_mode: Some(BackupMode::Copy),
+        cron_schedule: Some("0 */5 * * * *".to_string()),
+        enable_event_driven: Some(false),
+        enable_periodic: Some(false),
+        durability: Some(Durability::Fsync),
+        ..Default::default()
+    };
+
+    let resolved = source.resolve(&global);
+    assert_eq!(global.durability, Durability::Fast);
+    assert_eq!(resolved.durability, Durability::Fsync);
+    assert_eq!(resolved.exclude_patterns, vec!["*.cache".to_string()]);
+    assert_eq!(resolved.max_backups, 3);
+    assert!(matches!(resolved.backup_mode, BackupMode::Copy));
+    assert_eq!(resolved.full_backup_interval, 2);
+    assert_eq!(resolved.cron_schedule, "0 */5 * * * *");
+    assert!(!resolved.enable_event_driven);
+    assert!(!resolved.enable_periodic);
+}
+
+#[test]
+fn resolve_full_backup_interval_is_never_below_one() {
+    let global = BackupConfig::default();
+    let source = SourceConfig {
+        source_dir: PathBuf::from("/tmp/source"),
+        backup_dirs: vec![],
+        enabled: true,
+        exclude_patterns: None,
+        max_backups: Some(1),
+        backup_mode: None,
+        cron_schedule: None,
+        enable_event_driven: None,
+        enable_periodic: None,
+        ..Default::default()
+    };
+
+    let resolved = source.resolve(&global);
+    assert_eq!(resolved.full_backup_interval, 1);
+}
+
+#[test]
+fn backup_config_default_values_are_consistent() {
+    let config = BackupConfig::default();
+    assert!(config.enable_periodic);
+    assert!(config.enable_event_driven);
+    assert!(config.enable_min_interval_by_size);
+    assert_eq!(config.max_backups, 10);
+    assert_eq!(config.max_log_file_size_mb, 20);
+    assert!(matches!(config.backup_mode, BackupMode::Delta));
+    assert_eq!(config.cron_schedule, "0 0 * * * *");
+}
+
+#[test]
+fn backup_config_deserialization_fails_for_invalid_backup_mode() {
+    let json = r#"{
+        "sources": [],
+        "enable_periodic": true,
+        "enable_event_driven": true,
+        "exclude_patterns": [],
+        "max_backups": 10,
+        "backup_mode": "invalid_mode",
+        "cron_schedule": "0 0 * * * *",
+        "enable_min_interval_by_size": true,
+        "max_log_file_size_mb": 20,
+        "metadata": {}
+    }"#;
+
+    let err = serde_json::from_str::<BackupConfig>(json)
+        .expect_err("invalid backup_mode must fail deserialization");
+    assert!(!err.to_string().is_empty());
+}
+
+#[test]
+fn backup_config_deserialization_fails_when_required_field_is_missing() {
+    let json = r#"{
+        "sources": [],
+        "enable_periodic": true,
+        "enable_event_driven": true,
+        "exclude_patterns": [],
+        "backup_mode": "delta",
+        "cron_schedule": "0 0 * * * *",
+        "enable_min_interval_by_size": true,
+        "max_log_file_size_mb": 20,
+        "metadata": {}
+    }"#;
+
+    let err = serde_json::from_str::<BackupConfig>(json)
+        .expect_err("missing max_backups must fail deserialization");
+    assert!(!err.to_string().is_empty());
+}
+
+#[test]
+fn backup_config_accepts_unit_strings_for_sizes_and_durations() -> Result<()> {
+    let json = r#"{
+        "sources": [],
+        "enable_periodic": true,
+        "enable_event_driven": true,
+        "exclude_patterns": [],
+        "max_backups": 10,
+        "max_log_file_size_mb": "1GB",
+        "forward_interval_secs": "5m",
+        "forward_alert_after_hours": "2d",
+        "metadata": {}
+    }"#;
+
+    let config: BackupConfig = serde_json::from_str(json)?;
+    assert_eq!(config.max_log_file_size_mb, 1024);
+    assert_eq!(config.forward_interval_secs, 300);
+    assert_eq!(config.forward_alert_after_hours, 48);
+
+    let bad = json.replace("\"1GB\"", "\"100KB\"");
+    let err = serde_json::from_str::<BackupConfig>(&bad)
+        .expect_err("sizes that are not whole MB must fail");
+    assert!(err.to_string().contains("multiple of 1MB"));
+    Ok(())
+}
+
+#[test]
+fn localized_maps_source_and_backup_dirs_when_host_path_is_missing() -> Result<()> {
+    let container_root = unique_temp_dir("ardiex_path_mapping");
+    fs::create_dir_all(&container_root)?;
+
+    let config = BackupConfig {
+        sources: vec![SourceConfig {
+            source_dir: PathBuf::from("/ardiex_missing_host/data/docs"),
+            backup_dirs: vec![PathBuf::from("/ardiex_missing_host/backup/docs").into()],
+            enabled: true,
+            ..Default::default()
+        }],
+        path_mappings: vec![PathMapping {
+            from: PathBuf::from("/ardiex_missing_host"),
+            to: container_root.clone(),
+        }],
+        ..Default::default()
+    };
+
+    let localized = config.localized();
+    assert_eq!(
+        localized.sources[0].source_dir,
+        container_root.join("data/docs")
+    );
+    assert_eq!(
+        localized.sources[0].effective_backup_dirs(),
+        vec![container_root.join("backup/docs")]
+    );
+
+    fs::remove_dir_all(&container_root)?;
+    Ok(())
+}
+
+#[test]
+fn localized_keeps_paths_when_configured_path_exists() -> Result<()> {
+    let host_root = unique_temp_dir("ardiex_path_mapping_host");
+    let container_root = unique_temp_dir("ardiex_path_mapping_container");
+    fs::create_dir_all(&host_root)?;
+    fs::create_dir_all(&container_root)?;
+
+    let config = BackupConfig {
+        sources: vec![SourceConfig {
+            source_dir: host_root.join("docs"),
+            enabled: true,
+            ..Default::default()
+        }],
+        path_mappings: vec![PathMapping {
+            from: host_root.clone(),
+            to: container_root.clone(),
+        }],
+        ..Default::default()
+    };
+
+    let localized = config.localized();
+    assert_eq!(localized.sources[0].source_dir, host_root.join("docs"));
+
+    fs::remove_dir_all(&host_root)?;
+    fs::remove_dir_all(&container_root)?;
+    Ok(())
+}
+
+#[test]
+fn localized_namespaces_backup_dirs_by_host_and_source() {
+    let source = |path: &str| SourceConfig {
+        source_dir: PathBuf::from(path),
+        backup_dirs: vec![PathBuf::from("/nas/backups").into()],
+        enabled: true,
+        ..Default::default()
+    };
+    let mut config = BackupConfig {
+        sources: vec![source("/home/a/docs"), source("/home/b/docs")],
+        namespace_backups: true,
+        host_id: Some("Laptop 1".to_string()),
+        ..Default::default()
+    };
+    config.sources[1].namespace_backups = Some(false);
+    config.sources.push(source("/srv/docs"));
+
+    let localized = config.localized();
+    let first = &localized.sources[0].effective_backup_dirs()[0];
+    let third = &localized.sources[2].effective_backup_dirs()[0];
+    assert!(first.starts_with("/nas/backups/Laptop_1"));
+    assert!(
+        first
+            .file_name()
+            .is_some_and(|id| id.to_string_lossy().starts_with("docs-"))
+    );
+    assert_ne!(first, third);
+    assert_eq!(
+        localized.sources[1].effective_backup_dirs(),
+        vec![PathBuf::from("/nas/backups")]
+    );
+}
+
+#[test]
+fn remapped_moves_source_backup_and_metadata_paths() {
+    let mut config = BackupConfig::default();
+    config.sources.push(SourceConfig {
+        source_dir: PathBuf::from("/mnt/old/documents"),
+        backup_dirs: vec![
+            PathBuf::from("/mnt/old/backup").into(),
+            PathBuf::from("/srv/backup").into(),
+        ],
+        enabled: true,
+        forward_dir: Some(PathBuf::from("/mnt/nas/documents")),
+        ..Default::default()
+    });
+    config
+        .metadata
+        .insert("/mnt/old/documents".to_string(), SourceMetadata::default());
+
+    let remapped = config.remapped(&[
+        PathMapping {
+            from: PathBuf::from("/mnt/old"),
+            to: PathBuf::from("/data"),
+        },
+        PathMapping {
+            from: PathBuf::from("/mnt/nas"),
+            to: PathBuf::from("/mnt/nas2"),
+        },
+    ]);
+
+    let source = &remapped.sources[0];
+    assert_eq!(source.source_dir, PathBuf::from("/data/documents"));
+    assert_eq!(
+        source.effective_backup_dirs(),
+        vec![PathBuf::from("/data/backup"), PathBuf::from("/srv/backup")]
+    );
+    assert_eq!(
+        source.forward_dir,
+        Some(PathBuf::from("/mnt/nas2/documents"))
+    );
+    assert!(remapped.metadata.contains_key("/data/documents"));
+    assert!(!remapped.metadata.contains_key("/mnt/old/documents"));
+}
+
+#[test]
+fn backup_dirs_accept_plain_paths_and_override_tables() -> Result<()> {
+    let json = r#"{
+        "source_dir": "/home/user/documents",
+        "backup_dirs": [
+            "/backup/local",
+            {"path": "/mnt/nas/documents", "backup_mode": "copy", "max_backups": 30, "cron_schedule": "0 0 2 * * *"}
+        ],
+        "enabled": true
+    }"#;
+    let source: SourceConfig = serde_json::from_str(json)?;
+    assert_eq!(
+        source.effective_backup_dirs(),
+        vec![
+            PathBuf::from("/backup/local"),
+            PathBuf::from("/mnt/nas/documents")
+        ]
+    );
+
+    let config = BackupConfig::default();
+    let local = source.resolve_for(&config, Path::new("/backup/local"));
+    assert_eq!(local.backup_mode, config.backup_mode);
+    assert_eq!(local.cron_schedule, config.cron_schedule);
+    let nas = source.resolve_for(&config, Path::new("/mnt/nas/documents"));
+    assert_eq!(nas.backup_mode, BackupMode::Copy);
+    assert_eq!(nas.max_backups, 30);
+    assert_eq!(nas.full_backup_interval, 29);
+    assert_eq!(nas.cron_schedule, "0 0 2 * * *");
+
+    // Entries without overrides are written back as plain paths.
+    let saved = serde_json::to_value(&source)?;
+    assert_eq!(saved["backup_dirs"][0], "/backup/local");
+    assert_eq!(saved["backup_dirs"][1]["backup_mode"], "copy");
+    Ok(())
+}
+
+#[test]
+fn blackout_windows_and_jitter_parse_from_settings() {
+    let config: BackupConfig = serde_json::from_value(serde_json::json!({
+        "sources": [{
+            "source_dir": "/data",
+            "backup_dirs": ["/backup"],
+            "enabled": true,
+            "cron_jitter_secs": "2m"
+        }],
+        "enable_periodic": true,
+        "enable_event_driven": true,
+        "exclude_patterns": [],
+        "max_backups": 10,
+        "cron_jitter_secs": "30s",
+        "blackout_windows": ["Mon-Fri 09:00-18:00"],
+        "metadata": {}
+    }))
+    .expect("valid settings");
+
+    assert_eq!(config.cron_jitter_secs, 30);
+    assert_eq!(config.sources[0].resolve(&config).cron_jitter_secs, 120);
+    assert_eq!(
+        serde_json::to_value(&config.blackout_windows).expect("serialize"),
+        serde_json::json!(["Mon-Fri 09:00-18:00"])
+    );
+}
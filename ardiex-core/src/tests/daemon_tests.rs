@@ -0,0 +1,103 @@
+use super::*;
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "{}-{}-{}",
+        prefix,
+        std::process::id(),
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0)
+    ));
+    fs::create_dir_all(&dir).expect("create temp dir");
+    dir
+}
+
+#[test]
+fn daemon_args_drop_only_the_daemonize_flag() {
+    let args: Vec<String> = ["--profile", "work", "run", "--daemonize"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    assert_eq!(daemon_args(&args), vec!["--profile", "work", "run"]);
+}
+
+#[test]
+fn pid_file_is_written_and_removed_on_drop() -> Result<()> {
+    let dir = unique_temp_dir("ardiex-pid");
+    let path = dir.join(PID_FILE_NAME);
+
+    let pid_file = PidFile::create(&path)?;
+    assert_eq!(read_pid(&path)?, Some(std::process::id()));
+    drop(pid_file);
+    assert_eq!(read_pid(&path)?, None);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn stale_pid_file_is_taken_over_and_stop_clears_it() -> Result<()> {
+    let dir = unique_temp_dir("ardiex-pid-stale");
+    let path = dir.join(PID_FILE_NAME);
+    // PIDs this large are never handed out.
+    let dead_pid = 999_999_999;
+    fs::write(&path, format!("{}\n", dead_pid))?;
+
+    assert_eq!(stop(&path, Duration::from_secs(1))?, None);
+    assert!(!path.exists());
+
+    fs::write(&path, format!("{}\n", dead_pid))?;
+    let pid_file = PidFile::create(&path)?;
+    assert_eq!(read_pid(&path)?, Some(std::process::id()));
+    drop(pid_file);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn running_service_blocks_a_second_pid_file() -> Result<()> {
+    let dir = unique_temp_dir("ardiex-pid-live");
+    let path = dir.join(PID_FILE_NAME);
+
+    let pid_file = PidFile::create(&path)?;
+    assert_eq!(running_pid(&path)?, Some(std::process::id()));
+    // The lock is on a file of its own, so the PID stays readable even
+    // where locks are mandatory (Windows).
+    assert!(dir.join(format!("{}.lock", PID_FILE_NAME)).is_file());
+    assert_eq!(read_pid(&path)?, Some(std::process::id()));
+    let err = PidFile::create(&path).expect_err("held pid file must block");
+    assert!(err.to_string().contains("already running"));
+    drop(pid_file);
+    assert_eq!(running_pid(&path)?, None);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn stop_signals_only_a_locked_ardiex_process() -> Result<()> {
+    let dir = unique_temp_dir("ardiex-pid-foreign");
+    let path = dir.join(PID_FILE_NAME);
+    let mut child = Command::new("sleep").arg("30").spawn()?;
+
+    // A live pid in an unlocked file is just a stale file.
+    fs::write(&path, format!("{}\n", child.id()))?;
+    assert_eq!(running_pid(&path)?, None);
+    assert_eq!(stop(&path, Duration::from_secs(1))?, None);
+    assert!(!path.exists());
+
+    // Locked, but the pid in it is not ardiex.
+    let pid_file = PidFile::create(&path)?;
+    fs::write(&path, format!("{}\n", child.id()))?;
+    let err = stop(&path, Duration::from_secs(1)).expect_err("foreign pid must not be signalled");
+    assert!(err.to_string().contains("not ardiex"));
+    assert!(child.try_wait()?.is_none());
+    assert!(is_ardiex(std::process::id()));
+
+    drop(pid_file);
+    child.kill().ok();
+    child.wait()?;
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
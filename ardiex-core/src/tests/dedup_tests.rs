@@ -0,0 +1,84 @@
+use super::*;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_nanos();
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), ts))
+}
+
+#[test]
+fn identical_content_is_stored_once_and_restored_exactly() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_dedup_store");
+    fs::create_dir_all(&dir)?;
+    let content: Vec<u8> = (0..CHUNK_SIZE + 1000).map(|i| (i % 251) as u8).collect();
+    fs::write(dir.join("a.bin"), &content)?;
+    fs::write(dir.join("copy_of_a.bin"), &content)?;
+    fs::write(dir.join("empty"), b"")?;
+
+    let store = ChunkStore::open(&dir.join("objects")).with_write_options(true, false);
+    let (a, written_a) = store.store_file(&dir.join("a.bin"), None)?;
+    assert_eq!(a.chunks.len(), 2);
+    assert_eq!(a.size, content.len() as u64);
+    assert_eq!(written_a, content.len() as u64);
+
+    let (copy, written_copy) = store.store_file(&dir.join("copy_of_a.bin"), None)?;
+    assert_eq!(copy, a);
+    assert_eq!(written_copy, 0);
+    assert_eq!(store.list_chunks()?.len(), 2);
+
+    let (empty, _) = store.store_file(&dir.join("empty"), None)?;
+    assert!(empty.chunks.is_empty());
+
+    store.restore_file(&a, &dir.join("restored.bin"), None)?;
+    assert_eq!(fs::read(dir.join("restored.bin"))?, content);
+    assert!(ChunkStore::matches(&a, &dir.join("restored.bin"))?);
+    assert!(!ChunkStore::matches(&a, &dir.join("empty"))?);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn corrupted_chunk_is_rejected_on_restore() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_dedup_corrupt");
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join("a.txt"), b"hello")?;
+    let store = ChunkStore::open(&dir.join("objects"));
+    let (entry, _) = store.store_file(&dir.join("a.txt"), None)?;
+    fs::write(store.object_path(&entry.chunks[0]), b"jello")?;
+
+    let err = store
+        .restore_file(&entry, &dir.join("out.txt"), None)
+        .expect_err("corrupted chunk must fail");
+    assert!(err.to_string().contains("corrupted"));
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn index_round_trips_and_lists_referenced_chunks() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_dedup_index");
+    fs::create_dir_all(&dir)?;
+    assert!(read_index(&dir)?.is_none());
+
+    let mut index = DedupIndex::default();
+    index.files.insert(
+        "docs/a.txt".to_string(),
+        IndexedFile {
+            size: 3,
+            chunks: vec!["aa".to_string(), "bb".to_string()],
+        },
+    );
+    write_index(&dir, &index)?;
+    let loaded = read_index(&dir)?.expect("index written");
+    assert_eq!(loaded.files, index.files);
+    assert_eq!(loaded.store_root(&dir), dir.join(DEFAULT_STORE_DIR_NAME));
+    assert_eq!(referenced_chunks([&loaded]).len(), 2);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
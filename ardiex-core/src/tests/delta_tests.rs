@@ -18,10 +18,49 @@ fn calculate_block_hashes_is_deterministic() -> Result<()> {
     let file = base.join("a.bin");
     fs::write(&file, b"abcdefg")?;
 
-    let h1 = calculate_block_hashes(&file)?;
-    let h2 = calculate_block_hashes(&file)?;
+    let (h1, whole1) = calculate_block_hashes(&file, DEFAULT_BLOCK_SIZE)?;
+    let (h2, whole2) = calculate_block_hashes(&file, DEFAULT_BLOCK_SIZE)?;
     assert_eq!(h1, h2);
     assert_eq!(h1.len(), 1);
+    assert_eq!(whole1, whole2);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn batched_hashing_matches_per_block_hashes_across_batches() -> Result<()> {
+    let base = unique_temp_dir("ardiex_delta_batches");
+    fs::create_dir_all(&base)?;
+    let original = base.join("old.bin");
+    let new = base.join("new.bin");
+    // Several batches with a partial last block.
+    let size = 3 * hash_threads() * MAX_BLOCK_SIZE + 123;
+    let content: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+    fs::write(&original, &content)?;
+    let mut changed = content.clone();
+    let far_block = size / DEFAULT_BLOCK_SIZE - 1;
+    changed[5] ^= 0xff;
+    changed[far_block * DEFAULT_BLOCK_SIZE] ^= 0xff;
+    fs::write(&new, &changed)?;
+
+    let expected: Vec<String> = content
+        .chunks(DEFAULT_BLOCK_SIZE)
+        .map(|block| format!("{:x}", Sha256::digest(block)))
+        .collect();
+    assert_eq!(
+        calculate_block_hashes(&original, DEFAULT_BLOCK_SIZE)?.0,
+        expected
+    );
+
+    let delta = create_delta(&original, &new, None)?;
+    assert_eq!(
+        delta.original_file_hash,
+        format!("{:x}", Sha256::digest(&content))
+    );
+    assert_eq!(delta.total_blocks, expected.len());
+    let indices: Vec<usize> = delta.changed_blocks.iter().map(|b| b.index).collect();
+    assert_eq!(indices, vec![0, far_block]);
 
     fs::remove_dir_all(&base)?;
     Ok(())
@@ -36,7 +75,7 @@ fn create_delta_has_no_changed_blocks_when_files_are_equal() -> Result<()> {
     fs::write(&original, b"same-content")?;
     fs::write(&new, b"same-content")?;
 
-    let delta = create_delta(&original, &new)?;
+    let delta = create_delta(&original, &new, None)?;
     assert_eq!(delta.changed_blocks.len(), 0);
     assert_eq!(delta.total_blocks, 1);
     assert_eq!(delta.new_file_size, b"same-content".len() as u64);
@@ -54,7 +93,7 @@ fn create_delta_marks_changed_block_when_content_differs() -> Result<()> {
     fs::write(&original, b"aaaa")?;
     fs::write(&new, b"bbbb")?;
 
-    let delta = create_delta(&original, &new)?;
+    let delta = create_delta(&original, &new, None)?;
     assert_eq!(delta.changed_blocks.len(), 1);
     assert_eq!(delta.changed_blocks[0].index, 0);
     assert_eq!(delta.new_file_size, 4);
@@ -63,6 +102,35 @@ fn create_delta_marks_changed_block_when_content_differs() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn deltas_record_and_apply_their_own_block_size() -> Result<()> {
+    let base = unique_temp_dir("ardiex_delta_block_size");
+    fs::create_dir_all(&base)?;
+    let original = base.join("old.bin");
+    let new = base.join("new.bin");
+    let restored = base.join("restored.bin");
+    let content: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+    fs::write(&original, &content)?;
+    let mut changed = content.clone();
+    changed[2_000] ^= 0xff;
+    changed.extend_from_slice(b"tail");
+    fs::write(&new, &changed)?;
+
+    let delta = create_delta_with_block_size(&original, &new, MIN_BLOCK_SIZE, None)?;
+    assert_eq!(delta.block_size, MIN_BLOCK_SIZE);
+    let indices: Vec<usize> = delta.changed_blocks.iter().map(|b| b.index).collect();
+    assert_eq!(indices, vec![3, 19]);
+    apply_delta(&original, &delta, &restored, None)?;
+    assert_eq!(fs::read(&restored)?, changed);
+
+    assert!(is_valid_block_size(DEFAULT_BLOCK_SIZE));
+    assert!(!is_valid_block_size(3000));
+    assert!(!is_valid_block_size(2 * MAX_BLOCK_SIZE));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
 #[test]
 fn create_delta_with_missing_original_includes_all_new_blocks() -> Result<()> {
     let base = unique_temp_dir("ardiex_delta_missing_original");
@@ -71,7 +139,7 @@ fn create_delta_with_missing_original_includes_all_new_blocks() -> Result<()> {
     let new = base.join("new.bin");
     fs::write(&new, vec![1u8; 5000])?;
 
-    let delta = create_delta(&original, &new)?;
+    let delta = create_delta(&original, &new, None)?;
     assert_eq!(delta.total_blocks, 2);
     assert_eq!(delta.changed_blocks.len(), 2);
     assert_eq!(delta.new_file_size, 5000);
@@ -91,8 +159,8 @@ fn apply_delta_reconstructs_new_file_from_original() -> Result<()> {
     fs::write(&original, b"old-content-1234")?;
     fs::write(&new, b"new-content-9876")?;
 
-    let delta = create_delta(&original, &new)?;
-    apply_delta(&original, &delta, &restored)?;
+    let delta = create_delta(&original, &new, None)?;
+    apply_delta(&original, &delta, &restored, None)?;
 
     assert_eq!(fs::read(&restored)?, fs::read(&new)?);
 
@@ -100,6 +168,57 @@ fn apply_delta_reconstructs_new_file_from_original() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn new_file_size_is_read_from_the_tail_of_a_stored_delta() -> Result<()> {
+    let base = unique_temp_dir("ardiex_delta_size_tail");
+    fs::create_dir_all(&base)?;
+    let original = base.join("old.bin");
+    let new = base.join("new.bin");
+    let stored = base.join("new.bin.delta");
+    fs::write(&original, vec![1u8; 20_000])?;
+    fs::write(&new, vec![2u8; 30_000])?;
+
+    let delta = create_delta(&original, &new, None)?;
+    let encoded = encode_delta(&delta)?;
+    save_delta(&encoded, &stored)?;
+    let tail = &encoded[encoded.len() - SIZE_TAIL_LEN as usize..];
+    assert_eq!(new_file_size_in_tail(tail), Some(30_000));
+    assert_eq!(read_new_file_size(&stored)?, 30_000);
+    assert_eq!(new_file_size_in_tail(b"{\"block_size\":4096}"), None);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn verify_applied_detects_corrupted_output() -> Result<()> {
+    let base = unique_temp_dir("ardiex_delta_verify");
+    fs::create_dir_all(&base)?;
+    let original = base.join("old.bin");
+    let new = base.join("new.bin");
+    let restored = base.join("restored.bin");
+    fs::write(&original, vec![b'a'; 10_000])?;
+    let mut changed = vec![b'a'; 10_000];
+    changed[5_000] = b'b';
+    fs::write(&new, &changed)?;
+
+    let delta = create_delta(&original, &new, None)?;
+    apply_delta(&original, &delta, &restored, None)?;
+    verify_applied(&delta, &restored)?;
+
+    // A flipped byte inside a replaced block is caught.
+    changed[5_001] = b'c';
+    fs::write(&restored, &changed)?;
+    assert!(verify_applied(&delta, &restored).is_err());
+
+    // So is a truncated file.
+    fs::write(&restored, &changed[..4_096])?;
+    assert!(verify_applied(&delta, &restored).is_err());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
 #[test]
 fn apply_delta_without_original_creates_file() -> Result<()> {
     let base = unique_temp_dir("ardiex_delta_apply_missing_original");
@@ -109,8 +228,8 @@ fn apply_delta_without_original_creates_file() -> Result<()> {
     let restored = base.join("restored.bin");
 
     fs::write(&new, b"brand-new-content")?;
-    let delta = create_delta(&original, &new)?;
-    apply_delta(&original, &delta, &restored)?;
+    let delta = create_delta(&original, &new, None)?;
+    apply_delta(&original, &delta, &restored, None)?;
 
     assert_eq!(fs::read(&restored)?, b"brand-new-content");
 
@@ -128,8 +247,8 @@ fn save_and_load_delta_roundtrip() -> Result<()> {
 
     fs::write(&original, b"abc")?;
     fs::write(&new, b"abd")?;
-    let delta = create_delta(&original, &new)?;
-    save_delta(&delta, &delta_path)?;
+    let delta = create_delta(&original, &new, None)?;
+    save_delta(&encode_delta(&delta)?, &delta_path)?;
     let loaded = load_delta(&delta_path)?;
 
     assert_eq!(loaded.block_size, delta.block_size);
@@ -150,7 +269,7 @@ fn delta_size_returns_sum_of_changed_block_bytes() -> Result<()> {
 
     fs::write(&original, vec![1u8; 4096])?;
     fs::write(&new, vec![2u8; 5000])?;
-    let delta = create_delta(&original, &new)?;
+    let delta = create_delta(&original, &new, None)?;
     let size = delta_size(&delta);
     let expected: usize = delta.changed_blocks.iter().map(|b| b.data.len()).sum();
     assert_eq!(size, expected);
@@ -162,8 +281,8 @@ fn delta_size_returns_sum_of_changed_block_bytes() -> Result<()> {
 #[test]
 fn calculate_block_hashes_fails_for_missing_file() {
     let missing = PathBuf::from("/tmp/ardiex_missing_hash_target.bin");
-    let err =
-        calculate_block_hashes(&missing).expect_err("missing file hash calculation must fail");
+    let err = calculate_block_hashes(&missing, DEFAULT_BLOCK_SIZE)
+        .expect_err("missing file hash calculation must fail");
     assert!(err.to_string().contains("Failed to open file"));
 }
 
@@ -175,7 +294,7 @@ fn create_delta_fails_when_new_file_is_missing() {
     let new = base.join("missing_new.bin");
     fs::write(&original, b"old").expect("old file write must succeed");
 
-    let err = create_delta(&original, &new).expect_err("missing new file must fail");
+    let err = create_delta(&original, &new, None).expect_err("missing new file must fail");
     assert!(err.to_string().contains("Failed to open new file"));
 
     fs::remove_dir_all(&base).expect("base cleanup must succeed");
@@ -203,9 +322,10 @@ fn save_delta_fails_when_target_path_is_directory() -> Result<()> {
     let new = base.join("new.bin");
     fs::write(&original, b"abc")?;
     fs::write(&new, b"abd")?;
-    let delta = create_delta(&original, &new)?;
+    let delta = create_delta(&original, &new, None)?;
 
-    let err = save_delta(&delta, &base).expect_err("saving delta to directory path must fail");
+    let err = save_delta(&encode_delta(&delta)?, &base)
+        .expect_err("saving delta to directory path must fail");
     assert!(!err.to_string().is_empty());
 
     fs::remove_dir_all(&base)?;
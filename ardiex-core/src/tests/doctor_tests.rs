@@ -0,0 +1,202 @@
+use super::*;
+use crate::config::{SourceConfig, SourceMetadata};
+use crate::lock::{LOCK_FILE_NAME, LockInfo};
+use anyhow::Result;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_nanos();
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), ts))
+}
+
+fn make_config(source_dir: &Path, backup_dir: &Path) -> BackupConfig {
+    BackupConfig {
+        sources: vec![SourceConfig {
+            source_dir: source_dir.to_path_buf(),
+            backup_dirs: vec![backup_dir.to_path_buf().into()],
+            enabled: true,
+            ..Default::default()
+        }],
+        enable_event_driven: false,
+        ..Default::default()
+    }
+}
+
+fn find<'a>(report: &'a DoctorReport, check: &str) -> Vec<&'a DoctorFinding> {
+    report
+        .findings
+        .iter()
+        .filter(|finding| finding.check == check)
+        .collect()
+}
+
+#[test]
+fn healthy_setup_reports_no_problems() -> Result<()> {
+    let root = unique_temp_dir("ardiex_doctor_ok");
+    let source_dir = root.join("source");
+    let backup_dir = root.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+    let config_path = root.join("settings.json");
+    fs::write(&config_path, "{}")?;
+
+    let report = diagnose(
+        &make_config(&source_dir, &backup_dir),
+        &config_path,
+        Some(Utc::now()),
+    );
+    assert_eq!(report.count(DoctorStatus::Error), 0, "{:?}", report);
+    assert_eq!(report.count(DoctorStatus::Warning), 0, "{:?}", report);
+    assert_eq!(find(&report, "instance")[0].status, DoctorStatus::Ok);
+    assert!(
+        find(&report, "cron")[0]
+            .message
+            .contains("1 cron expression")
+    );
+    assert!(!backup_dir.join(PROBE_FILE_NAME).exists());
+
+    fs::remove_dir_all(&root)?;
+    Ok(())
+}
+
+#[test]
+fn missing_paths_bad_cron_and_stale_lock_come_with_hints() -> Result<()> {
+    let root = unique_temp_dir("ardiex_doctor_problems");
+    let backup_dir = root.join("backup");
+    fs::create_dir_all(&backup_dir)?;
+    let stale = LockInfo {
+        pid: 999_999,
+        operation: "backup".to_string(),
+        acquired_at: Utc::now(),
+    };
+    fs::write(
+        backup_dir.join(LOCK_FILE_NAME),
+        serde_json::to_string(&stale)?,
+    )?;
+    let config_path = root.join("settings.json");
+
+    let mut config = make_config(&root.join("gone"), &backup_dir);
+    config.sources[0].verify_schedule = Some("every day".to_string());
+    config.sources[0]
+        .backup_dirs
+        .push(root.join("not-mounted").into());
+    let report = diagnose(&config, &config_path, None);
+
+    let cron = find(&report, "cron");
+    assert_eq!(cron.len(), 1);
+    assert_eq!(cron[0].status, DoctorStatus::Error);
+    assert!(cron[0].message.contains("verify_schedule"));
+    assert_eq!(find(&report, "source")[0].status, DoctorStatus::Error);
+    assert!(
+        find(&report, "source")[0]
+            .hint
+            .as_deref()
+            .is_some_and(|hint| hint.contains("disable-source"))
+    );
+    let missing = find(&report, "backup_dir");
+    assert_eq!(missing.len(), 1);
+    assert!(missing[0].message.contains("not-mounted"));
+    let lock = find(&report, "lock");
+    assert_eq!(lock[0].status, DoctorStatus::Warning);
+    assert!(lock[0].message.contains("stale lock left by pid 999999"));
+    // Space is still reported for the dir that does not exist yet.
+    assert_eq!(find(&report, "disk_space").len(), 2);
+
+    fs::remove_dir_all(&root)?;
+    Ok(())
+}
+
+#[test]
+fn clock_skew_and_future_backups_are_warnings() -> Result<()> {
+    let root = unique_temp_dir("ardiex_doctor_clock");
+    let source_dir = root.join("source");
+    let backup_dir = root.join("backup");
+    fs::create_dir_all(&source_dir)?;
+    fs::create_dir_all(&backup_dir)?;
+    let config = make_config(&source_dir, &backup_dir);
+    let now = Utc::now();
+
+    let mut report = DoctorReport::default();
+    check_clock(
+        &mut report,
+        &config,
+        now,
+        Some(now - chrono::Duration::seconds(30)),
+    );
+    assert_eq!(report.findings[0].status, DoctorStatus::Ok);
+
+    let mut report = DoctorReport::default();
+    check_clock(
+        &mut report,
+        &config,
+        now,
+        Some(now + chrono::Duration::minutes(10)),
+    );
+    assert_eq!(report.findings[0].status, DoctorStatus::Warning);
+    assert!(report.findings[0].message.contains("600s behind"));
+
+    let metadata = SourceMetadata {
+        last_backup: Some(now + chrono::Duration::hours(2)),
+        ..Default::default()
+    };
+    fs::write(
+        backup_dir.join("metadata.json"),
+        serde_json::to_string(&metadata)?,
+    )?;
+    let mut report = DoctorReport::default();
+    check_clock(&mut report, &config, now, None);
+    assert_eq!(report.findings.len(), 1);
+    assert!(report.findings[0].message.contains("moved backwards"));
+
+    fs::remove_dir_all(&root)?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn inotify_usage_warns_near_the_limit() {
+    let status = |watched, limit| {
+        let mut report = DoctorReport::default();
+        report_watch_usage(&mut report, watched, limit);
+        report.findings[0].status
+    };
+    assert_eq!(status(100, 8192), DoctorStatus::Ok);
+    assert_eq!(status(7000, 8192), DoctorStatus::Warning);
+    assert_eq!(status(9000, 8192), DoctorStatus::Error);
+}
+
+#[cfg(unix)]
+#[test]
+fn secrets_in_a_shared_config_file_are_flagged() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = unique_temp_dir("ardiex_doctor_perms");
+    fs::create_dir_all(&root)?;
+    let config_path = root.join("settings.json");
+    fs::write(&config_path, "{}")?;
+    fs::set_permissions(&config_path, fs::Permissions::from_mode(0o644))?;
+    let mut config = BackupConfig::default();
+
+    let mut report = DoctorReport::default();
+    check_config_file(&mut report, &config_path, &config);
+    assert!(find(&report, "permissions").is_empty());
+
+    config.api_token = Some("token".to_string());
+    let mut report = DoctorReport::default();
+    check_config_file(&mut report, &config_path, &config);
+    let permissions = find(&report, "permissions");
+    assert_eq!(permissions.len(), 1);
+    assert!(
+        permissions[0]
+            .hint
+            .as_deref()
+            .is_some_and(|hint| hint.contains("chmod 600"))
+    );
+
+    fs::remove_dir_all(&root)?;
+    Ok(())
+}
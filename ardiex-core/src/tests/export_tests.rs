@@ -0,0 +1,98 @@
+use super::*;
+use std::io::Read;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), ts))
+}
+
+/// A full point and a delta incremental on top of it.
+fn delta_chain(backup_dir: &Path, scratch: &Path) -> Result<()> {
+    let full_dir = backup_dir.join("full_20260224_120000");
+    let inc_dir = backup_dir.join("inc_20260224_121000");
+    fs::create_dir_all(full_dir.join("docs"))?;
+    fs::create_dir_all(&inc_dir)?;
+    fs::write(full_dir.join("docs/readme.txt"), b"unchanged")?;
+    fs::write(full_dir.join("a.txt"), b"hello-old")?;
+    fs::write(scratch, b"hello-new, and longer")?;
+    let delta_data = delta::create_delta(&full_dir.join("a.txt"), scratch, None)?;
+    delta::save_delta(
+        &delta::encode_delta(&delta_data)?,
+        &inc_dir.join("a.txt.delta"),
+    )?;
+    Ok(())
+}
+
+#[test]
+fn export_rebuilds_the_point_into_tar_gz_and_zip() -> Result<()> {
+    let base = unique_temp_dir("ardiex_export_formats");
+    let backup_dir = base.join("backup");
+    delta_chain(&backup_dir, &base.join("new.txt"))?;
+    let expected = [
+        ("a.txt", b"hello-new, and longer".to_vec()),
+        ("docs/readme.txt", b"unchanged".to_vec()),
+    ];
+
+    let tar_path = base.join("out.tar.gz");
+    let summary = export_point(
+        &backup_dir,
+        None,
+        ExportFormat::TarGz,
+        &tar_path,
+        None,
+        None,
+    )?;
+    assert_eq!(summary.files, 2);
+    assert_eq!(summary.points.len(), 2);
+    let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(File::open(&tar_path)?));
+    let mut from_tar = Vec::new();
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        let name = entry.path()?.to_string_lossy().to_string();
+        from_tar.push((name, content));
+    }
+    let expected: Vec<(String, Vec<u8>)> = expected
+        .iter()
+        .map(|(name, content)| (name.to_string(), content.clone()))
+        .collect();
+    assert_eq!(from_tar, expected);
+
+    let zip_path = base.join("out.zip");
+    export_point(&backup_dir, None, ExportFormat::Zip, &zip_path, None, None)?;
+    let mut zip = zip::ZipArchive::new(File::open(&zip_path)?)?;
+    let mut from_zip = Vec::new();
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i)?;
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+        from_zip.push((file.name().to_string(), content));
+    }
+    assert_eq!(from_zip, expected);
+    assert!(!base.join("out.zip.partial").exists());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn export_format_comes_from_the_output_name() {
+    assert_eq!(
+        ExportFormat::from_output(Path::new("/tmp/docs.TAR.GZ")),
+        Some(ExportFormat::TarGz)
+    );
+    assert_eq!(
+        ExportFormat::from_output(Path::new("docs.tgz")),
+        Some(ExportFormat::TarGz)
+    );
+    assert_eq!(
+        ExportFormat::from_output(Path::new("docs.zip")),
+        Some(ExportFormat::Zip)
+    );
+    assert_eq!(ExportFormat::from_output(Path::new("docs.tar")), None);
+}
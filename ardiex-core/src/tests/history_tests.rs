@@ -0,0 +1,98 @@
+use super::*;
+use crate::config::ResourceUsage;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_nanos();
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), ts))
+}
+
+fn make_result(dir: &str, files: usize) -> BackupResult {
+    BackupResult {
+        backup_dir: PathBuf::from(dir),
+        backup_type: BackupType::Incremental,
+        files_backed_up: files,
+        files_added: 0,
+        files_changed: 0,
+        bytes_processed: 100,
+        changed_bytes: 0,
+        delta_saved_bytes: 0,
+        duration_ms: 5,
+        context: None,
+        resource_usage: ResourceUsage::default(),
+        over_quota: None,
+        skipped_files: Vec::new(),
+    }
+}
+
+#[test]
+fn records_roundtrip_and_filter_by_source() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_history_roundtrip");
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(HISTORY_FILE_NAME);
+
+    append_records(
+        &path,
+        &[
+            RunRecord::success(Path::new("/src/a"), &make_result("/bk/a", 1)),
+            RunRecord::failure(Path::new("/src/b"), "disk full".to_string()),
+        ],
+    )?;
+    append_records(
+        &path,
+        &[RunRecord::success(
+            Path::new("/src/a"),
+            &make_result("/bk/a", 2),
+        )],
+    )?;
+
+    let all = read_records(&path, None, 10)?;
+    assert_eq!(all.len(), 3);
+    assert_eq!(all[1].status, RunStatus::Failure);
+    assert_eq!(all[1].error.as_deref(), Some("disk full"));
+    assert_eq!(all[0].backup_type.as_deref(), Some("inc"));
+
+    let only_a = read_records(&path, Some(Path::new("/src/a")), 10)?;
+    assert_eq!(only_a.len(), 2);
+    assert!(only_a.iter().all(|r| r.source_dir == Path::new("/src/a")));
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn read_records_keeps_newest_and_skips_torn_lines() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_history_limit");
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(HISTORY_FILE_NAME);
+
+    for files in 1..=5 {
+        append_records(
+            &path,
+            &[RunRecord::success(
+                Path::new("/src/a"),
+                &make_result("/bk/a", files),
+            )],
+        )?;
+    }
+    let mut file = OpenOptions::new().append(true).open(&path)?;
+    file.write_all(b"{\"finished_at\":\"2026-")?;
+    append_records(
+        &path,
+        &[RunRecord::success(
+            Path::new("/src/a"),
+            &make_result("/bk/a", 6),
+        )],
+    )?;
+
+    let newest = read_records(&path, None, 2)?;
+    let files: Vec<usize> = newest.iter().map(|r| r.files_backed_up).collect();
+    assert_eq!(files, vec![5, 6]);
+    assert!(read_records(&dir.join("missing.jsonl"), None, 10)?.is_empty());
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
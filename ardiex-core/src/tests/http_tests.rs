@@ -0,0 +1,33 @@
+use super::*;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+
+#[test]
+fn parse_target_decodes_path_and_query() {
+    let (path, query) = parse_target("/api/history?source=%2Fhome%2Fmy+docs&limit=5&flag");
+    assert_eq!(path, "/api/history");
+    assert_eq!(
+        query.get("source").map(String::as_str),
+        Some("/home/my docs")
+    );
+    assert_eq!(query.get("limit").map(String::as_str), Some("5"));
+    assert_eq!(query.get("flag").map(String::as_str), Some(""));
+    assert_eq!(percent_decode("100%"), "100%");
+}
+
+#[tokio::test]
+async fn stalled_requests_time_out() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let mut client = TcpStream::connect(listener.local_addr()?).await?;
+    let (mut server, _) = listener.accept().await?;
+    client.write_all(b"GET /api/status HTTP/1.1\r\n").await?;
+
+    let started = std::time::Instant::now();
+    let err = read_request(&mut server, Duration::from_millis(200))
+        .await
+        .err()
+        .expect("a request head that never ends must time out");
+    assert!(err.to_string().contains("Timed out"), "{}", err);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    Ok(())
+}
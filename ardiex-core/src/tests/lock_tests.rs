@@ -0,0 +1,205 @@
+use super::*;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_nanos();
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), ts))
+}
+
+#[test]
+fn acquire_fails_while_lock_is_held() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_lock_held");
+    fs::create_dir_all(&dir)?;
+    let _held = BackupDirLock::acquire(&dir, "backup")?;
+
+    let err = BackupDirLock::acquire(&dir, "restore").expect_err("second lock must fail");
+    let msg = err.to_string();
+    assert!(msg.contains("is locked by another ardiex process"));
+    assert!(msg.contains("operation: backup"));
+
+    drop(_held);
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn acquire_succeeds_after_release() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_lock_release");
+    fs::create_dir_all(&dir)?;
+    let first = BackupDirLock::acquire(&dir, "backup")?;
+    drop(first);
+
+    let second = BackupDirLock::acquire(&dir, "backup")?;
+    drop(second);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn acquire_takes_over_stale_lock_file() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_lock_stale");
+    fs::create_dir_all(&dir)?;
+    let stale = LockInfo {
+        pid: 999_999,
+        operation: "backup".to_string(),
+        acquired_at: Utc::now(),
+    };
+    fs::write(dir.join(LOCK_FILE_NAME), serde_json::to_string(&stale)?)?;
+
+    let lock = BackupDirLock::acquire(&dir, "restore")?;
+    assert_eq!(lock.recovered_stale().map(|info| info.pid), Some(999_999));
+    let info: LockInfo = serde_json::from_str(&fs::read_to_string(dir.join(LOCK_FILE_NAME))?)?;
+    assert_eq!(info.pid, std::process::id());
+    assert_eq!(info.operation, "restore");
+
+    drop(lock);
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn acquire_fails_for_missing_backup_dir() {
+    let dir = unique_temp_dir("ardiex_lock_missing");
+    let err = BackupDirLock::acquire(&dir, "restore").expect_err("missing dir must fail");
+    assert!(err.to_string().contains("Failed to open lock file"));
+    assert!(!dir.exists());
+}
+
+#[test]
+fn shared_locks_coexist_but_block_exclusive() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_lock_shared");
+    fs::create_dir_all(&dir)?;
+    let first = BackupDirLock::acquire_shared(&dir, "restore", Some(Duration::ZERO))?;
+    let second = BackupDirLock::acquire_shared(&dir, "restore", Some(Duration::ZERO))?;
+
+    let err = BackupDirLock::acquire(&dir, "backup").expect_err("writer must wait for readers");
+    assert!(
+        err.to_string()
+            .contains("is locked by another ardiex process")
+    );
+
+    drop(first);
+    drop(second);
+    drop(BackupDirLock::acquire(&dir, "backup")?);
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn writers_wait_for_readers_but_not_for_other_writers() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_lock_reader_wait");
+    fs::create_dir_all(&dir)?;
+    let reader = BackupDirLock::acquire_shared(&dir, "forward", None)?;
+    let releaser = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(300));
+        drop(reader);
+    });
+
+    let started = std::time::Instant::now();
+    let writer = BackupDirLock::acquire_after_readers(&dir, "backup", Duration::from_secs(10))?;
+    assert!(started.elapsed() >= Duration::from_millis(250));
+    releaser.join().expect("releaser thread panicked");
+
+    let started = std::time::Instant::now();
+    let err = BackupDirLock::acquire_after_readers(&dir, "backup", Duration::from_secs(10))
+        .expect_err("a second writer must not wait");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::Locked {
+            holder: Some(_),
+            ..
+        })
+    ));
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    drop(writer);
+    let _reader = BackupDirLock::acquire_shared(&dir, "restore", None)?;
+    let err = BackupDirLock::acquire_after_readers(&dir, "backup", Duration::from_millis(100))
+        .expect_err("readers outlasting the wait must time out");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::LockTimeout { .. })
+    ));
+
+    drop(_reader);
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn acquire_shared_waits_for_exclusive_holder() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_lock_shared_wait");
+    fs::create_dir_all(&dir)?;
+    let held = BackupDirLock::acquire(&dir, "backup")?;
+    let releaser = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(300));
+        drop(held);
+    });
+
+    let started = std::time::Instant::now();
+    let shared = BackupDirLock::acquire_shared(&dir, "restore", Some(Duration::from_secs(10)))?;
+    assert!(started.elapsed() >= Duration::from_millis(250));
+    releaser.join().expect("releaser thread panicked");
+
+    drop(shared);
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn acquire_shared_times_out_while_writer_holds_lock() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_lock_shared_timeout");
+    fs::create_dir_all(&dir)?;
+    let _held = BackupDirLock::acquire(&dir, "backup")?;
+
+    let err = BackupDirLock::acquire_shared(&dir, "restore", Some(Duration::from_millis(100)))
+        .expect_err("shared lock must time out");
+    let msg = err.to_string();
+    assert!(msg.contains("Timed out waiting to restore"));
+    assert!(msg.contains("operation: backup"));
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::LockTimeout {
+            holder: Some(_),
+            ..
+        })
+    ));
+
+    drop(_held);
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn inspect_tells_free_held_and_stale_locks_apart() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_lock_inspect");
+    fs::create_dir_all(&dir)?;
+    assert!(matches!(inspect(&dir)?, LockState::Free));
+    assert!(!dir.join(LOCK_FILE_NAME).exists());
+
+    let held = BackupDirLock::acquire(&dir, "backup")?;
+    match inspect(&dir)? {
+        LockState::Held(Some(info)) => {
+            assert_eq!(info.pid, std::process::id());
+            assert_eq!(info.operation, "backup");
+        }
+        other => panic!("expected a held lock, got {:?}", other),
+    }
+    drop(held);
+    assert!(matches!(inspect(&dir)?, LockState::Free));
+
+    let stale = LockInfo {
+        pid: 999_999,
+        operation: "backup".to_string(),
+        acquired_at: Utc::now(),
+    };
+    fs::write(dir.join(LOCK_FILE_NAME), serde_json::to_string(&stale)?)?;
+    assert!(matches!(inspect(&dir)?, LockState::Stale(info) if info.pid == 999_999));
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
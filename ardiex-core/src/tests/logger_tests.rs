@@ -0,0 +1,251 @@
+use super::*;
+
+#[derive(Default)]
+struct MockWriter {
+    buf: Vec<u8>,
+    writes: usize,
+    flushes: usize,
+    fail_write: bool,
+    fail_flush: bool,
+}
+
+impl Write for MockWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.fail_write {
+            return Err(io::Error::other("mock write failed"));
+        }
+        self.buf.extend_from_slice(buf);
+        self.writes += 1;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.fail_flush {
+            return Err(io::Error::other("mock flush failed"));
+        }
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+#[test]
+fn tee_writer_writes_same_payload_to_both_targets() {
+    let mut writer = TeeLogWriter::new(MockWriter::default(), MockWriter::default());
+    writer.write_all(b"log line").expect("write must succeed");
+
+    let file = writer
+        .file_writer
+        .lock()
+        .expect("file writer mutex must not be poisoned");
+    let stdout = writer
+        .stdout
+        .lock()
+        .expect("stdout writer mutex must not be poisoned");
+
+    assert_eq!(file.buf, b"log line");
+    assert_eq!(stdout.buf, b"log line");
+    assert_eq!(file.writes, 1);
+    assert_eq!(stdout.writes, 1);
+}
+
+#[test]
+fn tee_writer_stops_when_file_write_fails() {
+    let file = MockWriter {
+        fail_write: true,
+        ..Default::default()
+    };
+    let mut writer = TeeLogWriter::new(file, MockWriter::default());
+
+    let err = writer
+        .write_all(b"payload")
+        .expect_err("file write error must propagate");
+    assert!(err.to_string().contains("mock write failed"));
+
+    let stdout = writer
+        .stdout
+        .lock()
+        .expect("stdout writer mutex must not be poisoned");
+    assert_eq!(stdout.writes, 0);
+    assert!(stdout.buf.is_empty());
+}
+
+#[test]
+fn tee_writer_returns_stdout_error_after_file_write() {
+    let stdout = MockWriter {
+        fail_write: true,
+        ..Default::default()
+    };
+    let mut writer = TeeLogWriter::new(MockWriter::default(), stdout);
+
+    let err = writer
+        .write_all(b"payload")
+        .expect_err("stdout write error must propagate");
+    assert!(err.to_string().contains("mock write failed"));
+
+    let file = writer
+        .file_writer
+        .lock()
+        .expect("file writer mutex must not be poisoned");
+    assert_eq!(file.buf, b"payload");
+    assert_eq!(file.writes, 1);
+}
+
+#[test]
+fn tee_writer_flushes_both_targets() {
+    let mut writer = TeeLogWriter::new(MockWriter::default(), MockWriter::default());
+    writer.flush().expect("flush must succeed");
+
+    let file = writer
+        .file_writer
+        .lock()
+        .expect("file writer mutex must not be poisoned");
+    let stdout = writer
+        .stdout
+        .lock()
+        .expect("stdout writer mutex must not be poisoned");
+
+    assert_eq!(file.flushes, 1);
+    assert_eq!(stdout.flushes, 1);
+}
+
+#[test]
+fn tee_writer_stops_when_file_flush_fails() {
+    let file = MockWriter {
+        fail_flush: true,
+        ..Default::default()
+    };
+    let mut writer = TeeLogWriter::new(file, MockWriter::default());
+
+    let err = writer.flush().expect_err("file flush error must propagate");
+    assert!(err.to_string().contains("mock flush failed"));
+
+    let stdout = writer
+        .stdout
+        .lock()
+        .expect("stdout writer mutex must not be poisoned");
+    assert_eq!(stdout.flushes, 0);
+}
+
+#[test]
+fn tee_writer_returns_stdout_flush_error_after_file_flush() {
+    let stdout = MockWriter {
+        fail_flush: true,
+        ..Default::default()
+    };
+    let mut writer = TeeLogWriter::new(MockWriter::default(), stdout);
+
+    let err = writer
+        .flush()
+        .expect_err("stdout flush error must propagate");
+    assert!(err.to_string().contains("mock flush failed"));
+
+    let file = writer
+        .file_writer
+        .lock()
+        .expect("file writer mutex must not be poisoned");
+    assert_eq!(file.flushes, 1);
+}
+
+#[test]
+fn verbosity_flags_pick_the_log_level() {
+    assert_eq!(Verbosity::from_flags(false, 0), None);
+    assert_eq!(Verbosity::from_flags(true, 0), Some(Verbosity::Quiet));
+    assert_eq!(Verbosity::from_flags(false, 1), Some(Verbosity::Verbose));
+    assert_eq!(Verbosity::from_flags(false, 3), Some(Verbosity::Trace));
+}
+
+#[test]
+fn source_log_file_names_come_from_the_source_path() {
+    assert_eq!(
+        source_log_file_name(Path::new("/home/user/my docs")),
+        "home_user_my_docs.log"
+    );
+    assert_eq!(
+        source_log_file_name(Path::new("/srv/app-data.v2")),
+        "srv_app-data.v2.log"
+    );
+    assert_eq!(source_log_file_name(Path::new("/")), "root.log");
+}
+
+#[test]
+fn tee_writer_copies_scoped_lines_to_the_source_log() {
+    let source_log = Arc::new(Mutex::new(MockWriter::default()));
+    let mut writer = TeeLogWriter::new(MockWriter::default(), MockWriter::default());
+
+    SOURCE_LOG
+        .sync_scope(source_log.clone(), || writer.write_all(b"source line"))
+        .expect("write must succeed");
+    writer.write_all(b"other line").expect("write must succeed");
+
+    let source_log = source_log
+        .lock()
+        .expect("source log mutex must not be poisoned");
+    assert_eq!(source_log.buf, b"source line");
+    let file = writer
+        .file_writer
+        .lock()
+        .expect("file writer mutex must not be poisoned");
+    assert_eq!(file.buf, b"source lineother line");
+}
+
+#[test]
+fn prune_logs_keeps_newest_rotated_files_within_limits() -> Result<()> {
+    let ts = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let dir = std::env::temp_dir().join(format!("ardiex_log_prune_{}_{}", std::process::id(), ts));
+    fs::create_dir_all(dir.join(SOURCE_LOG_DIR))?;
+    let write_log = |path: PathBuf, age_secs: u64| -> Result<PathBuf> {
+        fs::write(&path, [0u8; 100])?;
+        fs::File::options()
+            .write(true)
+            .open(&path)?
+            .set_modified(SystemTime::now() - std::time::Duration::from_secs(age_secs))?;
+        Ok(path)
+    };
+    let live = write_log(dir.join("ardiex.log"), 0)?;
+    let newest = write_log(dir.join("ardiex.log.20260103T000000.gz"), 10)?;
+    let middle = write_log(dir.join("ardiex.log.20260102T000000.gz"), 20)?;
+    let oldest = write_log(dir.join("ardiex.log.20260101T000000.gz"), 30)?;
+    let source = write_log(dir.join(SOURCE_LOG_DIR).join("a.log.20260101T000000"), 5)?;
+
+    let dry = prune_logs(&dir, 2, None, true)?;
+    assert_eq!(dry.files, vec![oldest.clone()]);
+    assert!(oldest.exists());
+
+    // Two rotated files per log, then the oldest until 300 bytes remain.
+    let report = prune_logs(&dir, 2, Some(300), false)?;
+    assert_eq!(report.files, vec![oldest.clone(), middle.clone()]);
+    assert_eq!(report.bytes, 200);
+    assert!(live.exists() && newest.exists() && source.exists());
+    assert!(!oldest.exists() && !middle.exists());
+
+    // Live logs stay even when they alone exceed the cap.
+    let report = prune_logs(&dir, 2, Some(50), false)?;
+    assert_eq!(report.files, vec![newest, source]);
+    assert!(live.exists());
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn read_tail_returns_the_last_whole_lines() -> Result<()> {
+    let ts = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let path = std::env::temp_dir().join(format!("ardiex_log_tail_{}_{}", std::process::id(), ts));
+    fs::write(&path, "one\ntwo\nthree\n")?;
+    assert_eq!(read_tail(&path, 2)?, vec!["two", "three"]);
+    assert_eq!(read_tail(&path, 10)?, vec!["one", "two", "three"]);
+
+    // Past the window, the cut first line is dropped rather than shown half.
+    let long_line = "x".repeat(TAIL_WINDOW_BYTES as usize);
+    fs::write(&path, format!("{}\nlast\n", long_line))?;
+    assert_eq!(read_tail(&path, 10)?, vec!["last"]);
+
+    fs::remove_file(&path)?;
+    Ok(())
+}
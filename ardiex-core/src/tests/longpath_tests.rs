@@ -0,0 +1,48 @@
+use super::*;
+
+#[test]
+fn drive_paths_become_verbatim_and_normalized() {
+    assert_eq!(
+        to_extended(r"C:\Users\me\docs").as_deref(),
+        Some(r"\\?\C:\Users\me\docs")
+    );
+    assert_eq!(
+        to_extended("C:/Users/./me/../you/docs/").as_deref(),
+        Some(r"\\?\C:\Users\you\docs")
+    );
+    assert_eq!(to_extended(r"D:\").as_deref(), Some(r"\\?\D:\"));
+    assert_eq!(to_extended(r"C:\..\..\a").as_deref(), Some(r"\\?\C:\a"));
+}
+
+#[test]
+fn unc_paths_keep_server_and_share() {
+    assert_eq!(
+        to_extended(r"\\nas\backup\ardiex").as_deref(),
+        Some(r"\\?\UNC\nas\backup\ardiex")
+    );
+    assert_eq!(
+        to_extended(r"\\nas\backup\..\..\x").as_deref(),
+        Some(r"\\?\UNC\nas\backup\x")
+    );
+    assert_eq!(to_extended(r"\\nas"), None);
+}
+
+#[test]
+fn verbatim_and_relative_paths_are_left_alone() {
+    let shadow = r"\\?\GLOBALROOT\Device\HarddiskVolumeShadowCopy3\Users";
+    assert_eq!(to_extended(shadow).as_deref(), Some(shadow));
+    assert_eq!(
+        to_extended(r"\\.\pipe\ardiex").as_deref(),
+        Some(r"\\.\pipe\ardiex")
+    );
+    assert_eq!(to_extended(r"docs\a.txt"), None);
+    assert_eq!(to_extended("C:docs"), None);
+}
+
+#[test]
+fn extended_keeps_paths_outside_windows_unchanged() {
+    if cfg!(windows) {
+        return;
+    }
+    assert_eq!(extended(Path::new("/srv/data")), PathBuf::from("/srv/data"));
+}
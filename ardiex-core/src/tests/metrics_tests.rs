@@ -0,0 +1,104 @@
+use super::*;
+use crate::config::ResourceUsage;
+use tokio::io::AsyncReadExt;
+
+fn make_result(backup_type: BackupType, bytes_processed: u64, changed_bytes: u64) -> BackupResult {
+    BackupResult {
+        backup_dir: PathBuf::from("/backup/docs"),
+        backup_type,
+        files_backed_up: 1,
+        files_added: 0,
+        files_changed: 0,
+        bytes_processed,
+        changed_bytes,
+        delta_saved_bytes: 0,
+        duration_ms: 5,
+        context: None,
+        // Hashing unchanged files reads far more than the changed size.
+        resource_usage: ResourceUsage {
+            bytes_read: changed_bytes * 40,
+            ..Default::default()
+        },
+        over_quota: None,
+        skipped_files: Vec::new(),
+    }
+}
+
+#[test]
+fn render_reports_counters_and_gauges_per_source() {
+    let mut metrics = Metrics::default();
+    let source = Path::new("/home/user/docs");
+    metrics.record_backup(source, &make_result(BackupType::Full, 1000, 1000));
+    metrics.record_backup(source, &make_result(BackupType::Incremental, 25, 100));
+    metrics.record_failure(source);
+    metrics
+        .forward_queue_depth
+        .insert(PathBuf::from("/backup/docs"), 3);
+
+    let text = metrics.render();
+    assert!(text.contains("# TYPE ardiex_backups_total counter"));
+    assert!(text.contains("ardiex_backups_total{source=\"/home/user/docs\",type=\"full\"} 1"));
+    assert!(text.contains("ardiex_backups_total{source=\"/home/user/docs\",type=\"inc\"} 1"));
+    assert!(text.contains("ardiex_backup_bytes_processed_total{source=\"/home/user/docs\"} 1025"));
+    assert!(text.contains("ardiex_backup_failures_total{source=\"/home/user/docs\"} 1"));
+    assert!(text.contains("ardiex_last_success_timestamp_seconds{source=\"/home/user/docs\"}"));
+    assert!(text.contains("ardiex_delta_savings_ratio{source=\"/home/user/docs\"} 0.7500"));
+    assert!(text.contains("ardiex_forward_queue_depth{backup_dir=\"/backup/docs\"} 3"));
+}
+
+#[test]
+fn label_values_are_escaped() {
+    assert_eq!(
+        label_value(Path::new("C:\\data\\\"q\"")),
+        "C:\\\\data\\\\\\\"q\\\""
+    );
+}
+
+#[tokio::test]
+async fn server_serves_metrics_and_404s_other_paths() -> Result<()> {
+    record_backup(
+        Path::new("/srv/metrics-test"),
+        &make_result(BackupType::Full, 10, 10),
+    );
+    let listener = bind(None, 0).await?;
+    assert!(listener.local_addr()?.ip().is_loopback());
+    let port = listener.local_addr()?.port();
+    let server = spawn_server(listener);
+
+    let fetch = |path: &'static str| async move {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await?;
+        stream
+            .write_all(format!("GET {} HTTP/1.1\r\nhost: localhost\r\n\r\n", path).as_bytes())
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        anyhow::Ok(response)
+    };
+
+    let metrics = fetch("/metrics").await?;
+    assert!(metrics.starts_with("HTTP/1.1 200 OK"));
+    assert!(metrics.contains("text/plain; version=0.0.4"));
+    assert!(metrics.contains("ardiex_backups_total{source=\"/srv/metrics-test\",type=\"full\"} 1"));
+
+    let missing = fetch("/").await?;
+    assert!(missing.starts_with("HTTP/1.1 404 Not Found"));
+
+    server.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn stalled_scrapes_are_dropped_after_the_read_deadline() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let mut client = TcpStream::connect(listener.local_addr()?).await?;
+    let (server, _) = listener.accept().await?;
+    client.write_all(b"GET /metrics HTTP/1.1\r\n").await?;
+
+    let started = std::time::Instant::now();
+    let err = handle_connection(server, Duration::from_millis(200))
+        .await
+        .expect_err("a request head that never ends must time out");
+    assert!(err.to_string().contains("Timed out"), "{}", err);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use super::*;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_nanos();
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), ts))
+}
+
+fn patterned(len: usize, seed: u8) -> Vec<u8> {
+    (0..len)
+        .map(|i| (i as u8).wrapping_mul(31).wrapping_add(seed))
+        .collect()
+}
+
+fn flip_byte(path: &Path, offset: usize) -> Result<()> {
+    let mut content = fs::read(path)?;
+    content[offset] ^= 0xff;
+    fs::write(path, content)?;
+    Ok(())
+}
+
+#[test]
+fn cauchy_submatrices_invert() {
+    let matrix: Vec<Vec<u8>> = [3usize, 7, 40]
+        .iter()
+        .map(|&row| {
+            [0usize, 5, 99]
+                .iter()
+                .map(|&col| coefficient(row, col))
+                .collect()
+        })
+        .collect();
+    let inverse = invert(matrix.clone()).expect("invertible");
+    for (i, row) in matrix.iter().enumerate() {
+        let product: Vec<u8> = (0..3)
+            .map(|j| (0..3).fold(0u8, |acc, k| acc ^ gf_mul(row[k], inverse[k][j])))
+            .collect();
+        let identity: Vec<u8> = (0..3).map(|j| u8::from(i == j)).collect();
+        assert_eq!(product, identity);
+    }
+}
+
+#[test]
+fn repair_rebuilds_damaged_and_missing_files_of_a_directory_point() -> Result<()> {
+    let backup_dir = unique_temp_dir("ardiex_parity_dir");
+    let point = backup_dir.join("full_20260224_120000123");
+    fs::create_dir_all(point.join("nested"))?;
+    let big = patterned(50_000, 1);
+    let small = patterned(300, 2);
+    fs::write(point.join("big.bin"), &big)?;
+    fs::write(point.join("nested/small.txt"), &small)?;
+
+    write(&backup_dir, "full_20260224_120000123", &point, 10, false)?;
+    assert!(verify(&backup_dir, "full_20260224_120000123", &point)?.is_clean());
+
+    flip_byte(&point.join("big.bin"), 20_000)?;
+    fs::remove_file(point.join("nested/small.txt"))?;
+    let check = verify(&backup_dir, "full_20260224_120000123", &point)?;
+    assert_eq!(check.damaged_blocks, 2);
+    assert_eq!(check.unrecoverable_blocks, 0);
+    assert_eq!(check.damaged_files, vec!["big.bin", "nested/small.txt"]);
+
+    repair(&backup_dir, "full_20260224_120000123", &point)?;
+    assert_eq!(fs::read(point.join("big.bin"))?, big);
+    assert_eq!(fs::read(point.join("nested/small.txt"))?, small);
+    assert!(verify(&backup_dir, "full_20260224_120000123", &point)?.is_clean());
+
+    fs::remove_dir_all(&backup_dir)?;
+    Ok(())
+}
+
+#[test]
+fn repair_refuses_more_damage_than_parity_and_rewrites_damaged_parity() -> Result<()> {
+    let backup_dir = unique_temp_dir("ardiex_parity_archive");
+    fs::create_dir_all(&backup_dir)?;
+    let point = backup_dir.join("inc_20260224_120100456.ardx");
+    let content = patterned(40_000, 3);
+    fs::write(&point, &content)?;
+    write(&backup_dir, "inc_20260224_120100456", &point, 10, false)?;
+
+    // Ten 4 KiB blocks get one parity block: two damaged blocks are too many.
+    flip_byte(&point, 100)?;
+    flip_byte(&point, 30_000)?;
+    let check = verify(&backup_dir, "inc_20260224_120100456", &point)?;
+    assert_eq!(check.unrecoverable_blocks, 2);
+    assert!(repair(&backup_dir, "inc_20260224_120100456", &point).is_err());
+    flip_byte(&point, 30_000)?;
+    repair(&backup_dir, "inc_20260224_120100456", &point)?;
+    assert_eq!(fs::read(&point)?, content);
+
+    let parity_file = parity_path(&backup_dir, "inc_20260224_120100456");
+    flip_byte(&parity_file, 10)?;
+    let check = verify(&backup_dir, "inc_20260224_120100456", &point)?;
+    assert_eq!((check.damaged_blocks, check.damaged_parity_blocks), (0, 1));
+    repair(&backup_dir, "inc_20260224_120100456", &point)?;
+    assert!(verify(&backup_dir, "inc_20260224_120100456", &point)?.is_clean());
+
+    // A damaged header makes the parity unusable rather than the point.
+    let len = fs::metadata(&parity_file)?.len() as usize;
+    flip_byte(&parity_file, len - TRAILER_LEN as usize - 5)?;
+    assert!(verify(&backup_dir, "inc_20260224_120100456", &point).is_err());
+
+    fs::remove_dir_all(&backup_dir)?;
+    Ok(())
+}
+
+#[test]
+fn parity_of_removed_points_is_orphaned_or_removed_with_them() -> Result<()> {
+    let backup_dir = unique_temp_dir("ardiex_parity_orphans");
+    let kept = backup_dir.join("full_20260224_120000123");
+    let removed = backup_dir.join("inc_20260224_120100456");
+    fs::create_dir_all(&kept)?;
+    fs::create_dir_all(&removed)?;
+    fs::write(kept.join("a.txt"), b"a")?;
+    fs::write(removed.join("b.txt"), b"b")?;
+    write(&backup_dir, "full_20260224_120000123", &kept, 5, false)?;
+    write(&backup_dir, "inc_20260224_120100456", &removed, 5, false)?;
+
+    fs::remove_dir_all(&removed)?;
+    assert_eq!(
+        orphaned(&backup_dir),
+        vec![parity_path(&backup_dir, "inc_20260224_120100456")]
+    );
+    remove_for_point(&kept);
+    assert!(!parity_path(&backup_dir, "full_20260224_120000123").exists());
+
+    fs::remove_dir_all(&backup_dir)?;
+    Ok(())
+}
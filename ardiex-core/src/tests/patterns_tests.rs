@@ -0,0 +1,82 @@
+use super::*;
+
+fn filter(include: &[&str], exclude: &[&str]) -> PathFilter {
+    let owned = |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+    PathFilter::new(&owned(include), &owned(exclude)).expect("valid patterns")
+}
+
+#[test]
+fn exclude_patterns_match_whole_components_at_any_depth() {
+    let f = filter(&[], &["*.tmp", ".git/*", "**/node_modules/**"]);
+    assert!(f.is_excluded(Path::new("a.tmp")));
+    assert!(f.is_excluded(Path::new("deep/dir/b.tmp")));
+    assert!(f.is_excluded(Path::new(".git/HEAD")));
+    assert!(f.is_excluded(Path::new("sub/.git/objects/ab")));
+    assert!(f.is_excluded(Path::new("web/node_modules/pkg/index.js")));
+
+    // Substrings no longer match.
+    assert!(!f.is_excluded(Path::new("my.github/readme.md")));
+    assert!(!f.is_excluded(Path::new(".gitignore")));
+    assert!(!f.is_excluded(Path::new("a.tmp.bak")));
+}
+
+#[test]
+fn anchored_patterns_and_character_classes() {
+    let f = filter(&[], &["/build", "report[0-9].csv", "*.[oa]"]);
+    assert!(f.is_excluded(Path::new("build/out.bin")));
+    assert!(!f.is_excluded(Path::new("src/build/out.bin")));
+    assert!(f.is_excluded(Path::new("data/report7.csv")));
+    assert!(!f.is_excluded(Path::new("data/reportX.csv")));
+    assert!(f.is_excluded(Path::new("lib/x.o")));
+    assert!(f.is_excluded(Path::new("lib/x.a")));
+    assert!(!f.is_excluded(Path::new("lib/x.so")));
+}
+
+#[test]
+fn includes_apply_before_excludes() {
+    let f = filter(&["*.rs", "/docs"], &["generated_*.rs"]);
+    assert!(f.accepts_file(Path::new("src/main.rs")));
+    assert!(f.accepts_file(Path::new("docs/guide.md")));
+    assert!(!f.accepts_file(Path::new("src/generated_api.rs")));
+    assert!(!f.accepts_file(Path::new("notes.txt")));
+    assert!(filter(&[], &[]).accepts_file(Path::new("anything")));
+}
+
+#[test]
+fn one_file_system_rejects_directories_on_other_devices() -> Result<()> {
+    let source = std::env::temp_dir();
+    let f = filter(&[], &[]).with_one_file_system(&source)?;
+    assert!(f.accepts_dir(&std::fs::metadata(&source)?));
+    assert!(filter(&[], &[]).accepts_dir(&std::fs::metadata(&source)?));
+
+    // procfs is always its own filesystem.
+    #[cfg(target_os = "linux")]
+    if let Ok(proc_metadata) = std::fs::metadata("/proc/self") {
+        assert!(!f.accepts_dir(&proc_metadata));
+    }
+    Ok(())
+}
+
+#[test]
+fn invalid_pattern_is_reported() {
+    let err = validate_patterns(&["ok/*".to_string(), "bad[".to_string()]).unwrap_err();
+    assert!(err.to_string().contains("bad["));
+}
+
+#[test]
+fn pattern_map_prefers_the_longest_matching_pattern() -> Result<()> {
+    let map = PatternMap::new(&BTreeMap::from([
+        ("*.bin".to_string(), 1),
+        ("db/*.bin".to_string(), 2),
+        ("/media".to_string(), 3),
+    ]))?;
+    assert_eq!(map.get(Path::new("a/b.bin")), Some(1));
+    assert_eq!(map.get(Path::new("x/db/b.bin")), Some(2));
+    assert_eq!(map.get(Path::new("media/clip.mov")), Some(3));
+    assert_eq!(map.get(Path::new("notes.txt")), None);
+    assert_eq!(
+        PatternMap::<u8>::new(&BTreeMap::new())?.get(Path::new("a")),
+        None
+    );
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use super::*;
+
+#[test]
+fn parse_reads_default_and_labelled_names() {
+    assert_eq!(
+        parse("full_20240221_100000"),
+        Some(PointName {
+            is_full: true,
+            timestamp: "20240221_100000"
+        })
+    );
+    assert_eq!(
+        parse("docs-v2_inc_20240221_100000123"),
+        Some(PointName {
+            is_full: false,
+            timestamp: "20240221_100000123"
+        })
+    );
+    assert_eq!(
+        parse("nightly_20240221_100000123_full"),
+        Some(PointName {
+            is_full: true,
+            timestamp: "20240221_100000123"
+        })
+    );
+    assert_eq!(parse("partial_full_20240221_100000"), None);
+    assert_eq!(parse("full_inc_20240221_100000"), None);
+    assert_eq!(parse("full_2024022_100000"), None);
+    assert_eq!(parse("metadata.json"), None);
+}
+
+#[test]
+fn render_drops_a_missing_label() {
+    let template = "{label}_{type}_{timestamp}";
+    assert_eq!(
+        render(template, Some("docs"), true, "20240221_100000123"),
+        "docs_full_20240221_100000123"
+    );
+    assert_eq!(
+        render(template, None, false, "20240221_100000123"),
+        "inc_20240221_100000123"
+    );
+    assert_eq!(
+        render(DEFAULT_TEMPLATE, Some("docs"), true, "20240221_100000123"),
+        "full_20240221_100000123"
+    );
+}
+
+#[test]
+fn validate_template_rejects_names_that_cannot_be_parsed() {
+    assert!(validate_template(DEFAULT_TEMPLATE).is_ok());
+    assert!(validate_template("{label}_{type}_{timestamp}").is_ok());
+    assert!(validate_template("backup_{timestamp}_{type}").is_ok());
+    assert!(validate_template("{label}-{type}-{timestamp}").is_err());
+    assert!(validate_template("{type}_{timestamp}_{timestamp}").is_err());
+    assert!(validate_template("{type}/{timestamp}").is_err());
+    assert!(validate_template("partial_{type}_{timestamp}").is_err());
+
+    assert!(validate_label("docs-v2").is_ok());
+    assert!(validate_label("my_docs").is_err());
+    assert!(validate_label("full").is_err());
+}
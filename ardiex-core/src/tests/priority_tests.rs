@@ -0,0 +1,30 @@
+use super::*;
+
+#[test]
+fn nothing_to_change_is_a_no_op() -> Result<()> {
+    lower_process_priority(None, false)
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn lowering_niceness_reaches_every_thread() -> Result<()> {
+    // Threads started before the call must be lowered too.
+    let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+    let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+    let worker = std::thread::spawn(move || {
+        ready_tx.send(()).ok();
+        done_rx.recv().ok();
+        unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) }
+    });
+    ready_rx.recv()?;
+
+    // Only ever raised, so a higher niceness left by another test is fine.
+    let before = unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+    let target = (before + 1).min(19);
+    lower_process_priority(Some(target), false)?;
+    assert_eq!(unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) }, target);
+
+    done_tx.send(())?;
+    assert_eq!(worker.join().expect("worker thread"), target);
+    Ok(())
+}
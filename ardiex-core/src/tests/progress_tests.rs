@@ -0,0 +1,95 @@
+use super::*;
+use crate::{delta, throttle};
+use anyhow::Result;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), ts))
+}
+
+#[test]
+fn only_large_files_get_their_own_progress() {
+    let progress = Progress::new("Test progress", Path::new("/backup"), 2, 0);
+    assert!(
+        progress
+            .large_file(Path::new("small.bin"), LARGE_FILE_BYTES - 1)
+            .is_none()
+    );
+    let file = progress
+        .large_file(Path::new("large.bin"), LARGE_FILE_BYTES)
+        .expect("large file progress");
+    assert_eq!(file.total_bytes, LARGE_FILE_BYTES);
+    assert_eq!(file.done, 0);
+}
+
+#[test]
+fn copy_and_delta_report_running_byte_counts() -> Result<()> {
+    let base = unique_temp_dir("ardiex_progress_bytes");
+    fs::create_dir_all(&base)?;
+    let original = base.join("original.bin");
+    let new = base.join("new.bin");
+    fs::write(&original, vec![1u8; 300 * 1024])?;
+    let mut content = vec![1u8; 700 * 1024 + 123];
+    content[100_000] = 2;
+    fs::write(&new, &content)?;
+    let size = content.len() as u64;
+
+    let mut copied = FileProgress::new("Test progress", &new, size, None);
+    throttle::copy_file(&new, &base.join("copy.bin"), None, Some(&mut copied))?;
+    assert_eq!(copied.done, size);
+    assert_eq!(copied.last_logged, 100);
+
+    let mut created = FileProgress::new("Test progress", &new, size, None);
+    let delta_data = delta::create_delta(&original, &new, Some(&mut created))?;
+    assert_eq!(created.done, size);
+
+    let mut applied = FileProgress::new("Test progress", &new, size, None);
+    let restored = base.join("restored.bin");
+    delta::apply_delta(&original, &delta_data, &restored, Some(&mut applied))?;
+    assert_eq!(applied.done, size);
+    assert_eq!(fs::read(&restored)?, content);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn progress_callback_sees_each_file_start_and_finish() {
+    let target = unique_temp_dir("ardiex_progress_callback");
+    let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = Arc::clone(&seen);
+    let watched = target.clone();
+    set_progress_callback(move |update| {
+        if update.target == watched {
+            sink.lock().unwrap().push((
+                update.file.map(Path::to_path_buf),
+                update.files_done,
+                update.bytes_done,
+                update.total_bytes,
+            ));
+        }
+    });
+
+    let mut progress = Progress::new("Test progress", &target, 2, 30);
+    clear_progress_callback();
+    progress.start_file(Path::new("a.txt"));
+    progress.finish_file(10);
+    progress.start_file(Path::new("b.txt"));
+    progress.finish_file(20);
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(
+        *seen,
+        vec![
+            (Some(PathBuf::from("a.txt")), 0, 0, 30),
+            (None, 1, 10, 30),
+            (Some(PathBuf::from("b.txt")), 1, 10, 30),
+            (None, 2, 30, 30),
+        ]
+    );
+}
@@ -0,0 +1,48 @@
+use super::*;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_nanos();
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), ts))
+}
+
+#[test]
+fn copy_file_copies_content_and_permissions_with_either_method() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_reflink_copy");
+    fs::create_dir_all(&dir)?;
+    let src = dir.join("src.bin");
+    let content: Vec<u8> = (0..200_000).map(|i| (i % 253) as u8).collect();
+    fs::write(&src, &content)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&src, fs::Permissions::from_mode(0o640))?;
+    }
+
+    let dst = dir.join("dst.bin");
+    let method = copy_file(&src, &dst, None)?;
+    assert!(matches!(method, CopyMethod::Reflink | CopyMethod::Copy));
+    assert_eq!(fs::read(&dst)?, content);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(fs::metadata(&dst)?.permissions().mode() & 0o777, 0o640);
+    }
+
+    // A clone must not share writes with its source.
+    fs::write(&dst, b"changed")?;
+    assert_eq!(fs::read(&src)?, content);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn copy_file_reports_missing_source() {
+    let dir = unique_temp_dir("ardiex_reflink_missing");
+    assert!(copy_file(&dir.join("missing"), &dir.join("dst"), None).is_err());
+}
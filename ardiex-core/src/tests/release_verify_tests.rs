@@ -0,0 +1,70 @@
+use super::*;
+use std::fs;
+use std::path::PathBuf;
+
+/// minisign key and signature of the bytes `test`.
+const PUBLIC_KEY: &str = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1633700835\tfile:test\tprehashed
+wLMDjy9FLAuxZ3q4NlEvkgtyhrr0gtTu6KC4KBJdITbbOeAi1zBIYo0v4iTgt8jJpIidRJnp94ABQkJAgAooBQ==
+";
+const TEST_SHA256: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let ts = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), ts))
+}
+
+#[test]
+fn signatures_verify_only_the_signed_checksums() {
+    assert!(verify_signature(b"test", SIGNATURE, PUBLIC_KEY).is_ok());
+    let public_key_file = format!("untrusted comment: minisign public key\n{}\n", PUBLIC_KEY);
+    assert!(verify_signature(b"test", SIGNATURE, &public_key_file).is_ok());
+
+    assert!(verify_signature(b"Test", SIGNATURE, PUBLIC_KEY).is_err());
+    assert!(verify_signature(b"test", "not a signature", PUBLIC_KEY).is_err());
+    assert!(verify_signature(b"test", SIGNATURE, "not a key").is_err());
+}
+
+#[test]
+fn expected_checksum_reads_sha256sum_lines() {
+    let checksums = format!(
+        "{hash}  ardiex-linux-amd64.tar.gz\n{hash} *ardiex-windows-amd64.zip\n",
+        hash = TEST_SHA256.to_uppercase()
+    );
+    assert_eq!(
+        expected_checksum(&checksums, "ardiex-linux-amd64.tar.gz").as_deref(),
+        Some(TEST_SHA256)
+    );
+    assert_eq!(
+        expected_checksum(&checksums, "ardiex-windows-amd64.zip").as_deref(),
+        Some(TEST_SHA256)
+    );
+    assert_eq!(
+        expected_checksum(&checksums, "ardiex-linux-arm64.tar.gz"),
+        None
+    );
+}
+
+#[test]
+fn verify_archive_rejects_modified_and_unlisted_archives() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_release_verify");
+    fs::create_dir_all(&dir)?;
+    let archive = dir.join("ardiex-linux-amd64.tar.gz");
+    fs::write(&archive, b"test")?;
+    let checksums = format!("{}  ardiex-linux-amd64.tar.gz\n", TEST_SHA256);
+
+    assert!(verify_archive(&archive, "ardiex-linux-amd64.tar.gz", &checksums).is_ok());
+    assert!(verify_archive(&archive, "ardiex-macos-arm64.tar.gz", &checksums).is_err());
+    fs::write(&archive, b"tampered")?;
+    let err = verify_archive(&archive, "ardiex-linux-amd64.tar.gz", &checksums)
+        .expect_err("modified archive must fail");
+    assert!(err.to_string().contains("Checksum mismatch"));
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
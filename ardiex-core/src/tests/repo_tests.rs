@@ -0,0 +1,44 @@
+use super::*;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_nanos();
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), ts))
+}
+
+#[test]
+fn init_writes_repo_json_once() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_repo_init");
+    let info = RepoInfo::new(BackupMode::Delta, BackupFormat::Archive, true, "laptop");
+    init(&dir, &info)?;
+
+    let written = check_readable(&dir)?.expect("repo.json written");
+    assert_eq!(written.format_version, REPO_FORMAT_VERSION);
+    assert_eq!(written.compression, "deflate");
+    assert_eq!(written.encryption, "chacha20-poly1305");
+    assert_eq!(written.created_by.host, "laptop");
+    assert!(init(&dir, &info).is_err());
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn check_readable_refuses_newer_formats_only() -> Result<()> {
+    let dir = unique_temp_dir("ardiex_repo_newer");
+    fs::create_dir_all(&dir)?;
+    assert_eq!(check_readable(&dir)?, None);
+
+    let mut info = RepoInfo::new(BackupMode::Copy, BackupFormat::Directory, false, "laptop");
+    info.format_version = REPO_FORMAT_VERSION + 1;
+    write(&dir, &info)?;
+    let err = check_readable(&dir).unwrap_err();
+    assert!(err.to_string().contains("upgrade ardiex"));
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
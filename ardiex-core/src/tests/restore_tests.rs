@@ -0,0 +1,693 @@
+use super::*;
+use crate::delta;
+use std::collections::HashSet;
+use std::fs;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_nanos();
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), ts))
+}
+
+fn make_backup_entry(name: &str, is_full: bool) -> BackupEntry {
+    BackupEntry {
+        name: name.to_string(),
+        path: PathBuf::from(format!("/tmp/{}", name)),
+        is_full,
+        timestamp: if is_full {
+            name.trim_start_matches("full_").to_string()
+        } else {
+            name.trim_start_matches("inc_").to_string()
+        },
+    }
+}
+
+#[test]
+fn list_backups_filters_and_sorts_entries() -> Result<()> {
+    let base = unique_temp_dir("ardiex_restore_list");
+    fs::create_dir_all(base.join("full_20260224_120000"))?;
+    fs::create_dir_all(base.join("inc_20260224_130000"))?;
+    fs::create_dir_all(base.join("full_20260224_110000"))?;
+    fs::create_dir_all(base.join("ignored_dir"))?;
+    fs::write(base.join("inc_20260224_140000"), b"not a dir")?;
+
+    let backups = RestoreManager::list_backups(&base)?;
+    let names: Vec<String> = backups.into_iter().map(|b| b.name).collect();
+    assert_eq!(
+        names,
+        vec![
+            "full_20260224_110000".to_string(),
+            "full_20260224_120000".to_string(),
+            "inc_20260224_130000".to_string(),
+        ]
+    );
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn select_backups_uses_latest_full_before_cutoff() -> Result<()> {
+    let backups = vec![
+        make_backup_entry("full_20260224_100000", true),
+        make_backup_entry("inc_20260224_101000", false),
+        make_backup_entry("full_20260224_110000", true),
+        make_backup_entry("inc_20260224_111000", false),
+        make_backup_entry("inc_20260224_112000", false),
+    ];
+
+    let selected = RestoreManager::select_backups(&backups, Some("20260224_111500"))?;
+    let names: Vec<String> = selected.into_iter().map(|b| b.name.clone()).collect();
+    assert_eq!(
+        names,
+        vec![
+            "full_20260224_110000".to_string(),
+            "inc_20260224_111000".to_string(),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn select_backups_fails_when_no_full_exists_before_cutoff() {
+    let backups = vec![
+        make_backup_entry("inc_20260224_101000", false),
+        make_backup_entry("inc_20260224_102000", false),
+    ];
+
+    let err = RestoreManager::select_backups(&backups, Some("20260224_103000"))
+        .expect_err("must fail without full backup");
+    assert!(err.to_string().contains("No full backup found"));
+}
+
+#[test]
+fn strip_delta_extension_removes_only_delta_suffix() {
+    assert_eq!(
+        RestoreManager::strip_delta_extension(Path::new("a.txt.delta")),
+        PathBuf::from("a.txt")
+    );
+    assert_eq!(
+        RestoreManager::strip_delta_extension(Path::new("nested/a.bin.delta")),
+        PathBuf::from("nested/a.bin")
+    );
+    assert_eq!(
+        RestoreManager::strip_delta_extension(Path::new("a.txt")),
+        PathBuf::from("a.txt")
+    );
+    assert_eq!(
+        RestoreManager::strip_delta_extension(Path::new("nested/Makefile..delta")),
+        PathBuf::from("nested/Makefile")
+    );
+    assert_eq!(
+        RestoreManager::strip_delta_extension(Path::new("nested/a.bin.rdelta")),
+        PathBuf::from("nested/a.bin")
+    );
+}
+
+#[test]
+fn delta_names_round_trip_for_extensionless_and_trailing_dot_files() {
+    let names = [
+        "a.txt",
+        "Makefile",
+        "foo.",
+        "foo..",
+        ".bashrc",
+        "nested/x.tar.gz",
+    ];
+    let mut deltas = std::collections::HashSet::new();
+    for name in names {
+        for reverse in [false, true] {
+            let delta = delta::delta_path(Path::new(name), reverse);
+            assert_eq!(
+                RestoreManager::strip_delta_extension(&delta),
+                PathBuf::from(name),
+                "{:?}",
+                delta
+            );
+            assert!(deltas.insert(delta));
+        }
+    }
+    assert_eq!(
+        delta::delta_path(Path::new("Makefile"), false),
+        PathBuf::from("Makefile..delta")
+    );
+    assert_eq!(
+        delta::delta_path(Path::new("foo."), false),
+        PathBuf::from("foo...delta")
+    );
+}
+
+#[test]
+fn restore_to_point_fails_when_backup_dir_is_empty() -> Result<()> {
+    let backup_dir = unique_temp_dir("ardiex_restore_empty_backup");
+    let target_dir = unique_temp_dir("ardiex_restore_empty_target");
+    fs::create_dir_all(&backup_dir)?;
+
+    let err = RestoreManager::restore_to_point(
+        &backup_dir,
+        &target_dir,
+        None,
+        &RestoreOptions::default(),
+    )
+    .expect_err("restore must fail when no backups exist");
+    assert!(err.to_string().contains("No backups found"));
+
+    fs::remove_dir_all(&backup_dir)?;
+    if target_dir.exists() {
+        fs::remove_dir_all(&target_dir)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn restore_to_point_applies_full_and_incremental_copy_chain() -> Result<()> {
+    let base = unique_temp_dir("ardiex_restore_copy_chain");
+    let backup_dir = base.join("backup");
+    let target_dir = base.join("target");
+    let full_dir = backup_dir.join("full_20260224_120000");
+    let inc_dir = backup_dir.join("inc_20260224_121000");
+    fs::create_dir_all(&full_dir)?;
+    fs::create_dir_all(&inc_dir)?;
+
+    fs::write(full_dir.join("a.txt"), b"v1")?;
+    fs::write(inc_dir.join("a.txt"), b"v2")?;
+
+    let restored = RestoreManager::restore_to_point(
+        &backup_dir,
+        &target_dir,
+        None,
+        &RestoreOptions::default(),
+    )?;
+    assert_eq!(restored, 2);
+    assert_eq!(fs::read(target_dir.join("a.txt"))?, b"v2");
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn restore_to_point_applies_delta_incremental() -> Result<()> {
+    let base = unique_temp_dir("ardiex_restore_delta_chain");
+    let backup_dir = base.join("backup");
+    let target_dir = base.join("target");
+    let full_dir = backup_dir.join("full_20260224_120000");
+    let inc_dir = backup_dir.join("inc_20260224_121000");
+    fs::create_dir_all(&full_dir)?;
+    fs::create_dir_all(&inc_dir)?;
+
+    let full_file = full_dir.join("a.txt");
+    fs::write(&full_file, b"hello-old")?;
+    let tmp_new = base.join("tmp_new.txt");
+    fs::write(&tmp_new, b"hello-new")?;
+
+    let delta_data = delta::create_delta(&full_file, &tmp_new, None)?;
+    delta::save_delta(
+        &delta::encode_delta(&delta_data)?,
+        &inc_dir.join("a.txt.delta"),
+    )?;
+
+    let restored = RestoreManager::restore_to_point(
+        &backup_dir,
+        &target_dir,
+        None,
+        &RestoreOptions::default(),
+    )?;
+    assert_eq!(restored, 2);
+    assert_eq!(fs::read(target_dir.join("a.txt"))?, b"hello-new");
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn restore_to_point_respects_cutoff_and_skips_later_incrementals() -> Result<()> {
+    let base = unique_temp_dir("ardiex_restore_cutoff");
+    let backup_dir = base.join("backup");
+    let target_dir = base.join("target");
+    let full_dir = backup_dir.join("full_20260224_120000");
+    let inc1_dir = backup_dir.join("inc_20260224_121000");
+    let inc2_dir = backup_dir.join("inc_20260224_122000");
+    fs::create_dir_all(&full_dir)?;
+    fs::create_dir_all(&inc1_dir)?;
+    fs::create_dir_all(&inc2_dir)?;
+
+    fs::write(full_dir.join("a.txt"), b"v1")?;
+    fs::write(inc1_dir.join("a.txt"), b"v2")?;
+    fs::write(inc2_dir.join("a.txt"), b"v3")?;
+
+    let restored = RestoreManager::restore_to_point(
+        &backup_dir,
+        &target_dir,
+        Some("20260224_121000"),
+        &RestoreOptions::default(),
+    )?;
+    assert_eq!(restored, 2);
+    assert_eq!(fs::read(target_dir.join("a.txt"))?, b"v2");
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn list_backups_fails_when_backup_dir_does_not_exist() {
+    let missing = PathBuf::from("/tmp/ardiex_restore_missing_backup_dir");
+    let err = RestoreManager::list_backups(&missing)
+        .expect_err("listing backups on missing dir must fail");
+    assert!(!err.to_string().is_empty());
+}
+
+#[test]
+fn restore_to_point_fails_when_cutoff_is_before_any_full() -> Result<()> {
+    let base = unique_temp_dir("ardiex_restore_cutoff_before_full");
+    let backup_dir = base.join("backup");
+    let target_dir = base.join("target");
+    fs::create_dir_all(backup_dir.join("full_20260224_120000"))?;
+
+    let err = RestoreManager::restore_to_point(
+        &backup_dir,
+        &target_dir,
+        Some("20260224_110000"),
+        &RestoreOptions::default(),
+    )
+    .expect_err("cutoff before first full must fail");
+    assert!(
+        err.to_string()
+            .contains("No full backup found before restore point")
+    );
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn restore_to_point_fails_on_invalid_delta_file() -> Result<()> {
+    let base = unique_temp_dir("ardiex_restore_invalid_delta");
+    let backup_dir = base.join("backup");
+    let target_dir = base.join("target");
+    let full_dir = backup_dir.join("full_20260224_120000");
+    let inc_dir = backup_dir.join("inc_20260224_121000");
+    fs::create_dir_all(&full_dir)?;
+    fs::create_dir_all(&inc_dir)?;
+    fs::write(full_dir.join("a.txt"), b"v1")?;
+    fs::write(inc_dir.join("a.txt.delta"), b"{invalid delta")?;
+
+    let err = RestoreManager::restore_to_point(
+        &backup_dir,
+        &target_dir,
+        None,
+        &RestoreOptions::default(),
+    )
+    .expect_err("invalid delta content must fail restore");
+    assert!(!err.to_string().is_empty());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn restore_to_point_times_out_while_backup_dir_is_locked() -> Result<()> {
+    let base = unique_temp_dir("ardiex_restore_locked");
+    let backup_dir = base.join("backup");
+    let target_dir = base.join("target");
+    fs::create_dir_all(backup_dir.join("full_20260224_120000"))?;
+    fs::write(backup_dir.join("full_20260224_120000").join("a.txt"), b"v1")?;
+
+    let _held = BackupDirLock::acquire(&backup_dir, "backup")?;
+    let options = RestoreOptions {
+        lock_timeout_secs: Some(0),
+        ..Default::default()
+    };
+    let err = RestoreManager::restore_to_point(&backup_dir, &target_dir, None, &options)
+        .expect_err("restore must not read a chain that is being written");
+    assert!(
+        err.to_string()
+            .contains("is locked by another ardiex process")
+    );
+    assert!(!target_dir.exists());
+
+    drop(_held);
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn restore_to_point_waits_for_in_flight_backup() -> Result<()> {
+    let base = unique_temp_dir("ardiex_restore_wait");
+    let backup_dir = base.join("backup");
+    let target_dir = base.join("target");
+    fs::create_dir_all(backup_dir.join("full_20260224_120000"))?;
+    fs::write(backup_dir.join("full_20260224_120000").join("a.txt"), b"v1")?;
+
+    // Simulate a backup that lands a new incremental while restore waits.
+    let held = BackupDirLock::acquire(&backup_dir, "backup")?;
+    let writer_dir = backup_dir.clone();
+    let writer = std::thread::spawn(move || -> Result<()> {
+        std::thread::sleep(Duration::from_millis(300));
+        fs::create_dir_all(writer_dir.join("inc_20260224_130000"))?;
+        fs::write(writer_dir.join("inc_20260224_130000").join("a.txt"), b"v2")?;
+        drop(held);
+        Ok(())
+    });
+
+    let restored = RestoreManager::restore_to_point(
+        &backup_dir,
+        &target_dir,
+        None,
+        &RestoreOptions::default(),
+    )?;
+    writer.join().expect("writer thread panicked")?;
+
+    assert_eq!(restored, 2);
+    assert_eq!(fs::read(target_dir.join("a.txt"))?, b"v2");
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn list_backups_ignores_partial_backup_dirs() -> Result<()> {
+    let base = unique_temp_dir("ardiex_restore_list_partial");
+    fs::create_dir_all(base.join("full_20260224_120000"))?;
+    fs::create_dir_all(base.join("partial_inc_20260224_130000"))?;
+
+    let backups = RestoreManager::list_backups(&base)?;
+    let names: Vec<String> = backups.into_iter().map(|b| b.name).collect();
+    assert_eq!(names, vec!["full_20260224_120000".to_string()]);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn restore_to_point_with_throttle_restores_same_content() -> Result<()> {
+    let base = unique_temp_dir("ardiex_restore_throttled");
+    let backup_dir = base.join("backup");
+    let target_dir = base.join("target");
+    let full_dir = backup_dir.join("full_20260224_120000");
+    fs::create_dir_all(&full_dir)?;
+    fs::write(full_dir.join("a.txt"), vec![5u8; 128 * 1024])?;
+
+    let options = RestoreOptions {
+        throttle_mb_per_sec: Some(1.0),
+        ..Default::default()
+    };
+    let started = std::time::Instant::now();
+    let restored = RestoreManager::restore_to_point(&backup_dir, &target_dir, None, &options)?;
+    assert_eq!(restored, 1);
+    assert_eq!(fs::read(target_dir.join("a.txt"))?, vec![5u8; 128 * 1024]);
+    // 128 KiB at 1 MB/s takes ~125ms.
+    assert!(started.elapsed() >= Duration::from_millis(110));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn restore_to_point_rejects_non_positive_throttle() -> Result<()> {
+    let base = unique_temp_dir("ardiex_restore_bad_throttle");
+    let backup_dir = base.join("backup");
+    fs::create_dir_all(backup_dir.join("full_20260224_120000"))?;
+
+    let options = RestoreOptions {
+        throttle_mb_per_sec: Some(0.0),
+        ..Default::default()
+    };
+    let err = RestoreManager::restore_to_point(&backup_dir, &base.join("target"), None, &options)
+        .expect_err("zero throttle must be rejected");
+    assert!(err.to_string().contains("positive MB/s"));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn verify_backup_rejects_target_that_differs_from_point() -> Result<()> {
+    let base = unique_temp_dir("ardiex_restore_verify");
+    let point = base.join("full_20260224_100000");
+    let target = base.join("target");
+    fs::create_dir_all(point.join("sub"))?;
+    fs::create_dir_all(target.join("sub"))?;
+    fs::write(point.join("sub/a.txt"), b"stored")?;
+    fs::write(target.join("sub/a.txt"), b"stored")?;
+    let entry = BackupEntry {
+        name: "full_20260224_100000".to_string(),
+        path: point.clone(),
+        is_full: true,
+        timestamp: "20260224_100000".to_string(),
+    };
+
+    let chain = [&entry];
+    let final_points = BTreeMap::new();
+    let position = ChainPosition {
+        index: 0,
+        chain: &chain,
+        final_points: &final_points,
+    };
+
+    RestoreManager::verify_backup(&entry, &position, &target, &HashSet::new())?;
+    fs::write(target.join("sub/a.txt"), b"stoved")?;
+    let err =
+        RestoreManager::verify_backup(&entry, &position, &target, &HashSet::new()).unwrap_err();
+    assert!(err.to_string().contains("Verification failed"));
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+fn set_mtime(path: &Path, secs: u64) -> Result<()> {
+    fs::File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(UNIX_EPOCH + Duration::from_secs(secs))?;
+    Ok(())
+}
+
+#[test]
+fn overwrite_policy_decides_which_existing_files_are_replaced() -> Result<()> {
+    let base = unique_temp_dir("ardiex_restore_overwrite");
+    let backup_dir = base.join("backup");
+    let target_dir = base.join("target");
+    // Taken at 2026-02-24 12:00 UTC; without a manifest that is the
+    // backed-up version's age.
+    let full_dir = backup_dir.join("full_20260224_120000");
+    fs::create_dir_all(&full_dir)?;
+    fs::create_dir_all(&target_dir)?;
+    for name in ["old.txt", "new.txt", "missing.txt"] {
+        fs::write(full_dir.join(name), b"backup")?;
+    }
+    fs::write(target_dir.join("old.txt"), b"local")?;
+    fs::write(target_dir.join("new.txt"), b"local")?;
+    set_mtime(&target_dir.join("old.txt"), 1_700_000_000)?;
+    set_mtime(&target_dir.join("new.txt"), 1_800_000_000)?;
+
+    let never = RestoreOptions {
+        overwrite: OverwritePolicy::Never,
+        ..Default::default()
+    };
+    assert_eq!(
+        RestoreManager::restore_to_point(&backup_dir, &target_dir, None, &never)?,
+        1
+    );
+    assert_eq!(fs::read(target_dir.join("old.txt"))?, b"local");
+    assert_eq!(fs::read(target_dir.join("missing.txt"))?, b"backup");
+
+    let if_older = RestoreOptions {
+        overwrite: OverwritePolicy::IfOlder,
+        backup_existing: true,
+        ..Default::default()
+    };
+    fs::remove_file(target_dir.join("missing.txt"))?;
+    assert_eq!(
+        RestoreManager::restore_to_point(&backup_dir, &target_dir, None, &if_older)?,
+        2
+    );
+    assert_eq!(fs::read(target_dir.join("old.txt"))?, b"backup");
+    assert_eq!(fs::read(target_dir.join("new.txt"))?, b"local");
+
+    // The replaced file was moved next to the target, not discarded.
+    let side_dirs: Vec<PathBuf> = fs::read_dir(&base)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.to_string_lossy().contains("target.replaced_"))
+        .collect();
+    assert_eq!(side_dirs.len(), 1);
+    assert_eq!(fs::read(side_dirs[0].join("old.txt"))?, b"local");
+    assert!(!side_dirs[0].join("new.txt").exists());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn kept_files_skip_the_whole_delta_chain() -> Result<()> {
+    let base = unique_temp_dir("ardiex_restore_keep_delta");
+    let backup_dir = base.join("backup");
+    let target_dir = base.join("target");
+    let full_dir = backup_dir.join("full_20260224_120000");
+    let inc_dir = backup_dir.join("inc_20260224_121000");
+    fs::create_dir_all(&full_dir)?;
+    fs::create_dir_all(&inc_dir)?;
+    fs::create_dir_all(&target_dir)?;
+
+    let original = base.join("original.bin");
+    let changed = base.join("changed.bin");
+    fs::write(&original, vec![1u8; 8192])?;
+    fs::write(&changed, vec![2u8; 8192])?;
+    fs::copy(&original, full_dir.join("data.bin"))?;
+    let delta_data = delta::create_delta(&original, &changed, None)?;
+    delta::save_delta(
+        &delta::encode_delta(&delta_data)?,
+        &inc_dir.join("data.bin.delta"),
+    )?;
+    fs::write(target_dir.join("data.bin"), b"local edit")?;
+
+    let options = RestoreOptions {
+        overwrite: OverwritePolicy::Never,
+        verify_writes: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        RestoreManager::restore_to_point(&backup_dir, &target_dir, None, &options)?,
+        0
+    );
+    assert_eq!(fs::read(target_dir.join("data.bin"))?, b"local edit");
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn preview_restore_splits_new_and_existing_files_without_writing() -> Result<()> {
+    let base = unique_temp_dir("ardiex_restore_preview");
+    let backup_dir = base.join("backup");
+    let target_dir = base.join("target");
+    let full_dir = backup_dir.join("full_20260224_120000");
+    let inc_dir = backup_dir.join("inc_20260224_121000");
+    let later_dir = backup_dir.join("inc_20260224_122000");
+    fs::create_dir_all(full_dir.join("sub"))?;
+    fs::create_dir_all(&inc_dir)?;
+    fs::create_dir_all(&later_dir)?;
+    fs::create_dir_all(&target_dir)?;
+    fs::write(full_dir.join("a.txt"), b"v1")?;
+    fs::write(full_dir.join("sub/b.txt"), b"b")?;
+    fs::write(inc_dir.join("c.txt.delta"), b"delta")?;
+    fs::write(later_dir.join("d.txt"), b"later")?;
+    fs::write(target_dir.join("a.txt"), b"local")?;
+
+    let preview =
+        RestoreManager::preview_restore(&backup_dir, &target_dir, Some("20260224_121000"))?;
+    assert_eq!(
+        preview.points,
+        vec![
+            "full_20260224_120000".to_string(),
+            "inc_20260224_121000".to_string()
+        ]
+    );
+    assert_eq!(preview.existing_files, vec![PathBuf::from("a.txt")]);
+    assert_eq!(
+        preview.new_files,
+        vec![PathBuf::from("c.txt"), PathBuf::from("sub/b.txt")]
+    );
+    assert_eq!(fs::read(target_dir.join("a.txt"))?, b"local");
+    assert!(!target_dir.join("c.txt").exists());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn deltas_are_applied_to_the_version_they_were_made_against() -> Result<()> {
+    let base = unique_temp_dir("ardiex_restore_delta_base");
+    let backup_dir = base.join("backup");
+    let target_dir = base.join("target");
+    let full_dir = backup_dir.join("full_20260224_120000");
+    let first_inc = backup_dir.join("inc_20260224_121000");
+    let second_inc = backup_dir.join("inc_20260224_122000");
+    fs::create_dir_all(&full_dir)?;
+    fs::create_dir_all(&first_inc)?;
+    fs::create_dir_all(&second_inc)?;
+
+    // Both incrementals diff against the full's copy, as backups do, and
+    // change different blocks.
+    let v1 = [vec![b'a'; 4096], vec![b'a'; 4096]].concat();
+    let v2 = [vec![b'b'; 4096], vec![b'a'; 4096]].concat();
+    let v3 = [vec![b'a'; 4096], vec![b'c'; 4096]].concat();
+    fs::write(full_dir.join("a.bin"), &v1)?;
+    for (content, inc_dir) in [(&v2, &first_inc), (&v3, &second_inc)] {
+        let new = base.join("new.bin");
+        fs::write(&new, content)?;
+        let delta_data = delta::create_delta(&full_dir.join("a.bin"), &new, None)?;
+        delta::save_delta(
+            &delta::encode_delta(&delta_data)?,
+            &inc_dir.join("a.bin.delta"),
+        )?;
+    }
+
+    RestoreManager::restore_to_point(&backup_dir, &target_dir, None, &RestoreOptions::default())?;
+    assert_eq!(fs::read(target_dir.join("a.bin"))?, v3);
+
+    // A delta whose base is nowhere in the chain fails instead of writing
+    // only its changed blocks.
+    let orphan_base = base.join("orphan_base.bin");
+    let orphan_new = base.join("orphan_new.bin");
+    fs::write(&orphan_base, b"base nobody kept")?;
+    fs::write(&orphan_new, b"base nobody kept, changed")?;
+    let delta_data = delta::create_delta(&orphan_base, &orphan_new, None)?;
+    delta::save_delta(
+        &delta::encode_delta(&delta_data)?,
+        &second_inc.join("orphan.txt.delta"),
+    )?;
+    let other_target = base.join("other_target");
+    let err = RestoreManager::restore_to_point(
+        &backup_dir,
+        &other_target,
+        None,
+        &RestoreOptions::default(),
+    )
+    .expect_err("a delta without its base must fail");
+    assert!(format!("{:#}", err).contains("No base for the delta"));
+    assert!(!other_target.join("orphan.txt").exists());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
+
+#[test]
+fn rank_backup_dirs_puts_the_newest_healthy_chain_first() -> Result<()> {
+    let base = unique_temp_dir("ardiex_restore_rank");
+    let older = base.join("older");
+    let newer = base.join("newer");
+    let broken = base.join("broken");
+    let missing = base.join("missing");
+    fs::create_dir_all(older.join("full_20260224_120000"))?;
+    fs::create_dir_all(newer.join("full_20260224_120000"))?;
+    fs::create_dir_all(newer.join("inc_20260224_130000"))?;
+    fs::create_dir_all(broken.join("full_20260224_120000"))?;
+    fs::create_dir_all(broken.join("inc_20260224_140000"))?;
+    fs::write(
+        broken.join("inc_20260224_140000").join("a.txt.delta"),
+        b"{invalid delta",
+    )?;
+
+    let ranked = RestoreManager::rank_backup_dirs(
+        &[
+            missing.clone(),
+            broken.clone(),
+            older.clone(),
+            newer.clone(),
+        ],
+        None,
+    );
+    let order: Vec<&PathBuf> = ranked.iter().map(|health| &health.backup_dir).collect();
+    assert_eq!(order, vec![&newer, &older, &broken, &missing]);
+    assert_eq!(ranked[0].latest_point.as_deref(), Some("20260224_130000"));
+    assert!(ranked[0].problem.is_none() && ranked[1].problem.is_none());
+    assert_eq!(ranked[2].problem.as_deref(), Some("broken backup chain"));
+    assert!(ranked[3].problem.is_some());
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use super::*;
+
+fn key(byte: u8) -> SigningKey {
+    SigningKey::from_bytes(vec![byte; 32]).expect("32-byte key")
+}
+
+#[test]
+fn signatures_verify_only_for_the_same_key_and_data() {
+    let signature = key(1).sign(b"metadata");
+    assert_eq!(signature.len(), 64);
+    assert!(key(1).verify(b"metadata", &signature));
+    assert!(!key(1).verify(b"metadata!", &signature));
+    assert!(!key(2).verify(b"metadata", &signature));
+    assert!(!key(1).verify(b"metadata", "not hex"));
+}
+
+#[test]
+fn point_signatures_bind_name_and_content() {
+    let signature = key(1).sign_point("full_20260224_100000", "abc");
+    assert!(key(1).verify_point("full_20260224_100000", "abc", &signature));
+    assert!(!key(1).verify_point("full_20260224_110000", "abc", &signature));
+    assert!(!key(1).verify_point("full_20260224_100000", "abd", &signature));
+}
+
+#[test]
+fn short_keys_are_rejected() {
+    assert!(SigningKey::from_bytes(b"short".to_vec()).is_err());
+}
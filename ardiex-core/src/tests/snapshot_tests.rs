@@ -0,0 +1,70 @@
+use super::*;
+
+#[test]
+fn specs_round_trip_through_parse_and_spec() -> Result<()> {
+    for text in [
+        "btrfs",
+        "btrfs:/data",
+        "lvm:vg0/data:/srv/data",
+        "lvm:vg0/data:/srv/data:5G",
+        "vss",
+    ] {
+        assert_eq!(spec(&parse_spec(text)?), text);
+    }
+    assert!(parse_spec("zfs").is_err());
+    assert!(parse_spec("lvm:vg0/data").is_err());
+    Ok(())
+}
+
+#[test]
+fn source_is_mapped_into_the_snapshot() -> Result<()> {
+    assert_eq!(
+        map_into(
+            Path::new("/srv/data/db"),
+            Path::new("/srv/data"),
+            Path::new("/tmp/snap")
+        )?,
+        PathBuf::from("/tmp/snap/db")
+    );
+    assert!(
+        map_into(
+            Path::new("/home/me"),
+            Path::new("/srv"),
+            Path::new("/tmp/snap")
+        )
+        .is_err()
+    );
+    Ok(())
+}
+
+#[test]
+fn lvm_settings_must_cover_the_source() {
+    let config = SnapshotConfig::Lvm {
+        volume: "vg0/data".to_string(),
+        mount_point: PathBuf::from("/srv/data"),
+        size: None,
+    };
+    assert!(validate(Path::new("/srv/data/db"), &config).is_ok());
+    assert!(validate(Path::new("/home/me"), &config).is_err());
+
+    let config = SnapshotConfig::Lvm {
+        volume: "data".to_string(),
+        mount_point: PathBuf::from("/srv/data"),
+        size: None,
+    };
+    assert!(validate(Path::new("/srv/data/db"), &config).is_err());
+}
+
+#[test]
+fn vss_output_gives_the_shadow_path_of_the_source() -> Result<()> {
+    let (id, device) =
+        parse_vss_output("{1A2B}\r\n\\\\?\\GLOBALROOT\\Device\\HarddiskVolumeShadowCopy5\r\n")?;
+    assert_eq!(id, "{1A2B}");
+    assert_eq!(
+        vss_source_path(&device, "C:\\Users\\me")?,
+        PathBuf::from("\\\\?\\GLOBALROOT\\Device\\HarddiskVolumeShadowCopy5\\Users\\me")
+    );
+    assert!(parse_vss_output("").is_err());
+    assert!(vss_source_path(&device, "\\\\server\\share").is_err());
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use super::*;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_nanos();
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), ts))
+}
+
+#[test]
+fn throttle_rejects_non_positive_rates() {
+    assert!(Throttle::from_mb_per_sec(0.0).is_err());
+    assert!(Throttle::from_mb_per_sec(-1.0).is_err());
+    assert!(Throttle::from_mb_per_sec(f64::NAN).is_err());
+}
+
+#[test]
+fn throttled_copy_is_paced_to_the_configured_rate() -> Result<()> {
+    let base = unique_temp_dir("ardiex_throttle_copy");
+    fs::create_dir_all(&base)?;
+    let src = base.join("src.bin");
+    let dst = base.join("dst.bin");
+    fs::write(&src, vec![3u8; 512 * 1024])?;
+
+    // 2 MB/s over 0.5 MB must take at least ~250ms.
+    let mut throttle = Throttle::from_mb_per_sec(2.0)?;
+    let started = Instant::now();
+    let copied = copy_file(&src, &dst, Some(&mut throttle), None)?;
+
+    assert_eq!(copied, 512 * 1024);
+    assert!(started.elapsed() >= Duration::from_millis(240));
+    assert_eq!(fs::read(&dst)?, fs::read(&src)?);
+
+    fs::remove_dir_all(&base)?;
+    Ok(())
+}
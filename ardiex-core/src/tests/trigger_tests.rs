@@ -0,0 +1,77 @@
+use super::*;
+
+fn trigger(dir: &str, reason: TriggerReason) -> BackupTrigger {
+    BackupTrigger::new(PathBuf::from(dir), reason)
+}
+
+#[test]
+fn duplicate_triggers_for_a_source_coalesce_into_one() {
+    let mut queue = TriggerQueue::default();
+    assert!(queue.push(trigger("/a", TriggerReason::Cron)));
+    assert!(!queue.push(trigger("/a", TriggerReason::FileChange)));
+    assert!(!queue.push(trigger("/a", TriggerReason::Cron)));
+
+    assert_eq!(
+        queue.pop_batch(10),
+        vec![trigger("/a", TriggerReason::FileChange)]
+    );
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn batches_follow_priority_then_arrival_order() {
+    let mut queue = TriggerQueue::default();
+    queue.push(trigger("/cron-1", TriggerReason::Cron));
+    queue.push(trigger("/event", TriggerReason::FileChange));
+    queue.push(trigger("/cron-2", TriggerReason::Cron));
+    queue.push(trigger("/startup", TriggerReason::Startup));
+
+    assert_eq!(
+        queue.pending(),
+        vec![
+            (PathBuf::from("/startup"), TriggerReason::Startup),
+            (PathBuf::from("/event"), TriggerReason::FileChange),
+            (PathBuf::from("/cron-1"), TriggerReason::Cron),
+            (PathBuf::from("/cron-2"), TriggerReason::Cron),
+        ]
+    );
+    assert_eq!(
+        queue.pop_batch(2),
+        vec![
+            trigger("/startup", TriggerReason::Startup),
+            trigger("/event", TriggerReason::FileChange),
+        ]
+    );
+    assert_eq!(
+        queue.pop_batch(2),
+        vec![
+            trigger("/cron-1", TriggerReason::Cron),
+            trigger("/cron-2", TriggerReason::Cron),
+        ]
+    );
+    assert!(queue.pop_batch(2).is_empty());
+}
+
+#[test]
+fn merged_triggers_keep_changed_paths_unless_one_scans_everything() {
+    let paths = |names: &[&str]| Some(names.iter().map(PathBuf::from).collect());
+    let mut queue = TriggerQueue::default();
+    queue.push(BackupTrigger::file_change(
+        PathBuf::from("/a"),
+        paths(&["/a/x"]),
+    ));
+    queue.push(BackupTrigger::file_change(
+        PathBuf::from("/a"),
+        paths(&["/a/y"]),
+    ));
+    queue.push(BackupTrigger::file_change(
+        PathBuf::from("/b"),
+        paths(&["/b/x"]),
+    ));
+    queue.push(trigger("/b", TriggerReason::Cron));
+
+    let batch = queue.pop_batch(10);
+    assert_eq!(batch[0].changed_paths, paths(&["/a/x", "/a/y"]));
+    assert_eq!(batch[1].source_dir, PathBuf::from("/b"));
+    assert_eq!(batch[1].changed_paths, None);
+}
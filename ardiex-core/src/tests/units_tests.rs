@@ -0,0 +1,102 @@
+use super::*;
+
+#[test]
+fn parse_size_accepts_units_and_bare_numbers() -> Result<()> {
+    assert_eq!(parse_size("500MB", 1)?, 500 * MB);
+    assert_eq!(parse_size("1.5gb", 1)?, 1536 * MB);
+    assert_eq!(parse_size("64 KiB", 1)?, 64 * KB);
+    assert_eq!(parse_size("2k", 1)?, 2 * KB);
+    assert_eq!(parse_size("20", MB)?, 20 * MB);
+    assert!(parse_size("10XB", 1).is_err());
+    assert!(parse_size("MB", 1).is_err());
+    assert!(parse_size("-5MB", 1).is_err());
+    Ok(())
+}
+
+#[test]
+fn parse_duration_accepts_compound_values() -> Result<()> {
+    assert_eq!(parse_duration("2h", 1)?, 7200);
+    assert_eq!(parse_duration("90d", 1)?, 90 * 86400);
+    assert_eq!(parse_duration("1h30m", 1)?, 5400);
+    assert_eq!(parse_duration("1.5m", 1)?, 90);
+    assert_eq!(parse_duration("1w", 1)?, 7 * 86400);
+    assert_eq!(parse_duration("48", SECS_PER_HOUR)?, 48 * 3600);
+    assert!(parse_duration("", 1).is_err());
+    assert!(parse_duration("5y", 1).is_err());
+    assert!(parse_duration("1h30", 1).is_err());
+    Ok(())
+}
+
+#[test]
+fn parse_as_requires_whole_units() -> Result<()> {
+    assert_eq!(parse_size_as("1GB", MB)?, 1024);
+    assert_eq!(parse_duration_as("2d", SECS_PER_HOUR)?, 48);
+    let err = parse_size_as("100KB", MB).expect_err("must reject fractional MB");
+    assert!(err.to_string().contains("multiple of 1MB"));
+    assert!(parse_duration_as("90m", SECS_PER_HOUR).is_err());
+    Ok(())
+}
+
+#[test]
+fn format_uses_canonical_units() {
+    assert_eq!(format_size(500 * MB), "500MB");
+    assert_eq!(format_size(1024 * MB), "1GB");
+    assert_eq!(format_size(1536 * MB), "1536MB");
+    assert_eq!(format_size(1000), "1000B");
+    assert_eq!(format_duration(60), "1m");
+    assert_eq!(format_duration(5400), "1h30m");
+    assert_eq!(format_duration(90 * 86400), "90d");
+    assert_eq!(format_duration(0), "0s");
+}
+
+#[test]
+fn deserializers_accept_numbers_and_strings() {
+    #[derive(Deserialize)]
+    struct Sample {
+        #[serde(deserialize_with = "deserialize_size_mb")]
+        size_mb: u64,
+        #[serde(deserialize_with = "deserialize_duration_secs")]
+        interval_secs: u64,
+        #[serde(deserialize_with = "deserialize_duration_hours")]
+        alert_hours: u64,
+    }
+
+    let sample: Sample =
+        serde_json::from_str(r#"{"size_mb": "2GB", "interval_secs": "5m", "alert_hours": "3d"}"#)
+            .expect("unit strings should parse");
+    assert_eq!(sample.size_mb, 2048);
+    assert_eq!(sample.interval_secs, 300);
+    assert_eq!(sample.alert_hours, 72);
+
+    let sample: Sample =
+        serde_json::from_str(r#"{"size_mb": 20, "interval_secs": 60, "alert_hours": 24}"#)
+            .expect("plain numbers should still parse");
+    assert_eq!(sample.size_mb, 20);
+
+    let err = serde_json::from_str::<Sample>(
+        r#"{"size_mb": "20 parsecs", "interval_secs": 60, "alert_hours": 24}"#,
+    )
+    .err()
+    .expect("unknown unit must fail");
+    assert!(err.to_string().contains("Unknown size unit"));
+}
+
+#[test]
+fn optional_deserializers_keep_missing_values_unset() {
+    #[derive(Deserialize)]
+    struct Sample {
+        #[serde(default, deserialize_with = "deserialize_opt_size_bytes")]
+        max_size: Option<u64>,
+        #[serde(default, deserialize_with = "deserialize_opt_duration_secs")]
+        within: Option<u64>,
+    }
+
+    let sample: Sample = serde_json::from_str(r#"{"max_size": "4GB", "within": "30d"}"#)
+        .expect("unit strings should parse");
+    assert_eq!(sample.max_size, Some(4 * 1024 * MB));
+    assert_eq!(sample.within, Some(30 * 24 * SECS_PER_HOUR));
+
+    let sample: Sample = serde_json::from_str(r#"{"max_size": 512}"#).expect("bytes parse");
+    assert_eq!(sample.max_size, Some(512));
+    assert_eq!(sample.within, None);
+}
@@ -54,7 +54,7 @@ async fn debounce_events_sends_backup_trigger_after_quiet_period() {
     let (backup_tx, mut backup_rx) = tokio_mpsc::channel::<()>(2);
 
     let handle = std::thread::spawn(move || {
-        FileWatcher::debounce_events(event_rx, backup_tx, Duration::from_millis(30));
+        FileWatcher::debounce_events(event_rx, backup_tx, |_| (), Duration::from_millis(30));
     });
 
     event_tx
@@ -80,7 +80,7 @@ async fn debounce_events_ignores_temp_modify_event() {
     let (backup_tx, mut backup_rx) = tokio_mpsc::channel::<()>(2);
 
     let handle = std::thread::spawn(move || {
-        FileWatcher::debounce_events(event_rx, backup_tx, Duration::from_millis(30));
+        FileWatcher::debounce_events(event_rx, backup_tx, |_| (), Duration::from_millis(30));
     });
 
     event_tx
@@ -109,7 +109,7 @@ async fn debounce_events_coalesces_bursty_events_into_single_trigger() {
     let (backup_tx, mut backup_rx) = tokio_mpsc::channel::<()>(4);
 
     let handle = std::thread::spawn(move || {
-        FileWatcher::debounce_events(event_rx, backup_tx, Duration::from_millis(40));
+        FileWatcher::debounce_events(event_rx, backup_tx, |_| (), Duration::from_millis(40));
     });
 
     event_tx
@@ -157,7 +157,7 @@ async fn debounce_events_returns_without_trigger_when_sender_disconnected_withou
     let (backup_tx, mut backup_rx) = tokio_mpsc::channel::<()>(1);
 
     let handle = std::thread::spawn(move || {
-        FileWatcher::debounce_events(event_rx, backup_tx, Duration::from_millis(20));
+        FileWatcher::debounce_events(event_rx, backup_tx, |_| (), Duration::from_millis(20));
     });
 
     // Explicitly disconnect sender without producing any event.
@@ -170,3 +170,14 @@ async fn debounce_events_returns_without_trigger_when_sender_disconnected_withou
 
     handle.join().expect("debounce thread must finish cleanly");
 }
+
+#[test]
+fn file_watch_only_passes_events_for_the_watched_file() {
+    let db = PathBuf::from("/data/app.db");
+    let event = make_event(EventKind::Modify(ModifyKind::Any), "/data/app.db");
+    let sibling = make_event(EventKind::Modify(ModifyKind::Any), "/data/other.txt");
+
+    assert!(FileWatcher::concerns_file(&event, Some(&db)));
+    assert!(!FileWatcher::concerns_file(&sibling, Some(&db)));
+    assert!(FileWatcher::concerns_file(&sibling, None));
+}
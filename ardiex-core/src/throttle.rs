@@ -0,0 +1,83 @@
+use anyhow::{Context, Result};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::progress::FileProgress;
+
+const COPY_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Byte-rate limiter for bulk file I/O. Callers report bytes as they move
+/// them and `consume` sleeps whenever the average rate since creation runs
+/// ahead of the configured limit.
+#[derive(Debug)]
+pub struct Throttle {
+    bytes_per_sec: f64,
+    started: Instant,
+    transferred: u64,
+}
+
+impl Throttle {
+    pub fn from_mb_per_sec(mb_per_sec: f64) -> Result<Self> {
+        if !mb_per_sec.is_finite() || mb_per_sec <= 0.0 {
+            return Err(anyhow::anyhow!(
+                "Throttle must be a positive MB/s value, got {}",
+                mb_per_sec
+            ));
+        }
+        Ok(Self {
+            bytes_per_sec: mb_per_sec * 1024.0 * 1024.0,
+            started: Instant::now(),
+            transferred: 0,
+        })
+    }
+
+    pub fn consume(&mut self, bytes: u64) {
+        self.transferred += bytes;
+        let expected = Duration::from_secs_f64(self.transferred as f64 / self.bytes_per_sec);
+        let elapsed = self.started.elapsed();
+        if expected > elapsed {
+            std::thread::sleep(expected - elapsed);
+        }
+    }
+}
+
+/// `fs::copy` that feeds the throttle chunk by chunk so large files are paced
+/// smoothly instead of in one burst, and reports the running byte count to
+/// `progress`. Falls back to `fs::copy` when there is neither.
+pub fn copy_file(
+    src: &Path,
+    dst: &Path,
+    mut throttle: Option<&mut Throttle>,
+    mut progress: Option<&mut FileProgress>,
+) -> Result<u64> {
+    if throttle.is_none() && progress.is_none() {
+        return fs::copy(src, dst).with_context(|| format!("Failed to copy {:?}", src));
+    }
+
+    let mut reader = File::open(src).with_context(|| format!("Failed to open {:?}", src))?;
+    let mut writer = File::create(dst).with_context(|| format!("Failed to create {:?}", dst))?;
+    let mut buf = vec![0u8; COPY_CHUNK_SIZE];
+    let mut copied = 0u64;
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&buf[..n])?;
+        copied += n as u64;
+        if let Some(throttle) = throttle.as_deref_mut() {
+            throttle.consume(n as u64);
+        }
+        if let Some(progress) = progress.as_deref_mut() {
+            progress.update(copied);
+        }
+    }
+    fs::set_permissions(dst, fs::metadata(src)?.permissions())?;
+    Ok(copied)
+}
+
+#[cfg(test)]
+#[path = "tests/throttle_tests.rs"]
+mod tests;
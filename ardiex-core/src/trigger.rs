@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::watcher::ChangedPaths;
+
+/// Why a backup of a source was requested, in dispatch priority order:
+/// REST API requests and startup backups were asked for explicitly and go
+/// first, then file changes, then the regular schedule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TriggerReason {
+    Cron,
+    FileChange,
+    Startup,
+    Api,
+}
+
+impl fmt::Display for TriggerReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TriggerReason::Cron => "cron",
+            TriggerReason::FileChange => "file change",
+            TriggerReason::Startup => "startup",
+            TriggerReason::Api => "api",
+        })
+    }
+}
+
+/// Request to back up one source; every trigger is routed to its source
+/// only, so one source's schedule never runs the others.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupTrigger {
+    pub source_dir: PathBuf,
+    pub reason: TriggerReason,
+    /// Source paths the watcher saw change; `None` scans the whole source.
+    pub changed_paths: ChangedPaths,
+}
+
+impl BackupTrigger {
+    pub fn new(source_dir: PathBuf, reason: TriggerReason) -> Self {
+        Self {
+            source_dir,
+            reason,
+            changed_paths: None,
+        }
+    }
+
+    /// A file-change trigger for the paths a burst of events touched.
+    pub fn file_change(source_dir: PathBuf, changed_paths: ChangedPaths) -> Self {
+        Self {
+            source_dir,
+            reason: TriggerReason::FileChange,
+            changed_paths,
+        }
+    }
+}
+
+/// Pending triggers, at most one per source. A trigger for a source that
+/// is already waiting merges into it (keeping the higher priority reason,
+/// the original place in line and the changed paths of both, or none if
+/// either scans the whole source), so a burst of events runs one backup.
+#[derive(Debug, Default)]
+pub struct TriggerQueue {
+    pending: HashMap<PathBuf, (TriggerReason, u64, ChangedPaths)>,
+    next_seq: u64,
+}
+
+impl TriggerQueue {
+    /// Queue `trigger`; `false` when it merged into a pending one.
+    pub fn push(&mut self, trigger: BackupTrigger) -> bool {
+        if let Some((reason, _, changed)) = self.pending.get_mut(&trigger.source_dir) {
+            *reason = (*reason).max(trigger.reason);
+            *changed = changed
+                .take()
+                .zip(trigger.changed_paths)
+                .map(|(mut paths, more)| {
+                    paths.extend(more);
+                    paths
+                });
+            return false;
+        }
+        self.pending.insert(
+            trigger.source_dir,
+            (trigger.reason, self.next_seq, trigger.changed_paths),
+        );
+        self.next_seq += 1;
+        true
+    }
+
+    /// Remove and return up to `max` triggers, highest priority first and
+    /// oldest first within a priority.
+    pub fn pop_batch(&mut self, max: usize) -> Vec<BackupTrigger> {
+        self.pending()
+            .into_iter()
+            .take(max)
+            .filter_map(|(source_dir, _)| {
+                let (reason, _, changed_paths) = self.pending.remove(&source_dir)?;
+                Some(BackupTrigger {
+                    source_dir,
+                    reason,
+                    changed_paths,
+                })
+            })
+            .collect()
+    }
+
+    /// Waiting sources and their reasons, in the order they will run.
+    pub fn pending(&self) -> Vec<(PathBuf, TriggerReason)> {
+        let mut order: Vec<(TriggerReason, u64, &PathBuf)> = self
+            .pending
+            .iter()
+            .map(|(dir, &(reason, seq, _))| (reason, seq, dir))
+            .collect();
+        order.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        order
+            .into_iter()
+            .map(|(reason, _, dir)| (dir.clone(), reason))
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/trigger_tests.rs"]
+mod tests;
@@ -0,0 +1,234 @@
+use anyhow::Result;
+use serde::{Deserialize, Deserializer};
+
+pub const KB: u64 = 1024;
+pub const MB: u64 = 1024 * KB;
+pub const SECS_PER_HOUR: u64 = 3600;
+
+const SIZE_UNITS: &[(&str, u64)] = &[
+    ("TB", 1024 * 1024 * MB),
+    ("GB", 1024 * MB),
+    ("MB", MB),
+    ("KB", KB),
+    ("B", 1),
+];
+
+const DURATION_UNITS: &[(&str, u64)] = &[
+    ("w", 7 * 24 * SECS_PER_HOUR),
+    ("d", 24 * SECS_PER_HOUR),
+    ("h", SECS_PER_HOUR),
+    ("m", 60),
+    ("s", 1),
+];
+
+fn parse_number(text: &str, original: &str) -> Result<f64> {
+    let number: f64 = text
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid number in {:?}", original))?;
+    if !number.is_finite() || number < 0.0 {
+        return Err(anyhow::anyhow!(
+            "Value must be a non-negative number: {:?}",
+            original
+        ));
+    }
+    Ok(number)
+}
+
+fn split_number(text: &str) -> (&str, &str) {
+    let end = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    text.split_at(end)
+}
+
+/// Parse a size such as `"500MB"`, `"1.5GB"` or `"20"` into bytes. Units are
+/// binary (1KB = 1024B) like the rest of ardiex; a bare number is counted in
+/// `default_unit` bytes.
+pub fn parse_size(value: &str, default_unit: u64) -> Result<u64> {
+    let trimmed = value.trim();
+    let (number, unit) = split_number(trimmed);
+    let number = parse_number(number, value)?;
+    let unit = unit.trim().to_ascii_uppercase();
+    let multiplier = if unit.is_empty() {
+        default_unit
+    } else {
+        let unit = unit
+            .strip_suffix("IB")
+            .or_else(|| unit.strip_suffix('B'))
+            .unwrap_or(&unit);
+        SIZE_UNITS
+            .iter()
+            .find(|(name, _)| name.trim_end_matches('B') == unit)
+            .map(|(_, bytes)| *bytes)
+            .ok_or_else(|| {
+                anyhow::anyhow!("Unknown size unit in {:?} (use B, KB, MB, GB, TB)", value)
+            })?
+    };
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+/// Parse a duration such as `"90d"`, `"2h"` or `"1h30m"` into seconds. A
+/// bare number is counted in `default_unit` seconds.
+pub fn parse_duration(value: &str, default_unit: u64) -> Result<u64> {
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return Err(anyhow::anyhow!("Duration must not be empty"));
+    }
+
+    let mut total = 0.0;
+    while !rest.is_empty() {
+        let (number, after) = split_number(rest);
+        let number = parse_number(number, value)?;
+        let unit_len = after
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(after.len());
+        let (unit, after) = after.split_at(unit_len);
+        let multiplier = if unit.is_empty() {
+            if !after.trim().is_empty() || total > 0.0 {
+                return Err(anyhow::anyhow!("Missing duration unit in {:?}", value));
+            }
+            default_unit
+        } else {
+            DURATION_UNITS
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(unit))
+                .map(|(_, secs)| *secs)
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Unknown duration unit in {:?} (use s, m, h, d, w)", value)
+                })?
+        };
+        total += number * multiplier as f64;
+        rest = after.trim_start();
+    }
+    Ok(total.round() as u64)
+}
+
+fn exact_units(amount: u64, unit: u64, value: &str, unit_label: &str) -> Result<u64> {
+    if !amount.is_multiple_of(unit) {
+        return Err(anyhow::anyhow!(
+            "{:?} must be a multiple of {}",
+            value,
+            unit_label
+        ));
+    }
+    Ok(amount / unit)
+}
+
+/// Parse a size into a whole number of `unit` bytes, for settings whose
+/// stored value has an implicit unit (e.g. `max_log_file_size_mb`).
+pub fn parse_size_as(value: &str, unit: u64) -> Result<u64> {
+    exact_units(parse_size(value, unit)?, unit, value, &format_size(unit))
+}
+
+/// Parse a duration into a whole number of `unit` seconds.
+pub fn parse_duration_as(value: &str, unit: u64) -> Result<u64> {
+    exact_units(
+        parse_duration(value, unit)?,
+        unit,
+        value,
+        &format_duration(unit),
+    )
+}
+
+/// Canonical form of a byte count: the largest unit that divides it evenly.
+pub fn format_size(bytes: u64) -> String {
+    SIZE_UNITS
+        .iter()
+        .find(|(_, unit)| bytes != 0 && bytes.is_multiple_of(*unit))
+        .map(|(name, unit)| format!("{}{}", bytes / unit, name))
+        .unwrap_or_else(|| "0B".to_string())
+}
+
+/// Canonical form of a duration, e.g. `"90d"`, `"1h30m"`. Weeks are only
+/// accepted on input since `"12w6d"` reads worse than `"90d"`.
+pub fn format_duration(secs: u64) -> String {
+    if secs == 0 {
+        return "0s".to_string();
+    }
+    let mut rest = secs;
+    let mut text = String::new();
+    for (name, unit) in DURATION_UNITS.iter().skip(1) {
+        if rest >= *unit {
+            text.push_str(&format!("{}{}", rest / unit, name));
+            rest %= unit;
+        }
+    }
+    text
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawValue {
+    Number(u64),
+    Text(String),
+}
+
+fn deserialize_scaled<'de, D>(
+    deserializer: D,
+    unit: u64,
+    parse: fn(&str, u64) -> Result<u64>,
+) -> std::result::Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match RawValue::deserialize(deserializer)? {
+        RawValue::Number(n) => Ok(n),
+        RawValue::Text(text) => parse(&text, unit).map_err(serde::de::Error::custom),
+    }
+}
+
+fn deserialize_optional_scaled<'de, D>(
+    deserializer: D,
+    unit: u64,
+    parse: fn(&str, u64) -> Result<u64>,
+) -> std::result::Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<RawValue>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(RawValue::Number(n)) => Ok(Some(n)),
+        Some(RawValue::Text(text)) => parse(&text, unit)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+/// Accepts either a number of MB or a size string like `"500MB"`.
+pub fn deserialize_size_mb<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<u64, D::Error> {
+    deserialize_scaled(deserializer, MB, parse_size_as)
+}
+
+/// Accepts either a number of seconds or a duration string like `"5m"`.
+pub fn deserialize_duration_secs<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<u64, D::Error> {
+    deserialize_scaled(deserializer, 1, parse_duration_as)
+}
+
+/// Accepts either a number of hours or a duration string like `"2d"`.
+pub fn deserialize_duration_hours<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<u64, D::Error> {
+    deserialize_scaled(deserializer, SECS_PER_HOUR, parse_duration_as)
+}
+
+/// Optional byte count: a number of bytes or a size string like `"4GB"`.
+pub fn deserialize_opt_size_bytes<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<u64>, D::Error> {
+    deserialize_optional_scaled(deserializer, 1, parse_size)
+}
+
+/// Optional duration: a number of seconds or a string like `"30d"`.
+pub fn deserialize_opt_duration_secs<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<u64>, D::Error> {
+    deserialize_optional_scaled(deserializer, 1, parse_duration)
+}
+
+#[cfg(test)]
+#[path = "tests/units_tests.rs"]
+mod tests;
@@ -0,0 +1,210 @@
+use anyhow::Result;
+use log::{error, info, warn};
+use notify::event::Flag;
+use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tokio::sync::mpsc as tokio_mpsc;
+
+/// Paths a burst of events touched; `None` when they are not all known
+/// (the OS dropped events or the burst was too large to track).
+pub type ChangedPaths = Option<BTreeSet<PathBuf>>;
+
+/// Paths tracked per burst before falling back to `None`.
+const MAX_TRACKED_PATHS: usize = 10_000;
+
+pub struct FileWatcher {
+    _watchers: Vec<RecommendedWatcher>,
+    _debounce_duration: Duration,
+}
+
+impl FileWatcher {
+    /// Watch `watch_paths` and send `message` on `backup_tx` once changes
+    /// have been quiet for `debounce_duration`.
+    pub fn new<T: Clone + Send + 'static>(
+        watch_paths: Vec<PathBuf>,
+        backup_tx: tokio_mpsc::Sender<T>,
+        message: T,
+        debounce_duration: Duration,
+    ) -> Result<Self> {
+        Self::with_changes(
+            watch_paths,
+            backup_tx,
+            move |_| message.clone(),
+            debounce_duration,
+        )
+    }
+
+    /// Like `new`, with the message built from the paths the burst touched.
+    pub fn with_changes<T, F>(
+        watch_paths: Vec<PathBuf>,
+        backup_tx: tokio_mpsc::Sender<T>,
+        make_message: F,
+        debounce_duration: Duration,
+    ) -> Result<Self>
+    where
+        T: Send + 'static,
+        F: Fn(ChangedPaths) -> T + Clone + Send + 'static,
+    {
+        let mut watchers = Vec::new();
+
+        for path in watch_paths {
+            // Event paths carry the same prefix, so file filtering still matches.
+            let path = crate::longpath::extended(&path);
+            if !path.exists() {
+                warn!("Watch path does not exist: {:?}", path);
+                continue;
+            }
+
+            // A single file is watched through its parent dir so editors and
+            // databases that replace it via rename keep being seen; events
+            // for its siblings are dropped.
+            let (watch_target, only_file, mode) = match path.parent() {
+                Some(parent) if path.is_file() => (
+                    parent.to_path_buf(),
+                    Some(path),
+                    RecursiveMode::NonRecursive,
+                ),
+                _ => (path, None, RecursiveMode::Recursive),
+            };
+
+            let (tx, rx) = mpsc::channel();
+            let mut watcher = RecommendedWatcher::new(
+                move |res: Result<Event, notify::Error>| {
+                    let event = match res {
+                        Ok(event) if Self::concerns_file(&event, only_file.as_deref()) => event,
+                        Ok(_) => return,
+                        Err(e) => {
+                            // Events may have been lost with it.
+                            error!("File system watch error: {:?}", e);
+                            Event::new(EventKind::Other).set_flag(Flag::Rescan)
+                        }
+                    };
+                    if let Err(e) = tx.send(event) {
+                        error!("Failed to send file system event: {}", e);
+                    }
+                },
+                Config::default(),
+            )?;
+
+            watcher.watch(&watch_target, mode)?;
+            watchers.push(watcher);
+            info!("Started watching: {:?}", watch_target);
+            let backup_tx_clone = backup_tx.clone();
+            let make_message = make_message.clone();
+            let debounce = debounce_duration;
+
+            thread::spawn(move || {
+                Self::debounce_events(rx, backup_tx_clone, make_message, debounce);
+            });
+        }
+
+        Ok(Self {
+            _watchers: watchers,
+            _debounce_duration: debounce_duration,
+        })
+    }
+
+    fn debounce_events<T>(
+        rx: mpsc::Receiver<Event>,
+        backup_tx: tokio_mpsc::Sender<T>,
+        make_message: impl Fn(ChangedPaths) -> T,
+        debounce_duration: Duration,
+    ) {
+        let mut last_event_time;
+        let mut pending_backup;
+        // Paths of every event since the last trigger, including ones too
+        // minor to trigger a backup by themselves.
+        let mut changed: ChangedPaths = Some(BTreeSet::new());
+
+        while let Ok(event) = rx.recv() {
+            Self::record_paths(&mut changed, &event);
+            if Self::should_trigger_backup(&event) {
+                let now = std::time::Instant::now();
+                last_event_time = now;
+                pending_backup = true;
+
+                while pending_backup {
+                    match rx.recv_timeout(debounce_duration) {
+                        Ok(event) => {
+                            Self::record_paths(&mut changed, &event);
+                            if Self::should_trigger_backup(&event) {
+                                last_event_time = std::time::Instant::now();
+                            }
+                        }
+                        Err(mpsc::RecvTimeoutError::Timeout) => {
+                            if last_event_time.elapsed() >= debounce_duration {
+                                let paths = changed.replace(BTreeSet::new());
+                                if let Err(e) = backup_tx.blocking_send(make_message(paths)) {
+                                    error!("Failed to send backup trigger: {}", e);
+                                    break;
+                                }
+                                info!("File changes detected, triggering backup");
+                                pending_backup = false;
+                            }
+                        }
+                        Err(mpsc::RecvTimeoutError::Disconnected) => {
+                            error!("Event channel disconnected");
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Add the paths `event` touched to `changed`, or give up on tracking
+    /// them when the OS asks for a rescan or there are too many.
+    fn record_paths(changed: &mut ChangedPaths, event: &Event) {
+        if event.need_rescan() || (Self::should_trigger_backup(event) && event.paths.is_empty()) {
+            *changed = None;
+            return;
+        }
+        if !matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+        ) {
+            return;
+        }
+        if let Some(paths) = changed {
+            paths.extend(event.paths.iter().cloned());
+            if paths.len() > MAX_TRACKED_PATHS {
+                *changed = None;
+            }
+        }
+    }
+
+    /// Whether `event` touches the watched file, or any path when a whole
+    /// directory is watched.
+    fn concerns_file(event: &Event, only_file: Option<&Path>) -> bool {
+        only_file.is_none_or(|file| event.paths.iter().any(|p| p == file))
+    }
+
+    fn should_trigger_backup(event: &Event) -> bool {
+        if event.need_rescan() {
+            return true;
+        }
+        match &event.kind {
+            EventKind::Create(_) => true,
+            EventKind::Modify(_) => !event.paths.iter().any(|p| {
+                if let Some(name) = p.file_name() {
+                    let name_str = name.to_string_lossy();
+                    name_str.ends_with(".tmp")
+                        || name_str.ends_with(".swp")
+                        || name_str.ends_with(".lock")
+                } else {
+                    false
+                }
+            }),
+            EventKind::Remove(_) => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/watcher_tests.rs"]
+mod tests;
@@ -1,8 +1,5 @@
-#[allow(dead_code)]
-#[path = "../logger.rs"]
-mod logger;
-
 use anyhow::{Context, Result};
+use ardiex_core::{config_location, logger, release_verify, units};
 use clap::Parser;
 use log::{info, warn};
 use reqwest::blocking::Client;
@@ -34,8 +31,23 @@ struct UpdaterArgs {
     current_exe: PathBuf,
     #[arg(long)]
     parent_pid: u32,
+    /// The release's SHA256SUMS asset
+    #[arg(long)]
+    checksums_url: Option<String>,
+    /// minisign signature of SHA256SUMS
+    #[arg(long)]
+    signature_url: Option<String>,
+    /// Install even if the checksums cannot be verified with the embedded
+    /// public key; the archive is still checked against SHA256SUMS when
+    /// it exists.
+    #[arg(long)]
+    allow_unsigned: bool,
     #[arg(long = "forward-arg")]
     forward_args: Vec<String>,
+    /// Leave the new binary stopped instead of running it with the
+    /// forwarded args, for updates the user asked for.
+    #[arg(long)]
+    no_restart: bool,
 }
 
 fn now_millis() -> u128 {
@@ -45,41 +57,102 @@ fn now_millis() -> u128 {
         .unwrap_or(0)
 }
 
-fn read_max_log_file_size_mb(exe_dir: &Path) -> u64 {
-    let settings_path = exe_dir.join("settings.json");
-    let Ok(content) = fs::read_to_string(settings_path) else {
-        return DEFAULT_MAX_LOG_FILE_SIZE_MB;
-    };
+/// Value of `--<name>` among the arguments forwarded to the main binary.
+fn forwarded_arg(args: &[String], name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if *arg == flag {
+            return args.next().cloned();
+        }
+        if let Some(value) = arg.strip_prefix(&flag).and_then(|v| v.strip_prefix('=')) {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
 
-    let Ok(json) = serde_json::from_str::<serde_json::Value>(&content) else {
-        return DEFAULT_MAX_LOG_FILE_SIZE_MB;
+/// Same lookup as the main binary, without migrating anything: the log
+/// file size limit in MB and the number of rotated files to keep.
+fn read_log_settings(exe_dir: &Path, forward_args: &[String]) -> (u64, usize) {
+    let non_empty_env = |key: &str| std::env::var(key).ok().filter(|value| !value.is_empty());
+    let valid_profile = |profile: &String| config_location::validate_profile_name(profile).is_ok();
+    let platform_dir = config_location::platform_config_dir();
+    let location = match (
+        forwarded_arg(forward_args, "config"),
+        forwarded_arg(forward_args, "profile").filter(valid_profile),
+    ) {
+        (Some(path), _) => {
+            config_location::locate(Some(PathBuf::from(path)), platform_dir, exe_dir)
+        }
+        (None, Some(profile)) => config_location::locate_profile(&profile, platform_dir, exe_dir),
+        (None, None) => match (
+            non_empty_env(config_location::CONFIG_ENV_KEY),
+            non_empty_env(config_location::PROFILE_ENV_KEY).filter(valid_profile),
+        ) {
+            (Some(path), _) => {
+                config_location::locate(Some(PathBuf::from(path)), platform_dir, exe_dir)
+            }
+            (None, Some(profile)) => {
+                config_location::locate_profile(&profile, platform_dir, exe_dir)
+            }
+            (None, None) => config_location::locate(None, platform_dir, exe_dir),
+        },
     };
-
-    let Some(size_mb) = json
-        .get("max_log_file_size_mb")
+    let settings_path = location.migrate_from.unwrap_or(location.path);
+    let parsed = fs::read_to_string(&settings_path).ok().and_then(|content| {
+        if settings_path.extension().is_some_and(|ext| ext == "toml") {
+            toml::from_str::<serde_json::Value>(&content).ok()
+        } else {
+            serde_json::from_str::<serde_json::Value>(&content).ok()
+        }
+    });
+    let Some(json) = parsed else {
+        return (
+            DEFAULT_MAX_LOG_FILE_SIZE_MB,
+            logger::DEFAULT_MAX_ROTATED_LOG_FILES,
+        );
+    };
+    let max_rotated_files = json
+        .get("max_rotated_log_files")
         .and_then(|value| value.as_u64())
-    else {
-        return DEFAULT_MAX_LOG_FILE_SIZE_MB;
+        .filter(|count| *count > 0)
+        .map_or(logger::DEFAULT_MAX_ROTATED_LOG_FILES, |count| {
+            count as usize
+        });
+
+    // settings.json may hold either a plain MB count or a size like "500MB".
+    let Some(size_mb) = json.get("max_log_file_size_mb").and_then(|value| {
+        value.as_u64().or_else(|| {
+            value
+                .as_str()
+                .and_then(|text| units::parse_size_as(text, units::MB).ok())
+        })
+    }) else {
+        return (DEFAULT_MAX_LOG_FILE_SIZE_MB, max_rotated_files);
     };
 
     if size_mb == 0 {
-        DEFAULT_MAX_LOG_FILE_SIZE_MB
+        (DEFAULT_MAX_LOG_FILE_SIZE_MB, max_rotated_files)
     } else {
-        size_mb
+        (size_mb, max_rotated_files)
     }
 }
 
-fn init_updater_logging() {
+fn init_updater_logging(forward_args: &[String]) {
     let exe_dir = std::env::current_exe()
         .ok()
         .and_then(|path| path.parent().map(|parent| parent.to_path_buf()));
 
     if let Some(exe_dir) = exe_dir {
         let log_dir = exe_dir.join("logs");
-        let size_mb = read_max_log_file_size_mb(&exe_dir);
-        if let Err(err) =
-            logger::init_file_logging_with_size_and_name(&log_dir, size_mb, UPDATER_LOG_FILE_NAME)
-        {
+        let (size_mb, max_rotated_files) = read_log_settings(&exe_dir, forward_args);
+        if let Err(err) = logger::init_file_logging_with_size_and_name(
+            &log_dir,
+            size_mb,
+            max_rotated_files,
+            UPDATER_LOG_FILE_NAME,
+        ) {
             eprintln!("Failed to initialize updater file logging: {}", err);
             logger::init_console_logging();
         }
@@ -109,6 +182,70 @@ fn download_asset(url: &str, destination: &Path) -> Result<()> {
     Ok(())
 }
 
+fn download_text(url: &str, destination: &Path) -> Result<String> {
+    download_asset(url, destination)?;
+    fs::read_to_string(destination).with_context(|| format!("Failed to read {:?}", destination))
+}
+
+/// Check the downloaded archive against the release's signed SHA256SUMS
+/// before anything is extracted. Without `--allow-unsigned` an archive
+/// whose checksums are missing, unsigned or unverifiable is refused.
+fn verify_download(args: &UpdaterArgs, archive_path: &Path, work_dir: &Path) -> Result<()> {
+    let Some(checksums_url) = &args.checksums_url else {
+        if !args.allow_unsigned {
+            return Err(anyhow::anyhow!(
+                "Release has no {}; refusing unverified update",
+                release_verify::CHECKSUMS_ASSET
+            ));
+        }
+        warn!(
+            "[UPDATER] Release has no {}; installing unverified archive (unsigned updates allowed)",
+            release_verify::CHECKSUMS_ASSET
+        );
+        return Ok(());
+    };
+    let checksums = download_text(
+        checksums_url,
+        &work_dir.join(release_verify::CHECKSUMS_ASSET),
+    )?;
+
+    if let (Some(signature_url), Some(public_key)) =
+        (&args.signature_url, release_verify::embedded_public_key())
+    {
+        let signature = download_text(
+            signature_url,
+            &work_dir.join(release_verify::SIGNATURE_ASSET),
+        )?;
+        release_verify::verify_signature(checksums.as_bytes(), &signature, public_key)?;
+        info!(
+            "[UPDATER] {} signature verified",
+            release_verify::CHECKSUMS_ASSET
+        );
+    } else {
+        let reason = if args.signature_url.is_none() {
+            "release has no signature"
+        } else {
+            "updater has no public key"
+        };
+        if !args.allow_unsigned {
+            return Err(anyhow::anyhow!(
+                "Cannot verify {}: {}; refusing unsigned update",
+                release_verify::CHECKSUMS_ASSET,
+                reason
+            ));
+        }
+        warn!(
+            "[UPDATER] Cannot verify {}: {}; checking the archive against it unsigned",
+            release_verify::CHECKSUMS_ASSET,
+            reason
+        );
+    }
+
+    release_verify::verify_archive(archive_path, &args.asset_name, &checksums)?;
+    info!("[UPDATER] Archive checksum verified: {}", args.asset_name);
+    Ok(())
+}
+
 fn extract_zip_archive(archive_path: &Path, destination_dir: &Path) -> Result<()> {
     let file = File::open(archive_path)
         .with_context(|| format!("Failed to open zip archive {:?}", archive_path))?;
@@ -277,8 +414,7 @@ fn restart_target_binary(target_exe: &Path, args: &[String]) -> Result<()> {
     Ok(())
 }
 
-fn updater_main() -> Result<()> {
-    let args = UpdaterArgs::parse();
+fn updater_main(args: UpdaterArgs) -> Result<()> {
     info!(
         "[UPDATER] Start repo={}, target_version={}, asset_name={}",
         args.repo, args.target_version, args.asset_name
@@ -299,6 +435,11 @@ fn updater_main() -> Result<()> {
     download_asset(&args.asset_url, &archive_path)?;
     info!("[UPDATER] Download completed: {:?}", archive_path);
 
+    if let Err(err) = verify_download(&args, &archive_path, &work_dir) {
+        let _ = fs::remove_dir_all(&work_dir);
+        return Err(err.context("Release archive verification failed; nothing was installed"));
+    }
+
     extract_archive(&archive_path, &args.asset_name, &extract_dir)?;
     info!("[UPDATER] Extract completed: {:?}", extract_dir);
 
@@ -322,7 +463,14 @@ fn updater_main() -> Result<()> {
         Duration::from_millis(500),
     )?;
 
-    restart_target_binary(&args.current_exe, &args.forward_args)?;
+    if args.no_restart {
+        info!(
+            "[UPDATER] Installed version {}, not restarting",
+            args.target_version
+        );
+    } else {
+        restart_target_binary(&args.current_exe, &args.forward_args)?;
+    }
 
     if let Err(err) = fs::remove_dir_all(&work_dir) {
         warn!(
@@ -339,6 +487,7 @@ fn updater_main() -> Result<()> {
 }
 
 fn main() -> Result<()> {
-    init_updater_logging();
-    updater_main()
+    let args = UpdaterArgs::parse();
+    init_updater_logging(&args.forward_args);
+    updater_main(args)
 }
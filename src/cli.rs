@@ -1,10 +1,35 @@
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
+use crate::config::UpdateChannel;
+use crate::export::ExportFormat;
+use crate::restore::OverwritePolicy;
+
 #[derive(Parser)]
 #[command(name = "ardiex")]
 #[command(about = "Incremental backup system with periodic and event-driven triggers")]
 pub struct Cli {
+    /// Settings file to use (.json or .toml). Defaults to $ARDIEX_CONFIG,
+    /// then the platform config dir ($XDG_CONFIG_HOME/ardiex, %APPDATA%\ardiex)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Named settings set with its own settings file, history and logs
+    /// (profiles/<NAME>/ in the config dir). Defaults to $ARDIEX_PROFILE.
+    /// With --config, it only picks the log subdirectory
+    #[arg(long, global = true, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Log warnings and errors only and keep log lines off stdout (they
+    /// still go to the log file). Overrides RUST_LOG
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Log debug output (-vv: trace output, including libraries).
+    /// Overrides RUST_LOG
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -17,22 +42,358 @@ pub enum Commands {
         action: ConfigAction,
     },
     /// Perform a manual backup
-    Backup,
+    Backup {
+        /// Back up even if anomaly detection paused the source or a mirror
+        /// refused to empty its replica (after checking that the mass
+        /// change is legitimate)
+        #[arg(long)]
+        accept_changes: bool,
+    },
     /// Restore from backup
     Restore {
-        /// Backup directory to restore from
-        backup_dir: PathBuf,
+        /// Backup directory to restore from (with --source: the target
+        /// directory)
+        #[arg(required_unless_present = "source")]
+        backup_dir: Option<PathBuf>,
         /// Target directory to restore to
-        target_dir: PathBuf,
+        #[arg(required_unless_present_any = ["in_place", "source"])]
+        target_dir: Option<PathBuf>,
+        /// Restore the configured source at this path from whichever of its
+        /// backup dirs is healthiest (valid chain, newest point) instead of
+        /// naming a backup dir
+        #[arg(long, value_name = "SOURCE_DIR", conflicts_with = "target_dir")]
+        source: Option<PathBuf>,
+        /// Restore into the source that owns the backup dir, found in the
+        /// config (or the backup dir's reports), instead of a target dir.
+        /// Asks before overwriting changes made since the last backup
+        #[arg(long, conflicts_with = "target_dir")]
+        in_place: bool,
+        /// With --in-place, back up the source's current state first so
+        /// the restore can be undone
+        #[arg(long, requires = "in_place")]
+        snapshot: bool,
+        /// With --in-place, restore over unbacked changes without asking
+        #[arg(short, long, requires = "in_place")]
+        yes: bool,
         /// Restore point timestamp (e.g. 20240221_100000). If omitted, restores to latest.
         #[arg(short, long)]
         point: Option<String>,
+        /// Restore the newest point carrying this tag (see `ardiex tag`);
+        /// with --list, list only the points carrying it
+        #[arg(long, conflicts_with = "point")]
+        tag: Option<String>,
         /// List available backups instead of restoring
         #[arg(short, long)]
         list: bool,
+        /// Pick the restore point from a list with the arrow keys, preview
+        /// what the restore writes and confirm before it starts
+        #[arg(short, long, conflicts_with_all = ["point", "list", "tag"])]
+        interactive: bool,
+        /// Limit restore write throughput in MB/s (e.g. 50 or 12.5)
+        #[arg(long, value_name = "MB/s")]
+        throttle: Option<f64>,
+        /// Give up after this many seconds if a running backup holds the
+        /// backup directory (default: wait until it finishes)
+        #[arg(long, value_name = "SECS")]
+        lock_timeout: Option<u64>,
+        /// Keep restored files owned by the current user instead of the
+        /// recorded owner (needed when not running as root)
+        #[arg(long)]
+        no_owner: bool,
+        /// Re-read every restored file and check it against the backup
+        /// (copies by hash, deltas block by block)
+        #[arg(long)]
+        verify: bool,
+        /// What to do with files already in the target: always, never,
+        /// if-older (only replace files older than the backed-up version)
+        /// or prompt (ask for each file)
+        #[arg(long, value_name = "POLICY", default_value = "always")]
+        overwrite: OverwritePolicy,
+        /// Move files about to be replaced to `<target>.replaced_<time>`
+        /// next to the target instead of discarding them
+        #[arg(long)]
+        backup_existing: bool,
+    },
+    /// Write a restore point into a .tar.gz or .zip archive, rebuilding
+    /// each file straight into the archive instead of restoring to disk
+    Export {
+        /// Backup directory to export from
+        backup_dir: PathBuf,
+        /// Restore point timestamp (e.g. 20240221_100000). If omitted, exports the latest.
+        #[arg(short, long)]
+        point: Option<String>,
+        /// Archive format: tar.gz or zip (default: from the output file name)
+        #[arg(long, value_name = "FORMAT")]
+        format: Option<ExportFormat>,
+        /// Archive file to write
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+        /// Give up after this many seconds if a running backup holds the
+        /// backup directory (default: wait until it finishes)
+        #[arg(long, value_name = "SECS")]
+        lock_timeout: Option<u64>,
+    },
+    /// Copy the backup points a backup dir has and another path lacks,
+    /// plus its metadata, to keep an off-site copy of the backup history
+    Replicate {
+        /// Backup directory to copy from
+        backup_dir: PathBuf,
+        /// Destination path (e.g. a mounted remote share); created if missing
+        dest: PathBuf,
+        /// Give up after this many seconds if a running backup holds the
+        /// backup directory (default: wait until it finishes)
+        #[arg(long, value_name = "SECS")]
+        lock_timeout: Option<u64>,
+    },
+    /// Adopt a copy of a source already in its backup dir (e.g. made with
+    /// rsync) as the first full backup, so the next backup is incremental
+    /// instead of copying everything again
+    ImportSeed {
+        /// Configured source the copy was made from
+        source_dir: PathBuf,
+        /// Configured backup dir of that source holding the copy
+        backup_dir: PathBuf,
+    },
+    /// Write repo.json into a backup dir recording its format version,
+    /// backup mode and compression/encryption, so later versions of ardiex
+    /// can refuse or migrate it instead of misreading it
+    InitRepo {
+        /// Backup directory to initialize (created if missing)
+        backup_dir: PathBuf,
+    },
+    /// Keep a backup point (e.g. a known-good pre-migration state) through
+    /// retention and prune until it is unpinned
+    Pin {
+        /// Backup directory holding the point
+        backup_dir: PathBuf,
+        /// Backup name or timestamp (e.g. 20240221_100000), as shown by
+        /// `restore --list`
+        point: String,
+    },
+    /// Let retention and prune remove a pinned backup point again
+    Unpin {
+        /// Backup directory holding the point
+        backup_dir: PathBuf,
+        /// Backup name or timestamp
+        point: String,
+    },
+    /// Attach a free-form tag (e.g. pre-upgrade) to a backup point, for
+    /// `restore --tag`
+    Tag {
+        /// Backup directory holding the point
+        backup_dir: PathBuf,
+        /// Backup name or timestamp (e.g. 20240221_100000)
+        point: String,
+        /// Tag to attach
+        tag: String,
+        /// Remove the tag instead of attaching it
+        #[arg(long)]
+        remove: bool,
     },
     /// Start the backup service (periodic + event-driven)
-    Run,
+    Run {
+        /// Back up every source whose schedule is due, then exit (for
+        /// systemd timers or cron)
+        #[arg(long, conflicts_with = "daemonize")]
+        once: bool,
+        /// Detach into the background; the PID file is written next to the
+        /// settings and output goes to the log dir only
+        #[arg(long)]
+        daemonize: bool,
+    },
+    /// Install, remove or inspect the systemd unit running `run` (Linux)
+    Service {
+        #[command(subcommand)]
+        action: ServiceAction,
+    },
+    /// Stop the service started by `run`, using its PID file
+    Stop {
+        /// Seconds to wait for the service to exit
+        #[arg(long, default_value_t = 30)]
+        timeout: u64,
+    },
+    /// Preview the next cron fire times of every enabled source in local time
+    Schedule {
+        /// Number of upcoming fire times to show per schedule
+        #[arg(short = 'n', long, default_value_t = 5)]
+        count: usize,
+    },
+    /// Show recorded backup runs (successes and failures) across restarts
+    History {
+        /// Only show runs of this source directory
+        #[arg(long)]
+        source: Option<PathBuf>,
+        /// Number of most recent runs to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Report space usage, delta savings and change hot spots of a backup directory
+    Stats {
+        /// Backup directory to analyze
+        backup_dir: PathBuf,
+    },
+    /// Repair recoverable metadata problems in a backup directory
+    Repair {
+        /// Backup directory to repair
+        backup_dir: PathBuf,
+        /// Report what would be repaired without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Diagnose a backup directory: metadata history vs. disk, delta chain,
+    /// inc_checksums, signatures and leftover partial or unknown entries
+    Check {
+        /// Backup directory to check
+        backup_dir: PathBuf,
+        /// Resync metadata and remove leftovers, then check again
+        #[arg(long)]
+        repair: bool,
+        /// Print the findings as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Compare the stored copies of each file across a source's backup dirs
+    /// and repair corrupted ones from an intact copy
+    Scrub {
+        /// Only scrub this configured source (default: every enabled source)
+        #[arg(long)]
+        source: Option<PathBuf>,
+        /// Report corrupted copies without repairing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Apply retention to a backup directory by hand, also in append-only dirs
+    Prune {
+        /// Backup directory to prune
+        backup_dir: PathBuf,
+        /// Number of backups to keep (default: max_backups of the owning source)
+        #[arg(long)]
+        keep: Option<usize>,
+        /// Total size to stay within, e.g. "50GB" (default: the source's max_total_size)
+        #[arg(long)]
+        max_total_size: Option<String>,
+        /// Actually delete; without it only the backups that would go are listed
+        #[arg(long)]
+        allow_delete: bool,
+    },
+    /// Rewrite the delta chains of a backup directory into the reverse
+    /// layout: the newest version of each file whole, older ones as reverse
+    /// deltas
+    Compact {
+        /// Backup directory to compact
+        backup_dir: PathBuf,
+        /// Only report what would be rewritten
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Remove dedup chunks that no backup point references anymore
+    Gc {
+        /// Only report what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Measure hashing, copying and delta creation on generated data in a
+    /// directory and suggest max_concurrent_backups and delta_block_size
+    Bench {
+        /// Directory to write the sample data to (a backup dir's volume)
+        dir: PathBuf,
+        /// Amount of sample data, e.g. "512MB"; it is held in memory
+        #[arg(long, default_value = "256MB")]
+        size: String,
+        /// Print the measurements as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check the environment: settings file and permissions, cron
+    /// expressions, backup dirs and free space, locks, inotify watch limits,
+    /// clock skew and whether the service is already running
+    Doctor {
+        /// Print the findings as JSON
+        #[arg(long)]
+        json: bool,
+        /// Skip the clock skew check against a network time reference
+        #[arg(long)]
+        offline: bool,
+    },
+    /// Watch the running service in the terminal: queued triggers, backups
+    /// in progress, recent results and the log tail, over the REST API
+    Tui {
+        /// API address to connect to (default: api_bind from the settings);
+        /// the token comes from ARDIEX_API_TOKEN or api_token
+        #[arg(long)]
+        addr: Option<String>,
+        /// Seconds between refreshes
+        #[arg(long, default_value_t = 1)]
+        interval: u64,
+    },
+    /// Compare the installed version with the latest release, show its
+    /// release notes and install it after confirmation
+    Update {
+        /// Only report the versions and release notes
+        #[arg(long)]
+        check: bool,
+        /// Release channel to look at (default: update_channel from the settings)
+        #[arg(long)]
+        channel: Option<UpdateChannel>,
+        /// Install without asking
+        #[arg(short, long, conflicts_with = "check")]
+        yes: bool,
+    },
+    /// Prune or follow the log files
+    Logs {
+        #[command(subcommand)]
+        action: LogsAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum LogsAction {
+    /// Delete rotated log files beyond max_rotated_log_files per log and,
+    /// with max_log_dir_size, the oldest ones until the log directory fits
+    Prune {
+        /// Only report what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Print the last lines of the main log, or of a source's log with
+    /// per_source_logs
+    Tail {
+        /// Number of lines to print
+        #[arg(short = 'n', long, default_value_t = 20)]
+        lines: usize,
+        /// Keep printing lines as they are written
+        #[arg(short, long)]
+        follow: bool,
+        /// Source directory whose per-source log to show
+        #[arg(long)]
+        source: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ServiceAction {
+    /// Write a unit for this binary and settings file, then enable and
+    /// start it
+    Install {
+        /// Per-user unit (~/.config/systemd/user) instead of a system unit
+        #[arg(long)]
+        user: bool,
+        /// Only enable the unit; start it at the next boot or login
+        #[arg(long)]
+        no_start: bool,
+    },
+    /// Stop, disable and remove the unit
+    Uninstall {
+        /// Per-user unit instead of a system unit
+        #[arg(long)]
+        user: bool,
+    },
+    /// Show `systemctl status` of the unit
+    Status {
+        /// Per-user unit instead of a system unit
+        #[arg(long)]
+        user: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -41,6 +402,14 @@ pub enum ConfigAction {
     Init,
     /// Show current configuration
     List,
+    /// List the profiles that have a settings file
+    Profiles,
+    /// Show the effective settings of one source and whether each value is
+    /// overridden by the source or inherited from the global settings
+    ShowSource {
+        /// Source directory path
+        path: PathBuf,
+    },
     /// Add a new source directory
     AddSource {
         /// Source directory path
@@ -48,12 +417,26 @@ pub enum ConfigAction {
         /// Backup directory paths (optional)
         #[arg(short, long)]
         backup: Vec<PathBuf>,
+        /// Add without asking for confirmation (required when stdin is not
+        /// a terminal). The global -q/--quiet also skips the file listing
+        #[arg(short, long)]
+        yes: bool,
     },
     /// Remove a source directory
     RemoveSource {
         /// Source directory path
         path: PathBuf,
     },
+    /// Resume backups of a disabled source
+    EnableSource {
+        /// Source directory path
+        path: PathBuf,
+    },
+    /// Stop backing up a source without removing it or its settings
+    DisableSource {
+        /// Source directory path
+        path: PathBuf,
+    },
     /// Add a backup directory to a source
     AddBackup {
         /// Source directory path
@@ -68,18 +451,133 @@ pub enum ConfigAction {
         /// Backup directory path
         backup: PathBuf,
     },
+    /// Map a configured path prefix to the path visible in this environment
+    /// (e.g. host path -> container bind mount)
+    AddPathMapping {
+        /// Configured (host) path prefix
+        from: PathBuf,
+        /// Local (container) path prefix
+        to: PathBuf,
+    },
+    /// Remove a path mapping
+    RemovePathMapping {
+        /// Configured (host) path prefix
+        from: PathBuf,
+    },
+    /// Write the configuration (including backup metadata) to a file,
+    /// as TOML if it ends in .toml and JSON otherwise
+    Export {
+        /// Destination file
+        file: PathBuf,
+    },
+    /// Replace the configuration with an exported one and validate it
+    Import {
+        /// Exported settings file (.json or .toml)
+        file: PathBuf,
+        /// Move paths under a prefix, e.g. --remap /mnt/old=/mnt/new or
+        /// --remap 'D:\=E:\' (repeatable)
+        #[arg(long)]
+        remap: Vec<String>,
+        /// Replace the current configuration without asking (required when
+        /// stdin is not a terminal)
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Print the effective value of a configuration key
+    ///
+    /// Accepts the keys of `set` (dotted for nested ones, e.g.
+    /// anomaly_detection.min_files); with --source, the keys of `set-source`
+    /// resolved against the global value when the source has no override.
+    /// Strings are printed as is, everything else as JSON.
+    Get {
+        /// Key, e.g. max_backups or notifications.smtp.host
+        key: String,
+        /// Resolve the value for this source directory
+        #[arg(long)]
+        source: Option<PathBuf>,
+    },
+    /// Remove an optional configuration value
+    ///
+    /// Globally: max_total_size, metrics_port, metrics_address, api_bind, api_token,
+    /// include_patterns, dedup_store,
+    /// signing_key_file, notifications.webhook_url,
+    /// notifications.slack_webhook_url, notifications.smtp and
+    /// notifications.smtp.{username,password}. With --source, any
+    /// `set-source` key, which then falls back to the global value
+    /// (same as `set-source <source> <key> reset`).
+    Unset {
+        /// Key to remove
+        key: String,
+        /// Remove the override of this source directory
+        #[arg(long)]
+        source: Option<PathBuf>,
+    },
     /// Set a global configuration value
     ///
     /// Available keys:
     ///   enable_periodic        (true/false)
     ///   enable_event_driven    (true/false)
+    ///   full_scan_interval_secs (seconds or duration like "1h"; file-change backups hash only
+    ///                          the changed paths until then, 0 always scans everything)
+    ///   change_journal         (true/false, read changed paths from the USN journal/FSEvents; needs a build with the native-change-journal feature)
     ///   max_backups            (number)
-    ///   backup_mode            (delta/copy)
+    ///   max_total_size         (size like "50GB" per backup dir; "reset" removes the quota)
+    ///   max_backup_reports     (number, run reports kept in each backup dir's reports/; 0 disables)
+    ///   backup_mode            (delta/copy/dedup/mirror)
+    ///   backup_format          (directory/archive)
+    ///   durability             (fast/fsync, fsync flushes each backup before recording it)
+    ///   low_space_action       (abort/prune, when the backup volume is too full)
+    ///   low_space_min_backups  (number, fewest backups prune may leave)
+    ///   locked_file_policy     (fail/skip, for files still locked after the retries)
+    ///   locked_file_retries    (number, retries with doubling backoff for locked files)
+    ///   archive_encryption     (true/false, key from ARDIEX_ARCHIVE_PASSPHRASE)
+    ///   dedup_store            (absolute path of a chunk store shared by dedup sources; "reset" = per-dir objects/)
+    ///   signing_key_file       (absolute path outside backup dirs, HMAC-signs metadata; "reset" disables)
+    ///   preserve_xattrs        (true/false, record and restore xattrs/POSIX ACLs)
+    ///   hardlink_full_backups  (true/false, link files unchanged since the previous full)
+    ///   mirror_versions        (true/false, keep replaced files under versions/ in mirror mode)
+    ///   namespace_backups      (true/false, back up into <backup_dir>/<host>/<source id> for shared destinations)
+    ///   host_id                (this machine's name in namespaces and backup origins; "reset" uses the hostname)
+    ///   backup_name_template   (point names from {type}, {timestamp} and {label}, default "{type}_{timestamp}")
+    ///   verify_writes          (true/false, re-hash every written file before committing)
+    ///   prune_stale_hashes     (true/false, drop file hashes no backup point records; default true)
+    ///   delta_max_ratio        (> 0, e.g. 0.5: store a full copy when the delta is larger than that share of the file; "reset" always keeps deltas)
+    ///   delta_max_file_size    (size like "10GB"; larger files are copied whole instead of delta-ed; "reset" removes the cap)
+    ///   delta_block_size       (size like "16KB", power of two from 512 bytes to 1MB; block size of new deltas, default 4KB)
+    ///   file_strategies        ("<pattern>=<delta|copy|skip-delta>,...", how delta mode stores matching files; longest pattern wins)
+    ///   delta_layout           (forward/reverse; reverse keeps the newest version whole and older ones as reverse deltas)
+    ///   parity_percent         (0-100, Reed-Solomon parity per new backup point; 0 = off)
+    ///   append_only            (true/false, never delete backups outside `prune --allow-delete`)
     ///   cron_schedule          ("sec min hour day month dow")
+    ///   cron_jitter_secs       (seconds or duration like "5m"; random delay per cron trigger)
+    ///   blackout_windows       (";"-separated local windows like "Mon-Fri 09:00-18:00"; "reset" clears)
+    ///   max_concurrent_backups (number > 0, sources `run` backs up at once)
+    ///   io_throttle_mbps       (MB/s > 0, caps source reads during backups; "reset" removes the cap)
+    ///   worker_nice            (1-19, CPU niceness `run` lowers itself to; "reset" keeps the default)
+    ///   worker_ionice_idle     (true/false, idle I/O class for `run` on Linux)
     ///   enable_min_interval_by_size  (true/false)
-    ///   max_log_file_size_mb   (number, > 0)
+    ///   max_log_file_size_mb   (MB or size like "500MB", > 0)
+    ///   max_rotated_log_files  (number > 0, rotated files kept per log file)
+    ///   max_log_dir_size       (bytes or size like "500MB"; oldest rotated logs go beyond it; "reset" removes the cap)
+    ///   per_source_logs        (true/false, `run` also logs each source to logs/sources/)
+    ///   forward_interval_secs  (seconds or duration like "5m", > 0)
+    ///   forward_alert_after_hours  (hours or duration like "2d")
+    ///   metrics_port           (1-65535, "reset" disables the /metrics exporter)
+    ///   metrics_address        (IP the exporter listens on, default 127.0.0.1; 0.0.0.0 for all interfaces)
+    ///   api_bind               (address like 127.0.0.1:8686 for the REST API of `run`; "reset" disables it)
+    ///   api_token              (bearer token the REST API requires; "reset" clears)
+    ///   api_dashboard          (true/false, serve the web dashboard on / of api_bind)
+    ///   api_restore_roots      (comma-separated absolute dirs API restores may write into; "reset" clears)
+    ///   auto_update            (true/false, install newer releases on start instead of only via `ardiex update`)
+    ///   update_channel         (stable/beta, beta also installs pre-releases)
+    ///   allow_unsigned_updates (true/false, install releases whose checksums are not signed)
+    ///   include_patterns       (comma-separated, e.g. "*.rs,*.toml"; "reset" clears)
+    ///   anomaly_detection.enabled  (true/false, pause backups on ransomware-like changes)
+    ///   anomaly_detection.max_changed_ratio  (0-1, default 0.8)
+    ///   anomaly_detection.max_extension_change_ratio  (0-1, default 0.3)
+    ///   anomaly_detection.min_files  (number, smaller sources are never paused)
     Set {
-        /// Key: enable_periodic, enable_event_driven, max_backups, backup_mode, cron_schedule, enable_min_interval_by_size, max_log_file_size_mb
+        /// Key: enable_periodic, enable_event_driven, full_scan_interval_secs, change_journal, max_backups, max_total_size, max_backup_reports, backup_mode, backup_format, durability, low_space_action, low_space_min_backups, locked_file_policy, locked_file_retries, archive_encryption, signing_key_file, dedup_store, preserve_xattrs, hardlink_full_backups, mirror_versions, namespace_backups, host_id, backup_name_template, verify_writes, prune_stale_hashes, delta_max_ratio, delta_max_file_size, delta_block_size, file_strategies, delta_layout, parity_percent, append_only, cron_schedule, cron_jitter_secs, blackout_windows, max_concurrent_backups, io_throttle_mbps, worker_nice, worker_ionice_idle, enable_min_interval_by_size, max_log_file_size_mb, max_rotated_log_files, max_log_dir_size, per_source_logs, forward_interval_secs, forward_alert_after_hours, metrics_port, metrics_address, api_bind, api_token, api_dashboard, api_restore_roots, auto_update, update_channel, allow_unsigned_updates, include_patterns, anomaly_detection.{enabled,max_changed_ratio,max_extension_change_ratio,min_files}, notifications.webhook_url, notifications.slack_webhook_url ("reset" clears), notifications.only_on_failure, notifications.smtp.{host,port,security,username,password,from,to} ("notifications.smtp reset" removes SMTP)
         key: String,
         /// Configuration value
         value: String,
@@ -87,17 +585,75 @@ pub enum ConfigAction {
     /// Set a source-specific configuration value (overrides global)
     ///
     /// Available keys:
-    ///   exclude_patterns       (comma-separated, e.g. "*.cache,*.tmp")
+    ///   enabled                (true/false, same as enable-source/disable-source)
+    ///   include_patterns       (comma-separated, e.g. "*.rs,*.toml"; applied before excludes)
+    ///   exclude_patterns       (comma-separated globs, e.g. "*.cache,**/node_modules")
     ///   max_backups            (number)
-    ///   backup_mode            (delta/copy)
+    ///   max_total_size         (size like "50GB")
+    ///   backup_mode            (delta/copy/dedup/mirror)
+    ///   backup_format          (directory/archive)
+    ///   durability             (fast/fsync)
+    ///   low_space_action       (abort/prune)
+    ///   low_space_min_backups  (number)
+    ///   locked_file_policy     (fail/skip)
+    ///   locked_file_retries    (number)
+    ///   preserve_xattrs        (true/false)
+    ///   hardlink_full_backups  (true/false)
+    ///   mirror_versions        (true/false)
+    ///   namespace_backups      (true/false)
+    ///   backup_name_template   (e.g. "{label}_{type}_{timestamp}")
+    ///   label                  (letters, digits, '-' and '.'; fills {label} of the name template)
+    ///   verify_writes          (true/false)
+    ///   prune_stale_hashes     (true/false)
+    ///   delta_max_ratio        (> 0, fraction of the file size)
+    ///   delta_max_file_size    (size like "10GB")
+    ///   file_strategies        ("<pattern>=<delta|copy|skip-delta>,...", replaces the global map)
+    ///   delta_layout           (forward/reverse)
+    ///   parity_percent         (0-100)
+    ///   append_only            (true/false)
+    ///   one_file_system        (true/false, skip directories on other mounts)
+    ///   backup_on_start        (true/false, back up as soon as `run` starts)
     ///   cron_schedule          ("sec min hour day month dow")
+    ///   cron_jitter_secs       (seconds or duration like "5m")
+    ///   verify_schedule        ("sec min hour day month dow", background `check` of the backup dirs)
     ///   enable_event_driven    (true/false)
+    ///   full_scan_interval_secs (seconds or duration like "1h")
+    ///   change_journal         (true/false)
     ///   enable_periodic        (true/false)
+    ///   context_command        (shell command, e.g. "git rev-parse HEAD")
+    ///   paths                  (comma-separated subpaths, e.g. "docs/,configs/")
+    ///   forward_dir            (absolute path, e.g. a NAS mount for queue-and-forward)
+    ///   healthcheck_url        (http(s) URL pinged at start/success/fail)
+    ///   snapshot               (btrfs, btrfs:<subvolume>, lvm:<vg/lv>:<mount point>[:<size>] or vss)
+    ///   max_file_size          (bytes or size like "4GB"; larger files are skipped)
+    ///   min_file_size          (bytes or size like "1KB"; smaller files are skipped)
+    ///   modified_within        (seconds or duration like "30d"; older files are skipped)
+    ///   backup_timeout         (seconds or duration like "2h"; a longer backup is cancelled)
+    ///   io_throttle_mbps       (MB/s > 0, caps source reads during backups)
     /// Use "reset" as value to clear and fall back to global
     SetSource {
         /// Source directory path
         source: PathBuf,
-        /// Key: exclude_patterns, max_backups, backup_mode, cron_schedule, enable_event_driven, enable_periodic (use "reset" as value to clear)
+        /// Key: enabled, include_patterns, exclude_patterns, max_backups, max_total_size, backup_mode, backup_format, durability, low_space_action, low_space_min_backups, locked_file_policy, locked_file_retries, preserve_xattrs, hardlink_full_backups, mirror_versions, namespace_backups, backup_name_template, label, verify_writes, prune_stale_hashes, delta_max_ratio, delta_max_file_size, file_strategies, delta_layout, parity_percent, append_only, one_file_system, backup_on_start, cron_schedule, cron_jitter_secs, verify_schedule, enable_event_driven, full_scan_interval_secs, change_journal, enable_periodic, context_command, paths, forward_dir, healthcheck_url, snapshot, max_file_size, min_file_size, modified_within, backup_timeout, io_throttle_mbps (use "reset" as value to clear)
+        key: String,
+        /// Configuration value (use "reset" to clear override)
+        value: String,
+    },
+    /// Set a value for one backup dir of a source (overrides the source)
+    ///
+    /// Available keys:
+    ///   backup_mode            (delta/copy/dedup/mirror)
+    ///   backup_format          (directory/archive, archive compresses each point)
+    ///   max_backups            (number)
+    ///   cron_schedule          ("sec min hour day month dow"; under `run` the dir is
+    ///                           only written once this schedule is due)
+    /// Use "reset" as value to fall back to the source setting
+    SetDestination {
+        /// Source directory path
+        source: PathBuf,
+        /// Backup directory path (one of the source's backup dirs)
+        backup: PathBuf,
+        /// Key: backup_mode, backup_format, max_backups, cron_schedule
         key: String,
         /// Configuration value (use "reset" to clear override)
         value: String,
@@ -3,16 +3,34 @@ use log::{error, info};
 
 use crate::backup::BackupManager;
 use crate::config::ConfigManager;
+use crate::logger;
+use crate::notification;
+use crate::progress;
 
-pub async fn handle_backup() -> Result<()> {
+pub async fn handle_backup(accept_changes: bool) -> Result<()> {
     let config_manager = ConfigManager::load_or_create()?;
-    let config = config_manager.get_config().clone();
-    let mut backup_manager = BackupManager::new(config);
+    let config = config_manager.get_config().localized();
+    let notifications = config.notifications.clone();
+    let mut backup_manager = BackupManager::new(config).with_history(config_manager.history_path());
+    if accept_changes {
+        backup_manager = backup_manager.accept_changes();
+    }
 
+    progress::enable_bars_if_interactive();
     info!("Starting manual backup");
-    backup_manager.validate_all_sources()?;
+    if let Err(e) = backup_manager.validate_all_sources() {
+        notification::notify_failures_by_email(
+            &notifications,
+            "pre-flight validation",
+            &[format!("{:#}", e)],
+        )
+        .await;
+        return Err(e);
+    }
 
     match backup_manager.backup_all_sources().await {
+        // Scripted quiet runs only hear about failures.
+        Ok(_) if logger::is_quiet() => {}
         Ok(results) => {
             for result in results {
                 println!(
@@ -22,6 +40,17 @@ pub async fn handle_backup() -> Result<()> {
                     result.bytes_processed as f64 / 1024.0 / 1024.0,
                     result.duration_ms
                 );
+                if let Some(ref context) = result.context {
+                    println!("  Context: {}", context);
+                }
+                println!("  Resources: {}", result.resource_usage.summary());
+                if !result.skipped_files.is_empty() {
+                    println!(
+                        "  Skipped {} locked file(s): {}",
+                        result.skipped_files.len(),
+                        result.skipped_files.join(", ")
+                    );
+                }
             }
         }
         Err(e) => {
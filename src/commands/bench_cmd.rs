@@ -0,0 +1,59 @@
+use anyhow::{Context, Result};
+use log::info;
+use std::path::PathBuf;
+
+use crate::bench;
+use crate::units;
+
+pub async fn handle_bench(dir: PathBuf, size: String, json: bool) -> Result<()> {
+    let sample_bytes = units::parse_size(&size, 1).context("Invalid value for --size")?;
+    if sample_bytes == 0 {
+        return Err(anyhow::anyhow!("--size must be > 0"));
+    }
+    if !dir.is_dir() {
+        return Err(anyhow::anyhow!("Not a directory: {:?}", dir));
+    }
+    info!(
+        "Benchmarking {:?} with {} of sample data",
+        dir,
+        units::format_size(sample_bytes)
+    );
+    let report = tokio::task::spawn_blocking(move || bench::run(&dir, sample_bytes)).await??;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    println!(
+        "Benchmarked {:?} with {} of sample data",
+        report.dir,
+        units::format_size(report.sample_bytes)
+    );
+    println!("Hashing:");
+    for run in &report.hash {
+        println!("  {:<8} {:>8.0} MB/s", run.algorithm, run.mb_per_sec);
+    }
+    println!("Copying ({}):", report.copy_method);
+    for run in &report.copy {
+        println!("  {} worker(s) {:>8.0} MB/s", run.workers, run.mb_per_sec);
+    }
+    println!("Delta creation:");
+    for run in &report.delta {
+        println!(
+            "  {:>8} blocks {:>8.0} MB/s, delta {}",
+            units::format_size(run.block_size as u64),
+            run.mb_per_sec,
+            units::format_size(run.delta_bytes)
+        );
+    }
+    println!("Recommended settings:");
+    for recommendation in &report.recommendations {
+        println!(
+            "  ardiex config set {} {}",
+            recommendation.setting, recommendation.value
+        );
+        println!("      {}", recommendation.reason);
+    }
+    println!("  Hashes are always SHA-256; blake3 is shown for comparison only");
+    Ok(())
+}
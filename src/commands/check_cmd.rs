@@ -0,0 +1,62 @@
+use anyhow::{Context, Result};
+use log::info;
+use std::path::PathBuf;
+
+use crate::backup::BackupManager;
+use crate::config::ConfigManager;
+use crate::signing;
+
+pub async fn handle_check(backup_dir: PathBuf, repair: bool, json: bool) -> Result<()> {
+    info!("Checking {:?} (repair: {})", backup_dir, repair);
+
+    let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
+    let signing_key =
+        signing::load_optional(config_manager.get_config().signing_key_file.as_deref())?;
+    let report = BackupManager::check_backup_dir(&backup_dir, repair, signing_key.as_ref())?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!(
+            "Checked {:?}: {} backup point(s)",
+            report.backup_dir, report.backup_points
+        );
+        if let Some(reason) = &report.repair_skipped {
+            println!("Not repairing: {}", reason);
+        }
+        if let Some(repaired) = &report.repair {
+            println!("Repairs applied:");
+            for action in &repaired.actions {
+                println!("  - {}", action);
+            }
+        }
+        if report.findings.is_empty() {
+            println!("No problems found");
+        }
+        for finding in &report.findings {
+            let point = finding
+                .point
+                .as_deref()
+                .map(|point| format!(" {}:", point))
+                .unwrap_or_default();
+            println!(
+                "  [{}] {}:{} {}",
+                finding.severity.as_str(),
+                finding.check,
+                point,
+                finding.message
+            );
+            println!("      fix: {}", finding.suggested_fix);
+        }
+    }
+
+    let errors = report.error_count();
+    if errors > 0 {
+        return Err(anyhow::anyhow!(
+            "{} problem(s) in {:?} can break restores",
+            errors,
+            report.backup_dir
+        ));
+    }
+    Ok(())
+}
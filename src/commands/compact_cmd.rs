@@ -0,0 +1,58 @@
+use anyhow::{Context, Result};
+use log::info;
+use std::path::PathBuf;
+
+use crate::backup::BackupManager;
+use crate::config::ConfigManager;
+use crate::signing;
+use crate::units;
+
+pub async fn handle_compact(backup_dir: PathBuf, dry_run: bool) -> Result<()> {
+    let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
+    let config = config_manager.get_config();
+    let resolved = config
+        .source_for_backup_dir(&backup_dir)
+        .map(|(source, dir)| source.resolve_for(config, &dir));
+    let signing_key = signing::load_optional(config.signing_key_file.as_deref())?;
+
+    info!("Compacting {:?} (dry_run: {})", backup_dir, dry_run);
+    let report = BackupManager::compact_backup_dir(
+        &backup_dir,
+        resolved.as_ref(),
+        dry_run,
+        signing_key.as_ref(),
+    )?;
+
+    if report.chains_skipped > 0 {
+        println!(
+            "{} chain(s) with archive or dedup points left as they are",
+            report.chains_skipped
+        );
+    }
+    if report.points_rewritten.is_empty() {
+        println!("Nothing to compact in {:?}", report.backup_dir);
+        return Ok(());
+    }
+    if report.dry_run {
+        println!(
+            "Up to {} stored version(s) would be rewritten in {:?} (pass without --dry-run to compact):",
+            report.versions_rewritten, report.backup_dir
+        );
+    } else {
+        println!(
+            "Rewrote {} stored version(s) in {:?}:",
+            report.versions_rewritten, report.backup_dir
+        );
+    }
+    for name in &report.points_rewritten {
+        println!("  - {}", name);
+    }
+    if !report.dry_run {
+        println!(
+            "Size: {} -> {}",
+            units::format_size(report.bytes_before),
+            units::format_size(report.bytes_after)
+        );
+    }
+    Ok(())
+}
@@ -1,10 +1,24 @@
 use anyhow::{Context, Result};
 use cron::Schedule;
 use log::warn;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Write};
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
 
+use crate::backup::BackupManager;
 use crate::cli::ConfigAction;
-use crate::config::{self, ConfigManager};
+use crate::config::{self, BackupConfig, ConfigManager};
+use crate::config_format::ConfigFormat;
+use crate::delta;
+use crate::logger;
+use crate::metrics;
+use crate::notification;
+use crate::patterns;
+use crate::point_name;
+use crate::snapshot;
+use crate::units;
 
 pub fn ensure_absolute(path: &std::path::Path, label: &str) -> Result<()> {
     if !path.is_absolute() {
@@ -17,10 +31,494 @@ pub fn ensure_absolute(path: &std::path::Path, label: &str) -> Result<()> {
     Ok(())
 }
 
+fn parse_delta_layout(value: &str) -> Result<config::DeltaLayout> {
+    match value {
+        "forward" => Ok(config::DeltaLayout::Forward),
+        "reverse" => Ok(config::DeltaLayout::Reverse),
+        _ => Err(anyhow::anyhow!(
+            "Invalid delta_layout: '{}'. Use 'forward' or 'reverse'",
+            value
+        )),
+    }
+}
+
+fn parse_backup_format(value: &str) -> Result<config::BackupFormat> {
+    match value {
+        "directory" => Ok(config::BackupFormat::Directory),
+        "archive" => Ok(config::BackupFormat::Archive),
+        _ => Err(anyhow::anyhow!(
+            "Invalid backup_format: '{}'. Use 'directory' or 'archive'",
+            value
+        )),
+    }
+}
+
+fn parse_backup_mode(value: &str) -> Result<config::BackupMode> {
+    match value {
+        "delta" => Ok(config::BackupMode::Delta),
+        "copy" => Ok(config::BackupMode::Copy),
+        "dedup" => Ok(config::BackupMode::Dedup),
+        "mirror" => Ok(config::BackupMode::Mirror),
+        _ => Err(anyhow::anyhow!(
+            "Invalid backup_mode: '{}'. Use 'delta', 'copy', 'dedup' or 'mirror'",
+            value
+        )),
+    }
+}
+
+fn parse_durability(value: &str) -> Result<config::Durability> {
+    match value {
+        "fast" => Ok(config::Durability::Fast),
+        "fsync" => Ok(config::Durability::Fsync),
+        _ => Err(anyhow::anyhow!(
+            "Invalid durability: '{}'. Use 'fast' or 'fsync'",
+            value
+        )),
+    }
+}
+
+fn parse_locked_file_policy(value: &str) -> Result<config::LockedFilePolicy> {
+    match value {
+        "fail" => Ok(config::LockedFilePolicy::Fail),
+        "skip" => Ok(config::LockedFilePolicy::Skip),
+        _ => Err(anyhow::anyhow!(
+            "Invalid locked_file_policy: '{}'. Use 'fail' or 'skip'",
+            value
+        )),
+    }
+}
+
+/// `<pattern>=<strategy>` pairs separated by commas, e.g.
+/// `*.jpg=copy,*.sql=delta`.
+fn parse_file_strategies(value: &str) -> Result<BTreeMap<String, config::FileStrategy>> {
+    let mut strategies = BTreeMap::new();
+    for pair in value.split(',') {
+        let (pattern, strategy) = pair.trim().rsplit_once('=').ok_or_else(|| {
+            anyhow::anyhow!("Invalid file strategy '{}'. Use <pattern>=<strategy>", pair)
+        })?;
+        let strategy = match strategy.trim() {
+            "delta" => config::FileStrategy::Delta,
+            "copy" | "skip-delta" => config::FileStrategy::Copy,
+            other => {
+                return Err(anyhow::anyhow!(
+                    "Invalid strategy '{}' for '{}'. Use 'delta', 'copy' or 'skip-delta'",
+                    other,
+                    pattern
+                ));
+            }
+        };
+        strategies.insert(pattern.trim().to_string(), strategy);
+    }
+    patterns::validate_patterns(&strategies.keys().cloned().collect::<Vec<_>>())?;
+    Ok(strategies)
+}
+
+fn format_file_strategies(strategies: &BTreeMap<String, config::FileStrategy>) -> String {
+    strategies
+        .iter()
+        .map(|(pattern, strategy)| {
+            let name = match strategy {
+                config::FileStrategy::Delta => "delta",
+                config::FileStrategy::Copy => "copy",
+            };
+            format!("{}={}", pattern, name)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn parse_delta_max_ratio(value: &str) -> Result<f64> {
+    let ratio: f64 = value.parse().context("Invalid value for delta_max_ratio")?;
+    if !(ratio.is_finite() && ratio > 0.0) {
+        return Err(anyhow::anyhow!("delta_max_ratio must be > 0"));
+    }
+    Ok(ratio)
+}
+
+fn parse_delta_block_size(value: &str) -> Result<usize> {
+    let size = units::parse_size(value, 1).context("Invalid value for delta_block_size")?;
+    match usize::try_from(size) {
+        Ok(size) if delta::is_valid_block_size(size) => Ok(size),
+        _ => Err(anyhow::anyhow!(
+            "Invalid delta_block_size: {}. Use a power of two from {} to {}",
+            value,
+            units::format_size(delta::MIN_BLOCK_SIZE as u64),
+            units::format_size(delta::MAX_BLOCK_SIZE as u64)
+        )),
+    }
+}
+
+fn parse_parity_percent(value: &str) -> Result<u32> {
+    let percent: u32 = value
+        .parse()
+        .context("Invalid value for parity_percent (0-100)")?;
+    if percent > 100 {
+        return Err(anyhow::anyhow!(
+            "Invalid parity_percent: {}. Use 0 (off) to 100",
+            percent
+        ));
+    }
+    Ok(percent)
+}
+
+fn parse_low_space_action(value: &str) -> Result<config::LowSpaceAction> {
+    match value {
+        "abort" => Ok(config::LowSpaceAction::Abort),
+        "prune" => Ok(config::LowSpaceAction::Prune),
+        _ => Err(anyhow::anyhow!(
+            "Invalid low_space_action: '{}'. Use 'abort' or 'prune'",
+            value
+        )),
+    }
+}
+
+/// Comma-separated glob list, rejected up front if any pattern is invalid.
+fn parse_patterns(value: &str) -> Result<Vec<String>> {
+    let list: Vec<String> = value.split(',').map(|s| s.trim().to_string()).collect();
+    patterns::validate_patterns(&list)?;
+    Ok(list)
+}
+
+/// `--remap <from>=<to>`, both absolute.
+fn parse_remap(value: &str) -> Result<config::PathMapping> {
+    let (from, to) = value
+        .split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("Invalid remap '{}'. Use <from>=<to>", value))?;
+    let mapping = config::PathMapping {
+        from: PathBuf::from(from),
+        to: PathBuf::from(to),
+    };
+    ensure_absolute(&mapping.from, "Remap source path")?;
+    ensure_absolute(&mapping.to, "Remap target path")?;
+    Ok(mapping)
+}
+
+/// Ask a yes/no question on stdin. `yes` answers it up front; without a
+/// terminal to ask on, the action is refused instead of blocking.
+pub(crate) fn confirm(prompt: &str, yes: bool) -> Result<bool> {
+    if yes {
+        return Ok(true);
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err(anyhow::anyhow!(
+            "stdin is not a terminal; pass --yes to confirm without prompting"
+        ));
+    }
+    print!("{} [y/N] ", prompt);
+    std::io::stdout().flush()?;
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    Ok(input.trim().eq_ignore_ascii_case("y"))
+}
+
+/// Global keys that hold an optional value, i.e. the ones `config set`
+/// accepts "reset" for.
+const GLOBAL_OPTIONAL_KEYS: &[&str] = &[
+    "max_total_size",
+    "max_log_dir_size",
+    "io_throttle_mbps",
+    "delta_max_ratio",
+    "delta_max_file_size",
+    "file_strategies",
+    "worker_nice",
+    "metrics_port",
+    "metrics_address",
+    "api_bind",
+    "api_token",
+    "api_restore_roots",
+    "include_patterns",
+    "blackout_windows",
+    "dedup_store",
+    "signing_key_file",
+    "host_id",
+    "notifications.webhook_url",
+    "notifications.slack_webhook_url",
+    "notifications.smtp",
+    "notifications.smtp.username",
+    "notifications.smtp.password",
+];
+
+fn lookup<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    key.split('.')
+        .try_fold(value, |value, part| value.get(part))
+        .filter(|value| !value.is_null())
+}
+
+/// Effective value of a dotted `key`: the global setting, or with `source`
+/// the source's own override falling back to the global one.
+pub(crate) fn effective_value(
+    config: &BackupConfig,
+    source: Option<&Path>,
+    key: &str,
+) -> Result<Value> {
+    let global = serde_json::to_value(config)?;
+    let local = match source {
+        Some(source) => {
+            let src = config
+                .sources
+                .iter()
+                .find(|s| s.source_dir == source)
+                .ok_or_else(|| anyhow::anyhow!("Source not found: {:?}", source))?;
+            Some(serde_json::to_value(src)?)
+        }
+        None => None,
+    };
+    local
+        .as_ref()
+        .and_then(|local| lookup(local, key))
+        .or_else(|| lookup(&global, key))
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("'{}' is not set or is not a configuration key", key))
+}
+
+/// One resolved setting of a source and where its value comes from:
+/// "source" (overridden), "global" (inherited) or "derived".
+pub(crate) struct EffectiveSetting {
+    pub key: &'static str,
+    pub value: String,
+    pub origin: &'static str,
+}
+
+/// Settings of `source` after `SourceConfig::resolve`, with their origin.
+pub(crate) fn effective_source_settings(
+    config: &BackupConfig,
+    source: &config::SourceConfig,
+) -> Vec<EffectiveSetting> {
+    let resolved = source.resolve(config);
+    let origin = |overridden: bool| if overridden { "source" } else { "global" };
+    let setting = |key, value: String, overridden| EffectiveSetting {
+        key,
+        value,
+        origin: origin(overridden),
+    };
+    vec![
+        setting(
+            "include_patterns",
+            format!("{:?}", resolved.include_patterns),
+            source.include_patterns.is_some(),
+        ),
+        setting(
+            "exclude_patterns",
+            format!("{:?}", resolved.exclude_patterns),
+            source.exclude_patterns.is_some(),
+        ),
+        setting(
+            "max_backups",
+            resolved.max_backups.to_string(),
+            source.max_backups.is_some(),
+        ),
+        EffectiveSetting {
+            key: "full_backup_interval",
+            value: resolved.full_backup_interval.to_string(),
+            origin: "derived",
+        },
+        setting(
+            "max_total_size",
+            resolved
+                .max_total_size
+                .map(units::format_size)
+                .unwrap_or_else(|| "unlimited".to_string()),
+            source.max_total_size.is_some(),
+        ),
+        setting(
+            "io_throttle_mbps",
+            resolved
+                .io_throttle_mbps
+                .map(|mbps| format!("{} MB/s", mbps))
+                .unwrap_or_else(|| "unlimited".to_string()),
+            source.io_throttle_mbps.is_some(),
+        ),
+        setting(
+            "backup_mode",
+            format!("{:?}", resolved.backup_mode),
+            source.backup_mode.is_some(),
+        ),
+        setting(
+            "backup_format",
+            format!("{:?}", resolved.backup_format),
+            source.backup_format.is_some(),
+        ),
+        setting(
+            "durability",
+            format!("{:?}", resolved.durability),
+            source.durability.is_some(),
+        ),
+        setting(
+            "low_space_action",
+            format!("{:?}", resolved.low_space_action),
+            source.low_space_action.is_some(),
+        ),
+        setting(
+            "low_space_min_backups",
+            resolved.low_space_min_backups.to_string(),
+            source.low_space_min_backups.is_some(),
+        ),
+        setting(
+            "locked_file_policy",
+            format!("{:?}", resolved.locked_file_policy),
+            source.locked_file_policy.is_some(),
+        ),
+        setting(
+            "locked_file_retries",
+            resolved.locked_file_retries.to_string(),
+            source.locked_file_retries.is_some(),
+        ),
+        setting(
+            "preserve_xattrs",
+            resolved.preserve_xattrs.to_string(),
+            source.preserve_xattrs.is_some(),
+        ),
+        setting(
+            "hardlink_full_backups",
+            resolved.hardlink_full_backups.to_string(),
+            source.hardlink_full_backups.is_some(),
+        ),
+        setting(
+            "mirror_versions",
+            resolved.mirror_versions.to_string(),
+            source.mirror_versions.is_some(),
+        ),
+        setting(
+            "namespace_backups",
+            source
+                .namespace_backups
+                .unwrap_or(config.namespace_backups)
+                .to_string(),
+            source.namespace_backups.is_some(),
+        ),
+        setting(
+            "backup_name_template",
+            resolved.backup_name_template.clone(),
+            source.backup_name_template.is_some(),
+        ),
+        setting(
+            "verify_writes",
+            resolved.verify_writes.to_string(),
+            source.verify_writes.is_some(),
+        ),
+        setting(
+            "prune_stale_hashes",
+            resolved.prune_stale_hashes.to_string(),
+            source.prune_stale_hashes.is_some(),
+        ),
+        setting(
+            "delta_max_ratio",
+            resolved
+                .delta_max_ratio
+                .map(|ratio| ratio.to_string())
+                .unwrap_or_else(|| "unlimited".to_string()),
+            source.delta_max_ratio.is_some(),
+        ),
+        setting(
+            "delta_max_file_size",
+            resolved
+                .delta_max_file_size
+                .map(units::format_size)
+                .unwrap_or_else(|| "unlimited".to_string()),
+            source.delta_max_file_size.is_some(),
+        ),
+        setting(
+            "file_strategies",
+            format_file_strategies(&resolved.file_strategies),
+            source.file_strategies.is_some(),
+        ),
+        setting(
+            "delta_layout",
+            format!("{:?}", resolved.delta_layout),
+            source.delta_layout.is_some(),
+        ),
+        setting(
+            "parity_percent",
+            resolved.parity_percent.to_string(),
+            source.parity_percent.is_some(),
+        ),
+        setting(
+            "append_only",
+            resolved.append_only.to_string(),
+            source.append_only.is_some(),
+        ),
+        setting(
+            "cron_schedule",
+            resolved.cron_schedule.clone(),
+            source.cron_schedule.is_some(),
+        ),
+        setting(
+            "cron_jitter_secs",
+            units::format_duration(resolved.cron_jitter_secs),
+            source.cron_jitter_secs.is_some(),
+        ),
+        setting(
+            "full_scan_interval_secs",
+            units::format_duration(resolved.full_scan_interval_secs),
+            source.full_scan_interval_secs.is_some(),
+        ),
+        setting(
+            "change_journal",
+            resolved.change_journal.to_string(),
+            source.change_journal.is_some(),
+        ),
+        setting(
+            "enable_event_driven",
+            resolved.enable_event_driven.to_string(),
+            source.enable_event_driven.is_some(),
+        ),
+        setting(
+            "enable_periodic",
+            resolved.enable_periodic.to_string(),
+            source.enable_periodic.is_some(),
+        ),
+        setting(
+            "archive_encryption",
+            resolved.archive_encryption.to_string(),
+            false,
+        ),
+        setting(
+            "anomaly_detection.enabled",
+            resolved.anomaly_detection.enabled.to_string(),
+            false,
+        ),
+    ]
+}
+
+fn parse_throttle_mbps(value: &str) -> Result<f64> {
+    let mbps: f64 = value
+        .parse()
+        .context("Invalid value for io_throttle_mbps")?;
+    if !(mbps.is_finite() && mbps > 0.0) {
+        return Err(anyhow::anyhow!("io_throttle_mbps must be > 0"));
+    }
+    Ok(mbps)
+}
+
 pub async fn handle_config(action: ConfigAction) -> Result<()> {
     let mut config_manager =
         ConfigManager::load_or_create().context("Failed to load configuration")?;
 
+    // Unset is "reset" through the matching set command.
+    let action = match action {
+        ConfigAction::Unset {
+            key,
+            source: Some(source),
+        } => ConfigAction::SetSource {
+            source,
+            key,
+            value: "reset".to_string(),
+        },
+        ConfigAction::Unset { key, source: None } => {
+            if !GLOBAL_OPTIONAL_KEYS.contains(&key.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "'{}' is not an optional global setting; change it with `config set`",
+                    key
+                ));
+            }
+            ConfigAction::Set {
+                key,
+                value: "reset".to_string(),
+            }
+        }
+        action => action,
+    };
+
     match action {
         ConfigAction::Init => {
             println!(
@@ -34,50 +532,493 @@ pub async fn handle_config(action: ConfigAction) -> Result<()> {
             println!("Configuration:");
             println!("  Enable periodic: {}", config.enable_periodic);
             println!("  Enable event-driven: {}", config.enable_event_driven);
+            println!(
+                "  Full scan interval (event-driven): {}",
+                units::format_duration(config.full_scan_interval_secs)
+            );
+            println!("  Change journal: {}", config.change_journal);
             println!("  Max backups: {}", config.max_backups);
+            match config.max_backup_reports {
+                0 => println!("  Backup reports: disabled"),
+                kept => println!("  Backup reports kept: {}", kept),
+            }
             println!("  Backup mode: {:?}", config.backup_mode);
+            if let Some(ref store) = config.dedup_store {
+                println!("  Shared dedup store: {:?}", store);
+            }
+            println!(
+                "  Backup format: {:?} (archive encryption: {})",
+                config.backup_format, config.archive_encryption
+            );
+            match config.signing_key_file {
+                Some(ref key_file) => println!("  Metadata signing key: {:?}", key_file),
+                None => println!("  Metadata signing: disabled"),
+            }
+            println!("  Durability: {:?}", config.durability);
+            println!(
+                "  Low space action: {:?} (keep at least {} backups)",
+                config.low_space_action, config.low_space_min_backups
+            );
+            println!(
+                "  Locked files: {:?} after {} retries",
+                config.locked_file_policy, config.locked_file_retries
+            );
+            println!("  Preserve xattrs/ACLs: {}", config.preserve_xattrs);
+            println!(
+                "  Hard-link unchanged files in fulls: {}",
+                config.hardlink_full_backups
+            );
+            println!("  Keep mirror versions: {}", config.mirror_versions);
+            println!("  Verify writes: {}", config.verify_writes);
+            println!("  Prune stale file hashes: {}", config.prune_stale_hashes);
+            if let Some(ratio) = config.delta_max_ratio {
+                println!("  Delta max ratio: {}", ratio);
+            }
+            if let Some(size) = config.delta_max_file_size {
+                println!("  Delta max file size: {}", units::format_size(size));
+            }
+            println!(
+                "  Delta block size: {}",
+                units::format_size(config.delta_block_size as u64)
+            );
+            if !config.file_strategies.is_empty() {
+                println!(
+                    "  File strategies: {}",
+                    format_file_strategies(&config.file_strategies)
+                );
+            }
+            println!("  Delta layout: {:?}", config.delta_layout);
+            println!("  Parity: {}%", config.parity_percent);
+            println!("  Append-only backup dirs: {}", config.append_only);
             println!(
                 "  Full backup interval (auto): {} (derived from max_backups)",
                 global_auto_full_interval
             );
             println!("  Cron schedule: {}", config.cron_schedule);
+            if config.cron_jitter_secs > 0 {
+                println!(
+                    "  Cron jitter: up to {}",
+                    units::format_duration(config.cron_jitter_secs)
+                );
+            }
+            if !config.blackout_windows.is_empty() {
+                let windows: Vec<String> = config
+                    .blackout_windows
+                    .iter()
+                    .map(|w| w.to_string())
+                    .collect();
+                println!("  Blackout windows: {}", windows.join("; "));
+            }
+            println!(
+                "  Max concurrent backups: {}",
+                config.max_concurrent_backups
+            );
+            if let Some(mbps) = config.io_throttle_mbps {
+                println!("  I/O throttle: {} MB/s", mbps);
+            }
+            if let Some(nice) = config.worker_nice {
+                println!("  Worker nice: {}", nice);
+            }
+            if config.worker_ionice_idle {
+                println!("  Worker I/O priority: idle");
+            }
             println!(
                 "  Min interval by size: {}",
                 config.enable_min_interval_by_size
             );
-            println!("  Max log file size (MB): {}", config.max_log_file_size_mb);
+            println!(
+                "  Max log file size: {}",
+                units::format_size(config.max_log_file_size_mb * units::MB)
+            );
+            println!("  Rotated log files kept: {}", config.max_rotated_log_files);
+            if let Some(limit) = config.max_log_dir_size {
+                println!("  Max log directory size: {}", units::format_size(limit));
+            }
+            if config.per_source_logs {
+                println!("  Per-source logs: enabled");
+            }
+            println!(
+                "  Forward interval: {}, alert after: {}",
+                units::format_duration(config.forward_interval_secs),
+                units::format_duration(config.forward_alert_after_hours * units::SECS_PER_HOUR)
+            );
+            if let Some(quota) = config.max_total_size {
+                println!(
+                    "  Max total size per backup dir: {}",
+                    units::format_size(quota)
+                );
+            }
+            if let Some(port) = config.metrics_port {
+                let address = config.metrics_address.unwrap_or(metrics::DEFAULT_ADDRESS);
+                println!(
+                    "  Metrics exporter: http://{}/metrics",
+                    std::net::SocketAddr::new(address, port)
+                );
+            }
+            if let Some(ref addr) = config.api_bind {
+                println!(
+                    "  REST API: http://{}/api (token {})",
+                    addr,
+                    if config.api_token.is_some() {
+                        "set"
+                    } else {
+                        "missing"
+                    }
+                );
+                if !config.api_restore_roots.is_empty() {
+                    println!("  API restore roots: {:?}", config.api_restore_roots);
+                }
+            }
+            println!(
+                "  Updates: {} channel, {}",
+                config.update_channel.as_str(),
+                if config.auto_update {
+                    "installed on start"
+                } else {
+                    "installed by `ardiex update`"
+                }
+            );
+            if config.allow_unsigned_updates {
+                println!("  Unsigned updates: allowed");
+            }
+            if !config.include_patterns.is_empty() {
+                println!("  Include patterns: {:?}", config.include_patterns);
+            }
             println!("  Exclude patterns: {:?}", config.exclude_patterns);
+            println!(
+                "  Pattern precedence: a file is backed up only if it matches an include \
+                 (when any are set) and no exclude; patterns are globs relative to the \
+                 source, '/' anchors to its root"
+            );
+            if !config.notifications.is_empty() {
+                println!("  Notifications:");
+                if let Some(ref url) = config.notifications.webhook_url {
+                    println!("    Webhook: {}", notification::mask_webhook_url(url));
+                }
+                if let Some(ref url) = config.notifications.slack_webhook_url {
+                    println!("    Slack webhook: {}", notification::mask_webhook_url(url));
+                }
+                println!(
+                    "    Only on failure: {}",
+                    config.notifications.only_on_failure
+                );
+                if let Some(ref smtp) = config.notifications.smtp {
+                    println!(
+                        "    SMTP: {}:{} ({:?}) {} -> {}",
+                        smtp.host,
+                        smtp.port,
+                        smtp.security,
+                        smtp.from,
+                        smtp.to.join(", ")
+                    );
+                }
+            }
+            let anomaly = &config.anomaly_detection;
+            println!(
+                "  Anomaly detection: {} (pause when > {:.0}% modified or > {:.0}% renamed to a new extension, sources with >= {} files)",
+                anomaly.enabled,
+                anomaly.max_changed_ratio * 100.0,
+                anomaly.max_extension_change_ratio * 100.0,
+                anomaly.min_files
+            );
+            println!(
+                "  Namespace backups by host/source: {} (host: {})",
+                config.namespace_backups,
+                config.host_name()
+            );
+            println!("  Backup name template: {}", config.backup_name_template);
+            if !config.path_mappings.is_empty() {
+                println!("  Path mappings:");
+                for mapping in &config.path_mappings {
+                    println!("    {:?} -> {:?}", mapping.from, mapping.to);
+                }
+            }
             println!("\nSources:");
             for source in &config.sources {
                 let effective_max_backups = source.max_backups.unwrap_or(config.max_backups);
                 println!("  Source: {:?}", source.source_dir);
                 println!("    Enabled: {}", source.enabled);
-                println!("    Backup dirs: {:?}", source.backup_dirs);
+                println!("    Backup dirs: {:?}", source.effective_backup_dirs());
+                if let Some(namespace) = config.backup_namespace(source) {
+                    println!("    Backup namespace: {:?}", namespace);
+                }
+                for destination in source.backup_dirs.iter().filter(|d| d.has_overrides()) {
+                    println!("    Destination {:?}:", destination.path);
+                    if let Some(ref bm) = destination.backup_mode {
+                        println!("      Backup mode: {:?}", bm);
+                    }
+                    if let Some(ref bf) = destination.backup_format {
+                        println!("      Backup format: {:?}", bf);
+                    }
+                    if let Some(mb) = destination.max_backups {
+                        println!("      Max backups: {}", mb);
+                    }
+                    if let Some(ref cs) = destination.cron_schedule {
+                        println!("      Cron schedule: {}", cs);
+                    }
+                }
                 println!(
                     "    Full backup interval (auto/effective): {}",
                     config::auto_full_backup_interval(effective_max_backups)
                 );
+                if let Some(ref ip) = source.include_patterns {
+                    println!("    Include patterns (local): {:?}", ip);
+                }
                 if let Some(ref ep) = source.exclude_patterns {
                     println!("    Exclude patterns (local): {:?}", ep);
                 }
+                if let Some(min) = source.min_file_size {
+                    println!("    Min file size: {}", units::format_size(min));
+                }
+                if let Some(max) = source.max_file_size {
+                    println!("    Max file size: {}", units::format_size(max));
+                }
+                if let Some(within) = source.modified_within {
+                    println!("    Modified within: {}", units::format_duration(within));
+                }
+                if let Some(timeout) = source.backup_timeout {
+                    println!("    Backup timeout: {}", units::format_duration(timeout));
+                }
                 if let Some(mb) = source.max_backups {
                     println!("    Max backups (local): {}", mb);
                 }
+                if let Some(mbps) = source.io_throttle_mbps {
+                    println!("    I/O throttle (local): {} MB/s", mbps);
+                }
+                if let Some(quota) = source.max_total_size {
+                    println!("    Max total size (local): {}", units::format_size(quota));
+                }
                 if let Some(ref bm) = source.backup_mode {
                     println!("    Backup mode (local): {:?}", bm);
                 }
+                if let Some(ref bf) = source.backup_format {
+                    println!("    Backup format (local): {:?}", bf);
+                }
+                if let Some(ref du) = source.durability {
+                    println!("    Durability (local): {:?}", du);
+                }
+                if let Some(ref la) = source.low_space_action {
+                    println!("    Low space action (local): {:?}", la);
+                }
+                if let Some(lm) = source.low_space_min_backups {
+                    println!("    Low space min backups (local): {}", lm);
+                }
+                if let Some(ref lp) = source.locked_file_policy {
+                    println!("    Locked file policy (local): {:?}", lp);
+                }
+                if let Some(lr) = source.locked_file_retries {
+                    println!("    Locked file retries (local): {}", lr);
+                }
+                if source.one_file_system {
+                    println!("    One file system: true");
+                }
+                if source.backup_on_start {
+                    println!("    Backup on start: true");
+                }
+                if let Some(vw) = source.verify_writes {
+                    println!("    Verify writes (local): {}", vw);
+                }
+                if let Some(ps) = source.prune_stale_hashes {
+                    println!("    Prune stale file hashes (local): {}", ps);
+                }
+                if let Some(ratio) = source.delta_max_ratio {
+                    println!("    Delta max ratio (local): {}", ratio);
+                }
+                if let Some(size) = source.delta_max_file_size {
+                    println!(
+                        "    Delta max file size (local): {}",
+                        units::format_size(size)
+                    );
+                }
+                if let Some(strategies) = &source.file_strategies {
+                    println!(
+                        "    File strategies (local): {}",
+                        format_file_strategies(strategies)
+                    );
+                }
+                if let Some(dl) = source.delta_layout {
+                    println!("    Delta layout (local): {:?}", dl);
+                }
+                if let Some(pp) = source.parity_percent {
+                    println!("    Parity (local): {}%", pp);
+                }
+                if let Some(ao) = source.append_only {
+                    println!("    Append-only backup dirs (local): {}", ao);
+                }
+                if let Some(hl) = source.hardlink_full_backups {
+                    println!("    Hard-link unchanged files in fulls (local): {}", hl);
+                }
+                if let Some(mv) = source.mirror_versions {
+                    println!("    Keep mirror versions (local): {}", mv);
+                }
+                if let Some(nb) = source.namespace_backups {
+                    println!("    Namespace backups by host/source (local): {}", nb);
+                }
+                if let Some(ref template) = source.backup_name_template {
+                    println!("    Backup name template (local): {}", template);
+                }
+                if let Some(ref label) = source.label {
+                    println!("    Label: {}", label);
+                }
+                if let Some(px) = source.preserve_xattrs {
+                    println!("    Preserve xattrs/ACLs (local): {}", px);
+                }
                 if let Some(ref cs) = source.cron_schedule {
                     println!("    Cron schedule (local): {}", cs);
                 }
+                if let Some(jitter) = source.cron_jitter_secs {
+                    println!(
+                        "    Cron jitter (local): up to {}",
+                        units::format_duration(jitter)
+                    );
+                }
+                if let Some(ref vs) = source.verify_schedule {
+                    println!("    Verify schedule: {}", vs);
+                }
                 if let Some(eed) = source.enable_event_driven {
                     println!("    Enable event-driven (local): {}", eed);
                 }
+                if let Some(interval) = source.full_scan_interval_secs {
+                    println!(
+                        "    Full scan interval (local): {}",
+                        units::format_duration(interval)
+                    );
+                }
+                if let Some(cj) = source.change_journal {
+                    println!("    Change journal (local): {}", cj);
+                }
                 if let Some(ep) = source.enable_periodic {
                     println!("    Enable periodic (local): {}", ep);
                 }
+                if let Some(ref cc) = source.context_command {
+                    println!("    Context command: {}", cc);
+                }
+                if !source.paths.is_empty() {
+                    println!("    Paths: {:?}", source.paths);
+                }
+                if let Some(ref fd) = source.forward_dir {
+                    println!("    Forward dir: {:?}", fd);
+                }
+                if let Some(ref url) = source.healthcheck_url {
+                    println!("    Healthcheck URL: {}", url);
+                }
+                if let Some(ref snapshot) = source.snapshot {
+                    println!("    Snapshot: {}", snapshot::spec(snapshot));
+                }
+            }
+        }
+        ConfigAction::Export { file } => {
+            let content = ConfigFormat::of(&file).render(config_manager.get_config(), None)?;
+            std::fs::write(&file, content)
+                .with_context(|| format!("Failed to write {:?}", file))?;
+            println!(
+                "Exported {} source(s) to {:?}",
+                config_manager.get_config().sources.len(),
+                file
+            );
+        }
+        ConfigAction::Import { file, remap, yes } => {
+            let remaps = remap
+                .iter()
+                .map(|value| parse_remap(value))
+                .collect::<Result<Vec<_>>>()?;
+            let content = std::fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read {:?}", file))?;
+            let imported = ConfigFormat::of(&file)
+                .parse(&content)
+                .with_context(|| format!("Failed to parse {:?}", file))?
+                .remapped(&remaps);
+
+            for source in &imported.sources {
+                println!("  Source: {:?}", source.source_dir);
+                println!("    Backup dirs: {:?}", source.effective_backup_dirs());
+            }
+            BackupManager::new(imported.localized())
+                .validate_all_sources()
+                .context("Imported configuration failed validation; nothing was changed")?;
+
+            let existing = config_manager.get_config().sources.len();
+            if existing > 0
+                && !confirm(
+                    &format!(
+                        "Replace the current configuration ({} source(s))?",
+                        existing
+                    ),
+                    yes,
+                )?
+            {
+                println!("Cancelled.");
+                return Ok(());
+            }
+
+            *config_manager.get_config_mut() = imported;
+            config_manager.save()?;
+            println!("Configuration imported from {:?}", file);
+        }
+        ConfigAction::ShowSource { path } => {
+            ensure_absolute(&path, "Source path")?;
+            let config = config_manager.get_config();
+            let source = config
+                .sources
+                .iter()
+                .find(|s| s.source_dir == path)
+                .ok_or_else(|| anyhow::anyhow!("Source not found: {:?}", path))?;
+            println!("Source: {:?}", source.source_dir);
+            println!("  enabled: {}", source.enabled);
+            println!("  backup_on_start: {}", source.backup_on_start);
+            if let Some(timeout) = source.backup_timeout {
+                println!("  backup_timeout: {}", units::format_duration(timeout));
+            }
+            println!("  backup_dirs: {:?}", source.effective_backup_dirs());
+            for setting in effective_source_settings(config, source) {
+                println!(
+                    "  {:<24} {:<32} ({})",
+                    setting.key, setting.value, setting.origin
+                );
+            }
+            for destination in source.backup_dirs.iter().filter(|d| d.has_overrides()) {
+                let resolved = destination.apply(&source.resolve(config));
+                println!(
+                    "  Destination {:?} (overrides the source):",
+                    destination.path
+                );
+                if destination.backup_mode.is_some() {
+                    println!("    backup_mode: {:?}", resolved.backup_mode);
+                }
+                if destination.backup_format.is_some() {
+                    println!("    backup_format: {:?}", resolved.backup_format);
+                }
+                if destination.max_backups.is_some() {
+                    println!("    max_backups: {}", resolved.max_backups);
+                }
+                if destination.cron_schedule.is_some() {
+                    println!("    cron_schedule: {}", resolved.cron_schedule);
+                }
+            }
+        }
+        ConfigAction::Profiles => {
+            let active = config::active_profile()?;
+            for profile in config::list_profiles()? {
+                let marker = if active.as_deref() == Some(profile.as_str()) {
+                    "*"
+                } else {
+                    " "
+                };
+                println!("{} {}", marker, profile);
+            }
+        }
+        ConfigAction::Get { key, source } => {
+            if let Some(ref source) = source {
+                ensure_absolute(source, "Source path")?;
+            }
+            match effective_value(config_manager.get_config(), source.as_deref(), &key)? {
+                Value::String(value) => println!("{}", value),
+                value => println!("{}", value),
             }
         }
-        ConfigAction::AddSource { path, backup } => {
+        ConfigAction::Unset { .. } => unreachable!("unset is rewritten to set above"),
+        ConfigAction::AddSource { path, backup, yes } => {
+            let quiet = logger::is_quiet();
             ensure_absolute(&path, "Source path")?;
             for b in &backup {
                 ensure_absolute(b, "Backup path")?;
@@ -90,52 +1031,62 @@ pub async fn handle_config(action: ConfigAction) -> Result<()> {
             }
 
             // Show file list for confirmation
-            let mut file_count = 0;
-            let mut total_size: u64 = 0;
-            println!("Files in {:?}:", path);
-            println!("{:-<60}", "");
-            for entry in walkdir::WalkDir::new(&path)
-                .max_depth(3)
-                .into_iter()
-                .filter_map(|e| e.ok())
-                .filter(|e| e.file_type().is_file())
-            {
-                let rel = entry.path().strip_prefix(&path).unwrap_or(entry.path());
-                let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
-                total_size += size;
-                file_count += 1;
-                if file_count <= 20 {
-                    println!("  {} ({:.1} KB)", rel.display(), size as f64 / 1024.0);
+            if !quiet {
+                let mut file_count = 0;
+                let mut total_size: u64 = 0;
+                println!("Files in {:?}:", path);
+                println!("{:-<60}", "");
+                for entry in walkdir::WalkDir::new(&path)
+                    .max_depth(3)
+                    .into_iter()
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.file_type().is_file())
+                {
+                    let rel = entry.path().strip_prefix(&path).unwrap_or(entry.path());
+                    let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+                    total_size += size;
+                    file_count += 1;
+                    if file_count <= 20 {
+                        println!("  {} ({:.1} KB)", rel.display(), size as f64 / 1024.0);
+                    }
                 }
+                if file_count > 20 {
+                    println!("  ... and {} more files", file_count - 20);
+                }
+                println!("{:-<60}", "");
+                println!(
+                    "Total: {} files ({:.2} MB)",
+                    file_count,
+                    total_size as f64 / 1024.0 / 1024.0
+                );
+                println!();
             }
-            if file_count > 20 {
-                println!("  ... and {} more files", file_count - 20);
-            }
-            println!("{:-<60}", "");
-            println!(
-                "Total: {} files ({:.2} MB)",
-                file_count,
-                total_size as f64 / 1024.0 / 1024.0
-            );
-            println!();
 
-            print!("Add this source? [y/N] ");
-            std::io::Write::flush(&mut std::io::stdout())?;
-            let mut input = String::new();
-            std::io::stdin().read_line(&mut input)?;
-            if input.trim().to_lowercase() != "y" {
+            if !confirm("Add this source?", yes)? {
                 println!("Cancelled.");
                 return Ok(());
             }
 
             config_manager.add_source(path, backup)?;
-            println!("Source added successfully");
+            if !quiet {
+                println!("Source added successfully");
+            }
         }
         ConfigAction::RemoveSource { path } => {
             ensure_absolute(&path, "Source path")?;
             config_manager.remove_source(&path)?;
             println!("Source removed successfully");
         }
+        ConfigAction::EnableSource { path } => {
+            ensure_absolute(&path, "Source path")?;
+            config_manager.set_source_enabled(&path, true)?;
+            println!("Source enabled");
+        }
+        ConfigAction::DisableSource { path } => {
+            ensure_absolute(&path, "Source path")?;
+            config_manager.set_source_enabled(&path, false)?;
+            println!("Source disabled; its settings and backups are kept");
+        }
         ConfigAction::AddBackup { source, backup } => {
             ensure_absolute(&source, "Source path")?;
             ensure_absolute(&backup, "Backup path")?;
@@ -148,6 +1099,17 @@ pub async fn handle_config(action: ConfigAction) -> Result<()> {
             config_manager.remove_backup_dir(&source, &backup)?;
             println!("Backup directory removed successfully");
         }
+        ConfigAction::AddPathMapping { from, to } => {
+            ensure_absolute(&from, "Mapping source path")?;
+            ensure_absolute(&to, "Mapping target path")?;
+            config_manager.add_path_mapping(from, to)?;
+            println!("Path mapping added successfully");
+        }
+        ConfigAction::RemovePathMapping { from } => {
+            ensure_absolute(&from, "Mapping source path")?;
+            config_manager.remove_path_mapping(&from)?;
+            println!("Path mapping removed successfully");
+        }
         ConfigAction::Set { key, value } => {
             let config = config_manager.get_config_mut();
             match key.as_str() {
@@ -160,6 +1122,15 @@ pub async fn handle_config(action: ConfigAction) -> Result<()> {
                         .parse()
                         .context("Invalid value for enable_event_driven")?;
                 }
+                "full_scan_interval_secs" => {
+                    config.full_scan_interval_secs = units::parse_duration_as(&value, 1)
+                        .context("Invalid value for full_scan_interval_secs")?;
+                }
+                "change_journal" => {
+                    config.change_journal = value
+                        .parse()
+                        .context("Invalid value for change_journal (true/false)")?;
+                }
                 "max_backups" => {
                     let v: usize = value.parse().context("Invalid value for max_backups")?;
                     if v == 0 {
@@ -167,41 +1138,443 @@ pub async fn handle_config(action: ConfigAction) -> Result<()> {
                     }
                     config.max_backups = v;
                 }
+                "max_backup_reports" => {
+                    config.max_backup_reports = value
+                        .parse()
+                        .context("Invalid value for max_backup_reports")?;
+                }
                 "backup_mode" => {
-                    let new_mode = match value.as_str() {
-                        "delta" => config::BackupMode::Delta,
-                        "copy" => config::BackupMode::Copy,
-                        _ => {
-                            return Err(anyhow::anyhow!(
-                                "Invalid backup_mode: '{}'. Use 'delta' or 'copy'",
-                                value
-                            ));
-                        }
-                    };
-                    config.backup_mode = new_mode;
+                    config.backup_mode = parse_backup_mode(&value)?;
                 }
-                "cron_schedule" => {
-                    Schedule::from_str(&value)
-                        .map_err(|e| anyhow::anyhow!("Invalid cron expression: '{}'. Error: {}\nFormat: sec min hour day-of-month month day-of-week year", value, e))?;
-                    config.cron_schedule = value;
+                "backup_format" => {
+                    config.backup_format = parse_backup_format(&value)?;
                 }
-                "enable_min_interval_by_size" => {
-                    config.enable_min_interval_by_size = value
+                "durability" => {
+                    config.durability = parse_durability(&value)?;
+                }
+                "low_space_action" => {
+                    config.low_space_action = parse_low_space_action(&value)?;
+                }
+                "low_space_min_backups" => {
+                    config.low_space_min_backups = value
                         .parse()
-                        .context("Invalid value for enable_min_interval_by_size")?;
+                        .context("Invalid value for low_space_min_backups")?;
                 }
-                "max_log_file_size_mb" => {
-                    let v: u64 = value
+                "locked_file_policy" => {
+                    config.locked_file_policy = parse_locked_file_policy(&value)?;
+                }
+                "locked_file_retries" => {
+                    config.locked_file_retries = value
                         .parse()
-                        .context("Invalid value for max_log_file_size_mb")?;
-                    if v == 0 {
-                        return Err(anyhow::anyhow!("max_log_file_size_mb must be > 0"));
-                    }
-                    config.max_log_file_size_mb = v;
+                        .context("Invalid value for locked_file_retries")?;
                 }
-                _ => {
-                    warn!("Unknown configuration key: {}", key);
-                    return Ok(());
+                "verify_writes" => {
+                    config.verify_writes = value
+                        .parse()
+                        .context("Invalid value for verify_writes (true/false)")?;
+                }
+                "prune_stale_hashes" => {
+                    config.prune_stale_hashes = value
+                        .parse()
+                        .context("Invalid value for prune_stale_hashes (true/false)")?;
+                }
+                "delta_max_ratio" => {
+                    config.delta_max_ratio = if value == "reset" {
+                        None
+                    } else {
+                        Some(parse_delta_max_ratio(&value)?)
+                    };
+                }
+                "delta_max_file_size" => {
+                    config.delta_max_file_size = if value == "reset" {
+                        None
+                    } else {
+                        Some(
+                            units::parse_size(&value, 1)
+                                .context("Invalid value for delta_max_file_size")?,
+                        )
+                    };
+                }
+                "file_strategies" => {
+                    config.file_strategies = if value == "reset" {
+                        BTreeMap::new()
+                    } else {
+                        parse_file_strategies(&value)?
+                    };
+                }
+                "delta_layout" => {
+                    config.delta_layout = parse_delta_layout(&value)?;
+                }
+                "delta_block_size" => {
+                    config.delta_block_size = parse_delta_block_size(&value)?;
+                }
+                "parity_percent" => {
+                    config.parity_percent = parse_parity_percent(&value)?;
+                }
+                "append_only" => {
+                    config.append_only = value
+                        .parse()
+                        .context("Invalid value for append_only (true/false)")?;
+                }
+                "hardlink_full_backups" => {
+                    config.hardlink_full_backups = value
+                        .parse()
+                        .context("Invalid value for hardlink_full_backups (true/false)")?;
+                }
+                "mirror_versions" => {
+                    config.mirror_versions = value
+                        .parse()
+                        .context("Invalid value for mirror_versions (true/false)")?;
+                }
+                "namespace_backups" => {
+                    config.namespace_backups = value
+                        .parse()
+                        .context("Invalid value for namespace_backups (true/false)")?;
+                }
+                "backup_name_template" => {
+                    point_name::validate_template(&value)?;
+                    config.backup_name_template = value;
+                }
+                "host_id" => {
+                    config.host_id = if value == "reset" {
+                        None
+                    } else if value.trim().is_empty()
+                        || value.trim_matches('.').is_empty()
+                        || value.contains(['/', '\\'])
+                    {
+                        return Err(anyhow::anyhow!(
+                            "host_id must be a single directory name, e.g. 'laptop'"
+                        ));
+                    } else {
+                        Some(value)
+                    };
+                }
+                "preserve_xattrs" => {
+                    config.preserve_xattrs = value
+                        .parse()
+                        .context("Invalid value for preserve_xattrs (true/false)")?;
+                }
+                "archive_encryption" => {
+                    config.archive_encryption = value
+                        .parse()
+                        .context("Invalid value for archive_encryption")?;
+                }
+                "dedup_store" => {
+                    config.dedup_store = if value == "reset" {
+                        None
+                    } else {
+                        let store = PathBuf::from(&value);
+                        if !store.is_absolute() {
+                            return Err(anyhow::anyhow!("dedup_store must be an absolute path"));
+                        }
+                        Some(store)
+                    };
+                }
+                "signing_key_file" => {
+                    config.signing_key_file = if value == "reset" {
+                        None
+                    } else {
+                        let key_file = PathBuf::from(&value);
+                        if !key_file.is_absolute() {
+                            return Err(anyhow::anyhow!(
+                                "signing_key_file must be an absolute path"
+                            ));
+                        }
+                        crate::signing::SigningKey::load(&key_file)?;
+                        Some(key_file)
+                    };
+                }
+                "cron_schedule" => {
+                    Schedule::from_str(&value)
+                        .map_err(|e| anyhow::anyhow!("Invalid cron expression: '{}'. Error: {}\nFormat: sec min hour day-of-month month day-of-week year", value, e))?;
+                    config.cron_schedule = value;
+                }
+                "cron_jitter_secs" => {
+                    config.cron_jitter_secs = units::parse_duration_as(&value, 1)
+                        .context("Invalid value for cron_jitter_secs")?;
+                }
+                "blackout_windows" => {
+                    config.blackout_windows = if value == "reset" {
+                        Vec::new()
+                    } else {
+                        value
+                            .split(';')
+                            .map(|window| window.trim().parse())
+                            .collect::<Result<_>>()
+                            .context("Invalid value for blackout_windows")?
+                    };
+                }
+                "max_concurrent_backups" => {
+                    let v: usize = value
+                        .parse()
+                        .context("Invalid value for max_concurrent_backups")?;
+                    if v == 0 {
+                        return Err(anyhow::anyhow!("max_concurrent_backups must be > 0"));
+                    }
+                    config.max_concurrent_backups = v;
+                }
+                "enable_min_interval_by_size" => {
+                    config.enable_min_interval_by_size = value
+                        .parse()
+                        .context("Invalid value for enable_min_interval_by_size")?;
+                }
+                "max_log_file_size_mb" => {
+                    let v = units::parse_size_as(&value, units::MB)
+                        .context("Invalid value for max_log_file_size_mb")?;
+                    if v == 0 {
+                        return Err(anyhow::anyhow!("max_log_file_size_mb must be > 0"));
+                    }
+                    config.max_log_file_size_mb = v;
+                }
+                "forward_interval_secs" => {
+                    let v = units::parse_duration_as(&value, 1)
+                        .context("Invalid value for forward_interval_secs")?;
+                    if v == 0 {
+                        return Err(anyhow::anyhow!("forward_interval_secs must be > 0"));
+                    }
+                    config.forward_interval_secs = v;
+                }
+                "forward_alert_after_hours" => {
+                    config.forward_alert_after_hours =
+                        units::parse_duration_as(&value, units::SECS_PER_HOUR)
+                            .context("Invalid value for forward_alert_after_hours")?;
+                }
+                "max_total_size" => {
+                    config.max_total_size = if value == "reset" {
+                        None
+                    } else {
+                        Some(
+                            units::parse_size(&value, 1)
+                                .context("Invalid value for max_total_size")?,
+                        )
+                    };
+                }
+                "io_throttle_mbps" => {
+                    config.io_throttle_mbps = if value == "reset" {
+                        None
+                    } else {
+                        Some(parse_throttle_mbps(&value)?)
+                    };
+                }
+                "worker_nice" => {
+                    config.worker_nice = if value == "reset" {
+                        None
+                    } else {
+                        let nice: i32 = value.parse().context("Invalid value for worker_nice")?;
+                        if !(1..=19).contains(&nice) {
+                            return Err(anyhow::anyhow!("worker_nice must be between 1 and 19"));
+                        }
+                        Some(nice)
+                    };
+                }
+                "worker_ionice_idle" => {
+                    config.worker_ionice_idle = value
+                        .parse()
+                        .context("Invalid value for worker_ionice_idle (true/false)")?;
+                }
+                "max_rotated_log_files" => {
+                    let v: usize = value
+                        .parse()
+                        .context("Invalid value for max_rotated_log_files")?;
+                    if v == 0 {
+                        return Err(anyhow::anyhow!("max_rotated_log_files must be > 0"));
+                    }
+                    config.max_rotated_log_files = v;
+                }
+                "max_log_dir_size" => {
+                    config.max_log_dir_size = if value == "reset" {
+                        None
+                    } else {
+                        let limit = units::parse_size(&value, 1)
+                            .context("Invalid value for max_log_dir_size")?;
+                        if limit == 0 {
+                            return Err(anyhow::anyhow!("max_log_dir_size must be > 0"));
+                        }
+                        Some(limit)
+                    };
+                }
+                "per_source_logs" => {
+                    config.per_source_logs = value
+                        .parse()
+                        .context("Invalid value for per_source_logs (true/false)")?;
+                }
+                "metrics_port" => {
+                    config.metrics_port = if value == "reset" {
+                        None
+                    } else {
+                        let port: u16 = value.parse().context("Invalid value for metrics_port")?;
+                        if port == 0 {
+                            return Err(anyhow::anyhow!(
+                                "metrics_port must be between 1 and 65535"
+                            ));
+                        }
+                        Some(port)
+                    };
+                }
+                "metrics_address" => {
+                    config.metrics_address = if value == "reset" {
+                        None
+                    } else {
+                        Some(value.parse().with_context(|| {
+                            format!(
+                                "Invalid metrics_address: '{}'. Use an IP address like 127.0.0.1",
+                                value
+                            )
+                        })?)
+                    };
+                }
+                "api_bind" => {
+                    config.api_bind = if value == "reset" {
+                        None
+                    } else {
+                        value.parse::<std::net::SocketAddr>().with_context(|| {
+                            format!(
+                                "Invalid api_bind: '{}'. Use an address like 127.0.0.1:8686",
+                                value
+                            )
+                        })?;
+                        Some(value)
+                    };
+                }
+                "api_dashboard" => {
+                    config.api_dashboard = value
+                        .parse()
+                        .context("Invalid value for api_dashboard (true/false)")?;
+                }
+                "auto_update" => {
+                    config.auto_update = value
+                        .parse()
+                        .context("Invalid value for auto_update (true/false)")?;
+                }
+                "update_channel" => {
+                    config.update_channel = value.parse()?;
+                }
+                "allow_unsigned_updates" => {
+                    config.allow_unsigned_updates = value
+                        .parse()
+                        .context("Invalid value for allow_unsigned_updates (true/false)")?;
+                }
+                "api_restore_roots" => {
+                    config.api_restore_roots = if value == "reset" {
+                        Vec::new()
+                    } else {
+                        let roots: Vec<PathBuf> = value
+                            .split(',')
+                            .map(|root| PathBuf::from(root.trim()))
+                            .collect();
+                        for root in &roots {
+                            ensure_absolute(root, "api_restore_roots")?;
+                        }
+                        roots
+                    };
+                }
+                "api_token" => {
+                    config.api_token = if value == "reset" {
+                        None
+                    } else if value.trim().is_empty() {
+                        return Err(anyhow::anyhow!("api_token must not be empty"));
+                    } else {
+                        Some(value)
+                    };
+                }
+                "include_patterns" => {
+                    config.include_patterns = if value == "reset" {
+                        Vec::new()
+                    } else {
+                        parse_patterns(&value)?
+                    };
+                }
+                "notifications.webhook_url" | "notifications.slack_webhook_url" => {
+                    let url = if value == "reset" {
+                        None
+                    } else if value.starts_with("http://") || value.starts_with("https://") {
+                        Some(value)
+                    } else {
+                        return Err(anyhow::anyhow!("{} must be an http(s) URL or 'reset'", key));
+                    };
+                    if key == "notifications.webhook_url" {
+                        config.notifications.webhook_url = url;
+                    } else {
+                        config.notifications.slack_webhook_url = url;
+                    }
+                }
+                "anomaly_detection.enabled" => {
+                    config.anomaly_detection.enabled = value
+                        .parse()
+                        .context("Invalid value for anomaly_detection.enabled (true/false)")?;
+                }
+                "anomaly_detection.max_changed_ratio"
+                | "anomaly_detection.max_extension_change_ratio" => {
+                    let ratio: f64 = value
+                        .parse()
+                        .with_context(|| format!("Invalid value for {}", key))?;
+                    if !(ratio > 0.0 && ratio <= 1.0) {
+                        return Err(anyhow::anyhow!("{} must be in (0, 1]", key));
+                    }
+                    if key == "anomaly_detection.max_changed_ratio" {
+                        config.anomaly_detection.max_changed_ratio = ratio;
+                    } else {
+                        config.anomaly_detection.max_extension_change_ratio = ratio;
+                    }
+                }
+                "anomaly_detection.min_files" => {
+                    config.anomaly_detection.min_files = value
+                        .parse()
+                        .context("Invalid value for anomaly_detection.min_files")?;
+                }
+                "notifications.only_on_failure" => {
+                    config.notifications.only_on_failure = value
+                        .parse()
+                        .context("Invalid value for notifications.only_on_failure")?;
+                }
+                "notifications.smtp" if value == "reset" => {
+                    config.notifications.smtp = None;
+                }
+                smtp_key if smtp_key.starts_with("notifications.smtp.") => {
+                    let smtp = config
+                        .notifications
+                        .smtp
+                        .get_or_insert_with(Default::default);
+                    let optional = |v: String| if v == "reset" { None } else { Some(v) };
+                    match &smtp_key["notifications.smtp.".len()..] {
+                        "host" => smtp.host = value,
+                        "port" => {
+                            smtp.port = value
+                                .parse()
+                                .context("Invalid value for notifications.smtp.port")?
+                        }
+                        "security" => {
+                            smtp.security = match value.as_str() {
+                                "starttls" => config::SmtpSecurity::StartTls,
+                                "tls" => config::SmtpSecurity::Tls,
+                                "none" => config::SmtpSecurity::None,
+                                _ => {
+                                    return Err(anyhow::anyhow!(
+                                        "Invalid smtp security: '{}'. Use 'starttls', 'tls' or 'none'",
+                                        value
+                                    ));
+                                }
+                            }
+                        }
+                        "username" => smtp.username = optional(value),
+                        "password" => smtp.password = optional(value),
+                        "from" => smtp.from = value,
+                        "to" => {
+                            smtp.to = value
+                                .split(',')
+                                .map(|s| s.trim().to_string())
+                                .filter(|s| !s.is_empty())
+                                .collect()
+                        }
+                        _ => {
+                            warn!("Unknown configuration key: {}", key);
+                            return Ok(());
+                        }
+                    }
+                }
+                _ => {
+                    warn!("Unknown configuration key: {}", key);
+                    return Ok(());
                 }
             }
             config_manager.save()?;
@@ -220,11 +1593,24 @@ pub async fn handle_config(action: ConfigAction) -> Result<()> {
             let is_reset = value == "reset";
 
             match key.as_str() {
+                "enabled" => {
+                    src.enabled = is_reset
+                        || value
+                            .parse()
+                            .context("Invalid value for enabled (true/false)")?;
+                }
+                "include_patterns" => {
+                    src.include_patterns = if is_reset {
+                        None
+                    } else {
+                        Some(parse_patterns(&value)?)
+                    };
+                }
                 "exclude_patterns" => {
                     src.exclude_patterns = if is_reset {
                         None
                     } else {
-                        Some(value.split(',').map(|s| s.trim().to_string()).collect())
+                        Some(parse_patterns(&value)?)
                     };
                 }
                 "max_backups" => {
@@ -243,16 +1629,57 @@ pub async fn handle_config(action: ConfigAction) -> Result<()> {
                     src.backup_mode = if is_reset {
                         None
                     } else {
-                        Some(match value.as_str() {
-                            "delta" => config::BackupMode::Delta,
-                            "copy" => config::BackupMode::Copy,
-                            _ => {
-                                return Err(anyhow::anyhow!(
-                                    "Invalid backup_mode: '{}'. Use 'delta' or 'copy'",
-                                    value
-                                ));
-                            }
-                        })
+                        Some(parse_backup_mode(&value)?)
+                    };
+                }
+                "backup_format" => {
+                    src.backup_format = if is_reset {
+                        None
+                    } else {
+                        Some(parse_backup_format(&value)?)
+                    };
+                }
+                "durability" => {
+                    src.durability = if is_reset {
+                        None
+                    } else {
+                        Some(parse_durability(&value)?)
+                    };
+                }
+                "low_space_action" => {
+                    src.low_space_action = if is_reset {
+                        None
+                    } else {
+                        Some(parse_low_space_action(&value)?)
+                    };
+                }
+                "low_space_min_backups" => {
+                    src.low_space_min_backups = if is_reset {
+                        None
+                    } else {
+                        Some(
+                            value
+                                .parse()
+                                .context("Invalid value for low_space_min_backups")?,
+                        )
+                    };
+                }
+                "locked_file_policy" => {
+                    src.locked_file_policy = if is_reset {
+                        None
+                    } else {
+                        Some(parse_locked_file_policy(&value)?)
+                    };
+                }
+                "locked_file_retries" => {
+                    src.locked_file_retries = if is_reset {
+                        None
+                    } else {
+                        Some(
+                            value
+                                .parse()
+                                .context("Invalid value for locked_file_retries")?,
+                        )
                     };
                 }
                 "cron_schedule" => {
@@ -264,6 +1691,46 @@ pub async fn handle_config(action: ConfigAction) -> Result<()> {
                         Some(value)
                     };
                 }
+                "verify_schedule" => {
+                    src.verify_schedule = if is_reset {
+                        None
+                    } else {
+                        Schedule::from_str(&value)
+                            .map_err(|e| anyhow::anyhow!("Invalid cron expression: '{}'. Error: {}\nFormat: sec min hour day-of-month month day-of-week year", value, e))?;
+                        Some(value)
+                    };
+                }
+                "cron_jitter_secs" => {
+                    src.cron_jitter_secs = if is_reset {
+                        None
+                    } else {
+                        Some(
+                            units::parse_duration_as(&value, 1)
+                                .context("Invalid value for cron_jitter_secs")?,
+                        )
+                    };
+                }
+                "full_scan_interval_secs" => {
+                    src.full_scan_interval_secs = if is_reset {
+                        None
+                    } else {
+                        Some(
+                            units::parse_duration_as(&value, 1)
+                                .context("Invalid value for full_scan_interval_secs")?,
+                        )
+                    };
+                }
+                "change_journal" => {
+                    src.change_journal = if is_reset {
+                        None
+                    } else {
+                        Some(
+                            value
+                                .parse()
+                                .context("Invalid value for change_journal (true/false)")?,
+                        )
+                    };
+                }
                 "enable_event_driven" => {
                     src.enable_event_driven = if is_reset {
                         None
@@ -275,6 +1742,149 @@ pub async fn handle_config(action: ConfigAction) -> Result<()> {
                         )
                     };
                 }
+                "backup_on_start" => {
+                    src.backup_on_start = !is_reset
+                        && value
+                            .parse()
+                            .context("Invalid value for backup_on_start (true/false)")?;
+                }
+                "one_file_system" => {
+                    src.one_file_system = !is_reset
+                        && value
+                            .parse()
+                            .context("Invalid value for one_file_system (true/false)")?;
+                }
+                "verify_writes" => {
+                    src.verify_writes = if is_reset {
+                        None
+                    } else {
+                        Some(
+                            value
+                                .parse()
+                                .context("Invalid value for verify_writes (true/false)")?,
+                        )
+                    };
+                }
+                "prune_stale_hashes" => {
+                    src.prune_stale_hashes = if is_reset {
+                        None
+                    } else {
+                        Some(
+                            value
+                                .parse()
+                                .context("Invalid value for prune_stale_hashes (true/false)")?,
+                        )
+                    };
+                }
+                "delta_max_ratio" => {
+                    src.delta_max_ratio = if is_reset {
+                        None
+                    } else {
+                        Some(parse_delta_max_ratio(&value)?)
+                    };
+                }
+                "delta_max_file_size" => {
+                    src.delta_max_file_size = if is_reset {
+                        None
+                    } else {
+                        Some(
+                            units::parse_size(&value, 1)
+                                .context("Invalid value for delta_max_file_size")?,
+                        )
+                    };
+                }
+                "file_strategies" => {
+                    src.file_strategies = if is_reset {
+                        None
+                    } else {
+                        Some(parse_file_strategies(&value)?)
+                    };
+                }
+                "delta_layout" => {
+                    src.delta_layout = if is_reset {
+                        None
+                    } else {
+                        Some(parse_delta_layout(&value)?)
+                    };
+                }
+                "parity_percent" => {
+                    src.parity_percent = if is_reset {
+                        None
+                    } else {
+                        Some(parse_parity_percent(&value)?)
+                    };
+                }
+                "append_only" => {
+                    src.append_only = if is_reset {
+                        None
+                    } else {
+                        Some(
+                            value
+                                .parse()
+                                .context("Invalid value for append_only (true/false)")?,
+                        )
+                    };
+                }
+                "hardlink_full_backups" => {
+                    src.hardlink_full_backups = if is_reset {
+                        None
+                    } else {
+                        Some(
+                            value
+                                .parse()
+                                .context("Invalid value for hardlink_full_backups (true/false)")?,
+                        )
+                    };
+                }
+                "mirror_versions" => {
+                    src.mirror_versions = if is_reset {
+                        None
+                    } else {
+                        Some(
+                            value
+                                .parse()
+                                .context("Invalid value for mirror_versions (true/false)")?,
+                        )
+                    };
+                }
+                "namespace_backups" => {
+                    src.namespace_backups = if is_reset {
+                        None
+                    } else {
+                        Some(
+                            value
+                                .parse()
+                                .context("Invalid value for namespace_backups (true/false)")?,
+                        )
+                    };
+                }
+                "backup_name_template" => {
+                    src.backup_name_template = if is_reset {
+                        None
+                    } else {
+                        point_name::validate_template(&value)?;
+                        Some(value)
+                    };
+                }
+                "label" => {
+                    src.label = if is_reset {
+                        None
+                    } else {
+                        point_name::validate_label(&value)?;
+                        Some(value)
+                    };
+                }
+                "preserve_xattrs" => {
+                    src.preserve_xattrs = if is_reset {
+                        None
+                    } else {
+                        Some(
+                            value
+                                .parse()
+                                .context("Invalid value for preserve_xattrs (true/false)")?,
+                        )
+                    };
+                }
                 "enable_periodic" => {
                     src.enable_periodic = if is_reset {
                         None
@@ -286,6 +1896,125 @@ pub async fn handle_config(action: ConfigAction) -> Result<()> {
                         )
                     };
                 }
+                "context_command" => {
+                    src.context_command = if is_reset {
+                        None
+                    } else if value.trim().is_empty() {
+                        return Err(anyhow::anyhow!("context_command must not be empty"));
+                    } else {
+                        Some(value)
+                    };
+                }
+                "paths" => {
+                    src.paths = if is_reset {
+                        Vec::new()
+                    } else {
+                        value
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|p| !p.is_empty())
+                            .map(PathBuf::from)
+                            .collect()
+                    };
+                    if let Some(bad) = src.paths.iter().find(|p| {
+                        p.is_absolute() || p.components().any(|c| matches!(c, Component::ParentDir))
+                    }) {
+                        return Err(anyhow::anyhow!(
+                            "paths entries must be relative subpaths without '..': {:?}",
+                            bad
+                        ));
+                    }
+                }
+                "forward_dir" => {
+                    src.forward_dir = if is_reset {
+                        None
+                    } else {
+                        let dir = PathBuf::from(&value);
+                        ensure_absolute(&dir, "Forward path")?;
+                        Some(dir)
+                    };
+                }
+                "max_total_size" => {
+                    src.max_total_size = if is_reset {
+                        None
+                    } else {
+                        Some(
+                            units::parse_size(&value, 1)
+                                .context("Invalid value for max_total_size")?,
+                        )
+                    };
+                }
+                "io_throttle_mbps" => {
+                    src.io_throttle_mbps = if is_reset {
+                        None
+                    } else {
+                        Some(parse_throttle_mbps(&value)?)
+                    };
+                }
+                "max_file_size" | "min_file_size" => {
+                    let size = if is_reset {
+                        None
+                    } else {
+                        Some(
+                            units::parse_size(&value, 1)
+                                .with_context(|| format!("Invalid value for {}", key))?,
+                        )
+                    };
+                    if key == "max_file_size" {
+                        src.max_file_size = size;
+                    } else {
+                        src.min_file_size = size;
+                    }
+                    if let (Some(min), Some(max)) = (src.min_file_size, src.max_file_size)
+                        && min > max
+                    {
+                        return Err(anyhow::anyhow!(
+                            "min_file_size must not exceed max_file_size"
+                        ));
+                    }
+                }
+                "modified_within" => {
+                    src.modified_within = if is_reset {
+                        None
+                    } else {
+                        Some(
+                            units::parse_duration(&value, 1)
+                                .context("Invalid value for modified_within")?,
+                        )
+                    };
+                }
+                "backup_timeout" => {
+                    src.backup_timeout = if is_reset {
+                        None
+                    } else {
+                        let secs = units::parse_duration(&value, 1)
+                            .context("Invalid value for backup_timeout")?;
+                        if secs == 0 {
+                            return Err(anyhow::anyhow!("backup_timeout must be > 0"));
+                        }
+                        Some(secs)
+                    };
+                }
+                "healthcheck_url" => {
+                    src.healthcheck_url = if is_reset {
+                        None
+                    } else if value.starts_with("http://") || value.starts_with("https://") {
+                        Some(value)
+                    } else {
+                        return Err(anyhow::anyhow!(
+                            "healthcheck_url must be an http(s) URL or 'reset'"
+                        ));
+                    };
+                }
+                "snapshot" => {
+                    src.snapshot = if is_reset {
+                        None
+                    } else {
+                        let snapshot = snapshot::parse_spec(&value)?;
+                        snapshot::validate(&src.source_dir, &snapshot)?;
+                        Some(snapshot)
+                    };
+                }
                 _ => {
                     warn!("Unknown source configuration key: {}", key);
                     return Ok(());
@@ -298,7 +2027,81 @@ pub async fn handle_config(action: ConfigAction) -> Result<()> {
                 println!("Source config updated successfully");
             }
         }
+        ConfigAction::SetDestination {
+            source,
+            backup,
+            key,
+            value,
+        } => {
+            ensure_absolute(&source, "Source path")?;
+            ensure_absolute(&backup, "Backup path")?;
+            let config = config_manager.get_config_mut();
+            let destination = config
+                .sources
+                .iter_mut()
+                .find(|s| s.source_dir == source)
+                .ok_or_else(|| anyhow::anyhow!("Source not found: {:?}", source))?
+                .backup_dirs
+                .iter_mut()
+                .find(|d| d.path == backup)
+                .ok_or_else(|| {
+                    anyhow::anyhow!("{:?} is not a backup dir of {:?}", backup, source)
+                })?;
+
+            let is_reset = value == "reset";
+            match key.as_str() {
+                "backup_mode" => {
+                    destination.backup_mode = if is_reset {
+                        None
+                    } else {
+                        Some(parse_backup_mode(&value)?)
+                    };
+                }
+                "backup_format" => {
+                    destination.backup_format = if is_reset {
+                        None
+                    } else {
+                        Some(parse_backup_format(&value)?)
+                    };
+                }
+                "max_backups" => {
+                    destination.max_backups = if is_reset {
+                        None
+                    } else {
+                        let parsed: usize =
+                            value.parse().context("Invalid value for max_backups")?;
+                        if parsed == 0 {
+                            return Err(anyhow::anyhow!("max_backups must be > 0"));
+                        }
+                        Some(parsed)
+                    };
+                }
+                "cron_schedule" => {
+                    destination.cron_schedule = if is_reset {
+                        None
+                    } else {
+                        Schedule::from_str(&value)
+                            .map_err(|e| anyhow::anyhow!("Invalid cron expression: '{}'. Error: {}\nFormat: sec min hour day-of-month month day-of-week year", value, e))?;
+                        Some(value)
+                    };
+                }
+                _ => {
+                    warn!("Unknown destination configuration key: {}", key);
+                    return Ok(());
+                }
+            }
+            config_manager.save()?;
+            if is_reset {
+                println!("Destination config '{}' reset to the source setting", key);
+            } else {
+                println!("Destination config updated successfully");
+            }
+        }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+#[path = "../tests/config_cmd_tests.rs"]
+mod tests;
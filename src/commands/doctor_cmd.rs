@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use std::time::Duration;
+
+use crate::config::ConfigManager;
+use crate::doctor::{self, DoctorStatus};
+
+/// Any HTTPS server sends a `Date` header; this one is already trusted for
+/// update checks.
+const TIME_REFERENCE_URL: &str = "https://api.github.com";
+
+pub async fn handle_doctor(json: bool, offline: bool) -> Result<()> {
+    let config_manager = ConfigManager::load_or_create().context(
+        "Failed to load configuration; fix the settings file or point --config at another one",
+    )?;
+    let reference_time = if offline {
+        None
+    } else {
+        tokio::task::spawn_blocking(fetch_reference_time).await?
+    };
+    let report = doctor::diagnose(
+        config_manager.get_config(),
+        &config_manager.config_path,
+        reference_time,
+    );
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for finding in &report.findings {
+            println!(
+                "  [{}] {}: {}",
+                finding.status.as_str(),
+                finding.check,
+                finding.message
+            );
+            if let Some(ref hint) = finding.hint {
+                println!("      fix: {}", hint);
+            }
+        }
+        println!(
+            "{} error(s), {} warning(s)",
+            report.count(DoctorStatus::Error),
+            report.count(DoctorStatus::Warning)
+        );
+    }
+
+    let errors = report.count(DoctorStatus::Error);
+    if errors > 0 {
+        return Err(anyhow::anyhow!(
+            "{} problem(s) keep backups from running as configured",
+            errors
+        ));
+    }
+    Ok(())
+}
+
+fn fetch_reference_time() -> Option<DateTime<Utc>> {
+    info!("Reading the reference time from {}", TIME_REFERENCE_URL);
+    let fetch = || -> Result<DateTime<Utc>> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .context("Failed to build HTTP client")?;
+        let response = client
+            .head(TIME_REFERENCE_URL)
+            .header(reqwest::header::USER_AGENT, "ardiex-updater")
+            .send()
+            .context("Failed to reach the reference clock")?;
+        let date = response
+            .headers()
+            .get(reqwest::header::DATE)
+            .context("Response has no Date header")?
+            .to_str()?;
+        Ok(DateTime::parse_from_rfc2822(date)?.with_timezone(&Utc))
+    };
+    fetch()
+        .inspect_err(|e| warn!("Skipping the clock skew check: {:#}", e))
+        .ok()
+}
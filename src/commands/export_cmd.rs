@@ -0,0 +1,51 @@
+use anyhow::{Context, Result};
+use indicatif::HumanBytes;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::config::ConfigManager;
+use crate::export::{self, ExportFormat};
+use crate::progress;
+use crate::signing;
+
+pub async fn handle_export(
+    backup_dir: PathBuf,
+    point: Option<String>,
+    format: Option<ExportFormat>,
+    output: PathBuf,
+    lock_timeout: Option<u64>,
+) -> Result<()> {
+    let format = match format {
+        Some(format) => format,
+        None => ExportFormat::from_output(&output).with_context(|| {
+            format!(
+                "Cannot tell the archive format from {:?}; pass --format tar.gz or zip",
+                output
+            )
+        })?,
+    };
+    let signing_key_file = ConfigManager::load_or_create()
+        .context("Failed to load configuration")?
+        .get_config()
+        .signing_key_file
+        .clone();
+    let signing_key = signing::load_optional(signing_key_file.as_deref())?;
+
+    progress::enable_bars_if_interactive();
+    let summary = export::export_point(
+        &backup_dir,
+        point.as_deref(),
+        format,
+        &output,
+        signing_key.as_ref(),
+        lock_timeout.map(Duration::from_secs),
+    )?;
+    println!(
+        "Exported {} files ({}) of {} to {:?}",
+        summary.files,
+        HumanBytes(summary.bytes),
+        summary.points.last().map(String::as_str).unwrap_or("-"),
+        output
+    );
+    Ok(())
+}
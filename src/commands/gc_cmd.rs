@@ -0,0 +1,60 @@
+use anyhow::{Context, Result};
+use log::info;
+use std::path::PathBuf;
+
+use crate::backup::BackupManager;
+use crate::config::ConfigManager;
+use crate::units;
+
+pub async fn handle_gc(dry_run: bool) -> Result<()> {
+    let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
+    let config = config_manager.get_config();
+
+    // Every configured dir is scanned, not only dedup sources: a source
+    // switched to another mode still has dedup points using the store.
+    // Dirs of removed sources come from each shared store's own list.
+    let mut backup_dirs: Vec<PathBuf> = Vec::new();
+    let mut shared_stores: Vec<PathBuf> = Vec::new();
+    for source in &config.sources {
+        for dir in source
+            .effective_backup_dirs()
+            .into_iter()
+            .chain(source.forward_dir.clone())
+        {
+            if !backup_dirs.contains(&dir) {
+                backup_dirs.push(dir);
+            }
+        }
+        if let Some(store) = source.resolve(config).dedup_store
+            && !shared_stores.contains(&store)
+        {
+            shared_stores.push(store);
+        }
+    }
+
+    info!(
+        "Collecting unreferenced dedup chunks (dry_run: {})",
+        dry_run
+    );
+    let reports = BackupManager::collect_garbage(&backup_dirs, &shared_stores, dry_run)?;
+    if reports.is_empty() {
+        println!("No dedup chunk stores found");
+        return Ok(());
+    }
+    for report in &reports {
+        let action = if report.dry_run {
+            "would be removed"
+        } else {
+            "removed"
+        };
+        println!(
+            "{:?}: {} chunks, {} unreferenced {} ({})",
+            report.store,
+            report.chunks,
+            report.unreferenced,
+            action,
+            units::format_size(report.unreferenced_bytes)
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::path::PathBuf;
+
+use crate::config::ConfigManager;
+use crate::history::{self, RunStatus};
+
+pub async fn handle_history(source: Option<PathBuf>, limit: usize) -> Result<()> {
+    let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
+    let history_path = config_manager.history_path();
+    let records = history::read_records(&history_path, source.as_deref(), limit)?;
+
+    if records.is_empty() {
+        println!("No backup runs recorded in {:?}", history_path);
+        return Ok(());
+    }
+
+    println!("Backup runs ({:?}):", history_path);
+    for record in &records {
+        let finished_at = record
+            .finished_at
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S");
+        match record.status {
+            RunStatus::Success => println!(
+                "  {} [OK  ] [{}] {:?} -> {:?}: {} files, {:.2} MB in {} ms",
+                finished_at,
+                record.backup_type.as_deref().unwrap_or("?"),
+                record.source_dir,
+                record.backup_dir.clone().unwrap_or_default(),
+                record.files_backed_up,
+                record.bytes_processed as f64 / 1024.0 / 1024.0,
+                record.duration_ms
+            ),
+            RunStatus::Failure => println!(
+                "  {} [FAIL] {:?}: {}",
+                finished_at,
+                record.source_dir,
+                record.error.as_deref().unwrap_or("unknown error")
+            ),
+        }
+    }
+
+    let failures = records
+        .iter()
+        .filter(|record| record.status == RunStatus::Failure)
+        .count();
+    println!("{} run(s) shown, {} failed", records.len(), failures);
+    Ok(())
+}
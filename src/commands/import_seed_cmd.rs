@@ -0,0 +1,56 @@
+use anyhow::{Context, Result, bail};
+use indicatif::HumanBytes;
+use log::{info, warn};
+use std::path::PathBuf;
+
+use crate::backup::BackupManager;
+use crate::config::ConfigManager;
+
+pub async fn handle_import_seed(source_dir: PathBuf, backup_dir: PathBuf) -> Result<()> {
+    let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
+    let config = config_manager.get_config().localized();
+    let source_dir = std::path::absolute(&source_dir).unwrap_or(source_dir);
+    let backup_dir = std::path::absolute(&backup_dir).unwrap_or(backup_dir);
+    let Some(source) = config
+        .sources
+        .iter()
+        .find(|source| source.source_dir == source_dir)
+    else {
+        bail!(
+            "{:?} is not a configured source; add it with `ardiex config add-source` first",
+            source_dir
+        );
+    };
+    let Some(backup_dir) = source
+        .effective_backup_dirs()
+        .into_iter()
+        .find(|dir| *dir == backup_dir)
+    else {
+        bail!(
+            "{:?} is not a backup dir of {:?}; add it with `ardiex config add-backup` first",
+            backup_dir,
+            source_dir
+        );
+    };
+
+    info!(
+        "Adopting {:?} as the first backup of {:?}",
+        backup_dir, source_dir
+    );
+    let resolved = source.resolve_for(&config, &backup_dir);
+    let result = BackupManager::import_seed(source, &resolved, &backup_dir)?;
+    if result.files_untracked > 0 {
+        warn!(
+            "{} files of the copy are outside the source's patterns or limits; \
+             they stay in {} but are not tracked",
+            result.files_untracked, result.backup_name
+        );
+    }
+    println!(
+        "Adopted {} files ({}) as {:?}; the next backup will be incremental",
+        result.files_adopted,
+        HumanBytes(result.bytes_adopted),
+        backup_dir.join(&result.backup_name)
+    );
+    Ok(())
+}
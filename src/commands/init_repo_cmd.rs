@@ -0,0 +1,46 @@
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+
+use crate::config::ConfigManager;
+use crate::repo::{self, RepoInfo};
+
+/// Write `repo.json` for `backup_dir` with the settings of the source that
+/// backs up into it, or the global settings when no source does yet.
+pub async fn handle_init_repo(backup_dir: PathBuf) -> Result<()> {
+    let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
+    let config = config_manager.get_config().localized();
+    let backup_dir = std::path::absolute(&backup_dir).unwrap_or(backup_dir);
+
+    let owner = config.sources.iter().find_map(|source| {
+        source
+            .effective_backup_dirs()
+            .into_iter()
+            .find(|dir| *dir == backup_dir)
+            .map(|dir| source.resolve_for(&config, &dir))
+    });
+    let info = match owner {
+        Some(resolved) => RepoInfo::new(
+            resolved.backup_mode,
+            resolved.backup_format,
+            resolved.archive_encryption,
+            &resolved.host,
+        ),
+        None => RepoInfo::new(
+            config.backup_mode.clone(),
+            config.backup_format.clone(),
+            config.archive_encryption,
+            &config.host_name(),
+        ),
+    };
+    repo::init(&backup_dir, &info)?;
+    println!(
+        "Initialized {:?} (format version {}, {:?}/{:?}, compression: {}, encryption: {})",
+        backup_dir,
+        info.format_version,
+        info.backup_mode,
+        info.backup_format,
+        info.compression,
+        info.encryption
+    );
+    Ok(())
+}
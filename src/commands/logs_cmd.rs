@@ -0,0 +1,108 @@
+use anyhow::{Context, Result, bail};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::cli::LogsAction;
+use crate::config::ConfigManager;
+use crate::logger;
+use crate::units;
+
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub async fn handle_logs(action: LogsAction, log_dir: Option<PathBuf>) -> Result<()> {
+    let Some(log_dir) = log_dir else {
+        bail!("Could not determine the log directory");
+    };
+    match action {
+        LogsAction::Prune { dry_run } => prune(&log_dir, dry_run),
+        LogsAction::Tail {
+            lines,
+            follow,
+            source,
+        } => {
+            let log_file = match source {
+                Some(source) => {
+                    let source = std::path::absolute(&source).unwrap_or(source);
+                    log_dir
+                        .join(logger::SOURCE_LOG_DIR)
+                        .join(logger::source_log_file_name(&source))
+                }
+                None => log_dir.join(logger::DEFAULT_LOG_FILE_NAME),
+            };
+            tail(&log_file, lines, follow).await
+        }
+    }
+}
+
+fn prune(log_dir: &Path, dry_run: bool) -> Result<()> {
+    let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
+    let config = config_manager.get_config();
+    let report = logger::prune_logs(
+        log_dir,
+        config.max_rotated_log_files,
+        config.max_log_dir_size,
+        dry_run,
+    )?;
+    if report.files.is_empty() {
+        println!("No log files to prune in {:?}", log_dir);
+        return Ok(());
+    }
+    for file in &report.files {
+        println!("{:?}", file);
+    }
+    let action = if dry_run {
+        "would be removed"
+    } else {
+        "removed"
+    };
+    println!(
+        "{} log file(s) {} ({})",
+        report.files.len(),
+        action,
+        units::format_size(report.bytes)
+    );
+    Ok(())
+}
+
+/// Print the last `lines` lines of `log_file`; with `follow`, keep printing
+/// what is appended, starting over when the file is rotated.
+async fn tail(log_file: &Path, lines: usize, follow: bool) -> Result<()> {
+    let mut file =
+        File::open(log_file).with_context(|| format!("Failed to open log file {:?}", log_file))?;
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)?;
+    let mut position = content.len() as u64;
+    let content = String::from_utf8_lossy(&content);
+    let skip = content.lines().count().saturating_sub(lines);
+    let mut stdout = std::io::stdout();
+    for line in content.lines().skip(skip) {
+        writeln!(stdout, "{}", line)?;
+    }
+    if !follow {
+        return Ok(());
+    }
+
+    loop {
+        tokio::time::sleep(FOLLOW_POLL_INTERVAL).await;
+        let Ok(metadata) = std::fs::metadata(log_file) else {
+            continue;
+        };
+        if metadata.len() < position {
+            // Rotated: the live file was moved aside and started again.
+            file = File::open(log_file)
+                .with_context(|| format!("Failed to reopen log file {:?}", log_file))?;
+            position = 0;
+        }
+        if metadata.len() == position {
+            continue;
+        }
+        file.seek(SeekFrom::Start(position))?;
+        let mut appended = Vec::new();
+        file.read_to_end(&mut appended)?;
+        position += appended.len() as u64;
+        stdout.write_all(&appended)?;
+        stdout.flush()?;
+    }
+}
@@ -1,4 +1,26 @@
 pub mod backup_cmd;
+pub mod bench_cmd;
+pub mod check_cmd;
+pub mod compact_cmd;
 pub mod config_cmd;
+pub mod doctor_cmd;
+pub mod export_cmd;
+pub mod gc_cmd;
+pub mod history_cmd;
+pub mod import_seed_cmd;
+pub mod init_repo_cmd;
+pub mod logs_cmd;
+pub mod pin_cmd;
+pub mod prune_cmd;
+pub mod repair_cmd;
+pub mod replicate_cmd;
 pub mod restore_cmd;
 pub mod run_cmd;
+pub mod schedule_cmd;
+pub mod scrub_cmd;
+pub mod service_cmd;
+pub mod stats_cmd;
+pub mod stop_cmd;
+pub mod tag_cmd;
+pub mod tui_cmd;
+pub mod update_cmd;
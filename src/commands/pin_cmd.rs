@@ -0,0 +1,22 @@
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+
+use crate::backup::BackupManager;
+use crate::config::ConfigManager;
+use crate::signing;
+
+pub async fn handle_pin(backup_dir: PathBuf, point: String, pinned: bool) -> Result<()> {
+    let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
+    let signing_key =
+        signing::load_optional(config_manager.get_config().signing_key_file.as_deref())?;
+    let backup_name = BackupManager::set_pinned(&backup_dir, &point, pinned, signing_key.as_ref())?;
+    if pinned {
+        println!(
+            "Pinned {}; retention and prune keep it until `ardiex unpin`",
+            backup_name
+        );
+    } else {
+        println!("Unpinned {}; retention may remove it again", backup_name);
+    }
+    Ok(())
+}
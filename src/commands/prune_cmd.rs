@@ -0,0 +1,83 @@
+use anyhow::{Context, Result};
+use log::info;
+use std::path::{Path, PathBuf};
+
+use crate::backup::BackupManager;
+use crate::config::{BackupConfig, ConfigManager, ResolvedSourceConfig};
+use crate::signing;
+use crate::units;
+
+/// Retention settings of the source that writes to `backup_dir`, either as
+/// one of its backup dirs (with that destination's overrides) or as its
+/// `forward_dir`.
+fn owning_source(config: &BackupConfig, backup_dir: &Path) -> Option<ResolvedSourceConfig> {
+    config
+        .source_for_backup_dir(backup_dir)
+        .map(|(source, dir)| source.resolve_for(config, &dir))
+}
+
+pub async fn handle_prune(
+    backup_dir: PathBuf,
+    keep: Option<usize>,
+    max_total_size: Option<String>,
+    allow_delete: bool,
+) -> Result<()> {
+    let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
+    let config = config_manager.get_config();
+    let resolved = owning_source(config, &backup_dir);
+    let keep = match (keep, &resolved) {
+        (Some(keep), _) => keep,
+        (None, Some(resolved)) => resolved.max_backups,
+        (None, None) => {
+            return Err(anyhow::anyhow!(
+                "{:?} does not belong to a configured source; pass --keep",
+                backup_dir
+            ));
+        }
+    };
+    let max_total_size = match max_total_size {
+        Some(size) => Some(units::parse_size(&size, 1).context("Invalid --max-total-size")?),
+        None => resolved.as_ref().and_then(|r| r.max_total_size),
+    };
+    let backup_mode = resolved
+        .as_ref()
+        .map(|r| r.backup_mode.clone())
+        .unwrap_or(config.backup_mode.clone());
+    let signing_key = signing::load_optional(config.signing_key_file.as_deref())?;
+
+    info!(
+        "Pruning {:?} (keep: {}, allow_delete: {})",
+        backup_dir, keep, allow_delete
+    );
+    let report = BackupManager::prune_backup_dir(
+        &backup_dir,
+        keep,
+        max_total_size,
+        &backup_mode,
+        allow_delete,
+        signing_key.as_ref(),
+    )?;
+
+    if report.append_only {
+        println!("{:?} is append-only", report.backup_dir);
+    }
+    if report.removed.is_empty() {
+        println!("Nothing to prune in {:?}", report.backup_dir);
+        return Ok(());
+    }
+    if report.deleted {
+        println!("Removed from {:?}:", report.backup_dir);
+    } else {
+        println!(
+            "Backups that would be removed from {:?} (pass --allow-delete to delete):",
+            report.backup_dir
+        );
+    }
+    for name in &report.removed {
+        println!("  - {}", name);
+    }
+    if let Some(size) = report.remaining_size {
+        println!("Remaining size: {}", units::format_size(size));
+    }
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use anyhow::{Context, Result};
+use log::info;
+use std::path::PathBuf;
+
+use crate::backup::BackupManager;
+use crate::config::ConfigManager;
+use crate::signing;
+
+pub async fn handle_repair(backup_dir: PathBuf, dry_run: bool) -> Result<()> {
+    info!("Starting repair of {:?} (dry_run: {})", backup_dir, dry_run);
+
+    let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
+    let signing_key =
+        signing::load_optional(config_manager.get_config().signing_key_file.as_deref())?;
+    let report = BackupManager::repair_backup_dir(&backup_dir, dry_run, signing_key.as_ref())?;
+
+    if report.is_clean() {
+        println!("No problems found in {:?}", report.backup_dir);
+        return Ok(());
+    }
+
+    if !report.actions.is_empty() {
+        if report.dry_run {
+            println!("Repairs that would be applied to {:?}:", report.backup_dir);
+        } else {
+            println!("Repairs applied to {:?}:", report.backup_dir);
+        }
+        for action in &report.actions {
+            println!("  - {}", action);
+        }
+    }
+
+    if !report.unrecoverable.is_empty() {
+        println!("Unrecoverable problems (the next backup will be forced full):");
+        for problem in &report.unrecoverable {
+            println!("  - {}", problem);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use anyhow::{Context, Result};
+use indicatif::HumanBytes;
+use log::info;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::backup::BackupManager;
+use crate::config::ConfigManager;
+use crate::signing;
+
+pub async fn handle_replicate(
+    backup_dir: PathBuf,
+    dest: PathBuf,
+    lock_timeout: Option<u64>,
+) -> Result<()> {
+    info!("Replicating {:?} to {:?}", backup_dir, dest);
+
+    let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
+    let signing_key =
+        signing::load_optional(config_manager.get_config().signing_key_file.as_deref())?;
+    let result = BackupManager::replicate_backup_dir(
+        &backup_dir,
+        &dest,
+        signing_key.as_ref(),
+        lock_timeout.map(Duration::from_secs),
+    )?;
+
+    if result.copied.is_empty() {
+        println!(
+            "{:?} is up to date ({} points)",
+            dest, result.already_present
+        );
+    } else {
+        println!(
+            "Copied {} points ({}) to {:?}; {} were already there",
+            result.copied.len(),
+            HumanBytes(result.bytes_copied),
+            dest,
+            result.already_present
+        );
+    }
+    Ok(())
+}
@@ -1,14 +1,46 @@
-use anyhow::Result;
-use log::{error, info};
-use std::path::PathBuf;
+use anyhow::{Context, Result, bail};
+use chrono::Local;
+use console::{Key, Term, style};
+use indicatif::HumanBytes;
+use log::{error, info, warn};
+use std::path::{Path, PathBuf};
 
-use crate::restore::RestoreManager;
+use super::config_cmd::confirm;
+use crate::backup::BackupManager;
+use crate::config::{BackupHistoryEntry, ConfigManager, SourceConfig};
+use crate::logger;
+use crate::point_name;
+use crate::progress;
+use crate::restore::{BackupEntry, OverwritePolicy, RestoreManager, RestoreOptions};
+use crate::signing::SigningKey;
+
+/// Files of each kind listed before asking to go ahead (unbacked changes,
+/// restore preview); the rest are counted.
+const MAX_LISTED_FILES: usize = 10;
+
+/// Points the interactive picker shows at once; it scrolls past that.
+const PICKER_VISIBLE_ROWS: usize = 15;
+
+/// Where `restore` writes.
+pub enum RestoreTarget {
+    Dir(PathBuf),
+    /// Back into the source that owns the backup dir.
+    InPlace {
+        /// Back up the source's current state before restoring over it.
+        snapshot: bool,
+        /// Restore over unbacked changes without asking.
+        yes: bool,
+    },
+}
 
 pub async fn handle_restore(
     backup_dir: PathBuf,
-    target_dir: PathBuf,
-    point: Option<String>,
+    target: Option<RestoreTarget>,
+    mut point: Option<String>,
+    tag: Option<String>,
     list: bool,
+    interactive: bool,
+    options: RestoreOptions,
 ) -> Result<()> {
     if list {
         let backups = RestoreManager::list_backups(&backup_dir)?;
@@ -16,18 +48,82 @@ pub async fn handle_restore(
             println!("No backups found in {:?}", backup_dir);
             return Ok(());
         }
-        println!("Available backups in {:?}:", backup_dir);
+        let metadata = BackupManager::load_source_metadata(&backup_dir.join("metadata.json"));
+        match &tag {
+            Some(tag) => println!("Backups tagged '{}' in {:?}:", tag, backup_dir),
+            None => println!("Available backups in {:?}:", backup_dir),
+        }
         for backup in &backups {
             let backup_type = if backup.is_full { "FULL" } else { "INC " };
-            println!("  [{}] {} ({})", backup_type, backup.timestamp, backup.name);
+            let entry = metadata
+                .backup_history
+                .iter()
+                .find(|entry| entry.backup_name == backup.name);
+            if let Some(tag) = &tag
+                && !entry.is_some_and(|entry| entry.tags.contains(tag))
+            {
+                continue;
+            }
+            let mut markers = String::new();
+            if entry.is_some_and(|entry| entry.pinned) {
+                markers.push_str(" [pinned]");
+            }
+            if let Some(entry) = entry.filter(|entry| !entry.tags.is_empty()) {
+                markers.push_str(&format!(" tags: {}", entry.tags.join(", ")));
+            }
+            match entry.and_then(|entry| entry.context.as_deref()) {
+                Some(context) => println!(
+                    "  [{}] {} ({}){} context: {}",
+                    backup_type, backup.timestamp, backup.name, markers, context
+                ),
+                None => println!(
+                    "  [{}] {} ({}){}",
+                    backup_type, backup.timestamp, backup.name, markers
+                ),
+            }
+        }
+        return Ok(());
+    }
+    if let Some(tag) = &tag {
+        let tagged = tagged_point(&backup_dir, tag)?;
+        info!("Restoring {} (newest point tagged '{}')", tagged, tag);
+        point = point_name::parse(&tagged).map(|name| name.timestamp.to_string());
+    }
+
+    let Some(target) = target else {
+        bail!("Pass a target directory or --in-place");
+    };
+
+    if interactive {
+        let Some(picked) = pick_point(&backup_dir)? else {
+            println!("Restore cancelled");
+            return Ok(());
+        };
+        point = Some(picked);
+    }
+
+    let target_dir = match target {
+        RestoreTarget::Dir(target_dir) => target_dir,
+        RestoreTarget::InPlace { snapshot, yes } => {
+            let (target_dir, pinned) = prepare_in_place(&backup_dir, point, snapshot, yes).await?;
+            point = pinned;
+            target_dir
         }
+    };
+    if interactive && !confirm_preview(&backup_dir, &target_dir, point.as_deref(), &options)? {
+        println!("Restore cancelled");
         return Ok(());
     }
 
+    progress::enable_bars_if_interactive();
     info!("Starting restore from {:?} to {:?}", backup_dir, target_dir);
+    if let Some(mb_per_sec) = options.throttle_mb_per_sec {
+        info!("Restore throttled to {} MB/s", mb_per_sec);
+    }
 
     let point_ref = point.as_deref();
-    match RestoreManager::restore_to_point(&backup_dir, &target_dir, point_ref) {
+    match RestoreManager::restore_to_point(&backup_dir, &target_dir, point_ref, &options) {
+        Ok(_) if logger::is_quiet() => {}
         Ok(files_restored) => {
             println!(
                 "Restore completed: {} files restored to {:?}",
@@ -42,3 +138,286 @@ pub async fn handle_restore(
 
     Ok(())
 }
+
+/// Name of the newest point of `backup_dir` tagged `tag`.
+fn tagged_point(backup_dir: &Path, tag: &str) -> Result<String> {
+    let metadata = BackupManager::load_source_metadata(&backup_dir.join("metadata.json"));
+    metadata
+        .backup_history
+        .iter()
+        .filter(|entry| entry.tags.iter().any(|existing| existing == tag))
+        .filter_map(|entry| {
+            point_name::parse(&entry.backup_name).map(|name| (name.timestamp, &entry.backup_name))
+        })
+        .max()
+        .map(|(_, name)| name.clone())
+        .with_context(|| format!("No backup in {:?} is tagged '{}'", backup_dir, tag))
+}
+
+/// The backup dir of the configured source at `source_dir` that is best to
+/// restore from: a healthy chain with the newest point. Every backup dir's
+/// check is logged so the choice can be followed.
+pub fn pick_backup_dir(source_dir: &Path, signing_key: Option<&SigningKey>) -> Result<PathBuf> {
+    let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
+    let config = config_manager.get_config().localized();
+    let source_dir = std::path::absolute(source_dir).unwrap_or_else(|_| source_dir.to_path_buf());
+    let Some(source) = config
+        .sources
+        .iter()
+        .find(|source| source.source_dir == source_dir)
+    else {
+        bail!("{:?} is not a configured source", source_dir);
+    };
+
+    let ranked = RestoreManager::rank_backup_dirs(&source.effective_backup_dirs(), signing_key);
+    for health in &ranked {
+        let latest = health.latest_point.as_deref().unwrap_or("-");
+        match health.problem {
+            Some(ref problem) => warn!(
+                "  {:?}: latest point {}, {}",
+                health.backup_dir, latest, problem
+            ),
+            None => info!("  {:?}: latest point {}, ok", health.backup_dir, latest),
+        }
+    }
+    match ranked.first() {
+        Some(best) if best.problem.is_none() => {
+            info!(
+                "Restoring {:?} from {:?}",
+                source.source_dir, best.backup_dir
+            );
+            Ok(best.backup_dir.clone())
+        }
+        _ => bail!(
+            "No backup dir of {:?} can be restored from",
+            source.source_dir
+        ),
+    }
+}
+
+/// One line per point for the picker: type, when it was taken and, from
+/// the history in metadata.json, how much it wrote.
+fn describe_point(backup: &BackupEntry, entry: Option<&BackupHistoryEntry>) -> String {
+    let backup_type = if backup.is_full { "FULL" } else { "INC " };
+    let Some(entry) = entry else {
+        return format!("[{}] {}", backup_type, backup.timestamp);
+    };
+    let mut line = format!(
+        "[{}] {}  {:>7} files  {:>10}",
+        backup_type,
+        entry
+            .created_at
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S"),
+        entry.files_backed_up,
+        HumanBytes(entry.bytes_processed).to_string()
+    );
+    if let Some(ref context) = entry.context {
+        line.push_str(&format!("  {}", context));
+    }
+    line
+}
+
+/// Let the user choose a restore point of `backup_dir` with the arrow
+/// keys, starting at the latest. `None` when they back out.
+fn pick_point(backup_dir: &Path) -> Result<Option<String>> {
+    let backups = RestoreManager::list_backups(backup_dir)?;
+    if backups.is_empty() {
+        bail!("No backups found in {:?}", backup_dir);
+    }
+    let term = Term::stdout();
+    if !term.is_term() {
+        bail!("--interactive needs a terminal; pass --point instead");
+    }
+    let metadata = BackupManager::load_source_metadata(&backup_dir.join("metadata.json"));
+    let rows: Vec<String> = backups
+        .iter()
+        .map(|backup| {
+            let entry = metadata
+                .backup_history
+                .iter()
+                .find(|entry| entry.backup_name == backup.name);
+            describe_point(backup, entry)
+        })
+        .collect();
+
+    term.write_line(&format!(
+        "Restore points in {:?} (up/down to move, Enter to pick, q to cancel):",
+        backup_dir
+    ))?;
+    term.hide_cursor()?;
+    let picked = run_picker(&term, &rows);
+    term.show_cursor()?;
+    let Some(index) = picked? else {
+        return Ok(None);
+    };
+    term.write_line(&format!("Restore point: {}", rows[index]))?;
+    Ok(Some(backups[index].timestamp.clone()))
+}
+
+fn run_picker(term: &Term, rows: &[String]) -> Result<Option<usize>> {
+    let last = rows.len() - 1;
+    let visible = rows.len().min(PICKER_VISIBLE_ROWS);
+    let mut selected = last;
+    let mut offset = rows.len() - visible;
+    loop {
+        for (index, row) in rows.iter().enumerate().skip(offset).take(visible) {
+            if index == selected {
+                term.write_line(&format!(
+                    "{} {}",
+                    style(">").cyan().bold(),
+                    style(row).reverse()
+                ))?;
+            } else {
+                term.write_line(&format!("  {}", row))?;
+            }
+        }
+        let key = term.read_key()?;
+        term.clear_last_lines(visible)?;
+        match key {
+            Key::ArrowUp | Key::Char('k') => selected = selected.saturating_sub(1),
+            Key::ArrowDown | Key::Char('j') => selected = (selected + 1).min(last),
+            Key::PageUp => selected = selected.saturating_sub(visible),
+            Key::PageDown => selected = (selected + visible).min(last),
+            Key::Home => selected = 0,
+            Key::End => selected = last,
+            Key::Enter => return Ok(Some(selected)),
+            Key::Escape | Key::CtrlC | Key::Char('q') => return Ok(None),
+            _ => {}
+        }
+        if selected < offset {
+            offset = selected;
+        } else if selected >= offset + visible {
+            offset = selected + 1 - visible;
+        }
+    }
+}
+
+/// Show which points restoring `point` into `target_dir` applies and which
+/// files it writes, then ask to go ahead.
+fn confirm_preview(
+    backup_dir: &Path,
+    target_dir: &Path,
+    point: Option<&str>,
+    options: &RestoreOptions,
+) -> Result<bool> {
+    let preview = RestoreManager::preview_restore(backup_dir, target_dir, point)?;
+    println!(
+        "Restoring into {:?} applies {} point(s): {}",
+        target_dir,
+        preview.points.len(),
+        preview.points.join(", ")
+    );
+    print_files("new", &preview.new_files);
+    let existing = match options.overwrite {
+        OverwritePolicy::Always => "already there, replaced",
+        OverwritePolicy::Never => "already there, left alone",
+        OverwritePolicy::IfOlder => "already there, replaced if older",
+        OverwritePolicy::Prompt => "already there, asked about one by one",
+    };
+    print_files(existing, &preview.existing_files);
+    confirm("Start the restore?", false)
+}
+
+fn print_files(label: &str, files: &[PathBuf]) {
+    println!("  {} file(s) {}", files.len(), label);
+    for file in files.iter().take(MAX_LISTED_FILES) {
+        println!("    {}", file.display());
+    }
+    if files.len() > MAX_LISTED_FILES {
+        println!("    ... and {} more", files.len() - MAX_LISTED_FILES);
+    }
+}
+
+/// Find the source that owns `backup_dir` (from the config, else from the
+/// backup dir's reports), check it for changes the backups do not have and
+/// optionally back it up first. Returns the directory to restore into and
+/// the restore point, pinned to the latest one before the snapshot.
+async fn prepare_in_place(
+    backup_dir: &Path,
+    point: Option<String>,
+    snapshot: bool,
+    yes: bool,
+) -> Result<(PathBuf, Option<String>)> {
+    let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
+    let config = config_manager.get_config().localized();
+    let (source, resolved) = match config.source_for_backup_dir(backup_dir) {
+        Some((source, dir)) => (source.clone(), source.resolve_for(&config, &dir)),
+        None => {
+            let Some(source_dir) = BackupManager::reported_source_dir(backup_dir) else {
+                bail!(
+                    "Cannot tell which source {:?} belongs to; restore to a target dir instead",
+                    backup_dir
+                );
+            };
+            if snapshot {
+                bail!(
+                    "{:?} is not configured, so it cannot be snapshotted before the restore",
+                    source_dir
+                );
+            }
+            let source = SourceConfig {
+                source_dir: config.map_path(&source_dir),
+                ..Default::default()
+            };
+            let resolved = source.resolve(&config);
+            (source, resolved)
+        }
+    };
+    let (target_dir, _) = source.scan_base();
+    info!(
+        "Restoring {:?} in place to its source {:?}",
+        backup_dir, source.source_dir
+    );
+
+    let unbacked = BackupManager::unbacked_changes(&source, &resolved, backup_dir)?;
+    if !unbacked.is_empty() {
+        warn!(
+            "{} file(s) in {:?} changed since the last backup:",
+            unbacked.len(),
+            source.source_dir
+        );
+        for file in unbacked.iter().take(MAX_LISTED_FILES) {
+            warn!("  {}", file);
+        }
+        if unbacked.len() > MAX_LISTED_FILES {
+            warn!("  ... and {} more", unbacked.len() - MAX_LISTED_FILES);
+        }
+        if snapshot {
+            info!("They are kept in the pre-restore snapshot");
+        } else if !confirm("Restore over them and lose these changes?", yes)? {
+            bail!("Restore cancelled; pass --snapshot to back them up first");
+        }
+    }
+
+    if !snapshot {
+        return Ok((target_dir, point));
+    }
+    // The snapshot becomes the latest point, so "latest" must mean the one
+    // before it.
+    let point = match point {
+        Some(point) => Some(point),
+        None => RestoreManager::list_backups(backup_dir)?
+            .last()
+            .map(|backup| backup.timestamp.clone()),
+    };
+    info!(
+        "Backing up {:?} before restoring over it",
+        source.source_dir
+    );
+    let results = BackupManager::new(config.clone())
+        .with_history(config_manager.history_path())
+        .backup_source_dirs(std::slice::from_ref(&source.source_dir))
+        .await
+        .context("Pre-restore snapshot failed")?;
+    if results.is_empty() {
+        bail!(
+            "Pre-restore snapshot of {:?} did not run (is the source disabled?)",
+            source.source_dir
+        );
+    }
+    for result in &results {
+        info!("Pre-restore snapshot written to {:?}", result.backup_dir);
+    }
+    Ok((target_dir, point))
+}
@@ -1,20 +1,32 @@
 use anyhow::{Context, Result};
 use cron::Schedule;
 use log::{error, info, warn};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 use tokio::time::{MissedTickBehavior, sleep};
 
+use crate::api::{self, ApiState};
 use crate::backup::BackupManager;
+use crate::blackout;
 use crate::config::{self, ConfigManager};
+use crate::daemon;
+use crate::logger;
+use crate::metrics;
+use crate::notification;
+use crate::priority;
+use crate::progress;
+use crate::trigger::{BackupTrigger, TriggerQueue, TriggerReason};
+use crate::units;
 use crate::watcher::FileWatcher;
 
 struct RuntimeHandles {
     cron_tasks: Vec<JoinHandle<()>>,
     watcher_task: Option<JoinHandle<()>>,
+    forward_task: Option<JoinHandle<()>>,
 }
 
 impl RuntimeHandles {
@@ -25,19 +37,59 @@ impl RuntimeHandles {
         if let Some(task) = &self.watcher_task {
             task.abort();
         }
+        if let Some(task) = &self.forward_task {
+            task.abort();
+        }
         self.cron_tasks.clear();
         self.watcher_task = None;
+        self.forward_task = None;
     }
 }
 
+const CONFIG_RELOAD_DEBOUNCE: Duration = Duration::from_millis(500);
+const LOG_PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 fn config_fingerprint(config: &config::BackupConfig) -> Result<String> {
     serde_json::to_string(config).context("Failed to serialize config fingerprint")
 }
 
+/// Placeholder for a secret in the logged config snapshot.
+const REDACTED: &str = "***";
+
+/// `config` with its secrets replaced, since the snapshot goes to stdout,
+/// ardiex.log and through that to `GET /api/logs`.
+fn redacted_config(config: &config::BackupConfig) -> config::BackupConfig {
+    let mut config = config.clone();
+    if let Some(smtp) = config.notifications.smtp.as_mut()
+        && smtp.password.is_some()
+    {
+        smtp.password = Some(REDACTED.to_string());
+    }
+    if config.api_token.is_some() {
+        config.api_token = Some(REDACTED.to_string());
+    }
+    let notifications = &mut config.notifications;
+    for url in [
+        &mut notifications.webhook_url,
+        &mut notifications.slack_webhook_url,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        *url = notification::mask_webhook_url(url);
+    }
+    for source in &mut config.sources {
+        if let Some(url) = source.healthcheck_url.as_mut() {
+            *url = notification::mask_webhook_url(url);
+        }
+    }
+    config
+}
+
 fn config_snapshot_pretty_json(config: &config::BackupConfig, phase: &str) -> String {
     let snapshot = serde_json::json!({
         "phase": phase,
-        "config": config
+        "config": redacted_config(config)
     });
 
     serde_json::to_string_pretty(&snapshot)
@@ -54,7 +106,8 @@ fn print_config_snapshot(config: &config::BackupConfig, phase: &str) {
     println!("[CONFIG] {}", pretty);
 }
 
-fn collect_event_watch_paths(config: &config::BackupConfig) -> Vec<PathBuf> {
+/// Paths to watch per event-driven source, keyed by its `source_dir`.
+fn collect_event_watch_paths(config: &config::BackupConfig) -> Vec<(PathBuf, Vec<PathBuf>)> {
     if !config.enable_event_driven {
         return Vec::new();
     }
@@ -63,14 +116,18 @@ fn collect_event_watch_paths(config: &config::BackupConfig) -> Vec<PathBuf> {
         .sources
         .iter()
         .filter(|s| s.enabled)
-        .filter_map(|s| {
-            let resolved = s.resolve(config);
-            if resolved.enable_event_driven {
-                Some(s.source_dir.clone())
-            } else {
-                None
-            }
-        })
+        .filter(|s| s.resolve(config).enable_event_driven)
+        .map(|s| (s.source_dir.clone(), s.scan_roots()))
+        .collect()
+}
+
+/// Enabled sources that want a backup as soon as the service starts.
+fn startup_backup_sources(config: &config::BackupConfig) -> Vec<PathBuf> {
+    config
+        .sources
+        .iter()
+        .filter(|s| s.enabled && s.backup_on_start)
+        .map(|s| s.source_dir.clone())
         .collect()
 }
 
@@ -83,9 +140,233 @@ fn should_skip_hot_reload(
         || failed_reload_fingerprint == Some(latest_fingerprint)
 }
 
+/// Random delay in `0..=max`.
+fn random_jitter(max: Duration) -> Duration {
+    use std::hash::{BuildHasher, Hasher};
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    // A freshly keyed hasher is random enough to spread triggers out.
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos()),
+    );
+    Duration::from_millis(hasher.finish() % (max.as_millis() as u64 + 1))
+}
+
+/// Local time at which the blackout window covering now ends, if any.
+fn active_blackout(config: &config::BackupConfig) -> Option<chrono::DateTime<chrono::Local>> {
+    let until =
+        blackout::blackout_until(&config.blackout_windows, chrono::Local::now().naive_local())?;
+    // A time skipped by a DST change falls back to an hour later.
+    until
+        .and_local_timezone(chrono::Local)
+        .earliest()
+        .or_else(|| {
+            (until + chrono::Duration::hours(1))
+                .and_local_timezone(chrono::Local)
+                .earliest()
+        })
+}
+
+/// `trigger` sent again once the blackout ending at `until` is over.
+fn spawn_deferred_trigger(
+    until: chrono::DateTime<chrono::Local>,
+    trigger: BackupTrigger,
+    backup_tx: mpsc::Sender<BackupTrigger>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let wait = (until - chrono::Local::now()).to_std().unwrap_or_default();
+        sleep(wait).await;
+        info!(
+            "[BLACKOUT] Window ended, running deferred backup of {:?}",
+            trigger.source_dir
+        );
+        if let Err(e) = backup_tx.send(trigger).await {
+            error!("Failed to send deferred backup trigger: {}", e);
+        }
+    })
+}
+
+/// Cron task sending a backup trigger for `source_dir` at every occurrence
+/// of `schedule`, delayed by a random `jitter` and spaced by the source's
+/// min interval when enabled.
+fn spawn_cron_task(
+    source_dir: PathBuf,
+    schedule: Schedule,
+    jitter: Duration,
+    enable_min_interval: bool,
+    backup_tx: mpsc::Sender<BackupTrigger>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        // Calculate min interval based on source size
+        let min_interval = if enable_min_interval {
+            let interval = BackupManager::calculate_min_interval_by_size(&source_dir);
+            info!(
+                "Source {:?}: min interval by size = {}s",
+                source_dir,
+                interval.as_secs()
+            );
+            interval
+        } else {
+            Duration::from_secs(0)
+        };
+
+        let mut last_backup_time: Option<std::time::Instant> = None;
+
+        loop {
+            let now = chrono::Utc::now();
+            if let Some(next) = schedule.upcoming(chrono::Utc).next() {
+                let wait_duration = (next - now).to_std().unwrap_or(Duration::from_secs(60));
+                sleep(wait_duration + random_jitter(jitter)).await;
+
+                // Enforce minimum interval
+                if let Some(last) = last_backup_time {
+                    let elapsed = last.elapsed();
+                    if elapsed < min_interval {
+                        let remaining = min_interval - elapsed;
+                        info!(
+                            "Source {:?}: min interval not reached, waiting {}s more",
+                            source_dir,
+                            remaining.as_secs()
+                        );
+                        sleep(remaining).await;
+                    }
+                }
+
+                info!("Cron triggered backup for source: {:?}", source_dir);
+                let trigger = BackupTrigger::new(source_dir.clone(), TriggerReason::Cron);
+                if let Err(e) = backup_tx.send(trigger).await {
+                    error!("Failed to send cron backup trigger: {}", e);
+                    break;
+                }
+                last_backup_time = Some(std::time::Instant::now());
+            } else {
+                sleep(Duration::from_secs(60)).await;
+            }
+        }
+    })
+}
+
+/// Cron task checking the backup dirs of a source at every occurrence of
+/// its `verify_schedule`, and alerting on the problems found so they are
+/// fixed before a restore needs the points. With several backup dirs they
+/// are scrubbed against each other first, repairing corrupted copies.
+fn spawn_verify_task(
+    source_dir: PathBuf,
+    targets: Vec<(PathBuf, config::ResolvedSourceConfig)>,
+    schedule: Schedule,
+    notifications: config::NotificationConfig,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let Some(next) = schedule.upcoming(chrono::Utc).next() else {
+                sleep(Duration::from_secs(60)).await;
+                continue;
+            };
+            let wait = (next - chrono::Utc::now())
+                .to_std()
+                .unwrap_or(Duration::from_secs(60));
+            sleep(wait).await;
+
+            if targets.len() > 1 {
+                let backup_dirs: Vec<PathBuf> =
+                    targets.iter().map(|(dir, _)| dir.clone()).collect();
+                // The throttle is set per source, not per destination.
+                let io_throttle_mbps = targets[0].1.io_throttle_mbps;
+                scrub_backup_dirs(&backup_dirs, io_throttle_mbps, &notifications).await;
+            }
+            info!("[VERIFY] Checking backups of {:?}", source_dir);
+            for (backup_dir, resolved) in &targets {
+                let (dir, resolved) = (backup_dir.clone(), resolved.clone());
+                let outcome = tokio::task::spawn_blocking(move || {
+                    let signing_key =
+                        crate::signing::load_optional(resolved.signing_key_file.as_deref())?;
+                    BackupManager::verify_backup_dir(
+                        &dir,
+                        signing_key.as_ref(),
+                        resolved.io_throttle_mbps,
+                    )
+                })
+                .await;
+                let report = match outcome {
+                    Ok(Ok(report)) => report,
+                    Ok(Err(e)) => {
+                        warn!("[VERIFY] Could not check {:?}: {:#}", backup_dir, e);
+                        notification::notify_verification(
+                            &notifications,
+                            backup_dir,
+                            &[format!("could not be checked: {:#}", e)],
+                        )
+                        .await;
+                        continue;
+                    }
+                    Err(e) => {
+                        error!("[VERIFY] Task join error: {}", e);
+                        continue;
+                    }
+                };
+                let problems = report.error_lines();
+                if problems.is_empty() {
+                    info!(
+                        "[VERIFY] {:?}: {} backup point(s) OK",
+                        backup_dir, report.backup_points
+                    );
+                    continue;
+                }
+                for problem in &problems {
+                    warn!("[VERIFY] {:?}: {}", backup_dir, problem);
+                }
+                notification::notify_verification(&notifications, backup_dir, &problems).await;
+            }
+        }
+    })
+}
+
+/// Scrub step of the verify task: repairs are logged by the scrub itself,
+/// copies left corrupted are alerted on per backup dir.
+async fn scrub_backup_dirs(
+    backup_dirs: &[PathBuf],
+    io_throttle_mbps: Option<f64>,
+    notifications: &config::NotificationConfig,
+) {
+    let dirs = backup_dirs.to_vec();
+    let outcome = tokio::task::spawn_blocking(move || {
+        BackupManager::scrub_backup_dirs(&dirs, false, io_throttle_mbps)
+    })
+    .await;
+    let report = match outcome {
+        Ok(Ok(report)) => report,
+        Ok(Err(e)) => {
+            warn!("[SCRUB] Skipped: {:#}", e);
+            return;
+        }
+        Err(e) => {
+            error!("[SCRUB] Task join error: {}", e);
+            return;
+        }
+    };
+    for backup_dir in backup_dirs {
+        let problems: Vec<String> = report
+            .findings
+            .iter()
+            .filter(|finding| !finding.repaired && finding.backup_dir == *backup_dir)
+            .map(|finding| {
+                format!(
+                    "scrub: {} is corrupted and no other backup dir has an intact copy",
+                    finding.file
+                )
+            })
+            .collect();
+        notification::notify_verification(notifications, backup_dir, &problems).await;
+    }
+}
+
 fn spawn_runtime_handles(
     config: &config::BackupConfig,
-    backup_tx: mpsc::Sender<()>,
+    backup_tx: mpsc::Sender<BackupTrigger>,
 ) -> Result<RuntimeHandles> {
     // Cron-based scheduler: spawn one task per source with its own schedule
     let mut cron_tasks = Vec::new();
@@ -102,66 +383,61 @@ fn spawn_runtime_handles(
                 );
                 continue;
             }
-            let cron_expr = resolved.cron_schedule.clone();
-            let source_dir = source.source_dir.clone();
-            let backup_tx = backup_tx.clone();
-            let enable_min_interval = config.enable_min_interval_by_size;
-
-            let schedule = Schedule::from_str(&cron_expr)
-                .map_err(|e| anyhow::anyhow!("Invalid cron for {:?}: {}", source_dir, e))?;
-
-            let task = tokio::spawn(async move {
-                // Calculate min interval based on source size
-                let min_interval = if enable_min_interval {
-                    let interval = BackupManager::calculate_min_interval_by_size(&source_dir);
-                    info!(
-                        "Source {:?}: min interval by size = {}s",
-                        source_dir,
-                        interval.as_secs()
-                    );
-                    interval
-                } else {
-                    Duration::from_secs(0)
+            let schedule = Schedule::from_str(&resolved.cron_schedule)
+                .map_err(|e| anyhow::anyhow!("Invalid cron for {:?}: {}", source.source_dir, e))?;
+            let jitter = Duration::from_secs(resolved.cron_jitter_secs);
+            cron_tasks.push(spawn_cron_task(
+                source.source_dir.clone(),
+                schedule,
+                jitter,
+                config.enable_min_interval_by_size,
+                backup_tx.clone(),
+            ));
+            // Destinations on their own schedule need a trigger of their
+            // own; the backup skips them on every other trigger.
+            for destination in &source.backup_dirs {
+                let Some(ref cron_expr) = destination.cron_schedule else {
+                    continue;
                 };
-
-                let mut last_backup_time: Option<std::time::Instant> = None;
-
-                loop {
-                    let now = chrono::Utc::now();
-                    if let Some(next) = schedule.upcoming(chrono::Utc).next() {
-                        let wait_duration =
-                            (next - now).to_std().unwrap_or(Duration::from_secs(60));
-                        sleep(wait_duration).await;
-
-                        // Enforce minimum interval
-                        if let Some(last) = last_backup_time {
-                            let elapsed = last.elapsed();
-                            if elapsed < min_interval {
-                                let remaining = min_interval - elapsed;
-                                info!(
-                                    "Source {:?}: min interval not reached, waiting {}s more",
-                                    source_dir,
-                                    remaining.as_secs()
-                                );
-                                sleep(remaining).await;
-                            }
-                        }
-
-                        info!("Cron triggered backup for source: {:?}", source_dir);
-                        if let Err(e) = backup_tx.send(()).await {
-                            error!("Failed to send cron backup trigger: {}", e);
-                            break;
-                        }
-                        last_backup_time = Some(std::time::Instant::now());
-                    } else {
-                        sleep(Duration::from_secs(60)).await;
-                    }
-                }
-            });
-            cron_tasks.push(task);
+                let schedule = Schedule::from_str(cron_expr).map_err(|e| {
+                    anyhow::anyhow!("Invalid cron for {:?}: {}", destination.path, e)
+                })?;
+                cron_tasks.push(spawn_cron_task(
+                    source.source_dir.clone(),
+                    schedule,
+                    jitter,
+                    config.enable_min_interval_by_size,
+                    backup_tx.clone(),
+                ));
+            }
         }
     }
 
+    // Verification runs even where periodic backups are off: event-driven
+    // backups corrupt as easily.
+    for source in config.sources.iter().filter(|s| s.enabled) {
+        let Some(ref cron_expr) = source.verify_schedule else {
+            continue;
+        };
+        let schedule = Schedule::from_str(cron_expr).map_err(|e| {
+            anyhow::anyhow!("Invalid verify_schedule for {:?}: {}", source.source_dir, e)
+        })?;
+        let targets = source
+            .effective_backup_dirs()
+            .into_iter()
+            .map(|dir| {
+                let resolved = source.resolve_for(config, &dir);
+                (dir, resolved)
+            })
+            .collect();
+        cron_tasks.push(spawn_verify_task(
+            source.source_dir.clone(),
+            targets,
+            schedule,
+            config.notifications.clone(),
+        ));
+    }
+
     let watch_paths = collect_event_watch_paths(config);
     let watcher_task = if watch_paths.is_empty() {
         if config.enable_event_driven {
@@ -170,40 +446,320 @@ fn spawn_runtime_handles(
         None
     } else {
         Some(tokio::task::spawn_blocking(move || {
-            match FileWatcher::new(watch_paths, backup_tx.clone(), Duration::from_millis(300)) {
-                Ok(_watcher) => {
-                    info!("File watcher started");
-                    // Keep _watcher alive — dropping it stops file watching
-                    loop {
-                        std::thread::sleep(Duration::from_secs(1));
+            // One watcher per source so each change triggers only its source.
+            let mut watchers = Vec::new();
+            for (source_dir, paths) in watch_paths {
+                let trigger_source = source_dir.clone();
+                match FileWatcher::with_changes(
+                    paths,
+                    backup_tx.clone(),
+                    move |changed| BackupTrigger::file_change(trigger_source.clone(), changed),
+                    Duration::from_millis(300),
+                ) {
+                    Ok(watcher) => watchers.push(watcher),
+                    Err(e) => {
+                        error!("Failed to start file watcher for {:?}: {}", source_dir, e);
                     }
                 }
-                Err(e) => {
-                    error!("Failed to start file watcher: {}", e);
-                }
+            }
+            if watchers.is_empty() {
+                return;
+            }
+            info!("File watcher started");
+            // Keep the watchers alive — dropping them stops file watching
+            loop {
+                std::thread::sleep(Duration::from_secs(1));
             }
         }))
     };
 
+    let forward_task = spawn_forward_task(config);
+
     Ok(RuntimeHandles {
         cron_tasks,
         watcher_task,
+        forward_task,
     })
 }
 
-pub async fn handle_run() -> Result<()> {
+/// (local backup dir, forward dir, resolved config) for every enabled source
+/// with a `forward_dir`. Backups are forwarded from the first backup dir.
+fn collect_forward_targets(
+    config: &config::BackupConfig,
+) -> Vec<(PathBuf, PathBuf, config::ResolvedSourceConfig)> {
+    config
+        .sources
+        .iter()
+        .filter(|s| s.enabled)
+        .filter_map(|s| {
+            let forward_dir = s.forward_dir.clone()?;
+            let local_dir = s.effective_backup_dirs().into_iter().next()?;
+            let resolved = s.resolve_for(config, &local_dir);
+            Some((local_dir, forward_dir, resolved))
+        })
+        .collect()
+}
+
+fn spawn_forward_task(config: &config::BackupConfig) -> Option<JoinHandle<()>> {
+    let targets = collect_forward_targets(config);
+    if targets.is_empty() {
+        return None;
+    }
+    let interval = Duration::from_secs(config.forward_interval_secs);
+    let alert_after = chrono::Duration::hours(config.forward_alert_after_hours as i64);
+
+    Some(tokio::spawn(async move {
+        let mut tick = tokio::time::interval(interval);
+        tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            tick.tick().await;
+            for (local_dir, forward_dir, resolved) in &targets {
+                let (local_dir, forward_dir, resolved) =
+                    (local_dir.clone(), forward_dir.clone(), resolved.clone());
+                let outcome = tokio::task::spawn_blocking(move || {
+                    let result =
+                        BackupManager::forward_queued_backups(&local_dir, &forward_dir, &resolved);
+                    (local_dir, forward_dir, result)
+                })
+                .await;
+                match outcome {
+                    Ok((local_dir, forward_dir, Ok(result))) => {
+                        metrics::set_forward_queue_depth(&local_dir, result.queued);
+                        if !result.reachable && result.queued > 0 {
+                            info!(
+                                "[FORWARD] {:?} unreachable, {} backup(s) queued from {:?}",
+                                forward_dir, result.queued, local_dir
+                            );
+                        }
+                        if result.queue_exceeds(alert_after) {
+                            warn!(
+                                "[FORWARD] {} backup(s) from {:?} not forwarded to {:?}; oldest queued since {}",
+                                result.queued,
+                                local_dir,
+                                forward_dir,
+                                result
+                                    .oldest_queued_at
+                                    .map(|t| t.to_rfc3339())
+                                    .unwrap_or_default()
+                            );
+                        }
+                    }
+                    Ok((_, forward_dir, Err(e))) => {
+                        error!("[FORWARD] Forwarding to {:?} failed: {}", forward_dir, e)
+                    }
+                    Err(e) => error!("[FORWARD] Task join error: {}", e),
+                }
+            }
+        }
+    }))
+}
+
+/// Start the service again as a detached process and return right away.
+fn start_daemon(config_manager: &ConfigManager, log_dir: Option<&Path>) -> Result<()> {
+    let pid_path = config_manager.pid_path();
+    if let Some(pid) = daemon::running_pid(&pid_path)? {
+        anyhow::bail!(
+            "The backup service is already running (pid {}, {:?})",
+            pid,
+            pid_path
+        );
+    }
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let pid = daemon::spawn_detached(&daemon::daemon_args(&args), log_dir)?;
+    info!("Backup service started in the background (pid {})", pid);
+    println!(
+        "Backup service started in the background (pid {}); stop it with `ardiex stop`",
+        pid
+    );
+    Ok(())
+}
+
+/// Lower the service to `worker_nice`/`worker_ionice_idle`. Failing only
+/// leaves the normal priority, so it is logged rather than fatal.
+fn apply_worker_priority(config: &config::BackupConfig) {
+    match priority::lower_process_priority(config.worker_nice, config.worker_ionice_idle) {
+        Ok(()) if config.worker_nice.is_some() || config.worker_ionice_idle => info!(
+            "Lowered service priority (nice: {:?}, idle I/O: {})",
+            config.worker_nice, config.worker_ionice_idle
+        ),
+        Ok(()) => {}
+        Err(e) => warn!("Failed to lower service priority: {:#}", e),
+    }
+}
+
+/// Turn `per_source_logs` on or off: each source's backup activity also
+/// goes to `logs/sources/<source path>.log`. Like a missing main log file,
+/// failing here only costs the extra files.
+fn apply_source_logs(config: &config::BackupConfig, log_dir: Option<&Path>) {
+    let dir = match log_dir {
+        Some(log_dir) if config.per_source_logs => Some(log_dir.join(logger::SOURCE_LOG_DIR)),
+        _ => None,
+    };
+    let enabled = dir.is_some();
+    match logger::set_source_log_dir(
+        dir,
+        config.max_log_file_size_mb,
+        config.max_rotated_log_files,
+    ) {
+        Ok(()) if enabled => info!("Per-source logs enabled"),
+        Ok(()) => {}
+        Err(e) => warn!("Failed to set up per-source logs: {:#}", e),
+    }
+}
+
+/// Hourly log retention for the service: rotated files beyond
+/// `max_rotated_log_files` per log, then the oldest ones while the log
+/// directory exceeds `max_log_dir_size`. The first pass runs right away.
+fn spawn_log_prune_task(
+    config: &config::BackupConfig,
+    log_dir: Option<&Path>,
+) -> Option<JoinHandle<()>> {
+    let log_dir = log_dir?.to_path_buf();
+    let max_files = config.max_rotated_log_files;
+    let max_total_bytes = config.max_log_dir_size;
+    Some(tokio::spawn(async move {
+        let mut tick = tokio::time::interval(LOG_PRUNE_INTERVAL);
+        tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            tick.tick().await;
+            match logger::prune_logs(&log_dir, max_files, max_total_bytes, false) {
+                Ok(report) if !report.files.is_empty() => info!(
+                    "Pruned {} old log file(s), freed {}",
+                    report.files.len(),
+                    units::format_size(report.bytes)
+                ),
+                Ok(_) => {}
+                Err(e) => warn!("Failed to prune old log files: {:#}", e),
+            }
+        }
+    }))
+}
+
+/// One scheduled pass: back up what is due, then exit.
+async fn run_once(config_manager: &ConfigManager, log_dir: Option<&Path>) -> Result<()> {
+    let config = config_manager.get_config().localized();
+    let mut backup_manager = BackupManager::new(config.clone())
+        .with_history(config_manager.history_path())
+        .only_due();
+    if let Err(e) = backup_manager.validate_all_sources() {
+        notification::notify_failures_by_email(
+            &config.notifications,
+            "pre-flight validation",
+            &[format!("{:#}", e)],
+        )
+        .await;
+        return Err(e);
+    }
+    apply_worker_priority(&config);
+    apply_source_logs(&config, log_dir);
+
+    if let Some(until) = active_blackout(&config) {
+        println!(
+            "Blackout window active until {}; no backup was run",
+            until.format("%Y-%m-%d %H:%M")
+        );
+        return Ok(());
+    }
+    info!("Starting one scheduled backup pass");
+    let results = backup_manager.backup_all_sources().await?;
+    if results.is_empty() {
+        println!("No backup was due");
+    }
+    for result in results {
+        println!(
+            "Backup completed: {} files to {:?} ({:.2} MB in {} ms)",
+            result.files_backed_up,
+            result.backup_dir,
+            result.bytes_processed as f64 / 1024.0 / 1024.0,
+            result.duration_ms
+        );
+    }
+    Ok(())
+}
+
+/// Resolves when the service is asked to stop: Ctrl+C, or SIGTERM from
+/// `ardiex stop` / systemd on Unix.
+async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => "Ctrl+C",
+                _ = sigterm.recv() => "SIGTERM",
+            },
+            Err(e) => {
+                warn!("Failed to listen for SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+                "Ctrl+C"
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl+C"
+    }
+}
+
+pub async fn handle_run(once: bool, daemonize: bool, log_dir: Option<PathBuf>) -> Result<()> {
     let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
-    let mut active_config = config_manager.get_config().clone();
+    if daemonize {
+        return start_daemon(&config_manager, log_dir.as_deref());
+    }
+    if once {
+        return run_once(&config_manager, log_dir.as_deref()).await;
+    }
+    let _pid_file = daemon::PidFile::create(&config_manager.pid_path())?;
+    let mut active_config = config_manager.get_config().localized();
     let mut active_fingerprint = config_fingerprint(&active_config)?;
     let mut failed_reload_fingerprint: Option<String> = None;
 
-    let (backup_tx, mut backup_rx) = mpsc::channel::<()>(100);
+    let (backup_tx, mut backup_rx) = mpsc::channel::<BackupTrigger>(100);
 
-    let mut backup_manager = BackupManager::new(active_config.clone());
-    backup_manager.validate_all_sources()?;
+    let history_path = config_manager.history_path();
+    let mut backup_manager = BackupManager::new(active_config.clone())
+        .with_history(history_path.clone())
+        .follow_destination_schedules();
+    if let Err(e) = backup_manager.validate_all_sources() {
+        notification::notify_failures_by_email(
+            &active_config.notifications,
+            "pre-flight validation",
+            &[format!("{:#}", e)],
+        )
+        .await;
+        return Err(e);
+    }
+    apply_worker_priority(&active_config);
+    apply_source_logs(&active_config, log_dir.as_deref());
     log_config_snapshot(&active_config, "startup");
     print_config_snapshot(&active_config, "startup");
+    // The exporter outlives hot reloads; only its address requires a restart.
+    let metrics_task = match active_config.metrics_port {
+        Some(port) => Some(metrics::spawn_server(
+            metrics::bind(active_config.metrics_address, port).await?,
+        )),
+        None => None,
+    };
+    let mut api_state = ApiState::new(
+        active_config.clone(),
+        history_path.clone(),
+        backup_tx.clone(),
+    );
+    if let Some(ref log_dir) = log_dir {
+        api_state = api_state.with_log_file(log_dir.join(logger::DEFAULT_LOG_FILE_NAME));
+    }
+    let api_task = match active_config.api_bind {
+        Some(ref addr) => {
+            let listener = api::bind(addr).await?;
+            let progress_state = api_state.clone();
+            progress::set_progress_callback(move |update| progress_state.record_progress(update));
+            Some(api::spawn_server(listener, api_state.clone()))
+        }
+        None => None,
+    };
     let mut runtime_handles = spawn_runtime_handles(&active_config, backup_tx.clone())?;
+    let mut log_prune_task = spawn_log_prune_task(&active_config, log_dir.as_deref());
 
     info!(
         "Ardiex backup service started (mode: {:?}, cron: {}, min_interval_by_size: {})",
@@ -212,27 +768,137 @@ pub async fn handle_run() -> Result<()> {
         active_config.enable_min_interval_by_size
     );
 
-    let mut reload_tick = tokio::time::interval(Duration::from_secs(2));
-    reload_tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    for source_dir in startup_backup_sources(&active_config) {
+        info!("backup_on_start: queueing a backup for {:?}", source_dir);
+        backup_tx
+            .send(BackupTrigger::new(source_dir, TriggerReason::Startup))
+            .await
+            .context("Failed to queue the startup backup")?;
+    }
+
+    // Editors write the settings in several steps; reload once they settle.
+    let (reload_tx, mut reload_rx) = mpsc::channel::<()>(8);
+    let config_path = std::path::absolute(&config_manager.config_path)
+        .unwrap_or_else(|_| config_manager.config_path.clone());
+    let _config_watcher =
+        FileWatcher::new(vec![config_path], reload_tx, (), CONFIG_RELOAD_DEBOUNCE)
+            .context("Failed to watch the settings file for hot reload")?;
+
+    // Triggers during a blackout collapse into one run per source when it ends.
+    let mut deferred_triggers: HashMap<PathBuf, JoinHandle<()>> = HashMap::new();
+    // Triggers arriving while a backup runs wait here, one per source.
+    let mut trigger_queue = TriggerQueue::default();
+
+    // Created once so a SIGTERM arriving mid-backup is not lost.
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
 
     loop {
         tokio::select! {
             maybe_trigger = backup_rx.recv() => {
-                if maybe_trigger.is_none() {
+                let Some(trigger) = maybe_trigger else {
                     warn!("Backup trigger channel closed, shutting down");
                     break;
+                };
+                trigger_queue.push(trigger);
+                // Take the rest of a burst too, so duplicates coalesce.
+                while let Ok(trigger) = backup_rx.try_recv() {
+                    trigger_queue.push(trigger);
                 }
+                api_state.set_queued(&trigger_queue.pending());
+            }
+            () = std::future::ready(()), if !trigger_queue.is_empty() => {
+                let blackout = active_blackout(&active_config);
+                let batch_size = if blackout.is_some() {
+                    usize::MAX
+                } else {
+                    active_config.max_concurrent_backups
+                };
+
+                let mut source_dirs = Vec::new();
+                let batch = trigger_queue.pop_batch(batch_size);
+                api_state.set_queued(&trigger_queue.pending());
+                for trigger in batch {
+                    // Queued before a hot reload removed or disabled the source.
+                    if !active_config
+                        .sources
+                        .iter()
+                        .any(|s| s.enabled && s.source_dir == trigger.source_dir)
+                    {
+                        warn!(
+                            "Ignoring {} trigger for {:?}: no longer an enabled source",
+                            trigger.reason, trigger.source_dir
+                        );
+                        continue;
+                    }
 
-                info!("Backup triggered");
-                match backup_manager.backup_all_sources().await {
+                    if let Some(until) = blackout {
+                        let pending = deferred_triggers
+                            .get(&trigger.source_dir)
+                            .is_some_and(|task| !task.is_finished());
+                        if !pending {
+                            info!(
+                                "[BLACKOUT] {} backup of {:?} deferred until {}",
+                                trigger.reason,
+                                trigger.source_dir,
+                                until.format("%Y-%m-%d %H:%M")
+                            );
+                            // Later triggers are dropped while this one waits,
+                            // so it rescans the whole source.
+                            let source_dir = trigger.source_dir.clone();
+                            let trigger = BackupTrigger::new(source_dir.clone(), trigger.reason);
+                            let task = spawn_deferred_trigger(until, trigger, backup_tx.clone());
+                            deferred_triggers.insert(source_dir, task);
+                        }
+                        continue;
+                    }
+
+                    info!(
+                        "Backup triggered for {:?} ({})",
+                        trigger.source_dir, trigger.reason
+                    );
+                    if let Some(changed) = trigger.changed_paths {
+                        backup_manager.note_changed_paths(&trigger.source_dir, changed);
+                    }
+                    source_dirs.push(trigger.source_dir);
+                }
+                if source_dirs.is_empty() {
+                    continue;
+                }
+
+                api_state.set_running(&source_dirs);
+                let backups = backup_manager.backup_source_dirs(&source_dirs);
+                tokio::pin!(backups);
+                // Keep taking triggers meanwhile so they coalesce and show
+                // up as queued.
+                let outcome = loop {
+                    tokio::select! {
+                        outcome = &mut backups => break outcome,
+                        Some(trigger) = backup_rx.recv() => {
+                            trigger_queue.push(trigger);
+                            api_state.set_queued(&trigger_queue.pending());
+                        }
+                    }
+                };
+                api_state.set_running(&[]);
+                match outcome {
                     Ok(results) => {
                         for result in results {
                             info!(
-                                "Backup completed: {} files to {:?} ({:.2} MB)",
+                                "Backup completed: {} files to {:?} ({:.2} MB; {})",
                                 result.files_backed_up,
                                 result.backup_dir,
-                                result.bytes_processed as f64 / 1024.0 / 1024.0
+                                result.bytes_processed as f64 / 1024.0 / 1024.0,
+                                result.resource_usage.summary()
                             );
+                            if !result.skipped_files.is_empty() {
+                                warn!(
+                                    "Skipped {} locked file(s) in {:?}: {}",
+                                    result.skipped_files.len(),
+                                    result.backup_dir,
+                                    result.skipped_files.join(", ")
+                                );
+                            }
                         }
                     }
                     Err(e) => {
@@ -240,9 +906,9 @@ pub async fn handle_run() -> Result<()> {
                     }
                 }
             }
-            _ = reload_tick.tick() => {
+            Some(()) = reload_rx.recv() => {
                 let latest = match ConfigManager::load_or_create() {
-                    Ok(manager) => manager.get_config().clone(),
+                    Ok(manager) => manager.get_config().localized(),
                     Err(e) => {
                         error!("Failed to reload configuration file: {}", e);
                         continue;
@@ -266,12 +932,21 @@ pub async fn handle_run() -> Result<()> {
                 }
 
                 info!(
-                    "[HOT-RELOAD] Detected settings.json change, attempting to apply new configuration"
+                    "[HOT-RELOAD] Detected settings file change, attempting to apply new configuration"
                 );
 
-                let mut new_backup_manager = BackupManager::new(latest.clone());
+                let mut new_backup_manager =
+                    BackupManager::new(latest.clone())
+                    .with_history(history_path.clone())
+                    .follow_destination_schedules();
                 if let Err(e) = new_backup_manager.validate_all_sources() {
                     error!("[HOT-RELOAD] Rejected invalid configuration: {}", e);
+                    notification::notify_failures_by_email(
+                        &latest.notifications,
+                        "pre-flight validation (hot-reload)",
+                        &[format!("{:#}", e)],
+                    )
+                    .await;
                     failed_reload_fingerprint = Some(latest_fingerprint);
                     continue;
                 }
@@ -288,6 +963,35 @@ pub async fn handle_run() -> Result<()> {
                     }
                 };
 
+                if latest.metrics_port != active_config.metrics_port
+                    || latest.metrics_address != active_config.metrics_address
+                {
+                    warn!("[HOT-RELOAD] metrics_port/metrics_address change takes effect after restarting the service");
+                }
+                if latest.api_bind != active_config.api_bind {
+                    warn!("[HOT-RELOAD] api_bind change takes effect after restarting the service");
+                }
+                api_state.set_config(latest.clone());
+                // A lowered priority cannot be raised again without privileges.
+                if latest.worker_nice != active_config.worker_nice
+                    || latest.worker_ionice_idle != active_config.worker_ionice_idle
+                {
+                    warn!("[HOT-RELOAD] worker_nice/worker_ionice_idle changes take effect after restarting the service");
+                }
+                if latest.per_source_logs != active_config.per_source_logs
+                    || latest.max_log_file_size_mb != active_config.max_log_file_size_mb
+                    || latest.max_rotated_log_files != active_config.max_rotated_log_files
+                {
+                    apply_source_logs(&latest, log_dir.as_deref());
+                }
+                if latest.max_rotated_log_files != active_config.max_rotated_log_files
+                    || latest.max_log_dir_size != active_config.max_log_dir_size
+                {
+                    if let Some(task) = log_prune_task.take() {
+                        task.abort();
+                    }
+                    log_prune_task = spawn_log_prune_task(&latest, log_dir.as_deref());
+                }
                 runtime_handles.abort_all();
                 runtime_handles = new_runtime_handles;
                 backup_manager = new_backup_manager;
@@ -302,14 +1006,26 @@ pub async fn handle_run() -> Result<()> {
                     active_config.backup_mode, active_config.cron_schedule, active_config.enable_min_interval_by_size
                 );
             }
-            _ = tokio::signal::ctrl_c() => {
-                info!("Received Ctrl+C, shutting down");
+            signal = &mut shutdown => {
+                info!("Received {}, shutting down", signal);
                 break;
             }
         }
     }
 
     runtime_handles.abort_all();
+    for task in deferred_triggers.values() {
+        task.abort();
+    }
+    if let Some(task) = metrics_task {
+        task.abort();
+    }
+    if let Some(task) = api_task {
+        task.abort();
+    }
+    if let Some(task) = log_prune_task {
+        task.abort();
+    }
     info!("Ardiex backup service stopped");
     Ok(())
 }
@@ -0,0 +1,106 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, Utc};
+use cron::Schedule;
+use std::str::FromStr;
+
+use crate::blackout::{self, BlackoutWindow};
+use crate::config::ConfigManager;
+use crate::units;
+
+/// Next `count` fire times of `expr` after `after`, evaluated in UTC the
+/// way `run` does.
+pub(crate) fn upcoming_fires(
+    expr: &str,
+    count: usize,
+    after: DateTime<Utc>,
+) -> Result<Vec<DateTime<Utc>>> {
+    let schedule = Schedule::from_str(expr)
+        .map_err(|e| anyhow::anyhow!("Invalid cron expression '{}': {}", expr, e))?;
+    Ok(schedule.after(&after).take(count).collect())
+}
+
+/// One fire time in local time, noting when a blackout window defers it.
+fn describe_fire(fire: DateTime<Utc>, windows: &[BlackoutWindow]) -> String {
+    let local = fire.with_timezone(&Local);
+    let formatted = local.format("%Y-%m-%d %H:%M:%S %a").to_string();
+    match blackout::blackout_until(windows, local.naive_local()) {
+        Some(until) => format!(
+            "{} (blackout, deferred to {})",
+            formatted,
+            until.format("%Y-%m-%d %H:%M")
+        ),
+        None => formatted,
+    }
+}
+
+fn print_fires(expr: &str, count: usize, windows: &[BlackoutWindow]) {
+    match upcoming_fires(expr, count, Utc::now()) {
+        Ok(fires) if fires.is_empty() => println!("    (never fires)"),
+        Ok(fires) => {
+            for fire in fires {
+                println!("    {}", describe_fire(fire, windows));
+            }
+        }
+        Err(e) => println!("    {}", e),
+    }
+}
+
+pub async fn handle_schedule(count: usize) -> Result<()> {
+    let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
+    let config = config_manager.get_config();
+    let windows = &config.blackout_windows;
+
+    if !config.enable_periodic {
+        println!("Periodic backups are disabled globally (enable_periodic = false)");
+        return Ok(());
+    }
+    let sources: Vec<_> = config.sources.iter().filter(|s| s.enabled).collect();
+    if sources.is_empty() {
+        println!("No enabled sources");
+        return Ok(());
+    }
+
+    println!(
+        "Next {} fire time(s) per source, local time ({}):",
+        count,
+        Local::now().format("%:z")
+    );
+    for source in sources {
+        let resolved = source.resolve(config);
+        println!("{:?}", source.source_dir);
+        if let Some(ref expr) = source.verify_schedule {
+            // Verification ignores blackout windows.
+            println!("  verify: {}", expr);
+            print_fires(expr, count, &[]);
+        }
+        if !resolved.enable_periodic {
+            println!("  periodic backups disabled (enable_periodic = false)");
+            continue;
+        }
+        let jitter = if resolved.cron_jitter_secs > 0 {
+            format!(
+                " (+ up to {} jitter)",
+                units::format_duration(resolved.cron_jitter_secs)
+            )
+        } else {
+            String::new()
+        };
+        println!("  cron: {}{}", resolved.cron_schedule, jitter);
+        print_fires(&resolved.cron_schedule, count, windows);
+        for destination in &source.backup_dirs {
+            if let Some(ref expr) = destination.cron_schedule {
+                println!("  destination {:?} cron: {}", destination.path, expr);
+                print_fires(expr, count, windows);
+            }
+        }
+    }
+    if !windows.is_empty() {
+        let windows: Vec<String> = windows.iter().map(|w| w.to_string()).collect();
+        println!("Blackout windows: {}", windows.join("; "));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[path = "../tests/schedule_cmd_tests.rs"]
+mod tests;
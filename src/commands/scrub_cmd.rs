@@ -0,0 +1,78 @@
+use anyhow::{Context, Result};
+use log::info;
+use std::path::PathBuf;
+
+use crate::backup::BackupManager;
+use crate::config::ConfigManager;
+
+pub async fn handle_scrub(source: Option<PathBuf>, dry_run: bool) -> Result<()> {
+    let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
+    let config = config_manager.get_config();
+
+    let sources: Vec<_> = match source {
+        Some(ref source_dir) => vec![
+            config
+                .sources
+                .iter()
+                .find(|s| s.source_dir == *source_dir)
+                .ok_or_else(|| anyhow::anyhow!("Source not found: {:?}", source_dir))?,
+        ],
+        None => config.sources.iter().filter(|s| s.enabled).collect(),
+    };
+
+    let mut unrecoverable = 0;
+    let mut scrubbed = 0;
+    for source in sources {
+        let backup_dirs = source.effective_backup_dirs();
+        if backup_dirs.len() < 2 {
+            info!(
+                "Source {:?} has a single backup dir, nothing to compare",
+                source.source_dir
+            );
+            continue;
+        }
+        info!(
+            "Scrubbing {} backup dirs of {:?} (dry_run: {})",
+            backup_dirs.len(),
+            source.source_dir,
+            dry_run
+        );
+        let report = BackupManager::scrub_backup_dirs(&backup_dirs, dry_run, None)?;
+        scrubbed += 1;
+
+        println!(
+            "{:?}: {} stored copies compared across {} backup dirs",
+            source.source_dir,
+            report.copies_checked,
+            backup_dirs.len()
+        );
+        for finding in &report.findings {
+            let outcome = match (&finding.healthy_copy, finding.repaired) {
+                (Some(healthy), true) => format!("repaired from {:?}", healthy),
+                (Some(healthy), false) => format!("would repair from {:?}", healthy),
+                (None, _) => {
+                    unrecoverable += 1;
+                    "no intact copy in another backup dir".to_string()
+                }
+            };
+            println!(
+                "  {} in {:?} is corrupted ({:?}), {}",
+                finding.file, finding.backup_dir, finding.stored_copy, outcome
+            );
+        }
+        if report.findings.is_empty() {
+            println!("  No corrupted copies found");
+        }
+    }
+
+    if scrubbed == 0 {
+        println!("No source has two or more backup dirs to compare");
+    }
+    if unrecoverable > 0 {
+        return Err(anyhow::anyhow!(
+            "{} corrupted copies could not be repaired; restoring them from those dirs gives wrong content",
+            unrecoverable
+        ));
+    }
+    Ok(())
+}
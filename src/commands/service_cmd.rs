@@ -0,0 +1,217 @@
+use anyhow::{Context, Result};
+use log::info;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::cli::ServiceAction;
+use crate::config::{self, ConfigManager};
+use crate::logger;
+
+const SYSTEM_UNIT_DIR: &str = "/etc/systemd/system";
+const UNIT_NAME: &str = "ardiex";
+/// `LogsDirectory=` of system units, i.e. `/var/log/ardiex`.
+const SYSTEM_LOGS_DIRECTORY: &str = "ardiex";
+
+/// Where a unit lives and how `systemctl` is called for it.
+struct UnitScope {
+    user: bool,
+}
+
+impl UnitScope {
+    fn unit_dir(&self) -> Result<PathBuf> {
+        if !self.user {
+            return Ok(PathBuf::from(SYSTEM_UNIT_DIR));
+        }
+        let non_empty = |key: &str| std::env::var_os(key).filter(|value| !value.is_empty());
+        let base = non_empty("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| non_empty("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .ok_or_else(|| anyhow::anyhow!("Neither XDG_CONFIG_HOME nor HOME is set"))?;
+        Ok(base.join("systemd").join("user"))
+    }
+
+    fn systemctl(&self, args: &[&str]) -> Result<()> {
+        let mut command = Command::new("systemctl");
+        if self.user {
+            command.arg("--user");
+        }
+        let status = command
+            .args(args)
+            .status()
+            .context("Failed to run systemctl")?;
+        if !status.success() {
+            anyhow::bail!("systemctl {} failed ({})", args.join(" "), status);
+        }
+        Ok(())
+    }
+}
+
+/// `ardiex.service`, or `ardiex-<profile>.service` so every profile can
+/// run as its own service.
+pub(crate) fn unit_file_name(profile: Option<&str>) -> String {
+    match profile {
+        Some(profile) => format!("{}-{}.service", UNIT_NAME, profile),
+        None => format!("{}.service", UNIT_NAME),
+    }
+}
+
+/// Quote one `ExecStart=` argument: systemd expands `%` specifiers and
+/// `$` variables, and unquotes C-style escapes.
+fn quote_arg(arg: &str) -> String {
+    let escaped = arg
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    format!("\"{}\"", escaped)
+}
+
+/// Unit file running `exe run` against `config_path` as `profile`. System
+/// units get a read-only view of the OS with write access to the settings
+/// dir and log to `/var/log/ardiex` (backup dirs live elsewhere and stay
+/// writable); user units only the options systemd supports without
+/// privileges and keep logging next to the executable.
+pub(crate) fn render_unit(
+    exe: &Path,
+    config_path: &Path,
+    profile: Option<&str>,
+    user: bool,
+) -> String {
+    let exe = exe.to_string_lossy();
+    let config_arg = config_path.to_string_lossy();
+    let mut args = vec![exe.as_ref(), "--config", config_arg.as_ref()];
+    if let Some(profile) = profile {
+        args.extend(["--profile", profile]);
+    }
+    args.push("run");
+    let exec_start = args
+        .iter()
+        .map(|arg| quote_arg(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut unit = String::new();
+    unit.push_str("[Unit]\n");
+    unit.push_str("Description=Ardiex incremental backup service\n");
+    unit.push_str("Documentation=https://github.com/ardi-orrorin/ardiex\n");
+    unit.push_str("After=network-online.target local-fs.target\n");
+    unit.push_str("Wants=network-online.target\n\n");
+
+    unit.push_str("[Service]\n");
+    unit.push_str("Type=simple\n");
+    unit.push_str(&format!("ExecStart={}\n", exec_start));
+    // The self-updater restarts the binary outside the service's control.
+    unit.push_str("Environment=ARDIEX_SKIP_UPDATE_CHECK=1\n");
+    unit.push_str("Restart=on-failure\n");
+    unit.push_str("RestartSec=10\n");
+    unit.push_str("TimeoutStopSec=60\n");
+    unit.push_str("NoNewPrivileges=yes\n");
+    if !user {
+        unit.push_str("ProtectSystem=full\n");
+        if let Some(config_dir) = config_path.parent() {
+            unit.push_str(&format!(
+                "ReadWritePaths=-{}\n",
+                quote_arg(&config_dir.to_string_lossy())
+            ));
+        }
+        // The directory next to the executable is read-only here; systemd
+        // creates this one writable for the service.
+        unit.push_str(&format!("LogsDirectory={}\n", SYSTEM_LOGS_DIRECTORY));
+        unit.push_str(&format!(
+            "Environment={}=/var/log/{}\n",
+            logger::LOG_DIR_ENV_KEY,
+            SYSTEM_LOGS_DIRECTORY
+        ));
+        unit.push_str("PrivateTmp=yes\n");
+        unit.push_str("ProtectKernelTunables=yes\n");
+        unit.push_str("ProtectKernelModules=yes\n");
+        unit.push_str("ProtectControlGroups=yes\n");
+        unit.push_str("RestrictSUIDSGID=yes\n");
+        unit.push_str("RestrictRealtime=yes\n");
+        unit.push_str("LockPersonality=yes\n");
+    }
+    unit.push('\n');
+
+    unit.push_str("[Install]\n");
+    unit.push_str(if user {
+        "WantedBy=default.target\n"
+    } else {
+        "WantedBy=multi-user.target\n"
+    });
+    unit
+}
+
+pub async fn handle_service(action: ServiceAction) -> Result<()> {
+    if !cfg!(target_os = "linux") {
+        anyhow::bail!("Service installation is only supported on Linux (systemd)");
+    }
+    let profile = config::active_profile()?;
+    let unit_name = unit_file_name(profile.as_deref());
+
+    match action {
+        ServiceAction::Install { user, no_start } => {
+            let scope = UnitScope { user };
+            let config_manager =
+                ConfigManager::load_or_create().context("Failed to load configuration")?;
+            let config_path = std::path::absolute(&config_manager.config_path)
+                .context("Failed to resolve the settings file path")?;
+            let exe = std::env::current_exe().context("Failed to resolve current executable")?;
+            let unit = render_unit(&exe, &config_path, profile.as_deref(), user);
+
+            let unit_dir = scope.unit_dir()?;
+            fs::create_dir_all(&unit_dir)
+                .with_context(|| format!("Failed to create {:?}", unit_dir))?;
+            let unit_path = unit_dir.join(&unit_name);
+            fs::write(&unit_path, unit)
+                .with_context(|| format!("Failed to write {:?}", unit_path))?;
+            info!("[SERVICE] Wrote {:?}", unit_path);
+            println!("Wrote {:?}", unit_path);
+            if !user {
+                println!(
+                    "The service logs to /var/log/{0}; read them with {1}=/var/log/{0} ardiex logs",
+                    SYSTEM_LOGS_DIRECTORY,
+                    logger::LOG_DIR_ENV_KEY
+                );
+            }
+
+            scope.systemctl(&["daemon-reload"])?;
+            if no_start {
+                scope.systemctl(&["enable", &unit_name])?;
+                println!("Enabled {} (starts at next boot)", unit_name);
+            } else {
+                scope.systemctl(&["enable", "--now", &unit_name])?;
+                println!("Enabled and started {}", unit_name);
+            }
+        }
+        ServiceAction::Uninstall { user } => {
+            let scope = UnitScope { user };
+            let unit_path = scope.unit_dir()?.join(&unit_name);
+            if !unit_path.exists() {
+                println!("{} is not installed ({:?})", unit_name, unit_path);
+                return Ok(());
+            }
+            scope.systemctl(&["disable", "--now", &unit_name])?;
+            fs::remove_file(&unit_path)
+                .with_context(|| format!("Failed to remove {:?}", unit_path))?;
+            scope.systemctl(&["daemon-reload"])?;
+            info!("[SERVICE] Removed {:?}", unit_path);
+            println!("Stopped, disabled and removed {}", unit_name);
+        }
+        ServiceAction::Status { user } => {
+            let scope = UnitScope { user };
+            let unit_path = scope.unit_dir()?.join(&unit_name);
+            if !unit_path.exists() {
+                println!("{} is not installed ({:?})", unit_name, unit_path);
+                return Ok(());
+            }
+            // `status` exits non-zero for stopped units; its output says why.
+            let _ = scope.systemctl(&["status", "--no-pager", &unit_name]);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[path = "../tests/service_cmd_tests.rs"]
+mod tests;
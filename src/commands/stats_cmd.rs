@@ -0,0 +1,70 @@
+use anyhow::Result;
+use std::path::PathBuf;
+
+use crate::backup::BackupManager;
+use crate::config::BackupHistoryType;
+
+fn mb(bytes: u64) -> f64 {
+    bytes as f64 / 1024.0 / 1024.0
+}
+
+pub async fn handle_stats(backup_dir: PathBuf) -> Result<()> {
+    let stats = BackupManager::collect_backup_stats(&backup_dir)?;
+
+    println!("Backup statistics for {:?}", stats.backup_dir);
+    println!(
+        "  Total space used: {:.2} MB in {} backup point(s)",
+        mb(stats.total_bytes),
+        stats.points.len()
+    );
+
+    if !stats.points.is_empty() {
+        println!("\nBackup points:");
+        for point in &stats.points {
+            let backup_type = match point.backup_type {
+                BackupHistoryType::Full => "FULL",
+                BackupHistoryType::Incremental => "INC ",
+            };
+            println!(
+                "  [{}] {}: {} files ({} delta), {:.2} MB",
+                backup_type,
+                point.name,
+                point.files,
+                point.delta_files,
+                mb(point.stored_bytes)
+            );
+        }
+    }
+
+    if stats.delta_full_bytes > 0 {
+        let saved = stats.delta_saved_bytes();
+        println!(
+            "\nDelta savings: {:.2} MB stored instead of {:.2} MB as full copies ({:.2} MB, {:.1}% saved)",
+            mb(stats.delta_stored_bytes),
+            mb(stats.delta_full_bytes),
+            saved as f64 / 1024.0 / 1024.0,
+            saved as f64 * 100.0 / stats.delta_full_bytes as f64
+        );
+    }
+
+    if !stats.largest_files.is_empty() {
+        println!("\nLargest stored files:");
+        for file in &stats.largest_files {
+            println!(
+                "  {:.2} MB  {} ({})",
+                mb(file.size),
+                file.path,
+                file.backup_name
+            );
+        }
+    }
+
+    if !stats.hot_spots.is_empty() {
+        println!("\nMost frequently changed files:");
+        for spot in &stats.hot_spots {
+            println!("  {} change(s)  {}", spot.changes, spot.path);
+        }
+    }
+
+    Ok(())
+}
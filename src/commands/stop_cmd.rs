@@ -0,0 +1,21 @@
+use anyhow::{Context, Result};
+use std::time::Duration;
+
+use crate::config::ConfigManager;
+use crate::daemon;
+
+pub async fn handle_stop(timeout_secs: u64) -> Result<()> {
+    let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
+    let pid_path = config_manager.pid_path();
+    let stopped = tokio::task::spawn_blocking(move || {
+        daemon::stop(&pid_path, Duration::from_secs(timeout_secs))
+    })
+    .await
+    .context("Stop task failed")??;
+
+    match stopped {
+        Some(pid) => println!("Backup service stopped (pid {})", pid),
+        None => println!("Backup service is not running"),
+    }
+    Ok(())
+}
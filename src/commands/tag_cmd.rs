@@ -0,0 +1,25 @@
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+
+use crate::backup::BackupManager;
+use crate::config::ConfigManager;
+use crate::signing;
+
+pub async fn handle_tag(
+    backup_dir: PathBuf,
+    point: String,
+    tag: String,
+    remove: bool,
+) -> Result<()> {
+    let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
+    let signing_key =
+        signing::load_optional(config_manager.get_config().signing_key_file.as_deref())?;
+    let backup_name =
+        BackupManager::set_tag(&backup_dir, &point, &tag, remove, signing_key.as_ref())?;
+    if remove {
+        println!("Removed tag '{}' from {}", tag.trim(), backup_name);
+    } else {
+        println!("Tagged {} '{}'", backup_name, tag.trim());
+    }
+    Ok(())
+}
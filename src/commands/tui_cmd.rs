@@ -0,0 +1,404 @@
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local, Utc};
+use ratatui::DefaultTerminal;
+use ratatui::Frame;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph, Wrap};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::config::ConfigManager;
+use crate::history::{RunRecord, RunStatus};
+use crate::units;
+
+/// Environment variable overriding `api_token`, for watching a service
+/// whose settings live elsewhere.
+const TOKEN_ENV: &str = "ARDIEX_API_TOKEN";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+const RECENT_RUNS: usize = 20;
+const LOG_LINES: usize = 200;
+const BAR_WIDTH: usize = 20;
+
+/// `GET /api/status` as far as the monitor shows it.
+#[derive(Debug, Deserialize)]
+struct ServiceStatus {
+    started_at: DateTime<Utc>,
+    running: Vec<PathBuf>,
+    queued: Vec<QueuedTrigger>,
+    progress: Vec<TransferProgress>,
+    restores_running: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct QueuedTrigger {
+    source_dir: PathBuf,
+    reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TransferProgress {
+    label: String,
+    target: PathBuf,
+    file: Option<PathBuf>,
+    files_done: usize,
+    total_files: usize,
+    bytes_done: u64,
+    total_bytes: u64,
+}
+
+/// Everything one refresh fetches from the service.
+struct Snapshot {
+    status: ServiceStatus,
+    /// Newest first.
+    runs: Vec<RunRecord>,
+    /// Log tail, or why it could not be read.
+    logs: Result<Vec<String>, String>,
+}
+
+/// What the screen shows: the last snapshot that arrived, and the error
+/// of the latest refresh if it failed.
+#[derive(Default)]
+struct View {
+    base_url: String,
+    snapshot: Option<Snapshot>,
+    fetched_at: Option<DateTime<Local>>,
+    error: Option<String>,
+}
+
+struct ApiClient {
+    client: Client,
+    base_url: String,
+    token: String,
+}
+
+impl ApiClient {
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let response = self
+            .client
+            .get(format!("{}{}", self.base_url, path))
+            .bearer_auth(&self.token)
+            .send()
+            .with_context(|| format!("Failed to reach the service at {}", self.base_url))?;
+        let status = response.status();
+        if !status.is_success() {
+            let message = response
+                .json::<serde_json::Value>()
+                .ok()
+                .and_then(|body| body["error"].as_str().map(str::to_string))
+                .unwrap_or_default();
+            bail!("{} returned {}: {}", path, status, message);
+        }
+        response
+            .json()
+            .with_context(|| format!("Invalid response from {}", path))
+    }
+
+    fn snapshot(&self) -> Result<Snapshot> {
+        let status = self.get("/api/status")?;
+        let mut runs: Vec<RunRecord> = self.get(&format!("/api/history?limit={}", RECENT_RUNS))?;
+        runs.reverse();
+        let logs = self
+            .get(&format!("/api/logs?lines={}", LOG_LINES))
+            .map_err(|e| format!("{:#}", e));
+        Ok(Snapshot { status, runs, logs })
+    }
+}
+
+/// URL of the API bound at `api_bind`; a wildcard bind is reached over
+/// loopback.
+fn api_base_url(api_bind: &str) -> Result<String> {
+    let mut addr: SocketAddr = api_bind
+        .parse()
+        .with_context(|| format!("api_bind {:?} is not an address:port", api_bind))?;
+    if addr.ip().is_unspecified() {
+        addr.set_ip(match addr.ip() {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        });
+    }
+    Ok(format!("http://{}", addr))
+}
+
+pub async fn handle_tui(addr: Option<String>, interval: u64) -> Result<()> {
+    let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
+    let config = config_manager.get_config();
+    let Some(api_bind) = addr.or_else(|| config.api_bind.clone()) else {
+        bail!("The REST API is off; set api_bind and api_token, then restart `ardiex run`");
+    };
+    let Some(token) = std::env::var(TOKEN_ENV)
+        .ok()
+        .or_else(|| config.api_token.clone())
+    else {
+        bail!("No API token; set api_token or {}", TOKEN_ENV);
+    };
+    let base_url = api_base_url(&api_bind)?;
+    let interval = Duration::from_secs(interval.max(1));
+
+    // The blocking client and the terminal both want their own thread.
+    tokio::task::spawn_blocking(move || {
+        let client = ApiClient {
+            client: Client::builder().timeout(REQUEST_TIMEOUT).build()?,
+            base_url,
+            token,
+        };
+        let mut terminal = ratatui::init();
+        let result = event_loop(&mut terminal, &client, interval);
+        ratatui::restore();
+        result
+    })
+    .await?
+}
+
+/// Refresh every `interval` until `q`, Esc or Ctrl-C; `r` refreshes now.
+fn event_loop(
+    terminal: &mut DefaultTerminal,
+    client: &ApiClient,
+    interval: Duration,
+) -> Result<()> {
+    let mut view = View {
+        base_url: client.base_url.clone(),
+        ..Default::default()
+    };
+    let mut next_fetch = Instant::now();
+    loop {
+        if Instant::now() >= next_fetch {
+            match client.snapshot() {
+                Ok(snapshot) => {
+                    view.snapshot = Some(snapshot);
+                    view.fetched_at = Some(Local::now());
+                    view.error = None;
+                }
+                Err(e) => view.error = Some(format!("{:#}", e)),
+            }
+            next_fetch = Instant::now() + interval;
+        }
+        terminal.draw(|frame| draw(frame, &view))?;
+
+        if !event::poll(next_fetch.saturating_duration_since(Instant::now()))? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+        {
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(());
+                }
+                KeyCode::Char('r') => next_fetch = Instant::now(),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, view: &View) {
+    let [header, top, logs] = Layout::vertical([
+        Constraint::Length(2),
+        Constraint::Percentage(50),
+        Constraint::Min(5),
+    ])
+    .areas(frame.area());
+    let [left, runs] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(top);
+
+    frame.render_widget(Paragraph::new(header_lines(view)), header);
+    let Some(ref snapshot) = view.snapshot else {
+        return;
+    };
+    let queued_height = (snapshot.status.queued.len().clamp(1, 8) + 2) as u16;
+    let [running, queued] =
+        Layout::vertical([Constraint::Min(3), Constraint::Length(queued_height)]).areas(left);
+
+    draw_lines(frame, running, "Running", running_lines(&snapshot.status));
+    draw_lines(frame, queued, "Queued", queued_lines(&snapshot.status));
+    draw_lines(frame, runs, "Recent results", run_lines(&snapshot.runs));
+    draw_logs(frame, logs, &snapshot.logs);
+}
+
+fn draw_lines(frame: &mut Frame, area: Rect, title: &str, lines: Vec<Line<'static>>) {
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(Block::bordered().title(title.to_string()))
+            .wrap(Wrap { trim: false }),
+        area,
+    );
+}
+
+fn header_lines(view: &View) -> Vec<Line<'static>> {
+    let mut title = vec![
+        Span::styled("ardiex", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(format!("  {}", view.base_url)),
+    ];
+    if let Some(ref snapshot) = view.snapshot {
+        title.push(Span::raw(format!(
+            "  up since {}  restores running: {}",
+            snapshot
+                .status
+                .started_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S"),
+            snapshot.status.restores_running
+        )));
+    }
+    let status = match (&view.error, view.fetched_at) {
+        (Some(error), _) => Span::styled(
+            format!("refresh failed: {}", error),
+            Style::default().fg(Color::Red),
+        ),
+        (None, Some(at)) => Span::raw(format!("updated {}", at.format("%H:%M:%S"))),
+        (None, None) => Span::raw("connecting..."),
+    };
+    vec![
+        Line::from(title),
+        Line::from(vec![status, Span::raw("   q: quit  r: refresh")]),
+    ]
+}
+
+/// `[#####     ]  42%` for `done` of `total`.
+fn progress_bar(done: u64, total: u64) -> String {
+    let ratio = if total == 0 {
+        0.0
+    } else {
+        (done as f64 / total as f64).min(1.0)
+    };
+    let filled = (ratio * BAR_WIDTH as f64).round() as usize;
+    format!(
+        "[{}{}] {:>3}%",
+        "#".repeat(filled),
+        " ".repeat(BAR_WIDTH - filled),
+        (ratio * 100.0).round() as u64
+    )
+}
+
+fn running_lines(status: &ServiceStatus) -> Vec<Line<'static>> {
+    if status.running.is_empty() && status.progress.is_empty() {
+        return vec![Line::styled("idle", Style::default().fg(Color::DarkGray))];
+    }
+    let mut lines: Vec<Line<'static>> = status
+        .running
+        .iter()
+        .map(|source| Line::from(format!("source {}", source.display())))
+        .collect();
+    for progress in &status.progress {
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{} ", progress.label),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(progress.target.display().to_string()),
+        ]));
+        lines.push(Line::from(format!(
+            "  {} {}/{} files, {} / {}",
+            progress_bar(progress.bytes_done, progress.total_bytes),
+            progress.files_done,
+            progress.total_files,
+            units::format_size(progress.bytes_done),
+            units::format_size(progress.total_bytes)
+        )));
+        if let Some(ref file) = progress.file {
+            lines.push(Line::styled(
+                format!("  {}", file.display()),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+    }
+    lines
+}
+
+fn queued_lines(status: &ServiceStatus) -> Vec<Line<'static>> {
+    if status.queued.is_empty() {
+        return vec![Line::styled("empty", Style::default().fg(Color::DarkGray))];
+    }
+    status
+        .queued
+        .iter()
+        .map(|queued| {
+            Line::from(vec![
+                Span::styled(
+                    format!("{:<12}", queued.reason),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::raw(queued.source_dir.display().to_string()),
+            ])
+        })
+        .collect()
+}
+
+fn run_lines(runs: &[RunRecord]) -> Vec<Line<'static>> {
+    if runs.is_empty() {
+        return vec![Line::styled(
+            "no runs recorded",
+            Style::default().fg(Color::DarkGray),
+        )];
+    }
+    runs.iter()
+        .map(|run| {
+            let at = run
+                .finished_at
+                .with_timezone(&Local)
+                .format("%m-%d %H:%M:%S")
+                .to_string();
+            let (status, detail) = match run.status {
+                RunStatus::Success => (
+                    Span::styled("OK  ", Style::default().fg(Color::Green)),
+                    format!(
+                        "{} files, {} in {} ms",
+                        run.files_backed_up,
+                        units::format_size(run.bytes_processed),
+                        run.duration_ms
+                    ),
+                ),
+                RunStatus::Failure => (
+                    Span::styled("FAIL", Style::default().fg(Color::Red)),
+                    run.error
+                        .clone()
+                        .unwrap_or_else(|| "unknown error".to_string()),
+                ),
+            };
+            Line::from(vec![
+                Span::raw(format!("{} ", at)),
+                status,
+                Span::raw(format!(" {} {}", run.source_dir.display(), detail)),
+            ])
+        })
+        .collect()
+}
+
+fn draw_logs(frame: &mut Frame, area: Rect, logs: &Result<Vec<String>, String>) {
+    let lines: Vec<Line<'static>> = match logs {
+        Err(error) => vec![Line::styled(error.clone(), Style::default().fg(Color::Red))],
+        Ok(logs) => {
+            // Only the newest lines that fit inside the border.
+            let visible = usize::from(area.height.saturating_sub(2));
+            logs[logs.len().saturating_sub(visible)..]
+                .iter()
+                .map(|line| {
+                    let color = if line.contains(" ERROR ") {
+                        Color::Red
+                    } else if line.contains(" WARN ") {
+                        Color::Yellow
+                    } else {
+                        Color::Reset
+                    };
+                    Line::styled(line.clone(), Style::default().fg(color))
+                })
+                .collect()
+        }
+    };
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title("Log")),
+        area,
+    );
+}
+
+#[cfg(test)]
+#[path = "../tests/tui_cmd_tests.rs"]
+mod tests;
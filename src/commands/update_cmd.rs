@@ -0,0 +1,89 @@
+use anyhow::{Context, Result};
+use log::info;
+
+use super::config_cmd::confirm;
+use crate::config::{ConfigManager, UpdateChannel};
+use crate::release_verify;
+use crate::update;
+
+/// Release note lines printed before pointing at the full notes.
+const MAX_NOTE_LINES: usize = 40;
+
+pub async fn handle_update(check: bool, channel: Option<UpdateChannel>, yes: bool) -> Result<()> {
+    let config_manager = ConfigManager::load_or_create().context("Failed to load configuration")?;
+    let auto_update = config_manager.get_config().auto_update;
+    let allow_unsigned = config_manager.get_config().allow_unsigned_updates;
+    let channel = channel.unwrap_or(config_manager.get_config().update_channel);
+
+    let release =
+        tokio::task::spawn_blocking(move || update::fetch_release(update::UPDATE_REPO, channel))
+            .await??;
+    let installed = env!("CARGO_PKG_VERSION");
+    let latest = release.tag_name.trim_start_matches(['v', 'V']);
+    println!("Installed: {}", installed);
+    println!(
+        "Latest:    {} ({} channel{})",
+        latest,
+        channel.as_str(),
+        if release.prerelease {
+            ", pre-release"
+        } else {
+            ""
+        }
+    );
+
+    if !update::is_newer_version(installed, latest) {
+        println!("ardiex is up to date");
+        return Ok(());
+    }
+
+    let notes = release.body.as_deref().unwrap_or("").trim();
+    if !notes.is_empty() {
+        println!();
+        println!("Release notes:");
+        let lines: Vec<&str> = notes.lines().collect();
+        for line in lines.iter().take(MAX_NOTE_LINES) {
+            println!("  {}", line);
+        }
+        if lines.len() > MAX_NOTE_LINES {
+            println!("  ... ({} more lines)", lines.len() - MAX_NOTE_LINES);
+        }
+    }
+    if !release.html_url.is_empty() {
+        println!("Full notes: {}", release.html_url);
+    }
+    // Refuse an update that cannot be verified before asking for it.
+    let public_key = release_verify::embedded_public_key();
+    let verification = update::verification_assets(&release, public_key, allow_unsigned);
+    match &verification {
+        Ok(assets) if assets.signature_url.is_some() && public_key.is_some() => {
+            println!("Verification: signed {}", release_verify::CHECKSUMS_ASSET)
+        }
+        Ok(_) => println!("Verification: unsigned, allowed by allow_unsigned_updates"),
+        Err(e) => println!("Verification: {}", e),
+    }
+    if check {
+        return Ok(());
+    }
+    verification?;
+
+    println!();
+    if !confirm(
+        &format!("Update ardiex {} to {}?", installed, latest),
+        yes || auto_update,
+    )? {
+        println!("Update cancelled");
+        return Ok(());
+    }
+    let forward_args: Vec<String> = std::env::args().skip(1).collect();
+    let pid = update::spawn_updater(&release, &forward_args, false, allow_unsigned)?;
+    info!(
+        "[UPDATE] Delegated update to updater (pid: {}, target: {})",
+        pid, release.tag_name
+    );
+    println!(
+        "Installing {} in the background (updater pid {}); see logs/updater.log next to the binary",
+        latest, pid
+    );
+    Ok(())
+}
@@ -1,45 +1,54 @@
-mod backup;
 mod cli;
 mod commands;
-mod config;
-mod delta;
-mod logger;
-mod restore;
 mod update;
-mod watcher;
 
 use anyhow::{Context, Result};
+use ardiex_core::{
+    api, backup, bench, blackout, config, config_format, daemon, delta, doctor, export, history,
+    logger, metrics, notification, patterns, point_name, priority, progress, release_verify, repo,
+    restore, signing, snapshot, trigger, units, watcher,
+};
 use clap::Parser;
 use log::{info, warn};
 use mimalloc::MiMalloc;
-use std::process::{Command, Stdio};
 
 use cli::{Cli, Commands};
 use commands::backup_cmd::handle_backup;
+use commands::bench_cmd::handle_bench;
+use commands::check_cmd::handle_check;
+use commands::compact_cmd::handle_compact;
 use commands::config_cmd::handle_config;
-use commands::restore_cmd::handle_restore;
+use commands::doctor_cmd::handle_doctor;
+use commands::export_cmd::handle_export;
+use commands::gc_cmd::handle_gc;
+use commands::history_cmd::handle_history;
+use commands::import_seed_cmd::handle_import_seed;
+use commands::init_repo_cmd::handle_init_repo;
+use commands::logs_cmd::handle_logs;
+use commands::pin_cmd::handle_pin;
+use commands::prune_cmd::handle_prune;
+use commands::repair_cmd::handle_repair;
+use commands::replicate_cmd::handle_replicate;
+use commands::restore_cmd::{RestoreTarget, handle_restore, pick_backup_dir};
 use commands::run_cmd::handle_run;
+use commands::schedule_cmd::handle_schedule;
+use commands::scrub_cmd::handle_scrub;
+use commands::service_cmd::handle_service;
+use commands::stats_cmd::handle_stats;
+use commands::stop_cmd::handle_stop;
+use commands::tag_cmd::handle_tag;
+use commands::tui_cmd::handle_tui;
+use commands::update_cmd::handle_update;
 use config::ConfigManager;
+use restore::RestoreOptions;
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
-const UPDATE_REPO: &str = "ardi-orrorin/ardiex";
-const UPDATE_SKIP_ENV_KEY: &str = "ARDIEX_SKIP_UPDATE_CHECK";
-const UPDATE_SKIP_ENV_VALUE: &str = "1";
-
-fn updater_binary_name() -> &'static str {
-    if cfg!(windows) {
-        "updater.exe"
-    } else {
-        "updater"
-    }
-}
-
 fn should_skip_update_check(args: &[String]) -> bool {
-    if std::env::var(UPDATE_SKIP_ENV_KEY)
+    if std::env::var(update::UPDATE_SKIP_ENV_KEY)
         .ok()
-        .map(|v| v == UPDATE_SKIP_ENV_VALUE || v.eq_ignore_ascii_case("true"))
+        .map(|v| v == update::UPDATE_SKIP_ENV_VALUE || v.eq_ignore_ascii_case("true"))
         .unwrap_or(false)
     {
         return true;
@@ -49,27 +58,34 @@ fn should_skip_update_check(args: &[String]) -> bool {
         .any(|arg| matches!(arg.as_str(), "--help" | "-h" | "--version" | "-V"))
 }
 
-async fn maybe_delegate_to_updater(forward_args: &[String]) -> Result<bool> {
+async fn maybe_delegate_to_updater(
+    forward_args: &[String],
+    channel: config::UpdateChannel,
+    allow_unsigned: bool,
+) -> Result<bool> {
     if should_skip_update_check(forward_args) {
         return Ok(false);
     }
 
     let current_version = env!("CARGO_PKG_VERSION");
-    let latest_release =
-        match tokio::task::spawn_blocking(|| update::fetch_latest_release(UPDATE_REPO)).await {
-            Ok(Ok(release)) => release,
-            Ok(Err(e)) => {
-                warn!("[UPDATE] Latest release check failed: {}", e);
-                return Ok(false);
-            }
-            Err(e) => {
-                warn!("[UPDATE] Latest release check task failed: {}", e);
-                return Ok(false);
-            }
-        };
+    let latest_release = match tokio::task::spawn_blocking(move || {
+        update::fetch_release(update::UPDATE_REPO, channel)
+    })
+    .await
+    {
+        Ok(Ok(release)) => release,
+        Ok(Err(e)) => {
+            warn!("[UPDATE] Latest release check failed: {}", e);
+            return Ok(false);
+        }
+        Err(e) => {
+            warn!("[UPDATE] Latest release check task failed: {}", e);
+            return Ok(false);
+        }
+    };
 
     let latest_version = update::normalize_version(&latest_release.tag_name);
-    if !update::is_newer_version(current_version, &latest_version) {
+    if !update::is_newer_version(current_version, &latest_release.tag_name) {
         info!(
             "[UPDATE] Current version {} is up-to-date (latest: {})",
             current_version, latest_version
@@ -77,95 +93,80 @@ async fn maybe_delegate_to_updater(forward_args: &[String]) -> Result<bool> {
         return Ok(false);
     }
 
-    let asset_name = match update::expected_release_asset_name_for_current_target() {
-        Ok(name) => name,
-        Err(e) => {
-            warn!("[UPDATE] Unsupported target for auto-update: {}", e);
-            return Ok(false);
+    match update::spawn_updater(&latest_release, forward_args, true, allow_unsigned) {
+        Ok(pid) => {
+            info!(
+                "[UPDATE] Delegated update to updater (pid: {}, target: {})",
+                pid, latest_release.tag_name
+            );
+            Ok(true)
         }
-    };
-    let asset_url = match update::find_release_asset_download_url(&latest_release, asset_name) {
-        Ok(url) => url,
         Err(e) => {
-            warn!("[UPDATE] Latest release asset lookup failed: {}", e);
-            return Ok(false);
+            warn!(
+                "[UPDATE] New version {} found but the update cannot start: {}",
+                latest_version, e
+            );
+            Ok(false)
         }
-    };
-
-    let current_exe = std::env::current_exe().context("Failed to resolve current executable")?;
-    let exe_dir = current_exe
-        .parent()
-        .ok_or_else(|| anyhow::anyhow!("Current executable has no parent directory"))?;
-    let updater_path = exe_dir.join(updater_binary_name());
-
-    if !updater_path.exists() {
-        warn!(
-            "[UPDATE] New version {} exists but updater binary is missing: {:?}",
-            latest_version, updater_path
-        );
-        return Ok(false);
-    }
-
-    let mut cmd = Command::new(&updater_path);
-    cmd.arg("--repo")
-        .arg(UPDATE_REPO)
-        .arg("--asset-url")
-        .arg(asset_url)
-        .arg("--asset-name")
-        .arg(asset_name)
-        .arg("--target-version")
-        .arg(latest_version)
-        .arg("--current-exe")
-        .arg(&current_exe)
-        .arg("--parent-pid")
-        .arg(std::process::id().to_string());
-
-    for arg in forward_args {
-        cmd.arg("--forward-arg").arg(arg);
     }
-
-    cmd.env(UPDATE_SKIP_ENV_KEY, UPDATE_SKIP_ENV_VALUE)
-        .stdin(Stdio::null())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null());
-
-    let child = cmd.spawn().with_context(|| {
-        format!(
-            "Failed to spawn updater process: {:?} (target version: {})",
-            updater_path, latest_release.tag_name
-        )
-    })?;
-
-    info!(
-        "[UPDATE] Delegated update to {:?} (pid: {}, target: {})",
-        updater_path,
-        child.id(),
-        latest_release.tag_name
-    );
-    Ok(true)
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize logging
-    let log_dir = std::env::current_exe()
-        .ok()
-        .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-        .map(|p| p.join("logs"));
+    let cli = Cli::parse();
+    // JSON output and the terminal UI own stdout, so log lines stay in the
+    // log file.
+    let quiet = cli.quiet
+        || (cli.verbose == 0
+            && matches!(
+                cli.command,
+                Commands::Check { json: true, .. }
+                    | Commands::Doctor { json: true, .. }
+                    | Commands::Bench { json: true, .. }
+                    | Commands::Tui { .. }
+            ));
+    if let Some(verbosity) = logger::Verbosity::from_flags(quiet, cli.verbose) {
+        logger::set_verbosity(verbosity);
+    }
+    if let Some(ref config_path) = cli.config {
+        config::set_config_path_override(config_path)?;
+    }
+    if let Some(ref profile) = cli.profile {
+        config::set_profile_override(profile)?;
+    }
+    let profile = config::active_profile()?;
+
+    // Initialize logging; each profile logs to its own subdirectory.
+    let log_dir = std::env::var_os(logger::LOG_DIR_ENV_KEY)
+        .filter(|dir| !dir.is_empty())
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::current_exe()
+                .ok()
+                .and_then(|p| p.parent().map(|p| p.join("logs")))
+        })
+        .map(|logs| match profile {
+            Some(ref profile) => logs.join(profile),
+            None => logs,
+        });
 
-    let max_log_file_size_mb = match ConfigManager::load_or_create() {
-        Ok(cm) => cm.get_config().max_log_file_size_mb,
+    let settings = match ConfigManager::load_or_create() {
+        Ok(cm) => cm.get_config().clone(),
         Err(e) => {
             eprintln!(
-                "Failed to read settings for max_log_file_size_mb, using default: {}",
+                "Failed to read settings for log rotation, using defaults: {}",
                 e
             );
-            20
+            config::BackupConfig::default()
         }
     };
 
     if let Some(ref log_dir) = log_dir {
-        if let Err(e) = logger::init_file_logging_with_size(log_dir, max_log_file_size_mb) {
+        if let Err(e) = logger::init_file_logging_with_size(
+            log_dir,
+            settings.max_log_file_size_mb,
+            settings.max_rotated_log_files,
+        ) {
             eprintln!("Failed to initialize file logging: {}", e);
             logger::init_console_logging();
         }
@@ -173,28 +174,142 @@ async fn main() -> Result<()> {
         logger::init_console_logging();
     }
 
-    let forward_args: Vec<String> = std::env::args().skip(1).collect();
-    match maybe_delegate_to_updater(&forward_args).await {
-        Ok(true) => return Ok(()),
-        Ok(false) => {}
-        Err(e) => warn!(
-            "[UPDATE] Failed to delegate updater, continue current process: {}",
-            e
-        ),
+    // Updates are only installed on start when opted in; `ardiex update`
+    // handles its own.
+    if settings.auto_update && !matches!(cli.command, Commands::Update { .. }) {
+        let forward_args: Vec<String> = std::env::args().skip(1).collect();
+        match maybe_delegate_to_updater(
+            &forward_args,
+            settings.update_channel,
+            settings.allow_unsigned_updates,
+        )
+        .await
+        {
+            Ok(true) => return Ok(()),
+            Ok(false) => {}
+            Err(e) => warn!(
+                "[UPDATE] Failed to delegate updater, continue current process: {}",
+                e
+            ),
+        }
     }
 
-    let cli = Cli::parse();
-
     match cli.command {
         Commands::Config { action } => handle_config(action).await?,
-        Commands::Backup => handle_backup().await?,
+        Commands::Backup { accept_changes } => handle_backup(accept_changes).await?,
         Commands::Restore {
             backup_dir,
             target_dir,
+            source,
+            in_place,
+            snapshot,
+            yes,
             point,
+            tag,
             list,
-        } => handle_restore(backup_dir, target_dir, point, list).await?,
-        Commands::Run => handle_run().await?,
+            interactive,
+            throttle,
+            lock_timeout,
+            no_owner,
+            verify,
+            overwrite,
+            backup_existing,
+        } => {
+            let signing_key_file = ConfigManager::load_or_create()
+                .context("Failed to load configuration")?
+                .get_config()
+                .signing_key_file
+                .clone();
+            let options = RestoreOptions {
+                throttle_mb_per_sec: throttle,
+                lock_timeout_secs: lock_timeout,
+                skip_ownership: no_owner,
+                verify_writes: verify,
+                signing_key: signing::load_optional(signing_key_file.as_deref())?,
+                overwrite,
+                backup_existing,
+            };
+            // With --source the one positional dir is the target.
+            let (backup_dir, target_dir) = match source {
+                Some(source) => (
+                    pick_backup_dir(&source, options.signing_key.as_ref())?,
+                    backup_dir,
+                ),
+                None => (backup_dir.context("Missing backup directory")?, target_dir),
+            };
+            let target = match (target_dir, in_place) {
+                (Some(_), true) => {
+                    return Err(anyhow::anyhow!(
+                        "--in-place restores into the source; drop the target dir"
+                    ));
+                }
+                (Some(target_dir), false) => Some(RestoreTarget::Dir(target_dir)),
+                (None, true) => Some(RestoreTarget::InPlace { snapshot, yes }),
+                (None, false) => None,
+            };
+            handle_restore(backup_dir, target, point, tag, list, interactive, options).await?
+        }
+        Commands::Export {
+            backup_dir,
+            point,
+            format,
+            output,
+            lock_timeout,
+        } => handle_export(backup_dir, point, format, output, lock_timeout).await?,
+        Commands::Replicate {
+            backup_dir,
+            dest,
+            lock_timeout,
+        } => handle_replicate(backup_dir, dest, lock_timeout).await?,
+        Commands::ImportSeed {
+            source_dir,
+            backup_dir,
+        } => handle_import_seed(source_dir, backup_dir).await?,
+        Commands::InitRepo { backup_dir } => handle_init_repo(backup_dir).await?,
+        Commands::Pin { backup_dir, point } => handle_pin(backup_dir, point, true).await?,
+        Commands::Unpin { backup_dir, point } => handle_pin(backup_dir, point, false).await?,
+        Commands::Tag {
+            backup_dir,
+            point,
+            tag,
+            remove,
+        } => handle_tag(backup_dir, point, tag, remove).await?,
+        Commands::Run { once, daemonize } => handle_run(once, daemonize, log_dir).await?,
+        Commands::Service { action } => handle_service(action).await?,
+        Commands::Stop { timeout } => handle_stop(timeout).await?,
+        Commands::Schedule { count } => handle_schedule(count).await?,
+        Commands::History { source, limit } => handle_history(source, limit).await?,
+        Commands::Stats { backup_dir } => handle_stats(backup_dir).await?,
+        Commands::Repair {
+            backup_dir,
+            dry_run,
+        } => handle_repair(backup_dir, dry_run).await?,
+        Commands::Check {
+            backup_dir,
+            repair,
+            json,
+        } => handle_check(backup_dir, repair, json).await?,
+        Commands::Prune {
+            backup_dir,
+            keep,
+            max_total_size,
+            allow_delete,
+        } => handle_prune(backup_dir, keep, max_total_size, allow_delete).await?,
+        Commands::Compact {
+            backup_dir,
+            dry_run,
+        } => handle_compact(backup_dir, dry_run).await?,
+        Commands::Scrub { source, dry_run } => handle_scrub(source, dry_run).await?,
+        Commands::Gc { dry_run } => handle_gc(dry_run).await?,
+        Commands::Bench { dir, size, json } => handle_bench(dir, size, json).await?,
+        Commands::Doctor { json, offline } => handle_doctor(json, offline).await?,
+        Commands::Tui { addr, interval } => handle_tui(addr, interval).await?,
+        Commands::Update {
+            check,
+            channel,
+            yes,
+        } => handle_update(check, channel, yes).await?,
+        Commands::Logs { action } => handle_logs(action, log_dir).await?,
     }
 
     Ok(())
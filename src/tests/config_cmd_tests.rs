@@ -0,0 +1,66 @@
+use super::*;
+use crate::config::SourceConfig;
+use serde_json::json;
+
+fn config_with_source() -> BackupConfig {
+    let mut config = BackupConfig {
+        max_backups: 10,
+        ..Default::default()
+    };
+    config.sources.push(SourceConfig {
+        source_dir: PathBuf::from("/home/user/documents"),
+        backup_dirs: vec![PathBuf::from("/backup/documents").into()],
+        enabled: true,
+        max_backups: Some(3),
+        ..Default::default()
+    });
+    config
+}
+
+#[test]
+fn get_resolves_source_overrides_before_global_values() -> Result<()> {
+    let config = config_with_source();
+    let source = Path::new("/home/user/documents");
+
+    assert_eq!(effective_value(&config, None, "max_backups")?, json!(10));
+    assert_eq!(
+        effective_value(&config, Some(source), "max_backups")?,
+        json!(3)
+    );
+    assert_eq!(
+        effective_value(&config, Some(source), "cron_schedule")?,
+        json!(config.cron_schedule)
+    );
+    assert_eq!(
+        effective_value(&config, None, "anomaly_detection.min_files")?,
+        json!(config.anomaly_detection.min_files)
+    );
+    Ok(())
+}
+
+#[test]
+fn get_rejects_unset_keys_and_unknown_sources() {
+    let config = config_with_source();
+    assert!(effective_value(&config, None, "metrics_port").is_err());
+    assert!(effective_value(&config, None, "no_such_key").is_err());
+    assert!(effective_value(&config, Some(Path::new("/elsewhere")), "max_backups").is_err());
+}
+
+#[test]
+fn show_source_marks_overridden_and_inherited_values() {
+    let config = config_with_source();
+    let settings = effective_source_settings(&config, &config.sources[0]);
+    let find = |key: &str| {
+        settings
+            .iter()
+            .find(|s| s.key == key)
+            .unwrap_or_else(|| panic!("missing {}", key))
+    };
+
+    assert_eq!(find("max_backups").value, "3");
+    assert_eq!(find("max_backups").origin, "source");
+    assert_eq!(find("full_backup_interval").value, "2");
+    assert_eq!(find("full_backup_interval").origin, "derived");
+    assert_eq!(find("cron_schedule").value, config.cron_schedule);
+    assert_eq!(find("cron_schedule").origin, "global");
+}
@@ -6,7 +6,7 @@ use std::path::PathBuf;
 fn make_source(path: &str) -> SourceConfig {
     SourceConfig {
         source_dir: PathBuf::from(path),
-        backup_dirs: vec![PathBuf::from("/tmp/backup")],
+        backup_dirs: vec![PathBuf::from("/tmp/backup").into()],
         enabled: true,
         exclude_patterns: None,
         max_backups: None,
@@ -14,6 +14,7 @@ fn make_source(path: &str) -> SourceConfig {
         cron_schedule: None,
         enable_event_driven: None,
         enable_periodic: None,
+        ..Default::default()
     }
 }
 
@@ -24,7 +25,7 @@ fn make_source_with_flags(
 ) -> SourceConfig {
     SourceConfig {
         source_dir: PathBuf::from(path),
-        backup_dirs: vec![PathBuf::from("/tmp/backup")],
+        backup_dirs: vec![PathBuf::from("/tmp/backup").into()],
         enabled,
         exclude_patterns: None,
         max_backups: None,
@@ -32,6 +33,7 @@ fn make_source_with_flags(
         cron_schedule: None,
         enable_event_driven,
         enable_periodic: None,
+        ..Default::default()
     }
 }
 
@@ -47,6 +49,7 @@ fn base_config(backup_mode: BackupMode, enable_event_driven: bool) -> BackupConf
         enable_min_interval_by_size: false,
         max_log_file_size_mb: 20,
         metadata: HashMap::new(),
+        ..Default::default()
     }
 }
 
@@ -54,7 +57,13 @@ fn base_config(backup_mode: BackupMode, enable_event_driven: bool) -> BackupConf
 fn collect_event_watch_paths_includes_copy_mode_source() {
     let config = base_config(BackupMode::Copy, true);
     let paths = collect_event_watch_paths(&config);
-    assert_eq!(paths, vec![PathBuf::from("/tmp/source")]);
+    assert_eq!(
+        paths,
+        vec![(
+            PathBuf::from("/tmp/source"),
+            vec![PathBuf::from("/tmp/source")]
+        )]
+    );
 }
 
 #[test]
@@ -81,7 +90,13 @@ fn collect_event_watch_paths_skips_disabled_sources() {
     ];
 
     let paths = collect_event_watch_paths(&config);
-    assert_eq!(paths, vec![PathBuf::from("/tmp/source_enabled")]);
+    assert_eq!(
+        paths,
+        vec![(
+            PathBuf::from("/tmp/source_enabled"),
+            vec![PathBuf::from("/tmp/source_enabled")]
+        )]
+    );
 }
 
 #[test]
@@ -94,7 +109,13 @@ fn collect_event_watch_paths_filters_mixed_source_overrides() {
     ];
 
     let paths = collect_event_watch_paths(&config);
-    assert_eq!(paths, vec![PathBuf::from("/tmp/source_a")]);
+    assert_eq!(
+        paths,
+        vec![(
+            PathBuf::from("/tmp/source_a"),
+            vec![PathBuf::from("/tmp/source_a")]
+        )]
+    );
 }
 
 #[test]
@@ -110,6 +131,38 @@ fn config_snapshot_pretty_json_contains_phase_and_config() {
     assert!(parsed.get("config").is_some());
 }
 
+#[test]
+fn config_snapshot_leaves_out_secrets() {
+    let mut config = base_config(BackupMode::Copy, true);
+    config.api_token = Some("api-secret".to_string());
+    config.sources[0].healthcheck_url = Some("https://hc-ping.com/ping-secret".to_string());
+    config.notifications.webhook_url = Some("https://example.com/hooks/hook-secret".to_string());
+    config.notifications.slack_webhook_url =
+        Some("https://hooks.slack.com/services/T0/B0/slack-secret".to_string());
+    config.notifications.smtp = Some(crate::config::SmtpConfig {
+        host: "smtp.example.com".to_string(),
+        from: "ardiex@example.com".to_string(),
+        to: vec!["ops@example.com".to_string()],
+        username: Some("ardiex".to_string()),
+        password: Some("smtp-secret".to_string()),
+        ..Default::default()
+    });
+
+    let json = super::config_snapshot_pretty_json(&config, "startup");
+
+    assert!(!json.contains("smtp-secret"), "{}", json);
+    assert!(!json.contains("api-secret"), "{}", json);
+    assert!(!json.contains("hook-secret"), "{}", json);
+    assert!(!json.contains("slack-secret"), "{}", json);
+    assert!(!json.contains("ping-secret"), "{}", json);
+    assert!(json.contains("https://hooks.slack.com/***"));
+    assert!(json.contains("smtp.example.com"));
+    assert_eq!(
+        config.notifications.smtp.and_then(|smtp| smtp.password),
+        Some("smtp-secret".to_string())
+    );
+}
+
 #[test]
 fn config_fingerprint_is_stable_and_changes_on_mutation() {
     let config = base_config(BackupMode::Copy, true);
@@ -128,7 +181,7 @@ fn spawn_runtime_handles_returns_empty_when_all_triggers_disabled() {
     let mut config = base_config(BackupMode::Copy, false);
     config.enable_periodic = false;
     config.sources = vec![make_source_with_flags("/tmp/source", true, Some(false))];
-    let (tx, _rx) = tokio::sync::mpsc::channel::<()>(1);
+    let (tx, _rx) = tokio::sync::mpsc::channel::<super::BackupTrigger>(1);
 
     let mut handles = super::spawn_runtime_handles(&config, tx)
         .expect("spawning runtime handles without triggers must succeed");
@@ -143,7 +196,7 @@ fn spawn_runtime_handles_rejects_invalid_source_cron() {
     config.enable_periodic = true;
     config.sources = vec![make_source_with_flags("/tmp/source", true, None)];
     config.sources[0].cron_schedule = Some("invalid cron expression".to_string());
-    let (tx, _rx) = tokio::sync::mpsc::channel::<()>(1);
+    let (tx, _rx) = tokio::sync::mpsc::channel::<super::BackupTrigger>(1);
 
     match super::spawn_runtime_handles(&config, tx) {
         Ok(mut handles) => {
@@ -183,7 +236,7 @@ fn spawn_runtime_handles_rejects_invalid_global_cron_when_source_has_no_override
     config.enable_periodic = true;
     config.cron_schedule = "invalid global cron".to_string();
     config.sources = vec![make_source_with_flags("/tmp/source", true, None)];
-    let (tx, _rx) = tokio::sync::mpsc::channel::<()>(1);
+    let (tx, _rx) = tokio::sync::mpsc::channel::<super::BackupTrigger>(1);
 
     match super::spawn_runtime_handles(&config, tx) {
         Ok(mut handles) => {
@@ -193,3 +246,68 @@ fn spawn_runtime_handles_rejects_invalid_global_cron_when_source_has_no_override
         Err(err) => assert!(err.to_string().contains("Invalid cron")),
     }
 }
+
+#[test]
+fn collect_event_watch_paths_watches_only_configured_subpaths() {
+    let mut config = base_config(BackupMode::Copy, true);
+    config.sources[0].paths = vec![PathBuf::from("docs"), PathBuf::from("configs")];
+    let paths = collect_event_watch_paths(&config);
+    assert_eq!(
+        paths,
+        vec![(
+            PathBuf::from("/tmp/source"),
+            vec![
+                PathBuf::from("/tmp/source/docs"),
+                PathBuf::from("/tmp/source/configs")
+            ]
+        )]
+    );
+}
+
+#[test]
+fn collect_forward_targets_uses_first_backup_dir_of_enabled_sources() {
+    let mut config = base_config(BackupMode::Copy, false);
+    config.sources = vec![
+        make_source_with_flags("/tmp/source_a", true, None),
+        make_source_with_flags("/tmp/source_b", false, None),
+        make_source_with_flags("/tmp/source_c", true, None),
+    ];
+    config.sources[0].backup_dirs = vec![
+        crate::config::BackupDestination {
+            path: PathBuf::from("/tmp/local"),
+            backup_mode: Some(BackupMode::Delta),
+            ..Default::default()
+        },
+        PathBuf::from("/tmp/usb").into(),
+    ];
+    config.sources[0].forward_dir = Some(PathBuf::from("/mnt/nas/a"));
+    config.sources[1].forward_dir = Some(PathBuf::from("/mnt/nas/b"));
+
+    let targets = super::collect_forward_targets(&config);
+    assert_eq!(targets.len(), 1);
+    assert_eq!(targets[0].0, PathBuf::from("/tmp/local"));
+    assert_eq!(targets[0].1, PathBuf::from("/mnt/nas/a"));
+    // The local dir's own overrides apply, not just the source's.
+    assert!(matches!(targets[0].2.backup_mode, BackupMode::Delta));
+}
+
+#[test]
+fn startup_backup_sources_lists_enabled_sources_that_opt_in() {
+    let mut config = base_config(BackupMode::Delta, true);
+    config.sources = vec![
+        SourceConfig {
+            backup_on_start: true,
+            ..make_source("/tmp/laptop")
+        },
+        SourceConfig {
+            backup_on_start: true,
+            ..make_source_with_flags("/tmp/disabled", false, None)
+        },
+        make_source("/tmp/scheduled"),
+    ];
+
+    assert_eq!(
+        super::startup_backup_sources(&config),
+        vec![PathBuf::from("/tmp/laptop")]
+    );
+}
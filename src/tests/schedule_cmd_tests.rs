@@ -0,0 +1,24 @@
+use super::*;
+use chrono::TimeZone;
+
+#[test]
+fn upcoming_fires_lists_the_next_occurrences() -> Result<()> {
+    let after = Utc.with_ymd_and_hms(2026, 3, 2, 10, 15, 0).unwrap();
+
+    let fires = upcoming_fires("0 */30 * * * *", 3, after)?;
+    assert_eq!(
+        fires,
+        vec![
+            Utc.with_ymd_and_hms(2026, 3, 2, 10, 30, 0).unwrap(),
+            Utc.with_ymd_and_hms(2026, 3, 2, 11, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2026, 3, 2, 11, 30, 0).unwrap(),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn five_field_crontab_syntax_is_rejected() {
+    let err = upcoming_fires("*/5 * * * *", 3, Utc::now()).expect_err("needs seconds");
+    assert!(err.to_string().contains("Invalid cron expression"));
+}
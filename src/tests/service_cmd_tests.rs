@@ -0,0 +1,57 @@
+use super::*;
+
+#[test]
+fn each_profile_gets_its_own_unit() {
+    assert_eq!(unit_file_name(None), "ardiex.service");
+    assert_eq!(unit_file_name(Some("work")), "ardiex-work.service");
+}
+
+#[test]
+fn system_unit_runs_the_binary_against_the_config_with_hardening() {
+    let unit = render_unit(
+        Path::new("/opt/ardiex/ardiex"),
+        Path::new("/etc/ardiex/settings.toml"),
+        None,
+        false,
+    );
+
+    assert!(unit.contains(
+        "ExecStart=\"/opt/ardiex/ardiex\" \"--config\" \"/etc/ardiex/settings.toml\" \"run\"\n"
+    ));
+    assert!(unit.contains("ProtectSystem=full\n"));
+    assert!(unit.contains("ReadWritePaths=-\"/etc/ardiex\"\n"));
+    // Next to the executable is read-only under ProtectSystem=full.
+    assert!(unit.contains("LogsDirectory=ardiex\n"));
+    assert!(unit.contains("Environment=ARDIEX_LOG_DIR=/var/log/ardiex\n"));
+    assert!(unit.contains("WantedBy=multi-user.target\n"));
+}
+
+#[test]
+fn profile_units_run_as_their_profile() {
+    let unit = render_unit(
+        Path::new("/opt/ardiex/ardiex"),
+        Path::new("/root/.config/ardiex/profiles/work/settings.json"),
+        Some("work"),
+        false,
+    );
+
+    assert!(unit.contains(
+        "ExecStart=\"/opt/ardiex/ardiex\" \"--config\" \"/root/.config/ardiex/profiles/work/settings.json\" \"--profile\" \"work\" \"run\"\n"
+    ));
+}
+
+#[test]
+fn user_unit_skips_privileged_options_and_escapes_paths() {
+    let unit = render_unit(
+        Path::new("/home/me/bin/ardiex"),
+        Path::new("/home/me/.config/ardiex/100%$HOME\"s.json"),
+        None,
+        true,
+    );
+
+    assert!(unit.contains("\"/home/me/.config/ardiex/100%%$$HOME\\\"s.json\""));
+    assert!(!unit.contains("ProtectSystem"));
+    assert!(!unit.contains("ReadWritePaths"));
+    assert!(!unit.contains("LogsDirectory"));
+    assert!(unit.contains("WantedBy=default.target\n"));
+}
@@ -0,0 +1,97 @@
+use super::*;
+use ratatui::Terminal;
+use ratatui::backend::TestBackend;
+
+fn screen_text(view: &View) -> Result<String> {
+    let mut terminal = Terminal::new(TestBackend::new(120, 30))?;
+    terminal.draw(|frame| draw(frame, view))?;
+    let buffer = terminal.backend().buffer();
+    Ok(buffer
+        .content()
+        .chunks(usize::from(buffer.area.width))
+        .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+#[test]
+fn wildcard_binds_are_reached_over_loopback() -> Result<()> {
+    assert_eq!(api_base_url("0.0.0.0:8686")?, "http://127.0.0.1:8686");
+    assert_eq!(api_base_url("[::]:8686")?, "http://[::1]:8686");
+    assert_eq!(api_base_url("192.168.0.5:80")?, "http://192.168.0.5:80");
+    assert!(api_base_url("localhost").is_err());
+    Ok(())
+}
+
+#[test]
+fn progress_bar_fills_by_ratio() {
+    assert_eq!(
+        progress_bar(1, 2),
+        format!("[{}{}]  50%", "#".repeat(10), " ".repeat(10))
+    );
+    assert_eq!(progress_bar(0, 0), format!("[{}]   0%", " ".repeat(20)));
+    assert_eq!(progress_bar(9, 3), format!("[{}] 100%", "#".repeat(20)));
+}
+
+#[test]
+fn screen_shows_queue_progress_results_and_log() -> Result<()> {
+    let finished_at = Utc::now();
+    let view = View {
+        base_url: "http://127.0.0.1:8686".to_string(),
+        snapshot: Some(Snapshot {
+            status: ServiceStatus {
+                started_at: finished_at,
+                running: vec![PathBuf::from("/srv/docs")],
+                queued: vec![QueuedTrigger {
+                    source_dir: PathBuf::from("/srv/photos"),
+                    reason: "file change".to_string(),
+                }],
+                progress: vec![TransferProgress {
+                    label: "Backup progress".to_string(),
+                    target: PathBuf::from("/backup/docs"),
+                    file: Some(PathBuf::from("report.pdf")),
+                    files_done: 3,
+                    total_files: 4,
+                    bytes_done: 512,
+                    total_bytes: 1024,
+                }],
+                restores_running: 0,
+            },
+            runs: vec![RunRecord {
+                finished_at,
+                source_dir: PathBuf::from("/srv/mail"),
+                status: RunStatus::Failure,
+                backup_dir: None,
+                backup_type: None,
+                files_backed_up: 0,
+                bytes_processed: 0,
+                duration_ms: 0,
+                error: Some("disk full".to_string()),
+            }],
+            logs: Ok(vec!["[.. WARN ardiex] low space".to_string()]),
+        }),
+        fetched_at: Some(Local::now()),
+        error: None,
+    };
+
+    let screen = screen_text(&view)?;
+    assert!(screen.contains("source /srv/docs"));
+    assert!(screen.contains("Backup progress /backup/docs"));
+    assert!(screen.contains("3/4 files"));
+    assert!(screen.contains("report.pdf"));
+    assert!(screen.contains("file change /srv/photos"));
+    assert!(screen.contains("FAIL /srv/mail disk full"));
+    assert!(screen.contains("low space"));
+    Ok(())
+}
+
+#[test]
+fn failed_refresh_is_shown_in_the_header() -> Result<()> {
+    let view = View {
+        base_url: "http://127.0.0.1:8686".to_string(),
+        error: Some("connection refused".to_string()),
+        ..Default::default()
+    };
+    assert!(screen_text(&view)?.contains("refresh failed: connection refused"));
+    Ok(())
+}
@@ -16,6 +16,37 @@ fn compare_versions_orders_semver_triplets() {
     assert_eq!(compare_versions("1.10.0", "1.2.0"), Ordering::Greater);
 }
 
+#[test]
+fn compare_versions_orders_prerelease_before_its_release() {
+    assert_eq!(compare_versions("1.3.0-beta.1", "1.3.0"), Ordering::Less);
+    assert_eq!(compare_versions("1.3.0", "1.3.0-beta.1"), Ordering::Greater);
+    assert!(is_newer_version("1.2.0", "1.3.0-beta.1"));
+    assert!(!is_newer_version("1.3.0", "1.3.0-beta.1"));
+    assert!(is_newer_version("1.3.0-beta.2", "v1.3.0-beta.10"));
+    assert!(is_newer_version("1.3.0-alpha", "1.3.0-beta"));
+}
+
+#[test]
+fn newest_release_skips_drafts_and_takes_highest_version() {
+    let release = |tag: &str, prerelease: bool, draft: bool| GitHubRelease {
+        tag_name: tag.to_string(),
+        prerelease,
+        draft,
+        ..Default::default()
+    };
+    let releases = vec![
+        release("v1.2.0", false, false),
+        release("v1.4.0", false, true),
+        release("v1.3.0-beta.1", true, false),
+        release("v1.3.0-beta.2", true, false),
+        release("v1.3.0-beta.1", true, false),
+    ];
+
+    let newest = newest_release(releases).expect("a published release");
+    assert_eq!(newest.tag_name, "v1.3.0-beta.2");
+    assert!(newest_release(vec![release("v2.0.0", false, true)]).is_none());
+}
+
 #[test]
 fn is_newer_version_detects_candidate_newer() {
     assert!(is_newer_version("0.1.0", "0.2.0"));
@@ -31,9 +62,47 @@ fn find_release_asset_download_url_returns_error_for_missing_asset() {
             name: "ardiex-linux-amd64.tar.gz".to_string(),
             browser_download_url: "https://example.invalid/asset".to_string(),
         }],
+        ..Default::default()
     };
 
     let err = find_release_asset_download_url(&release, "ardiex-windows-amd64.zip")
         .expect_err("missing asset must return error");
     assert!(err.to_string().contains("Release asset"));
 }
+
+#[test]
+fn verification_assets_refuse_unsigned_releases_by_default() {
+    let asset = |name: &str| GitHubReleaseAsset {
+        name: name.to_string(),
+        browser_download_url: format!("https://example.invalid/{name}"),
+    };
+    let signed = GitHubRelease {
+        tag_name: "v1.0.0".to_string(),
+        assets: vec![
+            asset("ardiex-linux-amd64.tar.gz"),
+            asset("SHA256SUMS"),
+            asset("SHA256SUMS.minisig"),
+        ],
+        ..Default::default()
+    };
+    let unsigned = GitHubRelease {
+        tag_name: "v1.0.0".to_string(),
+        assets: vec![asset("ardiex-linux-amd64.tar.gz"), asset("SHA256SUMS")],
+        ..Default::default()
+    };
+
+    let assets = verification_assets(&signed, Some("key"), false).expect("signed release");
+    assert_eq!(
+        assets.signature_url.as_deref(),
+        Some("https://example.invalid/SHA256SUMS.minisig")
+    );
+    assert!(verification_assets(&signed, None, false).is_err());
+    assert!(verification_assets(&unsigned, Some("key"), false).is_err());
+
+    let assets = verification_assets(&unsigned, None, true).expect("unsigned allowed");
+    assert_eq!(
+        assets.checksums_url.as_deref(),
+        Some("https://example.invalid/SHA256SUMS")
+    );
+    assert_eq!(assets.signature_url, None);
+}
@@ -1,39 +1,82 @@
 use anyhow::{Context, Result};
 use serde::Deserialize;
 use std::cmp::Ordering;
+use std::process::{Command, Stdio};
 use std::time::Duration;
 
+use crate::config::UpdateChannel;
+use crate::release_verify;
+
+pub const UPDATE_REPO: &str = "ardi-orrorin/ardiex";
+pub const UPDATE_SKIP_ENV_KEY: &str = "ARDIEX_SKIP_UPDATE_CHECK";
+pub const UPDATE_SKIP_ENV_VALUE: &str = "1";
+
+/// Releases listed when looking for the newest pre-release.
+const BETA_RELEASES_PER_PAGE: u32 = 30;
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct GitHubReleaseAsset {
     pub name: String,
     pub browser_download_url: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct GitHubRelease {
     pub tag_name: String,
     #[serde(default)]
     pub assets: Vec<GitHubReleaseAsset>,
+    /// Release notes (Markdown).
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub html_url: String,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub draft: bool,
 }
 
-pub fn fetch_latest_release(repo: &str) -> Result<GitHubRelease> {
-    let url = format!("https://api.github.com/repos/{repo}/releases/latest");
+fn github_get<T: serde::de::DeserializeOwned>(url: &str) -> Result<T> {
     let client = reqwest::blocking::Client::builder()
         .timeout(Duration::from_secs(15))
         .build()
         .context("Failed to build HTTP client")?;
 
-    let release = client
+    client
         .get(url)
         .header(reqwest::header::USER_AGENT, "ardiex-updater")
         .send()
-        .context("Failed to request latest GitHub release")?
+        .context("Failed to request GitHub releases")?
         .error_for_status()
         .context("GitHub release endpoint returned error status")?
-        .json::<GitHubRelease>()
-        .context("Failed to parse latest GitHub release response")?;
+        .json::<T>()
+        .context("Failed to parse GitHub release response")
+}
 
-    Ok(release)
+/// Newest release of `repo` on `channel`: GitHub's latest release for
+/// stable, the highest version among published releases including
+/// pre-releases for beta.
+pub fn fetch_release(repo: &str, channel: UpdateChannel) -> Result<GitHubRelease> {
+    match channel {
+        UpdateChannel::Stable => github_get(&format!(
+            "https://api.github.com/repos/{repo}/releases/latest"
+        )),
+        UpdateChannel::Beta => {
+            let releases: Vec<GitHubRelease> = github_get(&format!(
+                "https://api.github.com/repos/{repo}/releases?per_page={BETA_RELEASES_PER_PAGE}"
+            ))?;
+            newest_release(releases)
+                .ok_or_else(|| anyhow::anyhow!("No published releases in {}", repo))
+        }
+    }
+}
+
+/// Highest-versioned release that is not a draft.
+pub fn newest_release(releases: Vec<GitHubRelease>) -> Option<GitHubRelease> {
+    releases
+        .into_iter()
+        .filter(|release| !release.draft)
+        .max_by(|a, b| compare_versions(&a.tag_name, &b.tag_name))
 }
 
 pub fn normalize_version(input: &str) -> String {
@@ -64,12 +107,50 @@ fn parse_semver_triplet(input: &str) -> Option<(u64, u64, u64)> {
     Some((major, minor, patch))
 }
 
+fn prerelease_part(input: &str) -> Option<&str> {
+    let version = input.trim().split('+').next().unwrap_or_default();
+    version.split_once('-').map(|(_, pre)| pre)
+}
+
+/// Semver pre-release precedence: a release outranks its pre-releases, and
+/// pre-releases compare identifier by identifier, numerically where both
+/// are numbers (`beta.2` < `beta.10`).
+fn compare_prerelease(current: Option<&str>, candidate: Option<&str>) -> Ordering {
+    match (current, candidate) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(c), Some(n)) => {
+            let mut c_ids = c.split('.');
+            let mut n_ids = n.split('.');
+            loop {
+                let ordering = match (c_ids.next(), n_ids.next()) {
+                    (None, None) => return Ordering::Equal,
+                    (None, Some(_)) => return Ordering::Less,
+                    (Some(_), None) => return Ordering::Greater,
+                    (Some(a), Some(b)) => match (a.parse::<u64>(), b.parse::<u64>()) {
+                        (Ok(a), Ok(b)) => a.cmp(&b),
+                        (Ok(_), Err(_)) => Ordering::Less,
+                        (Err(_), Ok(_)) => Ordering::Greater,
+                        (Err(_), Err(_)) => a.cmp(b),
+                    },
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
 pub fn compare_versions(current: &str, candidate: &str) -> Ordering {
     match (
         parse_semver_triplet(current),
         parse_semver_triplet(candidate),
     ) {
-        (Some(c), Some(n)) => c.cmp(&n),
+        (Some(c), Some(n)) => c
+            .cmp(&n)
+            .then_with(|| compare_prerelease(prerelease_part(current), prerelease_part(candidate))),
         _ => normalize_version(current).cmp(&normalize_version(candidate)),
     }
 }
@@ -112,6 +193,131 @@ pub fn find_release_asset_download_url(
         })
 }
 
+/// Download URLs of the release's checksum list and its signature.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct VerificationAssets {
+    pub checksums_url: Option<String>,
+    pub signature_url: Option<String>,
+}
+
+/// Find what the updater needs to verify `release`. Unless
+/// `allow_unsigned`, a release without a signed checksum list, or a build
+/// without `public_key` to check it with, is refused.
+pub fn verification_assets(
+    release: &GitHubRelease,
+    public_key: Option<&str>,
+    allow_unsigned: bool,
+) -> Result<VerificationAssets> {
+    let url = |name: &str| find_release_asset_download_url(release, name).ok();
+    let assets = VerificationAssets {
+        checksums_url: url(release_verify::CHECKSUMS_ASSET),
+        signature_url: url(release_verify::SIGNATURE_ASSET),
+    };
+    if allow_unsigned {
+        return Ok(assets);
+    }
+    if assets.checksums_url.is_none() || assets.signature_url.is_none() {
+        return Err(anyhow::anyhow!(
+            "Release '{}' has no signed checksums ({} + {}); refusing unsigned update \
+             (set allow_unsigned_updates to install it anyway)",
+            release.tag_name,
+            release_verify::CHECKSUMS_ASSET,
+            release_verify::SIGNATURE_ASSET
+        ));
+    }
+    if public_key.is_none() {
+        return Err(anyhow::anyhow!(
+            "This build has no update public key to verify release '{}' with; \
+             refusing unsigned update (set allow_unsigned_updates to install it anyway)",
+            release.tag_name
+        ));
+    }
+    Ok(assets)
+}
+
+fn updater_binary_name() -> &'static str {
+    if cfg!(windows) {
+        "updater.exe"
+    } else {
+        "updater"
+    }
+}
+
+/// Start the `updater` next to the running executable to install
+/// `release`; the caller should exit right after so the binary can be
+/// replaced. `forward_args` are this process's args, which the updater
+/// reads its log settings from and, with `restart`, runs the new binary
+/// with. The updater verifies the archive before installing it; see
+/// `verification_assets`. Returns the updater's PID.
+pub fn spawn_updater(
+    release: &GitHubRelease,
+    forward_args: &[String],
+    restart: bool,
+    allow_unsigned: bool,
+) -> Result<u32> {
+    let asset_name = expected_release_asset_name_for_current_target()?;
+    let asset_url = find_release_asset_download_url(release, asset_name)?;
+    let verification = verification_assets(
+        release,
+        release_verify::embedded_public_key(),
+        allow_unsigned,
+    )?;
+    let current_exe = std::env::current_exe().context("Failed to resolve current executable")?;
+    let updater_path = current_exe
+        .parent()
+        .map(|dir| dir.join(updater_binary_name()))
+        .ok_or_else(|| anyhow::anyhow!("Current executable has no parent directory"))?;
+    if !updater_path.exists() {
+        return Err(anyhow::anyhow!(
+            "Updater binary is missing: {:?}",
+            updater_path
+        ));
+    }
+
+    let mut cmd = Command::new(&updater_path);
+    cmd.arg("--repo")
+        .arg(UPDATE_REPO)
+        .arg("--asset-url")
+        .arg(asset_url)
+        .arg("--asset-name")
+        .arg(asset_name)
+        .arg("--target-version")
+        .arg(normalize_version(&release.tag_name))
+        .arg("--current-exe")
+        .arg(&current_exe)
+        .arg("--parent-pid")
+        .arg(std::process::id().to_string());
+    if let Some(url) = verification.checksums_url {
+        cmd.arg("--checksums-url").arg(url);
+    }
+    if let Some(url) = verification.signature_url {
+        cmd.arg("--signature-url").arg(url);
+    }
+    if allow_unsigned {
+        cmd.arg("--allow-unsigned");
+    }
+    for arg in forward_args {
+        cmd.arg("--forward-arg").arg(arg);
+    }
+    if !restart {
+        cmd.arg("--no-restart");
+    }
+
+    let child = cmd
+        .env(UPDATE_SKIP_ENV_KEY, UPDATE_SKIP_ENV_VALUE)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| {
+            format!(
+                "Failed to spawn updater process: {:?} (target version: {})",
+                updater_path, release.tag_name
+            )
+        })?;
+    Ok(child.id())
+}
+
 #[cfg(test)]
 #[path = "tests/update_tests.rs"]
 mod tests;